@@ -19,6 +19,7 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::multiaddr::{Multiaddr, Protocol};
+use crate::transport::TransportKind;
 
 /// The endpoint roles associated with a peer-to-peer communication channel.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -140,6 +141,14 @@ impl ConnectedPoint {
         .any(|p| p == Protocol::P2pCircuit)
     }
 
+    /// Returns the kind of transport the connection is established with.
+    pub fn transport(&self) -> TransportKind {
+        match self {
+            ConnectedPoint::Dialer { address, .. } => TransportKind::from_address(address),
+            ConnectedPoint::Listener { local_addr, .. } => TransportKind::from_address(local_addr),
+        }
+    }
+
     /// Returns the address of the remote stored in this struct.
     ///
     /// For `Dialer`, this returns `address`. For `Listener`, this returns `send_back_addr`.
@@ -21,7 +21,7 @@
 use crate::muxing::StreamMuxerEvent;
 use crate::{
     muxing::StreamMuxer,
-    transport::{ListenerId, Transport, TransportError, TransportEvent},
+    transport::{DialOpts, ListenerId, Transport, TransportError, TransportEvent},
     Multiaddr, ProtocolName,
 };
 use either::Either;
@@ -221,6 +221,29 @@ where
         }
     }
 
+    fn dial_with_opts(
+        &mut self,
+        addr: Multiaddr,
+        opts: DialOpts,
+    ) -> Result<Self::Dial, TransportError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        use TransportError::*;
+        match self {
+            Either::Left(a) => match a.dial_with_opts(addr, opts) {
+                Ok(connec) => Ok(EitherFuture::First(connec)),
+                Err(MultiaddrNotSupported(addr)) => Err(MultiaddrNotSupported(addr)),
+                Err(Other(err)) => Err(Other(Either::Left(err))),
+            },
+            Either::Right(b) => match b.dial_with_opts(addr, opts) {
+                Ok(connec) => Ok(EitherFuture::Second(connec)),
+                Err(MultiaddrNotSupported(addr)) => Err(MultiaddrNotSupported(addr)),
+                Err(Other(err)) => Err(Other(Either::Right(err))),
+            },
+        }
+    }
+
     fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
         match self {
             Either::Left(a) => a.address_translation(server, observed),
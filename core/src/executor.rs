@@ -1,4 +1,9 @@
 //! Provides executors for spawning background tasks.
+//!
+//! The [`Executor`] trait abstracts over the runtime the background tasks of libp2p, e.g. the
+//! tasks of the connections of a `Swarm`, are spawned on. Built-in executors are provided for
+//! tokio, async-std and `wasm-bindgen`, behind the features of the same name. Bespoke runtimes
+//! implement [`Executor`] themselves, or pass a closure.
 use futures::executor::ThreadPool;
 use std::{future::Future, pin::Pin};
 
@@ -26,6 +31,7 @@ impl Executor for ThreadPool {
     }
 }
 
+/// Spawns tasks on the current tokio runtime.
 #[cfg(all(
     feature = "tokio",
     not(any(target_os = "emscripten", target_os = "wasi", target_os = "unknown"))
@@ -43,6 +49,7 @@ impl Executor for TokioExecutor {
     }
 }
 
+/// Spawns tasks on the global async-std runtime.
 #[cfg(all(
     feature = "async-std",
     not(any(target_os = "emscripten", target_os = "wasi", target_os = "unknown"))
@@ -60,6 +67,7 @@ impl Executor for AsyncStdExecutor {
     }
 }
 
+/// Spawns tasks on the JavaScript event loop via `wasm-bindgen-futures`.
 #[cfg(feature = "wasm-bindgen")]
 #[derive(Default, Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct WasmBindgenExecutor;
@@ -70,3 +78,22 @@ impl Executor for WasmBindgenExecutor {
         wasm_bindgen_futures::spawn_local(future)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::oneshot;
+
+    #[test]
+    fn closure_runs_future() {
+        let pool = ThreadPool::new().unwrap();
+        let executor = move |future| pool.spawn_ok(future);
+
+        let (tx, rx) = oneshot::channel();
+        executor.exec(Box::pin(async move {
+            tx.send(()).unwrap();
+        }));
+
+        futures::executor::block_on(rx).unwrap();
+    }
+}
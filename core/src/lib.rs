@@ -103,6 +103,8 @@ mod translation;
 
 pub mod connection;
 pub mod either;
+pub mod executor;
+pub mod multiaddr_pattern;
 pub mod muxing;
 pub mod peer_record;
 pub mod signed_envelope;
@@ -119,7 +121,9 @@ pub type PeerId = libp2p_identity::PeerId;
 pub type ParseError = libp2p_identity::ParseError;
 
 pub use connection::{ConnectedPoint, Endpoint};
+pub use executor::Executor;
 pub use multiaddr::Multiaddr;
+pub use multiaddr_pattern::MultiaddrPattern;
 pub use multihash;
 pub use muxing::StreamMuxer;
 pub use peer_record::PeerRecord;
@@ -0,0 +1,332 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Matching [`Multiaddr`]s against patterns.
+//!
+//! A [`MultiaddrPattern`] is written like a [`Multiaddr`], with the following additions:
+//!
+//! - `*` in place of a value matches any value of the preceding protocol, e.g. `/ip4/*/tcp/*`.
+//! - `*` in place of a protocol matches any single protocol, including its value.
+//! - `**` matches any sequence of protocols, including the empty one, e.g. `/ip4/*/**`.
+//!
+//! All other segments have to match exactly.
+//!
+//! ```
+//! # use libp2p_core::{Multiaddr, MultiaddrPattern};
+//! let pattern = MultiaddrPattern::parse("/ip4/*/tcp/*/**").unwrap();
+//!
+//! assert!(pattern.matches(&"/ip4/127.0.0.1/tcp/4001".parse::<Multiaddr>().unwrap()));
+//! assert!(pattern.matches(&"/ip4/127.0.0.1/tcp/4001/ws".parse::<Multiaddr>().unwrap()));
+//! assert!(!pattern.matches(&"/ip4/127.0.0.1/udp/4001/quic".parse::<Multiaddr>().unwrap()));
+//! ```
+
+use multiaddr::{Multiaddr, Protocol};
+use std::fmt;
+use std::str::FromStr;
+
+/// A pattern [`Multiaddr`]s can be matched against.
+///
+/// See the [module documentation](self) for the syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiaddrPattern {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// A protocol with a value matching the given one.
+    Protocol { tag: String, value: Value },
+    /// Any single protocol.
+    AnyProtocol,
+    /// Any sequence of protocols.
+    AnySuffix,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    /// The protocol does not carry a value.
+    None,
+    /// Any value.
+    Any,
+    /// The given protocol, value included.
+    Exact(Protocol<'static>),
+}
+
+impl MultiaddrPattern {
+    /// Parses a pattern, e.g. `/ip4/*/tcp/*`.
+    pub fn parse(pattern: &str) -> Result<Self, ParseError> {
+        let mut parts = pattern.split('/').peekable();
+
+        if Some("") != parts.next() || parts.peek().is_none() {
+            return Err(ParseError::MissingLeadingSlash);
+        }
+
+        let mut segments = Vec::new();
+        while let Some(name) = parts.next() {
+            let segment = match name {
+                "*" => Segment::AnyProtocol,
+                "**" => Segment::AnySuffix,
+                name => match Protocol::from_str_parts(std::iter::once(name)) {
+                    Ok(protocol) => Segment::Protocol {
+                        tag: protocol.tag().to_owned(),
+                        value: Value::None,
+                    },
+                    Err(multiaddr::Error::UnknownProtocolString(_)) => {
+                        return Err(ParseError::UnknownProtocol(name.to_owned()))
+                    }
+                    Err(_) => match parts.next() {
+                        None => return Err(ParseError::MissingValue(name.to_owned())),
+                        Some("*") => Segment::Protocol {
+                            tag: name.to_owned(),
+                            value: Value::Any,
+                        },
+                        Some(value) => {
+                            let protocol = Protocol::from_str_parts([name, value].into_iter())
+                                .map_err(|source| ParseError::InvalidValue {
+                                    protocol: name.to_owned(),
+                                    source,
+                                })?
+                                .acquire();
+
+                            Segment::Protocol {
+                                tag: protocol.tag().to_owned(),
+                                value: Value::Exact(protocol),
+                            }
+                        }
+                    },
+                },
+            };
+            segments.push(segment);
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Returns whether the given [`Multiaddr`] matches this pattern.
+    pub fn matches(&self, addr: &Multiaddr) -> bool {
+        let protocols = addr.iter().collect::<Vec<_>>();
+
+        matches_from(&self.segments, &protocols)
+    }
+}
+
+/// Matches the protocols against the segments in a single pass, tracking all segment
+/// positions reachable so far, such that `**` segments do not cause backtracking.
+fn matches_from(segments: &[Segment], protocols: &[Protocol<'_>]) -> bool {
+    // `reachable[i]` is whether the first `i` segments match the protocols consumed so far.
+    let mut reachable = vec![false; segments.len() + 1];
+    reachable[0] = true;
+    skip_any_suffixes(segments, &mut reachable);
+
+    for protocol in protocols {
+        let mut next = vec![false; segments.len() + 1];
+        for (i, segment) in segments.iter().enumerate() {
+            if !reachable[i] {
+                continue;
+            }
+            match segment {
+                Segment::AnySuffix => next[i] = true,
+                Segment::AnyProtocol => next[i + 1] = true,
+                Segment::Protocol { tag, value } => {
+                    let matches = match value {
+                        Value::None | Value::Any => protocol.tag() == tag,
+                        Value::Exact(expected) => protocol == expected,
+                    };
+                    if matches {
+                        next[i + 1] = true;
+                    }
+                }
+            }
+        }
+        skip_any_suffixes(segments, &mut next);
+        reachable = next;
+    }
+
+    reachable[segments.len()]
+}
+
+/// Marks the positions after `**` segments as reachable, as they match the empty sequence.
+fn skip_any_suffixes(segments: &[Segment], reachable: &mut [bool]) {
+    for (i, segment) in segments.iter().enumerate() {
+        if reachable[i] && *segment == Segment::AnySuffix {
+            reachable[i + 1] = true;
+        }
+    }
+}
+
+impl FromStr for MultiaddrPattern {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for MultiaddrPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for segment in &self.segments {
+            match segment {
+                Segment::Protocol {
+                    tag,
+                    value: Value::None,
+                } => write!(f, "/{tag}")?,
+                Segment::Protocol {
+                    tag,
+                    value: Value::Any,
+                } => write!(f, "/{tag}/*")?,
+                Segment::Protocol {
+                    value: Value::Exact(protocol),
+                    ..
+                } => write!(f, "{protocol}")?,
+                Segment::AnyProtocol => f.write_str("/*")?,
+                Segment::AnySuffix => f.write_str("/**")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Error when parsing a [`MultiaddrPattern`].
+#[derive(Debug, thiserror::Error)]
+pub enum ParseError {
+    #[error("Pattern does not start with `/`")]
+    MissingLeadingSlash,
+    #[error("Unknown protocol `{0}`")]
+    UnknownProtocol(String),
+    #[error("Missing value for protocol `{0}`")]
+    MissingValue(String),
+    #[error("Invalid value for protocol `{protocol}`")]
+    InvalidValue {
+        protocol: String,
+        #[source]
+        source: multiaddr::Error,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, addr: &str) -> bool {
+        MultiaddrPattern::parse(pattern)
+            .unwrap()
+            .matches(&addr.parse().unwrap())
+    }
+
+    #[test]
+    fn wildcard_values() {
+        assert!(matches("/ip4/*/tcp/*", "/ip4/127.0.0.1/tcp/4001"));
+        assert!(matches("/ip4/*/tcp/*", "/ip4/1.2.3.4/tcp/1"));
+        assert!(!matches("/ip4/*/tcp/*", "/ip6/::1/tcp/4001"));
+        assert!(!matches("/ip4/*/tcp/*", "/ip4/127.0.0.1/udp/4001"));
+        assert!(!matches("/ip4/*/tcp/*", "/ip4/127.0.0.1/tcp/4001/ws"));
+        assert!(!matches("/ip4/*/tcp/*", "/ip4/127.0.0.1"));
+    }
+
+    #[test]
+    fn exact_values() {
+        assert!(matches("/ip4/*/tcp/4001", "/ip4/127.0.0.1/tcp/4001"));
+        assert!(!matches("/ip4/*/tcp/4001", "/ip4/127.0.0.1/tcp/4002"));
+        assert!(matches(
+            "/ip4/10.0.0.1/udp/*/quic-v1",
+            "/ip4/10.0.0.1/udp/1/quic-v1"
+        ));
+        assert!(!matches(
+            "/ip4/10.0.0.1/udp/*/quic-v1",
+            "/ip4/10.0.0.2/udp/1/quic-v1"
+        ));
+        assert!(!matches(
+            "/ip4/10.0.0.1/udp/*/quic-v1",
+            "/ip4/10.0.0.1/udp/1/quic"
+        ));
+    }
+
+    #[test]
+    fn wildcard_protocols() {
+        assert!(matches("/*/tcp/*", "/ip4/127.0.0.1/tcp/4001"));
+        assert!(matches("/*/tcp/*", "/dns/example.com/tcp/4001"));
+        assert!(!matches("/*/tcp/*", "/tcp/4001"));
+    }
+
+    #[test]
+    fn wildcard_suffix() {
+        assert!(matches("/ip4/*/**", "/ip4/127.0.0.1"));
+        assert!(matches("/ip4/*/**", "/ip4/127.0.0.1/tcp/4001/ws"));
+        assert!(matches(
+            "/**/p2p-circuit/**",
+            "/ip4/127.0.0.1/tcp/4001/p2p-circuit"
+        ));
+        assert!(matches(
+            "/**/p2p-circuit/**",
+            "/ip4/127.0.0.1/tcp/4001/p2p-circuit/p2p/12D3KooWRBy97UB99e3J6hiPesre1MZeuNQvfan4gBziswrRJsNK"
+        ));
+        assert!(!matches("/**/p2p-circuit/**", "/ip4/127.0.0.1/tcp/4001"));
+        assert!(matches("/**", "/memory/1"));
+    }
+
+    #[test]
+    fn many_wildcard_suffixes() {
+        let pattern = format!("{}/memory/0", "/**".repeat(32));
+        let addr = "/memory/1".repeat(64);
+
+        assert!(!matches(&pattern, &addr));
+        assert!(matches(&pattern, &format!("{addr}/memory/0")));
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        for pattern in [
+            "/ip4/*/tcp/*",
+            "/ip4/127.0.0.1/udp/*/quic-v1",
+            "/*/tcp/4001/ws/**",
+            "/dns/example.com/**",
+        ] {
+            assert_eq!(
+                MultiaddrPattern::parse(pattern).unwrap().to_string(),
+                pattern
+            );
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(
+            MultiaddrPattern::parse("ip4/*"),
+            Err(ParseError::MissingLeadingSlash)
+        ));
+        assert!(matches!(
+            MultiaddrPattern::parse(""),
+            Err(ParseError::MissingLeadingSlash)
+        ));
+        assert!(matches!(
+            MultiaddrPattern::parse("/foo/*"),
+            Err(ParseError::UnknownProtocol(p)) if p == "foo"
+        ));
+        assert!(matches!(
+            MultiaddrPattern::parse("/ip4/*/tcp"),
+            Err(ParseError::MissingValue(p)) if p == "tcp"
+        ));
+        assert!(matches!(
+            MultiaddrPattern::parse("/ip4/1.2.3"),
+            Err(ParseError::InvalidValue { protocol, .. }) if protocol == "ip4"
+        ));
+    }
+}
@@ -52,8 +52,10 @@
 
 use futures::{task::Context, task::Poll, AsyncRead, AsyncWrite};
 use multiaddr::Multiaddr;
+use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::Arc;
 
 pub use self::boxed::StreamMuxerBox;
 pub use self::boxed::SubstreamBox;
@@ -111,6 +113,54 @@ pub trait StreamMuxer {
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Result<StreamMuxerEvent, Self::Error>>;
+
+    /// Returns a [`PriorityHandle`] to set the priority of a substream of this [`StreamMuxer`].
+    ///
+    /// Implementations supporting priorities prefer writes of higher priority substreams over
+    /// those of lower priority substreams when the underlying connection is congested. The
+    /// priority can be changed for as long as the substream exists, e.g. once the protocol of
+    /// an inbound substream is negotiated. The default implementation returns `None`, i.e. it
+    /// doesn't support priorities.
+    ///
+    /// Of the muxers maintained in this repository, only mplex supports priorities.
+    fn priority_handle(&self, _substream: &Self::Substream) -> Option<PriorityHandle> {
+        None
+    }
+}
+
+/// Sets the priority of a substream, see [`StreamMuxer::priority_handle`].
+#[derive(Clone)]
+pub struct PriorityHandle(Arc<dyn Fn(StreamPriority) + Send + Sync>);
+
+impl PriorityHandle {
+    /// Creates a new [`PriorityHandle`] applying priorities with the given function.
+    pub fn new(f: impl Fn(StreamPriority) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    /// Sets the priority of the substream.
+    pub fn set(&self, priority: StreamPriority) {
+        (self.0)(priority)
+    }
+}
+
+impl fmt::Debug for PriorityHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PriorityHandle").finish_non_exhaustive()
+    }
+}
+
+/// The priority of a substream, see [`StreamMuxer::priority_handle`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StreamPriority {
+    /// For bulk transfers that may be delayed in favour of other substreams.
+    Low,
+    /// The priority of substreams unless set otherwise.
+    #[default]
+    Normal,
+    /// For control protocols, e.g. ping or identify, that should stay responsive during large
+    /// transfers.
+    High,
 }
 
 /// An event produced by a [`StreamMuxer`].
@@ -1,6 +1,7 @@
-use crate::muxing::{StreamMuxer, StreamMuxerEvent};
+use crate::muxing::{PriorityHandle, StreamMuxer, StreamMuxerEvent};
 use futures::{AsyncRead, AsyncWrite};
 use pin_project::pin_project;
+use std::any::Any;
 use std::error::Error;
 use std::fmt;
 use std::io;
@@ -76,6 +77,11 @@ where
     ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
         self.project().inner.poll(cx).map_err(into_io_error)
     }
+
+    fn priority_handle(&self, substream: &Self::Substream) -> Option<PriorityHandle> {
+        self.inner
+            .priority_handle(substream.downcast_ref::<T::Substream>()?)
+    }
 }
 
 fn into_io_error<E>(err: E) -> io::Error
@@ -136,6 +142,10 @@ impl StreamMuxer for StreamMuxerBox {
     ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
         self.project().poll(cx)
     }
+
+    fn priority_handle(&self, substream: &Self::Substream) -> Option<PriorityHandle> {
+        self.inner.priority_handle(substream)
+    }
 }
 
 impl SubstreamBox {
@@ -143,6 +153,11 @@ impl SubstreamBox {
     pub fn new<S: AsyncRead + AsyncWrite + Send + 'static>(stream: S) -> Self {
         Self(Box::pin(stream))
     }
+
+    /// Returns the erased inner substream if it is of type `S`.
+    fn downcast_ref<S: 'static>(&self) -> Option<&S> {
+        self.0.as_ref().get_ref().as_any().downcast_ref()
+    }
 }
 
 impl fmt::Debug for SubstreamBox {
@@ -157,15 +172,24 @@ trait AsyncReadWrite: AsyncRead + AsyncWrite {
     ///
     /// Used to make the [`Debug`] implementation of [`SubstreamBox`] more useful.
     fn type_name(&self) -> &'static str;
+
+    /// Helper function to recover the erased inner type.
+    ///
+    /// Used to hand the substream back to its [`StreamMuxer`].
+    fn as_any(&self) -> &dyn Any;
 }
 
 impl<S> AsyncReadWrite for S
 where
-    S: AsyncRead + AsyncWrite,
+    S: AsyncRead + AsyncWrite + 'static,
 {
     fn type_name(&self) -> &'static str {
         std::any::type_name::<S>()
     }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 impl AsyncRead for SubstreamBox {
@@ -211,3 +235,116 @@ impl AsyncWrite for SubstreamBox {
         self.0.as_mut().poll_close(cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::muxing::StreamPriority;
+    use futures::io::Cursor;
+    use std::sync::{Arc, Mutex};
+
+    /// A muxer recording the priorities set on its substreams.
+    #[derive(Default)]
+    struct RecordingMuxer {
+        priorities: Arc<Mutex<Vec<(u8, StreamPriority)>>>,
+    }
+
+    struct Substream {
+        id: u8,
+        io: Cursor<Vec<u8>>,
+    }
+
+    impl AsyncRead for Substream {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.io).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for Substream {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.io).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.io).poll_flush(cx)
+        }
+
+        fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.io).poll_close(cx)
+        }
+    }
+
+    impl StreamMuxer for RecordingMuxer {
+        type Substream = Substream;
+        type Error = io::Error;
+
+        fn poll_inbound(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<Self::Substream, Self::Error>> {
+            Poll::Pending
+        }
+
+        fn poll_outbound(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<Self::Substream, Self::Error>> {
+            Poll::Ready(Ok(Substream {
+                id: 1,
+                io: Cursor::new(Vec::new()),
+            }))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+        ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+            Poll::Pending
+        }
+
+        fn priority_handle(&self, substream: &Self::Substream) -> Option<PriorityHandle> {
+            let priorities = self.priorities.clone();
+            let id = substream.id;
+            Some(PriorityHandle::new(move |priority| {
+                priorities.lock().unwrap().push((id, priority))
+            }))
+        }
+    }
+
+    #[test]
+    fn substream_priority_is_forwarded_to_inner_muxer() {
+        use crate::muxing::StreamMuxerExt;
+
+        let muxer = RecordingMuxer::default();
+        let priorities = muxer.priorities.clone();
+        let mut muxer = StreamMuxerBox::new(muxer);
+
+        let substream = futures::executor::block_on(futures::future::poll_fn(|cx| {
+            muxer.poll_outbound_unpin(cx)
+        }))
+        .unwrap();
+        muxer
+            .priority_handle(&substream)
+            .unwrap()
+            .set(StreamPriority::High);
+
+        // Substreams not created by the inner muxer have no handle.
+        assert!(muxer
+            .priority_handle(&SubstreamBox::new(Cursor::new(Vec::new())))
+            .is_none());
+
+        assert_eq!(*priorities.lock().unwrap(), vec![(1, StreamPriority::High)]);
+    }
+}
@@ -26,7 +26,7 @@
 //! modifying a transport through composition with other transports or protocol upgrades.
 
 use futures::prelude::*;
-use multiaddr::Multiaddr;
+use multiaddr::{Multiaddr, Protocol};
 use std::{
     error::Error,
     fmt,
@@ -36,20 +36,24 @@ use std::{
 
 pub mod and_then;
 pub mod choice;
+pub mod dialer;
 pub mod dummy;
 pub mod map;
 pub mod map_err;
 pub mod memory;
+#[cfg(unix)]
+pub mod systemd;
 pub mod timeout;
 pub mod upgrade;
 
 mod boxed;
 mod optional;
 
-use crate::ConnectedPoint;
+use crate::{ConnectedPoint, Endpoint};
 
 pub use self::boxed::Boxed;
 pub use self::choice::OrTransport;
+pub use self::dialer::{Dialer, WithDialers};
 pub use self::memory::MemoryTransport;
 pub use self::optional::OptionalTransport;
 pub use self::upgrade::Upgrade;
@@ -133,6 +137,23 @@ pub trait Transport {
         addr: Multiaddr,
     ) -> Result<Self::Dial, TransportError<Self::Error>>;
 
+    /// As [`Transport::dial`] but with the given [`DialOpts`].
+    ///
+    /// The default implementation dispatches to [`Transport::dial`] or
+    /// [`Transport::dial_as_listener`] depending on [`DialOpts::role`], ignoring
+    /// [`DialOpts::port_use`]. Transports for which the local port of a connection can be
+    /// chosen, as well as transports wrapping other transports, should override it.
+    fn dial_with_opts(
+        &mut self,
+        addr: Multiaddr,
+        opts: DialOpts,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        match opts.role {
+            Endpoint::Dialer => self.dial(addr),
+            Endpoint::Listener => self.dial_as_listener(addr),
+        }
+    }
+
     /// Poll for [`TransportEvent`]s.
     ///
     /// A [`TransportEvent::Incoming`] should be produced whenever a connection is received at the lowest
@@ -210,6 +231,20 @@ pub trait Transport {
         OrTransport::new(self, other)
     }
 
+    /// Adds a [`Dialer`] for addresses the transport doesn't support, e.g. `/onion3` or
+    /// `/garlic64` addresses dialed via the proxy of an anonymity network.
+    ///
+    /// The returned transport dials via `dialer` whenever `self` returns
+    /// [`TransportError::MultiaddrNotSupported`]. Further dialers can be added via
+    /// [`WithDialers::with_dialer`] and are tried in the order of registration.
+    fn with_dialer<D>(self, dialer: D) -> WithDialers<Self>
+    where
+        Self: Sized,
+        D: Dialer<Output = Self::Output> + Send + 'static,
+    {
+        WithDialers::new(self).with_dialer(dialer)
+    }
+
     /// Applies a function producing an asynchronous result to every connection
     /// created by this transport.
     ///
@@ -238,6 +273,93 @@ pub trait Transport {
     }
 }
 
+/// Options for a dial, see [`Transport::dial_with_opts`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DialOpts {
+    /// The role of the local node on the connection, see [`Transport::dial_as_listener`].
+    pub role: Endpoint,
+    /// Which local port to dial from.
+    pub port_use: PortUse,
+}
+
+impl Default for DialOpts {
+    fn default() -> Self {
+        Self {
+            role: Endpoint::Dialer,
+            port_use: PortUse::default(),
+        }
+    }
+}
+
+/// Which local port to dial from, see [`DialOpts::port_use`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PortUse {
+    /// Dial from a new, ephemeral port.
+    #[default]
+    New,
+    /// Dial from the port of a listener, if the transport supports it.
+    ///
+    /// Needed for NAT hole punching, where the remote has to observe the connection coming from
+    /// the same port the local node is listening on.
+    Reuse,
+}
+
+/// The kind of transport a connection is established with, derived from its address.
+///
+/// Allows telling apart the transports of a node combining several of them, e.g. via
+/// [`OrTransport`], which otherwise all produce the same connection type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TransportKind {
+    Tcp,
+    WebSocket,
+    Quic,
+    WebRtc,
+    /// A connection relayed via a circuit relay, regardless of the transport to the relay.
+    Relay,
+    Memory,
+    /// A transport not known to `libp2p-core`.
+    Other,
+}
+
+impl TransportKind {
+    /// Returns the kind of transport used to dial or listen on the given address.
+    pub fn from_address(address: &Multiaddr) -> Self {
+        let mut kind = TransportKind::Other;
+        for protocol in address.iter() {
+            match protocol {
+                Protocol::P2pCircuit => return TransportKind::Relay,
+                Protocol::Ws(_) | Protocol::Wss(_) => kind = TransportKind::WebSocket,
+                Protocol::WebRTC | Protocol::P2pWebRtcDirect => kind = TransportKind::WebRtc,
+                Protocol::Quic | Protocol::QuicV1 => kind = TransportKind::Quic,
+                Protocol::Tcp(_) if kind == TransportKind::Other => kind = TransportKind::Tcp,
+                Protocol::Memory(_) => kind = TransportKind::Memory,
+                _ => {}
+            }
+        }
+        kind
+    }
+
+    /// Returns a short identifier of the transport, e.g. `tcp` or `quic`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransportKind::Tcp => "tcp",
+            TransportKind::WebSocket => "ws",
+            TransportKind::Quic => "quic",
+            TransportKind::WebRtc => "webrtc",
+            TransportKind::Relay => "relay",
+            TransportKind::Memory => "memory",
+            TransportKind::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for TransportKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// The ID of a single listener.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ListenerId(u64);
@@ -559,3 +681,30 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transport_kind_from_address() {
+        let kind = |address: &str| TransportKind::from_address(&address.parse().unwrap());
+
+        assert_eq!(kind("/ip4/127.0.0.1/tcp/4001"), TransportKind::Tcp);
+        assert_eq!(
+            kind("/dns4/example.com/tcp/443/wss"),
+            TransportKind::WebSocket
+        );
+        assert_eq!(kind("/ip6/::1/udp/4001/quic-v1"), TransportKind::Quic);
+        assert_eq!(
+            kind("/ip4/127.0.0.1/udp/4001/webrtc"),
+            TransportKind::WebRtc
+        );
+        assert_eq!(
+            kind("/ip4/127.0.0.1/udp/4001/quic-v1/p2p/12D3KooWDpJ7As7BWAwRMfu1VU2WCqNjvq387JEYKDBj4kx6nXTN/p2p-circuit"),
+            TransportKind::Relay
+        );
+        assert_eq!(kind("/memory/1234"), TransportKind::Memory);
+        assert_eq!(kind("/ip4/127.0.0.1/udp/4001"), TransportKind::Other);
+    }
+}
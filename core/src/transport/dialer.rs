@@ -0,0 +1,274 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Dialing of addresses the transport stack doesn't support natively.
+//!
+//! A [`Dialer`] establishes connections to addresses of protocols like `/onion3` or `/garlic64`,
+//! e.g. through the SOCKS proxy of a Tor or I2P router. Registered via
+//! [`Transport::with_dialer`], it is consulted whenever the wrapped transport rejects an address
+//! with [`TransportError::MultiaddrNotSupported`].
+
+use crate::transport::{DialOpts, ListenerId, Transport, TransportError, TransportEvent};
+use either::Either;
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use multiaddr::Multiaddr;
+use std::{fmt, io, pin::Pin, task::Context, task::Poll};
+
+/// Dials addresses on behalf of a [`Transport`], see [`Transport::with_dialer`].
+pub trait Dialer {
+    /// The connection established by the dialer.
+    type Output;
+
+    /// Dials the given [`Multiaddr`], returning a future for a pending outbound connection.
+    ///
+    /// Returns [`TransportError::MultiaddrNotSupported`] for addresses the dialer doesn't handle,
+    /// in which case the next registered dialer is tried.
+    fn dial(
+        &mut self,
+        addr: Multiaddr,
+        opts: DialOpts,
+    ) -> Result<BoxFuture<'static, io::Result<Self::Output>>, TransportError<io::Error>>;
+}
+
+type DialResult<T> = Result<
+    DialFuture<<T as Transport>::Dial, <T as Transport>::Output>,
+    TransportError<Either<<T as Transport>::Error, io::Error>>,
+>;
+
+/// Transport returned by [`Transport::with_dialer`], dialing addresses not supported by the
+/// wrapped transport via the registered [`Dialer`]s.
+#[pin_project::pin_project]
+pub struct WithDialers<T>
+where
+    T: Transport,
+{
+    #[pin]
+    inner: T,
+    dialers: Vec<Box<dyn Dialer<Output = T::Output> + Send>>,
+}
+
+impl<T> WithDialers<T>
+where
+    T: Transport,
+{
+    pub(crate) fn new(inner: T) -> Self {
+        WithDialers {
+            inner,
+            dialers: Vec::new(),
+        }
+    }
+
+    /// Registers another [`Dialer`], tried after the ones registered before.
+    pub fn with_dialer(mut self, dialer: impl Dialer<Output = T::Output> + Send + 'static) -> Self {
+        self.dialers.push(Box::new(dialer));
+        self
+    }
+
+    fn do_dial(&mut self, addr: Multiaddr, opts: DialOpts) -> DialResult<T> {
+        let mut addr = match self.inner.dial_with_opts(addr, opts) {
+            Ok(dial) => return Ok(DialFuture::Inner(dial)),
+            Err(TransportError::MultiaddrNotSupported(addr)) => addr,
+            Err(TransportError::Other(err)) => {
+                return Err(TransportError::Other(Either::Left(err)))
+            }
+        };
+
+        for dialer in self.dialers.iter_mut() {
+            addr = match dialer.dial(addr, opts) {
+                Ok(dial) => return Ok(DialFuture::Dialer(dial)),
+                Err(TransportError::MultiaddrNotSupported(addr)) => addr,
+                Err(TransportError::Other(err)) => {
+                    return Err(TransportError::Other(Either::Right(err)))
+                }
+            };
+        }
+
+        Err(TransportError::MultiaddrNotSupported(addr))
+    }
+}
+
+impl<T> fmt::Debug for WithDialers<T>
+where
+    T: Transport + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WithDialers")
+            .field("inner", &self.inner)
+            .field("dialers", &self.dialers.len())
+            .finish()
+    }
+}
+
+impl<T> Transport for WithDialers<T>
+where
+    T: Transport,
+{
+    type Output = T::Output;
+    type Error = Either<T::Error, io::Error>;
+    type ListenerUpgrade = future::MapErr<T::ListenerUpgrade, fn(T::Error) -> Self::Error>;
+    type Dial = DialFuture<T::Dial, T::Output>;
+
+    fn listen_on(&mut self, addr: Multiaddr) -> Result<ListenerId, TransportError<Self::Error>> {
+        self.inner
+            .listen_on(addr)
+            .map_err(|err| err.map(Either::Left))
+    }
+
+    fn remove_listener(&mut self, id: ListenerId) -> bool {
+        self.inner.remove_listener(id)
+    }
+
+    fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.do_dial(addr, DialOpts::default())
+    }
+
+    fn dial_as_listener(
+        &mut self,
+        addr: Multiaddr,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.do_dial(
+            addr,
+            DialOpts {
+                role: crate::Endpoint::Listener,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn dial_with_opts(
+        &mut self,
+        addr: Multiaddr,
+        opts: DialOpts,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.do_dial(addr, opts)
+    }
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<TransportEvent<Self::ListenerUpgrade, Self::Error>> {
+        self.project().inner.poll(cx).map(|event| {
+            event
+                .map_upgrade(|upgr| upgr.map_err::<_, fn(_) -> _>(Either::Left))
+                .map_err(Either::Left)
+        })
+    }
+
+    fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        self.inner.address_translation(server, observed)
+    }
+}
+
+/// Pending outbound connection of [`WithDialers`].
+#[pin_project::pin_project(project = DialFutureProj)]
+pub enum DialFuture<TDial, TOutput> {
+    /// Dial of the wrapped transport.
+    Inner(#[pin] TDial),
+    /// Dial of a registered [`Dialer`].
+    Dialer(BoxFuture<'static, io::Result<TOutput>>),
+}
+
+impl<TDial, TOutput, TError> Future for DialFuture<TDial, TOutput>
+where
+    TDial: TryFuture<Ok = TOutput, Error = TError>,
+{
+    type Output = Result<TOutput, Either<TError, io::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            DialFutureProj::Inner(dial) => dial.try_poll(cx).map_err(Either::Left),
+            DialFutureProj::Dialer(dial) => dial.poll_unpin(cx).map_err(Either::Right),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::memory::{Channel, MemoryTransport};
+    use multiaddr::Protocol;
+
+    /// Dials `/onion3` addresses by connecting to a fixed memory address, standing in for a proxy.
+    struct ProxyDialer {
+        proxy: Multiaddr,
+        dialed: Vec<Multiaddr>,
+    }
+
+    impl Dialer for ProxyDialer {
+        type Output = Channel<Vec<u8>>;
+
+        fn dial(
+            &mut self,
+            addr: Multiaddr,
+            _: DialOpts,
+        ) -> Result<BoxFuture<'static, io::Result<Self::Output>>, TransportError<io::Error>>
+        {
+            if !matches!(addr.iter().next(), Some(Protocol::Onion3(_))) {
+                return Err(TransportError::MultiaddrNotSupported(addr));
+            }
+            self.dialed.push(addr);
+
+            let dial = MemoryTransport::default()
+                .dial(self.proxy.clone())
+                .map_err(|_| TransportError::Other(io::ErrorKind::Other.into()))?;
+            Ok(dial
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+                .boxed())
+        }
+    }
+
+    #[test]
+    fn dials_unsupported_addresses_via_dialer() {
+        let proxy: Multiaddr = Protocol::Memory(rand::random::<u64>().saturating_add(1)).into();
+        let mut listener = MemoryTransport::default().boxed();
+        listener.listen_on(proxy.clone()).unwrap();
+
+        let mut transport = MemoryTransport::default().with_dialer(ProxyDialer {
+            proxy,
+            dialed: Vec::new(),
+        });
+
+        let onion: Multiaddr =
+            "/onion3/vww6ybal4bd7szmgncyruucpgfkqahzddi37ktceo3ah7ngmcopnpyyd:1234"
+                .parse()
+                .unwrap();
+        let dial = transport.dial(onion).unwrap();
+        assert_eq!(transport.dialers.len(), 1);
+
+        futures::executor::block_on(async move {
+            let (_, _) = futures::join!(async { dial.await.unwrap() }, async {
+                loop {
+                    if let TransportEvent::Incoming { upgrade, .. } =
+                        listener.select_next_some().await
+                    {
+                        break upgrade.await.unwrap();
+                    }
+                }
+            });
+        });
+
+        let dns: Multiaddr = "/dns4/example.com/tcp/1234".parse().unwrap();
+        assert!(matches!(
+            transport.dial(dns.clone()),
+            Err(TransportError::MultiaddrNotSupported(a)) if a == dns
+        ));
+    }
+}
@@ -20,7 +20,7 @@
 
 use crate::{
     connection::{ConnectedPoint, Endpoint},
-    transport::{Transport, TransportError, TransportEvent},
+    transport::{DialOpts, PortUse, Transport, TransportError, TransportEvent},
 };
 use futures::prelude::*;
 use multiaddr::Multiaddr;
@@ -70,25 +70,37 @@ where
     }
 
     fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
-        let future = self.transport.dial(addr.clone())?;
-        let p = ConnectedPoint::Dialer {
-            address: addr,
-            role_override: Endpoint::Dialer,
-        };
-        Ok(MapFuture {
-            inner: future,
-            args: Some((self.fun.clone(), p)),
-        })
+        self.dial_with_opts(
+            addr,
+            DialOpts {
+                role: Endpoint::Dialer,
+                port_use: PortUse::New,
+            },
+        )
     }
 
     fn dial_as_listener(
         &mut self,
         addr: Multiaddr,
     ) -> Result<Self::Dial, TransportError<Self::Error>> {
-        let future = self.transport.dial_as_listener(addr.clone())?;
+        self.dial_with_opts(
+            addr,
+            DialOpts {
+                role: Endpoint::Listener,
+                port_use: PortUse::New,
+            },
+        )
+    }
+
+    fn dial_with_opts(
+        &mut self,
+        addr: Multiaddr,
+        opts: DialOpts,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let future = self.transport.dial_with_opts(addr.clone(), opts)?;
         let p = ConnectedPoint::Dialer {
             address: addr,
-            role_override: Endpoint::Listener,
+            role_override: opts.role,
         };
         Ok(MapFuture {
             inner: future,
@@ -0,0 +1,77 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Sockets passed to the process via systemd socket activation, see `sd_listen_fds(3)`.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::os::unix::io::{FromRawFd, RawFd};
+
+/// The first file descriptor passed by systemd.
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// The file descriptors passed by systemd that have not been taken yet.
+static LISTEN_FDS: Lazy<Mutex<Vec<RawFd>>> = Lazy::new(|| Mutex::new(read_env()));
+
+/// Takes ownership of the sockets passed via systemd socket activation for which `filter` returns
+/// `true`.
+///
+/// The environment variables `LISTEN_PID` and `LISTEN_FDS` are read on the first call and left
+/// untouched. Each socket is handed out at most once across all calls, sockets rejected by
+/// `filter` remain available to later calls. Returns an empty list if the process has not been
+/// socket-activated.
+///
+/// Code reading the environment directly, e.g. through the `sd_listen_fds(3)` of libsystemd,
+/// bypasses this bookkeeping and must not be used alongside this function.
+///
+/// `filter` is passed the raw file descriptor to inspect the socket without taking ownership of
+/// it, e.g. through a socket type wrapped in [`std::mem::ManuallyDrop`].
+pub fn take_listen_fds<T, F>(mut filter: F) -> Vec<T>
+where
+    T: FromRawFd,
+    F: FnMut(RawFd) -> bool,
+{
+    let mut taken = Vec::new();
+    LISTEN_FDS.lock().retain(|fd| {
+        if filter(*fd) {
+            // SAFETY: systemd passes open file descriptors owned by this process. Each file
+            // descriptor is removed from `LISTEN_FDS` once taken, thus this is its only owner.
+            taken.push(unsafe { T::from_raw_fd(*fd) });
+            return false;
+        }
+        true
+    });
+    taken
+}
+
+/// Reads the environment variables describing the file descriptors passed by systemd.
+fn read_env() -> Vec<RawFd> {
+    let is_activated = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        .map_or(false, |pid| pid == std::process::id());
+    let num_fds = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|n| n.parse::<RawFd>().ok())
+        .filter(|_| is_activated)
+        .unwrap_or(0);
+
+    (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START.saturating_add(num_fds)).collect()
+}
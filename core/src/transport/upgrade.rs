@@ -26,18 +26,18 @@ use crate::{
     connection::ConnectedPoint,
     muxing::{StreamMuxer, StreamMuxerBox},
     transport::{
-        and_then::AndThen, boxed::boxed, timeout::TransportTimeout, ListenerId, Transport,
-        TransportError, TransportEvent,
+        and_then::AndThen, boxed::boxed, timeout::TransportTimeout, DialOpts, ListenerId,
+        Transport, TransportError, TransportEvent,
     },
     upgrade::{
-        self, apply_inbound, apply_outbound, InboundUpgrade, InboundUpgradeApply, OutboundUpgrade,
-        OutboundUpgradeApply, UpgradeError,
+        self, apply_inbound, apply_outbound, ExpectPeerId, InboundUpgrade, InboundUpgradeApply,
+        OutboundUpgrade, OutboundUpgradeApply, UpgradeError,
     },
     Negotiated,
 };
 use futures::{prelude::*, ready};
 use libp2p_identity::PeerId;
-use multiaddr::Multiaddr;
+use multiaddr::{Multiaddr, Protocol};
 use std::{
     error::Error,
     fmt,
@@ -113,6 +113,67 @@ where
             version,
         ))
     }
+
+    /// Like [`Builder::authenticate`], but pins the [`PeerId`] of the remote on dials to
+    /// addresses ending with `/p2p/<peer-id>`, as the `Swarm` dials known peers.
+    ///
+    /// The expected [`PeerId`] is passed to the upgrade via [`ExpectPeerId`], aborting the
+    /// handshake with a remote authenticating as a different peer, before any further upgrades
+    /// are applied.
+    pub fn authenticate_pinned<C, D, U, E>(
+        self,
+        upgrade: U,
+    ) -> Authenticated<AndThen<T, impl AuthenticateFn<C, U>>>
+    where
+        T: Transport<Output = C>,
+        C: AsyncRead + AsyncWrite + Unpin,
+        D: AsyncRead + AsyncWrite + Unpin,
+        U: InboundUpgrade<Negotiated<C>, Output = (PeerId, D), Error = E>,
+        U: OutboundUpgrade<Negotiated<C>, Output = (PeerId, D), Error = E> + ExpectPeerId + Clone,
+        E: Error + 'static,
+    {
+        let version = self.version;
+        Authenticated(Builder::new(
+            self.inner.and_then(move |conn, endpoint| {
+                let upgrade = match expected_peer_id(&endpoint) {
+                    Some(peer_id) => upgrade.expect_peer_id(peer_id),
+                    None => upgrade,
+                };
+                Authenticate {
+                    inner: upgrade::apply(conn, upgrade, endpoint, version),
+                }
+            }),
+            version,
+        ))
+    }
+}
+
+/// A function applying an authentication upgrade to a connection, see
+/// [`Builder::authenticate_pinned`].
+pub trait AuthenticateFn<C, U>: FnOnce(C, ConnectedPoint) -> Authenticate<C, U> + Clone
+where
+    C: AsyncRead + AsyncWrite + Unpin,
+    U: InboundUpgrade<Negotiated<C>> + OutboundUpgrade<Negotiated<C>>,
+{
+}
+
+impl<F, C, U> AuthenticateFn<C, U> for F
+where
+    F: FnOnce(C, ConnectedPoint) -> Authenticate<C, U> + Clone,
+    C: AsyncRead + AsyncWrite + Unpin,
+    U: InboundUpgrade<Negotiated<C>> + OutboundUpgrade<Negotiated<C>>,
+{
+}
+
+/// Returns the [`PeerId`] a dialed address ends with, if any.
+fn expected_peer_id(endpoint: &ConnectedPoint) -> Option<PeerId> {
+    match endpoint {
+        ConnectedPoint::Dialer { address, .. } => match address.iter().last()? {
+            Protocol::P2p(multihash) => PeerId::from_multihash(multihash).ok(),
+            _ => None,
+        },
+        ConnectedPoint::Listener { .. } => None,
+    }
 }
 
 /// An upgrade that authenticates the remote peer, typically
@@ -350,6 +411,14 @@ where
         self.0.dial_as_listener(addr)
     }
 
+    fn dial_with_opts(
+        &mut self,
+        addr: Multiaddr,
+        opts: DialOpts,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.0.dial_with_opts(addr, opts)
+    }
+
     fn listen_on(&mut self, addr: Multiaddr) -> Result<ListenerId, TransportError<Self::Error>> {
         self.0.listen_on(addr)
     }
@@ -429,6 +498,21 @@ where
         })
     }
 
+    fn dial_with_opts(
+        &mut self,
+        addr: Multiaddr,
+        opts: DialOpts,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let future = self
+            .inner
+            .dial_with_opts(addr, opts)
+            .map_err(|err| err.map(TransportUpgradeError::Transport))?;
+        Ok(DialUpgradeFuture {
+            future: Box::pin(future),
+            upgrade: future::Either::Left(Some(self.upgrade.clone())),
+        })
+    }
+
     fn listen_on(&mut self, addr: Multiaddr) -> Result<ListenerId, TransportError<Self::Error>> {
         self.inner
             .listen_on(addr)
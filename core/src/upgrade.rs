@@ -67,6 +67,7 @@ mod optional;
 mod pending;
 mod ready;
 mod select;
+mod simultaneous_open;
 mod transfer;
 
 use futures::future::Future;
@@ -81,6 +82,7 @@ pub use self::{
     pending::PendingUpgrade,
     ready::ReadyUpgrade,
     select::SelectUpgrade,
+    simultaneous_open::simultaneous_open_role,
     transfer::{read_length_prefixed, read_varint, write_length_prefixed, write_varint},
 };
 pub use crate::Negotiated;
@@ -224,3 +226,13 @@ pub trait OutboundUpgradeExt<C>: OutboundUpgrade<C> {
 }
 
 impl<C, U: OutboundUpgrade<C>> OutboundUpgradeExt<C> for U {}
+
+/// Implemented by security upgrades able to check the [`PeerId`](libp2p_identity::PeerId) of the
+/// remote during their handshake.
+///
+/// See [`Builder::authenticate_pinned`](crate::transport::upgrade::Builder::authenticate_pinned).
+pub trait ExpectPeerId {
+    /// Makes the upgrade fail during the handshake if the remote does not authenticate as
+    /// `peer_id`.
+    fn expect_peer_id(self, peer_id: libp2p_identity::PeerId) -> Self;
+}
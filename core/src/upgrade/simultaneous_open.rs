@@ -0,0 +1,70 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::connection::Endpoint;
+use libp2p_identity::PeerId;
+
+/// Resolves the role of the local node in upgrading a connection that was opened by both nodes
+/// dialing each other at the same time, e.g. during a hole punch.
+///
+/// On such a _simultaneous open_ both nodes consider themselves the dialer, yet upgrading the
+/// connection requires exactly one of them to act as the dialer and the other as the listener,
+/// both for negotiating protocols via `multistream-select` and in the handshakes of security
+/// protocols and multiplexers. The tie is broken by comparing the byte representations of the
+/// two peer IDs: the node with the smaller peer ID acts as the dialer. As both nodes compare the
+/// same two peer IDs, they always resolve complementary roles.
+///
+/// The returned [`Endpoint`] is meant to be used as role override of the dial, i.e. the
+/// connection is dialed via [`Transport::dial_as_listener`](crate::Transport::dial_as_listener)
+/// if it resolves to [`Endpoint::Listener`].
+///
+/// Returns `None` if both peer IDs are equal.
+pub fn simultaneous_open_role(local_peer_id: &PeerId, remote_peer_id: &PeerId) -> Option<Endpoint> {
+    match local_peer_id.to_bytes().cmp(&remote_peer_id.to_bytes()) {
+        std::cmp::Ordering::Less => Some(Endpoint::Dialer),
+        std::cmp::Ordering::Greater => Some(Endpoint::Listener),
+        std::cmp::Ordering::Equal => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_are_complementary() {
+        for _ in 0..100 {
+            let a = PeerId::random();
+            let b = PeerId::random();
+
+            let role_a = simultaneous_open_role(&a, &b).unwrap();
+            let role_b = simultaneous_open_role(&b, &a).unwrap();
+
+            assert_ne!(role_a, role_b);
+        }
+    }
+
+    #[test]
+    fn no_role_for_self() {
+        let a = PeerId::random();
+
+        assert_eq!(simultaneous_open_role(&a, &a), None);
+    }
+}
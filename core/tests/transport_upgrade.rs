@@ -124,3 +124,70 @@ fn upgrade_pipeline() {
     async_std::task::spawn(server);
     async_std::task::block_on(client);
 }
+
+#[test]
+fn authenticate_pinned_rejects_unexpected_peer() {
+    let listener_keys = identity::Keypair::generate_ed25519();
+    let listener_id = listener_keys.public().to_peer_id();
+    let mut listener_transport = MemoryTransport::default()
+        .upgrade(upgrade::Version::V1)
+        .authenticate(noise::NoiseAuthenticated::xx(&listener_keys).unwrap())
+        .multiplex(MplexConfig::default())
+        .boxed();
+
+    let dialer_keys = identity::Keypair::generate_ed25519();
+    let mut dialer_transport = MemoryTransport::default()
+        .upgrade(upgrade::Version::V1)
+        .authenticate_pinned(noise::NoiseAuthenticated::xx(&dialer_keys).unwrap())
+        .multiplex(MplexConfig::default())
+        .boxed();
+
+    let listen_addr = Multiaddr::from(Protocol::Memory(random::<u64>()));
+    listener_transport.listen_on(listen_addr.clone()).unwrap();
+
+    async_std::task::spawn(async move {
+        loop {
+            if let Some((upgrade, _)) = listener_transport.select_next_some().await.into_incoming()
+            {
+                let _ = upgrade.await;
+            }
+        }
+    });
+
+    async_std::task::block_on(async move {
+        let (peer, _mplex) = dialer_transport
+            .dial(listen_addr.clone().with(Protocol::P2p(listener_id.into())))
+            .unwrap()
+            .await
+            .unwrap();
+        assert_eq!(peer, listener_id);
+
+        let expected = identity::Keypair::generate_ed25519().public().to_peer_id();
+        let error = dialer_transport
+            .dial(listen_addr.with(Protocol::P2p(expected.into())))
+            .unwrap()
+            .await
+            .unwrap_err();
+
+        let mut source: Option<&(dyn std::error::Error + 'static)> = Some(&error);
+        let noise_error = loop {
+            match source {
+                Some(e) => match e.downcast_ref::<noise::NoiseError>() {
+                    Some(noise_error) => break noise_error,
+                    None => source = e.source(),
+                },
+                None => panic!("Unexpected error: {error:?}"),
+            }
+        };
+        match noise_error {
+            noise::NoiseError::PeerIdMismatch {
+                expected: e,
+                actual,
+            } => {
+                assert_eq!(*e, expected);
+                assert_eq!(actual.to_peer_id(), listener_id);
+            }
+            e => panic!("Unexpected error: {e:?}"),
+        }
+    });
+}
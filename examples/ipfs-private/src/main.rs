@@ -156,7 +156,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     enum MyBehaviourEvent {
         Gossipsub(gossipsub::Event),
-        Identify(identify::Event),
+        Identify(Box<identify::Event>),
         Ping(ping::Event),
     }
 
@@ -168,7 +168,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     impl From<identify::Event> for MyBehaviourEvent {
         fn from(event: identify::Event) -> Self {
-            MyBehaviourEvent::Identify(event)
+            MyBehaviourEvent::Identify(Box::new(event))
         }
     }
 
@@ -277,6 +277,12 @@ async fn main() -> Result<(), Box<dyn Error>> {
                             } => {
                                 println!("ping: {} does not support ping protocol", peer.to_base58());
                             }
+                            ping::Event {
+                                peer,
+                                result: Result::Err(ping::Failure::Mismatch),
+                            } => {
+                                println!("ping: payload mismatch from {}", peer.to_base58());
+                            }
                             ping::Event {
                                 peer,
                                 result: Result::Err(ping::Failure::Other { error }),
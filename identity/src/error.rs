@@ -94,6 +94,19 @@ impl DecodingError {
         }
     }
 
+    #[cfg(any(
+        feature = "ecdsa",
+        feature = "secp256k1",
+        feature = "ed25519",
+        feature = "rsa"
+    ))]
+    pub(crate) fn invalid_signature(what: &'static str) -> Self {
+        Self {
+            msg: format!("invalid signature of {what}"),
+            source: None,
+        }
+    }
+
     #[cfg(any(
         all(feature = "rsa", not(target_arch = "wasm32")),
         feature = "secp256k1",
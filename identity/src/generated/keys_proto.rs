@@ -123,3 +123,47 @@ impl MessageWrite for PrivateKey {
     }
 }
 
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct KeyBinding {
+    pub primary_key: Vec<u8>,
+    pub secondary_key: Vec<u8>,
+    pub primary_signature: Vec<u8>,
+    pub secondary_signature: Vec<u8>,
+}
+
+impl<'a> MessageRead<'a> for KeyBinding {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.primary_key = r.read_bytes(bytes)?.to_owned(),
+                Ok(18) => msg.secondary_key = r.read_bytes(bytes)?.to_owned(),
+                Ok(26) => msg.primary_signature = r.read_bytes(bytes)?.to_owned(),
+                Ok(34) => msg.secondary_signature = r.read_bytes(bytes)?.to_owned(),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for KeyBinding {
+    fn get_size(&self) -> usize {
+        0
+        + 1 + sizeof_len((&self.primary_key).len())
+        + 1 + sizeof_len((&self.secondary_key).len())
+        + 1 + sizeof_len((&self.primary_signature).len())
+        + 1 + sizeof_len((&self.secondary_signature).len())
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        w.write_with_tag(10, |w| w.write_bytes(&**&self.primary_key))?;
+        w.write_with_tag(18, |w| w.write_bytes(&**&self.secondary_key))?;
+        w.write_with_tag(26, |w| w.write_bytes(&**&self.primary_signature))?;
+        w.write_with_tag(34, |w| w.write_bytes(&**&self.secondary_signature))?;
+        Ok(())
+    }
+}
@@ -22,6 +22,7 @@ use crate::error::{DecodingError, SigningError};
 use crate::proto;
 use quick_protobuf::{BytesReader, Writer};
 use std::convert::TryFrom;
+use std::fmt;
 
 #[cfg(feature = "ed25519")]
 use crate::ed25519;
@@ -85,6 +86,36 @@ pub enum Keypair {
     Ecdsa(ecdsa::Keypair),
 }
 
+/// The type of an identity key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyType {
+    Ed25519,
+    Rsa,
+    Secp256k1,
+    Ecdsa,
+}
+
+impl KeyType {
+    /// Returns the first key type of `local` that is also supported by the remote,
+    /// i.e. contained in `remote`.
+    ///
+    /// `local` is expected to be ordered by preference.
+    pub fn negotiate(local: &[KeyType], remote: &[KeyType]) -> Option<KeyType> {
+        local.iter().find(|t| remote.contains(t)).copied()
+    }
+}
+
+impl fmt::Display for KeyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyType::Ed25519 => f.write_str("Ed25519"),
+            KeyType::Rsa => f.write_str("RSA"),
+            KeyType::Secp256k1 => f.write_str("Secp256k1"),
+            KeyType::Ecdsa => f.write_str("ECDSA"),
+        }
+    }
+}
+
 impl Keypair {
     /// Generate a new Ed25519 keypair.
     #[cfg(feature = "ed25519")]
@@ -193,6 +224,22 @@ impl Keypair {
         }
     }
 
+    /// Get the type of this keypair.
+    pub fn key_type(&self) -> KeyType {
+        use Keypair::*;
+        #[allow(deprecated)]
+        match self {
+            #[cfg(feature = "ed25519")]
+            Ed25519(_) => KeyType::Ed25519,
+            #[cfg(all(feature = "rsa", not(target_arch = "wasm32")))]
+            Rsa(_) => KeyType::Rsa,
+            #[cfg(feature = "secp256k1")]
+            Secp256k1(_) => KeyType::Secp256k1,
+            #[cfg(feature = "ecdsa")]
+            Ecdsa(_) => KeyType::Ecdsa,
+        }
+    }
+
     /// Get the public key of this keypair.
     pub fn public(&self) -> PublicKey {
         use Keypair::*;
@@ -424,6 +471,22 @@ impl PublicKey {
         pubkey.try_into()
     }
 
+    /// Get the type of this public key.
+    pub fn key_type(&self) -> KeyType {
+        use PublicKey::*;
+        #[allow(deprecated)]
+        match self {
+            #[cfg(feature = "ed25519")]
+            Ed25519(_) => KeyType::Ed25519,
+            #[cfg(all(feature = "rsa", not(target_arch = "wasm32")))]
+            Rsa(_) => KeyType::Rsa,
+            #[cfg(feature = "secp256k1")]
+            Secp256k1(_) => KeyType::Secp256k1,
+            #[cfg(feature = "ecdsa")]
+            Ecdsa(_) => KeyType::Ecdsa,
+        }
+    }
+
     /// Convert the `PublicKey` into the corresponding `PeerId`.
     #[cfg(feature = "peerid")]
     pub fn to_peer_id(&self) -> crate::PeerId {
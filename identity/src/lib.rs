@@ -72,6 +72,13 @@ mod keypair;
 ))]
 #[path = "./keypair_dummy.rs"]
 mod keypair;
+#[cfg(any(
+    feature = "ecdsa",
+    feature = "secp256k1",
+    feature = "ed25519",
+    feature = "rsa"
+))]
+mod multi_key;
 #[cfg(feature = "peerid")]
 mod peer_id;
 
@@ -122,6 +129,20 @@ impl From<&PublicKey> for proto::PublicKey {
 }
 
 pub use error::{DecodingError, SigningError};
+#[cfg(any(
+    feature = "ecdsa",
+    feature = "secp256k1",
+    feature = "ed25519",
+    feature = "rsa"
+))]
+pub use keypair::KeyType;
 pub use keypair::{Keypair, PublicKey};
+#[cfg(any(
+    feature = "ecdsa",
+    feature = "secp256k1",
+    feature = "ed25519",
+    feature = "rsa"
+))]
+pub use multi_key::{KeyBinding, MultiKeypair};
 #[cfg(feature = "peerid")]
 pub use peer_id::{ParseError, PeerId};
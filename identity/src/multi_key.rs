@@ -0,0 +1,305 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Node identities consisting of multiple keypairs.
+//!
+//! Networks migrating from one identity key type to another can have nodes hold a keypair
+//! of each type. The primary keypair determines the identity of the node, i.e. its `PeerId`.
+//! Ownership of every secondary keypair is proven by a [`KeyBinding`], signed by both the
+//! primary and the secondary keypair.
+
+use crate::error::{DecodingError, SigningError};
+use crate::keypair::{KeyType, Keypair, PublicKey};
+use crate::proto;
+use quick_protobuf::{BytesReader, Writer};
+
+/// The domain separation string of the payload signed for a [`KeyBinding`].
+const KEY_BINDING_DOMAIN: &[u8] = b"libp2p-key-binding:";
+
+/// Identity of a node holding multiple keypairs, e.g. an Ed25519 primary and
+/// a Secp256k1 secondary keypair.
+#[derive(Debug, Clone)]
+pub struct MultiKeypair {
+    primary: Keypair,
+    /// The secondary keypairs, at most one per key type, with their bindings to `primary`.
+    secondary: Vec<(Keypair, KeyBinding)>,
+}
+
+impl MultiKeypair {
+    /// Creates a node identity with the given primary keypair and no secondary keypairs.
+    pub fn new(primary: Keypair) -> Self {
+        Self {
+            primary,
+            secondary: Vec::new(),
+        }
+    }
+
+    /// Adds a secondary keypair, binding it to the primary keypair.
+    ///
+    /// Replaces a previously added secondary keypair of the same type.
+    pub fn add_secondary(&mut self, keypair: Keypair) -> Result<&KeyBinding, SigningError> {
+        let binding = KeyBinding::new(&self.primary, &keypair)?;
+        self.secondary
+            .retain(|(k, _)| k.key_type() != keypair.key_type());
+        self.secondary.push((keypair, binding));
+        Ok(&self.secondary.last().expect("just pushed").1)
+    }
+
+    /// Removes the secondary keypair of the given type, returning it.
+    pub fn remove_secondary(&mut self, key_type: KeyType) -> Option<Keypair> {
+        let index = self
+            .secondary
+            .iter()
+            .position(|(k, _)| k.key_type() == key_type)?;
+        Some(self.secondary.remove(index).0)
+    }
+
+    /// Returns the primary keypair, determining the identity of the node.
+    pub fn primary(&self) -> &Keypair {
+        &self.primary
+    }
+
+    /// Returns the `PeerId` of the node, derived from the primary keypair.
+    #[cfg(feature = "peerid")]
+    pub fn peer_id(&self) -> crate::PeerId {
+        self.primary.public().to_peer_id()
+    }
+
+    /// Returns the secondary keypairs.
+    pub fn secondary(&self) -> impl Iterator<Item = &Keypair> {
+        self.secondary.iter().map(|(k, _)| k)
+    }
+
+    /// Returns the bindings of the secondary keypairs to the primary keypair.
+    pub fn bindings(&self) -> impl Iterator<Item = &KeyBinding> {
+        self.secondary.iter().map(|(_, b)| b)
+    }
+
+    /// Returns the binding of the secondary keypair of the given type, if any.
+    pub fn binding(&self, key_type: KeyType) -> Option<&KeyBinding> {
+        self.secondary
+            .iter()
+            .find(|(k, _)| k.key_type() == key_type)
+            .map(|(_, b)| b)
+    }
+
+    /// Returns the keypair of the given type, preferring the primary keypair.
+    pub fn keypair(&self, key_type: KeyType) -> Option<&Keypair> {
+        self.keypairs().find(|k| k.key_type() == key_type)
+    }
+
+    /// Returns the types of the keypairs of the node, the type of the primary keypair first.
+    pub fn key_types(&self) -> Vec<KeyType> {
+        self.keypairs().map(Keypair::key_type).collect()
+    }
+
+    /// Returns the keypair to use with a remote supporting the given key types.
+    ///
+    /// The primary keypair is preferred, followed by the secondary keypairs in the order
+    /// they were added. See also [`KeyType::negotiate`].
+    pub fn negotiate(&self, remote: &[KeyType]) -> Option<&Keypair> {
+        let key_type = KeyType::negotiate(&self.key_types(), remote)?;
+        self.keypair(key_type)
+    }
+
+    fn keypairs(&self) -> impl Iterator<Item = &Keypair> {
+        std::iter::once(&self.primary).chain(self.secondary())
+    }
+}
+
+impl From<Keypair> for MultiKeypair {
+    fn from(primary: Keypair) -> Self {
+        Self::new(primary)
+    }
+}
+
+/// Proof that a secondary key is owned by the node identified by a primary key.
+///
+/// Both keys sign the pair of public keys, so that neither key can be bound to the other
+/// without the consent of its owner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBinding {
+    primary: PublicKey,
+    secondary: PublicKey,
+    primary_signature: Vec<u8>,
+    secondary_signature: Vec<u8>,
+}
+
+impl KeyBinding {
+    /// Binds the `secondary` keypair to the `primary` keypair, signing the binding with both.
+    pub fn new(primary: &Keypair, secondary: &Keypair) -> Result<Self, SigningError> {
+        let primary_key = primary.public();
+        let secondary_key = secondary.public();
+        let payload = signing_payload(&primary_key, &secondary_key);
+
+        Ok(Self {
+            primary_signature: primary.sign(&payload)?,
+            secondary_signature: secondary.sign(&payload)?,
+            primary: primary_key,
+            secondary: secondary_key,
+        })
+    }
+
+    /// Returns the primary public key, identifying the node.
+    pub fn primary(&self) -> &PublicKey {
+        &self.primary
+    }
+
+    /// Returns the secondary public key bound to the primary key.
+    pub fn secondary(&self) -> &PublicKey {
+        &self.secondary
+    }
+
+    /// Returns the `PeerId` of the node owning the secondary key.
+    #[cfg(feature = "peerid")]
+    pub fn peer_id(&self) -> crate::PeerId {
+        self.primary.to_peer_id()
+    }
+
+    /// Checks the signatures of both keys.
+    ///
+    /// Always `true` for bindings obtained via [`KeyBinding::new`] or
+    /// [`KeyBinding::from_protobuf_encoding`].
+    #[must_use]
+    pub fn verify(&self) -> bool {
+        let payload = signing_payload(&self.primary, &self.secondary);
+        self.primary.verify(&payload, &self.primary_signature)
+            && self.secondary.verify(&payload, &self.secondary_signature)
+    }
+
+    /// Encodes the binding as protobuf structure.
+    pub fn to_protobuf_encoding(&self) -> Vec<u8> {
+        use quick_protobuf::MessageWrite;
+
+        let binding = proto::KeyBinding {
+            primary_key: self.primary.to_protobuf_encoding(),
+            secondary_key: self.secondary.to_protobuf_encoding(),
+            primary_signature: self.primary_signature.clone(),
+            secondary_signature: self.secondary_signature.clone(),
+        };
+
+        let mut buf = Vec::with_capacity(binding.get_size());
+        let mut writer = Writer::new(&mut buf);
+        binding
+            .write_message(&mut writer)
+            .expect("Encoding to succeed");
+
+        buf
+    }
+
+    /// Decodes a binding from a protobuf structure, verifying its signatures.
+    pub fn from_protobuf_encoding(bytes: &[u8]) -> Result<KeyBinding, DecodingError> {
+        use quick_protobuf::MessageRead;
+
+        let mut reader = BytesReader::from_bytes(bytes);
+        let binding = proto::KeyBinding::from_reader(&mut reader, bytes)
+            .map_err(|e| DecodingError::bad_protobuf("key binding bytes", e))?;
+
+        let binding = KeyBinding {
+            primary: PublicKey::from_protobuf_encoding(&binding.primary_key)?,
+            secondary: PublicKey::from_protobuf_encoding(&binding.secondary_key)?,
+            primary_signature: binding.primary_signature,
+            secondary_signature: binding.secondary_signature,
+        };
+
+        if !binding.verify() {
+            return Err(DecodingError::invalid_signature("key binding"));
+        }
+
+        Ok(binding)
+    }
+}
+
+/// Returns the payload signed by both keys of a [`KeyBinding`].
+fn signing_payload(primary: &PublicKey, secondary: &PublicKey) -> Vec<u8> {
+    let primary = primary.to_protobuf_encoding();
+    let secondary = secondary.to_protobuf_encoding();
+
+    let mut payload =
+        Vec::with_capacity(KEY_BINDING_DOMAIN.len() + 4 + primary.len() + secondary.len());
+    payload.extend_from_slice(KEY_BINDING_DOMAIN);
+    payload.extend_from_slice(&(primary.len() as u32).to_be_bytes());
+    payload.extend_from_slice(&primary);
+    payload.extend_from_slice(&secondary);
+    payload
+}
+
+#[cfg(all(test, feature = "ed25519", feature = "secp256k1"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secondary_keys_are_bound_to_primary() {
+        let primary = Keypair::generate_ed25519();
+        let mut identity = MultiKeypair::new(primary.clone());
+        identity
+            .add_secondary(Keypair::generate_secp256k1())
+            .unwrap();
+
+        assert_eq!(identity.peer_id(), primary.public().to_peer_id());
+        assert_eq!(
+            identity.key_types(),
+            vec![KeyType::Ed25519, KeyType::Secp256k1]
+        );
+
+        let binding = identity.binding(KeyType::Secp256k1).unwrap();
+        assert!(binding.verify());
+        assert_eq!(binding.peer_id(), identity.peer_id());
+        assert_eq!(
+            binding.secondary(),
+            &identity.keypair(KeyType::Secp256k1).unwrap().public()
+        );
+
+        let decoded = KeyBinding::from_protobuf_encoding(&binding.to_protobuf_encoding()).unwrap();
+        assert_eq!(&decoded, binding);
+    }
+
+    #[test]
+    fn binding_to_other_primary_is_rejected() {
+        let secondary = Keypair::generate_secp256k1();
+        let mut binding = KeyBinding::new(&Keypair::generate_ed25519(), &secondary).unwrap();
+        binding.primary = Keypair::generate_ed25519().public();
+
+        assert!(!binding.verify());
+        assert!(KeyBinding::from_protobuf_encoding(&binding.to_protobuf_encoding()).is_err());
+    }
+
+    #[test]
+    fn negotiate_prefers_primary() {
+        let mut identity = MultiKeypair::new(Keypair::generate_ed25519());
+        identity
+            .add_secondary(Keypair::generate_secp256k1())
+            .unwrap();
+
+        let negotiated = identity
+            .negotiate(&[KeyType::Secp256k1, KeyType::Ed25519])
+            .unwrap();
+        assert_eq!(negotiated.key_type(), KeyType::Ed25519);
+
+        let negotiated = identity.negotiate(&[KeyType::Secp256k1]).unwrap();
+        assert_eq!(negotiated.key_type(), KeyType::Secp256k1);
+
+        assert!(identity.negotiate(&[KeyType::Rsa]).is_none());
+        assert_eq!(
+            KeyType::negotiate(&[KeyType::Rsa, KeyType::Ecdsa], &[KeyType::Ecdsa]),
+            Some(KeyType::Ecdsa)
+        );
+    }
+}
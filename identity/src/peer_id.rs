@@ -42,6 +42,10 @@ const MAX_INLINE_KEY_LENGTH: usize = 42;
 const MULTIHASH_IDENTITY_CODE: u64 = 0;
 const MULTIHASH_SHA256_CODE: u64 = 0x12;
 
+/// The varint-encoded version and `libp2p-key` multicodec prefixing the multihash in the CIDv1
+/// of a `PeerId`. Both codes are below 128, thus a single byte each.
+const CID_V1_LIBP2P_KEY_PREFIX: [u8; 2] = [0x01, 0x72];
+
 /// Identifier of a peer of the network.
 ///
 /// The data is a CIDv0 compatible multihash of the protobuf encoded public key of the peer
@@ -132,6 +136,31 @@ impl PeerId {
         bs58::encode(self.to_bytes()).into_string()
     }
 
+    /// Parses a `PeerId` from the binary representation of a CIDv1 with the
+    /// `libp2p-key` multicodec.
+    pub fn from_cid(cid: &[u8]) -> Result<PeerId, ParseError> {
+        let multihash = cid
+            .strip_prefix(&CID_V1_LIBP2P_KEY_PREFIX)
+            .ok_or(ParseError::MultiHash)?;
+        PeerId::from_bytes(multihash).map_err(|_| ParseError::MultiHash)
+    }
+
+    /// Returns the binary representation of this `PeerId` as a CIDv1 with the
+    /// `libp2p-key` multicodec.
+    pub fn to_cid(&self) -> Vec<u8> {
+        let mut cid = CID_V1_LIBP2P_KEY_PREFIX.to_vec();
+        cid.extend(self.to_bytes());
+        cid
+    }
+
+    /// Returns the base-32 encoded CIDv1 of this `PeerId`, e.g. `bafzaa...`.
+    ///
+    /// This is the textual form printed by other libp2p implementations in some places. Like the
+    /// base-58 form, it is accepted by [`PeerId::from_str`].
+    pub fn to_cid_string(&self) -> String {
+        multibase::encode(multibase::Base::Base32Lower, self.to_cid())
+    }
+
     /// Checks whether the public key passed as parameter matches the public key of this `PeerId`.
     ///
     /// Returns `None` if this `PeerId`s hash algorithm is not supported when encoding the
@@ -259,6 +288,14 @@ impl FromStr for PeerId {
 
     #[inline]
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // As per the peer ID spec, legacy base-58 encoded multihashes start with `1` or `Qm`,
+        // everything else is a multibase encoded CID.
+        if !s.starts_with('1') && !s.starts_with("Qm") {
+            if let Ok((_, cid)) = multibase::decode(s) {
+                return PeerId::from_cid(&cid);
+            }
+        }
+
         let bytes = bs58::decode(s).into_vec()?;
         PeerId::from_bytes(&bytes).map_err(|_| ParseError::MultiHash)
     }
@@ -290,6 +327,37 @@ mod tests {
         assert_eq!(peer_id, second);
     }
 
+    #[test]
+    fn peer_id_to_cid_then_from_cid() {
+        let peer_id = Keypair::generate_ed25519().public().to_peer_id();
+        assert_eq!(peer_id, PeerId::from_cid(&peer_id.to_cid()).unwrap());
+
+        let second: PeerId = peer_id.to_cid_string().parse().unwrap();
+        assert_eq!(peer_id, second);
+    }
+
+    #[test]
+    fn parse_cid_string() {
+        // Example from the peer ID spec.
+        let peer_id: PeerId = "QmYyQSo1c1Ym7orWxLYvCrM2EmxFTANf8wXmmE7DWjhx5N"
+            .parse()
+            .unwrap();
+        let cid = "bafzbeie5745rpv2m6tjyuugywy4d5ewrqgqqhfnf445he3omzpjbx5xqxe";
+
+        assert_eq!(peer_id.to_cid_string(), cid);
+        assert_eq!(cid.parse::<PeerId>().unwrap(), peer_id);
+    }
+
+    #[test]
+    fn from_cid_rejects_other_codecs() {
+        let peer_id = PeerId::random();
+        // CIDv1 with the `raw` multicodec.
+        let mut cid = vec![0x01, 0x55];
+        cid.extend(peer_id.to_bytes());
+
+        assert!(PeerId::from_cid(&cid).is_err());
+    }
+
     #[test]
     fn random_peer_id_is_valid() {
         for _ in 0..5000 {
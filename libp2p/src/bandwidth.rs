@@ -36,6 +36,11 @@ use std::{
     task::{Context, Poll},
 };
 
+mod limit;
+
+pub(crate) use limit::BandwidthLimiter;
+pub use limit::{BandwidthLimits, LimitedConnection};
+
 /// Wraps around a [`StreamMuxer`] and counts the number of bytes that go through all the opened
 /// streams.
 #[derive(Clone)]
@@ -0,0 +1,263 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::{prelude::*, ready};
+use futures_timer::Delay;
+use instant::Instant;
+use std::{
+    io,
+    num::NonZeroU64,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// Aggregate upload and download rate limits of all the connections of a transport, see
+/// [`TransportExt::with_bandwidth_limits`](crate::TransportExt::with_bandwidth_limits).
+///
+/// Each limit allows bursts of up to `burst` bytes, after which the capacity is replenished at
+/// `bytes_per_second`. Without a limit, the traffic in the respective direction is not shaped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BandwidthLimits {
+    upload: Option<(NonZeroU64, NonZeroU64)>,
+    download: Option<(NonZeroU64, NonZeroU64)>,
+}
+
+impl BandwidthLimits {
+    /// Limits the bytes written to all connections to `bytes_per_second`, allowing bursts of up to
+    /// `burst` bytes.
+    pub fn upload(mut self, bytes_per_second: NonZeroU64, burst: NonZeroU64) -> Self {
+        self.upload = Some((bytes_per_second, burst));
+        self
+    }
+
+    /// Limits the bytes read from all connections to `bytes_per_second`, allowing bursts of up to
+    /// `burst` bytes.
+    pub fn download(mut self, bytes_per_second: NonZeroU64, burst: NonZeroU64) -> Self {
+        self.download = Some((bytes_per_second, burst));
+        self
+    }
+}
+
+/// The remaining capacity for bytes in one direction.
+#[derive(Debug)]
+struct TokenBucket {
+    bytes_per_second: f64,
+    burst: f64,
+    /// May become negative when connections transfer concurrently.
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new((bytes_per_second, burst): (NonZeroU64, NonZeroU64), now: Instant) -> Self {
+        let burst = burst.get() as f64;
+        Self {
+            bytes_per_second: bytes_per_second.get() as f64,
+            burst,
+            tokens: burst,
+            last_refill: now,
+        }
+    }
+
+    /// Returns the number of bytes that may be transferred, or how long to wait until at least one
+    /// byte may be transferred.
+    fn available(&mut self, now: Instant) -> Result<usize, Duration> {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.bytes_per_second).min(self.burst);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            return Ok(self.tokens as usize);
+        }
+        Err(Duration::from_secs_f64(
+            (1.0 - self.tokens) / self.bytes_per_second,
+        ))
+    }
+
+    fn consume(&mut self, num_bytes: usize) {
+        self.tokens -= num_bytes as f64;
+    }
+}
+
+/// The token buckets shared by all connections of a transport.
+#[derive(Debug)]
+pub(crate) struct BandwidthLimiter {
+    upload: Option<Mutex<TokenBucket>>,
+    download: Option<Mutex<TokenBucket>>,
+}
+
+impl BandwidthLimiter {
+    pub(crate) fn new(limits: BandwidthLimits) -> Arc<Self> {
+        let now = Instant::now();
+        Arc::new(Self {
+            upload: limits
+                .upload
+                .map(|limit| Mutex::new(TokenBucket::new(limit, now))),
+            download: limits
+                .download
+                .map(|limit| Mutex::new(TokenBucket::new(limit, now))),
+        })
+    }
+}
+
+/// Waits until `bucket` has capacity and returns how many of the `wanted` bytes may be
+/// transferred.
+fn poll_capacity(
+    bucket: &Mutex<TokenBucket>,
+    delay: &mut Option<Delay>,
+    cx: &mut Context<'_>,
+    wanted: usize,
+) -> Poll<usize> {
+    loop {
+        if let Some(d) = delay.as_mut() {
+            ready!(d.poll_unpin(cx));
+            *delay = None;
+        }
+
+        match bucket
+            .lock()
+            .expect("Lock is never poisoned")
+            .available(Instant::now())
+        {
+            Ok(available) => return Poll::Ready(available.min(wanted)),
+            Err(wait) => *delay = Some(Delay::new(wait)),
+        }
+    }
+}
+
+/// Wraps around a raw connection of a transport and delays reads and writes exceeding the shared
+/// [`BandwidthLimits`].
+///
+/// Created by [`TransportExt::with_bandwidth_limits`](crate::TransportExt::with_bandwidth_limits).
+#[pin_project::pin_project]
+pub struct LimitedConnection<TInner> {
+    #[pin]
+    inner: TInner,
+    limiter: Arc<BandwidthLimiter>,
+    read_delay: Option<Delay>,
+    write_delay: Option<Delay>,
+}
+
+impl<TInner> LimitedConnection<TInner> {
+    /// Creates a new [`LimitedConnection`] around the raw connection.
+    pub(crate) fn new(inner: TInner, limiter: Arc<BandwidthLimiter>) -> Self {
+        Self {
+            inner,
+            limiter,
+            read_delay: None,
+            write_delay: None,
+        }
+    }
+}
+
+impl<TInner: AsyncRead> AsyncRead for LimitedConnection<TInner> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let bucket = match &this.limiter.download {
+            Some(bucket) if !buf.is_empty() => bucket,
+            _ => return this.inner.poll_read(cx, buf),
+        };
+        let allowed = ready!(poll_capacity(bucket, this.read_delay, cx, buf.len()));
+        let num_bytes = ready!(this.inner.poll_read(cx, &mut buf[..allowed]))?;
+        bucket
+            .lock()
+            .expect("Lock is never poisoned")
+            .consume(num_bytes);
+        Poll::Ready(Ok(num_bytes))
+    }
+}
+
+impl<TInner: AsyncWrite> AsyncWrite for LimitedConnection<TInner> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let bucket = match &this.limiter.upload {
+            Some(bucket) if !buf.is_empty() => bucket,
+            _ => return this.inner.poll_write(cx, buf),
+        };
+        let allowed = ready!(poll_capacity(bucket, this.write_delay, cx, buf.len()));
+        let num_bytes = ready!(this.inner.poll_write(cx, &buf[..allowed]))?;
+        bucket
+            .lock()
+            .expect("Lock is never poisoned")
+            .consume(num_bytes);
+        Poll::Ready(Ok(num_bytes))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.project();
+        this.inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.project();
+        this.inner.poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, io::Cursor};
+
+    fn limit(bytes_per_second: u64, burst: u64) -> (NonZeroU64, NonZeroU64) {
+        (
+            NonZeroU64::new(bytes_per_second).unwrap(),
+            NonZeroU64::new(burst).unwrap(),
+        )
+    }
+
+    #[test]
+    fn token_bucket_allows_bursts_and_refills() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(limit(1000, 500), now);
+
+        assert_eq!(bucket.available(now), Ok(500));
+        bucket.consume(500);
+        assert!(bucket.available(now).is_err());
+
+        // The capacity is replenished, but never beyond the burst.
+        assert_eq!(bucket.available(now + Duration::from_millis(100)), Ok(100));
+        assert_eq!(bucket.available(now + Duration::from_secs(10)), Ok(500));
+    }
+
+    #[test]
+    fn writes_are_delayed_beyond_the_burst() {
+        let (rate, burst) = limit(10_000, 1000);
+        let limiter = BandwidthLimiter::new(BandwidthLimits::default().upload(rate, burst));
+        let mut connection = LimitedConnection::new(Cursor::new(Vec::new()), limiter);
+
+        let start = Instant::now();
+        block_on(connection.write_all(&[0; 3000])).unwrap();
+
+        // 1000 bytes are sent right away, the remaining 2000 bytes take 200ms.
+        assert!(start.elapsed() >= Duration::from_millis(150));
+        assert_eq!(connection.inner.into_inner().len(), 3000);
+    }
+}
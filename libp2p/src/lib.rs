@@ -163,6 +163,24 @@ pub use libp2p_yamux as yamux;
 mod transport_ext;
 
 pub mod bandwidth;
+#[cfg(all(
+    feature = "autonat",
+    feature = "dcutr",
+    feature = "kad",
+    feature = "macros",
+    feature = "relay"
+))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(
+        feature = "autonat",
+        feature = "dcutr",
+        feature = "kad",
+        feature = "macros",
+        feature = "relay"
+    )))
+)]
+pub mod nat_aware;
 
 #[cfg(doc)]
 pub mod tutorials;
@@ -0,0 +1,284 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Adapting a node to its reachability as determined by AutoNAT.
+//!
+//! The [`Behaviour`] of this module combines [`autonat`] with [`kad`], the [`relay`] client and
+//! [`dcutr`] and reconfigures the latter on every [`autonat::Event::StatusChanged`]:
+//!
+//! | NAT status                   | Kademlia [`Mode`] | Relay reservations | Hole punching |
+//! |------------------------------|-------------------|--------------------|---------------|
+//! | [`NatStatus::Public`]        | Server            | disabled           | disabled      |
+//! | [`NatStatus::Private`]       | Client            | enabled            | enabled       |
+//! | [`NatStatus::Unknown`]       | unchanged         | unchanged          | unchanged     |
+//!
+//! Until AutoNAT has determined the status, the node is configured as if it was private.
+
+use crate::autonat::{self, NatStatus};
+use crate::core::{Endpoint, Multiaddr};
+use crate::dcutr;
+use crate::identity::PeerId;
+use crate::kad::{record::store::RecordStore, Kademlia, KademliaEvent, Mode};
+use crate::relay;
+use crate::swarm::{
+    behaviour::toggle::Toggle, ConnectionDenied, ConnectionHandlerSelect, ConnectionId, FromSwarm,
+    NetworkBehaviour, PollParameters, THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
+};
+use std::task::{Context, Poll};
+
+/// A [`NetworkBehaviour`] reconfiguring Kademlia, the relay client and DCUtR according to the
+/// NAT status reported by AutoNAT.
+///
+/// See the [module documentation](self).
+pub struct Behaviour<TStore>
+where
+    TStore: RecordStore + Send + 'static,
+{
+    inner: Inner<TStore>,
+}
+
+/// Event of the [`Behaviour`], emitted by one of the wrapped behaviours.
+#[derive(Debug)]
+pub enum Event {
+    Autonat(autonat::Event),
+    Kademlia(KademliaEvent),
+    Relay(relay::client::Event),
+    Dcutr(dcutr::Event),
+}
+
+impl From<autonat::Event> for Event {
+    fn from(event: autonat::Event) -> Self {
+        Event::Autonat(event)
+    }
+}
+
+impl From<KademliaEvent> for Event {
+    fn from(event: KademliaEvent) -> Self {
+        Event::Kademlia(event)
+    }
+}
+
+impl From<relay::client::Event> for Event {
+    fn from(event: relay::client::Event) -> Self {
+        Event::Relay(event)
+    }
+}
+
+impl From<dcutr::Event> for Event {
+    fn from(event: dcutr::Event) -> Self {
+        Event::Dcutr(event)
+    }
+}
+
+#[derive(NetworkBehaviour)]
+#[behaviour(out_event = "Event", prelude = "crate::swarm::derive_prelude")]
+struct Inner<TStore>
+where
+    TStore: RecordStore + Send + 'static,
+{
+    autonat: autonat::Behaviour,
+    kademlia: Toggle<Kademlia<TStore>>,
+    relay_client: Toggle<relay::client::Behaviour>,
+    dcutr: Toggle<dcutr::Behaviour>,
+}
+
+impl<TStore> Behaviour<TStore>
+where
+    TStore: RecordStore + Send + 'static,
+{
+    /// Creates a new [`Behaviour`].
+    ///
+    /// Each of `kademlia`, `relay_client` and `dcutr` is optional. Only the given ones are
+    /// reconfigured.
+    pub fn new(
+        autonat: autonat::Behaviour,
+        kademlia: Option<Kademlia<TStore>>,
+        relay_client: Option<relay::client::Behaviour>,
+        dcutr: Option<dcutr::Behaviour>,
+    ) -> Self {
+        let mut behaviour = Self {
+            inner: Inner {
+                autonat,
+                kademlia: kademlia.into(),
+                relay_client: relay_client.into(),
+                dcutr: dcutr.into(),
+            },
+        };
+        behaviour.apply_nat_status(&NatStatus::Private);
+
+        behaviour
+    }
+
+    pub fn autonat(&self) -> &autonat::Behaviour {
+        &self.inner.autonat
+    }
+
+    pub fn autonat_mut(&mut self) -> &mut autonat::Behaviour {
+        &mut self.inner.autonat
+    }
+
+    pub fn kademlia(&self) -> Option<&Kademlia<TStore>> {
+        self.inner.kademlia.as_ref()
+    }
+
+    /// Returns the wrapped [`Kademlia`] behaviour, if any.
+    ///
+    /// Note that changes to its [`Mode`] are overridden on the next NAT status change.
+    pub fn kademlia_mut(&mut self) -> Option<&mut Kademlia<TStore>> {
+        self.inner.kademlia.as_mut()
+    }
+
+    pub fn relay_client(&self) -> Option<&relay::client::Behaviour> {
+        self.inner.relay_client.as_ref()
+    }
+
+    pub fn relay_client_mut(&mut self) -> Option<&mut relay::client::Behaviour> {
+        self.inner.relay_client.as_mut()
+    }
+
+    pub fn dcutr(&self) -> Option<&dcutr::Behaviour> {
+        self.inner.dcutr.as_ref()
+    }
+
+    pub fn dcutr_mut(&mut self) -> Option<&mut dcutr::Behaviour> {
+        self.inner.dcutr.as_mut()
+    }
+
+    fn apply_nat_status(&mut self, status: &NatStatus) {
+        let public = match status {
+            NatStatus::Public(_) => true,
+            NatStatus::Private => false,
+            NatStatus::Unknown => return,
+        };
+
+        if let Some(kademlia) = self.inner.kademlia.as_mut() {
+            kademlia.set_mode(if public { Mode::Server } else { Mode::Client });
+        }
+        if let Some(relay_client) = self.inner.relay_client.as_mut() {
+            relay_client.set_reservations_enabled(!public);
+        }
+        if let Some(dcutr) = self.inner.dcutr.as_mut() {
+            dcutr.set_enabled(!public);
+        }
+    }
+}
+
+impl<TStore> NetworkBehaviour for Behaviour<TStore>
+where
+    TStore: RecordStore + Send + 'static,
+{
+    // The handler derived for `Inner`, spelled out as `Inner` is private.
+    type ConnectionHandler = ConnectionHandlerSelect<
+        ConnectionHandlerSelect<
+            ConnectionHandlerSelect<
+                THandler<autonat::Behaviour>,
+                THandler<Toggle<Kademlia<TStore>>>,
+            >,
+            THandler<Toggle<relay::client::Behaviour>>,
+        >,
+        THandler<Toggle<dcutr::Behaviour>>,
+    >;
+    type OutEvent = Event;
+
+    fn handle_pending_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<(), ConnectionDenied> {
+        self.inner
+            .handle_pending_inbound_connection(connection_id, local_addr, remote_addr)
+    }
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner.handle_established_inbound_connection(
+            connection_id,
+            peer,
+            local_addr,
+            remote_addr,
+        )
+    }
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        maybe_peer: Option<PeerId>,
+        addresses: &[Multiaddr],
+        effective_role: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        self.inner.handle_pending_outbound_connection(
+            connection_id,
+            maybe_peer,
+            addresses,
+            effective_role,
+        )
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner
+            .handle_established_outbound_connection(connection_id, peer, addr, role_override)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm<Self::ConnectionHandler>) {
+        self.inner.on_swarm_event(event)
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        self.inner
+            .on_connection_handler_event(peer_id, connection_id, event)
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+        params: &mut impl PollParameters,
+    ) -> Poll<ToSwarm<Self::OutEvent, THandlerInEvent<Self>>> {
+        let event = match self.inner.poll(cx, params) {
+            Poll::Ready(event) => event,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        if let ToSwarm::GenerateEvent(Event::Autonat(autonat::Event::StatusChanged {
+            new, ..
+        })) = &event
+        {
+            self.apply_nat_status(new);
+        }
+
+        Poll::Ready(event)
+    }
+}
@@ -25,9 +25,12 @@ use crate::core::{
     transport::Boxed,
 };
 use crate::{
-    bandwidth::{BandwidthLogging, BandwidthSinks},
+    bandwidth::{
+        BandwidthLimiter, BandwidthLimits, BandwidthLogging, BandwidthSinks, LimitedConnection,
+    },
     Transport,
 };
+use futures::{AsyncRead, AsyncWrite};
 use libp2p_identity::PeerId;
 use std::sync::Arc;
 
@@ -89,6 +92,66 @@ pub trait TransportExt: Transport {
         .boxed();
         (transport, sinks)
     }
+
+    /// Adds a layer on the `Transport` that limits the aggregate upload and download rate of all
+    /// the connections created by it, e.g. to cap the bandwidth consumed on metered links.
+    ///
+    /// Reads and writes exceeding the capacity of the [`BandwidthLimits`] are delayed. The layer
+    /// wraps the raw connections and must thus be applied before upgrading the transport, so that
+    /// the overhead of the security and multiplexing protocols is accounted for as well.
+    /// Transports without raw connections to wrap, e.g. QUIC and WebRTC which handle security and
+    /// multiplexing themselves, cannot be limited with this layer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use libp2p_mplex as mplex;
+    /// use libp2p_noise as noise;
+    /// use libp2p_tcp as tcp;
+    /// use libp2p::{
+    ///     bandwidth::BandwidthLimits,
+    ///     core::upgrade,
+    ///     identity,
+    ///     TransportExt,
+    ///     Transport,
+    /// };
+    /// use std::num::NonZeroU64;
+    ///
+    /// let id_keys = identity::Keypair::generate_ed25519();
+    ///
+    /// // 1 MB/s upload with bursts of up to 256 KB.
+    /// let limits = BandwidthLimits::default().upload(
+    ///     NonZeroU64::new(1024 * 1024).unwrap(),
+    ///     NonZeroU64::new(256 * 1024).unwrap(),
+    /// );
+    ///
+    /// let transport = tcp::tokio::Transport::new(tcp::Config::default().nodelay(true))
+    ///     .with_bandwidth_limits(limits)
+    ///     .upgrade(upgrade::Version::V1)
+    ///     .authenticate(
+    ///         noise::NoiseAuthenticated::xx(&id_keys)
+    ///             .expect("Signing libp2p-noise static DH keypair failed."),
+    ///     )
+    ///     .multiplex(mplex::MplexConfig::new())
+    ///     .boxed();
+    /// ```
+    fn with_bandwidth_limits(
+        self,
+        limits: BandwidthLimits,
+    ) -> Boxed<LimitedConnection<Self::Output>>
+    where
+        Self: Sized + Send + Unpin + 'static,
+        Self::Dial: Send + 'static,
+        Self::ListenerUpgrade: Send + 'static,
+        Self::Error: Send + Sync,
+        Self::Output: AsyncRead + AsyncWrite,
+    {
+        let limiter = BandwidthLimiter::new(limits);
+        Transport::map(self, move |connection, _| {
+            LimitedConnection::new(connection, limiter.clone())
+        })
+        .boxed()
+    }
 }
 
 impl<TTransport> TransportExt for TTransport where TTransport: Transport {}
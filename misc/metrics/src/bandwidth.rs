@@ -0,0 +1,493 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::protocol_stack;
+use futures::{
+    future::MapOk,
+    io::{IoSlice, IoSliceMut},
+    prelude::*,
+    ready,
+};
+use instant::Instant;
+use libp2p_core::{
+    muxing::{StreamMuxer, StreamMuxerBox, StreamMuxerEvent},
+    transport::{DialOpts, ListenerId, TransportError, TransportEvent},
+    Multiaddr,
+};
+use libp2p_identity::PeerId;
+use prometheus_client::{
+    encoding::{EncodeLabelSet, EncodeLabelValue},
+    metrics::{
+        counter::Counter,
+        family::Family,
+        histogram::{exponential_buckets, Histogram},
+    },
+    registry::{Registry, Unit},
+};
+use std::{
+    convert::TryFrom as _,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Wraps around a [`Transport`](libp2p_core::Transport) and records the bytes sent and received
+/// on each connection as well as the duration of each connection.
+///
+/// Both metrics are labeled with the protocol stack of the remote address of the connection, e.g.
+/// `/ip4/tcp` or `/ip4/udp/quic-v1`. Connections through a relay carry `p2p-circuit` in their
+/// protocol stack, e.g. `/ip4/tcp/p2p/p2p-circuit/p2p`, which allows to tell traffic flowing over
+/// relays apart from traffic flowing over direct connections.
+///
+/// ```
+/// use libp2p_core::{transport::MemoryTransport, upgrade::Version, Transport};
+/// use libp2p_metrics::BandwidthTransport;
+/// use prometheus_client::registry::Registry;
+/// # let keypair = libp2p_identity::Keypair::generate_ed25519();
+///
+/// let mut registry = Registry::default();
+/// let transport = BandwidthTransport::new(
+///     MemoryTransport::default()
+///         .upgrade(Version::V1)
+///         .authenticate(libp2p_noise::NoiseAuthenticated::xx(&keypair).unwrap())
+///         .multiplex(libp2p_yamux::YamuxConfig::default())
+///         .boxed(),
+///     &mut registry,
+/// );
+/// ```
+#[derive(Debug, Clone)]
+#[pin_project::pin_project]
+pub struct BandwidthTransport<T> {
+    #[pin]
+    transport: T,
+    metrics: Metrics,
+}
+
+impl<T> BandwidthTransport<T> {
+    /// Wraps the given transport, registering its metrics with the given [`Registry`].
+    pub fn new(transport: T, registry: &mut Registry) -> Self {
+        Self {
+            transport,
+            metrics: Metrics::new(registry),
+        }
+    }
+}
+
+type MapMuxer = Box<dyn FnOnce((PeerId, StreamMuxerBox)) -> (PeerId, StreamMuxerBox) + Send>;
+
+impl<T> libp2p_core::Transport for BandwidthTransport<T>
+where
+    T: libp2p_core::Transport<Output = (PeerId, StreamMuxerBox)>,
+{
+    type Output = (PeerId, StreamMuxerBox);
+    type Error = T::Error;
+    type ListenerUpgrade = MapOk<T::ListenerUpgrade, MapMuxer>;
+    type Dial = MapOk<T::Dial, MapMuxer>;
+
+    fn listen_on(&mut self, addr: Multiaddr) -> Result<ListenerId, TransportError<Self::Error>> {
+        self.transport.listen_on(addr)
+    }
+
+    fn remove_listener(&mut self, id: ListenerId) -> bool {
+        self.transport.remove_listener(id)
+    }
+
+    fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let map = self.metrics.map_muxer(&addr);
+        Ok(self.transport.dial(addr)?.map_ok(map))
+    }
+
+    fn dial_as_listener(
+        &mut self,
+        addr: Multiaddr,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let map = self.metrics.map_muxer(&addr);
+        Ok(self.transport.dial_as_listener(addr)?.map_ok(map))
+    }
+
+    fn dial_with_opts(
+        &mut self,
+        addr: Multiaddr,
+        opts: DialOpts,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let map = self.metrics.map_muxer(&addr);
+        Ok(self.transport.dial_with_opts(addr, opts)?.map_ok(map))
+    }
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<TransportEvent<Self::ListenerUpgrade, Self::Error>> {
+        let this = self.project();
+        match ready!(this.transport.poll(cx)) {
+            TransportEvent::Incoming {
+                listener_id,
+                upgrade,
+                local_addr,
+                send_back_addr,
+            } => {
+                let map = this.metrics.map_muxer(&send_back_addr);
+                Poll::Ready(TransportEvent::Incoming {
+                    listener_id,
+                    upgrade: upgrade.map_ok(map),
+                    local_addr,
+                    send_back_addr,
+                })
+            }
+            other => Poll::Ready(
+                other.map_upgrade(|_| unreachable!("only `Incoming` events carry an upgrade")),
+            ),
+        }
+    }
+
+    fn address_translation(&self, listen: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
+        self.transport.address_translation(listen, observed)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Metrics {
+    bandwidth: Family<BandwidthLabels, Counter>,
+    connection_duration: Family<ConnectionLabels, Histogram>,
+}
+
+impl Metrics {
+    fn new(registry: &mut Registry) -> Self {
+        let sub_registry = registry.sub_registry_with_prefix("libp2p");
+
+        let bandwidth = Family::default();
+        sub_registry.register_with_unit(
+            "bandwidth",
+            "Bytes sent and received per direction and protocol stack",
+            Unit::Bytes,
+            bandwidth.clone(),
+        );
+
+        let connection_duration =
+            Family::new_with_constructor(create_connection_duration_histogram as fn() -> Histogram);
+        sub_registry.register_with_unit(
+            "connection_duration",
+            "Duration of closed connections per protocol stack",
+            Unit::Seconds,
+            connection_duration.clone(),
+        );
+
+        Self {
+            bandwidth,
+            connection_duration,
+        }
+    }
+
+    /// Returns a function wrapping the muxer of a connection to the given address.
+    fn map_muxer(&self, addr: &Multiaddr) -> MapMuxer {
+        let protocols = protocol_stack::as_string(addr);
+        let inbound = self
+            .bandwidth
+            .get_or_create(&BandwidthLabels {
+                protocols: protocols.clone(),
+                direction: Direction::Inbound,
+            })
+            .clone();
+        let outbound = self
+            .bandwidth
+            .get_or_create(&BandwidthLabels {
+                protocols: protocols.clone(),
+                direction: Direction::Outbound,
+            })
+            .clone();
+        let duration = self
+            .connection_duration
+            .get_or_create(&ConnectionLabels { protocols })
+            .clone();
+
+        Box::new(move |(peer_id, muxer)| {
+            let muxer = Muxer {
+                inner: muxer,
+                metrics: ConnectionMetrics {
+                    inbound,
+                    outbound,
+                    duration,
+                    established: Instant::now(),
+                },
+            };
+            (peer_id, StreamMuxerBox::new(muxer))
+        })
+    }
+}
+
+#[derive(EncodeLabelSet, Hash, Clone, Eq, PartialEq, Debug)]
+struct BandwidthLabels {
+    protocols: String,
+    direction: Direction,
+}
+
+#[derive(EncodeLabelValue, Hash, Clone, Eq, PartialEq, Debug)]
+enum Direction {
+    Inbound,
+    Outbound,
+}
+
+#[derive(EncodeLabelSet, Hash, Clone, Eq, PartialEq, Debug)]
+struct ConnectionLabels {
+    protocols: String,
+}
+
+fn create_connection_duration_histogram() -> Histogram {
+    Histogram::new(exponential_buckets(1.0, 2.0, 20))
+}
+
+/// The metrics of a single connection.
+///
+/// Records the duration of the connection once dropped.
+struct ConnectionMetrics {
+    inbound: Counter,
+    outbound: Counter,
+    duration: Histogram,
+    established: Instant,
+}
+
+impl Drop for ConnectionMetrics {
+    fn drop(&mut self) {
+        self.duration
+            .observe(self.established.elapsed().as_secs_f64());
+    }
+}
+
+/// Wraps around a [`StreamMuxer`] and counts the bytes that go through all its streams.
+#[pin_project::pin_project]
+struct Muxer<SMInner> {
+    #[pin]
+    inner: SMInner,
+    metrics: ConnectionMetrics,
+}
+
+impl<SMInner> StreamMuxer for Muxer<SMInner>
+where
+    SMInner: StreamMuxer,
+{
+    type Substream = InstrumentedStream<SMInner::Substream>;
+    type Error = SMInner::Error;
+
+    fn poll_inbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let this = self.project();
+        let inner = ready!(this.inner.poll_inbound(cx)?);
+        Poll::Ready(Ok(InstrumentedStream::new(inner, this.metrics)))
+    }
+
+    fn poll_outbound(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        let this = self.project();
+        let inner = ready!(this.inner.poll_outbound(cx)?);
+        Poll::Ready(Ok(InstrumentedStream::new(inner, this.metrics)))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_close(cx)
+    }
+
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+        self.project().inner.poll(cx)
+    }
+}
+
+/// Wraps around an [`AsyncRead`] + [`AsyncWrite`] and counts the bytes that go through it.
+#[pin_project::pin_project]
+struct InstrumentedStream<SMInner> {
+    #[pin]
+    inner: SMInner,
+    inbound: Counter,
+    outbound: Counter,
+}
+
+impl<SMInner> InstrumentedStream<SMInner> {
+    fn new(inner: SMInner, metrics: &ConnectionMetrics) -> Self {
+        Self {
+            inner,
+            inbound: metrics.inbound.clone(),
+            outbound: metrics.outbound.clone(),
+        }
+    }
+}
+
+impl<SMInner: AsyncRead> AsyncRead for InstrumentedStream<SMInner> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let num_bytes = ready!(this.inner.poll_read(cx, buf))?;
+        this.inbound
+            .inc_by(u64::try_from(num_bytes).unwrap_or(u64::MAX));
+        Poll::Ready(Ok(num_bytes))
+    }
+
+    fn poll_read_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let num_bytes = ready!(this.inner.poll_read_vectored(cx, bufs))?;
+        this.inbound
+            .inc_by(u64::try_from(num_bytes).unwrap_or(u64::MAX));
+        Poll::Ready(Ok(num_bytes))
+    }
+}
+
+impl<SMInner: AsyncWrite> AsyncWrite for InstrumentedStream<SMInner> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let num_bytes = ready!(this.inner.poll_write(cx, buf))?;
+        this.outbound
+            .inc_by(u64::try_from(num_bytes).unwrap_or(u64::MAX));
+        Poll::Ready(Ok(num_bytes))
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.project();
+        let num_bytes = ready!(this.inner.poll_write_vectored(cx, bufs))?;
+        this.outbound
+            .inc_by(u64::try_from(num_bytes).unwrap_or(u64::MAX));
+        Poll::Ready(Ok(num_bytes))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use libp2p_core::{upgrade::Version, Transport as _};
+    use libp2p_identity::Keypair;
+    use libp2p_swarm::{keep_alive, NetworkBehaviour, Swarm, SwarmBuilder, SwarmEvent};
+
+    #[derive(NetworkBehaviour)]
+    #[behaviour(prelude = "libp2p_swarm::derive_prelude")]
+    struct Behaviour {
+        keep_alive: keep_alive::Behaviour,
+        ping: libp2p_ping::Behaviour,
+    }
+
+    fn build_swarm(registry: &mut Registry) -> Swarm<Behaviour> {
+        let keypair = Keypair::generate_ed25519();
+        let transport = BandwidthTransport::new(
+            libp2p_tcp::async_io::Transport::default()
+                .upgrade(Version::V1)
+                .authenticate(libp2p_noise::NoiseAuthenticated::xx(&keypair).unwrap())
+                .multiplex(libp2p_yamux::YamuxConfig::default())
+                .boxed(),
+            registry,
+        )
+        .boxed();
+
+        SwarmBuilder::without_executor(
+            transport,
+            Behaviour {
+                keep_alive: keep_alive::Behaviour,
+                ping: libp2p_ping::Behaviour::default(),
+            },
+            keypair.public().to_peer_id(),
+        )
+        .build()
+    }
+
+    #[test]
+    fn records_bandwidth_and_connection_duration() {
+        let mut registry = Registry::default();
+        let mut listener = build_swarm(&mut registry);
+        let mut dialer = build_swarm(&mut Registry::default());
+
+        listener
+            .listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .unwrap();
+        let listen_addr = block_on(async {
+            loop {
+                if let SwarmEvent::NewListenAddr { address, .. } = listener.select_next_some().await
+                {
+                    break address;
+                }
+            }
+        });
+        dialer.dial(listen_addr).unwrap();
+
+        block_on(async {
+            loop {
+                futures::select! {
+                    event = listener.select_next_some() => {
+                        if let SwarmEvent::Behaviour(BehaviourEvent::Ping(libp2p_ping::Event {
+                            result: Ok(libp2p_ping::Success::Ping { .. }),
+                            ..
+                        })) = event
+                        {
+                            break;
+                        }
+                    }
+                    _ = dialer.select_next_some() => {}
+                }
+            }
+        });
+        drop(dialer);
+        drop(listener);
+
+        let mut encoded = String::new();
+        prometheus_client::encoding::text::encode(&mut encoded, &registry).unwrap();
+
+        for direction in ["Inbound", "Outbound"] {
+            let line = encoded
+                .lines()
+                .find(|line| {
+                    line.starts_with("libp2p_bandwidth_bytes_total{")
+                        && line.contains("protocols=\"/ip4/tcp\"")
+                        && line.contains(&format!("direction=\"{direction}\""))
+                })
+                .unwrap_or_else(|| panic!("no {direction} bandwidth in {encoded}"));
+            let bytes: u64 = line.rsplit(' ').next().unwrap().parse().unwrap();
+            assert!(bytes > 0, "{line}");
+        }
+        assert!(
+            encoded.contains("libp2p_connection_duration_seconds_count{protocols=\"/ip4/tcp\"} 1"),
+            "{encoded}"
+        );
+    }
+}
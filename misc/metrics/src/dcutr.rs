@@ -18,13 +18,26 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+use instant::Instant;
+use libp2p_identity::PeerId;
 use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
 use prometheus_client::metrics::counter::Counter;
 use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
 use prometheus_client::registry::Registry;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Duration after which a direct connection upgrade without outcome is forgotten, e.g. when the
+/// relayed connection closed during the upgrade.
+const UPGRADE_TIMEOUT: Duration = Duration::from_secs(60);
 
 pub struct Metrics {
     events: Family<EventLabels, Counter>,
+    upgrade_duration: Histogram,
+    /// The start of the ongoing direct connection upgrades.
+    upgrades: Mutex<HashMap<PeerId, Instant>>,
 }
 
 impl Metrics {
@@ -38,7 +51,18 @@ impl Metrics {
             events.clone(),
         );
 
-        Self { events }
+        let upgrade_duration = Histogram::new(exponential_buckets(0.01, 2.0, 12));
+        sub_registry.register(
+            "direct_connection_upgrade_duration_seconds",
+            "Time from initiating a direct connection upgrade of a relayed connection until it succeeded",
+            upgrade_duration.clone(),
+        );
+
+        Self {
+            events,
+            upgrade_duration,
+            upgrades: Default::default(),
+        }
     }
 }
 
@@ -79,10 +103,112 @@ impl From<&libp2p_dcutr::Event> for EventType {
 
 impl super::Recorder<libp2p_dcutr::Event> for Metrics {
     fn record(&self, event: &libp2p_dcutr::Event) {
+        self.record_at(event, Instant::now())
+    }
+}
+
+impl Metrics {
+    fn record_at(&self, event: &libp2p_dcutr::Event, now: Instant) {
         self.events
             .get_or_create(&EventLabels {
                 event: event.into(),
             })
             .inc();
+
+        let mut upgrades = self.upgrades.lock().unwrap();
+        upgrades.retain(|_, started| now.saturating_duration_since(*started) < UPGRADE_TIMEOUT);
+        match event {
+            libp2p_dcutr::Event::InitiatedDirectConnectionUpgrade { remote_peer_id, .. }
+            | libp2p_dcutr::Event::RemoteInitiatedDirectConnectionUpgrade {
+                remote_peer_id, ..
+            } => {
+                upgrades.insert(*remote_peer_id, now);
+            }
+            libp2p_dcutr::Event::DirectConnectionUpgradeSucceeded { remote_peer_id } => {
+                if let Some(started) = upgrades.remove(remote_peer_id) {
+                    self.upgrade_duration
+                        .observe(now.saturating_duration_since(started).as_secs_f64());
+                }
+            }
+            libp2p_dcutr::Event::DirectConnectionUpgradeFailed { remote_peer_id, .. } => {
+                upgrades.remove(remote_peer_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_dcutr::{Error, Event};
+
+    fn initiated(remote_peer_id: PeerId) -> Event {
+        Event::InitiatedDirectConnectionUpgrade {
+            remote_peer_id,
+            local_relayed_addr: "/ip4/127.0.0.1/tcp/4001/p2p-circuit".parse().unwrap(),
+        }
+    }
+
+    fn upgrade_durations(registry: &Registry) -> String {
+        let mut encoded = String::new();
+        prometheus_client::encoding::text::encode(&mut encoded, registry).unwrap();
+        encoded
+            .lines()
+            .find(|line| line.starts_with("dcutr_direct_connection_upgrade_duration_seconds_count"))
+            .unwrap()
+            .to_owned()
+    }
+
+    #[test]
+    fn upgrades_are_removed_on_every_outcome() {
+        let mut registry = Registry::default();
+        let metrics = Metrics::new(&mut registry);
+        let (succeeded, failed) = (PeerId::random(), PeerId::random());
+        let now = Instant::now();
+
+        metrics.record_at(&initiated(succeeded), now);
+        metrics.record_at(&initiated(failed), now);
+        assert_eq!(metrics.upgrades.lock().unwrap().len(), 2);
+
+        metrics.record_at(
+            &Event::DirectConnectionUpgradeSucceeded {
+                remote_peer_id: succeeded,
+            },
+            now + Duration::from_secs(1),
+        );
+        metrics.record_at(
+            &Event::DirectConnectionUpgradeFailed {
+                remote_peer_id: failed,
+                error: Error::Dial,
+            },
+            now + Duration::from_secs(1),
+        );
+
+        assert!(metrics.upgrades.lock().unwrap().is_empty());
+        assert!(upgrade_durations(&registry).ends_with(" 1"));
+    }
+
+    #[test]
+    fn abandoned_upgrades_are_forgotten() {
+        let mut registry = Registry::default();
+        let metrics = Metrics::new(&mut registry);
+        let (abandoned, other) = (PeerId::random(), PeerId::random());
+        let now = Instant::now();
+
+        metrics.record_at(&initiated(abandoned), now);
+        metrics.record_at(&initiated(other), now + UPGRADE_TIMEOUT);
+        assert_eq!(
+            metrics.upgrades.lock().unwrap().keys().collect::<Vec<_>>(),
+            [&other]
+        );
+
+        // A late outcome of the abandoned upgrade is not observed.
+        metrics.record_at(
+            &Event::DirectConnectionUpgradeSucceeded {
+                remote_peer_id: abandoned,
+            },
+            now + UPGRADE_TIMEOUT,
+        );
+        assert!(upgrade_durations(&registry).ends_with(" 0"));
     }
 }
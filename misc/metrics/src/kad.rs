@@ -273,6 +273,10 @@ enum QueryType {
     GetRecord,
     PutRecord,
     RepublishRecord,
+    RepairRecord,
+    GetRecordFrom,
+    PutRecordAt,
+    FindAndDial,
 }
 
 impl From<&libp2p_kad::QueryResult> for QueryResult {
@@ -302,6 +306,18 @@ impl From<&libp2p_kad::QueryResult> for QueryResult {
             libp2p_kad::QueryResult::RepublishRecord(_) => QueryResult {
                 r#type: QueryType::RepublishRecord,
             },
+            libp2p_kad::QueryResult::RepairRecord(_) => QueryResult {
+                r#type: QueryType::RepairRecord,
+            },
+            libp2p_kad::QueryResult::GetRecordFrom(_) => QueryResult {
+                r#type: QueryType::GetRecordFrom,
+            },
+            libp2p_kad::QueryResult::PutRecordAt(_) => QueryResult {
+                r#type: QueryType::PutRecordAt,
+            },
+            libp2p_kad::QueryResult::FindAndDial(_) => QueryResult {
+                r#type: QueryType::FindAndDial,
+            },
         }
     }
 }
@@ -27,6 +27,7 @@
 
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
+mod bandwidth;
 #[cfg(feature = "dcutr")]
 mod dcutr;
 #[cfg(feature = "gossipsub")]
@@ -41,8 +42,11 @@ mod ping;
 mod protocol_stack;
 #[cfg(feature = "relay")]
 mod relay;
+#[cfg(feature = "relay")]
+mod relay_client;
 mod swarm;
 
+pub use bandwidth::BandwidthTransport;
 use prometheus_client::registry::Registry;
 
 /// Set of Swarm and protocol metrics derived from emitted events.
@@ -60,6 +64,8 @@ pub struct Metrics {
     ping: ping::Metrics,
     #[cfg(feature = "relay")]
     relay: relay::Metrics,
+    #[cfg(feature = "relay")]
+    relay_client: relay_client::Metrics,
     swarm: swarm::Metrics,
 }
 
@@ -88,6 +94,8 @@ impl Metrics {
             ping: ping::Metrics::new(sub_registry),
             #[cfg(feature = "relay")]
             relay: relay::Metrics::new(sub_registry),
+            #[cfg(feature = "relay")]
+            relay_client: relay_client::Metrics::new(sub_registry),
             swarm: swarm::Metrics::new(sub_registry),
         }
     }
@@ -142,6 +150,13 @@ impl Recorder<libp2p_relay::Event> for Metrics {
     }
 }
 
+#[cfg(feature = "relay")]
+impl Recorder<libp2p_relay::client::Event> for Metrics {
+    fn record(&self, event: &libp2p_relay::client::Event) {
+        self.relay_client.record(event)
+    }
+}
+
 impl<TBvEv, THandleErr> Recorder<libp2p_swarm::SwarmEvent<TBvEv, THandleErr>> for Metrics {
     fn record(&self, event: &libp2p_swarm::SwarmEvent<TBvEv, THandleErr>) {
         self.swarm.record(event);
@@ -38,6 +38,9 @@ impl From<&libp2p_ping::Failure> for FailureLabels {
             libp2p_ping::Failure::Unsupported => FailureLabels {
                 reason: Failure::Unsupported,
             },
+            libp2p_ping::Failure::Mismatch => FailureLabels {
+                reason: Failure::Mismatch,
+            },
             libp2p_ping::Failure::Other { .. } => FailureLabels {
                 reason: Failure::Other,
             },
@@ -49,6 +52,7 @@ impl From<&libp2p_ping::Failure> for FailureLabels {
 enum Failure {
     Timeout,
     Unsupported,
+    Mismatch,
     Other,
 }
 
@@ -21,10 +21,12 @@
 use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
 use prometheus_client::metrics::counter::Counter;
 use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
 use prometheus_client::registry::Registry;
 
 pub struct Metrics {
     events: Family<EventLabels, Counter>,
+    message_latency: Family<MessageLabels, Histogram>,
 }
 
 impl Metrics {
@@ -38,7 +40,18 @@ impl Metrics {
             events.clone(),
         );
 
-        Self { events }
+        let message_latency: Family<_, _> =
+            Family::new_with_constructor(|| Histogram::new(exponential_buckets(0.001, 2.0, 14)));
+        sub_registry.register(
+            "message_latency_seconds",
+            "Latency of responses to HOP and STOP requests handled by the relay",
+            message_latency.clone(),
+        );
+
+        Self {
+            events,
+            message_latency,
+        }
     }
 }
 
@@ -54,6 +67,7 @@ enum EventType {
     ReservationReqDenied,
     ReservationReqDenyFailed,
     ReservationTimedOut,
+    ReservationRestored,
     CircuitReqReceiveFailed,
     CircuitReqDenied,
     CircuitReqDenyFailed,
@@ -61,6 +75,37 @@ enum EventType {
     CircuitReqAccepted,
     CircuitReqAcceptFailed,
     CircuitClosed,
+    Message,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct MessageLabels {
+    response: Response,
+    outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelValue)]
+enum Response {
+    Reservation,
+    Stop,
+    Circuit,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelValue)]
+enum Outcome {
+    Accepted,
+    Denied,
+    Failed,
+}
+
+impl From<libp2p_relay::MessageOutcome> for Outcome {
+    fn from(outcome: libp2p_relay::MessageOutcome) -> Self {
+        match outcome {
+            libp2p_relay::MessageOutcome::Accepted => Outcome::Accepted,
+            libp2p_relay::MessageOutcome::Denied => Outcome::Denied,
+            libp2p_relay::MessageOutcome::Failed => Outcome::Failed,
+        }
+    }
 }
 
 impl From<&libp2p_relay::Event> for EventType {
@@ -75,6 +120,7 @@ impl From<&libp2p_relay::Event> for EventType {
                 EventType::ReservationReqDenyFailed
             }
             libp2p_relay::Event::ReservationTimedOut { .. } => EventType::ReservationTimedOut,
+            libp2p_relay::Event::ReservationRestored { .. } => EventType::ReservationRestored,
             libp2p_relay::Event::CircuitReqReceiveFailed { .. } => {
                 EventType::CircuitReqReceiveFailed
             }
@@ -86,6 +132,7 @@ impl From<&libp2p_relay::Event> for EventType {
             libp2p_relay::Event::CircuitReqAccepted { .. } => EventType::CircuitReqAccepted,
             libp2p_relay::Event::CircuitReqAcceptFailed { .. } => EventType::CircuitReqAcceptFailed,
             libp2p_relay::Event::CircuitClosed { .. } => EventType::CircuitClosed,
+            libp2p_relay::Event::Message { .. } => EventType::Message,
         }
     }
 }
@@ -97,5 +144,27 @@ impl super::Recorder<libp2p_relay::Event> for Metrics {
                 event: event.into(),
             })
             .inc();
+
+        if let libp2p_relay::Event::Message { message, .. } = event {
+            let (response, outcome, latency) = match *message {
+                libp2p_relay::ProtocolMessage::ReservationRes { outcome, latency } => {
+                    (Response::Reservation, outcome, latency)
+                }
+                libp2p_relay::ProtocolMessage::StopRes {
+                    outcome, latency, ..
+                } => (Response::Stop, outcome, latency),
+                libp2p_relay::ProtocolMessage::CircuitRes {
+                    outcome, latency, ..
+                } => (Response::Circuit, outcome, latency),
+                libp2p_relay::ProtocolMessage::ReservationReq { .. }
+                | libp2p_relay::ProtocolMessage::CircuitReq { .. } => return,
+            };
+            self.message_latency
+                .get_or_create(&MessageLabels {
+                    response,
+                    outcome: outcome.into(),
+                })
+                .observe(latency.as_secs_f64());
+        }
     }
 }
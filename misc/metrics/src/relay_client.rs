@@ -0,0 +1,204 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use libp2p_core::upgrade::UpgradeError;
+use libp2p_relay::outbound::hop::{CircuitFailedReason, ReservationFailedReason};
+use libp2p_swarm::handler::ConnectionHandlerUpgrErr;
+use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::registry::Registry;
+
+/// Metrics of the relay client.
+///
+/// The bytes sent and received over circuits are recorded by the
+/// [`BandwidthTransport`](crate::BandwidthTransport), labeled by a protocol stack containing
+/// `p2p-circuit`.
+pub struct Metrics {
+    reservations: Family<ReservationLabels, Counter>,
+    circuits: Family<CircuitLabels, Counter>,
+}
+
+impl Metrics {
+    pub fn new(registry: &mut Registry) -> Self {
+        let sub_registry = registry.sub_registry_with_prefix("relay_client");
+
+        let reservations = Family::default();
+        sub_registry.register(
+            "reservations",
+            "Responses to reservations requested by the relay client",
+            reservations.clone(),
+        );
+
+        let circuits = Family::default();
+        sub_registry.register(
+            "circuits",
+            "Inbound and outbound circuits of the relay client",
+            circuits.clone(),
+        );
+
+        Self {
+            reservations,
+            circuits,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct ReservationLabels {
+    kind: ReservationKind,
+    outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelValue)]
+enum ReservationKind {
+    New,
+    Renewal,
+}
+
+impl From<bool> for ReservationKind {
+    fn from(renewal: bool) -> Self {
+        if renewal {
+            ReservationKind::Renewal
+        } else {
+            ReservationKind::New
+        }
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct CircuitLabels {
+    direction: Direction,
+    outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelValue)]
+enum Direction {
+    Inbound,
+    Outbound,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelValue)]
+enum Outcome {
+    Accepted,
+    Denied,
+    Failed,
+}
+
+impl From<&ConnectionHandlerUpgrErr<ReservationFailedReason>> for Outcome {
+    fn from(error: &ConnectionHandlerUpgrErr<ReservationFailedReason>) -> Self {
+        match error {
+            ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Apply(
+                ReservationFailedReason::Refused | ReservationFailedReason::ResourceLimitExceeded,
+            )) => Outcome::Denied,
+            _ => Outcome::Failed,
+        }
+    }
+}
+
+impl From<&ConnectionHandlerUpgrErr<CircuitFailedReason>> for Outcome {
+    fn from(error: &ConnectionHandlerUpgrErr<CircuitFailedReason>) -> Self {
+        match error {
+            // Only a status denying the circuit is a denial, failing to connect to the destination
+            // or it lacking a reservation are failures.
+            ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Apply(
+                CircuitFailedReason::PermissionDenied | CircuitFailedReason::ResourceLimitExceeded,
+            )) => Outcome::Denied,
+            _ => Outcome::Failed,
+        }
+    }
+}
+
+impl super::Recorder<libp2p_relay::client::Event> for Metrics {
+    fn record(&self, event: &libp2p_relay::client::Event) {
+        use libp2p_relay::client::Event;
+
+        let (direction, outcome) = match event {
+            Event::ReservationReqAccepted { renewal, .. } => {
+                self.reservations
+                    .get_or_create(&ReservationLabels {
+                        kind: (*renewal).into(),
+                        outcome: Outcome::Accepted,
+                    })
+                    .inc();
+                return;
+            }
+            Event::ReservationReqFailed { renewal, error, .. } => {
+                self.reservations
+                    .get_or_create(&ReservationLabels {
+                        kind: (*renewal).into(),
+                        outcome: error.into(),
+                    })
+                    .inc();
+                return;
+            }
+            Event::OutboundCircuitEstablished { .. } => (Direction::Outbound, Outcome::Accepted),
+            Event::OutboundCircuitReqFailed { error, .. } => (Direction::Outbound, error.into()),
+            Event::InboundCircuitEstablished { .. } => (Direction::Inbound, Outcome::Accepted),
+            Event::InboundCircuitReqDenied { .. } => (Direction::Inbound, Outcome::Denied),
+            Event::InboundCircuitReqFailed { .. } | Event::InboundCircuitReqDenyFailed { .. } => {
+                (Direction::Inbound, Outcome::Failed)
+            }
+            Event::CircuitStatsReported { .. } => return,
+        };
+
+        self.circuits
+            .get_or_create(&CircuitLabels { direction, outcome })
+            .inc();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome<E>(error: E) -> Outcome
+    where
+        for<'a> Outcome: From<&'a ConnectionHandlerUpgrErr<E>>,
+    {
+        (&ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Apply(error))).into()
+    }
+
+    #[test]
+    fn only_refusals_are_denials() {
+        assert_eq!(outcome(ReservationFailedReason::Refused), Outcome::Denied);
+        assert_eq!(
+            outcome(ReservationFailedReason::ResourceLimitExceeded),
+            Outcome::Denied
+        );
+        assert_eq!(
+            outcome(CircuitFailedReason::PermissionDenied),
+            Outcome::Denied
+        );
+        assert_eq!(
+            outcome(CircuitFailedReason::ResourceLimitExceeded),
+            Outcome::Denied
+        );
+        assert_eq!(
+            outcome(CircuitFailedReason::ConnectionFailed),
+            Outcome::Failed
+        );
+        assert_eq!(outcome(CircuitFailedReason::NoReservation), Outcome::Failed);
+        assert_eq!(
+            Outcome::from(&ConnectionHandlerUpgrErr::<CircuitFailedReason>::Timeout),
+            Outcome::Failed
+        );
+    }
+}
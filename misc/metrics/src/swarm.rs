@@ -19,6 +19,7 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::protocol_stack;
+use libp2p_core::transport::TransportKind;
 use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
 use prometheus_client::metrics::counter::Counter;
 use prometheus_client::metrics::family::Family;
@@ -163,6 +164,7 @@ impl<TBvEv, THandleErr> super::Recorder<libp2p_swarm::SwarmEvent<TBvEv, THandleE
                 let labels = ConnectionEstablishedLabels {
                     role: endpoint.into(),
                     protocols: protocol_stack::as_string(endpoint.get_remote_address()),
+                    transport: endpoint.transport().into(),
                 };
                 self.connections_established.get_or_create(&labels).inc();
                 self.connections_establishment_duration
@@ -174,6 +176,7 @@ impl<TBvEv, THandleErr> super::Recorder<libp2p_swarm::SwarmEvent<TBvEv, THandleE
                     .get_or_create(&ConnectionClosedLabels {
                         role: endpoint.into(),
                         protocols: protocol_stack::as_string(endpoint.get_remote_address()),
+                        transport: endpoint.transport().into(),
                     })
                     .inc();
             }
@@ -186,6 +189,7 @@ impl<TBvEv, THandleErr> super::Recorder<libp2p_swarm::SwarmEvent<TBvEv, THandleE
             }
             libp2p_swarm::SwarmEvent::IncomingConnectionError {
                 error,
+                local_addr,
                 send_back_addr,
                 ..
             } => {
@@ -193,60 +197,77 @@ impl<TBvEv, THandleErr> super::Recorder<libp2p_swarm::SwarmEvent<TBvEv, THandleE
                     .get_or_create(&IncomingConnectionErrorLabels {
                         error: error.into(),
                         protocols: protocol_stack::as_string(send_back_addr),
+                        transport: TransportKind::from_address(local_addr).into(),
                     })
                     .inc();
             }
-            libp2p_swarm::SwarmEvent::OutgoingConnectionError { error, peer_id } => {
+            libp2p_swarm::SwarmEvent::OutgoingConnectionError { error, peer_id, .. } => {
                 let peer = match peer_id {
                     Some(_) => PeerStatus::Known,
                     None => PeerStatus::Unknown,
                 };
 
-                let record = |error| {
+                // Errors not tied to a connection attempt are recorded without a transport.
+                let transport = error
+                    .transports()
+                    .first()
+                    .map_or(Transport::Unknown, |kind| (*kind).into());
+                let record = |error, transport| {
                     self.outgoing_connection_error
-                        .get_or_create(&OutgoingConnectionErrorLabels { peer, error })
+                        .get_or_create(&OutgoingConnectionErrorLabels {
+                            peer,
+                            error,
+                            transport,
+                        })
                         .inc();
                 };
+                let record_error = |error| record(error, transport);
 
                 match error {
                     libp2p_swarm::DialError::Transport(errors) => {
-                        for (_multiaddr, error) in errors {
+                        for (multiaddr, error) in errors {
+                            let transport = TransportKind::from_address(multiaddr).into();
                             match error {
                                 libp2p_core::transport::TransportError::MultiaddrNotSupported(
                                     _,
-                                ) => {
-                                    record(OutgoingConnectionError::TransportMultiaddrNotSupported)
-                                }
+                                ) => record(
+                                    OutgoingConnectionError::TransportMultiaddrNotSupported,
+                                    transport,
+                                ),
                                 libp2p_core::transport::TransportError::Other(_) => {
-                                    record(OutgoingConnectionError::TransportOther)
+                                    record(OutgoingConnectionError::TransportOther, transport)
                                 }
                             };
                         }
                     }
                     #[allow(deprecated)]
-                    libp2p_swarm::DialError::Banned => record(OutgoingConnectionError::Banned),
+                    libp2p_swarm::DialError::Banned => {
+                        record_error(OutgoingConnectionError::Banned)
+                    }
                     #[allow(deprecated)]
                     libp2p_swarm::DialError::ConnectionLimit(_) => {
-                        record(OutgoingConnectionError::ConnectionLimit)
+                        record_error(OutgoingConnectionError::ConnectionLimit)
                     }
                     libp2p_swarm::DialError::LocalPeerId { .. } => {
-                        record(OutgoingConnectionError::LocalPeerId)
+                        record_error(OutgoingConnectionError::LocalPeerId)
                     }
                     libp2p_swarm::DialError::NoAddresses => {
-                        record(OutgoingConnectionError::NoAddresses)
+                        record_error(OutgoingConnectionError::NoAddresses)
                     }
                     libp2p_swarm::DialError::DialPeerConditionFalse(_) => {
-                        record(OutgoingConnectionError::DialPeerConditionFalse)
+                        record_error(OutgoingConnectionError::DialPeerConditionFalse)
+                    }
+                    libp2p_swarm::DialError::Aborted => {
+                        record_error(OutgoingConnectionError::Aborted)
                     }
-                    libp2p_swarm::DialError::Aborted => record(OutgoingConnectionError::Aborted),
                     libp2p_swarm::DialError::InvalidPeerId { .. } => {
-                        record(OutgoingConnectionError::InvalidPeerId)
+                        record_error(OutgoingConnectionError::InvalidPeerId)
                     }
                     libp2p_swarm::DialError::WrongPeerId { .. } => {
-                        record(OutgoingConnectionError::WrongPeerId)
+                        record_error(OutgoingConnectionError::WrongPeerId)
                     }
                     libp2p_swarm::DialError::Denied { .. } => {
-                        record(OutgoingConnectionError::Denied)
+                        record_error(OutgoingConnectionError::Denied)
                     }
                 };
             }
@@ -287,6 +308,8 @@ impl<TBvEv, THandleErr> super::Recorder<libp2p_swarm::SwarmEvent<TBvEv, THandleE
             libp2p_swarm::SwarmEvent::Dialing(_) => {
                 self.dial_attempt.inc();
             }
+            libp2p_swarm::SwarmEvent::DialAddresses { .. } => {}
+            libp2p_swarm::SwarmEvent::NodeStatus(_) => {}
         }
     }
 }
@@ -295,6 +318,7 @@ impl<TBvEv, THandleErr> super::Recorder<libp2p_swarm::SwarmEvent<TBvEv, THandleE
 struct ConnectionEstablishedLabels {
     role: Role,
     protocols: String,
+    transport: Transport,
 }
 
 type ConnectionEstablishmentDurationLabels = ConnectionEstablishedLabels;
@@ -303,6 +327,7 @@ type ConnectionEstablishmentDurationLabels = ConnectionEstablishedLabels;
 struct ConnectionClosedLabels {
     role: Role,
     protocols: String,
+    transport: Transport,
 }
 
 #[derive(EncodeLabelSet, Hash, Clone, Eq, PartialEq, Debug)]
@@ -325,10 +350,39 @@ impl From<&libp2p_core::ConnectedPoint> for Role {
     }
 }
 
+/// The transport of a connection, see [`TransportKind`].
+#[derive(EncodeLabelValue, Hash, Clone, Eq, PartialEq, Copy, Debug)]
+enum Transport {
+    Tcp,
+    WebSocket,
+    Quic,
+    WebRtc,
+    Relay,
+    Memory,
+    Other,
+    /// The event is not tied to a connection attempt.
+    Unknown,
+}
+
+impl From<TransportKind> for Transport {
+    fn from(kind: TransportKind) -> Self {
+        match kind {
+            TransportKind::Tcp => Transport::Tcp,
+            TransportKind::WebSocket => Transport::WebSocket,
+            TransportKind::Quic => Transport::Quic,
+            TransportKind::WebRtc => Transport::WebRtc,
+            TransportKind::Relay => Transport::Relay,
+            TransportKind::Memory => Transport::Memory,
+            _ => Transport::Other,
+        }
+    }
+}
+
 #[derive(EncodeLabelSet, Hash, Clone, Eq, PartialEq, Debug)]
 struct OutgoingConnectionErrorLabels {
     peer: PeerStatus,
     error: OutgoingConnectionError,
+    transport: Transport,
 }
 
 #[derive(EncodeLabelValue, Hash, Clone, Eq, PartialEq, Copy, Debug)]
@@ -356,6 +410,7 @@ enum OutgoingConnectionError {
 struct IncomingConnectionErrorLabels {
     error: IncomingConnectionError,
     protocols: String,
+    transport: Transport,
 }
 
 #[derive(EncodeLabelValue, Hash, Clone, Eq, PartialEq, Debug)]
@@ -41,6 +41,10 @@ use unsigned_varint as uvi;
 
 /// The maximum number of supported protocols that can be processed.
 const MAX_PROTOCOLS: usize = 1000;
+/// The maximum length of a protocol (name) in bytes.
+pub(crate) const MAX_PROTOCOL_NAME_LEN: usize = 1024;
+/// The maximum number of bytes received from the remote while negotiating a protocol.
+pub(crate) const MAX_NEGOTIATION_BYTES: usize = 64 * 1024;
 
 /// The encoded form of a multistream-select 1.0.0 header message.
 const MSG_MULTISTREAM_1_0: &[u8] = b"/multistream/1.0.0\n";
@@ -83,6 +87,9 @@ impl TryFrom<Bytes> for Protocol {
         if !value.as_ref().starts_with(b"/") {
             return Err(ProtocolError::InvalidProtocol);
         }
+        if value.len() > MAX_PROTOCOL_NAME_LEN {
+            return Err(ProtocolError::ProtocolNameTooLong);
+        }
         Ok(Protocol(value))
     }
 }
@@ -223,6 +230,8 @@ impl Message {
 pub struct MessageIO<R> {
     #[pin]
     inner: LengthDelimited<R>,
+    /// The number of bytes received so far, see [`MAX_NEGOTIATION_BYTES`].
+    received: usize,
 }
 
 impl<R> MessageIO<R> {
@@ -233,6 +242,7 @@ impl<R> MessageIO<R> {
     {
         Self {
             inner: LengthDelimited::new(inner),
+            received: 0,
         }
     }
 
@@ -246,6 +256,7 @@ impl<R> MessageIO<R> {
     pub fn into_reader(self) -> MessageReader<R> {
         MessageReader {
             inner: self.inner.into_reader(),
+            received: self.received,
         }
     }
 
@@ -298,7 +309,8 @@ where
     type Item = Result<Message, ProtocolError>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        match poll_stream(self.project().inner, cx) {
+        let this = self.project();
+        match poll_stream(this.inner, this.received, cx) {
             Poll::Pending => Poll::Pending,
             Poll::Ready(None) => Poll::Ready(None),
             Poll::Ready(Some(Ok(m))) => Poll::Ready(Some(Ok(m))),
@@ -314,6 +326,8 @@ where
 pub struct MessageReader<R> {
     #[pin]
     inner: LengthDelimitedReader<R>,
+    /// The number of bytes received so far, see [`MAX_NEGOTIATION_BYTES`].
+    received: usize,
 }
 
 impl<R> MessageReader<R> {
@@ -340,7 +354,8 @@ where
     type Item = Result<Message, ProtocolError>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        poll_stream(self.project().inner, cx)
+        let this = self.project();
+        poll_stream(this.inner, this.received, cx)
     }
 }
 
@@ -375,12 +390,17 @@ where
 
 fn poll_stream<S>(
     stream: Pin<&mut S>,
+    received: &mut usize,
     cx: &mut Context<'_>,
 ) -> Poll<Option<Result<Message, ProtocolError>>>
 where
     S: Stream<Item = Result<Bytes, io::Error>>,
 {
     let msg = if let Some(msg) = ready!(stream.poll_next(cx)?) {
+        *received += msg.len();
+        if *received > MAX_NEGOTIATION_BYTES {
+            return Poll::Ready(Some(Err(ProtocolError::TooManyBytes)));
+        }
         match Message::decode(msg) {
             Ok(m) => m,
             Err(err) => return Poll::Ready(Some(Err(err))),
@@ -396,6 +416,7 @@ where
 
 /// A protocol error.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ProtocolError {
     /// I/O error.
     IoError(io::Error),
@@ -408,6 +429,12 @@ pub enum ProtocolError {
 
     /// Too many protocols have been returned by the remote.
     TooManyProtocols,
+
+    /// A protocol (name) exceeds the maximum length of 1024 bytes.
+    ProtocolNameTooLong,
+
+    /// The remote sent more than 64 KiB without agreeing on a protocol.
+    TooManyBytes,
 }
 
 impl From<io::Error> for ProtocolError {
@@ -447,6 +474,10 @@ impl fmt::Display for ProtocolError {
             ProtocolError::InvalidMessage => write!(fmt, "Received an invalid message."),
             ProtocolError::InvalidProtocol => write!(fmt, "A protocol (name) is invalid."),
             ProtocolError::TooManyProtocols => write!(fmt, "Too many protocols received."),
+            ProtocolError::ProtocolNameTooLong => write!(fmt, "A protocol (name) is too long."),
+            ProtocolError::TooManyBytes => {
+                write!(fmt, "Too many bytes received during negotiation.")
+            }
         }
     }
 }
@@ -495,4 +526,47 @@ mod tests {
         }
         quickcheck(prop as fn(_))
     }
+
+    #[test]
+    fn protocol_name_too_long() {
+        let name = format!("/{}", "a".repeat(MAX_PROTOCOL_NAME_LEN));
+        assert!(matches!(
+            Protocol::try_from(name.as_bytes()),
+            Err(ProtocolError::ProtocolNameTooLong)
+        ));
+        assert!(Protocol::try_from(&name.as_bytes()[..MAX_PROTOCOL_NAME_LEN]).is_ok());
+
+        let mut msg = BytesMut::from(name.as_bytes());
+        msg.put_u8(b'\n');
+        assert!(matches!(
+            Message::decode(msg.freeze()),
+            Err(ProtocolError::ProtocolNameTooLong)
+        ));
+    }
+
+    #[test]
+    fn negotiation_byte_cap() {
+        let protocol = Protocol::try_from(format!("/{}", "a".repeat(1000)).as_bytes()).unwrap();
+        let mut sent = Vec::new();
+        futures::executor::block_on(async {
+            let mut io = MessageIO::new(futures::io::Cursor::new(&mut sent));
+            for _ in 0..=MAX_NEGOTIATION_BYTES / 1000 {
+                io.send(Message::Protocol(protocol.clone())).await.unwrap();
+            }
+        });
+
+        futures::executor::block_on(async {
+            let mut io = MessageIO::new(futures::io::Cursor::new(sent));
+            for _ in 0..MAX_NEGOTIATION_BYTES / 1002 {
+                assert_eq!(
+                    io.next().await.unwrap().unwrap(),
+                    Message::Protocol(protocol.clone())
+                );
+            }
+            assert!(matches!(
+                io.next().await.unwrap(),
+                Err(ProtocolError::TooManyBytes)
+            ));
+        });
+    }
 }
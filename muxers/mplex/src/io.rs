@@ -24,6 +24,7 @@ use asynchronous_codec::Framed;
 use bytes::Bytes;
 use futures::task::{waker_ref, ArcWake, AtomicWaker, WakerRef};
 use futures::{prelude::*, ready, stream::Fuse};
+use libp2p_core::muxing::StreamPriority;
 use log::{debug, trace};
 use nohash_hasher::{IntMap, IntSet};
 use parking_lot::Mutex;
@@ -88,6 +89,14 @@ pub struct Multiplexed<C> {
     substreams: IntMap<LocalStreamId, SubstreamState>,
     /// The ID for the next outbound substream.
     next_outbound_stream_id: LocalStreamId,
+    /// The priorities of substreams other than [`StreamPriority::Normal`].
+    priorities: IntMap<LocalStreamId, StreamPriority>,
+    /// The substreams whose last write is pending on the underlying
+    /// I/O stream, with their priority.
+    ///
+    /// Writes of substreams with a lower priority are deferred until
+    /// these writes went through.
+    blocked_writes: IntMap<LocalStreamId, StreamPriority>,
     /// Registry of wakers for pending tasks interested in reading.
     notifier_read: Arc<NotifierRead>,
     /// Registry of wakers for pending tasks interested in writing.
@@ -130,6 +139,8 @@ where
             pending_frames: Default::default(),
             blocking_stream: None,
             next_outbound_stream_id: LocalStreamId::dialer(0),
+            priorities: Default::default(),
+            blocked_writes: Default::default(),
             notifier_read: Arc::new(NotifierRead {
                 read_stream: Mutex::new(Default::default()),
                 next_stream: AtomicWaker::new(),
@@ -330,6 +341,9 @@ where
         // are all woken on every new write opportunity.
         self.notifier_read.wake_read_stream(id);
 
+        self.priorities.remove(&id);
+        self.unblock_write(id);
+
         // Remove the substream, scheduling pending frames as necessary.
         match self.substreams.remove(&id) {
             None => {}
@@ -366,7 +380,35 @@ where
         }
     }
 
+    /// Sets the priority of a substream.
+    ///
+    /// Priorities of substreams that are no longer writable are ignored.
+    pub fn set_stream_priority(&mut self, id: LocalStreamId, priority: StreamPriority) {
+        if !matches!(
+            self.substreams.get(&id),
+            Some(SubstreamState::Open { .. }) | Some(SubstreamState::RecvClosed { .. })
+        ) {
+            return;
+        }
+
+        if priority == StreamPriority::Normal {
+            self.priorities.remove(&id);
+        } else {
+            self.priorities.insert(id, priority);
+        }
+
+        if let Some(blocked) = self.blocked_writes.get_mut(&id) {
+            *blocked = priority;
+            // Writes deferred in favour of the substream may proceed now.
+            ArcWake::wake_by_ref(&self.notifier_write);
+        }
+    }
+
     /// Writes data to a substream.
+    ///
+    /// If the write of a substream with a higher priority is pending
+    /// on the underlying I/O stream, the write is deferred until the
+    /// former went through.
     pub fn poll_write_stream(
         &mut self,
         cx: &mut Context<'_>,
@@ -388,21 +430,47 @@ where
             }
         }
 
+        // Defer to pending writes of substreams with a higher priority.
+        let priority = self.priorities.get(&id).copied().unwrap_or_default();
+        if self
+            .blocked_writes
+            .iter()
+            .any(|(other, p)| *other != id && *p > priority)
+        {
+            trace!("{}: Deferring write of stream {}", self.id, id);
+            let _ = NotifierWrite::register(&self.notifier_write, cx.waker());
+            return Poll::Pending;
+        }
+
         // Determine the size of the frame to send.
         let frame_len = cmp::min(buf.len(), self.config.split_send_size);
 
         // Send the data frame.
-        ready!(self.poll_send_frame(cx, || {
+        let sent = self.poll_send_frame(cx, || {
             let data = Bytes::copy_from_slice(&buf[..frame_len]);
             Frame::Data {
                 stream_id: id,
                 data,
             }
-        }))?;
+        });
+        if sent.is_pending() {
+            self.blocked_writes.insert(id, priority);
+            return Poll::Pending;
+        }
+        self.unblock_write(id);
+        ready!(sent)?;
 
         Poll::Ready(Ok(frame_len))
     }
 
+    /// Removes the pending write of a substream, waking the writes
+    /// that may have been deferred in its favour.
+    fn unblock_write(&mut self, id: LocalStreamId) {
+        if self.blocked_writes.remove(&id).is_some() {
+            ArcWake::wake_by_ref(&self.notifier_write);
+        }
+    }
+
     /// Reads data from a substream.
     ///
     /// Data frames read for substreams other than `id` in the context
@@ -740,6 +808,8 @@ where
                 | SubstreamState::Open { buf } => {
                     debug!("{}: Substream {} reset by remote.", self.id, id);
                     self.substreams.insert(id, SubstreamState::Reset { buf });
+                    self.priorities.remove(&id);
+                    self.unblock_write(id);
                     // Notify tasks interested in reading from that stream,
                     // so they may read the EOF.
                     NotifierRead::wake_read_stream(&self.notifier_read, id);
@@ -911,6 +981,8 @@ where
                     let buf = buf.clone();
                     self.check_max_pending_frames()?;
                     self.substreams.insert(id, SubstreamState::Reset { buf });
+                    self.priorities.remove(&id);
+                    self.unblock_write(id);
                     debug!("{}: Pending reset for stream {}", self.id, id);
                     self.pending_frames
                         .push_front(Frame::Reset { stream_id: id });
@@ -1137,6 +1209,8 @@ mod tests {
         w_buf: BytesMut,
         /// Whether the connection should return EOF on the next read.
         eof: bool,
+        /// Whether writes to the connection are pending.
+        write_blocked: bool,
     }
 
     impl AsyncRead for Connection {
@@ -1165,6 +1239,9 @@ mod tests {
             _: &mut Context<'_>,
             buf: &[u8],
         ) -> Poll<io::Result<usize>> {
+            if self.write_blocked {
+                return Poll::Pending;
+            }
             self.w_buf.extend_from_slice(buf);
             Poll::Ready(Ok(buf.len()))
         }
@@ -1212,6 +1289,7 @@ mod tests {
                 r_buf,
                 w_buf: BytesMut::new(),
                 eof: false,
+                write_blocked: false,
             };
             let mut m = Multiplexed::new(conn, cfg.clone());
 
@@ -1325,6 +1403,7 @@ mod tests {
                 r_buf: BytesMut::new(),
                 w_buf: BytesMut::new(),
                 eof: false,
+                write_blocked: false,
             };
             let mut m = Multiplexed::new(conn, cfg);
 
@@ -1356,4 +1435,94 @@ mod tests {
 
         quickcheck(prop as fn(_, _))
     }
+
+    #[test]
+    fn prioritized_writes() {
+        let _ = env_logger::try_init();
+
+        let conn = Connection {
+            r_buf: BytesMut::new(),
+            w_buf: BytesMut::new(),
+            eof: false,
+            write_blocked: true,
+        };
+        let mut m = Multiplexed::new(conn, MplexConfig::default());
+        let data = vec![0; 8 * 1024];
+
+        task::block_on(future::poll_fn(move |cx| {
+            let bulk = ready!(m.poll_open_stream(cx)).unwrap();
+            let control = ready!(m.poll_open_stream(cx)).unwrap();
+            m.set_stream_priority(bulk, StreamPriority::Low);
+            m.set_stream_priority(control, StreamPriority::High);
+
+            // Write to the bulk stream until the connection is congested.
+            let mut congested = false;
+            for _ in 0..1000 {
+                if m.poll_write_stream(cx, bulk, &data).is_pending() {
+                    congested = true;
+                    break;
+                }
+            }
+            assert!(congested);
+            assert!(m.poll_write_stream(cx, control, &data).is_pending());
+
+            // Once the connection can make progress, the write of the
+            // control stream goes first.
+            m.io.get_mut().deref_mut().write_blocked = false;
+            assert!(m.poll_write_stream(cx, bulk, &data).is_pending());
+            match m.poll_write_stream(cx, control, &data) {
+                Poll::Ready(Ok(n)) => assert_eq!(n, data.len()),
+                poll => panic!("Unexpected: {poll:?}"),
+            }
+            match m.poll_write_stream(cx, bulk, &data) {
+                Poll::Ready(Ok(n)) => assert_eq!(n, data.len()),
+                poll => panic!("Unexpected: {poll:?}"),
+            }
+
+            Poll::Ready(())
+        }));
+    }
+
+    #[test]
+    fn writes_are_not_deferred_to_reset_substreams() {
+        let _ = env_logger::try_init();
+
+        let conn = Connection {
+            r_buf: BytesMut::new(),
+            w_buf: BytesMut::new(),
+            eof: false,
+            write_blocked: true,
+        };
+        let mut m = Multiplexed::new(conn, MplexConfig::default());
+        let data = vec![0; 8 * 1024];
+
+        task::block_on(future::poll_fn(move |cx| {
+            let bulk = ready!(m.poll_open_stream(cx)).unwrap();
+            let control = ready!(m.poll_open_stream(cx)).unwrap();
+            m.set_stream_priority(control, StreamPriority::High);
+
+            // Write to the control stream until the connection is congested.
+            let mut congested = false;
+            for _ in 0..1000 {
+                if m.poll_write_stream(cx, control, &data).is_pending() {
+                    congested = true;
+                    break;
+                }
+            }
+            assert!(congested);
+            m.io.get_mut().deref_mut().write_blocked = false;
+            assert!(m.poll_write_stream(cx, bulk, &data).is_pending());
+
+            // Once the remote resets the control stream, its pending write
+            // no longer defers the bulk stream.
+            m.on_reset(control);
+            assert!(m.blocked_writes.is_empty());
+            match m.poll_write_stream(cx, bulk, &data) {
+                Poll::Ready(Ok(n)) => assert_eq!(n, data.len()),
+                poll => panic!("Unexpected: {poll:?}"),
+            }
+
+            Poll::Ready(())
+        }));
+    }
 }
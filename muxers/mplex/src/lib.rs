@@ -31,7 +31,7 @@ pub use config::{MaxBufferBehaviour, MplexConfig};
 use bytes::Bytes;
 use codec::LocalStreamId;
 use futures::{future, prelude::*, ready};
-use libp2p_core::muxing::{StreamMuxer, StreamMuxerEvent};
+use libp2p_core::muxing::{PriorityHandle, StreamMuxer, StreamMuxerEvent};
 use libp2p_core::upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo};
 use parking_lot::Mutex;
 use std::{cmp, iter, pin::Pin, sync::Arc, task::Context, task::Poll};
@@ -82,7 +82,7 @@ pub struct Multiplex<C> {
 
 impl<C> StreamMuxer for Multiplex<C>
 where
-    C: AsyncRead + AsyncWrite + Unpin,
+    C: AsyncRead + AsyncWrite + Unpin + Send + 'static,
 {
     type Substream = Substream<C>;
     type Error = io::Error;
@@ -117,6 +117,20 @@ where
     fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
         self.io.lock().poll_close(cx)
     }
+
+    fn priority_handle(&self, substream: &Self::Substream) -> Option<PriorityHandle> {
+        // Ignore substreams of other connections.
+        if !Arc::ptr_eq(&self.io, &substream.io) {
+            return None;
+        }
+        let io = Arc::downgrade(&self.io);
+        let id = substream.id;
+        Some(PriorityHandle::new(move |priority| {
+            if let Some(io) = io.upgrade() {
+                io.lock().set_stream_priority(id, priority)
+            }
+        }))
+    }
 }
 
 impl<C> AsyncRead for Substream<C>
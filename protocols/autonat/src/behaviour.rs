@@ -119,6 +119,16 @@ impl NatStatus {
     }
 }
 
+impl From<NatStatus> for libp2p_swarm::NatStatus {
+    fn from(status: NatStatus) -> Self {
+        match status {
+            NatStatus::Public(address) => libp2p_swarm::NatStatus::Public(address),
+            NatStatus::Private => libp2p_swarm::NatStatus::Private,
+            NatStatus::Unknown => libp2p_swarm::NatStatus::Unknown,
+        }
+    }
+}
+
 /// Unique identifier for a probe.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ProbeId(usize);
@@ -357,6 +367,7 @@ impl Behaviour {
             endpoint,
             handler,
             remaining_established,
+            reason,
         }: ConnectionClosed<<Self as NetworkBehaviour>::ConnectionHandler>,
     ) {
         self.inner
@@ -366,6 +377,7 @@ impl Behaviour {
                 endpoint,
                 handler,
                 remaining_established,
+                reason,
             }));
 
         if remaining_established == 0 {
@@ -385,6 +397,7 @@ impl Behaviour {
             peer_id,
             connection_id,
             error,
+            user_data,
         }: DialFailure,
     ) {
         self.inner
@@ -392,6 +405,7 @@ impl Behaviour {
                 peer_id,
                 connection_id,
                 error,
+                user_data,
             }));
         if let Some(event) = self.as_server().on_outbound_dial_error(peer_id, error) {
             self.pending_actions
@@ -451,7 +465,8 @@ impl NetworkBehaviour for Behaviour {
                         | request_response::Event::InboundFailure { .. } => {
                             self.as_server().handle_event(params, event)
                         }
-                        request_response::Event::ResponseSent { .. } => VecDeque::new(),
+                        request_response::Event::ResponseSent { .. }
+                        | request_response::Event::BroadcastCompleted { .. } => VecDeque::new(),
                     };
 
                     self.pending_actions.extend(actions);
@@ -552,11 +567,16 @@ impl NetworkBehaviour for Behaviour {
                 self.inner.on_swarm_event(listen_addr);
                 self.as_client().on_new_address();
             }
-            FromSwarm::ExpiredListenAddr(ExpiredListenAddr { listener_id, addr }) => {
+            FromSwarm::ExpiredListenAddr(ExpiredListenAddr {
+                listener_id,
+                addr,
+                transport,
+            }) => {
                 self.inner
                     .on_swarm_event(FromSwarm::ExpiredListenAddr(ExpiredListenAddr {
                         listener_id,
                         addr,
+                        transport,
                     }));
                 self.as_client().on_expired_address(addr);
             }
@@ -579,6 +599,15 @@ impl NetworkBehaviour for Behaviour {
             listener_closed @ FromSwarm::ListenerClosed(_) => {
                 self.inner.on_swarm_event(listener_closed)
             }
+            peer_protocols_changed @ FromSwarm::PeerProtocolsChanged(_) => {
+                self.inner.on_swarm_event(peer_protocols_changed)
+            }
+            peer_listen_addrs_changed @ FromSwarm::PeerListenAddrsChanged(_) => {
+                self.inner.on_swarm_event(peer_listen_addrs_changed)
+            }
+            nat_status_changed @ FromSwarm::NatStatusChanged(_) => {
+                self.inner.on_swarm_event(nat_status_changed)
+            }
         }
     }
 
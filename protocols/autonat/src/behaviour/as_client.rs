@@ -140,11 +140,12 @@ impl<'a> HandleInnerEvent for AsClient<'a> {
                     },
                 };
 
-                let mut actions = VecDeque::with_capacity(3);
+                let mut actions = VecDeque::with_capacity(4);
 
                 actions.push_back(ToSwarm::GenerateEvent(Event::OutboundProbe(event)));
 
                 if let Some(old) = self.handle_reported_status(response.result.clone().into()) {
+                    actions.push_back(ToSwarm::ReportNatStatus(self.nat_status.clone().into()));
                     actions.push_back(ToSwarm::GenerateEvent(Event::StatusChanged {
                         old,
                         new: self.nat_status.clone(),
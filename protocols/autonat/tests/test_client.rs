@@ -111,6 +111,10 @@ async fn test_auto_probe() {
     assert_eq!(client.behaviour().confidence(), 0);
     assert_eq!(client.behaviour().nat_status(), NatStatus::Private);
     assert!(client.behaviour().public_address().is_none());
+    assert_eq!(
+        client.node_status().nat_status(),
+        &libp2p_swarm::NatStatus::Private
+    );
 
     // Test new public listening address
     client.listen().await;
@@ -171,6 +175,10 @@ async fn test_auto_probe() {
     assert_eq!(client.behaviour().confidence(), 0);
     assert!(client.behaviour().nat_status().is_public());
     assert!(client.behaviour().public_address().is_some());
+    assert!(matches!(
+        client.node_status().nat_status(),
+        libp2p_swarm::NatStatus::Public(_)
+    ));
 }
 
 #[async_std::test]
@@ -21,6 +21,7 @@
 //! [`NetworkBehaviour`] to act as a direct connection upgrade through relay node.
 
 use crate::handler;
+use crate::trace::TraceRecorder;
 use either::Either;
 use libp2p_core::connection::ConnectedPoint;
 use libp2p_core::multiaddr::Protocol;
@@ -29,17 +30,105 @@ use libp2p_identity::PeerId;
 use libp2p_swarm::behaviour::{ConnectionClosed, ConnectionEstablished, DialFailure, FromSwarm};
 use libp2p_swarm::dial_opts::{self, DialOpts};
 use libp2p_swarm::{dummy, ConnectionDenied, ConnectionId, THandler, THandlerOutEvent};
+use libp2p_swarm::{Clock, SystemClock};
 use libp2p_swarm::{
     ConnectionHandlerUpgrErr, ExternalAddresses, NetworkBehaviour, NotifyHandler, PollParameters,
     THandlerInEvent, ToSwarm,
 };
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 use thiserror::Error;
 use void::Void;
 
 const MAX_NUMBER_OF_UPGRADE_ATTEMPTS: u8 = 3;
 
+/// How long a relayed connection is kept alive for an upgrade by default.
+const DEFAULT_KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Overrides of the handler configuration for the relayed connections to a specific peer,
+/// see [`Behaviour::set_peer_overrides`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PeerOverrides {
+    /// Whether direct connection upgrades are enabled, instead of [`Behaviour::is_enabled`].
+    pub enabled: Option<bool>,
+    /// How long a relayed connection is kept alive for an upgrade, instead of 30 seconds.
+    ///
+    /// E.g. a larger timeout allows upgrading connections to known-slow peers.
+    pub keep_alive_timeout: Option<Duration>,
+}
+
+/// The role of the local node in direct connection upgrades, see [`Config::role`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Role {
+    /// Initiate upgrades of inbound relayed connections and accept upgrades initiated by remotes.
+    #[default]
+    InitiatorAndResponder,
+    /// Only accept upgrades initiated by remotes, never initiate upgrades.
+    ///
+    /// E.g. for resource-constrained nodes, reducing the number of outbound dials.
+    ResponderOnly,
+}
+
+/// Configuration of the [`Behaviour`].
+#[derive(Debug, Clone)]
+pub struct Config {
+    role: Role,
+    clock: Arc<dyn Clock>,
+    trace_recorder: Option<TraceRecorder>,
+    simultaneous_open: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            role: Role::default(),
+            clock: Arc::new(SystemClock),
+            trace_recorder: None,
+            simultaneous_open: false,
+        }
+    }
+}
+
+impl Config {
+    /// Sets the role of the local node in direct connection upgrades.
+    ///
+    /// Defaults to [`Role::InitiatorAndResponder`].
+    pub fn role(mut self, role: Role) -> Self {
+        self.role = role;
+        self
+    }
+
+    /// Sets the [`Clock`] driving the keep-alive timeout of relayed connections and the
+    /// synchronization of hole punches.
+    ///
+    /// Defaults to the [`SystemClock`].
+    pub fn clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Resolves the roles of both nodes in upgrading the connection of a hole punch by comparing
+    /// their peer IDs, see
+    /// [`WithPeerIdWithAddresses::simultaneous_open`](dial_opts::WithPeerIdWithAddresses::simultaneous_open),
+    /// instead of by their role in the DCUtR protocol.
+    ///
+    /// Both nodes need to enable this, thus it must only be enabled if all remote nodes do too.
+    /// Disabled by default, as other implementations resolve the roles as per the specification.
+    pub fn simultaneous_open(mut self, enabled: bool) -> Self {
+        self.simultaneous_open = enabled;
+        self
+    }
+
+    /// Records the bytes exchanged on all DCUtR substreams with the given [`TraceRecorder`].
+    #[cfg(feature = "interop")]
+    pub fn trace_recorder(mut self, recorder: TraceRecorder) -> Self {
+        self.trace_recorder = Some(recorder);
+        self
+    }
+}
+
 /// The events produced by the [`Behaviour`].
 #[derive(Debug)]
 pub enum Event {
@@ -84,10 +173,23 @@ pub struct Behaviour {
     /// Indexed by the [`ConnectionId`] of the relayed connection and
     /// the [`PeerId`] we are trying to establish a direct connection to.
     outgoing_direct_connection_attempts: HashMap<(ConnectionId, PeerId), u8>,
+
+    /// See [`Behaviour::set_enabled`].
+    enabled: bool,
+
+    /// See [`Behaviour::set_peer_overrides`].
+    peer_overrides: HashMap<PeerId, PeerOverrides>,
+
+    config: Config,
 }
 
 impl Behaviour {
     pub fn new(local_peer_id: PeerId) -> Self {
+        Self::with_config(local_peer_id, Config::default())
+    }
+
+    /// Creates a new [`Behaviour`] with the given [`Config`].
+    pub fn with_config(local_peer_id: PeerId, config: Config) -> Self {
         Behaviour {
             queued_events: Default::default(),
             direct_connections: Default::default(),
@@ -95,9 +197,69 @@ impl Behaviour {
             local_peer_id,
             direct_to_relayed_connections: Default::default(),
             outgoing_direct_connection_attempts: Default::default(),
+            enabled: true,
+            peer_overrides: Default::default(),
+            config,
         }
     }
 
+    /// Enables or disables direct connection upgrades.
+    ///
+    /// While disabled, no upgrades are initiated or accepted on newly established relayed
+    /// connections. Upgrades already in progress are not affected.
+    ///
+    /// Enabled by default.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Whether direct connection upgrades are enabled, see [`Behaviour::set_enabled`].
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Overrides the configuration of the handlers of new relayed connections to the given peer.
+    ///
+    /// Handlers of established connections are not affected.
+    pub fn set_peer_overrides(&mut self, peer: PeerId, overrides: PeerOverrides) {
+        if overrides == PeerOverrides::default() {
+            self.peer_overrides.remove(&peer);
+        } else {
+            self.peer_overrides.insert(peer, overrides);
+        }
+    }
+
+    /// Returns the overrides for the given peer, see [`Behaviour::set_peer_overrides`].
+    pub fn peer_overrides(&self, peer: &PeerId) -> PeerOverrides {
+        self.peer_overrides.get(peer).copied().unwrap_or_default()
+    }
+
+    /// Whether direct connection upgrades are enabled for the given peer, taking its
+    /// [`PeerOverrides`] into account.
+    fn is_enabled_for(&self, peer: &PeerId) -> bool {
+        self.peer_overrides(peer).enabled.unwrap_or(self.enabled)
+    }
+
+    /// Creates the handler of a relayed connection to the given peer.
+    fn new_relayed_handler(&self, peer: &PeerId, endpoint: ConnectedPoint) -> THandler<Self> {
+        if !self.is_enabled_for(peer) {
+            return Either::Right(Either::Right(dummy::ConnectionHandler));
+        }
+
+        let keep_alive_timeout = self
+            .peer_overrides(peer)
+            .keep_alive_timeout
+            .unwrap_or(DEFAULT_KEEP_ALIVE_TIMEOUT);
+
+        // TODO: We could make two `handler::relayed::Handler` here, one inbound one outbound.
+        Either::Left(handler::relayed::Handler::new(
+            endpoint,
+            keep_alive_timeout,
+            self.config.clock.clone(),
+            self.config.trace_recorder.clone(),
+        ))
+    }
+
     fn observed_addreses(&self) -> Vec<Multiaddr> {
         self.external_addresses
             .iter()
@@ -117,7 +279,14 @@ impl Behaviour {
         }: ConnectionEstablished,
     ) {
         if connected_point.is_relayed() {
-            if connected_point.is_listener() && !self.direct_connections.contains_key(&peer_id) {
+            // The handler of the connection was created just before with the same `enabled`
+            // state, thus it is a `handler::relayed::Handler` iff upgrades are enabled for the
+            // peer.
+            if self.is_enabled_for(&peer_id)
+                && self.config.role == Role::InitiatorAndResponder
+                && connected_point.is_listener()
+                && !self.direct_connections.contains_key(&peer_id)
+            {
                 // TODO: Try dialing the remote peer directly. Specification:
                 //
                 // > The protocol starts with the completion of a relay connection from A to B. Upon
@@ -254,10 +423,13 @@ impl NetworkBehaviour for Behaviour {
         {
             None => {
                 let handler = if is_relayed(local_addr) {
-                    Either::Left(handler::relayed::Handler::new(ConnectedPoint::Listener {
-                        local_addr: local_addr.clone(),
-                        send_back_addr: remote_addr.clone(),
-                    })) // TODO: We could make two `handler::relayed::Handler` here, one inbound one outbound.
+                    self.new_relayed_handler(
+                        &peer,
+                        ConnectedPoint::Listener {
+                            local_addr: local_addr.clone(),
+                            send_back_addr: remote_addr.clone(),
+                        },
+                    )
                 } else {
                     Either::Right(Either::Right(dummy::ConnectionHandler))
                 };
@@ -290,10 +462,13 @@ impl NetworkBehaviour for Behaviour {
         {
             None => {
                 let handler = if is_relayed(addr) {
-                    Either::Left(handler::relayed::Handler::new(ConnectedPoint::Dialer {
-                        address: addr.clone(),
-                        role_override,
-                    })) // TODO: We could make two `handler::relayed::Handler` here, one inbound one outbound.
+                    self.new_relayed_handler(
+                        &peer,
+                        ConnectedPoint::Dialer {
+                            address: addr.clone(),
+                            role_override,
+                        },
+                    )
                 } else {
                     Either::Right(Either::Right(dummy::ConnectionHandler))
                 };
@@ -359,10 +534,14 @@ impl NetworkBehaviour for Behaviour {
                 ));
             }
             Either::Left(handler::relayed::Event::InboundConnectNegotiated(remote_addrs)) => {
-                let opts = DialOpts::peer_id(event_source)
+                let mut opts = DialOpts::peer_id(event_source)
                     .addresses(remote_addrs)
                     .condition(dial_opts::PeerCondition::Always)
-                    .build();
+                    .reuse_port();
+                if self.config.simultaneous_open {
+                    opts = opts.simultaneous_open();
+                }
+                let opts = opts.build();
 
                 let maybe_direct_connection_id = opts.connection_id();
 
@@ -379,11 +558,15 @@ impl NetworkBehaviour for Behaviour {
                 ));
             }
             Either::Left(handler::relayed::Event::OutboundConnectNegotiated { remote_addrs }) => {
-                let opts = DialOpts::peer_id(event_source)
+                let mut opts = DialOpts::peer_id(event_source)
                     .condition(dial_opts::PeerCondition::Always)
                     .addresses(remote_addrs)
                     .override_role()
-                    .build();
+                    .reuse_port();
+                if self.config.simultaneous_open {
+                    opts = opts.simultaneous_open();
+                }
+                let opts = opts.build();
 
                 let maybe_direct_connection_id = opts.connection_id();
 
@@ -444,7 +627,10 @@ impl NetworkBehaviour for Behaviour {
             | FromSwarm::ListenerError(_)
             | FromSwarm::ListenerClosed(_)
             | FromSwarm::NewExternalAddr(_)
-            | FromSwarm::ExpiredExternalAddr(_) => {}
+            | FromSwarm::ExpiredExternalAddr(_)
+            | FromSwarm::PeerProtocolsChanged(_)
+            | FromSwarm::PeerListenAddrsChanged(_)
+            | FromSwarm::NatStatusChanged(_) => {}
         }
     }
 }
@@ -452,3 +638,85 @@ impl NetworkBehaviour for Behaviour {
 fn is_relayed(addr: &Multiaddr) -> bool {
     addr.iter().any(|p| p == Protocol::P2pCircuit)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_swarm::{ConnectionHandler, KeepAlive, ManualClock};
+
+    fn relayed_handler(behaviour: &mut Behaviour, peer: PeerId) -> THandler<Behaviour> {
+        let relayed_addr = "/memory/1234/p2p-circuit".parse::<Multiaddr>().unwrap();
+        behaviour
+            .handle_established_inbound_connection(
+                ConnectionId::new_unchecked(0),
+                peer,
+                &relayed_addr,
+                &Multiaddr::empty(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn peer_overrides_only_apply_to_their_peer() {
+        let clock = ManualClock::new();
+        let mut behaviour =
+            Behaviour::with_config(PeerId::random(), Config::default().clock(clock.clone()));
+        let overridden = PeerId::random();
+        let other = PeerId::random();
+
+        behaviour.set_peer_overrides(
+            overridden,
+            PeerOverrides {
+                enabled: None,
+                keep_alive_timeout: Some(Duration::from_secs(120)),
+            },
+        );
+        match relayed_handler(&mut behaviour, overridden) {
+            Either::Left(handler) => assert_eq!(
+                handler.connection_keep_alive(),
+                KeepAlive::Until(clock.now() + Duration::from_secs(120))
+            ),
+            Either::Right(_) => panic!("Expected a relayed handler."),
+        }
+        match relayed_handler(&mut behaviour, other) {
+            Either::Left(handler) => assert_eq!(
+                handler.connection_keep_alive(),
+                KeepAlive::Until(clock.now() + DEFAULT_KEEP_ALIVE_TIMEOUT)
+            ),
+            Either::Right(_) => panic!("Expected a relayed handler."),
+        }
+
+        behaviour.set_peer_overrides(
+            overridden,
+            PeerOverrides {
+                enabled: Some(false),
+                keep_alive_timeout: None,
+            },
+        );
+        assert!(matches!(
+            relayed_handler(&mut behaviour, overridden),
+            Either::Right(Either::Right(_))
+        ));
+        assert!(matches!(
+            relayed_handler(&mut behaviour, other),
+            Either::Left(_)
+        ));
+
+        behaviour.set_enabled(false);
+        behaviour.set_peer_overrides(
+            overridden,
+            PeerOverrides {
+                enabled: Some(true),
+                keep_alive_timeout: None,
+            },
+        );
+        assert!(matches!(
+            relayed_handler(&mut behaviour, overridden),
+            Either::Left(_)
+        ));
+        assert!(matches!(
+            relayed_handler(&mut behaviour, other),
+            Either::Right(Either::Right(_))
+        ));
+    }
+}
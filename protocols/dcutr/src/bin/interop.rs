@@ -0,0 +1,230 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Hole punches to or from a remote peer, e.g. running go-libp2p, through a relay and records the
+//! bytes exchanged on the DCUtR substreams.
+//!
+//! The recorded traces are the fixtures replayed by the unit tests of the protocol, see
+//! `tests/fixtures`. To capture the traces of a session with a go-libp2p peer listening via
+//! the relay, run:
+//!
+//! ```sh
+//! cargo run -p libp2p-dcutr --features interop --bin interop -- \
+//!     --mode dial --secret-key-seed 1 --relay-address <relay-address> \
+//!     --remote-peer-id <go-peer-id> --traces-dir protocols/dcutr/tests/fixtures/go-libp2p
+//! ```
+//!
+//! The process exits with an error if the direct connection upgrade failed.
+
+use clap::Parser;
+use futures::prelude::*;
+use libp2p_core::{
+    multiaddr::{Multiaddr, Protocol},
+    transport::{OrTransport, Transport},
+    upgrade,
+};
+use libp2p_dcutr as dcutr;
+use libp2p_dcutr::trace::TraceRecorder;
+use libp2p_dns::DnsConfig;
+use libp2p_identify as identify;
+use libp2p_identity::{Keypair, PeerId};
+use libp2p_noise as noise;
+use libp2p_relay as relay;
+use libp2p_swarm::{NetworkBehaviour, SwarmBuilder, SwarmEvent};
+use libp2p_tcp as tcp;
+use libp2p_yamux as yamux;
+use log::info;
+use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Debug, Parser)]
+#[clap(name = "libp2p DCUtR interop")]
+struct Opts {
+    /// The mode (dial, listen).
+    #[clap(long)]
+    mode: Mode,
+
+    /// Fixed value to generate deterministic peer id.
+    #[clap(long)]
+    secret_key_seed: u8,
+
+    /// The address of the relay.
+    #[clap(long)]
+    relay_address: Multiaddr,
+
+    /// Peer ID of the remote peer to hole punch to.
+    #[clap(long)]
+    remote_peer_id: Option<PeerId>,
+
+    /// The directory to write the traces of the DCUtR substreams to.
+    #[clap(long)]
+    traces_dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, PartialEq, Parser)]
+enum Mode {
+    Dial,
+    Listen,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "dial" => Ok(Mode::Dial),
+            "listen" => Ok(Mode::Listen),
+            _ => Err("Expected either 'dial' or 'listen'".to_string()),
+        }
+    }
+}
+
+#[derive(NetworkBehaviour)]
+#[behaviour(prelude = "libp2p_swarm::derive_prelude")]
+struct Behaviour {
+    relay_client: relay::client::Behaviour,
+    identify: identify::Behaviour,
+    dcutr: dcutr::Behaviour,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::init();
+
+    let opts = Opts::parse();
+
+    let local_key = generate_ed25519(opts.secret_key_seed);
+    let local_peer_id = PeerId::from(local_key.public());
+    info!("Local peer id: {local_peer_id}");
+
+    let (relay_transport, client) = relay::client::new(local_peer_id);
+    let transport = OrTransport::new(
+        relay_transport,
+        async_std::task::block_on(DnsConfig::system(tcp::async_io::Transport::new(
+            tcp::Config::default().port_reuse(true),
+        )))?,
+    )
+    .upgrade(upgrade::Version::V1)
+    .authenticate(noise::NoiseAuthenticated::xx(&local_key)?)
+    .multiplex(yamux::YamuxConfig::default())
+    .boxed();
+
+    let trace_recorder = TraceRecorder::new();
+    let behaviour = Behaviour {
+        relay_client: client,
+        identify: identify::Behaviour::new(identify::Config::new(
+            "/ipfs/0.1.0".to_string(),
+            local_key.public(),
+        )),
+        dcutr: dcutr::Behaviour::with_config(
+            local_peer_id,
+            dcutr::Config::default().trace_recorder(trace_recorder.clone()),
+        ),
+    };
+    let mut swarm = SwarmBuilder::with_executor(
+        transport,
+        behaviour,
+        local_peer_id,
+        |fut: std::pin::Pin<Box<dyn Future<Output = ()> + Send>>| {
+            async_std::task::spawn(fut);
+        },
+    )
+    .build();
+
+    swarm.listen_on("/ip4/0.0.0.0/tcp/0".parse()?)?;
+
+    // Connect to the relay first to learn our public address via identify.
+    swarm.dial(opts.relay_address.clone())?;
+    async_std::task::block_on(async {
+        loop {
+            if let SwarmEvent::Behaviour(BehaviourEvent::Identify(identify::Event::Received {
+                info,
+                ..
+            })) = swarm.select_next_some().await
+            {
+                info!("Relay told us our public address: {}", info.observed_addr);
+                break;
+            }
+        }
+    });
+
+    let relay_peer_id = match opts.relay_address.iter().last() {
+        Some(Protocol::P2p(hash)) => PeerId::from_multihash(hash).ok(),
+        _ => None,
+    };
+    let circuit_addr = opts.relay_address.with(Protocol::P2pCircuit);
+    match opts.mode {
+        Mode::Dial => {
+            let remote_peer_id = opts
+                .remote_peer_id
+                .ok_or("Dialing requires --remote-peer-id")?;
+            swarm.dial(circuit_addr.with(Protocol::P2p(remote_peer_id.into())))?;
+        }
+        Mode::Listen => {
+            swarm.listen_on(circuit_addr)?;
+        }
+    }
+
+    let result = async_std::task::block_on(async {
+        loop {
+            match swarm.select_next_some().await {
+                // Both sides dial during the hole punch, only one of the dials may succeed.
+                SwarmEvent::ConnectionEstablished {
+                    peer_id, endpoint, ..
+                } if !endpoint.is_relayed() && Some(peer_id) != relay_peer_id => {
+                    info!("Hole punched to {peer_id} via {endpoint:?}");
+                    break Ok(peer_id);
+                }
+                SwarmEvent::Behaviour(BehaviourEvent::Dcutr(
+                    dcutr::Event::DirectConnectionUpgradeFailed {
+                        remote_peer_id,
+                        error,
+                    },
+                )) => break Err(format!("Hole punch to {remote_peer_id} failed: {error}")),
+                SwarmEvent::Behaviour(BehaviourEvent::RelayClient(event)) => info!("{event:?}"),
+                SwarmEvent::Behaviour(BehaviourEvent::Dcutr(event)) => info!("{event:?}"),
+                SwarmEvent::ConnectionEstablished {
+                    peer_id, endpoint, ..
+                } => info!("Established connection to {peer_id} via {endpoint:?}"),
+                _ => {}
+            }
+        }
+    });
+
+    // Substreams of failed attempts are recorded too, thus written regardless of the result.
+    if let Some(dir) = opts.traces_dir {
+        std::fs::create_dir_all(&dir)?;
+        let remote = match &result {
+            Ok(peer_id) => peer_id.to_string(),
+            Err(_) => "failed".to_string(),
+        };
+        for (i, trace) in trace_recorder.take().into_iter().enumerate() {
+            trace.write_to(&dir, &format!("{remote}-{i}"))?;
+        }
+    }
+
+    result.map(|_| ()).map_err(Into::into)
+}
+
+fn generate_ed25519(secret_key_seed: u8) -> Keypair {
+    let mut bytes = [0u8; 32];
+    bytes[0] = secret_key_seed;
+
+    Keypair::ed25519_from_bytes(bytes).expect("only errors on wrong length")
+}
@@ -21,10 +21,10 @@
 //! [`ConnectionHandler`] handling relayed connection potentially upgraded to a direct connection.
 
 use crate::protocol;
+use crate::trace::TraceRecorder;
 use either::Either;
 use futures::future;
 use futures::future::{BoxFuture, FutureExt};
-use instant::Instant;
 use libp2p_core::multiaddr::Multiaddr;
 use libp2p_core::upgrade::{DeniedUpgrade, NegotiationError, UpgradeError};
 use libp2p_core::ConnectedPoint;
@@ -33,11 +33,12 @@ use libp2p_swarm::handler::{
     ListenUpgradeError,
 };
 use libp2p_swarm::{
-    ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, KeepAlive,
+    Clock, ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, KeepAlive,
     SubstreamProtocol,
 };
 use std::collections::VecDeque;
 use std::fmt;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
@@ -146,16 +147,27 @@ pub struct Handler {
     inbound_connect:
         Option<BoxFuture<'static, Result<Vec<Multiaddr>, protocol::inbound::UpgradeError>>>,
     keep_alive: KeepAlive,
+    clock: Arc<dyn Clock>,
+    trace_recorder: Option<TraceRecorder>,
 }
 
 impl Handler {
-    pub fn new(endpoint: ConnectedPoint) -> Self {
+    /// Creates a handler keeping the relayed connection alive for an upgrade for at most
+    /// `keep_alive_timeout`, as measured by `clock`.
+    pub fn new(
+        endpoint: ConnectedPoint,
+        keep_alive_timeout: Duration,
+        clock: Arc<dyn Clock>,
+        trace_recorder: Option<TraceRecorder>,
+    ) -> Self {
         Self {
             endpoint,
             pending_error: Default::default(),
             queued_events: Default::default(),
             inbound_connect: Default::default(),
-            keep_alive: KeepAlive::Until(Instant::now() + Duration::from_secs(30)),
+            keep_alive: KeepAlive::Until(clock.now() + keep_alive_timeout),
+            clock,
+            trace_recorder,
         }
     }
 
@@ -306,9 +318,10 @@ impl ConnectionHandler for Handler {
 
     fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
         match self.endpoint {
-            ConnectedPoint::Dialer { .. } => {
-                SubstreamProtocol::new(Either::Left(protocol::inbound::Upgrade {}), ())
-            }
+            ConnectedPoint::Dialer { .. } => SubstreamProtocol::new(
+                Either::Left(protocol::inbound::Upgrade::new(self.trace_recorder.clone())),
+                (),
+            ),
             ConnectedPoint::Listener { .. } => {
                 // By the protocol specification the listening side of a relayed connection
                 // initiates the _direct connection upgrade_. In other words the listening side of
@@ -326,7 +339,11 @@ impl ConnectionHandler for Handler {
                 self.queued_events
                     .push_back(ConnectionHandlerEvent::OutboundSubstreamRequest {
                         protocol: SubstreamProtocol::new(
-                            protocol::outbound::Upgrade::new(obs_addrs),
+                            protocol::outbound::Upgrade::new(
+                                obs_addrs,
+                                self.clock.clone(),
+                                self.trace_recorder.clone(),
+                            ),
                             attempt,
                         ),
                     });
@@ -26,6 +26,11 @@
 mod behaviour_impl; // TODO: Rename back `behaviour` once deprecation symbols are removed.
 mod handler;
 mod protocol;
+#[cfg(feature = "interop")]
+pub mod trace;
+#[cfg(not(feature = "interop"))]
+#[allow(dead_code)]
+mod trace;
 
 mod proto {
     include!("generated/mod.rs");
@@ -33,8 +38,11 @@ mod proto {
 }
 
 pub use behaviour_impl::Behaviour;
+pub use behaviour_impl::Config;
 pub use behaviour_impl::Error;
 pub use behaviour_impl::Event;
+pub use behaviour_impl::PeerOverrides;
+pub use behaviour_impl::Role;
 pub use protocol::PROTOCOL_NAME;
 pub mod inbound {
     pub use crate::protocol::inbound::UpgradeError;
@@ -24,3 +24,230 @@ pub mod outbound;
 pub const PROTOCOL_NAME: &[u8; 13] = b"/libp2p/dcutr";
 
 const MAX_MESSAGE_SIZE_BYTES: usize = 4096;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto;
+    use asynchronous_codec::{BytesMut, Decoder, Encoder};
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+    use futures::prelude::*;
+    use libp2p_core::Multiaddr;
+    use libp2p_swarm::SystemClock;
+    use std::io;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+
+    // The vectors are encoded by hand following `message.proto` and the multiaddr specification.
+    // They are not captured from sessions with other implementations.
+
+    /// `CONNECT` with the observed addresses `/ip4/198.51.100.1/tcp/4001` and
+    /// `/ip6/2001:db8::1/udp/4001/quic-v1`, prefixed with its length.
+    const CONNECT: &[u8] = &[
+        0x25, 0x08, 0x64, 0x12, 0x08, 0x04, 0xc6, 0x33, 0x64, 0x01, 0x06, 0x0f, 0xa1, 0x12, 0x17,
+        0x29, 0x20, 0x01, 0x0d, 0xb8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x01, 0x91, 0x02, 0x0f, 0xa1, 0xcd, 0x03,
+    ];
+
+    /// `CONNECT` with the observed addresses `/ip4/198.51.100.1/tcp/4001` and
+    /// `/ip4/203.0.113.7/tcp/4001/p2p-circuit`, prefixed with its length.
+    const CONNECT_WITH_RELAYED_ADDRESS: &[u8] = &[
+        0x18, 0x08, 0x64, 0x12, 0x08, 0x04, 0xc6, 0x33, 0x64, 0x01, 0x06, 0x0f, 0xa1, 0x12, 0x0a,
+        0x04, 0xcb, 0x00, 0x71, 0x07, 0x06, 0x0f, 0xa1, 0xa2, 0x02,
+    ];
+
+    /// `CONNECT` with the observed address `/ip4/192.0.2.1/tcp/4001`, prefixed with its length.
+    const CONNECT_SINGLE_ADDRESS: &[u8] = &[
+        0x0c, 0x08, 0x64, 0x12, 0x08, 0x04, 0xc0, 0x00, 0x02, 0x01, 0x06, 0x0f, 0xa1,
+    ];
+
+    /// `SYNC`, prefixed with its length.
+    const SYNC: &[u8] = &[0x03, 0x08, 0xac, 0x02];
+
+    fn obs_addrs() -> Vec<Multiaddr> {
+        vec![
+            "/ip4/198.51.100.1/tcp/4001".parse().unwrap(),
+            "/ip6/2001:db8::1/udp/4001/quic-v1".parse().unwrap(),
+        ]
+    }
+
+    fn encode(msg: proto::HolePunch) -> Vec<u8> {
+        let mut codec =
+            quick_protobuf_codec::Codec::<proto::HolePunch>::new(MAX_MESSAGE_SIZE_BYTES);
+        let mut bytes = BytesMut::new();
+        codec.encode(msg, &mut bytes).unwrap();
+        bytes.to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> proto::HolePunch {
+        let mut codec =
+            quick_protobuf_codec::Codec::<proto::HolePunch>::new(MAX_MESSAGE_SIZE_BYTES);
+        codec
+            .decode(&mut BytesMut::from(bytes))
+            .unwrap()
+            .expect("a complete message")
+    }
+
+    /// Replays the given bytes as sent by the remote and records the bytes sent to it.
+    struct Trace {
+        remote: Cursor<Vec<u8>>,
+        local: Vec<u8>,
+    }
+
+    impl Trace {
+        fn new(remote: &[&[u8]]) -> Self {
+            Self {
+                remote: Cursor::new(remote.concat()),
+                local: Vec::new(),
+            }
+        }
+    }
+
+    impl AsyncRead for Trace {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.remote).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for Trace {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.local.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn connect_wire_format() {
+        let msg = proto::HolePunch {
+            type_pb: proto::Type::CONNECT,
+            ObsAddrs: obs_addrs().into_iter().map(|a| a.to_vec()).collect(),
+        };
+
+        assert_eq!(encode(msg.clone()), CONNECT);
+        assert_eq!(decode(CONNECT), msg);
+    }
+
+    #[test]
+    fn sync_wire_format() {
+        let msg = proto::HolePunch {
+            type_pb: proto::Type::SYNC,
+            ObsAddrs: vec![],
+        };
+
+        assert_eq!(encode(msg.clone()), SYNC);
+        assert_eq!(decode(SYNC), msg);
+    }
+
+    #[test]
+    fn unknown_fields_are_ignored() {
+        // `SYNC` with an additional varint field numbered 3.
+        let msg = decode(&[0x05, 0x08, 0xac, 0x02, 0x18, 0x01]);
+
+        assert_eq!(msg.type_pb, proto::Type::SYNC);
+        assert!(msg.ObsAddrs.is_empty());
+    }
+
+    #[test]
+    fn outbound_exchange_matches_trace() {
+        let mut trace = Trace::new(&[CONNECT_WITH_RELAYED_ADDRESS]);
+
+        let connect = block_on(outbound::connect(
+            &mut trace,
+            obs_addrs(),
+            Arc::new(SystemClock),
+        ))
+        .unwrap();
+
+        assert_eq!(trace.local, [CONNECT, SYNC].concat());
+        // The relayed address of the remote is dropped.
+        assert_eq!(
+            connect.obs_addrs,
+            vec!["/ip4/198.51.100.1/tcp/4001".parse::<Multiaddr>().unwrap()]
+        );
+    }
+
+    #[test]
+    fn inbound_exchange_matches_trace() {
+        let mut trace = Trace::new(&[CONNECT, SYNC]);
+
+        let remote_obs_addrs = block_on(async {
+            let pending = inbound::read_connect(&mut trace).await?;
+            pending
+                .accept(vec!["/ip4/192.0.2.1/tcp/4001".parse().unwrap()])
+                .await
+        })
+        .unwrap();
+
+        assert_eq!(trace.local, CONNECT_SINGLE_ADDRESS);
+        assert_eq!(remote_obs_addrs, obs_addrs());
+    }
+
+    /// Replays the traces recorded by the interop binary in `tests/fixtures/<implementation>`,
+    /// sending the addresses the local node sent during the recorded session.
+    #[test]
+    fn exchanges_match_recorded_traces() {
+        let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let mut replayed = 0;
+        for dir in std::fs::read_dir(fixtures).unwrap() {
+            let dir = dir.unwrap().path();
+            for (name, recorded) in crate::trace::Trace::read_dir(&dir).unwrap() {
+                let local_obs_addrs = decode(&recorded.sent)
+                    .ObsAddrs
+                    .into_iter()
+                    .map(|a| Multiaddr::try_from(a.to_vec()).unwrap())
+                    .collect::<Vec<_>>();
+                let mut trace = Trace::new(&[&recorded.received]);
+
+                if recorded.initiator {
+                    block_on(outbound::connect(
+                        &mut trace,
+                        local_obs_addrs,
+                        Arc::new(SystemClock),
+                    ))
+                    .unwrap();
+                } else {
+                    block_on(async {
+                        let pending = inbound::read_connect(&mut trace).await?;
+                        pending.accept(local_obs_addrs).await
+                    })
+                    .unwrap();
+                }
+
+                assert_eq!(trace.local, recorded.sent, "{}: {name}", dir.display());
+                replayed += 1;
+            }
+        }
+        assert!(replayed > 0);
+    }
+
+    #[test]
+    fn inbound_rejects_sync_before_connect() {
+        // `SYNC` with the observed address `/ip4/198.51.100.1/tcp/4001`.
+        let mut trace = Trace::new(&[&[
+            0x0d, 0x08, 0xac, 0x02, 0x12, 0x08, 0x04, 0xc6, 0x33, 0x64, 0x01, 0x06, 0x0f, 0xa1,
+        ]]);
+
+        let error = block_on(inbound::read_connect(&mut trace)).err().unwrap();
+
+        assert!(matches!(error, inbound::UpgradeError::UnexpectedTypeSync));
+        assert!(trace.local.is_empty());
+    }
+}
@@ -19,6 +19,7 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::proto;
+use crate::trace::{Recording, TraceRecorder};
 use asynchronous_codec::Framed;
 use futures::{future::BoxFuture, prelude::*};
 use libp2p_core::{multiaddr::Protocol, upgrade, Multiaddr};
@@ -27,7 +28,15 @@ use std::convert::TryFrom;
 use std::iter;
 use thiserror::Error;
 
-pub struct Upgrade {}
+pub struct Upgrade {
+    trace_recorder: Option<TraceRecorder>,
+}
+
+impl Upgrade {
+    pub fn new(trace_recorder: Option<TraceRecorder>) -> Self {
+        Self { trace_recorder }
+    }
+}
 
 impl upgrade::UpgradeInfo for Upgrade {
     type Info = &'static [u8];
@@ -44,59 +53,67 @@ impl upgrade::InboundUpgrade<NegotiatedSubstream> for Upgrade {
     type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
 
     fn upgrade_inbound(self, substream: NegotiatedSubstream, _: Self::Info) -> Self::Future {
-        let mut substream = Framed::new(
-            substream,
-            quick_protobuf_codec::Codec::new(super::MAX_MESSAGE_SIZE_BYTES),
-        );
-
-        async move {
-            let proto::HolePunch { type_pb, ObsAddrs } =
-                substream.next().await.ok_or(UpgradeError::StreamClosed)??;
-
-            let obs_addrs = if ObsAddrs.is_empty() {
-                return Err(UpgradeError::NoAddresses);
-            } else {
-                ObsAddrs
-                    .into_iter()
-                    .filter_map(|a| match Multiaddr::try_from(a.to_vec()) {
-                        Ok(a) => Some(a),
-                        Err(e) => {
-                            log::debug!("Unable to parse multiaddr: {e}");
-                            None
-                        }
-                    })
-                    // Filter out relayed addresses.
-                    .filter(|a| {
-                        if a.iter().any(|p| p == Protocol::P2pCircuit) {
-                            log::debug!("Dropping relayed address {a}");
-                            false
-                        } else {
-                            true
-                        }
-                    })
-                    .collect::<Vec<Multiaddr>>()
-            };
-
-            match type_pb {
-                proto::Type::CONNECT => {}
-                proto::Type::SYNC => return Err(UpgradeError::UnexpectedTypeSync),
-            }
-
-            Ok(PendingConnect {
-                substream,
-                remote_obs_addrs: obs_addrs,
+        read_connect(Recording::new(substream, self.trace_recorder, false)).boxed()
+    }
+}
+
+/// Reads the `CONNECT` message of the remote initiating the direct connection upgrade.
+pub(crate) async fn read_connect<S>(substream: S) -> Result<PendingConnect<S>, UpgradeError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut substream = Framed::new(
+        substream,
+        quick_protobuf_codec::Codec::new(super::MAX_MESSAGE_SIZE_BYTES),
+    );
+
+    let proto::HolePunch { type_pb, ObsAddrs } =
+        substream.next().await.ok_or(UpgradeError::StreamClosed)??;
+
+    let obs_addrs = if ObsAddrs.is_empty() {
+        return Err(UpgradeError::NoAddresses);
+    } else {
+        ObsAddrs
+            .into_iter()
+            .filter_map(|a| match Multiaddr::try_from(a.to_vec()) {
+                Ok(a) => Some(a),
+                Err(e) => {
+                    log::debug!("Unable to parse multiaddr: {e}");
+                    None
+                }
             })
-        }
-        .boxed()
+            // Filter out relayed addresses.
+            .filter(|a| {
+                if a.iter().any(|p| p == Protocol::P2pCircuit) {
+                    log::debug!("Dropping relayed address {a}");
+                    false
+                } else {
+                    true
+                }
+            })
+            .collect::<Vec<Multiaddr>>()
+    };
+
+    match type_pb {
+        proto::Type::CONNECT => {}
+        proto::Type::SYNC => return Err(UpgradeError::UnexpectedTypeSync),
     }
+
+    Ok(PendingConnect {
+        substream,
+        remote_obs_addrs: obs_addrs,
+    })
 }
 
-pub struct PendingConnect {
-    substream: Framed<NegotiatedSubstream, quick_protobuf_codec::Codec<proto::HolePunch>>,
+pub struct PendingConnect<S = Recording<NegotiatedSubstream>> {
+    substream: Framed<S, quick_protobuf_codec::Codec<proto::HolePunch>>,
     remote_obs_addrs: Vec<Multiaddr>,
 }
 
-impl PendingConnect {
+impl<S> PendingConnect<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
     pub async fn accept(
         mut self,
         local_obs_addrs: Vec<Multiaddr>,
@@ -19,18 +19,20 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::proto;
+use crate::trace::{Recording, TraceRecorder};
 use asynchronous_codec::Framed;
 use futures::{future::BoxFuture, prelude::*};
-use futures_timer::Delay;
-use instant::Instant;
 use libp2p_core::{multiaddr::Protocol, upgrade, Multiaddr};
-use libp2p_swarm::NegotiatedSubstream;
+use libp2p_swarm::{Clock, NegotiatedSubstream};
 use std::convert::TryFrom;
 use std::iter;
+use std::sync::Arc;
 use thiserror::Error;
 
 pub struct Upgrade {
     obs_addrs: Vec<Multiaddr>,
+    clock: Arc<dyn Clock>,
+    trace_recorder: Option<TraceRecorder>,
 }
 
 impl upgrade::UpgradeInfo for Upgrade {
@@ -43,8 +45,16 @@ impl upgrade::UpgradeInfo for Upgrade {
 }
 
 impl Upgrade {
-    pub fn new(obs_addrs: Vec<Multiaddr>) -> Self {
-        Self { obs_addrs }
+    pub fn new(
+        obs_addrs: Vec<Multiaddr>,
+        clock: Arc<dyn Clock>,
+        trace_recorder: Option<TraceRecorder>,
+    ) -> Self {
+        Self {
+            obs_addrs,
+            clock,
+            trace_recorder,
+        }
     }
 }
 
@@ -54,70 +64,86 @@ impl upgrade::OutboundUpgrade<NegotiatedSubstream> for Upgrade {
     type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
 
     fn upgrade_outbound(self, substream: NegotiatedSubstream, _: Self::Info) -> Self::Future {
-        let mut substream = Framed::new(
-            substream,
-            quick_protobuf_codec::Codec::new(super::MAX_MESSAGE_SIZE_BYTES),
-        );
-
-        let msg = proto::HolePunch {
-            type_pb: proto::Type::CONNECT,
-            ObsAddrs: self.obs_addrs.into_iter().map(|a| a.to_vec()).collect(),
-        };
-
-        async move {
-            substream.send(msg).await?;
-
-            let sent_time = Instant::now();
-
-            let proto::HolePunch { type_pb, ObsAddrs } =
-                substream.next().await.ok_or(UpgradeError::StreamClosed)??;
-
-            let rtt = sent_time.elapsed();
-
-            match type_pb {
-                proto::Type::CONNECT => {}
-                proto::Type::SYNC => return Err(UpgradeError::UnexpectedTypeSync),
-            }
-
-            let obs_addrs = if ObsAddrs.is_empty() {
-                return Err(UpgradeError::NoAddresses);
-            } else {
-                ObsAddrs
-                    .into_iter()
-                    .filter_map(|a| match Multiaddr::try_from(a.to_vec()) {
-                        Ok(a) => Some(a),
-                        Err(e) => {
-                            log::debug!("Unable to parse multiaddr: {e}");
-                            None
-                        }
-                    })
-                    // Filter out relayed addresses.
-                    .filter(|a| {
-                        if a.iter().any(|p| p == Protocol::P2pCircuit) {
-                            log::debug!("Dropping relayed address {a}");
-                            false
-                        } else {
-                            true
-                        }
-                    })
-                    .collect::<Vec<Multiaddr>>()
-            };
-
-            let msg = proto::HolePunch {
-                type_pb: proto::Type::SYNC,
-                ObsAddrs: vec![],
-            };
-
-            substream.send(msg).await?;
-
-            Delay::new(rtt / 2).await;
-
-            Ok(Connect { obs_addrs })
-        }
+        connect(
+            Recording::new(substream, self.trace_recorder, true),
+            self.obs_addrs,
+            self.clock,
+        )
         .boxed()
     }
 }
 
+/// Exchanges the `CONNECT` and `SYNC` messages initiating a direct connection upgrade.
+///
+/// The round-trip time and the wait for the remote to receive the `SYNC` are measured by `clock`.
+pub(crate) async fn connect<S>(
+    substream: S,
+    obs_addrs: Vec<Multiaddr>,
+    clock: Arc<dyn Clock>,
+) -> Result<Connect, UpgradeError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut substream = Framed::new(
+        substream,
+        quick_protobuf_codec::Codec::new(super::MAX_MESSAGE_SIZE_BYTES),
+    );
+
+    let msg = proto::HolePunch {
+        type_pb: proto::Type::CONNECT,
+        ObsAddrs: obs_addrs.into_iter().map(|a| a.to_vec()).collect(),
+    };
+
+    substream.send(msg).await?;
+
+    let sent_time = clock.now();
+
+    let proto::HolePunch { type_pb, ObsAddrs } =
+        substream.next().await.ok_or(UpgradeError::StreamClosed)??;
+
+    let rtt = clock.now().saturating_duration_since(sent_time);
+
+    match type_pb {
+        proto::Type::CONNECT => {}
+        proto::Type::SYNC => return Err(UpgradeError::UnexpectedTypeSync),
+    }
+
+    let obs_addrs = if ObsAddrs.is_empty() {
+        return Err(UpgradeError::NoAddresses);
+    } else {
+        ObsAddrs
+            .into_iter()
+            .filter_map(|a| match Multiaddr::try_from(a.to_vec()) {
+                Ok(a) => Some(a),
+                Err(e) => {
+                    log::debug!("Unable to parse multiaddr: {e}");
+                    None
+                }
+            })
+            // Filter out relayed addresses.
+            .filter(|a| {
+                if a.iter().any(|p| p == Protocol::P2pCircuit) {
+                    log::debug!("Dropping relayed address {a}");
+                    false
+                } else {
+                    true
+                }
+            })
+            .collect::<Vec<Multiaddr>>()
+    };
+
+    let msg = proto::HolePunch {
+        type_pb: proto::Type::SYNC,
+        ObsAddrs: vec![],
+    };
+
+    substream.send(msg).await?;
+
+    clock.timer(rtt / 2).await;
+
+    Ok(Connect { obs_addrs })
+}
+
 pub struct Connect {
     pub obs_addrs: Vec<Multiaddr>,
 }
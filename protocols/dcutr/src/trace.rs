@@ -0,0 +1,176 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Recording of the bytes exchanged on DCUtR substreams, e.g. to capture traces of sessions with
+//! other implementations, see [`Config::trace_recorder`](crate::Config::trace_recorder).
+
+use futures::io::{AsyncRead, AsyncWrite};
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+/// Collects the [`Trace`]s of the DCUtR substreams of a [`Behaviour`](crate::Behaviour).
+#[derive(Debug, Clone, Default)]
+pub struct TraceRecorder(Arc<Mutex<Vec<Trace>>>);
+
+impl TraceRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes the traces of the substreams dropped since the last call.
+    pub fn take(&self) -> Vec<Trace> {
+        std::mem::take(&mut self.0.lock().expect("not poisoned"))
+    }
+}
+
+/// The bytes exchanged on a DCUtR substream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    /// Whether the local node initiated the upgrade, i.e. sent the first `CONNECT`.
+    pub initiator: bool,
+    /// The bytes sent by the local node.
+    pub sent: Vec<u8>,
+    /// The bytes received from the remote.
+    pub received: Vec<u8>,
+}
+
+impl Trace {
+    /// Writes the trace to `<dir>/<role>-<name>.sent` and `<dir>/<role>-<name>.received`, with
+    /// the role being either `initiator` or `responder`.
+    pub fn write_to(&self, dir: &Path, name: &str) -> io::Result<()> {
+        let stem = format!("{}-{name}", role(self.initiator));
+        std::fs::write(dir.join(format!("{stem}.sent")), &self.sent)?;
+        std::fs::write(dir.join(format!("{stem}.received")), &self.received)
+    }
+
+    /// Reads all traces written to `dir` via [`Trace::write_to`], by their file stems.
+    pub fn read_dir(dir: &Path) -> io::Result<Vec<(String, Trace)>> {
+        let mut traces = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(true, |ext| ext != "sent") {
+                continue;
+            }
+            let stem = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid file name"))?
+                .to_owned();
+            let initiator = if stem.starts_with(role(true)) {
+                true
+            } else if stem.starts_with(role(false)) {
+                false
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unknown role of trace {stem}"),
+                ));
+            };
+            let trace = Trace {
+                initiator,
+                sent: std::fs::read(&path)?,
+                received: std::fs::read(path.with_extension("received"))?,
+            };
+            traces.push((stem, trace));
+        }
+        traces.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(traces)
+    }
+}
+
+fn role(initiator: bool) -> &'static str {
+    if initiator {
+        "initiator"
+    } else {
+        "responder"
+    }
+}
+
+/// A substream recording the bytes read and written into a [`Trace`], passed to the
+/// [`TraceRecorder`] once dropped.
+pub struct Recording<S> {
+    inner: S,
+    trace: Option<(TraceRecorder, Trace)>,
+}
+
+impl<S> Recording<S> {
+    pub(crate) fn new(inner: S, recorder: Option<TraceRecorder>, initiator: bool) -> Self {
+        Self {
+            inner,
+            trace: recorder.map(|recorder| {
+                let trace = Trace {
+                    initiator,
+                    sent: Vec::new(),
+                    received: Vec::new(),
+                };
+                (recorder, trace)
+            }),
+        }
+    }
+}
+
+impl<S> Drop for Recording<S> {
+    fn drop(&mut self) {
+        if let Some((recorder, trace)) = self.trace.take() {
+            recorder.0.lock().expect("not poisoned").push(trace);
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Recording<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let (Poll::Ready(Ok(n)), Some((_, trace))) = (&poll, &mut this.trace) {
+            trace.received.extend_from_slice(&buf[..*n]);
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Recording<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let (Poll::Ready(Ok(n)), Some((_, trace))) = (&poll, &mut this.trace) {
+            trace.sent.extend_from_slice(&buf[..*n]);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
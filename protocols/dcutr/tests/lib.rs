@@ -35,8 +35,8 @@ async fn connect() {
     let _ = env_logger::try_init();
 
     let mut relay = build_relay();
-    let mut dst = build_client();
-    let mut src = build_client();
+    let mut dst = build_client(dcutr::Config::default());
+    let mut src = build_client(dcutr::Config::default());
 
     // Have all swarms listen on a local memory address.
     let (relay_addr, _) = relay.listen().await;
@@ -95,6 +95,49 @@ async fn connect() {
     .await;
 }
 
+#[async_std::test]
+async fn connect_with_simultaneous_open_roles() {
+    let _ = env_logger::try_init();
+
+    let mut relay = build_relay();
+    let mut dst = build_client(dcutr::Config::default().simultaneous_open(true));
+    let mut src = build_client(dcutr::Config::default().simultaneous_open(true));
+
+    let (relay_addr, _) = relay.listen().await;
+    dst.listen().await;
+    src.listen().await;
+
+    let relay_peer_id = *relay.local_peer_id();
+    let dst_peer_id = *dst.local_peer_id();
+
+    async_std::task::spawn(relay.loop_on_next());
+
+    let dst_relayed_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit)
+        .with(Protocol::P2p(dst_peer_id.into()));
+    dst.listen_on(dst_relayed_addr.clone()).unwrap();
+
+    wait_for_reservation(&mut dst, dst_relayed_addr.clone(), relay_peer_id, false).await;
+    async_std::task::spawn(dst.loop_on_next());
+
+    src.dial_and_wait(dst_relayed_addr).await;
+
+    // Only one of the two dials of the hole punch upgrades with complementary roles.
+    src.wait(move |e| match e {
+        SwarmEvent::ConnectionEstablished {
+            peer_id, endpoint, ..
+        } => (peer_id == dst_peer_id
+            && !endpoint
+                .get_remote_address()
+                .iter()
+                .any(|p| p == Protocol::P2pCircuit))
+        .then_some(()),
+        _ => None,
+    })
+    .await;
+}
+
 fn build_relay() -> Swarm<relay::Behaviour> {
     Swarm::new_ephemeral(|identity| {
         let local_peer_id = identity.public().to_peer_id();
@@ -109,7 +152,42 @@ fn build_relay() -> Swarm<relay::Behaviour> {
     })
 }
 
-fn build_client() -> Swarm<Client> {
+#[async_std::test]
+async fn responder_only_never_initiates_upgrade() {
+    let _ = env_logger::try_init();
+
+    let mut relay = build_relay();
+    let mut dst = build_client(dcutr::Config::default().role(dcutr::Role::ResponderOnly));
+    let mut src = build_client(dcutr::Config::default());
+
+    let (relay_addr, _) = relay.listen().await;
+    dst.listen().await;
+    src.listen().await;
+
+    let relay_peer_id = *relay.local_peer_id();
+    let dst_peer_id = *dst.local_peer_id();
+
+    async_std::task::spawn(relay.loop_on_next());
+
+    let dst_relayed_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit)
+        .with(Protocol::P2p(dst_peer_id.into()));
+    dst.listen_on(dst_relayed_addr.clone()).unwrap();
+
+    wait_for_reservation(&mut dst, dst_relayed_addr.clone(), relay_peer_id, false).await;
+    async_std::task::spawn(dst.loop_on_next());
+
+    src.dial_and_wait(dst_relayed_addr).await;
+
+    let event = async_std::future::timeout(Duration::from_secs(2), src.next_swarm_event()).await;
+    assert!(
+        event.is_err(),
+        "Expected no upgrade to be initiated, got {event:?}."
+    );
+}
+
+fn build_client(config: dcutr::Config) -> Swarm<Client> {
     let local_key = identity::Keypair::generate_ed25519();
     let local_public_key = local_key.public();
     let local_peer_id = local_public_key.to_peer_id();
@@ -128,7 +206,7 @@ fn build_client() -> Swarm<Client> {
         transport,
         Client {
             relay: behaviour,
-            dcutr: dcutr::Behaviour::new(local_peer_id),
+            dcutr: dcutr::Behaviour::with_config(local_peer_id, config),
         },
         local_peer_id,
     )
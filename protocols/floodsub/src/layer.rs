@@ -495,7 +495,10 @@ impl NetworkBehaviour for Floodsub {
             | FromSwarm::ListenerError(_)
             | FromSwarm::ListenerClosed(_)
             | FromSwarm::NewExternalAddr(_)
-            | FromSwarm::ExpiredExternalAddr(_) => {}
+            | FromSwarm::ExpiredExternalAddr(_)
+            | FromSwarm::PeerProtocolsChanged(_)
+            | FromSwarm::PeerListenAddrsChanged(_)
+            | FromSwarm::NatStatusChanged(_) => {}
         }
     }
 }
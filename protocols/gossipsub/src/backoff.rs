@@ -20,13 +20,15 @@
 
 //! Data structure for efficiently storing known back-off's when pruning peers.
 use crate::topic::TopicHash;
+use instant::Instant;
 use libp2p_identity::PeerId;
+use libp2p_swarm::{Clock, SystemClock};
 use std::collections::{
     hash_map::{Entry, HashMap},
     HashSet,
 };
+use std::sync::Arc;
 use std::time::Duration;
-use wasm_timer::Instant;
 
 #[derive(Copy, Clone)]
 struct HeartbeatIndex(usize);
@@ -44,6 +46,8 @@ pub struct BackoffStorage {
     heartbeat_interval: Duration,
     /// Backoff slack from the config.
     backoff_slack: u32,
+    /// The clock the backoffs expire by.
+    clock: Arc<dyn Clock>,
 }
 
 impl BackoffStorage {
@@ -66,13 +70,20 @@ impl BackoffStorage {
             heartbeat_index: HeartbeatIndex(0),
             heartbeat_interval,
             backoff_slack,
+            clock: Arc::new(SystemClock),
         }
     }
 
+    /// Lets the backoffs expire by the given [`Clock`] instead of the [`SystemClock`].
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Updates the backoff for a peer (if there is already a more restrictive backoff then this call
     /// doesn't change anything).
     pub fn update_backoff(&mut self, topic: &TopicHash, peer: &PeerId, time: Duration) {
-        let instant = Instant::now() + time;
+        let instant = self.clock.now() + time;
         let insert_into_backoffs_by_heartbeat =
             |heartbeat_index: HeartbeatIndex,
              backoffs_by_heartbeat: &mut Vec<HashSet<_>>,
@@ -154,7 +165,7 @@ impl BackoffStorage {
         if let Some(s) = self.backoffs_by_heartbeat.get_mut(self.heartbeat_index.0) {
             let backoffs = &mut self.backoffs;
             let slack = self.heartbeat_interval * self.backoff_slack;
-            let now = Instant::now();
+            let now = self.clock.now();
             s.retain(|(topic, peer)| {
                 let keep = match Self::get_backoff_time_from_backoffs(backoffs, topic, peer) {
                     Some(backoff_time) => backoff_time + slack > now,
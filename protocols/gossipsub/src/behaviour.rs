@@ -29,45 +29,47 @@ use std::{
     time::Duration,
 };
 
-use futures::StreamExt;
 use log::{debug, error, trace, warn};
 use prometheus_client::registry::Registry;
 use rand::{seq::SliceRandom, thread_rng};
 
+use instant::Instant;
 use libp2p_core::{multiaddr::Protocol::Ip4, multiaddr::Protocol::Ip6, Endpoint, Multiaddr};
 use libp2p_identity::Keypair;
 use libp2p_identity::PeerId;
 use libp2p_swarm::{
     behaviour::{AddressChange, ConnectionClosed, ConnectionEstablished, FromSwarm},
-    dial_opts::DialOpts,
+    dial_opts::{DialOpts, PeerCondition},
     ConnectionDenied, ConnectionId, NetworkBehaviour, NotifyHandler, PollParameters, THandler,
     THandlerInEvent, THandlerOutEvent, ToSwarm,
 };
-use wasm_timer::Instant;
 
 use crate::backoff::BackoffStorage;
-use crate::config::{Config, ValidationMode};
+use crate::config::{Config, DuplicateSuppression, SelfDelivery, ValidationMode};
+use crate::discovery::{DiscoveredPeers, TopicDiscovery};
 use crate::gossip_promises::GossipPromises;
 use crate::handler::{Handler, HandlerEvent, HandlerIn};
 use crate::mcache::MessageCache;
 use crate::metrics::{Churn, Config as MetricsConfig, Inclusion, Metrics, Penalty};
+use crate::ordering::MessageOrdering;
 use crate::peer_score::{PeerScore, PeerScoreParams, PeerScoreThresholds, RejectReason};
 use crate::protocol::{ProtocolConfig, SIGNING_PREFIX};
+use crate::rate_limiter::{RateLimitOutcome, RateLimiter};
 use crate::subscription_filter::{AllowAllSubscriptionFilter, TopicSubscriptionFilter};
+use crate::ticker::Ticker;
 use crate::time_cache::{DuplicateCache, TimeCache};
 use crate::topic::{Hasher, Topic, TopicHash};
 use crate::transform::{DataTransform, IdentityTransform};
 use crate::types::{
-    ControlAction, FastMessageId, Message, MessageAcceptance, MessageId, PeerInfo, RawMessage,
-    Subscription, SubscriptionAction,
+    ControlAction, DroppedMessages, FastMessageId, Message, MessageAcceptance, MessageId, PeerInfo,
+    RawMessage, Subscription, SubscriptionAction,
 };
-use crate::types::{PeerConnections, PeerKind, Rpc};
+use crate::types::{Dump, PeerConnections, PeerDump, PeerKind, Published, Rpc};
 use crate::{rpc_proto::proto, TopicScoreParams};
 use crate::{PublishError, SubscriptionError, ValidationError};
 use instant::SystemTime;
 use quick_protobuf::{MessageWrite, Writer};
 use std::{cmp::Ordering::Equal, fmt::Debug};
-use wasm_timer::Interval;
 
 #[cfg(test)]
 mod tests;
@@ -129,6 +131,17 @@ pub enum Event {
         /// The decompressed message itself.
         message: Message,
     },
+    /// A message has been published by the local node on a subscribed topic and is delivered
+    /// locally, see [`SelfDelivery::Local`].
+    LocalMessage {
+        /// The [`MessageId`] of the message.
+        message_id: MessageId,
+        /// The message itself.
+        message: Message,
+        /// The origin given to [`Behaviour::publish_with_origin`], or else the configured
+        /// [`Config::origin_label`].
+        origin: Option<String>,
+    },
     /// A remote subscribed to a topic.
     Subscribed {
         /// Remote that has subscribed.
@@ -145,6 +158,40 @@ pub enum Event {
     },
     /// A peer that does not support gossipsub has connected.
     GossipsubNotSupported { peer_id: PeerId },
+    /// A peer exceeded the [`Config::message_rate_limit`] on a topic and its messages on the
+    /// topic are dropped.
+    ///
+    /// Emitted once per series of dropped messages, i.e. again only after a message of the peer
+    /// on the topic has been accepted in between.
+    MessageRateLimited {
+        /// The peer sending too many messages.
+        peer_id: PeerId,
+        /// The topic of the messages.
+        topic: TopicHash,
+    },
+    /// Publishing on a topic failed with [`PublishError::InsufficientPeers`] or
+    /// [`PublishError::QueueFull`], but the topic now has peers to publish to.
+    ///
+    /// Emitted once per failing series of publishes on the topic.
+    TopicPublishable {
+        /// The topic that can be published on.
+        topic: TopicHash,
+    },
+    /// A message received from a peer has been dropped.
+    ///
+    /// Not emitted for messages ignored via [`Behaviour::report_message_validation_result`] with
+    /// [`MessageAcceptance::Ignore`].
+    MessageRejected {
+        /// The peer that forwarded us the message.
+        propagation_source: PeerId,
+        /// The [`MessageId`] of the message, if it could be determined.
+        ///
+        /// `None` if the message could not be decoded or transformed, or if the RPC containing it
+        /// was discarded for exceeding [`Config::max_transmit_size`].
+        message_id: Option<MessageId>,
+        /// Why the message has been dropped.
+        reason: RejectReason,
+    },
 }
 
 /// A data structure for storing configuration for publishing messages. See [`MessageAuthenticity`]
@@ -255,6 +302,10 @@ pub struct Behaviour<D = IdentityTransform, F = AllowAllSubscriptionFilter> {
     /// duplicates from being propagated to the application and on the network.
     duplicate_cache: DuplicateCache<MessageId>,
 
+    /// The keys of recently received messages, if duplicates are suppressed by another key than
+    /// their ID. See [`crate::ConfigBuilder::duplicate_suppression`].
+    suppression_cache: Option<(DuplicateSuppression, DuplicateCache<Vec<u8>>)>,
+
     /// A set of connected peers, indexed by their [`PeerId`] tracking both the [`PeerKind`] and
     /// the set of [`ConnectionId`]s.
     connected_peers: HashMap<PeerId, PeerConnections>,
@@ -282,14 +333,30 @@ pub struct Behaviour<D = IdentityTransform, F = AllowAllSubscriptionFilter> {
     /// The last publish time for fanout topics.
     fanout_last_pub: HashMap<TopicHash, Instant>,
 
+    /// Peers pinned into the mesh of a topic by the application, see
+    /// [`Behaviour::pin_mesh_peer`].
+    pinned_mesh_peers: HashMap<TopicHash, BTreeSet<PeerId>>,
+
+    /// Mesh peers that disconnected, with the topics of the meshes they were part of and until
+    /// when they are grafted back into those upon reconnecting, see
+    /// [`Config::mesh_reconnect_grace`].
+    disconnected_mesh_peers: HashMap<PeerId, (BTreeSet<TopicHash>, Instant)>,
+
+    /// Peers whose send queue dropped published or forwarded messages and has not drained since.
+    congested_peers: HashSet<PeerId>,
+
+    /// Topics on which publishing failed for a lack of peers to send to, see
+    /// [`Event::TopicPublishable`].
+    unpublishable_topics: HashSet<TopicHash>,
+
     ///Storage for backoffs
     backoffs: BackoffStorage,
 
     /// Message cache for the last few heartbeats.
     mcache: MessageCache,
 
-    /// Heartbeat interval stream.
-    heartbeat: Interval,
+    /// Timer of the heartbeats.
+    heartbeat: Ticker,
 
     /// Number of heartbeats since the beginning of time; this allows us to amortize some resource
     /// clean up -- eg backoff clean up.
@@ -307,7 +374,13 @@ pub struct Behaviour<D = IdentityTransform, F = AllowAllSubscriptionFilter> {
 
     /// Stores optional peer score data together with thresholds, decay interval and gossip
     /// promises.
-    peer_score: Option<(PeerScore, PeerScoreThresholds, Interval, GossipPromises)>,
+    peer_score: Option<(PeerScore, PeerScoreThresholds, Ticker, GossipPromises)>,
+
+    /// Limits the messages accepted from each peer on each topic, if configured.
+    rate_limiter: Option<RateLimiter>,
+
+    /// Holds back out-of-order messages on the topics with ordered delivery.
+    ordering: MessageOrdering<Event>,
 
     /// Counts the number of `IHAVE` received from each peer since the last heartbeat.
     count_received_ihave: HashMap<PeerId, usize>,
@@ -329,6 +402,15 @@ pub struct Behaviour<D = IdentityTransform, F = AllowAllSubscriptionFilter> {
     /// The filter used to handle message subscriptions.
     subscription_filter: F,
 
+    /// Source of peers for topics whose mesh runs low.
+    ///
+    /// See [`Behaviour::set_topic_discovery`].
+    topic_discovery: Option<Box<dyn TopicDiscovery + Send>>,
+
+    /// The heartbeat tick at which the last discovery of peers was started, for each topic whose
+    /// mesh is low.
+    topic_discoveries: HashMap<TopicHash, u64>,
+
     /// A general transformation function that can be applied to data received from the wire before
     /// calculating the message-id and sending to the application. This is designed to allow the
     /// user to implement arbitrary topic-based compression algorithms.
@@ -445,8 +527,16 @@ where
             events: VecDeque::new(),
             control_pool: HashMap::new(),
             publish_config: privacy.into(),
-            duplicate_cache: DuplicateCache::new(config.duplicate_cache_time()),
-            fast_message_id_cache: TimeCache::new(config.duplicate_cache_time()),
+            duplicate_cache: DuplicateCache::new(config.duplicate_cache_time())
+                .with_clock(config.shared_clock()),
+            fast_message_id_cache: TimeCache::new(config.duplicate_cache_time())
+                .with_clock(config.shared_clock()),
+            suppression_cache: config.duplicate_suppression().map(|suppression| {
+                (
+                    suppression,
+                    DuplicateCache::new(suppression.window()).with_clock(config.shared_clock()),
+                )
+            }),
             topic_peers: HashMap::new(),
             peer_topics: HashMap::new(),
             explicit_peers: HashSet::new(),
@@ -454,28 +544,48 @@ where
             mesh: HashMap::new(),
             fanout: HashMap::new(),
             fanout_last_pub: HashMap::new(),
+            pinned_mesh_peers: HashMap::new(),
+            disconnected_mesh_peers: HashMap::new(),
+            congested_peers: HashSet::new(),
+            unpublishable_topics: HashSet::new(),
             backoffs: BackoffStorage::new(
                 &config.prune_backoff(),
                 config.heartbeat_interval(),
                 config.backoff_slack(),
-            ),
-            mcache: MessageCache::new(config.history_gossip(), config.history_length()),
-            heartbeat: Interval::new_at(
-                Instant::now() + config.heartbeat_initial_delay(),
+            )
+            .with_clock(config.shared_clock()),
+            mcache: MessageCache::new(config.history_gossip(), config.history_length())
+                .with_max_bytes(config.history_max_bytes()),
+            heartbeat: Ticker::new(
+                config.shared_clock(),
+                config.heartbeat_initial_delay(),
                 config.heartbeat_interval(),
             ),
             heartbeat_ticks: 0,
             px_peers: HashSet::new(),
             outbound_peers: HashSet::new(),
             peer_score: None,
+            rate_limiter: config
+                .message_rate_limit()
+                .map(|(capacity, refill_interval)| {
+                    RateLimiter::new(capacity, refill_interval).with_clock(config.shared_clock())
+                }),
+            ordering: MessageOrdering::new(
+                config.ordered_topics().clone(),
+                config.duplicate_cache_time(),
+            )
+            .with_clock(config.shared_clock()),
             count_received_ihave: HashMap::new(),
             count_sent_iwant: HashMap::new(),
             pending_iwant_msgs: HashSet::new(),
             connected_peers: HashMap::new(),
-            published_message_ids: DuplicateCache::new(config.published_message_ids_cache_time()),
+            published_message_ids: DuplicateCache::new(config.published_message_ids_cache_time())
+                .with_clock(config.shared_clock()),
             config,
             subscription_filter,
             data_transform,
+            topic_discovery: None,
+            topic_discoveries: HashMap::new(),
         })
     }
 }
@@ -495,6 +605,17 @@ where
         self.mesh.get(topic_hash).into_iter().flat_map(|x| x.iter())
     }
 
+    /// Lists all fanout peers for a certain topic hash.
+    ///
+    /// Fanout peers are maintained for topics we publish to without being subscribed.
+    pub fn fanout_peers(&self, topic_hash: &TopicHash) -> impl Iterator<Item = &PeerId> {
+        self.fanout
+            .get(topic_hash)
+            .into_iter()
+            .flat_map(|x| x.iter())
+    }
+
+    /// Lists all mesh peers across all subscribed topics.
     pub fn all_mesh_peers(&self) -> impl Iterator<Item = &PeerId> {
         let mut res = BTreeSet::new();
         for peers in self.mesh.values() {
@@ -510,11 +631,55 @@ where
             .map(|(peer_id, topic_set)| (peer_id, topic_set.iter().collect()))
     }
 
+    /// Lists all topics known to be subscribed to by peers, with the subscribed peers.
+    pub fn all_peers_with_topics(&self) -> impl Iterator<Item = (&TopicHash, Vec<&PeerId>)> {
+        self.topic_peers
+            .iter()
+            .map(|(topic, peer_set)| (topic, peer_set.iter().collect()))
+    }
+
+    /// Returns a snapshot of the mesh, the fanout and the state of all known peers.
+    ///
+    /// Intended to verify mesh formation and debug message propagation.
+    pub fn dump(&self) -> Dump {
+        let peers = self
+            .connected_peers
+            .iter()
+            .map(|(peer_id, connections)| {
+                let peer = PeerDump {
+                    kind: connections.kind.clone(),
+                    connections: connections.connections.len(),
+                    topics: self.peer_topics.get(peer_id).cloned().unwrap_or_default(),
+                    explicit: self.explicit_peers.contains(peer_id),
+                    outbound: self.outbound_peers.contains(peer_id),
+                    score: self.peer_score(peer_id),
+                    dropped_messages: connections.dropped_messages,
+                };
+                (*peer_id, peer)
+            })
+            .collect();
+
+        Dump {
+            mesh: self.mesh.clone(),
+            fanout: self.fanout.clone(),
+            peers,
+            heartbeat_ticks: self.heartbeat_ticks,
+        }
+    }
+
     /// Lists all known peers and their associated protocol.
     pub fn peer_protocol(&self) -> impl Iterator<Item = (&PeerId, &PeerKind)> {
         self.connected_peers.iter().map(|(k, v)| (k, &v.kind))
     }
 
+    /// Returns the number of outbound messages dropped for a connected peer because the send
+    /// queues of its connections were full.
+    pub fn dropped_messages(&self, peer_id: &PeerId) -> Option<DroppedMessages> {
+        self.connected_peers
+            .get(peer_id)
+            .map(|peer| peer.dropped_messages)
+    }
+
     /// Returns the gossipsub score for a given peer, if one exists.
     pub fn peer_score(&self, peer_id: &PeerId) -> Option<f64> {
         self.peer_score
@@ -611,9 +776,45 @@ where
         topic: impl Into<TopicHash>,
         data: impl Into<Vec<u8>>,
     ) -> Result<MessageId, PublishError> {
-        let data = data.into();
-        let topic = topic.into();
+        self.publish_inner(topic.into(), data.into(), None)
+            .map(|published| published.message_id)
+    }
+
+    /// Publishes a message like [`Behaviour::publish`], reporting the number of peers it was
+    /// sent to.
+    ///
+    /// Fails with [`PublishError::InsufficientPeers`] if there are no peers to send the message to
+    /// and with [`PublishError::QueueFull`] if the send queues of all of them are full.
+    /// [`Event::TopicPublishable`] reports when it is worth retrying.
+    pub fn publish_with_outcome(
+        &mut self,
+        topic: impl Into<TopicHash>,
+        data: impl Into<Vec<u8>>,
+    ) -> Result<Published, PublishError> {
+        self.publish_inner(topic.into(), data.into(), None)
+    }
 
+    /// Publishes a message like [`Behaviour::publish`], labelling it with the given origin
+    /// instead of the configured [`Config::origin_label`] if it is delivered locally.
+    ///
+    /// The origin is never sent to the network. It allows processes that bridge messages between
+    /// several swarms to recognize the messages they republished themselves.
+    pub fn publish_with_origin(
+        &mut self,
+        topic: impl Into<TopicHash>,
+        data: impl Into<Vec<u8>>,
+        origin: impl Into<String>,
+    ) -> Result<MessageId, PublishError> {
+        self.publish_inner(topic.into(), data.into(), Some(origin.into()))
+            .map(|published| published.message_id)
+    }
+
+    fn publish_inner(
+        &mut self,
+        topic: TopicHash,
+        data: Vec<u8>,
+        origin: Option<String>,
+    ) -> Result<Published, PublishError> {
         // Transform the data before building a raw_message.
         let transformed_data = self
             .data_transform
@@ -622,12 +823,13 @@ where
         let raw_message = self.build_raw_message(topic, transformed_data)?;
 
         // calculate the message id from the un-transformed data
-        let msg_id = self.config.message_id(&Message {
+        let message = Message {
             source: raw_message.source,
             data, // the uncompressed form
             sequence_number: raw_message.sequence_number,
             topic: raw_message.topic.clone(),
-        });
+        };
+        let msg_id = self.config.message_id(&message);
 
         let event = Rpc {
             subscriptions: Vec::new(),
@@ -656,11 +858,11 @@ where
 
         let topic_hash = raw_message.topic.clone();
 
-        // If we are not flood publishing forward the message to mesh peers.
-        let mesh_peers_sent = !self.config.flood_publish()
-            && self.forward_msg(&msg_id, raw_message.clone(), None, HashSet::new())?;
-
         let mut recipient_peers = HashSet::new();
+        // If we are not flood publishing send the message to mesh peers.
+        if !self.config.flood_publish() {
+            recipient_peers.extend(self.mesh_recipients(&raw_message, None, &HashSet::new()));
+        }
         if let Some(set) = self.topic_peers.get(&topic_hash) {
             if self.config.flood_publish() {
                 // Forward to all peers above score and all explicit peers
@@ -725,14 +927,22 @@ where
                     }
                     // We are publishing to fanout peers - update the time we published
                     self.fanout_last_pub
-                        .insert(topic_hash.clone(), Instant::now());
+                        .insert(topic_hash.clone(), self.config.clock().now());
                 }
             }
         }
 
-        if recipient_peers.is_empty() && !mesh_peers_sent {
+        if recipient_peers.is_empty() {
+            self.unpublishable_topics.insert(topic_hash);
             return Err(PublishError::InsufficientPeers);
         }
+        if recipient_peers
+            .iter()
+            .all(|p| self.congested_peers.contains(p))
+        {
+            self.unpublishable_topics.insert(topic_hash);
+            return Err(PublishError::QueueFull);
+        }
 
         // If the message isn't a duplicate and we have sent it to some peers add it to the
         // duplicate cache and memcache.
@@ -742,7 +952,9 @@ where
         // If the message is anonymous or has a random author add it to the published message ids
         // cache.
         if let PublishConfig::RandomAuthor | PublishConfig::Anonymous = self.publish_config {
-            if !self.config.allow_self_origin() {
+            if !self.config.allow_self_origin()
+                || self.config.self_delivery() != SelfDelivery::Network
+            {
                 self.published_message_ids.insert(msg_id.clone());
             }
         }
@@ -764,7 +976,21 @@ where
             metrics.register_published_message(&topic_hash);
         }
 
-        Ok(msg_id)
+        if self.config.self_delivery() == SelfDelivery::Local && self.mesh.contains_key(&topic_hash)
+        {
+            let origin = origin.or_else(|| self.config.origin_label().map(String::from));
+            self.events
+                .push_back(ToSwarm::GenerateEvent(Event::LocalMessage {
+                    message_id: msg_id.clone(),
+                    message,
+                    origin,
+                }));
+        }
+
+        Ok(Published {
+            message_id: msg_id,
+            peers: recipient_peers.len(),
+        })
     }
 
     /// This function should be called when [`Config::validate_messages()`] is `true` after
@@ -831,6 +1057,16 @@ where
                 metrics.register_msg_validation(&raw_message.topic, &acceptance);
             }
 
+            // The application already knows about the messages it ignored itself.
+            if reject_reason != RejectReason::ValidationIgnored {
+                self.events
+                    .push_back(ToSwarm::GenerateEvent(Event::MessageRejected {
+                        propagation_source: *propagation_source,
+                        message_id: Some(msg_id.clone()),
+                        reason: reject_reason,
+                    }));
+            }
+
             // Tell peer_score about reject
             // Reject the original source, and any duplicates we've seen from other peers.
             if let Some((peer_score, ..)) = &mut self.peer_score {
@@ -867,6 +1103,61 @@ where
         self.explicit_peers.remove(peer_id);
     }
 
+    /// Pins a peer into the mesh of a topic, e.g. for links between validators that must stay in
+    /// the mesh.
+    ///
+    /// While connected and subscribed to the topic, the peer is grafted on the next heartbeat,
+    /// accepted when grafting even if the mesh is full and never pruned by the mesh maintenance,
+    /// regardless of its score.
+    /// Backoffs received from the peer are respected. Explicit peers are never grafted.
+    ///
+    /// Returns `false` if the peer was already pinned into the mesh of the topic.
+    pub fn pin_mesh_peer(&mut self, topic_hash: &TopicHash, peer_id: &PeerId) -> bool {
+        if self
+            .pinned_mesh_peers
+            .entry(topic_hash.clone())
+            .or_default()
+            .insert(*peer_id)
+        {
+            debug!(
+                "Pinned peer {} into the mesh of topic {}",
+                peer_id, topic_hash
+            );
+            return true;
+        }
+        false
+    }
+
+    /// Unpins a peer from the mesh of a topic, leaving it to the regular mesh maintenance. Note
+    /// that this does not prune the peer.
+    ///
+    /// Returns `false` if the peer was not pinned into the mesh of the topic.
+    pub fn unpin_mesh_peer(&mut self, topic_hash: &TopicHash, peer_id: &PeerId) -> bool {
+        let peers = match self.pinned_mesh_peers.get_mut(topic_hash) {
+            Some(peers) => peers,
+            None => return false,
+        };
+        let removed = peers.remove(peer_id);
+        if peers.is_empty() {
+            self.pinned_mesh_peers.remove(topic_hash);
+        }
+        if removed {
+            debug!(
+                "Unpinned peer {} from the mesh of topic {}",
+                peer_id, topic_hash
+            );
+        }
+        removed
+    }
+
+    /// Lists the peers pinned into the mesh of a topic.
+    pub fn pinned_mesh_peers(&self, topic_hash: &TopicHash) -> impl Iterator<Item = &PeerId> {
+        self.pinned_mesh_peers
+            .get(topic_hash)
+            .into_iter()
+            .flat_map(|x| x.iter())
+    }
+
     /// Blacklists a peer. All messages from this peer will be rejected and any message that was
     /// created by this peer will be rejected.
     pub fn blacklist_peer(&mut self, peer_id: &PeerId) {
@@ -908,8 +1199,13 @@ where
             return Err("Peer score set twice".into());
         }
 
-        let interval = Interval::new(params.decay_interval);
-        let peer_score = PeerScore::new_with_message_delivery_time_callback(params, callback);
+        let interval = Ticker::new(
+            self.config.shared_clock(),
+            params.decay_interval,
+            params.decay_interval,
+        );
+        let peer_score = PeerScore::new_with_message_delivery_time_callback(params, callback)
+            .with_clock(self.config.shared_clock());
         self.peer_score = Some((peer_score, threshold, interval, GossipPromises::default()));
         Ok(())
     }
@@ -930,6 +1226,17 @@ where
         }
     }
 
+    /// Sets a source of peers subscribed to a topic, consulted when the mesh of a topic runs low.
+    ///
+    /// On every heartbeat at which the mesh of a subscribed topic holds fewer than
+    /// [`Config::mesh_n_low`] peers after grafting the known subscribers,
+    /// [`TopicDiscovery::discover`] is called, at most once per
+    /// [`Config::topic_discovery_ticks`] heartbeats for the same topic. Peers reported via
+    /// [`TopicDiscovery::poll_discovered`] are dialed.
+    pub fn set_topic_discovery(&mut self, discovery: impl TopicDiscovery + Send + 'static) {
+        self.topic_discovery = Some(Box::new(discovery));
+    }
+
     /// Sets the application specific score for a peer. Returns true if scoring is active and
     /// the peer is connected or if the score of the peer is not yet expired, false otherwise.
     pub fn set_application_score(&mut self, peer_id: &PeerId, new_score: f64) -> bool {
@@ -940,6 +1247,80 @@ where
         }
     }
 
+    /// Sets the round-trip time to a peer, e.g. as measured by ping, penalized in the application
+    /// specific score as per [`AppSpecificScoreParams::rtt_weight`](crate::AppSpecificScoreParams).
+    /// Returns true if scoring is active and the peer is connected or if the score of the peer
+    /// is not yet expired, false otherwise.
+    pub fn set_peer_rtt(&mut self, peer_id: &PeerId, rtt: Duration) -> bool {
+        if let Some((peer_score, ..)) = &mut self.peer_score {
+            peer_score.set_rtt(peer_id, rtt)
+        } else {
+            false
+        }
+    }
+
+    /// Sets the agent version of a peer, e.g. as reported by identify, checked in the application
+    /// specific score against
+    /// [`AppSpecificScoreParams::agent_version_allowlist`](crate::AppSpecificScoreParams).
+    /// Returns true if scoring is active and the peer is connected or if the score of the peer
+    /// is not yet expired, false otherwise.
+    pub fn set_peer_agent_version(&mut self, peer_id: &PeerId, agent_version: String) -> bool {
+        if let Some((peer_score, ..)) = &mut self.peer_score {
+            peer_score.set_agent_version(peer_id, agent_version)
+        } else {
+            false
+        }
+    }
+
+    /// Sets the reputation of a peer as determined by the application, weighted in the
+    /// application specific score with
+    /// [`AppSpecificScoreParams::reputation_weight`](crate::AppSpecificScoreParams).
+    /// Returns true if scoring is active and the peer is connected or if the score of the peer
+    /// is not yet expired, false otherwise.
+    pub fn set_peer_reputation(&mut self, peer_id: &PeerId, reputation: f64) -> bool {
+        if let Some((peer_score, ..)) = &mut self.peer_score {
+            peer_score.set_reputation(peer_id, reputation)
+        } else {
+            false
+        }
+    }
+
+    /// Asks the mesh peers of a subscribed topic for the messages they have recently seen on it.
+    ///
+    /// This re-sends a GRAFT to every peer of the topic mesh. Peers with
+    /// [`Config::backfill_on_graft`] enabled answer with an IHAVE, for which the missing messages
+    /// are requested via IWANT and delivered as [`Event::Message`]. Other peers ignore the GRAFT.
+    ///
+    /// Returns `false` if we are not subscribed to the topic.
+    pub fn request_history(&mut self, topic: &TopicHash) -> bool {
+        let peers = match self.mesh.get(topic) {
+            Some(peers) => peers.clone(),
+            None => return false,
+        };
+
+        debug!(
+            "Requesting history for topic {:?} from {} peers",
+            topic,
+            peers.len()
+        );
+        for peer_id in peers {
+            if let Err(e) = self.send_message(
+                peer_id,
+                Rpc {
+                    subscriptions: Vec::new(),
+                    messages: Vec::new(),
+                    control_msgs: vec![ControlAction::Graft {
+                        topic_hash: topic.clone(),
+                    }],
+                }
+                .into_protobuf(),
+            ) {
+                error!("Failed to send GRAFT: {:?}", e);
+            }
+        }
+        true
+    }
+
     /// Gossipsub JOIN(topic) - adds topic peers to mesh and sends them GRAFT messages.
     fn join(&mut self, topic_hash: &TopicHash) {
         debug!("Running JOIN for topic: {:?}", topic_hash);
@@ -1126,6 +1507,8 @@ where
     fn leave(&mut self, topic_hash: &TopicHash) {
         debug!("Running LEAVE for topic {:?}", topic_hash);
 
+        self.ordering.remove_topic(topic_hash);
+
         // If our mesh contains the topic, send prune to peers and delete it from the mesh
         if let Some((_, peers)) = self.mesh.remove_entry(topic_hash) {
             if let Some(m) = self.metrics.as_mut() {
@@ -1153,6 +1536,57 @@ where
         debug!("Completed LEAVE for topic: {:?}", topic_hash);
     }
 
+    /// Starts discovering peers for the given topics with a low mesh, along with the number of
+    /// peers missing in the mesh of each.
+    fn discover_topic_peers(&mut self, low_topics: Vec<(TopicHash, usize)>) {
+        // a topic whose mesh recovered starts over
+        self.topic_discoveries
+            .retain(|topic, _| low_topics.iter().any(|(t, _)| t == topic));
+
+        let discovery = match self.topic_discovery.as_mut() {
+            Some(discovery) => discovery,
+            None => return,
+        };
+        for (topic_hash, missing) in low_topics {
+            if let Some(tick) = self.topic_discoveries.get(&topic_hash) {
+                if self.heartbeat_ticks - tick < self.config.topic_discovery_ticks() {
+                    continue;
+                }
+            }
+            debug!("Discovering {} peers for topic {:?}", missing, topic_hash);
+            discovery.discover(&topic_hash, missing);
+            self.topic_discoveries
+                .insert(topic_hash, self.heartbeat_ticks);
+        }
+    }
+
+    /// Dials the peers reported by the [`TopicDiscovery`], if any.
+    fn poll_topic_discovery(&mut self, cx: &mut Context<'_>) {
+        let discovery = match self.topic_discovery.as_mut() {
+            Some(discovery) => discovery,
+            None => return,
+        };
+        while let Poll::Ready(DiscoveredPeers { topic, peers }) = discovery.poll_discovered(cx) {
+            for (peer_id, addresses) in peers {
+                if self.connected_peers.contains_key(&peer_id)
+                    || self.blacklisted_peers.contains(&peer_id)
+                {
+                    continue;
+                }
+                debug!(
+                    "Connecting to peer {} discovered for topic {:?}",
+                    peer_id, topic
+                );
+                self.events.push_back(ToSwarm::Dial {
+                    opts: DialOpts::peer_id(peer_id)
+                        .condition(PeerCondition::Disconnected)
+                        .addresses(addresses)
+                        .build(),
+                });
+            }
+        }
+    }
+
     /// Checks if the given peer is still connected and if not dials the peer again.
     fn check_explicit_peer_connection(&mut self, peer_id: &PeerId) {
         if !self.peer_topics.contains_key(peer_id) {
@@ -1175,7 +1609,7 @@ where
     }
 
     fn score_below_threshold_from_scores(
-        peer_score: &Option<(PeerScore, PeerScoreThresholds, Interval, GossipPromises)>,
+        peer_score: &Option<(PeerScore, PeerScoreThresholds, Ticker, GossipPromises)>,
         peer_id: &PeerId,
         threshold: impl Fn(&PeerScoreThresholds) -> f64,
     ) -> (bool, f64) {
@@ -1296,7 +1730,7 @@ where
                 gossip_promises.add_promise(
                     *peer_id,
                     &iwant_ids_vec,
-                    Instant::now() + self.config.iwant_followup_time(),
+                    self.config.clock().now() + self.config.iwant_followup_time(),
                 );
             }
             trace!(
@@ -1335,7 +1769,15 @@ where
         for id in iwant_msgs {
             // If we have it and the IHAVE count is not above the threshold, add it do the
             // cached_messages mapping
-            if let Some((msg, count)) = self.mcache.get_with_iwant_counts(&id, peer_id) {
+            let cached = self.mcache.get_with_iwant_counts(&id, peer_id);
+            if let Some(m) = self.metrics.as_mut() {
+                if cached.is_some() {
+                    m.register_iwant_cache_hit();
+                } else {
+                    m.register_iwant_cache_miss();
+                }
+            }
+            if let Some((msg, count)) = cached {
                 if count > self.config.gossip_retransimission() {
                     debug!(
                         "IWANT: Peer {} has asked for message {} too many times; ignoring \
@@ -1388,6 +1830,9 @@ where
 
         let mut do_px = self.config.do_px();
 
+        // topics the peer is in our mesh for, to be sent the recent message ids of
+        let mut backfill_topics = Vec::new();
+
         // For each topic, if a peer has grafted us, then we necessarily must be in their mesh
         // and they must be subscribed to the topic. Ensure we have recorded the mapping.
         for topic in &topics {
@@ -1410,7 +1855,7 @@ where
             do_px = false
         } else {
             let (below_zero, score) = self.score_below_threshold(peer_id, |_| 0.0);
-            let now = Instant::now();
+            let now = self.config.clock().now();
             for topic_hash in topics {
                 if let Some(peers) = self.mesh.get_mut(&topic_hash) {
                     // if the peer is already in the mesh ignore the graft
@@ -1419,6 +1864,7 @@ where
                             "GRAFT: Received graft for peer {:?} that is already in topic {:?}",
                             peer_id, &topic_hash
                         );
+                        backfill_topics.push(topic_hash);
                         continue;
                     }
 
@@ -1456,8 +1902,13 @@ where
                         }
                     }
 
-                    // check the score
-                    if below_zero {
+                    let is_pinned = self
+                        .pinned_mesh_peers
+                        .get(&topic_hash)
+                        .map_or(false, |pinned| pinned.contains(peer_id));
+
+                    // check the score, pinned peers are accepted regardless of their score
+                    if below_zero && !is_pinned {
                         // we don't GRAFT peers with negative score
                         debug!(
                             "GRAFT: ignoring peer {:?} with negative score [score = {}, \
@@ -1472,9 +1923,10 @@ where
                     }
 
                     // check mesh upper bound and only allow graft if the upper bound is not reached or
-                    // if it is an outbound peer
+                    // if it is an outbound or pinned peer
                     if peers.len() >= self.config.mesh_n_high()
                         && !self.outbound_peers.contains(peer_id)
+                        && !is_pinned
                     {
                         to_prune_topics.insert(topic_hash.clone());
                         continue;
@@ -1503,8 +1955,10 @@ where
                     );
 
                     if let Some((peer_score, ..)) = &mut self.peer_score {
-                        peer_score.graft(peer_id, topic_hash);
+                        peer_score.graft(peer_id, topic_hash.clone());
                     }
+
+                    backfill_topics.push(topic_hash);
                 } else {
                     // don't do PX when there is an unknown topic to avoid leaking our peers
                     do_px = false;
@@ -1543,9 +1997,55 @@ where
                 error!("Failed to send PRUNE: {:?}", e);
             }
         }
+
+        if self.config.backfill_on_graft() {
+            self.send_backfill(peer_id, backfill_topics);
+        }
         debug!("Completed GRAFT handling for peer: {}", peer_id);
     }
 
+    /// Sends an IHAVE with the message ids in the gossip window of the `memcache` for each of the
+    /// given topics to a peer that just grafted us, so it can request messages it missed.
+    fn send_backfill(&mut self, peer_id: &PeerId, topics: Vec<TopicHash>) {
+        let mut rng = thread_rng();
+        let control_msgs: Vec<_> = topics
+            .into_iter()
+            .filter_map(|topic_hash| {
+                let mut message_ids = self.mcache.get_gossip_message_ids(&topic_hash);
+                if message_ids.is_empty() {
+                    return None;
+                }
+                message_ids.partial_shuffle(&mut rng, self.config.max_ihave_length());
+                message_ids.truncate(self.config.max_ihave_length());
+                Some(ControlAction::IHave {
+                    topic_hash,
+                    message_ids,
+                })
+            })
+            .collect();
+
+        if control_msgs.is_empty() {
+            return;
+        }
+
+        debug!(
+            "GRAFT: Sending IHAVE for {} topics to peer: {}",
+            control_msgs.len(),
+            peer_id
+        );
+        if let Err(e) = self.send_message(
+            *peer_id,
+            Rpc {
+                subscriptions: Vec::new(),
+                messages: Vec::new(),
+                control_msgs,
+            }
+            .into_protobuf(),
+        ) {
+            error!("Failed to send IHAVE: {:?}", e);
+        }
+    }
+
     fn remove_peer_from_mesh(
         &mut self,
         peer_id: &PeerId,
@@ -1684,6 +2184,12 @@ where
                 "Rejecting message from blacklisted peer: {}",
                 propagation_source
             );
+            self.events
+                .push_back(ToSwarm::GenerateEvent(Event::MessageRejected {
+                    propagation_source: *propagation_source,
+                    message_id: Some(msg_id.clone()),
+                    reason: RejectReason::BlackListedPeer,
+                }));
             if let Some((peer_score, .., gossip_promises)) = &mut self.peer_score {
                 peer_score.reject_message(
                     propagation_source,
@@ -1706,6 +2212,7 @@ where
                 self.handle_invalid_message(
                     propagation_source,
                     raw_message,
+                    Some(msg_id.clone()),
                     RejectReason::BlackListedSource,
                 );
                 return false;
@@ -1733,7 +2240,12 @@ where
                 "Dropping message {} claiming to be from self but forwarded from {}",
                 msg_id, propagation_source
             );
-            self.handle_invalid_message(propagation_source, raw_message, RejectReason::SelfOrigin);
+            self.handle_invalid_message(
+                propagation_source,
+                raw_message,
+                Some(msg_id.clone()),
+                RejectReason::SelfOrigin,
+            );
             return false;
         }
 
@@ -1787,6 +2299,7 @@ where
                 self.handle_invalid_message(
                     propagation_source,
                     &raw_message,
+                    None,
                     RejectReason::ValidationError(ValidationError::TransformFailed),
                 );
                 return;
@@ -1803,6 +2316,34 @@ where
             return;
         }
 
+        // Only new messages on topics we are subscribed to take a token, hence the buckets are
+        // bounded by our subscriptions.
+        if self.mesh.contains_key(&message.topic) && !self.duplicate_cache.contains(&msg_id) {
+            if let Some(rate_limiter) = self.rate_limiter.as_mut() {
+                let outcome = rate_limiter.check(propagation_source, &message.topic);
+                if outcome != RateLimitOutcome::Accepted {
+                    debug!(
+                        "Message rate limit exceeded by peer {} on topic {}, dropping message",
+                        propagation_source, message.topic
+                    );
+                    if let Some((peer_score, ..)) = &mut self.peer_score {
+                        if let Some(metrics) = self.metrics.as_mut() {
+                            metrics.register_invalid_message(&message.topic);
+                        }
+                        peer_score.reject_invalid_message(propagation_source, &message.topic);
+                    }
+                    if outcome == RateLimitOutcome::FirstViolation {
+                        self.events
+                            .push_back(ToSwarm::GenerateEvent(Event::MessageRateLimited {
+                                peer_id: *propagation_source,
+                                topic: message.topic,
+                            }));
+                    }
+                    return;
+                }
+            }
+        }
+
         // Add the message to the duplicate caches
         if let Some(fast_message_id) = fast_message_id {
             // add id to cache
@@ -1824,11 +2365,6 @@ where
             msg_id
         );
 
-        // Record the received message with the metrics
-        if let Some(metrics) = self.metrics.as_mut() {
-            metrics.msg_recvd(&message.topic);
-        }
-
         // Tells score that message arrived (but is maybe not fully validated yet).
         // Consider the message as delivered for gossip promises.
         if let Some((peer_score, .., gossip_promises)) = &mut self.peer_score {
@@ -1836,18 +2372,65 @@ where
             gossip_promises.message_delivered(&msg_id);
         }
 
+        // Suppressed duplicates are neither delivered nor forwarded, the sender is not penalized.
+        if let Some((suppression, cache)) = &mut self.suppression_cache {
+            if let Some(key) = suppression.key(&message) {
+                if !cache.insert(key) {
+                    debug!("Suppressing duplicate message {}", msg_id);
+                    if let Some((peer_score, ..)) = &mut self.peer_score {
+                        peer_score.reject_message(
+                            propagation_source,
+                            &msg_id,
+                            &message.topic,
+                            RejectReason::ValidationIgnored,
+                        );
+                    }
+                    return;
+                }
+            }
+        }
+
+        // Record the received message with the metrics
+        if let Some(metrics) = self.metrics.as_mut() {
+            metrics.msg_recvd(&message.topic);
+        }
+
         // Add the message to our memcache
         self.mcache.put(&msg_id, raw_message.clone());
 
+        // Messages published by ourselves are either never delivered or were already
+        // delivered locally when they were published.
+        let suppressed = self.config.self_delivery() != SelfDelivery::Network
+            && match self.publish_config.get_own_id() {
+                Some(own_id) => raw_message.source.as_ref() == Some(own_id),
+                None => self.published_message_ids.contains(&msg_id),
+            };
+
         // Dispatch the message to the user if we are subscribed to any of the topics
-        if self.mesh.contains_key(&message.topic) {
+        if suppressed {
+            debug!("Not delivering message {} published by ourselves", msg_id);
+        } else if self.mesh.contains_key(&message.topic) {
             debug!("Sending received message to user");
-            self.events
-                .push_back(ToSwarm::GenerateEvent(Event::Message {
-                    propagation_source: *propagation_source,
-                    message_id: msg_id.clone(),
-                    message,
-                }));
+            let topic = message.topic.clone();
+            let ordered = match (message.source, message.sequence_number) {
+                (Some(author), Some(sequence_number)) if self.ordering.is_ordered(&topic) => {
+                    Some((author, sequence_number))
+                }
+                _ => None,
+            };
+            let event = Event::Message {
+                propagation_source: *propagation_source,
+                message_id: msg_id.clone(),
+                message,
+            };
+            match ordered {
+                Some((author, sequence_number)) => {
+                    for event in self.ordering.push(&topic, author, sequence_number, event) {
+                        self.events.push_back(ToSwarm::GenerateEvent(event));
+                    }
+                }
+                None => self.events.push_back(ToSwarm::GenerateEvent(event)),
+            }
         } else {
             debug!(
                 "Received message on a topic we are not subscribed to: {:?}",
@@ -1878,8 +2461,21 @@ where
         &mut self,
         propagation_source: &PeerId,
         raw_message: &RawMessage,
+        message_id: Option<MessageId>,
         reject_reason: RejectReason,
     ) {
+        let message_id = message_id.or_else(|| {
+            self.config
+                .fast_message_id(raw_message)
+                .and_then(|id| self.fast_message_id_cache.get(&id).cloned())
+        });
+        self.events
+            .push_back(ToSwarm::GenerateEvent(Event::MessageRejected {
+                propagation_source: *propagation_source,
+                message_id,
+                reason: reject_reason,
+            }));
+
         if let Some((peer_score, .., gossip_promises)) = &mut self.peer_score {
             if let Some(metrics) = self.metrics.as_mut() {
                 metrics.register_invalid_message(&raw_message.topic);
@@ -1994,16 +2590,30 @@ where
                             .is_backoff_with_slack(topic_hash, propagation_source)
                     {
                         if let Some(peers) = self.mesh.get_mut(topic_hash) {
-                            if peers.len() < self.config.mesh_n_low()
-                                && peers.insert(*propagation_source)
-                            {
+                            // peers that were in the mesh before disconnecting shortly ago are
+                            // grafted back as long as the mesh is not oversubscribed
+                            let reconnected = self
+                                .disconnected_mesh_peers
+                                .get_mut(propagation_source)
+                                .map_or(false, |(topics, _)| topics.remove(topic_hash));
+                            let mesh_n_limit = if reconnected {
+                                self.config.mesh_n_high()
+                            } else {
+                                self.config.mesh_n_low()
+                            };
+                            if peers.len() < mesh_n_limit && peers.insert(*propagation_source) {
                                 debug!(
                                     "SUBSCRIPTION: Adding peer {} to the mesh for topic {:?}",
                                     propagation_source.to_string(),
                                     topic_hash
                                 );
                                 if let Some(m) = self.metrics.as_mut() {
-                                    m.peers_included(topic_hash, Inclusion::Subscribed, 1)
+                                    let inclusion = if reconnected {
+                                        Inclusion::Reconnected
+                                    } else {
+                                        Inclusion::Subscribed
+                                    };
+                                    m.peers_included(topic_hash, inclusion, 1)
                                 }
                                 // send graft to the peer
                                 debug!(
@@ -2093,6 +2703,8 @@ where
             self.events.push_back(event);
         }
 
+        self.check_publishable_topics();
+
         trace!(
             "Completed handling subscriptions from source: {:?}",
             propagation_source
@@ -2102,7 +2714,7 @@ where
     /// Applies penalties to peers that did not respond to our IWANT requests.
     fn apply_iwant_penalties(&mut self) {
         if let Some((peer_score, .., gossip_promises)) = &mut self.peer_score {
-            for (peer, count) in gossip_promises.get_broken_promises() {
+            for (peer, count) in gossip_promises.get_broken_promises(self.config.clock().now()) {
                 peer_score.add_penalty(&peer, count);
                 if let Some(metrics) = self.metrics.as_mut() {
                     metrics.register_score_penalty(Penalty::BrokenPromise);
@@ -2114,7 +2726,7 @@ where
     /// Heartbeat function which shifts the memcache and updates the mesh.
     fn heartbeat(&mut self) {
         debug!("Starting heartbeat");
-        let start = Instant::now();
+        let start = self.config.clock().now();
 
         self.heartbeat_ticks += 1;
 
@@ -2125,10 +2737,24 @@ where
         // clean up expired backoffs
         self.backoffs.heartbeat();
 
+        // clean up refilled rate limit buckets
+        if let Some(rate_limiter) = self.rate_limiter.as_mut() {
+            rate_limiter.prune();
+        }
+
+        // deliver messages held back for too long on topics with ordered delivery
+        for event in self.ordering.poll_expired() {
+            self.events.push_back(ToSwarm::GenerateEvent(event));
+        }
+
         // clean up ihave counters
         self.count_sent_iwant.clear();
         self.count_received_ihave.clear();
 
+        // forget disconnected mesh peers whose grace period is over
+        self.disconnected_mesh_peers
+            .retain(|_, (_, deadline)| *deadline > start);
+
         // apply iwant penalties
         self.apply_iwant_penalties();
 
@@ -2150,11 +2776,14 @@ where
         }
 
         // maintain the mesh for each topic
+        let mut low_topics = Vec::new();
         for (topic_hash, peers) in self.mesh.iter_mut() {
             let explicit_peers = &self.explicit_peers;
             let backoffs = &self.backoffs;
             let topic_peers = &self.topic_peers;
             let outbound_peers = &self.outbound_peers;
+            let pinned_peers = self.pinned_mesh_peers.get(topic_hash);
+            let is_pinned = |peer: &PeerId| pinned_peers.map_or(false, |p| p.contains(peer));
 
             // drop all peers with negative score, without PX
             // if there is at some point a stable retain method for BTreeSet the following can be
@@ -2168,7 +2797,7 @@ where
                     metrics.observe_mesh_peers_score(topic_hash, peer_score);
                 }
 
-                if peer_score < 0.0 {
+                if peer_score < 0.0 && !is_pinned(peer_id) {
                     debug!(
                         "HEARTBEAT: Prune peer {:?} with negative score [score = {}, topic = \
                              {}]",
@@ -2190,6 +2819,34 @@ where
                 peers.remove(&peer_id);
             }
 
+            // graft the pinned peers that are connected and subscribed, unless they pruned us
+            if let Some(pinned_peers) = pinned_peers {
+                let peer_list: Vec<_> = pinned_peers
+                    .iter()
+                    .filter(|peer| {
+                        !peers.contains(*peer)
+                            && self.connected_peers.contains_key(*peer)
+                            && topic_peers
+                                .get(topic_hash)
+                                .map_or(false, |p| p.contains(*peer))
+                            && !explicit_peers.contains(*peer)
+                            && !backoffs.is_backoff_with_slack(topic_hash, peer)
+                    })
+                    .cloned()
+                    .collect();
+                for peer in &peer_list {
+                    let current_topic = to_graft.entry(*peer).or_insert_with(Vec::new);
+                    current_topic.push(topic_hash.clone());
+                }
+                if !peer_list.is_empty() {
+                    debug!("HEARTBEAT: Grafting pinned peers {:?}", peer_list);
+                }
+                if let Some(m) = self.metrics.as_mut() {
+                    m.peers_included(topic_hash, Inclusion::Pinned, peer_list.len())
+                }
+                peers.extend(peer_list);
+            }
+
             // too little peers - add some
             if peers.len() < self.config.mesh_n_low() {
                 debug!(
@@ -2263,6 +2920,10 @@ where
                     if removed == excess_peer_no {
                         break;
                     }
+                    if is_pinned(&peer) {
+                        // pinned peers are never pruned
+                        continue;
+                    }
                     if self.outbound_peers.contains(&peer) {
                         if outbound <= self.config.mesh_outbound_min() {
                             // do not remove anymore outbound peers
@@ -2389,6 +3050,11 @@ where
                     }
                 }
             }
+
+            if peers.len() < self.config.mesh_n_low() {
+                low_topics.push((topic_hash.clone(), self.config.mesh_n() - peers.len()));
+            }
+
             // Register the final count of peers in the mesh
             if let Some(m) = self.metrics.as_mut() {
                 m.set_mesh_peers(topic_hash, peers.len())
@@ -2399,8 +3065,9 @@ where
         {
             let fanout = &mut self.fanout; // help the borrow checker
             let fanout_ttl = self.config.fanout_ttl();
+            let now = self.config.clock().now();
             self.fanout_last_pub.retain(|topic_hash, last_pub_time| {
-                if *last_pub_time + fanout_ttl < Instant::now() {
+                if *last_pub_time + fanout_ttl < now {
                     debug!(
                         "HEARTBEAT: Fanout topic removed due to timeout. Topic: {:?}",
                         topic_hash
@@ -2504,12 +3171,18 @@ where
         // piggyback pooled control messages
         self.flush_control_pool();
 
+        // look for more peers for topics whose mesh is still low
+        self.discover_topic_peers(low_topics);
+
         // shift the memcache
         self.mcache.shift();
 
+        self.check_publishable_topics();
+
         debug!("Completed Heartbeat");
         if let Some(metrics) = self.metrics.as_mut() {
-            let duration = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
+            let elapsed = self.config.clock().now().saturating_duration_since(start);
+            let duration = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
             metrics.observe_heartbeat_duration(duration);
         }
     }
@@ -2714,38 +3387,8 @@ where
         }
 
         debug!("Forwarding message: {:?}", msg_id);
-        let mut recipient_peers = HashSet::new();
-
-        {
-            // Populate the recipient peers mapping
-
-            // Add explicit peers
-            for peer_id in &self.explicit_peers {
-                if let Some(topics) = self.peer_topics.get(peer_id) {
-                    if Some(peer_id) != propagation_source
-                        && !originating_peers.contains(peer_id)
-                        && Some(peer_id) != message.source.as_ref()
-                        && topics.contains(&message.topic)
-                    {
-                        recipient_peers.insert(*peer_id);
-                    }
-                }
-            }
-
-            // add mesh peers
-            let topic = &message.topic;
-            // mesh
-            if let Some(mesh_peers) = self.mesh.get(topic) {
-                for peer_id in mesh_peers {
-                    if Some(peer_id) != propagation_source
-                        && !originating_peers.contains(peer_id)
-                        && Some(peer_id) != message.source.as_ref()
-                    {
-                        recipient_peers.insert(*peer_id);
-                    }
-                }
-            }
-        }
+        let recipient_peers =
+            self.mesh_recipients(&message, propagation_source, &originating_peers);
 
         // forward the message to peers
         if !recipient_peers.is_empty() {
@@ -2771,6 +3414,73 @@ where
         }
     }
 
+    /// Returns the explicit and mesh\[topic\] peers to forward a message to, excluding the peers
+    /// that sent it to us and its author.
+    fn mesh_recipients(
+        &self,
+        message: &RawMessage,
+        propagation_source: Option<&PeerId>,
+        originating_peers: &HashSet<PeerId>,
+    ) -> HashSet<PeerId> {
+        let mut recipient_peers = HashSet::new();
+
+        // Add explicit peers
+        for peer_id in &self.explicit_peers {
+            if let Some(topics) = self.peer_topics.get(peer_id) {
+                if Some(peer_id) != propagation_source
+                    && !originating_peers.contains(peer_id)
+                    && Some(peer_id) != message.source.as_ref()
+                    && topics.contains(&message.topic)
+                {
+                    recipient_peers.insert(*peer_id);
+                }
+            }
+        }
+
+        // add mesh peers
+        if let Some(mesh_peers) = self.mesh.get(&message.topic) {
+            for peer_id in mesh_peers {
+                if Some(peer_id) != propagation_source
+                    && !originating_peers.contains(peer_id)
+                    && Some(peer_id) != message.source.as_ref()
+                {
+                    recipient_peers.insert(*peer_id);
+                }
+            }
+        }
+
+        recipient_peers
+    }
+
+    /// Returns whether a message published on the topic would be sent to any peer whose send
+    /// queue is not full.
+    fn has_publish_peers(&self, topic: &TopicHash) -> bool {
+        self.topic_peers.get(topic).map_or(false, |peers| {
+            peers.iter().any(|p| {
+                !self.congested_peers.contains(p)
+                    && (self.explicit_peers.contains(p)
+                        || !self.score_below_threshold(p, |ts| ts.publish_threshold).0)
+            })
+        })
+    }
+
+    /// Emits [`Event::TopicPublishable`] for the topics publishing on failed, that now have peers
+    /// to publish to.
+    fn check_publishable_topics(&mut self) {
+        let publishable = self
+            .unpublishable_topics
+            .iter()
+            .filter(|topic| self.has_publish_peers(topic))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for topic in publishable {
+            self.unpublishable_topics.remove(&topic);
+            self.events
+                .push_back(ToSwarm::GenerateEvent(Event::TopicPublishable { topic }));
+        }
+    }
+
     /// Constructs a [`RawMessage`] performing message signing if required.
     pub(crate) fn build_raw_message(
         &mut self,
@@ -3080,6 +3790,7 @@ where
             .or_insert(PeerConnections {
                 kind: PeerKind::Floodsub,
                 connections: vec![],
+                dropped_messages: Default::default(),
             })
             .connections
             .push(connection_id);
@@ -3182,6 +3893,9 @@ where
         } else {
             // remove from mesh, topic_peers, peer_topic and the fanout
             debug!("Peer disconnected: {}", peer_id);
+            if let Some(rate_limiter) = self.rate_limiter.as_mut() {
+                rate_limiter.remove_peer(&peer_id);
+            }
             {
                 let topics = match self.peer_topics.get(&peer_id) {
                     Some(topics) => topics,
@@ -3194,6 +3908,8 @@ where
                     }
                 };
 
+                let mut mesh_topics = BTreeSet::new();
+
                 // remove peer from all mappings
                 for topic in topics {
                     // check the mesh for the topic
@@ -3204,6 +3920,7 @@ where
                                 m.peers_removed(topic, Churn::Dc, 1);
                                 m.set_mesh_peers(topic, mesh_peers.len());
                             }
+                            mesh_topics.insert(topic.clone());
                         };
                     }
 
@@ -3231,6 +3948,14 @@ where
                         .get_mut(topic)
                         .map(|peers| peers.remove(&peer_id));
                 }
+
+                // remember the meshes the peer was part of, to graft it back if it reconnects
+                if let Some(grace) = self.config.mesh_reconnect_grace() {
+                    if !mesh_topics.is_empty() {
+                        self.disconnected_mesh_peers
+                            .insert(peer_id, (mesh_topics, self.config.clock().now() + grace));
+                    }
+                }
             }
 
             // Forget px and outbound status for this peer
@@ -3253,6 +3978,7 @@ where
             }
 
             self.connected_peers.remove(&peer_id);
+            self.congested_peers.remove(&peer_id);
 
             if let Some((peer_score, ..)) = &mut self.peer_score {
                 peer_score.remove_peer(&peer_id);
@@ -3319,6 +4045,7 @@ where
         Ok(Handler::new(
             ProtocolConfig::new(&self.config),
             self.config.idle_timeout(),
+            self.config.max_send_queue_size(),
         ))
     }
 
@@ -3332,6 +4059,7 @@ where
         Ok(Handler::new(
             ProtocolConfig::new(&self.config),
             self.config.idle_timeout(),
+            self.config.max_send_queue_size(),
         ))
     }
 
@@ -3371,6 +4099,37 @@ where
                     }
                 }
             }
+            HandlerEvent::MessagesDropped(dropped) => {
+                debug!(
+                    "Dropped {} outbound messages to peer {} due to a full send queue",
+                    dropped.total(),
+                    propagation_source
+                );
+
+                if let Some(metrics) = self.metrics.as_mut() {
+                    metrics.register_dropped_messages(&dropped);
+                }
+
+                if let Some(peer) = self.connected_peers.get_mut(&propagation_source) {
+                    peer.dropped_messages.add(&dropped);
+                }
+                if dropped.data > 0 {
+                    self.congested_peers.insert(propagation_source);
+                }
+            }
+            HandlerEvent::SendQueueDrained => {
+                if self.congested_peers.remove(&propagation_source) {
+                    self.check_publishable_topics();
+                }
+            }
+            HandlerEvent::RpcTooLarge => {
+                self.events
+                    .push_back(ToSwarm::GenerateEvent(Event::MessageRejected {
+                        propagation_source,
+                        message_id: None,
+                        reason: RejectReason::TooLarge,
+                    }));
+            }
             HandlerEvent::Message {
                 rpc,
                 invalid_messages,
@@ -3392,24 +4151,26 @@ where
                 }
 
                 // Handle any invalid messages from this peer
-                if self.peer_score.is_some() {
-                    for (raw_message, validation_error) in invalid_messages {
-                        self.handle_invalid_message(
-                            &propagation_source,
-                            &raw_message,
-                            RejectReason::ValidationError(validation_error),
-                        )
-                    }
-                } else {
-                    // log the invalid messages
-                    for (message, validation_error) in invalid_messages {
+                for (raw_message, validation_error) in invalid_messages {
+                    if self.peer_score.is_none() {
                         warn!(
                             "Invalid message. Reason: {:?} propagation_peer {} source {:?}",
                             validation_error,
                             propagation_source.to_string(),
-                            message.source
+                            raw_message.source
                         );
                     }
+                    let message_id = self
+                        .data_transform
+                        .inbound_transform(raw_message.clone())
+                        .ok()
+                        .map(|message| self.config.message_id(&message));
+                    self.handle_invalid_message(
+                        &propagation_source,
+                        &raw_message,
+                        message_id,
+                        RejectReason::ValidationError(validation_error),
+                    )
                 }
 
                 // Handle messages
@@ -3466,24 +4227,30 @@ where
         cx: &mut Context<'_>,
         _: &mut impl PollParameters,
     ) -> Poll<ToSwarm<Self::OutEvent, THandlerInEvent<Self>>> {
+        self.poll_topic_discovery(cx);
+
         if let Some(event) = self.events.pop_front() {
             return Poll::Ready(event);
         }
 
         // update scores
         if let Some((peer_score, _, interval, _)) = &mut self.peer_score {
-            while let Poll::Ready(Some(())) = interval.poll_next_unpin(cx) {
+            while interval.poll_tick(cx).is_ready() {
                 peer_score.refresh_scores();
             }
         }
 
-        while let Poll::Ready(Some(())) = self.heartbeat.poll_next_unpin(cx) {
+        while self.heartbeat.poll_tick(cx).is_ready() {
             self.heartbeat();
         }
 
         Poll::Pending
     }
 
+    fn queued_actions(&self) -> usize {
+        self.events.len()
+    }
+
     fn on_swarm_event(&mut self, event: FromSwarm<Self::ConnectionHandler>) {
         match event {
             FromSwarm::ConnectionEstablished(connection_established) => {
@@ -3501,7 +4268,10 @@ where
             | FromSwarm::ListenerError(_)
             | FromSwarm::ListenerClosed(_)
             | FromSwarm::NewExternalAddr(_)
-            | FromSwarm::ExpiredExternalAddr(_) => {}
+            | FromSwarm::ExpiredExternalAddr(_)
+            | FromSwarm::PeerProtocolsChanged(_)
+            | FromSwarm::PeerListenAddrsChanged(_)
+            | FromSwarm::NatStatusChanged(_) => {}
         }
     }
 }
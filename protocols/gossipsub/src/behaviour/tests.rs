@@ -228,6 +228,7 @@ where
         endpoint: &endpoint,
         failed_addresses: &[],
         other_established: 0, // first connection
+        user_data: None,
     }));
     if let Some(kind) = kind {
         gs.on_connection_handler_event(
@@ -271,8 +272,12 @@ where
         for connection_id in peer_connections.connections.clone() {
             active_connections = active_connections.checked_sub(1).unwrap();
 
-            let dummy_handler =
-                Handler::new(ProtocolConfig::new(&Config::default()), Duration::ZERO);
+            let config = Config::default();
+            let dummy_handler = Handler::new(
+                ProtocolConfig::new(&config),
+                Duration::ZERO,
+                config.max_send_queue_size(),
+            );
 
             gs.on_swarm_event(FromSwarm::ConnectionClosed(ConnectionClosed {
                 peer_id: *peer_id,
@@ -280,6 +285,7 @@ where
                 endpoint: &fake_endpoint,
                 handler: dummy_handler,
                 remaining_established: active_connections,
+                reason: None,
             }));
         }
     }
@@ -593,6 +599,7 @@ fn test_join() {
             },
             failed_addresses: &[],
             other_established: 0,
+            user_data: None,
         }));
 
         // add the new peer to the fanout
@@ -796,6 +803,61 @@ fn test_fanout() {
     );
 }
 
+/// Test the introspection of the mesh, the fanout and the known peers.
+#[test]
+fn test_introspection() {
+    let config = ConfigBuilder::default()
+        .flood_publish(false)
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(20)
+        .topics(vec![String::from("mesh"), String::from("fanout")])
+        .to_subscribe(true)
+        .explicit(1)
+        .outbound(2)
+        .gs_config(config)
+        .create_network();
+    let (mesh_topic, fanout_topic) = (&topic_hashes[0], &topic_hashes[1]);
+
+    gs.unsubscribe(&Topic::new("fanout")).unwrap();
+    gs.publish(Topic::new("fanout"), vec![0; 42]).unwrap();
+
+    let fanout_peers = gs
+        .fanout_peers(fanout_topic)
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    assert_eq!(fanout_peers.len(), gs.config.mesh_n());
+    assert_eq!(Some(&fanout_peers), gs.fanout.get(fanout_topic));
+    assert_eq!(gs.fanout_peers(mesh_topic).count(), 0);
+
+    let all_peers = peers.iter().collect::<BTreeSet<_>>();
+    let peers_with_topics = gs
+        .all_peers_with_topics()
+        .map(|(topic, peers)| (topic.clone(), peers.into_iter().collect::<BTreeSet<_>>()))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(peers_with_topics.len(), 2);
+    assert_eq!(peers_with_topics[mesh_topic], all_peers);
+    assert_eq!(peers_with_topics[fanout_topic], all_peers);
+
+    let dump = gs.dump();
+    assert_eq!(dump.mesh, gs.mesh);
+    assert!(!dump.mesh.contains_key(fanout_topic));
+    assert_eq!(dump.fanout.get(fanout_topic), Some(&fanout_peers));
+    assert_eq!(dump.peers.len(), peers.len());
+    assert_eq!(dump.heartbeat_ticks, 0);
+    for (i, peer_id) in peers.iter().enumerate() {
+        let peer = &dump.peers[peer_id];
+        assert_eq!(peer.kind, PeerKind::Gossipsubv1_1);
+        assert_eq!(peer.connections, 1);
+        assert_eq!(peer.topics, topic_hashes.iter().cloned().collect());
+        assert_eq!(peer.explicit, i < 1);
+        assert_eq!(peer.outbound, i < 2);
+        assert_eq!(peer.score, None);
+    }
+}
+
 #[test]
 /// Test the gossipsub NetworkBehaviour peer connection logic.
 fn test_inject_connected() {
@@ -968,6 +1030,7 @@ fn test_get_random_peers() {
                 PeerConnections {
                     kind: PeerKind::Gossipsubv1_1,
                     connections: vec![ConnectionId::new_unchecked(0)],
+                    dropped_messages: Default::default(),
                 },
             )
         })
@@ -1277,6 +1340,91 @@ fn test_handle_graft_is_not_subscribed() {
     );
 }
 
+#[test]
+// tests that a peer grafting us is sent an IHAVE for the recent messages of the topic if backfill
+// is enabled
+fn test_handle_graft_backfill() {
+    let config = ConfigBuilder::default()
+        .backfill_on_graft(true)
+        .build()
+        .unwrap();
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(20)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    let raw_message = RawMessage {
+        source: Some(peers[11]),
+        data: vec![1, 2, 3, 4],
+        sequence_number: Some(1u64),
+        topic: topic_hashes[0].clone(),
+        signature: None,
+        key: None,
+        validated: true,
+    };
+    let message = &gs
+        .data_transform
+        .inbound_transform(raw_message.clone())
+        .unwrap();
+    let msg_id = gs.config.message_id(message);
+    gs.mcache.put(&msg_id, raw_message);
+
+    let new_peer = add_peer(&mut gs, &topic_hashes, false, false);
+    flush_events(&mut gs);
+    gs.handle_graft(&new_peer, topic_hashes.clone());
+
+    assert_eq!(
+        count_control_msgs(&gs, |peer_id, m| *peer_id == new_peer
+            && matches!(m, ControlAction::IHave { message_ids, .. } if message_ids == &vec![msg_id.clone()])),
+        1,
+        "Expected an IHAVE for the cached message to be sent to the grafting peer"
+    );
+
+    // Without messages in the cache, nothing is sent.
+    let (mut gs, _, topic_hashes) = inject_nodes1()
+        .peer_no(20)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .gs_config(
+            ConfigBuilder::default()
+                .backfill_on_graft(true)
+                .build()
+                .unwrap(),
+        )
+        .create_network();
+    let new_peer = add_peer(&mut gs, &topic_hashes, false, false);
+    flush_events(&mut gs);
+    gs.handle_graft(&new_peer, topic_hashes);
+
+    assert_eq!(
+        count_control_msgs(&gs, |_, m| matches!(m, ControlAction::IHave { .. })),
+        0
+    );
+}
+
+#[test]
+// tests that requesting history sends a GRAFT to each mesh peer of the topic
+fn test_request_history() {
+    let (mut gs, _, topic_hashes) = inject_nodes1()
+        .peer_no(20)
+        .topics(vec![String::from("topic1")])
+        .to_subscribe(true)
+        .create_network();
+    flush_events(&mut gs);
+
+    let mesh_peers = gs.mesh.get(&topic_hashes[0]).unwrap().clone();
+    assert!(gs.request_history(&topic_hashes[0]));
+    assert_eq!(
+        count_control_msgs(&gs, |peer_id, m| mesh_peers.contains(peer_id)
+            && matches!(m, ControlAction::Graft { .. })),
+        mesh_peers.len()
+    );
+
+    assert!(!gs.request_history(&TopicHash::from_raw("unsubscribed topic")));
+}
+
 #[test]
 // tests multiple topics in a single graft message
 fn test_handle_graft_multiple_topics() {
@@ -1459,6 +1607,66 @@ fn test_explicit_peer_reconnects() {
     );
 }
 
+#[test]
+fn test_mesh_peer_regrafted_after_reconnect_within_grace() {
+    for grace in [None, Some(Duration::from_secs(60))] {
+        let mut config = ConfigBuilder::default();
+        if let Some(grace) = grace {
+            config.mesh_reconnect_grace(grace);
+        }
+        let config = config.build().unwrap();
+        let (mut gs, peers, topic_hashes) = inject_nodes1()
+            .peer_no(config.mesh_n_low() + 1)
+            .topics(vec![String::from("topic1")])
+            .to_subscribe(true)
+            .gs_config(config.clone())
+            .create_network();
+        let topic = &topic_hashes[0];
+
+        let peer = *peers
+            .iter()
+            .find(|p| gs.mesh[topic].contains(p))
+            .expect("mesh to contain peers");
+        disconnect_peer(&mut gs, &peer);
+        assert!(!gs.mesh[topic].contains(&peer));
+
+        // Fill the mesh up to `mesh_n_low` again.
+        add_peer(&mut gs, &topic_hashes, false, false);
+        assert_eq!(gs.mesh[topic].len(), config.mesh_n_low());
+
+        // Reconnect the peer.
+        gs.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+            peer_id: peer,
+            connection_id: ConnectionId::new_unchecked(1),
+            endpoint: &ConnectedPoint::Listener {
+                local_addr: Multiaddr::empty(),
+                send_back_addr: Multiaddr::empty(),
+            },
+            failed_addresses: &[],
+            other_established: 0,
+            user_data: None,
+        }));
+        gs.on_connection_handler_event(
+            peer,
+            ConnectionId::new_unchecked(1),
+            HandlerEvent::PeerKind(PeerKind::Gossipsubv1_1),
+        );
+        gs.handle_received_subscriptions(
+            &[Subscription {
+                action: SubscriptionAction::Subscribe,
+                topic_hash: topic.clone(),
+            }],
+            &peer,
+        );
+
+        assert_eq!(
+            gs.mesh[topic].contains(&peer),
+            grace.is_some(),
+            "Peer should only be grafted back with a grace period"
+        );
+    }
+}
+
 #[test]
 fn test_handle_graft_explicit_peer() {
     let (mut gs, peers, topic_hashes) = inject_nodes1()
@@ -2333,6 +2541,91 @@ fn test_do_not_remove_too_many_outbound_peers() {
     assert!(outbound.iter().all(|p| gs.mesh[&topics[0]].contains(p)));
 }
 
+#[test]
+fn test_pinned_peers_are_grafted_and_not_pruned() {
+    let config: Config = Config::default();
+
+    // Fill full mesh with inbound peers
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(config.mesh_n_high())
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .create_network();
+    for peer in peers {
+        gs.handle_graft(&peer, topics.clone());
+    }
+
+    // pin an additional peer that is not in the mesh yet
+    let pinned = add_peer(&mut gs, &topics, false, false);
+    assert!(gs.pin_mesh_peer(&topics[0], &pinned));
+    assert!(!gs.pin_mesh_peer(&topics[0], &pinned));
+    assert_eq!(
+        gs.pinned_mesh_peers(&topics[0]).collect::<Vec<_>>(),
+        vec![&pinned]
+    );
+    assert!(!gs.mesh[&topics[0]].contains(&pinned));
+    flush_events(&mut gs);
+
+    // the heartbeat grafts the pinned peer and keeps it while reducing the mesh to mesh_n
+    gs.heartbeat();
+    assert_eq!(gs.mesh[&topics[0]].len(), config.mesh_n());
+    assert!(gs.mesh[&topics[0]].contains(&pinned));
+    assert_eq!(
+        count_control_msgs(&gs, |peer_id, m| peer_id == &pinned
+            && matches!(m, ControlAction::Graft { .. })),
+        1
+    );
+
+    // a GRAFT of a pinned peer is accepted even though the mesh is full
+    let other = add_peer(&mut gs, &topics, false, false);
+    gs.pin_mesh_peer(&topics[0], &other);
+    for _ in gs.mesh[&topics[0]].len()..config.mesh_n_high() {
+        let peer = add_peer(&mut gs, &topics, false, false);
+        gs.handle_graft(&peer, topics.clone());
+    }
+    gs.handle_graft(&other, topics.clone());
+    assert!(gs.mesh[&topics[0]].contains(&other));
+
+    // unpinned peers are left to the regular mesh maintenance
+    assert!(gs.unpin_mesh_peer(&topics[0], &other));
+    assert!(!gs.unpin_mesh_peer(&topics[0], &other));
+    assert_eq!(gs.pinned_mesh_peers(&topics[0]).count(), 1);
+}
+
+#[test]
+fn test_pinned_peers_are_kept_regardless_of_their_score() {
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(2)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .scoring(Some((
+            PeerScoreParams::default(),
+            PeerScoreThresholds::default(),
+        )))
+        .create_network();
+    let (pinned, unpinned) = (peers[0], peers[1]);
+    gs.pin_mesh_peer(&topics[0], &pinned);
+    gs.mesh.get_mut(&topics[0]).unwrap().clear();
+
+    // both peers have a negative score
+    for peer in &peers {
+        gs.peer_score.as_mut().unwrap().0.add_penalty(peer, 1);
+    }
+
+    // only the GRAFT of the pinned peer is accepted
+    gs.handle_graft(&pinned, topics.clone());
+    gs.handle_graft(&unpinned, topics.clone());
+    assert_eq!(
+        gs.mesh[&topics[0]].iter().collect::<Vec<_>>(),
+        vec![&pinned]
+    );
+
+    // and the heartbeat keeps it in the mesh
+    gs.heartbeat();
+    assert!(gs.mesh[&topics[0]].contains(&pinned));
+    assert!(!gs.mesh[&topics[0]].contains(&unpinned));
+}
+
 #[test]
 fn test_add_outbound_peers_if_min_is_not_satisfied() {
     let config: Config = Config::default();
@@ -3646,6 +3939,119 @@ fn test_scoring_p4_invalid_signature() {
     );
 }
 
+#[test]
+fn test_scoring_p4_rate_limited_messages() {
+    let config = ConfigBuilder::default()
+        .message_rate_limit(2, Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let mut peer_score_params = PeerScoreParams::default();
+    let topic = Topic::new("test");
+    let topic_hash = topic.hash();
+    let topic_params = TopicScoreParams {
+        time_in_mesh_weight: 0.0,             //deactivate time in mesh
+        first_message_deliveries_weight: 0.0, //deactivate first time deliveries
+        mesh_message_deliveries_weight: 0.0,  //deactivate message deliveries
+        mesh_failure_penalty_weight: 0.0,     //deactivate mesh failure penalties
+        invalid_message_deliveries_weight: -2.0,
+        invalid_message_deliveries_decay: 0.9,
+        topic_weight: 0.7,
+        ..Default::default()
+    };
+    peer_score_params
+        .topics
+        .insert(topic_hash.clone(), topic_params);
+    peer_score_params.app_specific_weight = 1.0;
+    let peer_score_thresholds = PeerScoreThresholds::default();
+
+    //build mesh with one peer
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config)
+        .explicit(0)
+        .outbound(0)
+        .scoring(Some((peer_score_params, peer_score_thresholds)))
+        .create_network();
+
+    let mut seq = 0;
+    gs.events.clear();
+
+    //peer 0 delivers four messages, exceeding the limit of two
+    for _ in 0..4 {
+        let m = random_message(&mut seq, &topics);
+        gs.handle_received_message(m, &peers[0]);
+    }
+
+    let received = gs
+        .events
+        .iter()
+        .filter(|e| matches!(e, ToSwarm::GenerateEvent(Event::Message { .. })))
+        .count();
+    assert_eq!(received, 2);
+
+    let rate_limited: Vec<_> = gs
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::GenerateEvent(Event::MessageRateLimited { peer_id, topic }) => {
+                Some((*peer_id, topic.clone()))
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(rate_limited, vec![(peers[0], topic_hash)]);
+
+    // Both dropped messages are penalized as invalid message deliveries.
+    assert_eq!(
+        gs.peer_score.as_ref().unwrap().0.score(&peers[0]),
+        -2.0 * 0.7 * 4.0
+    );
+}
+
+#[test]
+fn rate_limit_applies_to_new_messages_on_subscribed_topics() {
+    let clock = libp2p_swarm::ManualClock::new();
+    let config = ConfigBuilder::default()
+        .clock(clock.clone())
+        .message_rate_limit(1, Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    let mut seq = 0;
+    let mut rate_limited = |gs: &mut Behaviour, message: RawMessage| {
+        gs.events.clear();
+        gs.handle_received_message(message, &peers[0]);
+        gs.events
+            .iter()
+            .any(|e| matches!(e, ToSwarm::GenerateEvent(Event::MessageRateLimited { .. })))
+    };
+
+    // Neither messages on topics we are not subscribed to nor duplicates take a token.
+    let unsubscribed = random_message(&mut seq, &vec![TopicHash::from_raw("other")]);
+    assert!(!rate_limited(&mut gs, unsubscribed));
+    let message = random_message(&mut seq, &topics);
+    assert!(!rate_limited(&mut gs, message.clone()));
+    assert!(!rate_limited(&mut gs, message));
+    assert!(rate_limited(&mut gs, random_message(&mut seq, &topics)));
+
+    // The bucket refills over time.
+    clock.advance(Duration::from_secs(60));
+    assert!(!rate_limited(&mut gs, random_message(&mut seq, &topics)));
+    assert!(rate_limited(&mut gs, random_message(&mut seq, &topics)));
+
+    // The buckets of a peer are dropped once it disconnected.
+    disconnect_peer(&mut gs, &peers[0]);
+    assert!(!rate_limited(&mut gs, random_message(&mut seq, &topics)));
+}
+
 #[test]
 fn test_scoring_p4_message_from_self() {
     let config = ConfigBuilder::default()
@@ -4120,6 +4526,7 @@ fn test_scoring_p6() {
             },
             failed_addresses: &[],
             other_established: 0,
+            user_data: None,
         }));
     }
 
@@ -4141,6 +4548,7 @@ fn test_scoring_p6() {
             },
             failed_addresses: &[],
             other_established: 1,
+            user_data: None,
         }));
     }
 
@@ -4171,6 +4579,7 @@ fn test_scoring_p6() {
         },
         failed_addresses: &[],
         other_established: 2,
+        user_data: None,
     }));
 
     //nothing changed
@@ -5260,3 +5669,664 @@ fn test_graft_without_subscribe() {
     // We unsubscribe from the topic.
     let _ = gs.unsubscribe(&Topic::new(topic));
 }
+
+#[test]
+fn test_dropped_messages_are_accumulated_per_peer() {
+    let (mut gs, peers, _) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .create_network();
+
+    assert_eq!(
+        gs.dropped_messages(&peers[0]),
+        Some(DroppedMessages::default())
+    );
+
+    for dropped in [
+        DroppedMessages {
+            control: 0,
+            data: 2,
+            gossip: 5,
+        },
+        DroppedMessages {
+            control: 1,
+            data: 0,
+            gossip: 3,
+        },
+    ] {
+        gs.on_connection_handler_event(
+            peers[0],
+            ConnectionId::new_unchecked(0),
+            HandlerEvent::MessagesDropped(dropped),
+        );
+    }
+
+    let dropped = gs.dropped_messages(&peers[0]).unwrap();
+    assert_eq!(
+        dropped,
+        DroppedMessages {
+            control: 1,
+            data: 2,
+            gossip: 8,
+        }
+    );
+    assert_eq!(dropped.total(), 11);
+
+    disconnect_peer(&mut gs, &peers[0]);
+    assert_eq!(gs.dropped_messages(&peers[0]), None);
+}
+
+fn count_publishable_events<D, F>(gs: &Behaviour<D, F>, topic: &TopicHash) -> usize {
+    gs.events
+        .iter()
+        .filter(|e| {
+            matches!(
+                e,
+                ToSwarm::GenerateEvent(Event::TopicPublishable { topic: t }) if t == topic
+            )
+        })
+        .count()
+}
+
+#[test]
+fn test_publish_without_peers_reports_publishable_topic() {
+    let (mut gs, _, topic_hashes) = inject_nodes1()
+        .peer_no(0)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .create_network();
+
+    for data in [vec![1], vec![2]] {
+        assert!(matches!(
+            gs.publish(topic_hashes[0].clone(), data),
+            Err(PublishError::InsufficientPeers)
+        ));
+    }
+    assert_eq!(count_publishable_events(&gs, &topic_hashes[0]), 0);
+
+    add_peer(&mut gs, &topic_hashes, false, false);
+    assert_eq!(count_publishable_events(&gs, &topic_hashes[0]), 1);
+
+    let published = gs
+        .publish_with_outcome(topic_hashes[0].clone(), vec![3])
+        .unwrap();
+    assert_eq!(published.peers, 1);
+
+    add_peer(&mut gs, &topic_hashes, false, false);
+    assert_eq!(count_publishable_events(&gs, &topic_hashes[0]), 1);
+}
+
+#[test]
+fn test_publish_fails_when_send_queues_are_full() {
+    let (mut gs, peers, topic_hashes) = inject_nodes1()
+        .peer_no(2)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .create_network();
+
+    for peer in &peers {
+        gs.on_connection_handler_event(
+            *peer,
+            ConnectionId::new_unchecked(0),
+            HandlerEvent::MessagesDropped(DroppedMessages {
+                control: 0,
+                data: 1,
+                gossip: 0,
+            }),
+        );
+    }
+
+    assert!(matches!(
+        gs.publish(topic_hashes[0].clone(), vec![1]),
+        Err(PublishError::QueueFull)
+    ));
+
+    // Congestion is not forgotten by a heartbeat, but once the send queue drained.
+    gs.heartbeat();
+    assert_eq!(count_publishable_events(&gs, &topic_hashes[0]), 0);
+    gs.on_connection_handler_event(
+        peers[0],
+        ConnectionId::new_unchecked(0),
+        HandlerEvent::SendQueueDrained,
+    );
+    assert_eq!(count_publishable_events(&gs, &topic_hashes[0]), 1);
+
+    let published = gs
+        .publish_with_outcome(topic_hashes[0].clone(), vec![1])
+        .unwrap();
+    assert_eq!(published.peers, 2);
+}
+
+#[derive(Clone, Default)]
+struct MockTopicDiscovery {
+    requests: std::sync::Arc<std::sync::Mutex<Vec<(TopicHash, usize)>>>,
+    discovered: std::sync::Arc<std::sync::Mutex<VecDeque<DiscoveredPeers>>>,
+}
+
+impl TopicDiscovery for MockTopicDiscovery {
+    fn discover(&mut self, topic: &TopicHash, missing: usize) {
+        self.requests.lock().unwrap().push((topic.clone(), missing));
+    }
+
+    fn poll_discovered(&mut self, _: &mut Context<'_>) -> Poll<DiscoveredPeers> {
+        match self.discovered.lock().unwrap().pop_front() {
+            Some(discovered) => Poll::Ready(discovered),
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[test]
+fn test_topic_discovery_on_low_mesh() {
+    let config = ConfigBuilder::default()
+        .topic_discovery_ticks(3)
+        .build()
+        .unwrap();
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(2)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config.clone())
+        .create_network();
+
+    let discovery = MockTopicDiscovery::default();
+    gs.set_topic_discovery(discovery.clone());
+
+    // The mesh holds both peers, fewer than `mesh_n_low`.
+    gs.heartbeat();
+    assert_eq!(gs.mesh.get(&topics[0]).unwrap().len(), 2);
+    assert_eq!(
+        *discovery.requests.lock().unwrap(),
+        vec![(topics[0].clone(), config.mesh_n() - 2)]
+    );
+
+    // The discovery is only repeated after `topic_discovery_ticks`.
+    gs.heartbeat();
+    gs.heartbeat();
+    assert_eq!(discovery.requests.lock().unwrap().len(), 1);
+    gs.heartbeat();
+    assert_eq!(discovery.requests.lock().unwrap().len(), 2);
+
+    // Discovered peers are dialed, unless already connected.
+    let new_peer = PeerId::random();
+    let address = "/ip4/127.0.0.1/tcp/4001".parse().unwrap();
+    discovery
+        .discovered
+        .lock()
+        .unwrap()
+        .push_back(DiscoveredPeers {
+            topic: topics[0].clone(),
+            peers: vec![(peers[0], vec![]), (new_peer, vec![address])],
+        });
+    gs.events.clear();
+    gs.poll_topic_discovery(&mut Context::from_waker(futures::task::noop_waker_ref()));
+
+    let dialed = gs
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::Dial { opts } => opts.get_peer_id(),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(dialed, vec![new_peer]);
+}
+
+#[test]
+fn test_self_delivery_local() {
+    let config = ConfigBuilder::default()
+        .allow_self_origin(true)
+        .self_delivery(SelfDelivery::Local)
+        .origin_label("bridge-a")
+        .build()
+        .unwrap();
+    let (mut gs, _, topic_hashes) = inject_nodes1()
+        .peer_no(5)
+        .topics(vec![String::from("topic")])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+
+    let first = gs.publish(topic_hashes[0].clone(), vec![1]).unwrap();
+    let second = gs
+        .publish_with_origin(topic_hashes[0].clone(), vec![2], "bridge-b")
+        .unwrap();
+
+    let local_messages: Vec<_> = gs
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::GenerateEvent(Event::LocalMessage {
+                message_id,
+                message,
+                origin,
+            }) => Some((message_id.clone(), message.data.clone(), origin.clone())),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        local_messages,
+        vec![
+            (first, vec![1], Some(String::from("bridge-a"))),
+            (second, vec![2], Some(String::from("bridge-b"))),
+        ]
+    );
+
+    // A message published by ourselves that is received back from the network is not delivered
+    // again.
+    gs.events.clear();
+    let own_id = *gs.publish_config.get_own_id().unwrap();
+    let echo = RawMessage {
+        source: Some(own_id),
+        data: vec![3],
+        sequence_number: Some(0),
+        topic: topic_hashes[0].clone(),
+        signature: None,
+        key: None,
+        validated: true,
+    };
+    gs.handle_received_message(echo, &PeerId::random());
+    assert!(!gs
+        .events
+        .iter()
+        .any(|e| matches!(e, ToSwarm::GenerateEvent(Event::Message { .. }))));
+}
+
+#[test]
+fn test_self_delivery_suppress() {
+    for (self_delivery, delivered) in [
+        (SelfDelivery::Network, true),
+        (SelfDelivery::Suppress, false),
+    ] {
+        let config = ConfigBuilder::default()
+            .allow_self_origin(true)
+            .self_delivery(self_delivery)
+            .build()
+            .unwrap();
+        let (mut gs, _, topic_hashes) = inject_nodes1()
+            .peer_no(5)
+            .topics(vec![String::from("topic")])
+            .to_subscribe(true)
+            .gs_config(config)
+            .create_network();
+
+        gs.publish(topic_hashes[0].clone(), vec![1]).unwrap();
+
+        let own_id = *gs.publish_config.get_own_id().unwrap();
+        let message = RawMessage {
+            source: Some(own_id),
+            data: vec![2],
+            sequence_number: Some(0),
+            topic: topic_hashes[0].clone(),
+            signature: None,
+            key: None,
+            validated: true,
+        };
+        gs.handle_received_message(message, &PeerId::random());
+
+        assert!(!gs
+            .events
+            .iter()
+            .any(|e| matches!(e, ToSwarm::GenerateEvent(Event::LocalMessage { .. }))));
+        assert_eq!(
+            gs.events
+                .iter()
+                .any(|e| matches!(e, ToSwarm::GenerateEvent(Event::Message { .. }))),
+            delivered
+        );
+    }
+}
+
+#[test]
+fn heartbeat_follows_clock() {
+    use futures::StreamExt;
+    use libp2p_core::{transport::dummy::DummyTransport, Transport};
+    use libp2p_swarm::{ManualClock, SwarmBuilder};
+
+    let clock = ManualClock::new();
+    let config = ConfigBuilder::default()
+        .heartbeat_initial_delay(Duration::from_secs(5))
+        .heartbeat_interval(Duration::from_secs(1))
+        .clock(clock.clone())
+        .validation_mode(ValidationMode::Anonymous)
+        .build()
+        .unwrap();
+    let gs: Behaviour = Behaviour::new(MessageAuthenticity::Anonymous, config).unwrap();
+    let mut swarm =
+        SwarmBuilder::without_executor(DummyTransport::new().boxed(), gs, PeerId::random()).build();
+    let waker = futures::task::noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut heartbeats = |clock: &ManualClock, duration: Duration| {
+        clock.advance(duration);
+        while swarm.poll_next_unpin(&mut cx).is_ready() {}
+        swarm.behaviour().heartbeat_ticks
+    };
+
+    assert_eq!(heartbeats(&clock, Duration::ZERO), 0);
+    assert_eq!(heartbeats(&clock, Duration::from_millis(5500)), 1);
+    // The next heartbeat is due one interval after the previous one was due, regardless of when
+    // the behaviour was polled.
+    assert_eq!(heartbeats(&clock, Duration::from_millis(500)), 2);
+    assert_eq!(heartbeats(&clock, Duration::from_millis(999)), 2);
+    assert_eq!(heartbeats(&clock, Duration::from_millis(1)), 3);
+}
+
+#[test]
+fn backoffs_and_promises_follow_clock() {
+    use libp2p_swarm::Clock;
+
+    let clock = libp2p_swarm::ManualClock::new();
+    let config = ConfigBuilder::default()
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config)
+        .scoring(Some((
+            PeerScoreParams::default(),
+            PeerScoreThresholds::default(),
+        )))
+        .create_network();
+
+    clock.advance(Duration::from_secs(3600));
+    gs.backoffs
+        .update_backoff(&topics[0], &peers[0], Duration::from_secs(2));
+    assert_eq!(
+        gs.backoffs.get_backoff_time(&topics[0], &peers[0]),
+        Some(clock.now() + Duration::from_secs(2))
+    );
+
+    // An IWANT is broken once the follow-up time passed on the clock.
+    let (.., gossip_promises) = gs.peer_score.as_mut().unwrap();
+    gossip_promises.add_promise(
+        peers[0],
+        &[MessageId::new(b"message")],
+        clock.now() + Duration::from_secs(3),
+    );
+    clock.advance(Duration::from_secs(4));
+    gs.apply_iwant_penalties();
+    let (peer_score, ..) = gs.peer_score.as_ref().unwrap();
+    assert!(peer_score.score(&peers[0]) < 0.0);
+}
+
+#[test]
+fn test_ordered_delivery() {
+    let topic_hash = Topic::new("test").hash();
+    let config = ConfigBuilder::default()
+        .ordered_delivery(topic_hash.clone(), 10, Duration::from_secs(60))
+        .build()
+        .unwrap();
+
+    let (mut gs, peers, _) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config)
+        .create_network();
+    gs.events.clear();
+
+    let author = PeerId::random();
+    for sequence_number in [1u64, 3, 4, 2, 3] {
+        let message = RawMessage {
+            source: Some(author),
+            data: sequence_number.to_be_bytes().to_vec(),
+            sequence_number: Some(sequence_number),
+            topic: topic_hash.clone(),
+            signature: None,
+            key: None,
+            validated: true,
+        };
+        gs.handle_received_message(message, &peers[0]);
+    }
+
+    // Messages are delivered in order of their sequence numbers, the duplicate only once.
+    let delivered: Vec<_> = gs
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::GenerateEvent(Event::Message { message, .. }) => message.sequence_number,
+            _ => None,
+        })
+        .collect();
+    assert_eq!(delivered, vec![1, 2, 3, 4]);
+}
+
+#[test]
+fn test_duplicate_suppression() {
+    let topic_hash = Topic::new("test").hash();
+    let window = Duration::from_secs(60);
+    // The sequence number and data of the received messages.
+    let messages = [(1u64, 1u8), (2, 1), (2, 2), (3, 3)];
+
+    for (suppression, expected) in [
+        (
+            DuplicateSuppression::ContentHash { window },
+            vec![(1, 1), (2, 2), (3, 3)],
+        ),
+        (
+            DuplicateSuppression::SourceSequenceNumber { window },
+            vec![(1, 1), (2, 1), (3, 3)],
+        ),
+    ] {
+        let config = ConfigBuilder::default()
+            // Message ids are unique to let the suppression decide on duplicates.
+            .message_id_fn(|message| {
+                let mut id = message.sequence_number.unwrap().to_be_bytes().to_vec();
+                id.extend_from_slice(&message.data);
+                MessageId::new(&id)
+            })
+            .duplicate_suppression(suppression)
+            .build()
+            .unwrap();
+        let (mut gs, peers, _) = inject_nodes1()
+            .peer_no(1)
+            .topics(vec!["test".into()])
+            .to_subscribe(true)
+            .gs_config(config)
+            .create_network();
+        gs.events.clear();
+
+        let author = PeerId::random();
+        for (sequence_number, data) in messages {
+            let message = RawMessage {
+                source: Some(author),
+                data: vec![data],
+                sequence_number: Some(sequence_number),
+                topic: topic_hash.clone(),
+                signature: None,
+                key: None,
+                validated: true,
+            };
+            gs.handle_received_message(message, &peers[0]);
+        }
+
+        let delivered: Vec<_> = gs
+            .events
+            .iter()
+            .filter_map(|e| match e {
+                ToSwarm::GenerateEvent(Event::Message { message, .. }) => {
+                    Some((message.sequence_number.unwrap(), message.data[0]))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(delivered, expected, "{suppression:?}");
+    }
+}
+
+#[test]
+fn suppressed_duplicates_resolve_gossip_promises() {
+    use libp2p_swarm::Clock;
+
+    let clock = libp2p_swarm::ManualClock::new();
+    let topic_hash = Topic::new("test").hash();
+    let message_id = |sequence_number: u64| MessageId::new(&sequence_number.to_be_bytes());
+    let config = ConfigBuilder::default()
+        .clock(clock.clone())
+        .message_id_fn(move |message| message_id(message.sequence_number.unwrap()))
+        .duplicate_suppression(DuplicateSuppression::ContentHash {
+            window: Duration::from_secs(60),
+        })
+        .build()
+        .unwrap();
+    let (mut gs, peers, _) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config)
+        .scoring(Some((
+            PeerScoreParams::default(),
+            PeerScoreThresholds::default(),
+        )))
+        .create_network();
+
+    // The second message was promised by the peer and is suppressed as it has the same content.
+    let (.., gossip_promises) = gs.peer_score.as_mut().unwrap();
+    gossip_promises.add_promise(
+        peers[0],
+        &[message_id(2)],
+        clock.now() + Duration::from_secs(3),
+    );
+    for sequence_number in [1, 2] {
+        let message = RawMessage {
+            source: Some(PeerId::random()),
+            data: vec![1],
+            sequence_number: Some(sequence_number),
+            topic: topic_hash.clone(),
+            signature: None,
+            key: None,
+            validated: true,
+        };
+        gs.handle_received_message(message, &peers[0]);
+    }
+    assert!(gs.mcache.get(&message_id(1)).is_some());
+    assert!(gs.mcache.get(&message_id(2)).is_none());
+
+    clock.advance(Duration::from_secs(4));
+    gs.apply_iwant_penalties();
+    let (peer_score, ..) = gs.peer_score.as_ref().unwrap();
+    assert_eq!(peer_score.score(&peers[0]), 0.0);
+}
+
+#[test]
+fn test_messages_ignored_by_the_application_are_not_reported() {
+    let config = ConfigBuilder::default()
+        .validate_messages()
+        .build()
+        .unwrap();
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(1)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .gs_config(config.clone())
+        .create_network();
+
+    let mut seq = 0;
+    let mut receive = |gs: &mut Behaviour| {
+        let m = random_message(&mut seq, &topics);
+        let id = config.message_id(&gs.data_transform.inbound_transform(m.clone()).unwrap());
+        gs.handle_received_message(m, &peers[0]);
+        id
+    };
+    let ignored_id = receive(&mut gs);
+    let rejected_id = receive(&mut gs);
+    gs.events.clear();
+
+    assert!(gs
+        .report_message_validation_result(&ignored_id, &peers[0], MessageAcceptance::Ignore)
+        .unwrap());
+    assert!(gs
+        .report_message_validation_result(&rejected_id, &peers[0], MessageAcceptance::Reject)
+        .unwrap());
+
+    let rejected: Vec<_> = gs
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::GenerateEvent(Event::MessageRejected {
+                message_id, reason, ..
+            }) => Some((message_id.clone(), *reason)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        rejected,
+        vec![(Some(rejected_id), RejectReason::ValidationFailed)]
+    );
+}
+
+#[test]
+fn test_rejected_messages_are_reported() {
+    let (mut gs, peers, topics) = inject_nodes1()
+        .peer_no(2)
+        .topics(vec!["test".into()])
+        .to_subscribe(true)
+        .create_network();
+
+    let mut seq = 0;
+    gs.events.clear();
+
+    // A message from a blacklisted peer.
+    gs.blacklist_peer(&peers[0]);
+    let m = random_message(&mut seq, &topics);
+    let blacklisted_id = gs
+        .config
+        .message_id(&gs.data_transform.inbound_transform(m.clone()).unwrap());
+    gs.handle_received_message(m, &peers[0]);
+
+    // An invalid message and an oversized RPC.
+    let m = random_message(&mut seq, &topics);
+    let invalid_id = gs
+        .config
+        .message_id(&gs.data_transform.inbound_transform(m.clone()).unwrap());
+    gs.on_connection_handler_event(
+        peers[1],
+        ConnectionId::new_unchecked(0),
+        HandlerEvent::Message {
+            rpc: Rpc {
+                messages: vec![],
+                subscriptions: vec![],
+                control_msgs: vec![],
+            },
+            invalid_messages: vec![(m, ValidationError::InvalidSignature)],
+        },
+    );
+    gs.on_connection_handler_event(
+        peers[1],
+        ConnectionId::new_unchecked(0),
+        HandlerEvent::RpcTooLarge,
+    );
+
+    let rejected: Vec<_> = gs
+        .events
+        .iter()
+        .filter_map(|e| match e {
+            ToSwarm::GenerateEvent(Event::MessageRejected {
+                propagation_source,
+                message_id,
+                reason,
+            }) => Some((*propagation_source, message_id.clone(), *reason)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        rejected,
+        vec![
+            (
+                peers[0],
+                Some(blacklisted_id),
+                RejectReason::BlackListedPeer
+            ),
+            (
+                peers[1],
+                Some(invalid_id),
+                RejectReason::ValidationError(ValidationError::InvalidSignature)
+            ),
+            (peers[1], None, RejectReason::TooLarge),
+        ]
+    );
+}
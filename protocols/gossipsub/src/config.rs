@@ -19,11 +19,16 @@
 // DEALINGS IN THE SOFTWARE.
 
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
 use libp2p_identity::PeerId;
+use libp2p_swarm::{Clock, SystemClock};
+use sha2::{Digest, Sha256};
 
+use crate::rpc_limits::RpcLimits;
+use crate::topic::TopicHash;
 use crate::types::{FastMessageId, Message, MessageId, RawMessage};
 
 /// The types of message validation that can be employed by gossipsub.
@@ -49,6 +54,65 @@ pub enum ValidationMode {
     None,
 }
 
+/// How messages published by the local node are delivered to the local application.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfDelivery {
+    /// This is the default setting. Published messages are not delivered locally, but are
+    /// delivered like any other message if they are received back from the network, which only
+    /// happens if [`Config::allow_self_origin`] is enabled.
+    Network,
+    /// Published messages are never delivered locally, even if they are received back from
+    /// the network.
+    Suppress,
+    /// Published messages are delivered locally as [`crate::Event::LocalMessage`] as soon as
+    /// they are published, and not again if they are received back from the network.
+    Local,
+}
+
+/// Suppression of duplicate messages in addition to the duplicate cache of message ids, see
+/// [`ConfigBuilder::duplicate_suppression`].
+///
+/// Suppressed messages are neither delivered to the application nor forwarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateSuppression {
+    /// Suppresses messages with the author and sequence number of a message received within
+    /// `window`. Messages without an author or sequence number, e.g. in
+    /// [`ValidationMode::Anonymous`], are not suppressed.
+    SourceSequenceNumber { window: Duration },
+    /// Suppresses messages with the topic and data of a message received within `window`.
+    /// Applications resending identical data must wait for `window` to pass.
+    ContentHash { window: Duration },
+}
+
+impl DuplicateSuppression {
+    /// The time a message suppresses its duplicates.
+    pub fn window(&self) -> Duration {
+        match self {
+            DuplicateSuppression::SourceSequenceNumber { window }
+            | DuplicateSuppression::ContentHash { window } => *window,
+        }
+    }
+
+    /// The key identifying the duplicates of a message, if they are suppressed.
+    pub(crate) fn key(&self, message: &Message) -> Option<Vec<u8>> {
+        match self {
+            DuplicateSuppression::SourceSequenceNumber { .. } => {
+                let mut key = message.source?.to_bytes();
+                key.extend_from_slice(&message.sequence_number?.to_be_bytes());
+                Some(key)
+            }
+            DuplicateSuppression::ContentHash { .. } => {
+                let topic = message.topic.as_str().as_bytes();
+                let mut hasher = Sha256::new();
+                hasher.update((topic.len() as u64).to_be_bytes());
+                hasher.update(topic);
+                hasher.update(&message.data);
+                Some(hasher.finalize().to_vec())
+            }
+        }
+    }
+}
+
 /// Selector for custom Protocol Id
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Version {
@@ -63,6 +127,7 @@ pub struct Config {
     custom_id_version: Option<Version>,
     history_length: usize,
     history_gossip: usize,
+    history_max_bytes: Option<usize>,
     mesh_n: usize,
     mesh_n_low: usize,
     mesh_n_high: usize,
@@ -73,31 +138,42 @@ pub struct Config {
     heartbeat_interval: Duration,
     fanout_ttl: Duration,
     check_explicit_peers_ticks: u64,
+    topic_discovery_ticks: u64,
     max_transmit_size: usize,
+    rpc_limits: RpcLimits,
     idle_timeout: Duration,
+    max_send_queue_size: usize,
     duplicate_cache_time: Duration,
     validate_messages: bool,
     validation_mode: ValidationMode,
     message_id_fn: Arc<dyn Fn(&Message) -> MessageId + Send + Sync + 'static>,
     fast_message_id_fn: Option<Arc<dyn Fn(&RawMessage) -> FastMessageId + Send + Sync + 'static>>,
     allow_self_origin: bool,
+    self_delivery: SelfDelivery,
+    origin_label: Option<String>,
     do_px: bool,
     prune_peers: usize,
     prune_backoff: Duration,
     unsubscribe_backoff: Duration,
     backoff_slack: u32,
     flood_publish: bool,
+    backfill_on_graft: bool,
     graft_flood_threshold: Duration,
+    mesh_reconnect_grace: Option<Duration>,
     mesh_outbound_min: usize,
     opportunistic_graft_ticks: u64,
     opportunistic_graft_peers: usize,
     gossip_retransimission: u32,
     max_messages_per_rpc: Option<usize>,
+    message_rate_limit: Option<(u32, Duration)>,
+    ordered_topics: HashMap<TopicHash, (usize, Duration)>,
+    duplicate_suppression: Option<DuplicateSuppression>,
     max_ihave_length: usize,
     max_ihave_messages: usize,
     iwant_followup_time: Duration,
     support_floodsub: bool,
     published_message_ids_cache_time: Duration,
+    clock: Arc<dyn Clock>,
 }
 
 impl Config {
@@ -130,6 +206,14 @@ impl Config {
         self.history_gossip
     }
 
+    /// The maximum total size in bytes of the messages kept in the `memcache`. Once exceeded, the
+    /// oldest validated messages are evicted before [`Config::history_length`] heartbeats passed.
+    /// Messages pending validation are never evicted. If this is unset, there is no limit. The
+    /// default is None.
+    pub fn history_max_bytes(&self) -> Option<usize> {
+        self.history_max_bytes
+    }
+
     /// Target number of peers for the mesh network (D in the spec, default is 6).
     pub fn mesh_n(&self) -> usize {
         self.mesh_n
@@ -189,6 +273,13 @@ impl Config {
         self.check_explicit_peers_ticks
     }
 
+    /// The number of heartbeat ticks until we again ask the
+    /// [`TopicDiscovery`](crate::TopicDiscovery) for peers of a topic whose mesh is still below
+    /// `mesh_n_low` (default 60).
+    pub fn topic_discovery_ticks(&self) -> u64 {
+        self.topic_discovery_ticks
+    }
+
     /// The maximum byte size for each gossipsub RPC (default is 65536 bytes).
     ///
     /// This represents the maximum size of the entire protobuf payload. It must be at least
@@ -199,6 +290,13 @@ impl Config {
         self.max_transmit_size
     }
 
+    /// The limits on the number of messages, subscriptions and control message entries of a
+    /// single inbound RPC. RPCs exceeding them are dropped before being decoded. See
+    /// [`RpcLimits`] for the defaults.
+    pub fn rpc_limits(&self) -> &RpcLimits {
+        &self.rpc_limits
+    }
+
     /// The time a connection is maintained to a peer without being in the mesh and without
     /// send/receiving a message from. Connections that idle beyond this timeout are disconnected.
     /// Default is 120 seconds.
@@ -206,6 +304,13 @@ impl Config {
         self.idle_timeout
     }
 
+    /// The maximum number of RPCs queued for sending on a single connection. Once the queue is
+    /// full, gossip is dropped first, then published and forwarded messages, to make room for
+    /// control messages. Default is 5000.
+    pub fn max_send_queue_size(&self) -> usize {
+        self.max_send_queue_size
+    }
+
     /// Duplicates are prevented by storing message id's of known messages in an LRU time cache.
     /// This settings sets the time period that messages are stored in the cache. Duplicates can be
     /// received if duplicate messages are sent at a time greater than this setting apart. The
@@ -262,6 +367,20 @@ impl Config {
         self.allow_self_origin
     }
 
+    /// How messages published by the local node are delivered to the local application. The
+    /// default is [`SelfDelivery::Network`].
+    pub fn self_delivery(&self) -> SelfDelivery {
+        self.self_delivery
+    }
+
+    /// The label attached to messages published by the local node when they are delivered
+    /// locally, unless another origin is given in [`crate::Behaviour::publish_with_origin`].
+    /// This allows processes running several bridged swarms to tell which swarm a message
+    /// entered through. The default is None.
+    pub fn origin_label(&self) -> Option<&str> {
+        self.origin_label.as_deref()
+    }
+
     /// Whether Peer eXchange is enabled; this should be enabled in bootstrappers and other well
     /// connected/trusted nodes. The default is false.
     ///
@@ -317,12 +436,29 @@ impl Config {
         self.flood_publish
     }
 
+    /// Whether to send an IHAVE for the recently seen messages of a topic to a peer grafting us
+    /// into the mesh of that topic. This allows peers that just joined the topic to fetch the
+    /// messages published shortly before via IWANT, instead of missing them until the next
+    /// message arrives. See also [`crate::Behaviour::request_history`]. The default is false.
+    pub fn backfill_on_graft(&self) -> bool {
+        self.backfill_on_graft
+    }
+
     /// If a GRAFT comes before `graft_flood_threshold` has elapsed since the last PRUNE,
     /// then there is an extra score penalty applied to the peer through P7.
     pub fn graft_flood_threshold(&self) -> Duration {
         self.graft_flood_threshold
     }
 
+    /// The time within which a mesh peer that disconnected is grafted back into the meshes it
+    /// was part of once it reconnects and subscribes again, as long as they have fewer than
+    /// `mesh_n_high` peers. Otherwise a reconnecting peer is treated like any other candidate and
+    /// only added while a mesh has fewer than `mesh_n_low` peers. This reduces the mesh churn
+    /// caused by flaky transports. The default is `None`, i.e. disabled.
+    pub fn mesh_reconnect_grace(&self) -> Option<Duration> {
+        self.mesh_reconnect_grace
+    }
+
     /// Minimum number of outbound peers in the mesh network before adding more (D_out in the spec).
     /// This value must be smaller or equal than `mesh_n / 2` and smaller than `mesh_n_low`.
     /// The default is 2.
@@ -357,6 +493,27 @@ impl Config {
         self.max_messages_per_rpc
     }
 
+    /// The token bucket limiting the messages accepted from each peer on each topic, as its
+    /// capacity and the interval in which one token is added. Messages exceeding the limit are
+    /// dropped and penalized like invalid messages. If this is unset, there is no limit. The
+    /// default is None.
+    pub fn message_rate_limit(&self) -> Option<(u32, Duration)> {
+        self.message_rate_limit
+    }
+
+    /// The topics whose messages are delivered in the order of their sequence numbers per author,
+    /// with the maximum number of messages held back per author and the maximum time a message
+    /// is held back. The default is no topics.
+    pub fn ordered_topics(&self) -> &HashMap<TopicHash, (usize, Duration)> {
+        &self.ordered_topics
+    }
+
+    /// The suppression of duplicate messages in addition to the duplicate cache of message ids.
+    /// The default is None.
+    pub fn duplicate_suppression(&self) -> Option<DuplicateSuppression> {
+        self.duplicate_suppression
+    }
+
     /// The maximum number of messages to include in an IHAVE message.
     /// Also controls the maximum number of IHAVE ids we will accept and request with IWANT from a
     /// peer within a heartbeat, to protect from IHAVE floods. You should adjust this value from the
@@ -388,6 +545,18 @@ impl Config {
     pub fn published_message_ids_cache_time(&self) -> Duration {
         self.published_message_ids_cache_time
     }
+
+    /// The [`Clock`] providing the current time and the timers of the behaviour, e.g. of the
+    /// heartbeat, the decay of peer scores and the expiry of backoffs and caches. The default is
+    /// the [`SystemClock`].
+    pub fn clock(&self) -> &dyn Clock {
+        &*self.clock
+    }
+
+    /// The [`Clock`] of the config, to be shared with the data structures of the behaviour.
+    pub(crate) fn shared_clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
+    }
 }
 
 impl Default for Config {
@@ -412,6 +581,7 @@ impl Default for ConfigBuilder {
                 custom_id_version: None,
                 history_length: 5,
                 history_gossip: 3,
+                history_max_bytes: None,
                 mesh_n: 6,
                 mesh_n_low: 5,
                 mesh_n_high: 12,
@@ -422,8 +592,11 @@ impl Default for ConfigBuilder {
                 heartbeat_interval: Duration::from_secs(1),
                 fanout_ttl: Duration::from_secs(60),
                 check_explicit_peers_ticks: 300,
+                topic_discovery_ticks: 60,
                 max_transmit_size: 65536,
+                rpc_limits: RpcLimits::default(),
                 idle_timeout: Duration::from_secs(120),
+                max_send_queue_size: 5000,
                 duplicate_cache_time: Duration::from_secs(60),
                 validate_messages: false,
                 validation_mode: ValidationMode::Strict,
@@ -443,23 +616,31 @@ impl Default for ConfigBuilder {
                 }),
                 fast_message_id_fn: None,
                 allow_self_origin: false,
+                self_delivery: SelfDelivery::Network,
+                origin_label: None,
                 do_px: false,
                 prune_peers: 0, // NOTE: Increasing this currently has little effect until Signed records are implemented.
                 prune_backoff: Duration::from_secs(60),
                 unsubscribe_backoff: Duration::from_secs(10),
                 backoff_slack: 1,
                 flood_publish: true,
+                backfill_on_graft: false,
                 graft_flood_threshold: Duration::from_secs(10),
+                mesh_reconnect_grace: None,
                 mesh_outbound_min: 2,
                 opportunistic_graft_ticks: 60,
                 opportunistic_graft_peers: 2,
                 gossip_retransimission: 3,
                 max_messages_per_rpc: None,
+                message_rate_limit: None,
+                ordered_topics: HashMap::new(),
+                duplicate_suppression: None,
                 max_ihave_length: 5000,
                 max_ihave_messages: 10,
                 iwant_followup_time: Duration::from_secs(3),
                 support_floodsub: false,
                 published_message_ids_cache_time: Duration::from_secs(10),
+                clock: Arc::new(SystemClock),
             },
         }
     }
@@ -505,6 +686,15 @@ impl ConfigBuilder {
         self
     }
 
+    /// The maximum total size in bytes of the messages kept in the `memcache`. Once exceeded, the
+    /// oldest validated messages are evicted before [`Config::history_length`] heartbeats passed.
+    /// Messages pending validation are never evicted. If this is unset, there is no limit. The
+    /// default is None.
+    pub fn history_max_bytes(&mut self, max_bytes: Option<usize>) -> &mut Self {
+        self.config.history_max_bytes = max_bytes;
+        self
+    }
+
     /// Target number of peers for the mesh network (D in the spec, default is 6).
     pub fn mesh_n(&mut self, mesh_n: usize) -> &mut Self {
         self.config.mesh_n = mesh_n;
@@ -568,6 +758,14 @@ impl ConfigBuilder {
         self
     }
 
+    /// The number of heartbeat ticks until we again ask the
+    /// [`TopicDiscovery`](crate::TopicDiscovery) for peers of a topic whose mesh is still below
+    /// `mesh_n_low` (default 60).
+    pub fn topic_discovery_ticks(&mut self, topic_discovery_ticks: u64) -> &mut Self {
+        self.config.topic_discovery_ticks = topic_discovery_ticks;
+        self
+    }
+
     /// Time to live for fanout peers (default is 60 seconds).
     pub fn fanout_ttl(&mut self, fanout_ttl: Duration) -> &mut Self {
         self.config.fanout_ttl = fanout_ttl;
@@ -580,6 +778,13 @@ impl ConfigBuilder {
         self
     }
 
+    /// The limits on the number of entries of a single inbound RPC (see [`RpcLimits`] for the
+    /// defaults).
+    pub fn rpc_limits(&mut self, rpc_limits: RpcLimits) -> &mut Self {
+        self.config.rpc_limits = rpc_limits;
+        self
+    }
+
     /// The time a connection is maintained to a peer without being in the mesh and without
     /// send/receiving a message from. Connections that idle beyond this timeout are disconnected.
     /// Default is 120 seconds.
@@ -588,6 +793,14 @@ impl ConfigBuilder {
         self
     }
 
+    /// The maximum number of RPCs queued for sending on a single connection. Once the queue is
+    /// full, gossip is dropped first, then published and forwarded messages, to make room for
+    /// control messages. Default is 5000.
+    pub fn max_send_queue_size(&mut self, max_send_queue_size: usize) -> &mut Self {
+        self.config.max_send_queue_size = max_send_queue_size;
+        self
+    }
+
     /// Duplicates are prevented by storing message id's of known messages in an LRU time cache.
     /// This settings sets the time period that messages are stored in the cache. Duplicates can be
     /// received if duplicate messages are sent at a time greater than this setting apart. The
@@ -706,6 +919,15 @@ impl ConfigBuilder {
         self
     }
 
+    /// Whether to send an IHAVE for the recently seen messages of a topic to a peer grafting us
+    /// into the mesh of that topic. This allows peers that just joined the topic to fetch the
+    /// messages published shortly before via IWANT, instead of missing them until the next
+    /// message arrives. See also [`crate::Behaviour::request_history`]. The default is false.
+    pub fn backfill_on_graft(&mut self, backfill_on_graft: bool) -> &mut Self {
+        self.config.backfill_on_graft = backfill_on_graft;
+        self
+    }
+
     /// If a GRAFT comes before `graft_flood_threshold` has elapsed since the last PRUNE,
     /// then there is an extra score penalty applied to the peer through P7.
     pub fn graft_flood_threshold(&mut self, graft_flood_threshold: Duration) -> &mut Self {
@@ -713,6 +935,16 @@ impl ConfigBuilder {
         self
     }
 
+    /// The time within which a mesh peer that disconnected is grafted back into the meshes it
+    /// was part of once it reconnects and subscribes again, as long as they have fewer than
+    /// `mesh_n_high` peers. Otherwise a reconnecting peer is treated like any other candidate and
+    /// only added while a mesh has fewer than `mesh_n_low` peers. This reduces the mesh churn
+    /// caused by flaky transports. The default is `None`, i.e. disabled.
+    pub fn mesh_reconnect_grace(&mut self, mesh_reconnect_grace: Duration) -> &mut Self {
+        self.config.mesh_reconnect_grace = Some(mesh_reconnect_grace);
+        self
+    }
+
     /// Minimum number of outbound peers in the mesh network before adding more (D_out in the spec).
     /// This value must be smaller or equal than `mesh_n / 2` and smaller than `mesh_n_low`.
     /// The default is 2.
@@ -752,6 +984,54 @@ impl ConfigBuilder {
         self
     }
 
+    /// Limits the messages accepted from each peer on each topic with a token bucket of
+    /// `capacity` tokens, one of which is added every `refill_interval`. Only new messages on
+    /// topics we are subscribed to take a token. Messages exceeding the limit are dropped before
+    /// they are delivered or forwarded and penalized like invalid messages. By default, there is
+    /// no limit.
+    pub fn message_rate_limit(&mut self, capacity: u32, refill_interval: Duration) -> &mut Self {
+        self.config.message_rate_limit = Some((capacity, refill_interval));
+        self
+    }
+
+    /// Delivers the messages of each author on `topic` to the application in the order of their
+    /// sequence numbers. Out-of-order messages are held back until the preceding messages
+    /// arrived, but at most `window` messages per author and for at most `max_delay`. Messages
+    /// arriving after a later message of the same author was delivered are dropped.
+    ///
+    /// Sequence numbers only increase by one from message to message of an author publishing
+    /// with [`crate::MessageAuthenticity::Signed`] on a single topic; gaps are skipped once
+    /// `window` or `max_delay` is exceeded. Messages without an author or sequence number are
+    /// delivered right away.
+    ///
+    /// Ordered delivery can't be combined with [`ConfigBuilder::validate_messages`], as held back
+    /// and dropped messages would only be forwarded once validated by the application.
+    pub fn ordered_delivery(
+        &mut self,
+        topic: TopicHash,
+        window: usize,
+        max_delay: Duration,
+    ) -> &mut Self {
+        self.config
+            .ordered_topics
+            .insert(topic, (window, max_delay));
+        self
+    }
+
+    /// Suppresses duplicate messages in addition to the messages with known message ids, see
+    /// [`ConfigBuilder::duplicate_cache_time`].
+    ///
+    /// Allows suppressing duplicates by another key than the message id, within a window
+    /// independent of the duplicate cache time. Unlike a content-based
+    /// [`ConfigBuilder::message_id_fn`], [`DuplicateSuppression::SourceSequenceNumber`] does not
+    /// suppress identical data published repeatedly, while
+    /// [`DuplicateSuppression::ContentHash`] also applies to anonymous messages. The default is
+    /// None.
+    pub fn duplicate_suppression(&mut self, suppression: DuplicateSuppression) -> &mut Self {
+        self.config.duplicate_suppression = Some(suppression);
+        self
+    }
+
     /// The maximum number of messages to include in an IHAVE message.
     /// Also controls the maximum number of IHAVE ids we will accept and request with IWANT from a
     /// peer within a heartbeat, to protect from IHAVE floods. You should adjust this value from the
@@ -777,6 +1057,20 @@ impl ConfigBuilder {
         self
     }
 
+    /// Sets how messages published by the local node are delivered to the local application.
+    /// The default is [`SelfDelivery::Network`].
+    pub fn self_delivery(&mut self, self_delivery: SelfDelivery) -> &mut Self {
+        self.config.self_delivery = self_delivery;
+        self
+    }
+
+    /// Sets the label attached to messages published by the local node when they are delivered
+    /// locally with [`SelfDelivery::Local`]. By default, no label is attached.
+    pub fn origin_label(&mut self, origin_label: impl Into<String>) -> &mut Self {
+        self.config.origin_label = Some(origin_label.into());
+        self
+    }
+
     /// Time to wait for a message requested through IWANT following an IHAVE advertisement.
     /// If the message is not received within this window, a broken promise is declared and
     /// the router may apply behavioural penalties. The default is 3 seconds.
@@ -800,6 +1094,15 @@ impl ConfigBuilder {
         self
     }
 
+    /// Sets the [`Clock`] providing the current time and the timers of the behaviour, e.g. of the
+    /// heartbeat, the decay of peer scores and the expiry of backoffs and caches.
+    /// Timers of a [`libp2p_swarm::ManualClock`] only fire when the clock is advanced, which
+    /// allows driving the heartbeat deterministically. The default is the [`SystemClock`].
+    pub fn clock(&mut self, clock: impl Clock) -> &mut Self {
+        self.config.clock = Arc::new(clock);
+        self
+    }
+
     /// Constructs a [`Config`] from the given configuration and validates the settings.
     pub fn build(&self) -> Result<Config, &'static str> {
         // check all constraints on config
@@ -808,6 +1111,31 @@ impl ConfigBuilder {
             return Err("The maximum transmission size must be greater than 100 to permit basic control messages");
         }
 
+        if self.config.max_send_queue_size == 0 {
+            return Err("The max_send_queue_size must be greater than 0");
+        }
+
+        if let Some((capacity, refill_interval)) = self.config.message_rate_limit {
+            if capacity == 0 || refill_interval.is_zero() {
+                return Err(
+                    "The message_rate_limit capacity and refill interval must be greater than 0",
+                );
+            }
+        }
+
+        if self
+            .config
+            .ordered_topics
+            .values()
+            .any(|(window, _)| *window == 0)
+        {
+            return Err("The ordered_delivery window must be greater than 0");
+        }
+
+        if self.config.validate_messages && !self.config.ordered_topics.is_empty() {
+            return Err("Ordered delivery can't be combined with validate_messages");
+        }
+
         if self.config.history_length < self.config.history_gossip {
             return Err(
                 "The history_length must be greater than or equal to the history_gossip \
@@ -844,6 +1172,7 @@ impl std::fmt::Debug for Config {
         let _ = builder.field("custom_id_version", &self.custom_id_version);
         let _ = builder.field("history_length", &self.history_length);
         let _ = builder.field("history_gossip", &self.history_gossip);
+        let _ = builder.field("history_max_bytes", &self.history_max_bytes);
         let _ = builder.field("mesh_n", &self.mesh_n);
         let _ = builder.field("mesh_n_low", &self.mesh_n_low);
         let _ = builder.field("mesh_n_high", &self.mesh_n_high);
@@ -854,21 +1183,30 @@ impl std::fmt::Debug for Config {
         let _ = builder.field("heartbeat_interval", &self.heartbeat_interval);
         let _ = builder.field("fanout_ttl", &self.fanout_ttl);
         let _ = builder.field("max_transmit_size", &self.max_transmit_size);
+        let _ = builder.field("rpc_limits", &self.rpc_limits);
         let _ = builder.field("idle_timeout", &self.idle_timeout);
+        let _ = builder.field("max_send_queue_size", &self.max_send_queue_size);
         let _ = builder.field("duplicate_cache_time", &self.duplicate_cache_time);
         let _ = builder.field("validate_messages", &self.validate_messages);
         let _ = builder.field("validation_mode", &self.validation_mode);
         let _ = builder.field("allow_self_origin", &self.allow_self_origin);
+        let _ = builder.field("self_delivery", &self.self_delivery);
+        let _ = builder.field("origin_label", &self.origin_label);
         let _ = builder.field("do_px", &self.do_px);
         let _ = builder.field("prune_peers", &self.prune_peers);
         let _ = builder.field("prune_backoff", &self.prune_backoff);
         let _ = builder.field("backoff_slack", &self.backoff_slack);
         let _ = builder.field("flood_publish", &self.flood_publish);
+        let _ = builder.field("backfill_on_graft", &self.backfill_on_graft);
         let _ = builder.field("graft_flood_threshold", &self.graft_flood_threshold);
+        let _ = builder.field("mesh_reconnect_grace", &self.mesh_reconnect_grace);
         let _ = builder.field("mesh_outbound_min", &self.mesh_outbound_min);
         let _ = builder.field("opportunistic_graft_ticks", &self.opportunistic_graft_ticks);
         let _ = builder.field("opportunistic_graft_peers", &self.opportunistic_graft_peers);
         let _ = builder.field("max_messages_per_rpc", &self.max_messages_per_rpc);
+        let _ = builder.field("message_rate_limit", &self.message_rate_limit);
+        let _ = builder.field("ordered_topics", &self.ordered_topics);
+        let _ = builder.field("duplicate_suppression", &self.duplicate_suppression);
         let _ = builder.field("max_ihave_length", &self.max_ihave_length);
         let _ = builder.field("max_ihave_messages", &self.max_ihave_messages);
         let _ = builder.field("iwant_followup_time", &self.iwant_followup_time);
@@ -877,6 +1215,7 @@ impl std::fmt::Debug for Config {
             "published_message_ids_cache_time",
             &self.published_message_ids_cache_time,
         );
+        let _ = builder.field("clock", &self.clock);
         builder.finish()
     }
 }
@@ -1022,4 +1361,14 @@ mod test {
         assert_eq!(protocol_ids[0].protocol_id, b"purple".to_vec());
         assert_eq!(protocol_ids[0].kind, PeerKind::Gossipsub);
     }
+
+    #[test]
+    fn ordered_delivery_rejects_validate_messages() {
+        let result = ConfigBuilder::default()
+            .ordered_delivery(TopicHash::from_raw("topic"), 10, Duration::from_secs(1))
+            .validate_messages()
+            .build();
+
+        assert!(result.is_err());
+    }
 }
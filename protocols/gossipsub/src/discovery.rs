@@ -0,0 +1,70 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Discovery of peers subscribed to a topic.
+//!
+//! Gossipsub only grafts peers it is connected to and knows to be subscribed to a topic. A
+//! [`TopicDiscovery`] implementation allows finding more such peers when the mesh of a topic runs
+//! low, e.g. via Kademlia provider records or rendezvous registrations for the topic.
+
+use crate::TopicHash;
+use libp2p_core::Multiaddr;
+use libp2p_identity::PeerId;
+use std::task::{Context, Poll};
+
+/// A source of peers subscribed to a topic.
+///
+/// See [`Behaviour::set_topic_discovery`](crate::Behaviour::set_topic_discovery).
+pub trait TopicDiscovery {
+    /// Starts looking for peers subscribed to `topic`.
+    ///
+    /// Called by the heartbeat when the mesh of a subscribed topic holds fewer than
+    /// [`Config::mesh_n_low`](crate::Config::mesh_n_low) peers after grafting all suitable known
+    /// peers. `missing` is the number of peers needed to reach
+    /// [`Config::mesh_n`](crate::Config::mesh_n). The call is repeated every
+    /// [`Config::topic_discovery_ticks`](crate::Config::topic_discovery_ticks) heartbeats for as
+    /// long as the mesh stays low.
+    fn discover(&mut self, topic: &TopicHash, missing: usize);
+
+    /// Polls for discovered peers.
+    ///
+    /// The behaviour dials the peers it is not connected to. Once connected and subscribed to
+    /// the topic, they are grafted as usual.
+    fn poll_discovered(&mut self, cx: &mut Context<'_>) -> Poll<DiscoveredPeers>;
+}
+
+impl<T: TopicDiscovery + ?Sized> TopicDiscovery for Box<T> {
+    fn discover(&mut self, topic: &TopicHash, missing: usize) {
+        (**self).discover(topic, missing)
+    }
+
+    fn poll_discovered(&mut self, cx: &mut Context<'_>) -> Poll<DiscoveredPeers> {
+        (**self).poll_discovered(cx)
+    }
+}
+
+/// Peers found by a [`TopicDiscovery`] for a topic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredPeers {
+    /// The topic the peers are subscribed to.
+    pub topic: TopicHash,
+    /// The peers along with their known addresses, if any.
+    pub peers: Vec<(PeerId, Vec<Multiaddr>)>,
+}
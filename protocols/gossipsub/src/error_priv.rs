@@ -38,6 +38,8 @@ pub enum PublishError {
     MessageTooLarge,
     /// The compression algorithm failed.
     TransformFailed(std::io::Error),
+    /// The send queues of all peers to send this message to are full.
+    QueueFull,
 }
 
 impl std::fmt::Display for PublishError {
@@ -95,6 +97,8 @@ pub enum HandlerError {
     MaxOutboundSubstreams,
     #[error("The message exceeds the maximum transmission size.")]
     MaxTransmissionSize,
+    #[error("The RPC exceeds the maximum number of {0}.")]
+    RpcLimitExceeded(&'static str),
     #[error("Protocol negotiation timeout.")]
     NegotiationTimeout,
     #[error("Protocol negotiation failed.")]
@@ -103,7 +107,7 @@ pub enum HandlerError {
     Codec(#[from] quick_protobuf_codec::Error),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ValidationError {
     /// The message has an invalid signature,
     InvalidSignature,
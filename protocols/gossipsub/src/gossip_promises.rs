@@ -21,10 +21,10 @@
 use crate::peer_score::RejectReason;
 use crate::MessageId;
 use crate::ValidationError;
+use instant::Instant;
 use libp2p_identity::PeerId;
 use log::debug;
 use std::collections::HashMap;
-use wasm_timer::Instant;
 
 /// Tracks recently sent `IWANT` messages and checks if peers respond to them.
 #[derive(Default)]
@@ -77,8 +77,7 @@ impl GossipPromises {
     /// request.
     /// This should be called not too often relative to the expire times, since it iterates over
     /// the whole stored data.
-    pub fn get_broken_promises(&mut self) -> HashMap<PeerId, usize> {
-        let now = Instant::now();
+    pub fn get_broken_promises(&mut self, now: Instant) -> HashMap<PeerId, usize> {
         let mut result = HashMap::new();
         self.promises.retain(|msg, peers| {
             peers.retain(|peer_id, expires| {
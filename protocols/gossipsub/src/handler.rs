@@ -20,7 +20,7 @@
 
 use crate::protocol::{GossipsubCodec, ProtocolConfig};
 use crate::rpc_proto::proto;
-use crate::types::{PeerKind, RawMessage, Rpc};
+use crate::types::{DroppedMessages, MessagePriority, PeerKind, RawMessage, Rpc};
 use crate::{HandlerError, ValidationError};
 use asynchronous_codec::Framed;
 use futures::prelude::*;
@@ -34,7 +34,6 @@ use libp2p_swarm::handler::{
 };
 use libp2p_swarm::NegotiatedSubstream;
 use log::{error, trace, warn};
-use smallvec::SmallVec;
 use std::{
     collections::VecDeque,
     io,
@@ -62,6 +61,12 @@ pub enum HandlerEvent {
     /// An inbound or outbound substream has been established with the peer and this informs over
     /// which protocol. This message only occurs once per connection.
     PeerKind(PeerKind),
+    /// Outbound messages were dropped because the send queue was full.
+    MessagesDropped(DroppedMessages),
+    /// The send queue, after dropping published or forwarded messages, has room again.
+    SendQueueDrained,
+    /// An inbound RPC exceeding the maximum transmission size was discarded.
+    RpcTooLarge,
 }
 
 /// A message sent from the behaviour to the handler.
@@ -94,7 +99,7 @@ pub struct Handler {
     inbound_substream: Option<InboundSubstreamState>,
 
     /// Queue of values that we want to send to the remote.
-    send_queue: SmallVec<[proto::RPC; 16]>,
+    send_queue: SendQueue,
 
     /// Flag indicating that an outbound substream is being established to prevent duplicate
     /// requests.
@@ -135,6 +140,97 @@ pub struct Handler {
     in_mesh: bool,
 }
 
+/// Bounded queue of RPCs to send to the remote, with one lane per [`MessagePriority`].
+struct SendQueue {
+    control: VecDeque<proto::RPC>,
+    data: VecDeque<proto::RPC>,
+    gossip: VecDeque<proto::RPC>,
+    /// The maximum number of RPCs across all lanes.
+    max_len: usize,
+    /// Messages dropped since they were last reported to the behaviour.
+    dropped: DroppedMessages,
+    /// Whether published or forwarded messages were dropped since the queue last drained, see
+    /// [`SendQueue::take_drained`].
+    congested: bool,
+}
+
+impl SendQueue {
+    fn new(max_len: usize) -> Self {
+        SendQueue {
+            control: VecDeque::new(),
+            data: VecDeque::new(),
+            gossip: VecDeque::new(),
+            max_len,
+            dropped: DroppedMessages::default(),
+            congested: false,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.control.len() + self.data.len() + self.gossip.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lane(&mut self, priority: MessagePriority) -> &mut VecDeque<proto::RPC> {
+        match priority {
+            MessagePriority::Control => &mut self.control,
+            MessagePriority::Data => &mut self.data,
+            MessagePriority::Gossip => &mut self.gossip,
+        }
+    }
+
+    /// Queues an RPC. If the queue is full, the oldest RPC of the lowest priority below the
+    /// priority of `rpc` is dropped to make room. If there is none, `rpc` itself is dropped.
+    fn push(&mut self, rpc: proto::RPC) {
+        let priority = MessagePriority::of(&rpc);
+
+        if self.len() >= self.max_len {
+            let evicted = [MessagePriority::Gossip, MessagePriority::Data]
+                .into_iter()
+                .filter(|lower| *lower > priority)
+                .find(|lower| self.lane(*lower).pop_front().is_some());
+
+            let dropped = evicted.unwrap_or(priority);
+            *self.dropped.get_mut(dropped) += 1;
+            self.congested |= dropped == MessagePriority::Data;
+            if evicted.is_none() {
+                return;
+            }
+        }
+
+        self.lane(priority).push_back(rpc);
+    }
+
+    /// Takes the oldest RPC of the highest priority.
+    fn pop(&mut self) -> Option<proto::RPC> {
+        self.control
+            .pop_front()
+            .or_else(|| self.data.pop_front())
+            .or_else(|| self.gossip.pop_front())
+    }
+
+    /// Takes the counters of messages dropped since the last call, if any.
+    fn take_dropped(&mut self) -> Option<DroppedMessages> {
+        if self.dropped.total() == 0 {
+            return None;
+        }
+        Some(std::mem::take(&mut self.dropped))
+    }
+
+    /// Returns `true` once if the queue, after dropping published or forwarded messages, drained
+    /// to at most half of its capacity.
+    fn take_drained(&mut self) -> bool {
+        if self.congested && self.len() <= self.max_len / 2 {
+            self.congested = false;
+            return true;
+        }
+        false
+    }
+}
+
 /// State of the inbound substream, opened either by us or by the remote.
 enum InboundSubstreamState {
     /// Waiting for a message from the remote. The idle state for an inbound substream.
@@ -161,7 +257,11 @@ enum OutboundSubstreamState {
 
 impl Handler {
     /// Builds a new [`Handler`].
-    pub fn new(protocol_config: ProtocolConfig, idle_timeout: Duration) -> Self {
+    pub fn new(
+        protocol_config: ProtocolConfig,
+        idle_timeout: Duration,
+        max_send_queue_size: usize,
+    ) -> Self {
         Handler {
             listen_protocol: SubstreamProtocol::new(protocol_config, ()),
             inbound_substream: None,
@@ -169,7 +269,7 @@ impl Handler {
             outbound_substream_establishing: false,
             outbound_substreams_created: 0,
             inbound_substreams_created: 0,
-            send_queue: SmallVec::new(),
+            send_queue: SendQueue::new(max_send_queue_size),
             peer_kind: None,
             peer_kind_sent: false,
             protocol_unsupported: false,
@@ -338,6 +438,18 @@ impl ConnectionHandler for Handler {
             }
         }
 
+        if let Some(dropped) = self.send_queue.take_dropped() {
+            return Poll::Ready(ConnectionHandlerEvent::Custom(
+                HandlerEvent::MessagesDropped(dropped),
+            ));
+        }
+
+        if self.send_queue.take_drained() {
+            return Poll::Ready(ConnectionHandlerEvent::Custom(
+                HandlerEvent::SendQueueDrained,
+            ));
+        }
+
         if self.inbound_substreams_created > MAX_SUBSTREAM_CREATION {
             // Too many inbound substreams have been created, end the connection.
             return Poll::Ready(ConnectionHandlerEvent::Close(
@@ -355,8 +467,7 @@ impl ConnectionHandler for Handler {
                     HandlerError::MaxOutboundSubstreams,
                 ));
             }
-            let message = self.send_queue.remove(0);
-            self.send_queue.shrink_to_fit();
+            let message = self.send_queue.pop().expect("send queue to be non-empty");
             self.outbound_substream_establishing = true;
             return Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest {
                 protocol: self.listen_protocol.clone().map_info(|()| message),
@@ -386,6 +497,14 @@ impl ConnectionHandler for Handler {
                                     warn!("Message exceeded the maximum transmission size");
                                     self.inbound_substream =
                                         Some(InboundSubstreamState::WaitingInput(substream));
+                                    return Poll::Ready(ConnectionHandlerEvent::Custom(
+                                        HandlerEvent::RpcTooLarge,
+                                    ));
+                                }
+                                HandlerError::RpcLimitExceeded(_) => {
+                                    warn!("Dropping inbound RPC: {}", error);
+                                    self.inbound_substream =
+                                        Some(InboundSubstreamState::WaitingInput(substream));
                                 }
                                 _ => {
                                     warn!("Inbound stream error: {}", error);
@@ -451,7 +570,6 @@ impl ConnectionHandler for Handler {
                 // outbound idle state
                 Some(OutboundSubstreamState::WaitingOutput(substream)) => {
                     if let Some(message) = self.send_queue.pop() {
-                        self.send_queue.shrink_to_fit();
                         self.outbound_substream =
                             Some(OutboundSubstreamState::PendingSend(substream, message));
                         continue;
@@ -580,3 +698,97 @@ impl ConnectionHandler for Handler {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rpc(priority: MessagePriority) -> proto::RPC {
+        let mut rpc = proto::RPC::default();
+        match priority {
+            MessagePriority::Control => rpc.subscriptions.push(proto::SubOpts::default()),
+            MessagePriority::Data => rpc.publish.push(proto::Message::default()),
+            MessagePriority::Gossip => {
+                rpc.control = Some(proto::ControlMessage {
+                    iwant: vec![proto::ControlIWant::default()],
+                    ..Default::default()
+                })
+            }
+        }
+        rpc
+    }
+
+    fn drain(queue: &mut SendQueue) -> Vec<MessagePriority> {
+        std::iter::from_fn(|| queue.pop())
+            .map(|rpc| MessagePriority::of(&rpc))
+            .collect()
+    }
+
+    #[test]
+    fn send_queue_pops_by_priority() {
+        let mut queue = SendQueue::new(10);
+        queue.push(rpc(MessagePriority::Gossip));
+        queue.push(rpc(MessagePriority::Data));
+        queue.push(rpc(MessagePriority::Control));
+
+        assert_eq!(
+            drain(&mut queue),
+            vec![
+                MessagePriority::Control,
+                MessagePriority::Data,
+                MessagePriority::Gossip
+            ]
+        );
+        assert!(queue.take_dropped().is_none());
+    }
+
+    #[test]
+    fn full_send_queue_drops_lowest_priority_first() {
+        let mut queue = SendQueue::new(2);
+        queue.push(rpc(MessagePriority::Gossip));
+        queue.push(rpc(MessagePriority::Data));
+
+        // Evicts the gossip message.
+        queue.push(rpc(MessagePriority::Data));
+        // Evicts a data message.
+        queue.push(rpc(MessagePriority::Control));
+        // Nothing of lower priority left to evict.
+        queue.push(rpc(MessagePriority::Data));
+
+        assert_eq!(
+            queue.take_dropped(),
+            Some(DroppedMessages {
+                control: 0,
+                data: 2,
+                gossip: 1,
+            })
+        );
+        assert!(queue.take_dropped().is_none());
+        assert_eq!(
+            drain(&mut queue),
+            vec![MessagePriority::Control, MessagePriority::Data]
+        );
+    }
+
+    #[test]
+    fn send_queue_reports_drained_after_dropping_data() {
+        let mut queue = SendQueue::new(4);
+        for _ in 0..5 {
+            queue.push(rpc(MessagePriority::Data));
+        }
+        assert!(!queue.take_drained());
+
+        queue.pop();
+        assert!(!queue.take_drained());
+        queue.pop();
+        assert!(queue.take_drained());
+        assert!(!queue.take_drained());
+
+        // Dropping gossip does not congest the queue.
+        let mut queue = SendQueue::new(1);
+        queue.push(rpc(MessagePriority::Gossip));
+        queue.push(rpc(MessagePriority::Gossip));
+        queue.pop();
+        assert!(!queue.take_drained());
+    }
+}
@@ -147,11 +147,16 @@ pub mod time_cache;
 mod backoff;
 mod behaviour;
 mod config;
+mod discovery;
 mod error_priv;
 mod gossip_promises;
 mod handler;
 mod mcache;
+mod ordering;
 mod peer_score;
+mod rate_limiter;
+mod rpc_limits;
+mod ticker;
 mod topic;
 mod transform;
 mod types;
@@ -159,15 +164,22 @@ mod types;
 mod rpc_proto;
 
 pub use self::behaviour::{Behaviour, Event, MessageAuthenticity};
-pub use self::config::{Config, ConfigBuilder, ValidationMode, Version};
+pub use self::config::{
+    Config, ConfigBuilder, DuplicateSuppression, SelfDelivery, ValidationMode, Version,
+};
+pub use self::discovery::{DiscoveredPeers, TopicDiscovery};
 pub use self::error_priv::{HandlerError, PublishError, SubscriptionError, ValidationError};
 pub use self::peer_score::{
-    score_parameter_decay, score_parameter_decay_with_base, PeerScoreParams, PeerScoreThresholds,
-    TopicScoreParams,
+    score_parameter_decay, score_parameter_decay_with_base, AppSpecificScoreParams,
+    PeerScoreParams, PeerScoreThresholds, RejectReason, TopicScoreParams,
 };
+pub use self::rpc_limits::RpcLimits;
 pub use self::topic::{Hasher, Topic, TopicHash};
 pub use self::transform::{DataTransform, IdentityTransform};
-pub use self::types::{FastMessageId, Message, MessageAcceptance, MessageId, RawMessage, Rpc};
+pub use self::types::{
+    DroppedMessages, Dump, FastMessageId, Message, MessageAcceptance, MessageId, MessagePriority,
+    PeerDump, Published, RawMessage, Rpc,
+};
 
 #[deprecated(
     since = "0.44.0",
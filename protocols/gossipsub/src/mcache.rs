@@ -47,6 +47,10 @@ pub struct MessageCache {
     /// won't get gossiped anymore when shift got called `gossip` many times after inserting the
     /// message in the cache.
     gossip: usize,
+    /// The maximum total size in bytes of the cached messages, if any.
+    max_bytes: Option<usize>,
+    /// The total size in bytes of the cached messages.
+    bytes: usize,
 }
 
 impl fmt::Debug for MessageCache {
@@ -55,6 +59,8 @@ impl fmt::Debug for MessageCache {
             .field("msgs", &self.msgs)
             .field("history", &self.history)
             .field("gossip", &self.gossip)
+            .field("max_bytes", &self.max_bytes)
+            .field("bytes", &self.bytes)
             .finish()
     }
 }
@@ -67,9 +73,21 @@ impl MessageCache {
             msgs: HashMap::default(),
             iwant_counts: HashMap::default(),
             history: vec![Vec::new(); history_capacity],
+            max_bytes: None,
+            bytes: 0,
         }
     }
 
+    /// Limits the total size in bytes of the cached messages. Once exceeded, the oldest validated
+    /// messages are evicted first.
+    ///
+    /// Messages pending validation are never evicted, as they would be lost before their
+    /// validation result is reported. The cache may thus exceed the limit until they are validated.
+    pub fn with_max_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
     /// Put a message into the memory cache.
     ///
     /// Returns true if the message didn't already exist in the cache.
@@ -84,10 +102,12 @@ impl MessageCache {
                     mid: message_id.clone(),
                     topic: msg.topic.clone(),
                 };
+                self.bytes += message_size(&msg);
                 entry.insert((msg, HashSet::default()));
                 self.history[0].push(cache_entry);
 
                 trace!("Put message {:?} in mcache", message_id);
+                self.evict_oldest();
                 true
             }
         }
@@ -184,6 +204,7 @@ impl MessageCache {
     pub fn shift(&mut self) {
         for entry in self.history.pop().expect("history is always > 1") {
             if let Some((msg, _)) = self.msgs.remove(&entry.mid) {
+                self.bytes -= message_size(&msg);
                 if !msg.validated {
                     // If GossipsubConfig::validate_messages is true, the implementing
                     // application has to ensure that Gossipsub::validate_message gets called for
@@ -209,10 +230,60 @@ impl MessageCache {
         // history vector. Zhe id in the history vector will simply be ignored on popping.
 
         self.iwant_counts.remove(message_id);
-        self.msgs.remove(message_id)
+        let removed = self.msgs.remove(message_id);
+        if let Some((msg, _)) = &removed {
+            self.bytes -= message_size(msg);
+        }
+        removed
+    }
+
+    /// Evicts the oldest validated messages until the cached messages fit into `max_bytes`.
+    fn evict_oldest(&mut self) {
+        let max_bytes = match self.max_bytes {
+            Some(max_bytes) => max_bytes,
+            None => return,
+        };
+
+        let msgs = &mut self.msgs;
+        let iwant_counts = &mut self.iwant_counts;
+        let bytes = &mut self.bytes;
+        for entries in self.history.iter_mut().rev() {
+            if *bytes <= max_bytes {
+                return;
+            }
+
+            entries.retain(|entry| {
+                if *bytes <= max_bytes {
+                    return true;
+                }
+                match msgs.get(&entry.mid) {
+                    // Keep messages pending validation.
+                    Some((msg, _)) if !msg.validated => return true,
+                    Some((msg, _)) => {
+                        *bytes -= message_size(msg);
+                        msgs.remove(&entry.mid);
+                        debug!(
+                            "Evicted message {} from the cache to stay within {} bytes",
+                            &entry.mid, max_bytes
+                        );
+                    }
+                    None => {}
+                }
+                iwant_counts.remove(&entry.mid);
+                false
+            });
+        }
     }
 }
 
+/// The size of a message accounted against [`MessageCache::with_max_bytes`].
+fn message_size(message: &RawMessage) -> usize {
+    message.data.len()
+        + message.topic.as_str().len()
+        + message.signature.as_ref().map_or(0, Vec::len)
+        + message.key.as_ref().map_or(0, Vec::len)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -379,4 +450,79 @@ mod tests {
         assert_eq!(mc.history[0].len(), 0);
         assert_eq!(mc.msgs.len(), 0);
     }
+
+    #[test]
+    /// Test that the oldest messages are evicted once the cache exceeds its maximum size.
+    fn test_evict_oldest_above_max_bytes() {
+        let topic1_hash = Topic::new("topic1").hash();
+        // Each test message holds one byte of data.
+        let size = 1 + topic1_hash.as_str().len();
+        let mut mc = new_cache(3, 5).with_max_bytes(Some(4 * size));
+
+        let messages = (0..6)
+            .map(|i| {
+                let (id, mut m) = gen_testm(i, topic1_hash.clone());
+                m.validated = true;
+                (id, m)
+            })
+            .collect::<Vec<_>>();
+
+        for (id, m) in &messages[..3] {
+            mc.put(id, m.clone());
+        }
+        mc.shift();
+        for (id, m) in &messages[3..] {
+            mc.put(id, m.clone());
+        }
+
+        assert_eq!(mc.msgs.len(), 4);
+        assert_eq!(mc.bytes, 4 * size);
+        assert!(mc.get(&messages[0].0).is_none());
+        assert!(mc.get(&messages[1].0).is_none());
+        for (id, _) in &messages[2..] {
+            assert!(mc.get(id).is_some());
+        }
+        assert_eq!(mc.history[1].len(), 1);
+        assert_eq!(mc.history[0].len(), 3);
+
+        // Removing and shifting out messages frees their bytes.
+        mc.remove(&messages[5].0);
+        assert_eq!(mc.bytes, 3 * size);
+        for _ in 0..5 {
+            mc.shift();
+        }
+        assert_eq!(mc.bytes, 0);
+    }
+
+    #[test]
+    /// Test that messages pending validation are not evicted once the cache exceeds its maximum
+    /// size.
+    fn test_evict_keeps_messages_pending_validation() {
+        let topic1_hash = Topic::new("topic1").hash();
+        // Each test message holds one byte of data.
+        let size = 1 + topic1_hash.as_str().len();
+        let mut mc = new_cache(3, 5).with_max_bytes(Some(2 * size));
+
+        let messages = (0..4)
+            .map(|i| gen_testm(i, topic1_hash.clone()))
+            .collect::<Vec<_>>();
+
+        for (id, m) in &messages {
+            mc.put(id, m.clone());
+        }
+
+        // None of the messages are validated yet, thus all of them are kept.
+        assert_eq!(mc.msgs.len(), 4);
+        for (id, _) in &messages {
+            assert!(mc.validate(id).is_some());
+        }
+
+        // Once validated, the oldest messages are evicted on the next insert.
+        let (id, m) = gen_testm(4, topic1_hash);
+        mc.put(&id, m);
+        assert_eq!(mc.bytes, 2 * size);
+        assert!(mc.get(&messages[2].0).is_none());
+        assert!(mc.get(&messages[3].0).is_some());
+        assert!(mc.get(&id).is_some());
+    }
 }
@@ -31,7 +31,7 @@ use prometheus_client::metrics::histogram::{linear_buckets, Histogram};
 use prometheus_client::registry::Registry;
 
 use crate::topic::TopicHash;
-use crate::types::{MessageAcceptance, PeerKind};
+use crate::types::{DroppedMessages, MessageAcceptance, MessagePriority, PeerKind};
 
 // Default value that limits for how many topics do we store metrics.
 const DEFAULT_MAX_TOPICS: usize = 300;
@@ -171,9 +171,17 @@ pub struct Metrics {
     /// message expires from the memcache before it can be validated, we count this a cache miss
     /// and it is an indicator that the memcache size should be increased.
     memcache_misses: Counter,
+    /// The number of messages requested via IWANT which were found in the memcache.
+    iwant_cache_hits: Counter,
+    /// The number of messages requested via IWANT which were not found in the memcache, e.g.
+    /// because they were evicted. Together with `iwant_cache_hits`, this gives the hit rate of
+    /// the memcache.
+    iwant_cache_misses: Counter,
     /// The number of times we have decided that an IWANT control message is required for this
     /// topic. A very high metric might indicate an underperforming network.
     topic_iwant_msgs: Family<TopicHash, Counter>,
+    /// The number of outbound messages dropped because the send queue of a connection was full.
+    dropped_messages: Family<PriorityLabel, Counter>,
 }
 
 impl Metrics {
@@ -292,6 +300,10 @@ impl Metrics {
             "topic_iwant_msgs",
             "Number of times we have decided an IWANT is required for this topic"
         );
+        let dropped_messages = register_family!(
+            "dropped_messages",
+            "Number of outbound messages dropped due to a full send queue, per message priority"
+        );
         let memcache_misses = {
             let metric = Counter::default();
             registry.register(
@@ -301,6 +313,24 @@ impl Metrics {
             );
             metric
         };
+        let iwant_cache_hits = {
+            let metric = Counter::default();
+            registry.register(
+                "iwant_cache_hits",
+                "Number of messages requested via IWANT which are found in the memcache",
+                metric.clone(),
+            );
+            metric
+        };
+        let iwant_cache_misses = {
+            let metric = Counter::default();
+            registry.register(
+                "iwant_cache_misses",
+                "Number of messages requested via IWANT which are not found in the memcache",
+                metric.clone(),
+            );
+            metric
+        };
 
         Self {
             max_topics,
@@ -326,7 +356,10 @@ impl Metrics {
             peers_per_protocol,
             heartbeat_duration,
             memcache_misses,
+            iwant_cache_hits,
+            iwant_cache_misses,
             topic_iwant_msgs,
+            dropped_messages,
         }
     }
 
@@ -486,6 +519,16 @@ impl Metrics {
         self.memcache_misses.inc();
     }
 
+    /// Register a message requested via IWANT being found in the memcache.
+    pub fn register_iwant_cache_hit(&mut self) {
+        self.iwant_cache_hits.inc();
+    }
+
+    /// Register a message requested via IWANT not being found in the memcache.
+    pub fn register_iwant_cache_miss(&mut self) {
+        self.iwant_cache_misses.inc();
+    }
+
     /// Register sending an IWANT msg for this topic.
     pub fn register_iwant(&mut self, topic: &TopicHash) {
         if self.register_topic(topic).is_ok() {
@@ -493,6 +536,21 @@ impl Metrics {
         }
     }
 
+    /// Register outbound messages dropped due to a full send queue.
+    pub fn register_dropped_messages(&mut self, dropped: &DroppedMessages) {
+        for (priority, count) in [
+            (MessagePriority::Control, dropped.control),
+            (MessagePriority::Data, dropped.data),
+            (MessagePriority::Gossip, dropped.gossip),
+        ] {
+            if count > 0 {
+                self.dropped_messages
+                    .get_or_create(&PriorityLabel { priority })
+                    .inc_by(count as u64);
+            }
+        }
+    }
+
     /// Observes a heartbeat duration.
     pub fn observe_heartbeat_duration(&mut self, millis: u64) {
         self.heartbeat_duration.observe(millis as f64);
@@ -535,6 +593,10 @@ pub enum Inclusion {
     Subscribed,
     /// Peer was included to fill the outbound quota.
     Outbound,
+    /// Peer reconnected within the grace period after being in the mesh.
+    Reconnected,
+    /// Peer was pinned into the mesh by the application.
+    Pinned,
 }
 
 /// Reasons why a peer was removed from the mesh.
@@ -585,6 +647,12 @@ struct ProtocolLabel {
     protocol: PeerKind,
 }
 
+/// Label for the priority of dropped outbound messages.
+#[derive(PartialEq, Eq, Hash, EncodeLabelSet, Clone, Debug)]
+struct PriorityLabel {
+    priority: MessagePriority,
+}
+
 /// Label for the kinds of scoring penalties that can occur
 #[derive(PartialEq, Eq, Hash, EncodeLabelSet, Clone, Debug)]
 struct PenaltyLabel {
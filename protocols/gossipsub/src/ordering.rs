@@ -0,0 +1,251 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Reordering of the messages of each author on a topic by their sequence numbers.
+
+use crate::topic::TopicHash;
+use instant::Instant;
+use libp2p_identity::PeerId;
+use libp2p_swarm::{Clock, SystemClock};
+use log::debug;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Buffers the messages of each author on the topics with ordered delivery, see
+/// [`crate::ConfigBuilder::ordered_delivery`].
+///
+/// A message directly following the last delivered one of its author is delivered right away.
+/// Other messages are held back until either the preceding messages arrived, more than `window`
+/// messages of the author are held back or they were held back for `max_delay`. Messages
+/// arriving after a message with a higher sequence number was delivered are dropped.
+pub(crate) struct MessageOrdering<T> {
+    /// The window and maximum delay by topic.
+    topics: HashMap<TopicHash, (usize, Duration)>,
+    /// How long to remember the last delivered message of an author without any new messages.
+    idle_timeout: Duration,
+    buffers: HashMap<(TopicHash, PeerId), Buffer<T>>,
+    clock: Arc<dyn Clock>,
+}
+
+struct Buffer<T> {
+    last_delivered: Option<u64>,
+    last_received: Instant,
+    /// The held back messages, with the time they were received.
+    pending: BTreeMap<u64, (Instant, T)>,
+}
+
+impl<T> MessageOrdering<T> {
+    pub(crate) fn new(
+        topics: HashMap<TopicHash, (usize, Duration)>,
+        idle_timeout: Duration,
+    ) -> Self {
+        Self {
+            topics,
+            idle_timeout,
+            buffers: HashMap::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Expires the held back messages by the given [`Clock`] instead of the [`SystemClock`].
+    pub(crate) fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Whether messages on the topic are delivered in order.
+    pub(crate) fn is_ordered(&self, topic: &TopicHash) -> bool {
+        self.topics.contains_key(topic)
+    }
+
+    /// Adds a message received on an ordered topic, returning the messages to deliver, in order.
+    pub(crate) fn push(
+        &mut self,
+        topic: &TopicHash,
+        author: PeerId,
+        sequence_number: u64,
+        message: T,
+    ) -> Vec<T> {
+        let (window, _) = match self.topics.get(topic) {
+            Some(params) => *params,
+            None => return vec![message],
+        };
+        let now = self.clock.now();
+        let buffer = self
+            .buffers
+            .entry((topic.clone(), author))
+            .or_insert_with(|| Buffer {
+                last_delivered: None,
+                last_received: now,
+                pending: BTreeMap::new(),
+            });
+        buffer.last_received = now;
+
+        if buffer
+            .last_delivered
+            .map_or(false, |last| sequence_number <= last)
+        {
+            debug!(
+                "Dropping message {} of {} on topic {} arriving after a later message",
+                sequence_number, author, topic
+            );
+            return Vec::new();
+        }
+        buffer.pending.insert(sequence_number, (now, message));
+
+        let mut deliver = Vec::new();
+        while buffer.pending.len() > window {
+            buffer.deliver_first(&mut deliver);
+        }
+        buffer.deliver_consecutive(&mut deliver);
+        deliver
+    }
+
+    /// Returns the messages held back for longer than the maximum delay of their topic, in order,
+    /// and forgets idle authors.
+    pub(crate) fn poll_expired(&mut self) -> Vec<T> {
+        let now = self.clock.now();
+        let mut deliver = Vec::new();
+        let (topics, idle_timeout) = (&self.topics, self.idle_timeout);
+        self.buffers.retain(|(topic, _), buffer| {
+            let max_delay = match topics.get(topic) {
+                Some((_, max_delay)) => *max_delay,
+                None => return false,
+            };
+            // Deliver everything up to the last expired message to preserve the order.
+            if let Some(last_expired) = buffer
+                .pending
+                .iter()
+                .filter(|(_, (received, _))| now.duration_since(*received) >= max_delay)
+                .map(|(seq_no, _)| *seq_no)
+                .last()
+            {
+                while buffer
+                    .pending
+                    .keys()
+                    .next()
+                    .map_or(false, |seq_no| *seq_no <= last_expired)
+                {
+                    buffer.deliver_first(&mut deliver);
+                }
+                buffer.deliver_consecutive(&mut deliver);
+            }
+            !buffer.pending.is_empty() || now.duration_since(buffer.last_received) < idle_timeout
+        });
+        deliver
+    }
+
+    /// Forgets the messages held back on the topic.
+    pub(crate) fn remove_topic(&mut self, topic: &TopicHash) {
+        self.buffers.retain(|(t, _), _| t != topic);
+    }
+}
+
+impl<T> Buffer<T> {
+    fn deliver_first(&mut self, deliver: &mut Vec<T>) {
+        let seq_no = match self.pending.keys().next() {
+            Some(seq_no) => *seq_no,
+            None => return,
+        };
+        if let Some((_, message)) = self.pending.remove(&seq_no) {
+            self.last_delivered = Some(seq_no);
+            deliver.push(message);
+        }
+    }
+
+    /// Delivers the held back messages directly following the last delivered one.
+    fn deliver_consecutive(&mut self, deliver: &mut Vec<T>) {
+        while let Some(first) = self.pending.keys().next() {
+            let follows = match self.last_delivered {
+                Some(last) => last.checked_add(1) == Some(*first),
+                // The first message of an author is delivered right away.
+                None => true,
+            };
+            if !follows {
+                break;
+            }
+            self.deliver_first(deliver);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_swarm::ManualClock;
+
+    fn ordering(
+        window: usize,
+        max_delay: Duration,
+    ) -> (MessageOrdering<u64>, TopicHash, ManualClock) {
+        let topic = TopicHash::from_raw("topic");
+        let topics = HashMap::from([(topic.clone(), (window, max_delay))]);
+        let clock = ManualClock::new();
+        let ordering = MessageOrdering::new(topics, Duration::from_secs(60))
+            .with_clock(Arc::new(clock.clone()));
+        (ordering, topic, clock)
+    }
+
+    #[test]
+    fn delivers_in_order() {
+        let (mut ordering, topic, _) = ordering(10, Duration::from_secs(60));
+        let author = PeerId::random();
+
+        assert_eq!(ordering.push(&topic, author, 1, 1), vec![1]);
+        assert!(ordering.push(&topic, author, 3, 3).is_empty());
+        assert!(ordering.push(&topic, author, 4, 4).is_empty());
+        assert_eq!(ordering.push(&topic, author, 2, 2), vec![2, 3, 4]);
+        // Messages of other authors are ordered separately.
+        assert_eq!(ordering.push(&topic, PeerId::random(), 7, 7), vec![7]);
+        // Late messages are dropped.
+        assert!(ordering.push(&topic, author, 2, 2).is_empty());
+        // Messages on other topics are not held back.
+        let other_topic = TopicHash::from_raw("other topic");
+        assert_eq!(ordering.push(&other_topic, author, 9, 9), vec![9]);
+    }
+
+    #[test]
+    fn skips_gaps_exceeding_window() {
+        let (mut ordering, topic, _) = ordering(2, Duration::from_secs(60));
+        let author = PeerId::random();
+
+        assert_eq!(ordering.push(&topic, author, 1, 1), vec![1]);
+        assert!(ordering.push(&topic, author, 4, 4).is_empty());
+        assert!(ordering.push(&topic, author, 3, 3).is_empty());
+        assert_eq!(ordering.push(&topic, author, 6, 6), vec![3, 4]);
+        assert!(ordering.push(&topic, author, 2, 2).is_empty());
+    }
+
+    #[test]
+    fn delivers_expired_messages() {
+        let (mut ordering, topic, clock) = ordering(10, Duration::from_millis(50));
+        let author = PeerId::random();
+
+        assert_eq!(ordering.push(&topic, author, 1, 1), vec![1]);
+        assert!(ordering.push(&topic, author, 3, 3).is_empty());
+        assert!(ordering.poll_expired().is_empty());
+
+        clock.advance(Duration::from_millis(60));
+        assert!(ordering.push(&topic, author, 5, 5).is_empty());
+        assert_eq!(ordering.poll_expired(), vec![3]);
+        assert_eq!(ordering.push(&topic, author, 4, 4), vec![4, 5]);
+    }
+}
@@ -24,18 +24,20 @@
 use crate::metrics::{Metrics, Penalty};
 use crate::time_cache::TimeCache;
 use crate::{MessageId, TopicHash};
+use instant::Instant;
 use libp2p_identity::PeerId;
+use libp2p_swarm::{Clock, SystemClock};
 use log::{debug, trace, warn};
 use std::collections::{hash_map, HashMap, HashSet};
 use std::net::IpAddr;
+use std::sync::Arc;
 use std::time::Duration;
-use wasm_timer::Instant;
 
 mod params;
 use crate::ValidationError;
 pub use params::{
-    score_parameter_decay, score_parameter_decay_with_base, PeerScoreParams, PeerScoreThresholds,
-    TopicScoreParams,
+    score_parameter_decay, score_parameter_decay_with_base, AppSpecificScoreParams,
+    PeerScoreParams, PeerScoreThresholds, TopicScoreParams,
 };
 
 #[cfg(test)]
@@ -54,6 +56,8 @@ pub(crate) struct PeerScore {
     deliveries: TimeCache<MessageId, DeliveryRecord>,
     /// callback for monitoring message delivery times
     message_delivery_time_callback: Option<fn(&PeerId, &TopicHash, f64)>,
+    /// The clock providing the time of deliveries and mesh membership.
+    clock: Arc<dyn Clock>,
 }
 
 /// General statistics for a given gossipsub peer.
@@ -68,6 +72,12 @@ struct PeerStats {
     behaviour_penalty: f64,
     /// Application specific score. Can be manipulated by calling PeerScore::set_application_score
     application_score: f64,
+    /// Round-trip time to the peer, set by the application.
+    rtt: Option<Duration>,
+    /// Agent version of the peer, set by the application.
+    agent_version: Option<String>,
+    /// Reputation of the peer, set by the application.
+    reputation: f64,
 }
 
 enum ConnectionStatus {
@@ -88,6 +98,9 @@ impl Default for PeerStats {
             known_ips: HashSet::new(),
             behaviour_penalty: 0f64,
             application_score: 0f64,
+            rtt: None,
+            agent_version: None,
+            reputation: 0f64,
         }
     }
 }
@@ -106,6 +119,24 @@ impl PeerStats {
             self.topics.get_mut(&topic_hash)
         }
     }
+
+    /// Returns the application specific score of the inputs set by the application.
+    fn app_specific_inputs_score(&self, params: &AppSpecificScoreParams) -> f64 {
+        let mut score = self.reputation * params.reputation_weight;
+
+        if let Some(rtt) = self.rtt {
+            let excess = rtt.saturating_sub(params.rtt_threshold);
+            score += excess.as_secs_f64() * params.rtt_weight;
+        }
+
+        if let Some(agent_version) = &self.agent_version {
+            if !params.accepts_agent_version(agent_version) {
+                score += params.agent_version_penalty;
+            }
+        }
+
+        score
+    }
 }
 
 /// Stats assigned to peer for each topic.
@@ -143,9 +174,9 @@ enum MeshStatus {
 
 impl MeshStatus {
     /// Initialises a new [`MeshStatus::Active`] mesh status.
-    pub fn new_active() -> Self {
+    pub fn new_active(graft_time: Instant) -> Self {
         MeshStatus::Active {
-            graft_time: Instant::now(),
+            graft_time,
             mesh_time: Duration::from_secs(0),
         }
     }
@@ -183,11 +214,11 @@ enum DeliveryStatus {
     Ignored,
 }
 
-impl Default for DeliveryRecord {
-    fn default() -> Self {
+impl DeliveryRecord {
+    fn new(first_seen: Instant) -> Self {
         DeliveryRecord {
             status: DeliveryStatus::Unknown,
-            first_seen: Instant::now(),
+            first_seen,
             peers: HashSet::new(),
         }
     }
@@ -210,9 +241,18 @@ impl PeerScore {
             peer_ips: HashMap::new(),
             deliveries: TimeCache::new(Duration::from_secs(TIME_CACHE_DURATION)),
             message_delivery_time_callback: callback,
+            clock: Arc::new(SystemClock),
         }
     }
 
+    /// Uses the given [`Clock`] instead of the [`SystemClock`].
+    pub(crate) fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.deliveries =
+            TimeCache::new(Duration::from_secs(TIME_CACHE_DURATION)).with_clock(clock.clone());
+        self.clock = clock;
+        self
+    }
+
     /// Returns the score for a peer
     pub fn score(&self, peer_id: &PeerId) -> f64 {
         self.metric_score(peer_id, None)
@@ -306,7 +346,8 @@ impl PeerScore {
         }
 
         // P5: application-specific score
-        let p5 = peer_stats.application_score;
+        let p5 = peer_stats.application_score
+            + peer_stats.app_specific_inputs_score(&self.params.app_specific_inputs);
         score += p5 * self.params.app_specific_weight;
 
         // P6: IP collocation factor
@@ -368,7 +409,7 @@ impl PeerScore {
     }
 
     pub fn refresh_scores(&mut self) {
-        let now = Instant::now();
+        let now = self.clock.now();
         let params_ref = &self.params;
         let peer_ips_ref = &mut self.peer_ips;
         self.peer_stats.retain(|peer_id, peer_stats| {
@@ -521,7 +562,7 @@ impl PeerScore {
             }
 
             peer_stats.status = ConnectionStatus::Disconnected {
-                expire: Instant::now() + self.params.retain_score,
+                expire: self.clock.now() + self.params.retain_score,
             };
         }
     }
@@ -529,10 +570,11 @@ impl PeerScore {
     /// Handles scoring functionality as a peer GRAFTs to a topic.
     pub fn graft(&mut self, peer_id: &PeerId, topic: impl Into<TopicHash>) {
         let topic = topic.into();
+        let now = self.clock.now();
         if let Some(peer_stats) = self.peer_stats.get_mut(peer_id) {
             // if we are scoring the topic, update the mesh status.
             if let Some(topic_stats) = peer_stats.stats_or_default_mut(topic, &self.params) {
-                topic_stats.mesh_status = MeshStatus::new_active();
+                topic_stats.mesh_status = MeshStatus::new_active(now);
                 topic_stats.mesh_message_deliveries_active = false;
             }
         }
@@ -565,9 +607,10 @@ impl PeerScore {
 
     pub fn validate_message(&mut self, from: &PeerId, msg_id: &MessageId, topic_hash: &TopicHash) {
         // adds an empty record with the message id
+        let now = self.clock.now();
         self.deliveries
             .entry(msg_id.clone())
-            .or_insert_with(DeliveryRecord::default);
+            .or_insert_with(|| DeliveryRecord::new(now));
 
         if let Some(callback) = self.message_delivery_time_callback {
             if self
@@ -585,19 +628,20 @@ impl PeerScore {
     pub fn deliver_message(&mut self, from: &PeerId, msg_id: &MessageId, topic_hash: &TopicHash) {
         self.mark_first_message_delivery(from, topic_hash);
 
+        let now = self.clock.now();
         let record = self
             .deliveries
             .entry(msg_id.clone())
-            .or_insert_with(DeliveryRecord::default);
+            .or_insert_with(|| DeliveryRecord::new(now));
 
         // this should be the first delivery trace
         if record.status != DeliveryStatus::Unknown {
-            warn!("Unexpected delivery trace: Message from {} was first seen {}s ago and has a delivery status {:?}", from, record.first_seen.elapsed().as_secs(), record.status);
+            warn!("Unexpected delivery trace: Message from {} was first seen {}s ago and has a delivery status {:?}", from, now.saturating_duration_since(record.first_seen).as_secs(), record.status);
             return;
         }
 
         // mark the message as valid and reward mesh peers that have already forwarded it to us
-        record.status = DeliveryStatus::Valid(Instant::now());
+        record.status = DeliveryStatus::Valid(now);
         for peer in record.peers.iter().cloned().collect::<Vec<_>>() {
             // this check is to make sure a peer can't send us a message twice and get a double
             // count if it is a first delivery
@@ -632,17 +676,20 @@ impl PeerScore {
                 return;
             }
             // we ignore those messages, so do nothing.
-            RejectReason::BlackListedPeer | RejectReason::BlackListedSource => {
+            RejectReason::BlackListedPeer
+            | RejectReason::BlackListedSource
+            | RejectReason::TooLarge => {
                 return;
             }
             _ => {} // the rest are handled after record creation
         }
 
+        let now = self.clock.now();
         let peers: Vec<_> = {
             let mut record = self
                 .deliveries
                 .entry(msg_id.clone())
-                .or_insert_with(DeliveryRecord::default);
+                .or_insert_with(|| DeliveryRecord::new(now));
 
             // Multiple peers can now reject the same message as we track which peers send us the
             // message. If we have already updated the status, return.
@@ -676,10 +723,11 @@ impl PeerScore {
         msg_id: &MessageId,
         topic_hash: &TopicHash,
     ) {
+        let now = self.clock.now();
         let record = self
             .deliveries
             .entry(msg_id.clone())
-            .or_insert_with(DeliveryRecord::default);
+            .or_insert_with(|| DeliveryRecord::new(now));
 
         if record.peers.get(from).is_some() {
             // we have already seen this duplicate!
@@ -688,7 +736,7 @@ impl PeerScore {
 
         if let Some(callback) = self.message_delivery_time_callback {
             let time = if let DeliveryStatus::Valid(validated) = record.status {
-                validated.elapsed().as_secs_f64()
+                now.saturating_duration_since(validated).as_secs_f64()
             } else {
                 0.0
             };
@@ -735,6 +783,39 @@ impl PeerScore {
         }
     }
 
+    /// Sets the round-trip time to a peer, see [`PeerScore::set_application_score`] for the
+    /// return value.
+    pub fn set_rtt(&mut self, peer_id: &PeerId, rtt: Duration) -> bool {
+        if let Some(peer_stats) = self.peer_stats.get_mut(peer_id) {
+            peer_stats.rtt = Some(rtt);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Sets the agent version of a peer, see [`PeerScore::set_application_score`] for the
+    /// return value.
+    pub fn set_agent_version(&mut self, peer_id: &PeerId, agent_version: String) -> bool {
+        if let Some(peer_stats) = self.peer_stats.get_mut(peer_id) {
+            peer_stats.agent_version = Some(agent_version);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Sets the reputation of a peer, see [`PeerScore::set_application_score`] for the
+    /// return value.
+    pub fn set_reputation(&mut self, peer_id: &PeerId, reputation: f64) -> bool {
+        if let Some(peer_stats) = self.peer_stats.get_mut(peer_id) {
+            peer_stats.reputation = reputation;
+            true
+        } else {
+            false
+        }
+    }
+
     /// Sets scoring parameters for a topic.
     pub fn set_topic_params(&mut self, topic_hash: TopicHash, params: TopicScoreParams) {
         use hash_map::Entry::*;
@@ -837,7 +918,7 @@ impl PeerScore {
     ) {
         if let Some(peer_stats) = self.peer_stats.get_mut(peer_id) {
             let now = if validated_time.is_some() {
-                Some(Instant::now())
+                Some(self.clock.now())
             } else {
                 None
             };
@@ -889,9 +970,10 @@ impl PeerScore {
     }
 }
 
-/// The reason a Gossipsub message has been rejected.
-#[derive(Clone, Copy)]
-pub(crate) enum RejectReason {
+/// The reason a Gossipsub message has been rejected, see [`crate::Event::MessageRejected`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RejectReason {
     /// The message failed the configured validation during decoding.
     ValidationError(ValidationError),
     /// The message source is us.
@@ -904,4 +986,7 @@ pub(crate) enum RejectReason {
     ValidationIgnored,
     /// The validation failed.
     ValidationFailed,
+    /// The RPC containing the message exceeded [`crate::Config::max_transmit_size`] and was
+    /// discarded before decoding.
+    TooLarge,
 }
@@ -115,6 +115,11 @@ pub struct PeerScoreParams {
     /// P5: Application-specific peer scoring
     pub app_specific_weight: f64,
 
+    /// Parameters of the inputs to P5 obtained outside of gossipsub, e.g. from other
+    /// behaviours. Their score is added to the one set via
+    /// [`Behaviour::set_application_score`](crate::Behaviour::set_application_score).
+    pub app_specific_inputs: AppSpecificScoreParams,
+
     ///  P6: IP-colocation factor.
     ///  The parameter has an associated counter which counts the number of peers with the same IP.
     ///  If the number of peers in the same IP exceeds `ip_colocation_factor_threshold, then the value
@@ -156,6 +161,7 @@ impl Default for PeerScoreParams {
             topics: HashMap::new(),
             topic_score_cap: 3600.0,
             app_specific_weight: 10.0,
+            app_specific_inputs: AppSpecificScoreParams::default(),
             ip_colocation_factor_weight: -5.0,
             ip_colocation_factor_threshold: 10.0,
             ip_colocation_factor_whitelist: HashSet::new(),
@@ -183,6 +189,12 @@ impl PeerScoreParams {
             return Err("Invalid topic score cap; must be positive (or 0 for no cap)".into());
         }
 
+        if let Err(e) = self.app_specific_inputs.validate() {
+            return Err(format!(
+                "Invalid application specific score parameters: {e}"
+            ));
+        }
+
         // check the IP colocation factor
         if self.ip_colocation_factor_weight > 0f64 {
             return Err(
@@ -222,6 +234,67 @@ impl PeerScoreParams {
     }
 }
 
+/// Parameters of the inputs to the application-specific score (P5) that gossipsub cannot observe
+/// itself, e.g. the round-trip time measured by ping or the agent version reported by identify.
+///
+/// The resulting score is added to the application specific score and weighted with
+/// [`PeerScoreParams::app_specific_weight`].
+#[derive(Debug, Clone)]
+pub struct AppSpecificScoreParams {
+    /// The weight of the round-trip time of a peer exceeding `rtt_threshold`, in seconds,
+    /// see [`Behaviour::set_peer_rtt`](crate::Behaviour::set_peer_rtt).
+    /// The weight MUST be negative (or zero to disable).
+    pub rtt_weight: f64,
+    /// The round-trip time up to which peers are not penalized.
+    pub rtt_threshold: Duration,
+
+    /// Prefixes of the accepted agent versions, see
+    /// [`Behaviour::set_peer_agent_version`](crate::Behaviour::set_peer_agent_version).
+    /// Peers whose agent version starts with none of them are penalized with
+    /// `agent_version_penalty`. Empty to accept all agent versions.
+    pub agent_version_allowlist: Vec<String>,
+    /// The penalty of a peer whose agent version is not accepted.
+    /// It MUST be negative (or zero to disable).
+    pub agent_version_penalty: f64,
+
+    /// The weight of the reputation of a peer, see
+    /// [`Behaviour::set_peer_reputation`](crate::Behaviour::set_peer_reputation).
+    pub reputation_weight: f64,
+}
+
+impl Default for AppSpecificScoreParams {
+    fn default() -> Self {
+        AppSpecificScoreParams {
+            rtt_weight: 0.0,
+            rtt_threshold: Duration::from_millis(200),
+            agent_version_allowlist: Vec::new(),
+            agent_version_penalty: 0.0,
+            reputation_weight: 1.0,
+        }
+    }
+}
+
+impl AppSpecificScoreParams {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.rtt_weight > 0f64 {
+            return Err("invalid rtt_weight; must be negative (or 0 to disable)");
+        }
+        if self.agent_version_penalty > 0f64 {
+            return Err("invalid agent_version_penalty; must be negative (or 0 to disable)");
+        }
+        Ok(())
+    }
+
+    /// Whether the given agent version is accepted.
+    pub(crate) fn accepts_agent_version(&self, agent_version: &str) -> bool {
+        self.agent_version_allowlist.is_empty()
+            || self
+                .agent_version_allowlist
+                .iter()
+                .any(|prefix| agent_version.starts_with(prefix.as_str()))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TopicScoreParams {
     /// The weight of the topic.
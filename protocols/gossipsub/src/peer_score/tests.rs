@@ -799,6 +799,64 @@ fn test_application_score() {
     }
 }
 
+#[test]
+fn test_application_score_inputs() {
+    let params = PeerScoreParams {
+        app_specific_weight: 1.0,
+        app_specific_inputs: AppSpecificScoreParams {
+            rtt_weight: -10.0,
+            rtt_threshold: Duration::from_millis(100),
+            agent_version_allowlist: vec!["rust-libp2p/".to_string()],
+            agent_version_penalty: -5.0,
+            reputation_weight: 2.0,
+        },
+        ..Default::default()
+    };
+    assert!(params.validate().is_ok());
+    let mut peer_score = PeerScore::new(params);
+
+    let peer_id_a = PeerId::random();
+    peer_score.add_peer(peer_id_a);
+    assert_eq!(peer_score.score(&peer_id_a), 0.0);
+
+    // Round-trip times up to the threshold are not penalized.
+    assert!(peer_score.set_rtt(&peer_id_a, Duration::from_millis(100)));
+    assert_eq!(peer_score.score(&peer_id_a), 0.0);
+    peer_score.set_rtt(&peer_id_a, Duration::from_millis(600));
+    assert!(within_variance(peer_score.score(&peer_id_a), -5.0, 1e-9));
+
+    assert!(peer_score.set_agent_version(&peer_id_a, "rust-libp2p/0.52.0".to_string()));
+    assert!(within_variance(peer_score.score(&peer_id_a), -5.0, 1e-9));
+    peer_score.set_agent_version(&peer_id_a, "other/1.0.0".to_string());
+    assert!(within_variance(peer_score.score(&peer_id_a), -10.0, 1e-9));
+
+    assert!(peer_score.set_reputation(&peer_id_a, 7.5));
+    assert!(within_variance(peer_score.score(&peer_id_a), 5.0, 1e-9));
+
+    // The inputs add up with the application specific score.
+    peer_score.set_application_score(&peer_id_a, 1.0);
+    assert!(within_variance(peer_score.score(&peer_id_a), 6.0, 1e-9));
+
+    assert!(!peer_score.set_reputation(&PeerId::random(), 1.0));
+}
+
+#[test]
+fn test_application_score_inputs_validation() {
+    let params = AppSpecificScoreParams {
+        rtt_weight: 1.0,
+        ..Default::default()
+    };
+    assert!(params.validate().is_err());
+
+    let params = AppSpecificScoreParams {
+        agent_version_penalty: 1.0,
+        ..Default::default()
+    };
+    assert!(params.validate().is_err());
+
+    assert!(AppSpecificScoreParams::default().validate().is_ok());
+}
+
 #[test]
 fn test_score_ip_colocation() {
     // Create parameters with reasonable default values
@@ -20,6 +20,7 @@
 
 use crate::config::{ValidationMode, Version};
 use crate::handler::HandlerEvent;
+use crate::rpc_limits::{self, RpcLimits};
 use crate::topic::TopicHash;
 use crate::types::{
     ControlAction, MessageId, PeerInfo, PeerKind, RawMessage, Rpc, Subscription, SubscriptionAction,
@@ -28,13 +29,14 @@ use crate::{rpc_proto::proto, Config};
 use crate::{HandlerError, ValidationError};
 use asynchronous_codec::{Decoder, Encoder, Framed};
 use byteorder::{BigEndian, ByteOrder};
-use bytes::BytesMut;
+use bytes::{Buf, BytesMut};
 use futures::future;
 use futures::prelude::*;
 use libp2p_core::{InboundUpgrade, OutboundUpgrade, ProtocolName, UpgradeInfo};
 use libp2p_identity::{PeerId, PublicKey};
 use log::{debug, warn};
-use quick_protobuf::Writer;
+use quick_protobuf::{BytesReader, MessageRead, Writer};
+use std::io;
 use std::pin::Pin;
 use unsigned_varint::codec;
 
@@ -47,6 +49,8 @@ pub struct ProtocolConfig {
     protocol_ids: Vec<ProtocolId>,
     /// The maximum transmit size for a packet.
     max_transmit_size: usize,
+    /// The limits on the entries of inbound RPCs.
+    rpc_limits: RpcLimits,
     /// Determines the level of validation to be done on incoming messages.
     validation_mode: ValidationMode,
 }
@@ -91,6 +95,7 @@ impl ProtocolConfig {
         ProtocolConfig {
             protocol_ids,
             max_transmit_size: gossipsub_config.max_transmit_size(),
+            rpc_limits: gossipsub_config.rpc_limits().clone(),
             validation_mode: gossipsub_config.validation_mode().clone(),
         }
     }
@@ -156,7 +161,8 @@ where
         Box::pin(future::ok((
             Framed::new(
                 socket,
-                GossipsubCodec::new(length_codec, self.validation_mode),
+                GossipsubCodec::new(length_codec, self.validation_mode)
+                    .with_rpc_limits(self.rpc_limits),
             ),
             protocol_id.kind,
         )))
@@ -177,7 +183,8 @@ where
         Box::pin(future::ok((
             Framed::new(
                 socket,
-                GossipsubCodec::new(length_codec, self.validation_mode),
+                GossipsubCodec::new(length_codec, self.validation_mode)
+                    .with_rpc_limits(self.rpc_limits),
             ),
             protocol_id.kind,
         )))
@@ -189,8 +196,14 @@ where
 pub struct GossipsubCodec {
     /// Determines the level of validation performed on incoming messages.
     validation_mode: ValidationMode,
-    /// The codec to handle common encoding/decoding of protobuf messages
+    /// The codec to handle common encoding of protobuf messages
     codec: quick_protobuf_codec::Codec<proto::RPC>,
+    /// The maximum length of an inbound RPC.
+    max_len: usize,
+    /// The limits on the entries of inbound RPCs.
+    rpc_limits: RpcLimits,
+    /// The remaining bytes of an oversized inbound RPC to discard.
+    discard: usize,
 }
 
 impl GossipsubCodec {
@@ -199,9 +212,56 @@ impl GossipsubCodec {
         GossipsubCodec {
             validation_mode,
             codec,
+            max_len: length_codec.max_len(),
+            rpc_limits: RpcLimits::default(),
+            discard: 0,
         }
     }
 
+    /// Sets the limits on the entries of inbound RPCs.
+    pub fn with_rpc_limits(mut self, rpc_limits: RpcLimits) -> GossipsubCodec {
+        self.rpc_limits = rpc_limits;
+        self
+    }
+
+    /// Splits the next length-prefixed RPC off the buffer.
+    ///
+    /// The RPC is only buffered once its length is known to be within `max_len`. The bytes of
+    /// oversized RPCs are discarded as they arrive, allowing to read the RPCs following them.
+    fn decode_frame(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, HandlerError> {
+        if self.discard > 0 {
+            let n = self.discard.min(src.len());
+            src.advance(n);
+            self.discard -= n;
+            if self.discard > 0 {
+                return Ok(None);
+            }
+        }
+
+        let (len, remaining) = match unsigned_varint::decode::usize(src) {
+            Ok(v) => v,
+            Err(unsigned_varint::decode::Error::Insufficient) => return Ok(None),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e).into()),
+        };
+        let prefix_len = src.len() - remaining.len();
+
+        if len > self.max_len {
+            src.advance(prefix_len);
+            let n = len.min(src.len());
+            src.advance(n);
+            self.discard = len - n;
+            return Err(HandlerError::MaxTransmissionSize);
+        }
+
+        if remaining.len() < len {
+            src.reserve(prefix_len + len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(prefix_len);
+        Ok(Some(src.split_to(len)))
+    }
+
     /// Verifies a gossipsub message. This returns either a success or failure. All errors
     /// are logged, which prevents error handling in the codec and handler. We simply drop invalid
     /// messages and log warnings, rather than propagating errors through the codec.
@@ -284,11 +344,20 @@ impl Decoder for GossipsubCodec {
     type Error = HandlerError;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, HandlerError> {
-        let rpc = match self.codec.decode(src)? {
-            Some(p) => p,
+        let frame = match self.decode_frame(src)? {
+            Some(f) => f,
             None => return Ok(None),
         };
 
+        // Check the entries of the RPC before decoding it, so that a malicious RPC can not make us
+        // allocate more than permitted.
+        let invalid_data = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+        if let Some(exceeded) = rpc_limits::check(&frame, &self.rpc_limits).map_err(invalid_data)? {
+            return Err(HandlerError::RpcLimitExceeded(exceeded.entries()));
+        }
+        let rpc = proto::RPC::from_reader(&mut BytesReader::from_bytes(&frame), &frame)
+            .map_err(invalid_data)?;
+
         // Store valid messages.
         let mut messages = Vec::with_capacity(rpc.publish.len());
         // Store any invalid messages.
@@ -656,4 +725,69 @@ mod tests {
 
         QuickCheck::new().quickcheck(prop as fn(_) -> _)
     }
+
+    fn subscriptions(n: usize) -> proto::RPC {
+        proto::RPC {
+            subscriptions: vec![
+                proto::SubOpts {
+                    subscribe: Some(true),
+                    topic_id: Some("topic".into()),
+                };
+                n
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    /// Test that oversized RPCs are discarded without buffering them and the RPCs following them
+    /// are still decoded.
+    fn oversized_rpc_is_discarded() {
+        let mut encoder = GossipsubCodec::new(codec::UviBytes::default(), ValidationMode::None);
+        let mut encoded = BytesMut::new();
+        encoder.encode(subscriptions(100), &mut encoded).unwrap();
+        encoder.encode(subscriptions(1), &mut encoded).unwrap();
+
+        let mut length_codec = codec::UviBytes::default();
+        length_codec.set_max_len(100);
+        let mut codec = GossipsubCodec::new(length_codec, ValidationMode::None);
+
+        // Only part of the oversized RPC has arrived.
+        let mut buf = encoded.split_to(10);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(HandlerError::MaxTransmissionSize)
+        ));
+        assert!(buf.is_empty());
+
+        buf.extend_from_slice(&encoded);
+        match codec.decode(&mut buf).unwrap().unwrap() {
+            HandlerEvent::Message { rpc, .. } => assert_eq!(rpc.subscriptions.len(), 1),
+            _ => panic!("Must decode the subscriptions"),
+        }
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    /// Test that RPCs exceeding the limits are rejected and the RPCs following them are still
+    /// decoded.
+    fn rpc_exceeding_limits_is_rejected() {
+        let mut codec = GossipsubCodec::new(codec::UviBytes::default(), ValidationMode::None)
+            .with_rpc_limits(RpcLimits {
+                max_subscriptions: 2,
+                ..Default::default()
+            });
+        let mut buf = BytesMut::new();
+        codec.encode(subscriptions(3), &mut buf).unwrap();
+        codec.encode(subscriptions(2), &mut buf).unwrap();
+
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(HandlerError::RpcLimitExceeded(_))
+        ));
+        match codec.decode(&mut buf).unwrap().unwrap() {
+            HandlerEvent::Message { rpc, .. } => assert_eq!(rpc.subscriptions.len(), 2),
+            _ => panic!("Must decode the subscriptions"),
+        }
+    }
 }
@@ -0,0 +1,184 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Token buckets limiting the rate of messages accepted from each peer on each topic.
+
+use crate::topic::TopicHash;
+use instant::Instant;
+use libp2p_identity::PeerId;
+use libp2p_swarm::{Clock, SystemClock};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Outcome of [`RateLimiter::check`] for a received message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RateLimitOutcome {
+    /// The message is within the limit.
+    Accepted,
+    /// The message exceeds the limit and is the first one to do so since the last accepted one.
+    FirstViolation,
+    /// The message exceeds the limit, as did the previous one.
+    Violation,
+}
+
+/// A token bucket per peer and topic.
+///
+/// Each bucket holds up to `capacity` tokens and gains one token every `refill_interval`.
+/// Accepting a message takes one token.
+pub(crate) struct RateLimiter {
+    capacity: u32,
+    refill_interval: Duration,
+    buckets: HashMap<(PeerId, TopicHash), Bucket>,
+    clock: Arc<dyn Clock>,
+}
+
+struct Bucket {
+    tokens: u32,
+    last_refill: Instant,
+    violating: bool,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(capacity: u32, refill_interval: Duration) -> Self {
+        Self {
+            capacity,
+            refill_interval,
+            buckets: HashMap::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Refills the buckets by the given [`Clock`] instead of the [`SystemClock`].
+    pub(crate) fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Takes a token for a message from `peer` on `topic`, if available.
+    pub(crate) fn check(&mut self, peer: &PeerId, topic: &TopicHash) -> RateLimitOutcome {
+        let now = self.clock.now();
+        let capacity = self.capacity;
+        let bucket = self
+            .buckets
+            .entry((*peer, topic.clone()))
+            .or_insert_with(|| Bucket {
+                tokens: capacity,
+                last_refill: now,
+                violating: false,
+            });
+        bucket.refill(now, capacity, self.refill_interval);
+
+        if bucket.tokens > 0 {
+            bucket.tokens -= 1;
+            bucket.violating = false;
+            return RateLimitOutcome::Accepted;
+        }
+
+        if std::mem::replace(&mut bucket.violating, true) {
+            RateLimitOutcome::Violation
+        } else {
+            RateLimitOutcome::FirstViolation
+        }
+    }
+
+    /// Removes the buckets of a peer, e.g. once it disconnected.
+    pub(crate) fn remove_peer(&mut self, peer: &PeerId) {
+        self.buckets.retain(|(p, _), _| p != peer);
+    }
+
+    /// Removes all buckets that are full again, as they are equivalent to a new bucket.
+    pub(crate) fn prune(&mut self) {
+        let now = self.clock.now();
+        let (capacity, refill_interval) = (self.capacity, self.refill_interval);
+        self.buckets.retain(|_, bucket| {
+            bucket.refill(now, capacity, refill_interval);
+            bucket.tokens < capacity
+        });
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant, capacity: u32, refill_interval: Duration) {
+        let elapsed = now.duration_since(self.last_refill);
+        let new_tokens = (elapsed.as_nanos() / refill_interval.as_nanos().max(1)) as u64;
+        if new_tokens == 0 {
+            return;
+        }
+
+        if self.tokens as u64 + new_tokens >= capacity as u64 {
+            self.tokens = capacity;
+            self.last_refill = now;
+        } else {
+            self.tokens += new_tokens as u32;
+            self.last_refill += refill_interval * new_tokens as u32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_each_peer_and_topic_separately() {
+        let mut limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let peer = PeerId::random();
+        let topic = TopicHash::from_raw("topic");
+
+        assert_eq!(limiter.check(&peer, &topic), RateLimitOutcome::Accepted);
+        assert_eq!(limiter.check(&peer, &topic), RateLimitOutcome::Accepted);
+        assert_eq!(
+            limiter.check(&peer, &topic),
+            RateLimitOutcome::FirstViolation
+        );
+        assert_eq!(limiter.check(&peer, &topic), RateLimitOutcome::Violation);
+
+        let other_topic = TopicHash::from_raw("other topic");
+        assert_eq!(
+            limiter.check(&peer, &other_topic),
+            RateLimitOutcome::Accepted
+        );
+        assert_eq!(
+            limiter.check(&PeerId::random(), &topic),
+            RateLimitOutcome::Accepted
+        );
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let clock = libp2p_swarm::ManualClock::new();
+        let mut limiter =
+            RateLimiter::new(1, Duration::from_millis(50)).with_clock(Arc::new(clock.clone()));
+        let peer = PeerId::random();
+        let topic = TopicHash::from_raw("topic");
+
+        assert_eq!(limiter.check(&peer, &topic), RateLimitOutcome::Accepted);
+        assert_eq!(
+            limiter.check(&peer, &topic),
+            RateLimitOutcome::FirstViolation
+        );
+
+        clock.advance(Duration::from_millis(60));
+        limiter.prune();
+        assert!(limiter.buckets.is_empty());
+        assert_eq!(limiter.check(&peer, &topic), RateLimitOutcome::Accepted);
+    }
+}
@@ -0,0 +1,282 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Limits on the contents of inbound RPCs, checked before an RPC is decoded.
+
+use quick_protobuf::{BytesReader, Result};
+
+/// Limits on the number of entries of a single inbound RPC.
+///
+/// The limits are checked on the encoded RPC before decoding it, thus a peer can not make us
+/// allocate more than these limits permit, regardless of
+/// [`Config::max_transmit_size`](crate::Config::max_transmit_size). RPCs exceeding a limit are
+/// dropped as a whole.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcLimits {
+    /// The maximum number of published messages. The default is 5000.
+    pub max_messages: usize,
+    /// The maximum number of subscription changes. The default is 5000.
+    pub max_subscriptions: usize,
+    /// The maximum number of IHAVE, IWANT, GRAFT and PRUNE control messages. The default is 5000.
+    pub max_control_messages: usize,
+    /// The maximum number of message ids over all IHAVE control messages. The default is 50000.
+    pub max_ihave_ids: usize,
+    /// The maximum number of message ids over all IWANT control messages. The default is 5000.
+    pub max_iwant_ids: usize,
+    /// The maximum number of peers over all PRUNE control messages. The default is 1000.
+    pub max_prune_peers: usize,
+}
+
+impl Default for RpcLimits {
+    fn default() -> Self {
+        Self {
+            max_messages: 5000,
+            max_subscriptions: 5000,
+            max_control_messages: 5000,
+            max_ihave_ids: 50000,
+            max_iwant_ids: 5000,
+            max_prune_peers: 1000,
+        }
+    }
+}
+
+/// The entries of an RPC exceeding one of the [`RpcLimits`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LimitExceeded {
+    Messages,
+    Subscriptions,
+    ControlMessages,
+    IHaveIds,
+    IWantIds,
+    PrunePeers,
+}
+
+impl LimitExceeded {
+    /// The name of the entries of which the RPC has too many.
+    pub(crate) fn entries(&self) -> &'static str {
+        match self {
+            LimitExceeded::Messages => "messages",
+            LimitExceeded::Subscriptions => "subscriptions",
+            LimitExceeded::ControlMessages => "control messages",
+            LimitExceeded::IHaveIds => "IHAVE message ids",
+            LimitExceeded::IWantIds => "IWANT message ids",
+            LimitExceeded::PrunePeers => "PRUNE peers",
+        }
+    }
+}
+
+/// Counts of the entries of an RPC.
+#[derive(Debug, Default)]
+struct Counts {
+    messages: usize,
+    subscriptions: usize,
+    control_messages: usize,
+    ihave_ids: usize,
+    iwant_ids: usize,
+    prune_peers: usize,
+}
+
+impl Counts {
+    fn check(&self, limits: &RpcLimits) -> Option<LimitExceeded> {
+        if self.messages > limits.max_messages {
+            Some(LimitExceeded::Messages)
+        } else if self.subscriptions > limits.max_subscriptions {
+            Some(LimitExceeded::Subscriptions)
+        } else if self.control_messages > limits.max_control_messages {
+            Some(LimitExceeded::ControlMessages)
+        } else if self.ihave_ids > limits.max_ihave_ids {
+            Some(LimitExceeded::IHaveIds)
+        } else if self.iwant_ids > limits.max_iwant_ids {
+            Some(LimitExceeded::IWantIds)
+        } else if self.prune_peers > limits.max_prune_peers {
+            Some(LimitExceeded::PrunePeers)
+        } else {
+            None
+        }
+    }
+}
+
+/// Checks the encoded RPC against the limits, without allocating any of its entries.
+///
+/// Returns the first limit exceeded, if any. Fails if the RPC is malformed.
+pub(crate) fn check(rpc: &[u8], limits: &RpcLimits) -> Result<Option<LimitExceeded>> {
+    let mut counts = Counts::default();
+    let mut r = BytesReader::from_bytes(rpc);
+
+    while !r.is_eof() {
+        match r.next_tag(rpc)? {
+            // RPC.subscriptions
+            10 => {
+                counts.subscriptions += 1;
+                r.read_bytes(rpc)?;
+            }
+            // RPC.publish
+            18 => {
+                counts.messages += 1;
+                r.read_bytes(rpc)?;
+            }
+            // RPC.control
+            26 => count_control(r.read_bytes(rpc)?, &mut counts)?,
+            t => r.read_unknown(rpc, t)?,
+        }
+
+        if let Some(exceeded) = counts.check(limits) {
+            return Ok(Some(exceeded));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Counts the entries of an encoded `ControlMessage`.
+fn count_control(control: &[u8], counts: &mut Counts) -> Result<()> {
+    let mut r = BytesReader::from_bytes(control);
+
+    while !r.is_eof() {
+        let tag = r.next_tag(control)?;
+        let (field, nested) = match tag {
+            // ControlMessage.ihave: ControlIHave.message_ids
+            10 => (&mut counts.ihave_ids, 18),
+            // ControlMessage.iwant: ControlIWant.message_ids
+            18 => (&mut counts.iwant_ids, 10),
+            // ControlMessage.graft
+            26 => {
+                counts.control_messages += 1;
+                r.read_bytes(control)?;
+                continue;
+            }
+            // ControlMessage.prune: ControlPrune.peers
+            34 => (&mut counts.prune_peers, 18),
+            t => {
+                r.read_unknown(control, t)?;
+                continue;
+            }
+        };
+
+        let message = r.read_bytes(control)?;
+        *field += count_field(message, nested)?;
+        counts.control_messages += 1;
+    }
+
+    Ok(())
+}
+
+/// Counts the occurrences of the field with the given tag in an encoded message.
+fn count_field(message: &[u8], tag: u32) -> Result<usize> {
+    let mut r = BytesReader::from_bytes(message);
+    let mut count = 0;
+
+    while !r.is_eof() {
+        let t = r.next_tag(message)?;
+        if t == tag {
+            count += 1;
+        }
+        r.read_unknown(message, t)?;
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc_proto::proto;
+    use quick_protobuf::{MessageWrite, Writer};
+
+    fn encode(rpc: &proto::RPC) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(rpc.get_size());
+        rpc.write_message(&mut Writer::new(&mut buf)).unwrap();
+        buf
+    }
+
+    fn ihave(ids: usize) -> proto::ControlIHave {
+        proto::ControlIHave {
+            topic_id: Some("topic".into()),
+            message_ids: vec![vec![]; ids],
+        }
+    }
+
+    #[test]
+    fn accepts_rpc_within_limits() {
+        let rpc = proto::RPC {
+            subscriptions: vec![proto::SubOpts::default(); 2],
+            publish: vec![proto::Message::default(); 3],
+            control: Some(proto::ControlMessage {
+                ihave: vec![ihave(4), ihave(4)],
+                graft: vec![proto::ControlGraft::default()],
+                ..Default::default()
+            }),
+        };
+        let limits = RpcLimits {
+            max_messages: 3,
+            max_subscriptions: 2,
+            max_control_messages: 3,
+            max_ihave_ids: 8,
+            ..Default::default()
+        };
+
+        assert_eq!(check(&encode(&rpc), &limits).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_rpc_exceeding_limits() {
+        let limits = RpcLimits {
+            max_messages: 2,
+            max_ihave_ids: 5,
+            ..Default::default()
+        };
+
+        let rpc = proto::RPC {
+            publish: vec![proto::Message::default(); 3],
+            ..Default::default()
+        };
+        assert_eq!(
+            check(&encode(&rpc), &limits).unwrap(),
+            Some(LimitExceeded::Messages)
+        );
+
+        // Message ids are counted over all IHAVE control messages.
+        let rpc = proto::RPC {
+            control: Some(proto::ControlMessage {
+                ihave: vec![ihave(3), ihave(3)],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            check(&encode(&rpc), &limits).unwrap(),
+            Some(LimitExceeded::IHaveIds)
+        );
+    }
+
+    #[test]
+    fn fails_on_truncated_rpc() {
+        let rpc = proto::RPC {
+            publish: vec![proto::Message {
+                data: Some(vec![1; 32]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let encoded = encode(&rpc);
+
+        assert!(check(&encoded[..encoded.len() - 1], &RpcLimits::default()).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Timer firing in regular intervals of a [`Clock`].
+
+use futures::{ready, FutureExt};
+use instant::Instant;
+use libp2p_swarm::{Clock, Timer};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Fires every `interval` of a [`Clock`].
+///
+/// Each tick is scheduled relative to the deadline of the previous one, so that the ticks do not
+/// drift by the latency of polling. Ticks missed entirely, e.g. while the task was blocked, are
+/// skipped instead of firing in a burst.
+#[derive(Debug)]
+pub(crate) struct Ticker {
+    clock: Arc<dyn Clock>,
+    interval: Duration,
+    deadline: Instant,
+    timer: Timer,
+}
+
+impl Ticker {
+    /// Creates a [`Ticker`] firing first after `delay` and then every `interval`.
+    pub(crate) fn new(clock: Arc<dyn Clock>, delay: Duration, interval: Duration) -> Self {
+        Self {
+            deadline: clock.now() + delay,
+            timer: clock.timer(delay),
+            clock,
+            interval,
+        }
+    }
+
+    pub(crate) fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        ready!(self.timer.poll_unpin(cx));
+
+        let now = self.clock.now();
+        self.deadline += self.interval;
+        if self.deadline <= now {
+            self.deadline = now + self.interval;
+        }
+        self.timer = self.clock.timer(self.deadline - now);
+
+        Poll::Ready(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::noop_waker;
+    use libp2p_swarm::ManualClock;
+
+    #[test]
+    fn ticks_are_scheduled_relative_to_the_previous_deadline() {
+        let clock = ManualClock::new();
+        let mut ticker = Ticker::new(
+            Arc::new(clock.clone()),
+            Duration::from_secs(5),
+            Duration::from_secs(1),
+        );
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(ticker.poll_tick(&mut cx).is_pending());
+
+        // Polled late, the next tick is still due one interval after the previous deadline.
+        clock.advance(Duration::from_millis(5500));
+        assert!(ticker.poll_tick(&mut cx).is_ready());
+        assert!(ticker.poll_tick(&mut cx).is_pending());
+        clock.advance(Duration::from_millis(500));
+        assert!(ticker.poll_tick(&mut cx).is_ready());
+
+        // Missed ticks are skipped.
+        clock.advance(Duration::from_secs(10));
+        assert!(ticker.poll_tick(&mut cx).is_ready());
+        assert!(ticker.poll_tick(&mut cx).is_pending());
+        clock.advance(Duration::from_millis(999));
+        assert!(ticker.poll_tick(&mut cx).is_pending());
+        clock.advance(Duration::from_millis(1));
+        assert!(ticker.poll_tick(&mut cx).is_ready());
+    }
+}
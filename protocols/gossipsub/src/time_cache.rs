@@ -21,13 +21,15 @@
 //! This implements a time-based LRU cache for checking gossipsub message duplicates.
 
 use fnv::FnvHashMap;
+use instant::Instant;
+use libp2p_swarm::{Clock, SystemClock};
 use std::collections::hash_map::{
     self,
     Entry::{Occupied, Vacant},
 };
 use std::collections::VecDeque;
+use std::sync::Arc;
 use std::time::Duration;
-use wasm_timer::Instant;
 
 struct ExpiringElement<Element> {
     /// The element that expires
@@ -44,6 +46,8 @@ pub struct TimeCache<Key, Value> {
     list: VecDeque<ExpiringElement<Key>>,
     /// The time elements remain in the cache.
     ttl: Duration,
+    /// The clock the elements expire by.
+    clock: Arc<dyn Clock>,
 }
 
 pub struct OccupiedEntry<'a, K, V> {
@@ -132,9 +136,16 @@ where
             map: FnvHashMap::default(),
             list: VecDeque::new(),
             ttl,
+            clock: Arc::new(SystemClock),
         }
     }
 
+    /// Lets the elements expire by the given [`Clock`] instead of the [`SystemClock`].
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     fn remove_expired_keys(&mut self, now: Instant) {
         while let Some(element) = self.list.pop_front() {
             if element.expires > now {
@@ -150,7 +161,7 @@ where
     }
 
     pub fn entry(&mut self, key: Key) -> Entry<Key, Value> {
-        let now = Instant::now();
+        let now = self.clock.now();
         self.remove_expired_keys(now);
         match self.map.entry(key) {
             Occupied(entry) => Entry::Occupied(OccupiedEntry {
@@ -195,6 +206,11 @@ where
         Self(TimeCache::new(ttl))
     }
 
+    /// Lets the elements expire by the given [`Clock`] instead of the [`SystemClock`].
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self(self.0.with_clock(clock))
+    }
+
     // Inserts new elements and removes any expired elements.
     //
     // If the key was not present this returns `true`. If the value was already present this
@@ -231,15 +247,17 @@ mod test {
 
     #[test]
     fn cache_entries_expire() {
-        let mut cache = DuplicateCache::new(Duration::from_millis(100));
+        let clock = libp2p_swarm::ManualClock::new();
+        let mut cache =
+            DuplicateCache::new(Duration::from_millis(100)).with_clock(Arc::new(clock.clone()));
 
         cache.insert("t");
         assert!(!cache.insert("t"));
         cache.insert("e");
         //assert!(!cache.insert("t"));
         assert!(!cache.insert("e"));
-        // sleep until cache expiry
-        std::thread::sleep(Duration::from_millis(101));
+        // advance until cache expiry
+        clock.advance(Duration::from_millis(101));
         // add another element to clear previous cache
         cache.insert("s");
 
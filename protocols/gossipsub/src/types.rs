@@ -24,6 +24,7 @@ use libp2p_identity::PeerId;
 use libp2p_swarm::ConnectionId;
 use prometheus_client::encoding::EncodeLabelValue;
 use quick_protobuf::MessageWrite;
+use std::collections::{BTreeSet, HashMap};
 use std::fmt;
 use std::fmt::Debug;
 
@@ -93,6 +94,115 @@ pub struct PeerConnections {
     pub kind: PeerKind,
     /// Its current connections.
     pub connections: Vec<ConnectionId>,
+    /// The outbound messages dropped across its connections because their send queue was full.
+    pub dropped_messages: DroppedMessages,
+}
+
+/// The priority of an outbound RPC in the send queue of a connection.
+///
+/// Messages are sent in order of priority. When the send queue is full, messages of the lowest
+/// priority are dropped first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, EncodeLabelValue)]
+pub enum MessagePriority {
+    /// Subscriptions and GRAFT/PRUNE control messages.
+    Control,
+    /// Published and forwarded messages.
+    Data,
+    /// IHAVE/IWANT gossip.
+    Gossip,
+}
+
+impl MessagePriority {
+    /// Classifies an RPC by its most important content.
+    pub(crate) fn of(rpc: &proto::RPC) -> Self {
+        let has_mesh_control = rpc.control.as_ref().map_or(false, |control| {
+            !control.graft.is_empty() || !control.prune.is_empty()
+        });
+
+        if !rpc.subscriptions.is_empty() || has_mesh_control {
+            MessagePriority::Control
+        } else if !rpc.publish.is_empty() {
+            MessagePriority::Data
+        } else {
+            MessagePriority::Gossip
+        }
+    }
+}
+
+/// The number of outbound messages dropped per [`MessagePriority`] because the send queue of a
+/// connection was full.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DroppedMessages {
+    /// Dropped subscriptions and GRAFT/PRUNE control messages.
+    pub control: usize,
+    /// Dropped published and forwarded messages.
+    pub data: usize,
+    /// Dropped IHAVE/IWANT gossip.
+    pub gossip: usize,
+}
+
+impl DroppedMessages {
+    /// The total number of dropped messages.
+    pub fn total(&self) -> usize {
+        self.control + self.data + self.gossip
+    }
+
+    pub(crate) fn get_mut(&mut self, priority: MessagePriority) -> &mut usize {
+        match priority {
+            MessagePriority::Control => &mut self.control,
+            MessagePriority::Data => &mut self.data,
+            MessagePriority::Gossip => &mut self.gossip,
+        }
+    }
+
+    pub(crate) fn add(&mut self, other: &DroppedMessages) {
+        self.control += other.control;
+        self.data += other.data;
+        self.gossip += other.gossip;
+    }
+}
+
+/// The outcome of publishing a message, see
+/// [`Behaviour::publish`](crate::Behaviour::publish).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Published {
+    /// The [`MessageId`] of the published message.
+    pub message_id: MessageId,
+    /// The number of peers the message was sent to.
+    pub peers: usize,
+}
+
+/// A snapshot of the state of the gossipsub router, see
+/// [`Behaviour::dump`](crate::Behaviour::dump).
+#[derive(Debug, Clone, Default)]
+pub struct Dump {
+    /// The mesh peers of each subscribed topic.
+    pub mesh: HashMap<TopicHash, BTreeSet<PeerId>>,
+    /// The fanout peers of each topic we published to without being subscribed.
+    pub fanout: HashMap<TopicHash, BTreeSet<PeerId>>,
+    /// The known gossipsub peers.
+    pub peers: HashMap<PeerId, PeerDump>,
+    /// The number of heartbeats since the behaviour was created.
+    pub heartbeat_ticks: u64,
+}
+
+/// The state of a single peer in a [`Dump`].
+#[derive(Debug, Clone)]
+pub struct PeerDump {
+    /// The kind of protocol the peer supports.
+    pub kind: PeerKind,
+    /// The number of connections to the peer.
+    pub connections: usize,
+    /// The topics the peer is subscribed to.
+    pub topics: BTreeSet<TopicHash>,
+    /// Whether the peer is an explicit peer.
+    pub explicit: bool,
+    /// Whether the peer is connected via an outbound connection.
+    pub outbound: bool,
+    /// The score of the peer, if peer scoring is enabled.
+    pub score: Option<f64>,
+    /// The outbound messages dropped because the send queues of its connections were full.
+    pub dropped_messages: DroppedMessages,
 }
 
 /// Describes the types of peers that can exist in the gossipsub context.
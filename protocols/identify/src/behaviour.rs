@@ -20,9 +20,9 @@
 
 use crate::handler::{self, Handler, InEvent};
 use crate::protocol::{Info, Protocol, UpgradeError};
-use libp2p_core::{multiaddr, ConnectedPoint, Endpoint, Multiaddr};
+use libp2p_core::{multiaddr, ConnectedPoint, Endpoint, Multiaddr, PeerRecord};
 use libp2p_identity::PeerId;
-use libp2p_identity::PublicKey;
+use libp2p_identity::{Keypair, PublicKey};
 use libp2p_swarm::behaviour::{ConnectionClosed, ConnectionEstablished, DialFailure, FromSwarm};
 use libp2p_swarm::{
     dial_opts::DialOpts, AddressScore, ConnectionDenied, ConnectionHandlerUpgrErr, DialError,
@@ -31,6 +31,7 @@ use libp2p_swarm::{
 };
 use libp2p_swarm::{ConnectionId, THandler, THandlerOutEvent};
 use lru::LruCache;
+use std::net::IpAddr;
 use std::num::NonZeroUsize;
 use std::{
     collections::{HashMap, HashSet, VecDeque},
@@ -46,10 +47,18 @@ use std::{
 /// All external addresses of the local node supposedly observed by remotes
 /// are reported via [`ToSwarm::ReportObservedAddr`] with a
 /// [score](AddressScore) of `1`.
+///
+/// Changes to the protocols supported by connected peers and to their listen addresses are
+/// reported to all other behaviours via [`ToSwarm::ReportPeerProtocols`] and
+/// [`ToSwarm::ReportPeerListenAddrs`].
 pub struct Behaviour {
     config: Config,
     /// For each peer we're connected to, the observed address to send back to it.
     connected: HashMap<PeerId, HashMap<ConnectionId, Multiaddr>>,
+    /// For each peer we're connected to, the protocols it reported to support.
+    protocols: HashMap<PeerId, HashSet<String>>,
+    /// For each peer we're connected to, its sorted listen addresses.
+    listen_addrs: HashMap<PeerId, Vec<Multiaddr>>,
     /// Pending requests to be fulfilled, either `Handler` requests for `Behaviour` info
     /// to address identification requests, or push requests to peers
     /// with current information about the local peer.
@@ -58,6 +67,9 @@ pub struct Behaviour {
     events: VecDeque<ToSwarm<Event, InEvent>>,
     /// The addresses of all peers that we have discovered.
     discovered_peers: PeerCache,
+    /// The last signed peer record of the local node, re-signed whenever
+    /// the advertised addresses change.
+    local_peer_record: Option<PeerRecord>,
 
     listen_addresses: ListenAddresses,
     external_addresses: ExternalAddresses,
@@ -113,6 +125,24 @@ pub struct Config {
     ///
     /// Disabled by default.
     pub cache_size: usize,
+
+    /// The maximum number of listen addresses accepted from a remote peer.
+    ///
+    /// Duplicate addresses, those with a peer ID other than the remote's and those
+    /// that can never be dialed, i.e. empty addresses and those with an unspecified,
+    /// multicast or broadcast IP address, are removed before this limit is applied.
+    /// Excess addresses are discarded.
+    ///
+    /// Defaults to 16.
+    pub max_listen_addrs: usize,
+
+    /// The keypair of the local node, used to sign a peer record of the
+    /// advertised addresses that is sent along with every identify message.
+    ///
+    /// Must belong to [`Config::local_public_key`].
+    ///
+    /// Disabled by default.
+    pub local_keypair: Option<Keypair>,
 }
 
 impl Config {
@@ -127,6 +157,8 @@ impl Config {
             interval: Duration::from_secs(5 * 60),
             push_listen_addr_updates: false,
             cache_size: 100,
+            max_listen_addrs: 16,
+            local_keypair: None,
         }
     }
 
@@ -166,6 +198,28 @@ impl Config {
         self.cache_size = cache_size;
         self
     }
+
+    /// Configures the maximum number of listen addresses accepted from a remote peer.
+    pub fn with_max_listen_addrs(mut self, max_listen_addrs: usize) -> Self {
+        self.max_listen_addrs = max_listen_addrs;
+        self
+    }
+
+    /// Configures the keypair used to send a signed peer record of the
+    /// local addresses along with every identify message.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the keypair does not belong to [`Config::local_public_key`].
+    pub fn with_local_keypair(mut self, keypair: Keypair) -> Self {
+        assert_eq!(
+            keypair.public(),
+            self.local_public_key,
+            "keypair must belong to the local public key"
+        );
+        self.local_keypair = Some(keypair);
+        self
+    }
 }
 
 impl Behaviour {
@@ -179,14 +233,56 @@ impl Behaviour {
         Self {
             config,
             connected: HashMap::new(),
+            protocols: HashMap::new(),
+            listen_addrs: HashMap::new(),
             requests: Vec::new(),
             events: VecDeque::new(),
             discovered_peers,
+            local_peer_record: None,
             listen_addresses: Default::default(),
             external_addresses: Default::default(),
         }
     }
 
+    /// Records the protocols reported by the peer and reports changes to the swarm.
+    fn on_protocols(&mut self, peer_id: PeerId, protocols: &[String]) {
+        let new = protocols.iter().cloned().collect::<HashSet<_>>();
+        let old = self
+            .protocols
+            .insert(peer_id, new.clone())
+            .unwrap_or_default();
+
+        let mut added = new.difference(&old).cloned().collect::<Vec<_>>();
+        let mut removed = old.difference(&new).cloned().collect::<Vec<_>>();
+        if added.is_empty() && removed.is_empty() {
+            return;
+        }
+        added.sort();
+        removed.sort();
+
+        self.events.push_back(ToSwarm::ReportPeerProtocols {
+            peer_id,
+            added,
+            removed,
+        });
+    }
+
+    /// Records the listen addresses reported by the peer and reports changes to the swarm.
+    fn on_listen_addrs(&mut self, peer_id: PeerId, listen_addrs: &[Multiaddr]) {
+        let mut new = listen_addrs.to_vec();
+        new.sort();
+
+        if self.listen_addrs.get(&peer_id) == Some(&new) {
+            return;
+        }
+        self.listen_addrs.insert(peer_id, new.clone());
+
+        self.events.push_back(ToSwarm::ReportPeerListenAddrs {
+            peer_id,
+            listen_addrs: new,
+        });
+    }
+
     /// Initiates an active push of the local peer information to the given peers.
     pub fn push<I>(&mut self, peers: I)
     where
@@ -207,6 +303,37 @@ impl Behaviour {
         }
     }
 
+    /// Returns the addresses advertised to remotes, along with a signed peer
+    /// record of them if [`Config::local_keypair`] is set.
+    fn local_addresses(&mut self) -> (Vec<Multiaddr>, Option<PeerRecord>) {
+        let listen_addrs = self
+            .listen_addresses
+            .iter()
+            .chain(self.external_addresses.iter())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let keypair = match &self.config.local_keypair {
+            Some(keypair) => keypair,
+            None => return (listen_addrs, None),
+        };
+
+        let is_current = self.local_peer_record.as_ref().map_or(false, |record| {
+            record.addresses() == listen_addrs.as_slice()
+        });
+        if !is_current {
+            self.local_peer_record = match PeerRecord::new(keypair, listen_addrs.clone()) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    log::warn!("Failed to sign local peer record: {e}");
+                    None
+                }
+            };
+        }
+
+        (listen_addrs, self.local_peer_record.clone())
+    }
+
     fn on_connection_established(
         &mut self,
         ConnectionEstablished {
@@ -283,13 +410,29 @@ impl NetworkBehaviour for Behaviour {
     ) {
         match event {
             handler::Event::Identified(mut info) => {
-                // Remove invalid multiaddrs.
-                info.listen_addrs
-                    .retain(|addr| multiaddr_matches_peer_id(addr, &peer_id));
+                // Remove invalid multiaddrs and limit their number.
+                sanitize_listen_addrs(
+                    &mut info.listen_addrs,
+                    &peer_id,
+                    self.config.max_listen_addrs,
+                );
+
+                // Prefer the authenticated addresses of a signed peer record, if any.
+                let addrs = match &info.signed_peer_record {
+                    Some(record) => {
+                        let mut addrs = record.addresses().to_vec();
+                        sanitize_listen_addrs(&mut addrs, &peer_id, self.config.max_listen_addrs);
+                        addrs
+                    }
+                    None => info.listen_addrs.clone(),
+                };
+
+                self.on_listen_addrs(peer_id, &addrs);
 
                 // Replace existing addresses to prevent other peer from filling up our memory.
-                self.discovered_peers
-                    .put(peer_id, info.listen_addrs.iter().cloned());
+                self.discovered_peers.put(peer_id, addrs.into_iter());
+
+                self.on_protocols(peer_id, &info.protocols);
 
                 let observed = info.observed_addr.clone();
                 self.events
@@ -330,43 +473,26 @@ impl NetworkBehaviour for Behaviour {
         }
 
         // Check for pending requests.
-        match self.requests.pop() {
-            Some(Request {
-                peer_id,
-                protocol: Protocol::Push,
-            }) => Poll::Ready(ToSwarm::NotifyHandler {
-                peer_id,
-                handler: NotifyHandler::Any,
-                event: InEvent {
-                    listen_addrs: self
-                        .listen_addresses
-                        .iter()
-                        .chain(self.external_addresses.iter())
-                        .cloned()
-                        .collect(),
-                    supported_protocols: supported_protocols(params),
-                    protocol: Protocol::Push,
-                },
-            }),
-            Some(Request {
-                peer_id,
-                protocol: Protocol::Identify(connection_id),
-            }) => Poll::Ready(ToSwarm::NotifyHandler {
-                peer_id,
-                handler: NotifyHandler::One(connection_id),
-                event: InEvent {
-                    listen_addrs: self
-                        .listen_addresses
-                        .iter()
-                        .chain(self.external_addresses.iter())
-                        .cloned()
-                        .collect(),
-                    supported_protocols: supported_protocols(params),
-                    protocol: Protocol::Identify(connection_id),
-                },
-            }),
-            None => Poll::Pending,
-        }
+        let request = match self.requests.pop() {
+            Some(request) => request,
+            None => return Poll::Pending,
+        };
+        let (listen_addrs, signed_peer_record) = self.local_addresses();
+        let handler = match request.protocol {
+            Protocol::Push => NotifyHandler::Any,
+            Protocol::Identify(connection_id) => NotifyHandler::One(connection_id),
+        };
+
+        Poll::Ready(ToSwarm::NotifyHandler {
+            peer_id: request.peer_id,
+            handler,
+            event: InEvent {
+                listen_addrs,
+                supported_protocols: supported_protocols(params),
+                signed_peer_record,
+                protocol: request.protocol,
+            },
+        })
     }
 
     fn handle_pending_outbound_connection(
@@ -384,6 +510,10 @@ impl NetworkBehaviour for Behaviour {
         Ok(self.discovered_peers.get(&peer))
     }
 
+    fn queued_actions(&self) -> usize {
+        self.events.len() + self.requests.len()
+    }
+
     fn on_swarm_event(&mut self, event: FromSwarm<Self::ConnectionHandler>) {
         self.listen_addresses.on_swarm_event(&event);
         self.external_addresses.on_swarm_event(&event);
@@ -400,6 +530,8 @@ impl NetworkBehaviour for Behaviour {
             }) => {
                 if remaining_established == 0 {
                     self.connected.remove(&peer_id);
+                    self.protocols.remove(&peer_id);
+                    self.listen_addrs.remove(&peer_id);
                     self.requests.retain(|request| {
                         request
                             != &Request {
@@ -451,7 +583,10 @@ impl NetworkBehaviour for Behaviour {
             | FromSwarm::ListenerError(_)
             | FromSwarm::ListenerClosed(_)
             | FromSwarm::NewExternalAddr(_)
-            | FromSwarm::ExpiredExternalAddr(_) => {}
+            | FromSwarm::ExpiredExternalAddr(_)
+            | FromSwarm::PeerProtocolsChanged(_)
+            | FromSwarm::PeerListenAddrsChanged(_)
+            | FromSwarm::NatStatusChanged(_) => {}
         }
     }
 }
@@ -507,6 +642,43 @@ fn multiaddr_matches_peer_id(addr: &Multiaddr, peer_id: &PeerId) -> bool {
     true
 }
 
+/// Returns `false` for addresses that can never be dialed, i.e. empty addresses
+/// and those with an unspecified, multicast or broadcast IP address.
+fn is_dialable(addr: &Multiaddr) -> bool {
+    let ip = match addr.iter().next() {
+        Some(multiaddr::Protocol::Ip4(ip)) => IpAddr::from(ip),
+        Some(multiaddr::Protocol::Ip6(ip)) => IpAddr::from(ip),
+        Some(_) => return true,
+        None => return false,
+    };
+
+    let is_broadcast = match ip {
+        IpAddr::V4(ip) => ip.is_broadcast(),
+        IpAddr::V6(_) => false,
+    };
+
+    !(ip.is_unspecified() || ip.is_multicast() || is_broadcast)
+}
+
+/// Removes invalid, undialable and duplicate listen addresses reported by `peer_id`,
+/// keeping at most `max` addresses.
+fn sanitize_listen_addrs(addrs: &mut Vec<Multiaddr>, peer_id: &PeerId, max: usize) {
+    let mut seen = HashSet::new();
+    addrs.retain(|addr| {
+        multiaddr_matches_peer_id(addr, peer_id) && is_dialable(addr) && seen.insert(addr.clone())
+    });
+
+    if addrs.len() > max {
+        log::debug!(
+            "Discarding {} listen addresses of {} exceeding the limit of {}",
+            addrs.len() - max,
+            peer_id,
+            max
+        );
+        addrs.truncate(max);
+    }
+}
+
 struct PeerCache(Option<LruCache<PeerId, HashSet<Multiaddr>>>);
 
 impl PeerCache {
@@ -564,16 +736,20 @@ mod tests {
         transport::Boxed<(PeerId, StreamMuxerBox)>,
     ) {
         let id_keys = identity::Keypair::generate_ed25519();
+        (id_keys.public(), transport_with_keys(&id_keys))
+    }
+
+    fn transport_with_keys(
+        id_keys: &identity::Keypair,
+    ) -> transport::Boxed<(PeerId, StreamMuxerBox)> {
         let noise_keys = noise::Keypair::<noise::X25519Spec>::new()
-            .into_authentic(&id_keys)
+            .into_authentic(id_keys)
             .unwrap();
-        let pubkey = id_keys.public();
-        let transport = tcp::async_io::Transport::new(tcp::Config::default().nodelay(true))
+        tcp::async_io::Transport::new(tcp::Config::default().nodelay(true))
             .upgrade(upgrade::Version::V1)
             .authenticate(noise::NoiseConfig::xx(noise_keys).into_authenticated())
             .multiplex(MplexConfig::new())
-            .boxed();
-        (pubkey, transport)
+            .boxed()
     }
 
     #[test]
@@ -734,6 +910,77 @@ mod tests {
         })
     }
 
+    #[test]
+    fn signed_peer_record_is_sent_and_verified() {
+        let _ = env_logger::try_init();
+
+        let id_keys = identity::Keypair::generate_ed25519();
+        let mut swarm1 = {
+            let pubkey = id_keys.public();
+            let protocol = Behaviour::new(
+                Config::new("a".to_string(), pubkey.clone())
+                    .with_local_keypair(id_keys.clone())
+                    .with_initial_delay(Duration::from_secs(10)),
+            );
+
+            SwarmBuilder::with_async_std_executor(
+                transport_with_keys(&id_keys),
+                protocol,
+                pubkey.to_peer_id(),
+            )
+            .build()
+        };
+
+        let mut swarm2 = {
+            let (pubkey, transport) = transport();
+            let protocol = Behaviour::new(Config::new("a".to_string(), pubkey.clone()));
+
+            SwarmBuilder::with_async_std_executor(transport, protocol, pubkey.to_peer_id()).build()
+        };
+
+        swarm1
+            .listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .unwrap();
+
+        let listen_addr = async_std::task::block_on(async {
+            loop {
+                if let SwarmEvent::NewListenAddr { address, .. } = swarm1.select_next_some().await {
+                    return address;
+                }
+            }
+        });
+
+        async_std::task::spawn(async move {
+            loop {
+                swarm1.next().await;
+            }
+        });
+
+        swarm2.dial(listen_addr.clone()).unwrap();
+
+        let info = async_std::task::block_on(async {
+            loop {
+                if let SwarmEvent::Behaviour(Event::Received { info, .. }) =
+                    swarm2.select_next_some().await
+                {
+                    break info;
+                }
+            }
+        });
+
+        let record = info.signed_peer_record.expect("a signed peer record");
+        assert_eq!(record.peer_id(), id_keys.public().to_peer_id());
+        assert_eq!(record.addresses(), &[listen_addr]);
+    }
+
+    #[test]
+    #[should_panic(expected = "keypair must belong to the local public key")]
+    fn local_keypair_must_match_public_key() {
+        let public_key = identity::Keypair::generate_ed25519().public();
+        let _ = Config::new("a".to_string(), public_key)
+            .with_local_keypair(identity::Keypair::generate_ed25519());
+    }
+
     #[test]
     fn discover_peer_after_disconnect() {
         let _ = env_logger::try_init();
@@ -848,4 +1095,140 @@ mod tests {
         ));
         assert!(multiaddr_matches_peer_id(&addr_without_peer_id, &peer_id));
     }
+
+    #[test]
+    fn sanitize_listen_addrs_removes_junk() {
+        let peer_id = PeerId::random();
+        let valid: Multiaddr = "/ip4/147.75.69.143/tcp/4001".parse().unwrap();
+        let mut addrs = vec![
+            valid.clone(),
+            valid.clone(),
+            Multiaddr::empty(),
+            "/ip4/0.0.0.0/tcp/4001".parse().unwrap(),
+            "/ip6/::/tcp/4001".parse().unwrap(),
+            "/ip4/224.0.0.1/udp/5353".parse().unwrap(),
+            "/ip4/255.255.255.255/tcp/4001".parse().unwrap(),
+            valid
+                .clone()
+                .with(multiaddr::Protocol::P2p(PeerId::random().into())),
+            "/dns4/example.com/tcp/443".parse().unwrap(),
+        ];
+
+        sanitize_listen_addrs(&mut addrs, &peer_id, 16);
+
+        assert_eq!(
+            addrs,
+            vec![valid, "/dns4/example.com/tcp/443".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn sanitize_listen_addrs_applies_limit() {
+        let peer_id = PeerId::random();
+        let mut addrs = (0..100u16)
+            .map(|port| {
+                "/ip4/147.75.69.143"
+                    .parse::<Multiaddr>()
+                    .unwrap()
+                    .with(multiaddr::Protocol::Tcp(port))
+            })
+            .collect::<Vec<_>>();
+
+        sanitize_listen_addrs(&mut addrs, &peer_id, 16);
+
+        assert_eq!(addrs.len(), 16);
+    }
+
+    #[test]
+    fn reports_changes_of_peer_protocols() {
+        let local_public_key = identity::Keypair::generate_ed25519().public();
+        let mut behaviour = Behaviour::new(Config::new("a".to_string(), local_public_key));
+        let peer_id = PeerId::random();
+
+        let mut identify = |protocols: &[&str]| {
+            behaviour.on_connection_handler_event(
+                peer_id,
+                ConnectionId::new_unchecked(0),
+                handler::Event::Identified(Info {
+                    public_key: identity::Keypair::generate_ed25519().public(),
+                    protocol_version: "b".to_string(),
+                    agent_version: "c".to_string(),
+                    listen_addrs: Vec::new(),
+                    protocols: protocols.iter().map(|p| p.to_string()).collect(),
+                    observed_addr: Multiaddr::empty(),
+                    signed_peer_record: None,
+                }),
+            );
+
+            behaviour
+                .events
+                .drain(..)
+                .filter_map(|event| match event {
+                    ToSwarm::ReportPeerProtocols {
+                        peer_id: p,
+                        added,
+                        removed,
+                    } => {
+                        assert_eq!(p, peer_id);
+                        Some((added, removed))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            identify(&["/b", "/a"]),
+            vec![(vec!["/a".to_string(), "/b".to_string()], vec![])]
+        );
+        assert_eq!(
+            identify(&["/b", "/c"]),
+            vec![(vec!["/c".to_string()], vec!["/a".to_string()])]
+        );
+        assert_eq!(identify(&["/c", "/b"]), vec![]);
+    }
+
+    #[test]
+    fn reports_changes_of_peer_listen_addrs() {
+        let local_public_key = identity::Keypair::generate_ed25519().public();
+        let mut behaviour = Behaviour::new(Config::new("a".to_string(), local_public_key));
+        let peer_id = PeerId::random();
+        let a = "/ip4/147.75.69.143/tcp/1".parse::<Multiaddr>().unwrap();
+        let b = "/ip4/147.75.69.143/tcp/2".parse::<Multiaddr>().unwrap();
+
+        let mut identify = |listen_addrs: &[&Multiaddr]| {
+            behaviour.on_connection_handler_event(
+                peer_id,
+                ConnectionId::new_unchecked(0),
+                handler::Event::Identified(Info {
+                    public_key: identity::Keypair::generate_ed25519().public(),
+                    protocol_version: "b".to_string(),
+                    agent_version: "c".to_string(),
+                    listen_addrs: listen_addrs.iter().map(|a| (*a).clone()).collect(),
+                    protocols: Vec::new(),
+                    observed_addr: Multiaddr::empty(),
+                    signed_peer_record: None,
+                }),
+            );
+
+            behaviour
+                .events
+                .drain(..)
+                .filter_map(|event| match event {
+                    ToSwarm::ReportPeerListenAddrs {
+                        peer_id: p,
+                        listen_addrs,
+                    } => {
+                        assert_eq!(p, peer_id);
+                        Some(listen_addrs)
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(identify(&[&b, &a]), vec![vec![a.clone(), b.clone()]]);
+        assert_eq!(identify(&[&a, &b]), Vec::<Vec<Multiaddr>>::new());
+        assert_eq!(identify(&[&b]), vec![vec![b.clone()]]);
+    }
 }
@@ -27,11 +27,12 @@ use futures::prelude::*;
 use futures::stream::FuturesUnordered;
 use futures_timer::Delay;
 use libp2p_core::upgrade::SelectUpgrade;
-use libp2p_core::Multiaddr;
+use libp2p_core::{Multiaddr, PeerRecord};
 use libp2p_identity::PeerId;
 use libp2p_identity::PublicKey;
 use libp2p_swarm::handler::{
     ConnectionEvent, DialUpgradeError, FullyNegotiatedInbound, FullyNegotiatedOutbound,
+    StreamPriority,
 };
 use libp2p_swarm::{
     ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, KeepAlive,
@@ -94,6 +95,9 @@ pub struct InEvent {
     /// The list of protocols supported by the peer, e.g. `/ipfs/ping/1.0.0`.
     pub supported_protocols: Vec<String>,
 
+    /// The signed peer record of the peer, if any.
+    pub signed_peer_record: Option<PeerRecord>,
+
     /// The protocol w.r.t. the information requested.
     pub protocol: Protocol,
 }
@@ -232,6 +236,7 @@ impl ConnectionHandler for Handler {
 
     fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
         SubstreamProtocol::new(SelectUpgrade::new(Identify, Push::inbound()), ())
+            .with_priority(StreamPriority::High)
     }
 
     fn on_behaviour_event(
@@ -239,6 +244,7 @@ impl ConnectionHandler for Handler {
         InEvent {
             listen_addrs,
             supported_protocols,
+            signed_peer_record,
             protocol,
         }: Self::InEvent,
     ) {
@@ -249,13 +255,15 @@ impl ConnectionHandler for Handler {
             listen_addrs,
             protocols: supported_protocols,
             observed_addr: self.observed_addr.clone(),
+            signed_peer_record,
         };
 
         match protocol {
             Protocol::Push => {
                 self.events
                     .push(ConnectionHandlerEvent::OutboundSubstreamRequest {
-                        protocol: SubstreamProtocol::new(Either::Right(Push::outbound(info)), ()),
+                        protocol: SubstreamProtocol::new(Either::Right(Push::outbound(info)), ())
+                            .with_priority(StreamPriority::High),
                     });
             }
             Protocol::Identify(_) => {
@@ -293,7 +301,8 @@ impl ConnectionHandler for Handler {
             Poll::Ready(()) => {
                 self.trigger_next_identify.reset(self.interval);
                 let ev = ConnectionHandlerEvent::OutboundSubstreamRequest {
-                    protocol: SubstreamProtocol::new(Either::Left(Identify), ()),
+                    protocol: SubstreamProtocol::new(Either::Left(Identify), ())
+                        .with_priority(StreamPriority::High),
                 };
                 return Poll::Ready(ev);
             }
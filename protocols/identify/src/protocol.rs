@@ -22,9 +22,9 @@ use crate::proto;
 use asynchronous_codec::{FramedRead, FramedWrite};
 use futures::{future::BoxFuture, prelude::*};
 use libp2p_core::{
-    multiaddr,
+    multiaddr, peer_record, signed_envelope,
     upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo},
-    Multiaddr,
+    Multiaddr, PeerRecord, SignedEnvelope,
 };
 use libp2p_identity as identity;
 use libp2p_identity::PublicKey;
@@ -87,6 +87,11 @@ pub struct Info {
     pub protocols: Vec<String>,
     /// Address observed by or for the remote.
     pub observed_addr: Multiaddr,
+    /// The signed peer record of the peer, authenticating its listen addresses.
+    ///
+    /// Records received from a remote have been verified to carry a valid
+    /// signature of the remote's [`PublicKey`]; invalid records are discarded.
+    pub signed_peer_record: Option<PeerRecord>,
 }
 
 impl UpgradeInfo for Identify {
@@ -178,6 +183,9 @@ where
         listenAddrs: listen_addrs,
         observedAddr: Some(info.observed_addr.to_vec()),
         protocols: info.protocols,
+        signedPeerRecord: info
+            .signed_peer_record
+            .map(|record| record.into_signed_envelope().into_protobuf_encoding()),
     };
 
     let mut framed_io = FramedWrite::new(
@@ -244,6 +252,16 @@ impl TryFrom<proto::Identify> for Info {
                 Multiaddr::empty()
             }
         };
+        let signed_peer_record = msg.signedPeerRecord.and_then(|bytes| {
+            match parse_signed_peer_record(&bytes, &public_key) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    debug!("Discarding invalid signed peer record: {e}");
+                    None
+                }
+            }
+        });
+
         let info = Info {
             public_key,
             protocol_version: msg.protocolVersion.unwrap_or_default(),
@@ -251,12 +269,38 @@ impl TryFrom<proto::Identify> for Info {
             listen_addrs,
             protocols: msg.protocols,
             observed_addr,
+            signed_peer_record,
         };
 
         Ok(info)
     }
 }
 
+/// Decodes a signed peer record and verifies that it was signed by `public_key`.
+fn parse_signed_peer_record(
+    bytes: &[u8],
+    public_key: &PublicKey,
+) -> Result<PeerRecord, SignedPeerRecordError> {
+    let envelope = SignedEnvelope::from_protobuf_encoding(bytes)?;
+    let record = PeerRecord::from_signed_envelope(envelope)?;
+
+    if record.peer_id() != public_key.to_peer_id() {
+        return Err(SignedPeerRecordError::MismatchedKey);
+    }
+
+    Ok(record)
+}
+
+#[derive(Debug, Error)]
+enum SignedPeerRecordError {
+    #[error("Failed decoding signed envelope")]
+    Envelope(#[from] signed_envelope::DecodingError),
+    #[error("Failed reading peer record from signed envelope")]
+    Record(#[from] peer_record::FromEnvelopeError),
+    #[error("Peer record is not signed by the remote's public key")]
+    MismatchedKey,
+}
+
 #[derive(Debug, Error)]
 pub enum UpgradeError {
     #[error(transparent)]
@@ -330,6 +374,7 @@ mod tests {
                     ],
                     protocols: vec!["proto1".to_string(), "proto2".to_string()],
                     observed_addr: "/ip4/100.101.102.103/tcp/5000".parse().unwrap(),
+                    signed_peer_record: None,
                 },
             )
             .await
@@ -388,10 +433,56 @@ mod tests {
                     .public()
                     .to_protobuf_encoding(),
             ),
+            signedPeerRecord: None,
         };
 
         let info = Info::try_from(payload).expect("not to fail");
 
         assert_eq!(info.listen_addrs, vec![valid_multiaddr])
     }
+
+    #[test]
+    fn verify_signed_peer_record() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let addr: Multiaddr = "/ip4/80.81.82.83/tcp/500".parse().unwrap();
+        let record = PeerRecord::new(&keypair, vec![addr.clone()]).unwrap();
+
+        let payload = proto::Identify {
+            agentVersion: None,
+            listenAddrs: vec![],
+            observedAddr: None,
+            protocolVersion: None,
+            protocols: vec![],
+            publicKey: Some(keypair.public().to_protobuf_encoding()),
+            signedPeerRecord: Some(record.into_signed_envelope().into_protobuf_encoding()),
+        };
+
+        let info = Info::try_from(payload).expect("not to fail");
+        let record = info.signed_peer_record.expect("a valid peer record");
+
+        assert_eq!(record.peer_id(), keypair.public().to_peer_id());
+        assert_eq!(record.addresses(), &[addr]);
+    }
+
+    #[test]
+    fn discard_signed_peer_record_of_other_peer() {
+        let keypair = identity::Keypair::generate_ed25519();
+        let other = identity::Keypair::generate_ed25519();
+        let record =
+            PeerRecord::new(&other, vec!["/ip4/80.81.82.83/tcp/500".parse().unwrap()]).unwrap();
+
+        let payload = proto::Identify {
+            agentVersion: None,
+            listenAddrs: vec![],
+            observedAddr: None,
+            protocolVersion: None,
+            protocols: vec![],
+            publicKey: Some(keypair.public().to_protobuf_encoding()),
+            signedPeerRecord: Some(record.into_signed_envelope().into_protobuf_encoding()),
+        };
+
+        let info = Info::try_from(payload).expect("not to fail");
+
+        assert!(info.signed_peer_record.is_none());
+    }
 }
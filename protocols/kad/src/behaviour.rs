@@ -23,6 +23,7 @@
 mod test;
 
 use crate::addresses::Addresses;
+use crate::diversity::IpDiversity;
 use crate::handler::{
     KademliaHandler, KademliaHandlerConfig, KademliaHandlerEvent, KademliaHandlerIn,
     KademliaRequestId,
@@ -31,31 +32,35 @@ use crate::jobs::*;
 use crate::kbucket::{self, Distance, KBucketsTable, NodeStatus};
 use crate::protocol::{KadConnectionType, KadPeer, KademliaProtocolConfig};
 use crate::query::{Query, QueryConfig, QueryId, QueryPool, QueryPoolState};
+use crate::rate_limit::{InboundRateLimit, InboundRateLimiter, InboundThrottleStats};
 use crate::record::{
     self,
     store::{self, RecordStore},
-    ProviderRecord, Record,
+    AcceptAllValidator, ProviderRecord, Record, RecordValidator, MAX_PROVIDER_METADATA_SIZE,
 };
+use crate::routing::{LookupId, Routing, RoutingEvent};
 use crate::K_VALUE;
 use fnv::{FnvHashMap, FnvHashSet};
-use instant::Instant;
 use libp2p_core::{ConnectedPoint, Endpoint, Multiaddr};
 use libp2p_identity::PeerId;
 use libp2p_swarm::behaviour::{
     AddressChange, ConnectionClosed, ConnectionEstablished, DialFailure, FromSwarm,
+    PeerProtocolsChanged,
 };
 use libp2p_swarm::{
     dial_opts::{self, DialOpts},
-    ConnectionDenied, ConnectionId, DialError, ExternalAddresses, ListenAddresses,
-    NetworkBehaviour, NotifyHandler, PollParameters, THandler, THandlerInEvent, THandlerOutEvent,
-    ToSwarm,
+    Clock, ConnectionDenied, ConnectionId, DialError, ExternalAddresses, ListenAddresses,
+    NetworkBehaviour, NotifyHandler, PollParameters, SystemClock, THandler, THandlerInEvent,
+    THandlerOutEvent, ToSwarm,
 };
 use log::{debug, info, warn};
 use smallvec::SmallVec;
-use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fmt;
 use std::num::NonZeroUsize;
-use std::task::{Context, Poll};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
 use std::vec;
 use std::{borrow::Cow, time::Duration};
 use thiserror::Error;
@@ -71,6 +76,29 @@ pub struct Kademlia<TStore> {
     /// The k-bucket insertion strategy.
     kbucket_inserts: KademliaBucketInserts,
 
+    /// See [`KademliaConfig::set_protocol_confirmation`].
+    protocol_confirmation: KademliaProtocolConfirmation,
+
+    /// The connected peers that advertised the Kademlia protocol, with the configured protocol
+    /// names they advertised, as reported via [`FromSwarm::PeerProtocolsChanged`].
+    advertising_peers: FnvHashMap<PeerId, HashSet<String>>,
+
+    /// The connected peers confirmed to support the Kademlia protocol by their handler, waiting
+    /// to advertise it before being inserted into the routing table, with the address to insert.
+    ///
+    /// Only tracked with [`KademliaProtocolConfirmation::Advertised`].
+    unadvertised_peers: FnvHashMap<PeerId, Option<Multiaddr>>,
+
+    /// See [`KademliaConfig::set_peer_probation`].
+    peer_probation: bool,
+
+    /// The connected peers on probation, waiting to answer a request before being inserted into
+    /// the routing table, with the address to insert.
+    probation: FnvHashMap<PeerId, Multiaddr>,
+
+    /// The connected peers that passed probation.
+    verified_peers: FnvHashSet<PeerId>,
+
     /// Configuration of the wire protocol.
     protocol_config: KademliaProtocolConfig,
 
@@ -85,6 +113,10 @@ pub struct Kademlia<TStore> {
     /// This is a superset of the connected peers currently in the routing table.
     connected_peers: FnvHashSet<PeerId>,
 
+    /// The currently established connections, used to notify all handlers of changes
+    /// to the [`Mode`].
+    connections: FnvHashMap<ConnectionId, PeerId>,
+
     /// Periodic job for re-publication of provider records for keys
     /// provided by the local node.
     add_provider_job: Option<AddProviderJob>,
@@ -93,6 +125,13 @@ pub struct Kademlia<TStore> {
     /// regular (value-)records.
     put_record_job: Option<PutRecordJob>,
 
+    /// Periodic job for checking the health of stored (value-)records,
+    /// see [`KademliaConfig::set_record_repair_interval`].
+    record_repair_job: Option<RecordRepairJob>,
+
+    /// See [`KademliaConfig::set_record_validator`].
+    record_validator: Arc<dyn RecordValidator>,
+
     /// The TTL of regular (value-)records.
     record_ttl: Option<Duration>,
 
@@ -109,6 +148,33 @@ pub struct Kademlia<TStore> {
 
     external_addresses: ExternalAddresses,
 
+    /// See [`KademliaConfig::set_advertise_listen_addresses`].
+    advertise_listen_addresses: bool,
+
+    /// Whether the behaviour is paused, see [`Kademlia::pause`].
+    paused: bool,
+
+    /// See [`KademliaConfig::set_paused_queries`].
+    paused_queries: KademliaPausedQueries,
+
+    /// See [`KademliaConfig::set_record_namespace`].
+    record_namespaces: Vec<RecordNamespace>,
+
+    /// See [`KademliaConfig::set_max_peers_per_ip_prefix`].
+    ip_diversity: IpDiversity,
+
+    /// See [`KademliaConfig::set_closest_peers_quorum`].
+    closest_peers_quorum: Option<NonZeroUsize>,
+
+    /// See [`KademliaConfig::set_inbound_rate_limit`].
+    inbound_rate_limiter: InboundRateLimiter,
+
+    /// See [`KademliaConfig::set_inbound_throttling`].
+    inbound_throttling: KademliaInboundThrottling,
+
+    /// See [`KademliaConfig::set_clock`].
+    clock: Arc<dyn Clock>,
+
     /// See [`KademliaConfig::caching`].
     caching: KademliaCaching,
 
@@ -116,6 +182,45 @@ pub struct Kademlia<TStore> {
 
     /// The record storage.
     store: TStore,
+
+    /// Router consulted in addition to the DHT for provider lookups.
+    ///
+    /// See [`Kademlia::set_routing`].
+    routing: Option<Box<dyn Routing + Send>>,
+
+    /// Provider lookups currently delegated to [`Kademlia::routing`].
+    delegated_lookups: FnvHashMap<LookupId, DelegatedLookup>,
+
+    /// Queries started through the [`Routing`] implementation of [`Kademlia`].
+    ///
+    /// The results of these queries are reported via [`Routing::poll_providers`]
+    /// instead of as [`KademliaEvent`]s.
+    routing_queries: FnvHashMap<QueryId, LookupId>,
+
+    /// Queued results of [`Kademlia::routing_queries`].
+    routing_events: VecDeque<RoutingEvent>,
+
+    /// Waker of the last call to [`Routing::poll_providers`] that returned `Pending`.
+    routing_waker: Option<Waker>,
+
+    /// Waker of the last call to [`NetworkBehaviour::poll`] that returned `Pending`, woken on
+    /// [`Kademlia::resume`].
+    waker: Option<Waker>,
+
+    /// The keys tracked with [`Kademlia::track_proximity`], with whether the local node is among
+    /// the closest nodes to the key.
+    proximity_keys: HashMap<record::Key, bool>,
+
+    /// Whether the routing table changed since the proximity of the `proximity_keys` was
+    /// last checked.
+    proximity_dirty: bool,
+
+    /// Dials of [`Kademlia::find_and_dial`] queries whose lookup finished, by the
+    /// [`ConnectionId`] of the dial.
+    find_and_dials: FnvHashMap<ConnectionId, FindAndDial>,
+
+    /// See [`Kademlia::set_mode`].
+    mode: Mode,
 }
 
 /// The configurable strategies for the insertion of peers
@@ -142,6 +247,23 @@ pub enum KademliaBucketInserts {
     Manual,
 }
 
+/// The confirmation required of a connected peer supporting the Kademlia
+/// protocol before it is newly inserted into the routing table.
+///
+/// See [`KademliaConfig::set_protocol_confirmation`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KademliaProtocolConfirmation {
+    /// The peer is inserted once a Kademlia substream has been negotiated with it.
+    Negotiated,
+    /// The peer is inserted once a Kademlia substream has been negotiated with it and it
+    /// advertised the Kademlia protocol, e.g. via the identify protocol.
+    ///
+    /// Prevents peers that merely accept Kademlia substreams, without taking part in the DHT,
+    /// from filling the routing table. Peers whose protocols are never reported via
+    /// [`ToSwarm::ReportPeerProtocols`] are never inserted.
+    Advertised,
+}
+
 /// The configurable filtering strategies for the acceptance of
 /// incoming records.
 ///
@@ -165,6 +287,52 @@ pub enum KademliaStoreInserts {
     FilterBoth,
 }
 
+/// The handling of queries started while a [`Kademlia`] behaviour is paused.
+///
+/// See [`Kademlia::pause`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KademliaPausedQueries {
+    /// Queries are held back and started once the behaviour is resumed.
+    Queue,
+    /// Queries fail immediately with a timeout error.
+    Fail,
+}
+
+/// The handling of inbound requests exceeding the rate limits.
+///
+/// See [`KademliaConfig::set_inbound_rate_limit`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KademliaInboundThrottling {
+    /// The request is reset, signaling an error to the remote.
+    Drop,
+    /// The request is answered without any peers or records, as if the local node knew none.
+    ///
+    /// `PUT_VALUE` requests are reset nonetheless, as the record is not stored.
+    EmptyResponse,
+}
+
+/// The mode a [`Kademlia`] behaviour operates in.
+///
+/// See [`Kademlia::set_mode`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Only issue requests to other peers, refusing inbound Kademlia requests.
+    ///
+    /// Suitable for nodes that are not publicly reachable.
+    Client,
+    /// Issue requests to other peers and answer inbound Kademlia requests.
+    Server,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mode::Client => write!(f, "client"),
+            Mode::Server => write!(f, "server"),
+        }
+    }
+}
+
 /// The configuration for the `Kademlia` behaviour.
 ///
 /// The configuration is consumed by [`Kademlia::new`].
@@ -177,11 +345,35 @@ pub struct KademliaConfig {
     record_replication_interval: Option<Duration>,
     record_publication_interval: Option<Duration>,
     record_filtering: KademliaStoreInserts,
+    record_validator: Arc<dyn RecordValidator>,
+    record_repair_interval: Option<Duration>,
+    record_repair_sample_size: NonZeroUsize,
     provider_record_ttl: Option<Duration>,
     provider_publication_interval: Option<Duration>,
     connection_idle_timeout: Duration,
     kbucket_inserts: KademliaBucketInserts,
+    protocol_confirmation: KademliaProtocolConfirmation,
+    peer_probation: bool,
     caching: KademliaCaching,
+    advertise_listen_addresses: bool,
+    paused_queries: KademliaPausedQueries,
+    record_namespaces: Vec<RecordNamespace>,
+    ip_diversity: IpDiversity,
+    closest_peers_quorum: Option<NonZeroUsize>,
+    inbound_rate_limit: Option<InboundRateLimit>,
+    global_inbound_rate_limit: Option<InboundRateLimit>,
+    inbound_throttling: KademliaInboundThrottling,
+    clock: Arc<dyn Clock>,
+}
+
+/// The replication settings of the records with keys starting with `prefix`.
+///
+/// See [`KademliaConfig::set_record_namespace`].
+#[derive(Debug, Clone)]
+struct RecordNamespace {
+    prefix: Vec<u8>,
+    replication_factor: NonZeroUsize,
+    quorum: Quorum,
 }
 
 impl Default for KademliaConfig {
@@ -194,11 +386,25 @@ impl Default for KademliaConfig {
             record_replication_interval: Some(Duration::from_secs(60 * 60)),
             record_publication_interval: Some(Duration::from_secs(24 * 60 * 60)),
             record_filtering: KademliaStoreInserts::Unfiltered,
+            record_validator: Arc::new(AcceptAllValidator),
+            record_repair_interval: None,
+            record_repair_sample_size: NonZeroUsize::new(16).expect("16 > 0"),
             provider_publication_interval: Some(Duration::from_secs(12 * 60 * 60)),
             provider_record_ttl: Some(Duration::from_secs(24 * 60 * 60)),
             connection_idle_timeout: Duration::from_secs(10),
             kbucket_inserts: KademliaBucketInserts::OnConnected,
+            protocol_confirmation: KademliaProtocolConfirmation::Negotiated,
+            peer_probation: false,
             caching: KademliaCaching::Enabled { max_peers: 1 },
+            advertise_listen_addresses: false,
+            paused_queries: KademliaPausedQueries::Queue,
+            record_namespaces: Vec::new(),
+            ip_diversity: IpDiversity::default(),
+            closest_peers_quorum: None,
+            inbound_rate_limit: None,
+            global_inbound_rate_limit: None,
+            inbound_throttling: KademliaInboundThrottling::Drop,
+            clock: Arc::new(SystemClock),
         }
     }
 }
@@ -283,6 +489,36 @@ impl KademliaConfig {
         self
     }
 
+    /// Prefer peers of low latency when selecting the next peers to contact in
+    /// iterative queries.
+    ///
+    /// When enabled, of the `parallelism` closest peers not yet contacted, the one
+    /// with the lowest round-trip time is queried first, reducing the tail latency
+    /// of lookups. Round-trip times are measured from responses to earlier requests
+    /// and can be supplemented via [`Kademlia::record_peer_latency`], e.g. with the
+    /// round-trip times measured by the ping protocol.
+    ///
+    /// Disabled by default.
+    pub fn set_latency_weighting(&mut self, enabled: bool) -> &mut Self {
+        self.query_config.latency_weighting = enabled;
+        self
+    }
+
+    /// Sets the hasher mapping peer IDs and record keys into the DHT keyspace.
+    ///
+    /// The default is [`kbucket::Sha256Hasher`], as used by the IPFS DHT. Networks
+    /// deriving DHT keys differently, e.g. from pre-hashed keys with
+    /// [`kbucket::IdentityHasher`], can provide their own [`kbucket::KeyHasher`].
+    /// All nodes of a network must use the same hasher, as it determines the
+    /// XOR distances along which records are stored and peers are looked up.
+    ///
+    /// When using a [`MemoryStore`](crate::store::MemoryStore), configure it with
+    /// the same hasher via [`MemoryStore::with_key_hasher`](crate::store::MemoryStore::with_key_hasher).
+    pub fn set_key_hasher(&mut self, hasher: Arc<dyn kbucket::KeyHasher>) -> &mut Self {
+        self.query_config.key_hasher = hasher;
+        self
+    }
+
     /// Sets the TTL for stored records.
     ///
     /// The TTL should be significantly longer than the (re-)publication
@@ -346,6 +582,93 @@ impl KademliaConfig {
         self
     }
 
+    /// Sets the [`RecordValidator`] checking the records in the local store
+    /// on every run of the record repair job.
+    ///
+    /// Records it rejects are removed from the local store and reported via
+    /// [`KademliaEvent::InvalidRecordRemoved`]. Defaults to
+    /// [`AcceptAllValidator`].
+    ///
+    /// See [`KademliaConfig::set_record_repair_interval`].
+    pub fn set_record_validator(&mut self, validator: Arc<dyn RecordValidator>) -> &mut Self {
+        self.record_validator = validator;
+        self
+    }
+
+    /// Sets the interval of the record repair job.
+    ///
+    /// On every run, the job samples stored records, see
+    /// [`KademliaConfig::set_record_repair_sample_size`], and re-validates them
+    /// with the configured [`RecordValidator`], removing the invalid ones. For
+    /// each valid record, the closest peers to its key are asked for the record.
+    /// If fewer of them than the replication factor of the record hold it,
+    /// the record is stored at those that don't, with the outcome reported
+    /// in a [`QueryResult::RepairRecord`]. The repair succeeds once at least
+    /// one of them stored the record, i.e. with [`Quorum::One`].
+    ///
+    /// This keeps long-lived records healthy under churn, complementing the
+    /// regular replication, see [`KademliaConfig::set_replication_interval`].
+    ///
+    /// `None` means that the job is disabled, which is the default.
+    pub fn set_record_repair_interval(&mut self, interval: Option<Duration>) -> &mut Self {
+        self.record_repair_interval = interval;
+        self
+    }
+
+    /// Sets the maximum number of records checked on a single run of the
+    /// record repair job.
+    ///
+    /// The default is 16.
+    ///
+    /// See [`KademliaConfig::set_record_repair_interval`].
+    pub fn set_record_repair_sample_size(&mut self, sample_size: NonZeroUsize) -> &mut Self {
+        self.record_repair_sample_size = sample_size;
+        self
+    }
+
+    /// Sets the number of distinct peers that must report each of the peers
+    /// returned by [`Kademlia::get_closest_peers`].
+    ///
+    /// A result containing a peer reported by fewer peers is flagged via
+    /// [`GetClosestPeersOk::low_confidence`].
+    ///
+    /// `None` means that results are not checked, which is the default.
+    pub fn set_closest_peers_quorum(&mut self, quorum: Option<NonZeroUsize>) -> &mut Self {
+        self.closest_peers_quorum = quorum;
+        self
+    }
+
+    /// Sets the rate limit on the inbound requests of each peer.
+    ///
+    /// Applies to all requests answered in [`Mode::Server`], i.e. `FIND_NODE`, `GET_VALUE`,
+    /// `PUT_VALUE`, `GET_PROVIDERS` and `ADD_PROVIDER`. Requests exceeding the limit are handled
+    /// according to [`KademliaConfig::set_inbound_throttling`] and counted in
+    /// [`Kademlia::inbound_throttle_stats`].
+    ///
+    /// The default is `None`, i.e. no limit.
+    pub fn set_inbound_rate_limit(&mut self, limit: Option<InboundRateLimit>) -> &mut Self {
+        self.inbound_rate_limit = limit;
+        self
+    }
+
+    /// Sets the rate limit on the inbound requests of all peers together.
+    ///
+    /// Applies in addition to, and in the same manner as, [`KademliaConfig::set_inbound_rate_limit`].
+    ///
+    /// The default is `None`, i.e. no limit.
+    pub fn set_global_inbound_rate_limit(&mut self, limit: Option<InboundRateLimit>) -> &mut Self {
+        self.global_inbound_rate_limit = limit;
+        self
+    }
+
+    /// Sets how inbound requests exceeding the rate limits are handled.
+    ///
+    /// The default is [`KademliaInboundThrottling::Drop`].
+    pub fn set_inbound_throttling(&mut self, throttling: KademliaInboundThrottling) -> &mut Self {
+        self.inbound_throttling = throttling;
+        self
+    }
+
     /// Sets the TTL for provider records.
     ///
     /// `None` means that stored provider records never expire.
@@ -389,6 +712,74 @@ impl KademliaConfig {
         self
     }
 
+    /// Sets the confirmation required of a connected peer supporting the Kademlia protocol
+    /// before it is newly inserted into the routing table.
+    ///
+    /// With [`KademliaProtocolConfirmation::Advertised`], the behaviour relies on another
+    /// behaviour, typically `libp2p-identify`, to report the protocols of connected peers.
+    /// Peers already in the routing table are updated regardless.
+    ///
+    /// The default is [`KademliaProtocolConfirmation::Negotiated`].
+    pub fn set_protocol_confirmation(
+        &mut self,
+        confirmation: KademliaProtocolConfirmation,
+    ) -> &mut Self {
+        self.protocol_confirmation = confirmation;
+        self
+    }
+
+    /// Sets whether newly connected peers are put on probation before being inserted into the
+    /// routing table.
+    ///
+    /// A peer on probation is sent a `FIND_NODE` request and only inserted once it answers,
+    /// keeping peers that accept connections but never answer requests out of the routing table.
+    /// Peers failing to answer are not inserted until they connect again. Peers already in the
+    /// routing table are updated regardless. Only applies with
+    /// [`KademliaBucketInserts::OnConnected`].
+    ///
+    /// The default is `false`.
+    pub fn set_peer_probation(&mut self, enabled: bool) -> &mut Self {
+        self.peer_probation = enabled;
+        self
+    }
+
+    /// Sets the maximum number of peers in the routing table whose addresses share an IP prefix,
+    /// i.e. the same /24 IPv4 or /48 IPv6 network.
+    ///
+    /// A peer is not added to the routing table, and an address not added to a peer, if that
+    /// would exceed the limit. Should a pending peer exceed it when replacing a disconnected one,
+    /// it is evicted again. This limits the share of the routing table an attacker can fill with
+    /// peers from a few networks, making eclipse attacks more expensive. Addresses without an IP,
+    /// e.g. DNS addresses, are not limited.
+    ///
+    /// The default is `None`, i.e. no limit.
+    pub fn set_max_peers_per_ip_prefix(&mut self, max: Option<usize>) -> &mut Self {
+        self.ip_diversity.max_peers = max;
+        self
+    }
+
+    /// Sets the maximum number of peers in a single k-bucket whose addresses share an IP prefix.
+    ///
+    /// Applies in addition to [`KademliaConfig::set_max_peers_per_ip_prefix`] and in the same
+    /// manner, ensuring that the peers of each bucket, in particular those closest to the local
+    /// node, come from different networks.
+    ///
+    /// The default is `None`, i.e. no limit.
+    pub fn set_max_bucket_peers_per_ip_prefix(&mut self, max: Option<usize>) -> &mut Self {
+        self.ip_diversity.max_bucket_peers = max;
+        self
+    }
+
+    /// Sets the [`Clock`] driving the periodic jobs and the expiry of records.
+    ///
+    /// The default is the [`SystemClock`]. A [`libp2p_swarm::ManualClock`] allows
+    /// tests to advance time instantly, e.g. to trigger republishing.
+    pub fn set_clock(&mut self, clock: impl Clock) -> &mut Self {
+        self.clock = Arc::new(clock);
+        self.protocol_config.set_clock(self.clock.clone());
+        self
+    }
+
     /// Sets the [`KademliaCaching`] strategy to use for successful lookups.
     ///
     /// The default is [`KademliaCaching::Enabled`] with a `max_peers` of 1.
@@ -399,6 +790,57 @@ impl KademliaConfig {
         self.caching = c;
         self
     }
+
+    /// Sets whether the local listen addresses are advertised alongside the
+    /// external addresses of the local node.
+    ///
+    /// By default, only the confirmed external addresses of the swarm, as reported
+    /// via [`FromSwarm::NewExternalAddr`], are included when this node returns
+    /// itself as a provider. Listen addresses are often private or otherwise
+    /// unreachable for remote peers and thus pollute the DHT. Enabling this
+    /// restores advertising them, e.g. for nodes on a local network without
+    /// external addresses.
+    pub fn set_advertise_listen_addresses(&mut self, enabled: bool) -> &mut Self {
+        self.advertise_listen_addresses = enabled;
+        self
+    }
+
+    /// Sets how queries started while the behaviour is paused are handled.
+    ///
+    /// See [`Kademlia::pause`]. The default is [`KademliaPausedQueries::Queue`].
+    pub fn set_paused_queries(&mut self, paused_queries: KademliaPausedQueries) -> &mut Self {
+        self.paused_queries = paused_queries;
+        self
+    }
+
+    /// Sets the replication factor and the default quorum of the records whose key starts with
+    /// `prefix`, e.g. `/pk/`.
+    ///
+    /// The records are stored at, and looked up among, the `replication_factor` closest nodes to
+    /// their key instead of the replication factor set via
+    /// [`KademliaConfig::set_replication_factor`]. The [`Quorum`] given to
+    /// [`Kademlia::put_record`] is understood w.r.t. this replication factor. The `quorum` applies
+    /// when the records are re-published and replicated by the node, instead of [`Quorum::All`].
+    ///
+    /// The namespace of a record is determined whenever it is put, looked up, re-published or
+    /// replicated. If several prefixes match the key of a record, the longest one applies.
+    /// Setting a namespace for a prefix already configured replaces it.
+    pub fn set_record_namespace(
+        &mut self,
+        prefix: impl Into<Vec<u8>>,
+        replication_factor: NonZeroUsize,
+        quorum: Quorum,
+    ) -> &mut Self {
+        let namespace = RecordNamespace {
+            prefix: prefix.into(),
+            replication_factor,
+            quorum,
+        };
+        self.record_namespaces
+            .retain(|ns| ns.prefix != namespace.prefix);
+        self.record_namespaces.push(namespace);
+        self
+    }
 }
 
 impl<TStore> Kademlia<TStore>
@@ -417,7 +859,7 @@ where
 
     /// Creates a new `Kademlia` network behaviour with the given configuration.
     pub fn with_config(id: PeerId, store: TStore, config: KademliaConfig) -> Self {
-        let local_key = kbucket::Key::from(id);
+        let local_key = kbucket::Key::peer_with_hasher(id, &*config.query_config.key_hasher);
 
         let put_record_job = config
             .record_replication_interval
@@ -428,34 +870,138 @@ where
                     interval,
                     config.record_publication_interval,
                     config.record_ttl,
+                    config.clock.clone(),
                 )
             });
 
         let add_provider_job = config
             .provider_publication_interval
-            .map(AddProviderJob::new);
+            .map(|interval| AddProviderJob::new(interval, config.clock.clone()));
+
+        let record_repair_job = config.record_repair_interval.map(|interval| {
+            RecordRepairJob::new(
+                interval,
+                config.record_repair_sample_size.get(),
+                config.clock.clone(),
+            )
+        });
 
         Kademlia {
             store,
             caching: config.caching,
             kbuckets: KBucketsTable::new(local_key, config.kbucket_pending_timeout),
             kbucket_inserts: config.kbucket_inserts,
+            protocol_confirmation: config.protocol_confirmation,
+            advertising_peers: Default::default(),
+            unadvertised_peers: Default::default(),
+            peer_probation: config.peer_probation,
+            probation: Default::default(),
+            verified_peers: Default::default(),
             protocol_config: config.protocol_config,
             record_filtering: config.record_filtering,
             queued_events: VecDeque::with_capacity(config.query_config.replication_factor.get()),
             listen_addresses: Default::default(),
             queries: QueryPool::new(config.query_config),
             connected_peers: Default::default(),
+            connections: Default::default(),
             add_provider_job,
             put_record_job,
+            record_repair_job,
+            record_validator: config.record_validator,
             record_ttl: config.record_ttl,
             provider_record_ttl: config.provider_record_ttl,
             connection_idle_timeout: config.connection_idle_timeout,
             external_addresses: Default::default(),
+            advertise_listen_addresses: config.advertise_listen_addresses,
+            paused: false,
+            paused_queries: config.paused_queries,
+            record_namespaces: config.record_namespaces,
+            ip_diversity: config.ip_diversity,
+            closest_peers_quorum: config.closest_peers_quorum,
+            inbound_rate_limiter: InboundRateLimiter::new(
+                config.inbound_rate_limit,
+                config.global_inbound_rate_limit,
+            ),
+            inbound_throttling: config.inbound_throttling,
+            clock: config.clock,
             local_peer_id: id,
+            routing: None,
+            delegated_lookups: Default::default(),
+            routing_queries: Default::default(),
+            routing_events: Default::default(),
+            routing_waker: None,
+            waker: None,
+            proximity_keys: Default::default(),
+            proximity_dirty: false,
+            find_and_dials: Default::default(),
+            mode: Mode::Server,
+        }
+    }
+
+    /// Sets the [`Mode`] this behaviour operates in.
+    ///
+    /// In [`Mode::Client`], inbound Kademlia requests are refused on all connections, including
+    /// the currently established ones. Outbound requests are unaffected.
+    ///
+    /// Defaults to [`Mode::Server`].
+    pub fn set_mode(&mut self, mode: Mode) {
+        if self.mode == mode {
+            return;
+        }
+
+        log::debug!("Switching to Kademlia {mode} mode");
+        self.mode = mode;
+        for (connection_id, peer_id) in self.connections.iter() {
+            self.queued_events.push_back(ToSwarm::NotifyHandler {
+                peer_id: *peer_id,
+                handler: NotifyHandler::One(*connection_id),
+                event: KademliaHandlerIn::SetAllowListening(mode == Mode::Server),
+            });
         }
     }
 
+    /// Returns the counters of the inbound requests exceeding the rate limits, see
+    /// [`KademliaConfig::set_inbound_rate_limit`].
+    pub fn inbound_throttle_stats(&self) -> InboundThrottleStats {
+        self.inbound_rate_limiter.stats()
+    }
+
+    /// Returns the [`Mode`] this behaviour currently operates in.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Pauses the behaviour, e.g. to quiesce DHT traffic while running in the background.
+    ///
+    /// While paused, the periodic record and provider (re-)publication jobs are suspended and
+    /// no queries make progress. Queries started in the meantime are handled according to
+    /// [`KademliaConfig::set_paused_queries`]. Queries that were already running resume with
+    /// [`Kademlia::resume`], but may time out if paused for longer than the query timeout.
+    ///
+    /// Inbound requests are still answered, see [`Kademlia::set_mode`] to refuse them.
+    pub fn pause(&mut self) {
+        if !self.paused {
+            log::debug!("Pausing Kademlia");
+            self.paused = true;
+        }
+    }
+
+    /// Resumes the behaviour after [`Kademlia::pause`].
+    pub fn resume(&mut self) {
+        if self.paused {
+            log::debug!("Resuming Kademlia");
+            self.paused = false;
+            if let Some(waker) = self.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Returns whether the behaviour is paused, see [`Kademlia::pause`].
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
     /// Gets an iterator over immutable references to all running queries.
     pub fn iter_queries(&self) -> impl Iterator<Item = QueryRef<'_>> {
         self.queries.iter().filter_map(|query| {
@@ -518,7 +1064,10 @@ where
     /// If the routing table has been updated as a result of this operation,
     /// a [`KademliaEvent::RoutingUpdated`] event is emitted.
     pub fn add_address(&mut self, peer: &PeerId, address: Multiaddr) -> RoutingUpdate {
-        let key = kbucket::Key::from(*peer);
+        let key = self.peer_key(*peer);
+        if self.exceeds_ip_diversity(&key, &address) {
+            return RoutingUpdate::Failed;
+        }
         match self.kbuckets.entry(&key) {
             kbucket::Entry::Present(mut entry, _) => {
                 if entry.value().insert(address) {
@@ -551,6 +1100,7 @@ where
                 };
                 match entry.insert(addresses.clone(), status) {
                     kbucket::InsertResult::Inserted => {
+                        self.proximity_dirty = true;
                         self.queued_events.push_back(ToSwarm::GenerateEvent(
                             KademliaEvent::RoutingUpdated {
                                 peer: *peer,
@@ -597,10 +1147,11 @@ where
         peer: &PeerId,
         address: &Multiaddr,
     ) -> Option<kbucket::EntryView<kbucket::Key<PeerId>, Addresses>> {
-        let key = kbucket::Key::from(*peer);
+        let key = self.peer_key(*peer);
         match self.kbuckets.entry(&key) {
             kbucket::Entry::Present(mut entry, _) => {
                 if entry.value().remove(address).is_err() {
+                    self.proximity_dirty = true;
                     Some(entry.remove()) // it is the last address, thus remove the peer.
                 } else {
                     None
@@ -625,14 +1176,31 @@ where
         &mut self,
         peer: &PeerId,
     ) -> Option<kbucket::EntryView<kbucket::Key<PeerId>, Addresses>> {
-        let key = kbucket::Key::from(*peer);
+        self.queries.remove_latency(peer);
+        let key = self.peer_key(*peer);
         match self.kbuckets.entry(&key) {
-            kbucket::Entry::Present(entry, _) => Some(entry.remove()),
+            kbucket::Entry::Present(entry, _) => {
+                self.proximity_dirty = true;
+                Some(entry.remove())
+            }
             kbucket::Entry::Pending(entry, _) => Some(entry.remove()),
             kbucket::Entry::Absent(..) | kbucket::Entry::SelfEntry => None,
         }
     }
 
+    /// Records a round-trip time sample of the given peer, e.g. measured by the ping protocol.
+    ///
+    /// The latency of a peer is forgotten when it is removed from the routing table
+    /// or after 30 minutes without new samples. See [`KademliaConfig::set_latency_weighting`].
+    pub fn record_peer_latency(&mut self, peer: PeerId, rtt: Duration) {
+        self.queries.record_latency(peer, rtt, self.clock.now());
+    }
+
+    /// Returns the smoothed round-trip time of the given peer, if known.
+    pub fn peer_latency(&self, peer: &PeerId) -> Option<Duration> {
+        self.queries.latency(peer, self.clock.now())
+    }
+
     /// Returns an iterator over all non-empty buckets in the routing table.
     pub fn kbuckets(
         &mut self,
@@ -643,6 +1211,9 @@ where
     /// Returns the k-bucket for the distance to the given key.
     ///
     /// Returns `None` if the given key refers to the local key.
+    ///
+    /// Note that `key` is converted with the default SHA-256 hashing, regardless
+    /// of [`KademliaConfig::set_key_hasher`].
     pub fn kbucket<K>(
         &mut self,
         key: K,
@@ -661,8 +1232,8 @@ where
     where
         K: Into<kbucket::Key<K>> + Into<Vec<u8>> + Clone,
     {
-        let target: kbucket::Key<K> = key.clone().into();
         let key: Vec<u8> = key.into();
+        let target = self.record_key(key.clone());
         let info = QueryInfo::GetClosestPeers {
             key,
             step: ProgressStep::first(),
@@ -672,6 +1243,38 @@ where
         self.queries.add_iter_closest(target, peer_keys, inner)
     }
 
+    /// Looks up the addresses of the given peer in the DHT and dials it.
+    ///
+    /// Performs a lookup of the peers closest to `peer`, like [`Kademlia::get_closest_peers`],
+    /// and dials `peer` at the addresses reported for it during the lookup as well as those in
+    /// the routing table. The outcome of both the lookup and the dial is reported by a single
+    /// [`QueryResult::FindAndDial`]. The peer is not dialed if it is connected by the time the
+    /// lookup finishes, e.g. because it was contacted during the lookup.
+    pub fn find_and_dial(&mut self, peer: PeerId) -> QueryId {
+        let target = self.peer_key(peer);
+        let info = QueryInfo::FindAndDial { peer };
+        let peer_keys: Vec<kbucket::Key<PeerId>> = self.kbuckets.closest_keys(&target).collect();
+        let inner = QueryInner::new(info);
+        self.queries.add_iter_closest(target, peer_keys, inner)
+    }
+
+    /// Returns the hasher mapping peer IDs and record keys into the DHT keyspace.
+    ///
+    /// See [`KademliaConfig::set_key_hasher`].
+    pub fn key_hasher(&self) -> &Arc<dyn kbucket::KeyHasher> {
+        &self.queries.config().key_hasher
+    }
+
+    /// Returns the XOR distance between a key and a peer in the DHT keyspace
+    /// of the configured [`kbucket::KeyHasher`].
+    ///
+    /// Can be used to rank the peers returned by a query, e.g. in
+    /// [`GetClosestPeersOk`] or [`GetProvidersOk`], by their distance to the
+    /// queried key.
+    pub fn distance(&self, key: &[u8], peer: &PeerId) -> Distance {
+        self.record_key(key).distance(&self.peer_key(*peer))
+    }
+
     /// Returns closest peers to the given key; takes peers from local routing table only.
     pub fn get_closest_local_peers<'a, K: Clone>(
         &'a mut self,
@@ -686,7 +1289,7 @@ where
     /// [`KademliaEvent::OutboundQueryCompleted{QueryResult::GetRecord}`].
     pub fn get_record(&mut self, key: record::Key) -> QueryId {
         let record = if let Some(record) = self.store.get(&key) {
-            if record.is_expired(Instant::now()) {
+            if record.is_expired(self.clock.now()) {
                 self.store.remove(&key);
                 None
             } else {
@@ -701,7 +1304,7 @@ where
 
         let step = ProgressStep::first();
 
-        let target = kbucket::Key::new(key.clone());
+        let target = self.record_key(key.clone());
         let info = if record.is_some() {
             QueryInfo::GetRecord {
                 key,
@@ -717,9 +1320,15 @@ where
                 cache_candidates: BTreeMap::new(),
             }
         };
+        let (replication_factor, _) = self.record_replication(target.preimage());
         let peers = self.kbuckets.closest_keys(&target);
         let inner = QueryInner::new(info);
-        let id = self.queries.add_iter_closest(target.clone(), peers, inner);
+        let id = self.queries.add_iter_closest_with_num_results(
+            target.clone(),
+            peers,
+            replication_factor,
+            inner,
+        );
 
         // No queries were actually done for the results yet.
         let stats = QueryStats::empty();
@@ -756,18 +1365,27 @@ where
     /// does not update the record's expiration in local storage, thus a given record
     /// with an explicit expiration will always expire at that instant and until then
     /// is subject to regular (re-)replication and (re-)publication.
+    ///
+    /// The given [`Quorum`] is understood w.r.t. the replication factor of the namespace of
+    /// the record, see [`KademliaConfig::set_record_namespace`]. Returns
+    /// [`store::Error::QuorumTooLarge`] if a [`Quorum::N`] exceeds that replication factor,
+    /// without storing the record.
     pub fn put_record(
         &mut self,
         mut record: Record,
         quorum: Quorum,
     ) -> Result<QueryId, store::Error> {
+        let (replication_factor, _) = self.record_replication(&record.key);
+        if matches!(quorum, Quorum::N(n) if n > replication_factor) {
+            return Err(store::Error::QuorumTooLarge);
+        }
         record.publisher = Some(*self.kbuckets.local_key().preimage());
         self.store.put(record.clone())?;
         record.expires = record
             .expires
-            .or_else(|| self.record_ttl.map(|ttl| Instant::now() + ttl));
-        let quorum = quorum.eval(self.queries.config().replication_factor);
-        let target = kbucket::Key::new(record.key.clone());
+            .or_else(|| self.record_ttl.map(|ttl| self.clock.now() + ttl));
+        let quorum = quorum.eval(replication_factor);
+        let target = self.record_key(record.key.clone());
         let peers = self.kbuckets.closest_keys(&target);
         let context = PutRecordContext::Publish;
         let info = QueryInfo::PutRecord {
@@ -777,7 +1395,12 @@ where
             phase: PutRecordPhase::GetClosestPeers,
         };
         let inner = QueryInner::new(info);
-        Ok(self.queries.add_iter_closest(target.clone(), peers, inner))
+        Ok(self.queries.add_iter_closest_with_num_results(
+            target.clone(),
+            peers,
+            replication_factor,
+            inner,
+        ))
     }
 
     /// Stores a record at specific peers, without storing it locally.
@@ -811,7 +1434,7 @@ where
         };
         record.expires = record
             .expires
-            .or_else(|| self.record_ttl.map(|ttl| Instant::now() + ttl));
+            .or_else(|| self.record_ttl.map(|ttl| self.clock.now() + ttl));
         let context = PutRecordContext::Custom;
         let info = QueryInfo::PutRecord {
             context,
@@ -826,6 +1449,54 @@ where
         self.queries.add_fixed(peers, inner)
     }
 
+    /// Stores a record at exactly the given peers, bypassing the iterative
+    /// lookup of the closest peers to the record key.
+    ///
+    /// Unlike [`Kademlia::put_record_to`], there is no quorum: the query
+    /// finishes once every peer responded or failed, and the outcome is
+    /// reported per peer in a [`QueryResult::PutRecordAt`]. This is useful
+    /// e.g. for repairing the replication of a record or for testing a
+    /// specific server implementation.
+    ///
+    /// If the record's expiration is `None`, the configured record TTL is used.
+    /// The record is not stored locally.
+    pub fn put_record_at<I>(&mut self, mut record: Record, peers: I) -> QueryId
+    where
+        I: IntoIterator<Item = PeerId>,
+    {
+        record.expires = record
+            .expires
+            .or_else(|| self.record_ttl.map(|ttl| self.clock.now() + ttl));
+        let peers = dedup_peers(peers);
+        let info = QueryInfo::PutRecordAt {
+            record,
+            peers: peers.clone(),
+            success: Vec::new(),
+        };
+        let inner = QueryInner::new(info);
+        self.queries.add_fixed(peers, inner)
+    }
+
+    /// Retrieves the record with the given key from exactly the given peers,
+    /// bypassing the iterative lookup and the local record store.
+    ///
+    /// The query finishes once every peer responded or failed, and the outcome
+    /// is reported per peer in a [`QueryResult::GetRecordFrom`].
+    pub fn get_record_from<I>(&mut self, key: record::Key, peers: I) -> QueryId
+    where
+        I: IntoIterator<Item = PeerId>,
+    {
+        let peers = dedup_peers(peers);
+        let info = QueryInfo::GetRecordFrom {
+            key,
+            peers: peers.clone(),
+            records: Vec::new(),
+            not_found: Vec::new(),
+        };
+        let inner = QueryInner::new(info);
+        self.queries.add_fixed(peers, inner)
+    }
+
     /// Removes the record with the given key from _local_ storage,
     /// if the local node is the publisher of the record.
     ///
@@ -906,6 +1577,25 @@ where
     /// The results of the (repeated) provider announcements sent by this node are
     /// reported via [`KademliaEvent::OutboundQueryCompleted{QueryResult::StartProviding}`].
     pub fn start_providing(&mut self, key: record::Key) -> Result<QueryId, store::Error> {
+        self.start_providing_with_metadata(key, Vec::new())
+    }
+
+    /// Establishes the local node as a provider of a value for the given key, announcing the
+    /// given opaque metadata along with the provider record, see [`ProviderRecord::metadata`].
+    ///
+    /// Returns [`store::Error::ValueTooLarge`] if the metadata exceeds
+    /// [`MAX_PROVIDER_METADATA_SIZE`](crate::MAX_PROVIDER_METADATA_SIZE) bytes.
+    ///
+    /// See [`Kademlia::start_providing`].
+    pub fn start_providing_with_metadata(
+        &mut self,
+        key: record::Key,
+        metadata: Vec<u8>,
+    ) -> Result<QueryId, store::Error> {
+        if metadata.len() > MAX_PROVIDER_METADATA_SIZE {
+            return Err(store::Error::ValueTooLarge);
+        }
+
         // Note: We store our own provider records locally without local addresses
         // to avoid redundant storage and outdated addresses. Instead these are
         // acquired on demand when returning a `ProviderRecord` for the local node.
@@ -914,9 +1604,10 @@ where
             key.clone(),
             *self.kbuckets.local_key().preimage(),
             local_addrs,
-        );
+        )
+        .with_metadata(metadata);
         self.store.add_provider(record)?;
-        let target = kbucket::Key::new(key.clone());
+        let target = self.record_key(key.clone());
         let peers = self.kbuckets.closest_keys(&target);
         let context = AddProviderContext::Publish;
         let info = QueryInfo::AddProvider {
@@ -938,18 +1629,41 @@ where
             .remove_provider(key, self.kbuckets.local_key().preimage());
     }
 
+    /// Sets a router to consult in addition to the DHT for provider lookups.
+    ///
+    /// Every subsequent [`Kademlia::get_providers`] is delegated to `routing` as well.
+    /// Providers found by `routing` are reported as part of the same query, i.e. through
+    /// [`KademliaEvent::OutboundQueryProgressed`] with [`GetProvidersOk::FoundProviders`],
+    /// and the final step of the query is only reported once `routing` finished the lookup.
+    ///
+    /// Use [`Routers`](crate::Routers) to consult multiple routers.
+    pub fn set_routing(&mut self, routing: impl Routing + Send + 'static) {
+        self.routing = Some(Box::new(routing));
+    }
+
     /// Performs a lookup for providers of a value to the given key.
     ///
     /// The result of this operation is delivered in a
     /// reported via [`KademliaEvent::OutboundQueryCompleted{QueryResult::GetProviders}`].
+    ///
+    /// If a router has been set via [`Kademlia::set_routing`], the lookup is
+    /// delegated to it as well.
     pub fn get_providers(&mut self, key: record::Key) -> QueryId {
-        let providers: HashSet<_> = self
-            .store
-            .providers(&key)
-            .into_iter()
-            .filter(|p| !p.is_expired(Instant::now()))
-            .map(|p| p.provider)
-            .collect();
+        let mut providers = HashSet::new();
+        let mut addresses = HashMap::new();
+        let mut metadata = HashMap::new();
+        for record in self.store.providers(&key) {
+            if record.is_expired(self.clock.now()) {
+                continue;
+            }
+            if !record.addresses.is_empty() {
+                addresses.insert(record.provider, record.addresses);
+            }
+            if !record.metadata.is_empty() {
+                metadata.insert(record.provider, record.metadata);
+            }
+            providers.insert(record.provider);
+        }
 
         let step = ProgressStep::first();
 
@@ -963,11 +1677,24 @@ where
             },
         };
 
-        let target = kbucket::Key::new(key.clone());
+        let target = self.record_key(key.clone());
         let peers = self.kbuckets.closest_keys(&target);
         let inner = QueryInner::new(info);
         let id = self.queries.add_iter_closest(target.clone(), peers, inner);
 
+        if let Some(routing) = self.routing.as_mut() {
+            let lookup = LookupId::next();
+            routing.find_providers(lookup, &key);
+            self.delegated_lookups.insert(
+                lookup,
+                DelegatedLookup {
+                    query: id,
+                    key: key.clone(),
+                    outcome: None,
+                },
+            );
+        }
+
         // No queries were actually done for the results yet.
         let stats = QueryStats::empty();
 
@@ -978,6 +1705,8 @@ where
                     result: QueryResult::GetProviders(Ok(GetProvidersOk::FoundProviders {
                         key,
                         providers,
+                        addresses,
+                        metadata,
                     })),
                     step,
                     stats,
@@ -1005,6 +1734,16 @@ where
                 );
                 let addrs = peer.multiaddrs.iter().cloned().collect();
                 query.inner.addresses.insert(peer.node_id, addrs);
+                if self.closest_peers_quorum.is_some()
+                    && matches!(query.inner.info, QueryInfo::GetClosestPeers { .. })
+                {
+                    query
+                        .inner
+                        .reporters
+                        .entry(peer.node_id)
+                        .or_default()
+                        .insert(*source);
+                }
             }
             query.on_success(source, others_iter.cloned().map(|kp| kp.node_id))
         }
@@ -1030,20 +1769,119 @@ where
         }
     }
 
-    /// Collects all peers who are known to be providers of the value for a given `Multihash`.
-    fn provider_peers(&mut self, key: &record::Key, source: &PeerId) -> Vec<KadPeer> {
-        let kbuckets = &mut self.kbuckets;
-        let connected = &mut self.connected_peers;
-        let listen_addresses = &self.listen_addresses;
-        let external_addresses = &self.external_addresses;
+    /// Maps a peer ID into the DHT keyspace with the configured [`kbucket::KeyHasher`].
+    fn peer_key(&self, peer: PeerId) -> kbucket::Key<PeerId> {
+        kbucket::Key::peer_with_hasher(peer, &*self.queries.config().key_hasher)
+    }
 
-        self.store
+    /// Maps a record key into the DHT keyspace with the configured [`kbucket::KeyHasher`].
+    fn record_key<T: Borrow<[u8]>>(&self, key: T) -> kbucket::Key<T> {
+        kbucket::Key::with_hasher(key, &*self.queries.config().key_hasher)
+    }
+
+    /// Dials the target of a [`Kademlia::find_and_dial`] query whose lookup finished or timed
+    /// out, returning the result right away if the peer is connected or no addresses are known.
+    fn find_and_dial_lookup_finished(
+        &mut self,
+        query_id: QueryId,
+        stats: QueryStats,
+        peer: PeerId,
+        mut discovered: FnvHashMap<PeerId, SmallVec<[Multiaddr; 8]>>,
+        timed_out: bool,
+    ) -> Option<KademliaEvent> {
+        let mut addresses = discovered
+            .remove(&peer)
+            .map(SmallVec::into_vec)
+            .unwrap_or_default();
+        let key = self.peer_key(peer);
+        if let kbucket::Entry::Present(mut entry, _) = self.kbuckets.entry(&key) {
+            for address in entry.value().iter() {
+                if !addresses.contains(address) {
+                    addresses.push(address.clone());
+                }
+            }
+        }
+
+        let result = if self.connected_peers.contains(&peer) {
+            Ok(FindAndDialOk { peer, addresses })
+        } else if addresses.is_empty() && timed_out {
+            Err(FindAndDialError::Timeout { peer })
+        } else if addresses.is_empty() {
+            Err(FindAndDialError::NotFound { peer })
+        } else {
+            let opts = DialOpts::peer_id(peer)
+                .condition(dial_opts::PeerCondition::Disconnected)
+                .addresses(addresses.clone())
+                .build();
+            self.find_and_dials.insert(
+                opts.connection_id(),
+                FindAndDial {
+                    query: query_id,
+                    stats,
+                    peer,
+                    addresses,
+                },
+            );
+            self.queued_events.push_back(ToSwarm::Dial { opts });
+            return None;
+        };
+
+        Some(KademliaEvent::OutboundQueryProgressed {
+            id: query_id,
+            stats,
+            result: QueryResult::FindAndDial(result),
+            step: ProgressStep::first_and_last(),
+        })
+    }
+
+    /// Reports the outcome of the dial of a [`Kademlia::find_and_dial`] query, if the connection
+    /// belongs to one.
+    fn find_and_dial_finished(&mut self, connection_id: ConnectionId) {
+        let FindAndDial {
+            query,
+            stats,
+            peer,
+            addresses,
+        } = match self.find_and_dials.remove(&connection_id) {
+            Some(dial) => dial,
+            None => return,
+        };
+
+        // The dial may have been refused since the peer got connected in the meantime.
+        let result = if self.connected_peers.contains(&peer) {
+            Ok(FindAndDialOk { peer, addresses })
+        } else {
+            Err(FindAndDialError::DialFailed { peer, addresses })
+        };
+
+        self.queued_events.push_back(ToSwarm::GenerateEvent(
+            KademliaEvent::OutboundQueryProgressed {
+                id: query,
+                stats,
+                result: QueryResult::FindAndDial(result),
+                step: ProgressStep::first_and_last(),
+            },
+        ));
+    }
+
+    /// Collects all peers who are known to be providers of the value for a given `Multihash`.
+    fn provider_peers(&mut self, key: &record::Key, source: &PeerId) -> Vec<KadPeer> {
+        let kbuckets = &mut self.kbuckets;
+        let connected = &mut self.connected_peers;
+        let listen_addresses = self
+            .advertise_listen_addresses
+            .then_some(&self.listen_addresses);
+        let external_addresses = &self.external_addresses;
+        let key_hasher = &*self.queries.config().key_hasher;
+
+        self.store
             .providers(key)
             .into_iter()
             .filter_map(move |p| {
                 if &p.provider != source {
                     let node_id = p.provider;
                     let multiaddrs = p.addresses;
+                    let metadata = p.metadata;
                     let connection_ty = if connected.contains(&node_id) {
                         KadConnectionType::Connected
                     } else {
@@ -1059,25 +1897,41 @@ where
                         if &node_id == kbuckets.local_key().preimage() {
                             Some(
                                 listen_addresses
-                                    .iter()
+                                    .into_iter()
+                                    .flat_map(|a| a.iter())
                                     .chain(external_addresses.iter())
                                     .cloned()
                                     .collect::<Vec<_>>(),
                             )
                         } else {
-                            let key = kbucket::Key::from(node_id);
+                            let key = kbucket::Key::peer_with_hasher(node_id, key_hasher);
                             kbuckets
                                 .entry(&key)
                                 .view()
                                 .map(|e| e.node.value.clone().into_vec())
                         }
                     } else {
-                        Some(multiaddrs)
+                        // Prefer the addresses from the routing table, which are
+                        // refreshed e.g. via identify, over the ones announced
+                        // with the provider record.
+                        let key = kbucket::Key::peer_with_hasher(node_id, key_hasher);
+                        let mut addrs = kbuckets
+                            .entry(&key)
+                            .view()
+                            .map(|e| e.node.value.clone().into_vec())
+                            .unwrap_or_default();
+                        for addr in multiaddrs {
+                            if !addrs.contains(&addr) {
+                                addrs.push(addr);
+                            }
+                        }
+                        Some(addrs)
                     }
                     .map(|multiaddrs| KadPeer {
                         node_id,
                         multiaddrs,
                         connection_ty,
+                        metadata,
                     })
                 } else {
                     None
@@ -1094,16 +1948,18 @@ where
             key: key.clone(),
             phase: AddProviderPhase::GetClosestPeers,
         };
-        let target = kbucket::Key::new(key);
+        let target = self.record_key(key);
         let peers = self.kbuckets.closest_keys(&target);
         let inner = QueryInner::new(info);
         self.queries.add_iter_closest(target.clone(), peers, inner);
     }
 
-    /// Starts an iterative `PUT_VALUE` query for the given record.
-    fn start_put_record(&mut self, record: Record, quorum: Quorum, context: PutRecordContext) {
-        let quorum = quorum.eval(self.queries.config().replication_factor);
-        let target = kbucket::Key::new(record.key.clone());
+    /// Starts an iterative `PUT_VALUE` query for the given record, with the quorum of its
+    /// namespace.
+    fn start_put_record(&mut self, record: Record, context: PutRecordContext) {
+        let (replication_factor, quorum) = self.record_replication(&record.key);
+        let quorum = quorum.eval(replication_factor);
+        let target = self.record_key(record.key.clone());
         let peers = self.kbuckets.closest_keys(&target);
         let info = QueryInfo::PutRecord {
             record,
@@ -1112,7 +1968,45 @@ where
             phase: PutRecordPhase::GetClosestPeers,
         };
         let inner = QueryInner::new(info);
-        self.queries.add_iter_closest(target.clone(), peers, inner);
+        self.queries.add_iter_closest_with_num_results(
+            target.clone(),
+            peers,
+            replication_factor,
+            inner,
+        );
+    }
+
+    /// Starts an iterative `GET_VALUE` query for the given record, checking whether
+    /// it is held by enough of the closest peers to its key.
+    ///
+    /// See [`KademliaConfig::set_record_repair_interval`].
+    fn start_repair_record(&mut self, record: Record) {
+        let (replication_factor, _) = self.record_replication(&record.key);
+        let target = self.record_key(record.key.clone());
+        let peers = self.kbuckets.closest_keys(&target);
+        let info = QueryInfo::RepairRecord {
+            record,
+            holders: Vec::new(),
+        };
+        let inner = QueryInner::new(info);
+        self.queries.add_iter_closest_with_num_results(
+            target.clone(),
+            peers,
+            replication_factor,
+            inner,
+        );
+    }
+
+    /// Returns the replication factor and the default quorum of records with the given key.
+    ///
+    /// See [`KademliaConfig::set_record_namespace`].
+    fn record_replication(&self, key: &record::Key) -> (NonZeroUsize, Quorum) {
+        self.record_namespaces
+            .iter()
+            .filter(|ns| key.as_ref().starts_with(&ns.prefix))
+            .max_by_key(|ns| ns.prefix.len())
+            .map(|ns| (ns.replication_factor, ns.quorum))
+            .unwrap_or((self.queries.config().replication_factor, Quorum::All))
     }
 
     /// Updates the routing table with a new connection status and address of a peer.
@@ -1122,13 +2016,17 @@ where
         address: Option<Multiaddr>,
         new_status: NodeStatus,
     ) {
-        let key = kbucket::Key::from(peer);
+        let key = self.peer_key(peer);
+        let admissible = match &address {
+            Some(address) => !self.exceeds_ip_diversity(&key, address),
+            None => true,
+        };
         match self.kbuckets.entry(&key) {
             kbucket::Entry::Present(mut entry, old_status) => {
                 if old_status != new_status {
                     entry.update(new_status)
                 }
-                if let Some(address) = address {
+                if let Some(address) = address.filter(|_| admissible) {
                     if entry.value().insert(address) {
                         self.queued_events.push_back(ToSwarm::GenerateEvent(
                             KademliaEvent::RoutingUpdated {
@@ -1148,7 +2046,7 @@ where
             }
 
             kbucket::Entry::Pending(mut entry, old_status) => {
-                if let Some(address) = address {
+                if let Some(address) = address.filter(|_| admissible) {
                     entry.value().insert(address);
                 }
                 if old_status != new_status {
@@ -1161,6 +2059,16 @@ where
                 if new_status != NodeStatus::Connected {
                     return;
                 }
+                if self.protocol_confirmation == KademliaProtocolConfirmation::Advertised
+                    && !self.advertising_peers.contains_key(&peer)
+                {
+                    debug!(
+                        "Peer {} not added to routing table until it advertises Kademlia.",
+                        peer
+                    );
+                    self.unadvertised_peers.insert(peer, address);
+                    return;
+                }
                 match (address, self.kbucket_inserts) {
                     (None, _) => {
                         self.queued_events.push_back(ToSwarm::GenerateEvent(
@@ -1172,10 +2080,21 @@ where
                             KademliaEvent::RoutablePeer { peer, address: a },
                         ));
                     }
+                    (Some(a), KademliaBucketInserts::OnConnected) if !admissible => {
+                        self.queued_events.push_back(ToSwarm::GenerateEvent(
+                            KademliaEvent::RoutablePeer { peer, address: a },
+                        ));
+                    }
+                    (Some(a), KademliaBucketInserts::OnConnected)
+                        if self.peer_probation && !self.verified_peers.contains(&peer) =>
+                    {
+                        self.start_probation(peer, a);
+                    }
                     (Some(a), KademliaBucketInserts::OnConnected) => {
                         let addresses = Addresses::new(a);
                         match entry.insert(addresses.clone(), new_status) {
                             kbucket::InsertResult::Inserted => {
+                                self.proximity_dirty = true;
                                 let event = KademliaEvent::RoutingUpdated {
                                     peer,
                                     is_new_peer: true,
@@ -1223,6 +2142,58 @@ where
         }
     }
 
+    /// Returns whether adding the address to the peer exceeds a limit on the peers in the
+    /// routing table sharing an IP prefix.
+    ///
+    /// See [`KademliaConfig::set_max_peers_per_ip_prefix`].
+    fn exceeds_ip_diversity(&mut self, key: &kbucket::Key<PeerId>, address: &Multiaddr) -> bool {
+        match self
+            .ip_diversity
+            .violation(&mut self.kbuckets, key, [address])
+        {
+            Some(prefix) => {
+                debug!(
+                    "Address {} of peer {} not added to routing table: too many peers in {:?}",
+                    address,
+                    key.preimage(),
+                    prefix
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Puts a peer on probation, sending it a request it has to answer before being inserted into
+    /// the routing table.
+    fn start_probation(&mut self, peer: PeerId, address: Multiaddr) {
+        if self.probation.insert(peer, address).is_some() {
+            // The peer is already being probed.
+            return;
+        }
+        debug!("Peer {} on probation until it answers a request.", peer);
+        let inner = QueryInner::new(QueryInfo::Probation { peer });
+        self.queries.add_fixed(std::iter::once(peer), inner);
+    }
+
+    /// Inserts a peer on probation into the routing table if it answered the request.
+    fn probation_finished(&mut self, peer: PeerId, answered: bool) {
+        let address = match self.probation.remove(&peer) {
+            Some(address) => address,
+            // The peer disconnected in the meantime.
+            None => return,
+        };
+        if answered {
+            self.verified_peers.insert(peer);
+            self.connection_updated(peer, Some(address), NodeStatus::Connected);
+        } else {
+            debug!(
+                "Peer {} not added to routing table as it did not answer during probation.",
+                peer
+            );
+        }
+    }
+
     /// Handles a finished (i.e. successful) query.
     fn query_finished(&mut self, q: Query<QueryInner>) -> Option<KademliaEvent> {
         let query_id = q.id();
@@ -1235,6 +2206,7 @@ where
                 mut step,
             } => {
                 let local_key = self.kbuckets.local_key().clone();
+                let key_hasher = self.queries.config().key_hasher.clone();
                 let mut remaining = remaining.unwrap_or_else(|| {
                     debug_assert_eq!(&peer, local_key.preimage());
                     // The lookup for the local key finished. To complete the bootstrap process,
@@ -1259,13 +2231,15 @@ where
                             // Pr(bucket-253) = 1 - (7/8)^16   ~= 0.88
                             // Pr(bucket-252) = 1 - (15/16)^16 ~= 0.64
                             // ...
-                            let mut target = kbucket::Key::from(PeerId::random());
+                            let mut target =
+                                kbucket::Key::peer_with_hasher(PeerId::random(), &*key_hasher);
                             for _ in 0..16 {
                                 let d = local_key.distance(&target);
                                 if b.contains(&d) {
                                     break;
                                 }
-                                target = kbucket::Key::from(PeerId::random());
+                                target =
+                                    kbucket::Key::peer_with_hasher(PeerId::random(), &*key_hasher);
                             }
                             target
                         })
@@ -1303,31 +2277,34 @@ where
             QueryInfo::GetClosestPeers { key, mut step } => {
                 step.last = true;
 
+                let peers: Vec<_> = result.peers.collect();
+                let reporters = result.inner.reporters;
+                let low_confidence = self.closest_peers_quorum.map_or(false, |quorum| {
+                    peers
+                        .iter()
+                        .any(|peer| reporters.get(peer).map_or(0, FnvHashSet::len) < quorum.get())
+                });
+
                 Some(KademliaEvent::OutboundQueryProgressed {
                     id: query_id,
                     stats: result.stats,
                     result: QueryResult::GetClosestPeers(Ok(GetClosestPeersOk {
                         key,
-                        peers: result.peers.collect(),
+                        peers,
+                        low_confidence,
                     })),
                     step,
                 })
             }
 
-            QueryInfo::GetProviders { mut step, .. } => {
-                step.last = true;
-
-                Some(KademliaEvent::OutboundQueryProgressed {
-                    id: query_id,
-                    stats: result.stats,
-                    result: QueryResult::GetProviders(Ok(
-                        GetProvidersOk::FinishedWithNoAdditionalRecord {
-                            closest_peers: result.peers.collect(),
-                        },
-                    )),
-                    step,
-                })
-            }
+            QueryInfo::GetProviders { step, .. } => self.provider_lookup_finished(
+                query_id,
+                result.stats,
+                step,
+                Ok(GetProvidersOk::FinishedWithNoAdditionalRecord {
+                    closest_peers: result.peers.collect(),
+                }),
+            ),
 
             QueryInfo::AddProvider {
                 context,
@@ -1336,12 +2313,20 @@ where
             } => {
                 let provider_id = self.local_peer_id;
                 let external_addresses = self.external_addresses.iter().cloned().collect();
+                let metadata = self
+                    .store
+                    .providers(&key)
+                    .into_iter()
+                    .find(|p| p.provider == provider_id)
+                    .map(|p| p.metadata)
+                    .unwrap_or_default();
                 let inner = QueryInner::new(QueryInfo::AddProvider {
                     context,
                     key,
                     phase: AddProviderPhase::AddProvider {
                         provider_id,
                         external_addresses,
+                        metadata,
                         get_closest_peers_stats: result.stats,
                     },
                 });
@@ -1396,6 +2381,42 @@ where
                 })
             }
 
+            QueryInfo::RepairRecord { record, holders } => {
+                let (replication_factor, _) = self.record_replication(&record.key);
+                if holders.len() >= replication_factor.get() {
+                    debug!("Record sufficiently replicated: {:?}", record.key);
+                    return None;
+                }
+
+                let missing = result
+                    .peers
+                    .filter(|peer| !holders.contains(peer))
+                    .collect::<Vec<_>>();
+                if missing.is_empty() {
+                    debug!("No peers to repair record at: {:?}", record.key);
+                    return None;
+                }
+
+                debug!(
+                    "Repairing record {:?} held by {} of {} peers",
+                    record.key,
+                    holders.len(),
+                    replication_factor
+                );
+                let info = QueryInfo::PutRecord {
+                    context: PutRecordContext::Repair,
+                    record,
+                    quorum: Quorum::One.eval(replication_factor),
+                    phase: PutRecordPhase::PutRecord {
+                        success: vec![],
+                        get_closest_peers_stats: result.stats,
+                    },
+                };
+                let inner = QueryInner::new(info);
+                self.queries.continue_fixed(query_id, missing, inner);
+                None
+            }
+
             QueryInfo::PutRecord {
                 context,
                 record,
@@ -1452,12 +2473,40 @@ where
                         result: QueryResult::RepublishRecord(mk_result(record.key)),
                         step: ProgressStep::first_and_last(),
                     }),
+                    PutRecordContext::Repair => Some(KademliaEvent::OutboundQueryProgressed {
+                        id: query_id,
+                        stats: get_closest_peers_stats.merge(result.stats),
+                        result: QueryResult::RepairRecord(mk_result(record.key)),
+                        step: ProgressStep::first_and_last(),
+                    }),
                     PutRecordContext::Replicate => {
                         debug!("Record replicated: {:?}", record.key);
                         None
                     }
                 }
             }
+
+            info @ (QueryInfo::PutRecordAt { .. } | QueryInfo::GetRecordFrom { .. }) => {
+                Some(KademliaEvent::OutboundQueryProgressed {
+                    id: query_id,
+                    stats: result.stats,
+                    result: targeted_query_result(info),
+                    step: ProgressStep::first_and_last(),
+                })
+            }
+
+            QueryInfo::FindAndDial { peer } => self.find_and_dial_lookup_finished(
+                query_id,
+                result.stats,
+                peer,
+                result.inner.addresses,
+                false,
+            ),
+
+            QueryInfo::Probation { peer } => {
+                self.probation_finished(peer, result.stats.num_successes() > 0);
+                None
+            }
         }
     }
 
@@ -1560,6 +2609,12 @@ where
                         result: QueryResult::RepublishRecord(err),
                         step: ProgressStep::first_and_last(),
                     }),
+                    PutRecordContext::Repair => Some(KademliaEvent::OutboundQueryProgressed {
+                        id: query_id,
+                        stats: result.stats,
+                        result: QueryResult::RepairRecord(err),
+                        step: ProgressStep::first_and_last(),
+                    }),
                     PutRecordContext::Replicate => match phase {
                         PutRecordPhase::GetClosestPeers => {
                             warn!("Locating closest peers for replication failed: {:?}", err);
@@ -1584,22 +2639,260 @@ where
                 })
             }
 
-            QueryInfo::GetProviders { key, mut step, .. } => {
-                step.last = true;
+            QueryInfo::RepairRecord { record, .. } => {
+                debug!("Checking replication of record timed out: {:?}", record.key);
+                None
+            }
 
+            // Peers that did not respond before the timeout are reported as failed.
+            info @ (QueryInfo::PutRecordAt { .. } | QueryInfo::GetRecordFrom { .. }) => {
                 Some(KademliaEvent::OutboundQueryProgressed {
                     id: query_id,
                     stats: result.stats,
-                    result: QueryResult::GetProviders(Err(GetProvidersError::Timeout {
-                        key,
-                        closest_peers: result.peers.collect(),
-                    })),
-                    step,
+                    result: targeted_query_result(info),
+                    step: ProgressStep::first_and_last(),
                 })
             }
+
+            // The peer is still dialed if addresses were found before the timeout.
+            QueryInfo::FindAndDial { peer } => self.find_and_dial_lookup_finished(
+                query_id,
+                result.stats,
+                peer,
+                result.inner.addresses,
+                true,
+            ),
+
+            QueryInfo::Probation { peer } => {
+                self.probation_finished(peer, false);
+                None
+            }
+
+            QueryInfo::GetProviders { key, step, .. } => self.provider_lookup_finished(
+                query_id,
+                result.stats,
+                step,
+                Err(GetProvidersError::Timeout {
+                    key,
+                    closest_peers: result.peers.collect(),
+                }),
+            ),
+        }
+    }
+
+    /// Handles the final result of a provider lookup in the DHT.
+    ///
+    /// If the lookup is still delegated to [`Kademlia::routing`], the result is held
+    /// back until the router finished as well.
+    fn provider_lookup_finished(
+        &mut self,
+        query_id: QueryId,
+        stats: QueryStats,
+        mut step: ProgressStep,
+        result: GetProvidersResult,
+    ) -> Option<KademliaEvent> {
+        if let Some(lookup) = self
+            .delegated_lookups
+            .values_mut()
+            .find(|lookup| lookup.query == query_id)
+        {
+            lookup.outcome = Some((stats, step, result));
+            return None;
+        }
+
+        step.last = true;
+
+        Some(KademliaEvent::OutboundQueryProgressed {
+            id: query_id,
+            stats,
+            result: QueryResult::GetProviders(result),
+            step,
+        })
+    }
+
+    /// Processes progress of a provider lookup delegated to [`Kademlia::routing`].
+    fn on_delegated_routing_event(&mut self, event: RoutingEvent) {
+        match event {
+            RoutingEvent::FoundProviders { id, providers } => {
+                let lookup = match self.delegated_lookups.get_mut(&id) {
+                    Some(lookup) => lookup,
+                    None => return,
+                };
+
+                let (stats, step) = match (&mut lookup.outcome, self.queries.get_mut(&lookup.query))
+                {
+                    (Some((stats, step, _)), _) => {
+                        let current = step.clone();
+                        *step = step.next();
+                        (stats.clone(), current)
+                    }
+                    (None, Some(query)) => {
+                        let stats = query.stats().clone();
+                        match &mut query.inner.info {
+                            QueryInfo::GetProviders {
+                                providers_found,
+                                step,
+                                ..
+                            } => {
+                                *providers_found += providers.len();
+                                let current = step.clone();
+                                *step = step.next();
+                                (stats, current)
+                            }
+                            _ => return,
+                        }
+                    }
+                    (None, None) => return,
+                };
+
+                self.queued_events.push_back(ToSwarm::GenerateEvent(
+                    KademliaEvent::OutboundQueryProgressed {
+                        id: lookup.query,
+                        result: QueryResult::GetProviders(Ok(GetProvidersOk::FoundProviders {
+                            key: lookup.key.clone(),
+                            providers,
+                            addresses: HashMap::new(),
+                            metadata: HashMap::new(),
+                        })),
+                        step,
+                        stats,
+                    },
+                ));
+            }
+            RoutingEvent::Finished { id } => {
+                if let Some(DelegatedLookup {
+                    query,
+                    outcome: Some((stats, mut step, result)),
+                    ..
+                }) = self.delegated_lookups.remove(&id)
+                {
+                    step.last = true;
+                    self.queued_events.push_back(ToSwarm::GenerateEvent(
+                        KademliaEvent::OutboundQueryProgressed {
+                            id: query,
+                            result: QueryResult::GetProviders(result),
+                            step,
+                            stats,
+                        },
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Checks an inbound request of `source` against the rate limits, returning whether it is
+    /// throttled. With [`KademliaInboundThrottling::Drop`], a throttled request is reset.
+    fn inbound_request_throttled(
+        &mut self,
+        source: PeerId,
+        connection: ConnectionId,
+        request_id: Option<KademliaRequestId>,
+    ) -> bool {
+        if self
+            .inbound_rate_limiter
+            .try_acquire(source, self.clock.now())
+        {
+            return false;
+        }
+        debug!("Throttling inbound request of {}", source);
+        if let (KademliaInboundThrottling::Drop, Some(request_id)) =
+            (self.inbound_throttling, request_id)
+        {
+            self.queued_events.push_back(ToSwarm::NotifyHandler {
+                peer_id: source,
+                handler: NotifyHandler::One(connection),
+                event: KademliaHandlerIn::Reset(request_id),
+            });
+        }
+        true
+    }
+
+    /// Starts tracking whether the local node is among the `replication_factor` closest nodes to
+    /// `key` in the routing table, returning whether it currently is.
+    ///
+    /// Whenever routing table updates move the local node into or out of the closest nodes to
+    /// a tracked key, [`KademliaEvent::ProximityChanged`] is emitted, e.g. to replicate the
+    /// records of keys the local node becomes responsible for.
+    pub fn track_proximity(&mut self, key: record::Key) -> bool {
+        let responsible = self.is_among_closest(&key);
+        self.proximity_keys.insert(key, responsible);
+        responsible
+    }
+
+    /// Stops tracking the proximity of the local node to `key`, see
+    /// [`Kademlia::track_proximity`].
+    pub fn untrack_proximity(&mut self, key: &record::Key) {
+        self.proximity_keys.remove(key);
+    }
+
+    /// Returns whether fewer than `replication_factor` nodes in the routing table are closer to
+    /// `key` than the local node.
+    fn is_among_closest(&mut self, key: &record::Key) -> bool {
+        let target = self.record_key(key.clone());
+        let local_distance = self.kbuckets.local_key().distance(&target);
+        let k = self.queries.config().replication_factor.get();
+        self.kbuckets
+            .closest_keys(&target)
+            .take_while(|peer| peer.distance(&target) < local_distance)
+            .take(k)
+            .count()
+            < k
+    }
+
+    /// Queues a [`KademliaEvent::ProximityChanged`] for every tracked key whose closest nodes
+    /// the local node entered or left since the last check.
+    fn check_proximity(&mut self) {
+        if !std::mem::take(&mut self.proximity_dirty) || self.proximity_keys.is_empty() {
+            return;
+        }
+        let keys = self.proximity_keys.keys().cloned().collect::<Vec<_>>();
+        for key in keys {
+            let now_responsible = self.is_among_closest(&key);
+            if self.proximity_keys.insert(key.clone(), now_responsible) != Some(now_responsible) {
+                self.queued_events.push_back(ToSwarm::GenerateEvent(
+                    KademliaEvent::ProximityChanged {
+                        key,
+                        now_responsible,
+                    },
+                ));
+            }
         }
     }
 
+    /// Reports the results of queries started via [`Routing::find_providers`]
+    /// through [`Routing::poll_providers`], passing through all other events.
+    fn divert_routing_event(&mut self, event: KademliaEvent) -> Option<KademliaEvent> {
+        let (query, result, step) = match &event {
+            KademliaEvent::OutboundQueryProgressed {
+                id,
+                result: QueryResult::GetProviders(result),
+                step,
+                ..
+            } => (*id, result, step),
+            _ => return Some(event),
+        };
+        let id = match self.routing_queries.get(&query) {
+            Some(id) => *id,
+            None => return Some(event),
+        };
+
+        if let Ok(GetProvidersOk::FoundProviders { providers, .. }) = result {
+            self.routing_events.push_back(RoutingEvent::FoundProviders {
+                id,
+                providers: providers.clone(),
+            });
+        }
+        if step.last {
+            self.routing_queries.remove(&query);
+            self.routing_events.push_back(RoutingEvent::Finished { id });
+        }
+        if let Some(waker) = self.routing_waker.take() {
+            waker.wake();
+        }
+
+        None
+    }
+
     /// Processes a record received from a peer.
     fn record_received(
         &mut self,
@@ -1624,13 +2917,13 @@ where
             return;
         }
 
-        let now = Instant::now();
+        let now = self.clock.now();
 
         // Calculate the expiration exponentially inversely proportional to the
         // number of nodes between the local node and the closest node to the key
         // (beyond the replication factor). This ensures avoiding over-caching
         // outside of the k closest nodes to a key.
-        let target = kbucket::Key::new(record.key.clone());
+        let target = self.record_key(record.key.clone());
         let num_between = self.kbuckets.count_nodes_between(&target);
         let k = self.queries.config().replication_factor.get();
         let num_beyond_k = (usize::max(k, num_between) - k) as u32;
@@ -1727,11 +3020,19 @@ where
     /// Processes a provider record received from a peer.
     fn provider_received(&mut self, key: record::Key, provider: KadPeer) {
         if &provider.node_id != self.kbuckets.local_key().preimage() {
+            if provider.metadata.len() > MAX_PROVIDER_METADATA_SIZE {
+                debug!(
+                    "Provider record of {} not stored: metadata too large",
+                    provider.node_id
+                );
+                return;
+            }
             let record = ProviderRecord {
                 key,
                 provider: provider.node_id,
-                expires: self.provider_record_ttl.map(|ttl| Instant::now() + ttl),
+                expires: self.provider_record_ttl.map(|ttl| self.clock.now() + ttl),
                 addresses: provider.multiaddrs,
+                metadata: provider.metadata,
             };
             match self.record_filtering {
                 KademliaStoreInserts::Unfiltered => {
@@ -1760,7 +3061,7 @@ where
     }
 
     fn address_failed(&mut self, peer_id: PeerId, address: &Multiaddr) {
-        let key = kbucket::Key::from(peer_id);
+        let key = self.peer_key(peer_id);
 
         if let Some(addrs) = self.kbuckets.entry(&key).value() {
             // TODO: Ideally, the address should only be removed if the error can
@@ -1801,11 +3102,14 @@ where
         &mut self,
         ConnectionEstablished {
             peer_id,
+            connection_id,
             failed_addresses,
             other_established,
             ..
         }: ConnectionEstablished,
     ) {
+        self.connections.insert(connection_id, peer_id);
+
         for addr in failed_addresses {
             self.address_failed(peer_id, addr);
         }
@@ -1835,6 +3139,8 @@ where
 
             self.connected_peers.insert(peer_id);
         }
+
+        self.find_and_dial_finished(connection_id);
     }
 
     fn on_address_change(
@@ -1849,7 +3155,8 @@ where
         let (old, new) = (old.get_remote_address(), new.get_remote_address());
 
         // Update routing table.
-        if let Some(addrs) = self.kbuckets.entry(&kbucket::Key::from(peer)).value() {
+        let key = self.peer_key(peer);
+        if let Some(addrs) = self.kbuckets.entry(&key).value() {
             if addrs.replace(old, new) {
                 debug!(
                     "Address '{}' replaced with '{}' for peer '{}'.",
@@ -1895,7 +3202,17 @@ where
         }
     }
 
-    fn on_dial_failure(&mut self, DialFailure { peer_id, error, .. }: DialFailure) {
+    fn on_dial_failure(
+        &mut self,
+        DialFailure {
+            peer_id,
+            error,
+            connection_id,
+            ..
+        }: DialFailure,
+    ) {
+        self.find_and_dial_finished(connection_id);
+
         let peer_id = match peer_id {
             Some(id) => id,
             // Not interested in dial failures to unknown peers.
@@ -1912,44 +3229,297 @@ where
             | DialError::Denied { .. }
             | DialError::Transport(_)
             | DialError::NoAddresses => {
-                if let DialError::Transport(addresses) = error {
-                    for (addr, _) in addresses {
-                        self.address_failed(peer_id, addr)
+                match error {
+                    DialError::Transport(addresses) => {
+                        for (addr, _) in addresses {
+                            self.address_failed(peer_id, addr)
+                        }
+                    }
+                    // The address belongs to another peer, the peer itself may still be reachable
+                    // at its other addresses.
+                    DialError::WrongPeerId { endpoint, .. } => {
+                        self.address_failed(peer_id, endpoint.get_remote_address())
+                    }
+                    // Dialing the peer at all is pointless.
+                    #[allow(deprecated)]
+                    DialError::Banned | DialError::LocalPeerId { .. }
+                        if self.remove_peer(&peer_id).is_some() =>
+                    {
+                        debug!(
+                            "Peer '{}' removed from the routing table as it can not be dialed: {}",
+                            peer_id, error
+                        );
+                    }
+                    _ => {}
+                }
+
+                for query in self.queries.iter_mut() {
+                    query.on_failure(&peer_id);
+                }
+            }
+            DialError::DialPeerConditionFalse(
+                dial_opts::PeerCondition::Disconnected | dial_opts::PeerCondition::NotDialing,
+            ) => {
+                // We might (still) be connected, or about to be connected, thus do not report the
+                // failure to the queries.
+            }
+            DialError::DialPeerConditionFalse(dial_opts::PeerCondition::Always) => {
+                unreachable!("DialPeerCondition::Always can not trigger DialPeerConditionFalse.");
+            }
+            #[allow(deprecated)]
+            DialError::ConnectionLimit(_) => {}
+        }
+    }
+
+    /// Removes peers that stopped supporting the Kademlia protocol from the routing table and
+    /// inserts those waiting to advertise it, see [`KademliaProtocolConfirmation::Advertised`].
+    fn on_peer_protocols_changed(
+        &mut self,
+        PeerProtocolsChanged {
+            peer_id,
+            added,
+            removed,
+        }: PeerProtocolsChanged,
+    ) {
+        let is_kad = |protocol: &String| {
+            self.protocol_config
+                .protocol_names()
+                .iter()
+                .any(|name| name.as_ref() == protocol.as_bytes())
+        };
+        let advertised = self.advertising_peers.entry(peer_id).or_default();
+        advertised.retain(|protocol| !removed.contains(protocol));
+        if self.connected_peers.contains(&peer_id) {
+            advertised.extend(added.iter().filter(|protocol| is_kad(protocol)).cloned());
+        }
+        let supports_kad = !advertised.is_empty();
+        if !supports_kad {
+            self.advertising_peers.remove(&peer_id);
+        }
+
+        // A peer may support several of the configured protocol names, it is only removed once
+        // it stopped supporting all of them.
+        let stopped_supporting_kad = removed.iter().any(is_kad) && !supports_kad;
+        let started_supporting_kad = added.iter().any(is_kad) && supports_kad;
+
+        if stopped_supporting_kad {
+            if self.remove_peer(&peer_id).is_some() {
+                debug!(
+                    "Removed peer {} from the routing table as it no longer supports Kademlia.",
+                    peer_id
+                );
+            }
+        } else if started_supporting_kad {
+            if let Some(address) = self.unadvertised_peers.remove(&peer_id) {
+                self.connection_updated(peer_id, address, NodeStatus::Connected);
+            }
+        }
+    }
+
+    fn on_connection_closed(
+        &mut self,
+        ConnectionClosed {
+            peer_id,
+            connection_id,
+            remaining_established,
+            ..
+        }: ConnectionClosed<<Self as NetworkBehaviour>::ConnectionHandler>,
+    ) {
+        self.connections.remove(&connection_id);
+
+        if remaining_established == 0 {
+            for query in self.queries.iter_mut() {
+                query.on_failure(&peer_id);
+            }
+            self.connection_updated(peer_id, None, NodeStatus::Disconnected);
+            self.connected_peers.remove(&peer_id);
+            self.advertising_peers.remove(&peer_id);
+            self.unadvertised_peers.remove(&peer_id);
+            self.probation.remove(&peer_id);
+            self.verified_peers.remove(&peer_id);
+        }
+    }
+
+    /// Runs the background jobs and drives the active queries.
+    fn poll_queries(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<KademliaEvent, KademliaHandlerIn<QueryId>>> {
+        let now = self.clock.now();
+
+        // Collect the results of provider lookups delegated to the router.
+        if let Some(mut routing) = self.routing.take() {
+            while let Poll::Ready(event) = routing.poll_providers(cx) {
+                self.on_delegated_routing_event(event);
+            }
+            self.routing = Some(routing);
+        }
+
+        if self.paused && self.paused_queries == KademliaPausedQueries::Fail {
+            // Fail the queries started while paused. Timed out bootstrap queries may start the
+            // next bootstrap step, thus repeat until no new queries are left.
+            loop {
+                let queries = self.queries.remove_unstarted();
+                if queries.is_empty() {
+                    break;
+                }
+                for query in queries {
+                    if let Some(event) = self.query_timeout(query) {
+                        self.queued_events.push_back(ToSwarm::GenerateEvent(event));
+                    }
+                }
+            }
+        }
+
+        // Run the background jobs, unless paused.
+        if !self.paused {
+            // Calculate the available capacity for queries triggered by background jobs.
+            let mut jobs_query_capacity = JOBS_MAX_QUERIES.saturating_sub(self.queries.size());
+
+            // Run the periodic provider announcement job.
+            if let Some(mut job) = self.add_provider_job.take() {
+                let num = usize::min(JOBS_MAX_NEW_QUERIES, jobs_query_capacity);
+                for _ in 0..num {
+                    if let Poll::Ready(r) = job.poll(cx, &mut self.store, now) {
+                        self.start_add_provider(r.key, AddProviderContext::Republish)
+                    } else {
+                        break;
+                    }
+                }
+                jobs_query_capacity -= num;
+                self.add_provider_job = Some(job);
+            }
+
+            // Run the periodic record replication / publication job.
+            if let Some(mut job) = self.put_record_job.take() {
+                let num = usize::min(JOBS_MAX_NEW_QUERIES, jobs_query_capacity);
+                for _ in 0..num {
+                    if let Poll::Ready(r) = job.poll(cx, &mut self.store, now) {
+                        let context =
+                            if r.publisher.as_ref() == Some(self.kbuckets.local_key().preimage()) {
+                                PutRecordContext::Republish
+                            } else {
+                                PutRecordContext::Replicate
+                            };
+                        self.start_put_record(r, context)
+                    } else {
+                        break;
+                    }
+                }
+                jobs_query_capacity -= num;
+                self.put_record_job = Some(job);
+            }
+
+            // Run the periodic record repair job.
+            if let Some(mut job) = self.record_repair_job.take() {
+                let num = usize::min(JOBS_MAX_NEW_QUERIES, jobs_query_capacity);
+                for _ in 0..num {
+                    if let Poll::Ready(r) = job.poll(cx, &mut self.store, now) {
+                        if self.record_validator.validate(&r) {
+                            self.start_repair_record(r)
+                        } else {
+                            debug!("Removing invalid record: {:?}", r.key);
+                            self.store.remove(&r.key);
+                            self.queued_events.push_back(ToSwarm::GenerateEvent(
+                                KademliaEvent::InvalidRecordRemoved { record: r },
+                            ));
+                        }
+                    } else {
+                        break;
+                    }
+                }
+                self.record_repair_job = Some(job);
+            }
+        }
+
+        loop {
+            // Drain queued events first.
+            if let Some(event) = self.queued_events.pop_front() {
+                return Poll::Ready(event);
+            }
+
+            // Drain applied pending entries from the routing table.
+            if let Some(entry) = self.kbuckets.take_applied_pending() {
+                let kbucket::Node { key, value } = entry.inserted;
+                if let Some(prefix) =
+                    self.ip_diversity
+                        .violation(&mut self.kbuckets, &key, value.iter())
+                {
+                    debug!(
+                        "Evicting peer {} from routing table: too many peers in {:?}",
+                        key.preimage(),
+                        prefix
+                    );
+                    self.remove_peer(key.preimage());
+                    continue;
+                }
+                self.proximity_dirty = true;
+                let event = KademliaEvent::RoutingUpdated {
+                    bucket_range: self
+                        .kbuckets
+                        .bucket(&key)
+                        .map(|b| b.range())
+                        .expect("Self to never be applied from pending."),
+                    peer: key.into_preimage(),
+                    is_new_peer: true,
+                    addresses: value,
+                    old_peer: entry.evicted.map(|n| n.key.into_preimage()),
+                };
+                return Poll::Ready(ToSwarm::GenerateEvent(event));
+            }
+
+            // Look for a finished query.
+            while !self.paused {
+                match self.queries.poll(now) {
+                    QueryPoolState::Finished(q) => {
+                        if let Some(event) = self.query_finished(q) {
+                            return Poll::Ready(ToSwarm::GenerateEvent(event));
+                        }
+                    }
+                    QueryPoolState::Timeout(q) => {
+                        if let Some(event) = self.query_timeout(q) {
+                            return Poll::Ready(ToSwarm::GenerateEvent(event));
+                        }
+                    }
+                    QueryPoolState::Waiting(Some((query, peer_id))) => {
+                        let event = query.inner.info.to_request(query.id());
+                        // TODO: AddProvider requests yield no response, so the query completes
+                        // as soon as all requests have been sent. However, the handler should
+                        // better emit an event when the request has been sent (and report
+                        // an error if sending fails), instead of immediately reporting
+                        // "success" somewhat prematurely here.
+                        if let QueryInfo::AddProvider {
+                            phase: AddProviderPhase::AddProvider { .. },
+                            ..
+                        } = &query.inner.info
+                        {
+                            query.on_success(&peer_id, vec![])
+                        }
+
+                        if self.connected_peers.contains(&peer_id) {
+                            self.queued_events.push_back(ToSwarm::NotifyHandler {
+                                peer_id,
+                                event,
+                                handler: NotifyHandler::Any,
+                            });
+                        } else if &peer_id != self.kbuckets.local_key().preimage() {
+                            query.inner.pending_rpcs.push((peer_id, event));
+                            self.queued_events.push_back(ToSwarm::Dial {
+                                opts: DialOpts::peer_id(peer_id).build(),
+                            });
+                        }
                     }
+                    QueryPoolState::Waiting(None) | QueryPoolState::Idle => break,
                 }
-
-                for query in self.queries.iter_mut() {
-                    query.on_failure(&peer_id);
-                }
-            }
-            DialError::DialPeerConditionFalse(
-                dial_opts::PeerCondition::Disconnected | dial_opts::PeerCondition::NotDialing,
-            ) => {
-                // We might (still) be connected, or about to be connected, thus do not report the
-                // failure to the queries.
             }
-            DialError::DialPeerConditionFalse(dial_opts::PeerCondition::Always) => {
-                unreachable!("DialPeerCondition::Always can not trigger DialPeerConditionFalse.");
-            }
-            #[allow(deprecated)]
-            DialError::ConnectionLimit(_) => {}
-        }
-    }
 
-    fn on_connection_closed(
-        &mut self,
-        ConnectionClosed {
-            peer_id,
-            remaining_established,
-            ..
-        }: ConnectionClosed<<Self as NetworkBehaviour>::ConnectionHandler>,
-    ) {
-        if remaining_established == 0 {
-            for query in self.queries.iter_mut() {
-                query.on_failure(&peer_id);
+            // No immediate event was produced as a result of a finished query.
+            // If no new events have been queued either, signal `NotReady` to
+            // be polled again later.
+            if self.queued_events.is_empty() {
+                self.waker = Some(cx.waker().clone());
+                return Poll::Pending;
             }
-            self.connection_updated(peer_id, None, NodeStatus::Disconnected);
-            self.connected_peers.remove(&peer_id);
         }
     }
 }
@@ -1959,6 +3529,55 @@ fn exp_decrease(ttl: Duration, exp: u32) -> Duration {
     Duration::from_secs(ttl.as_secs().checked_shr(exp).unwrap_or(0))
 }
 
+/// Collects the given peers, dropping duplicates while preserving their order.
+fn dedup_peers<I>(peers: I) -> Vec<PeerId>
+where
+    I: IntoIterator<Item = PeerId>,
+{
+    let mut seen = HashSet::new();
+    peers.into_iter().filter(|p| seen.insert(*p)).collect()
+}
+
+/// The peers of a targeted query that neither responded nor are accounted
+/// for otherwise.
+fn unanswered_peers(peers: Vec<PeerId>, answered: &[&[PeerId]]) -> Vec<PeerId> {
+    peers
+        .into_iter()
+        .filter(|p| !answered.iter().any(|a| a.contains(p)))
+        .collect()
+}
+
+/// Builds the per-peer result of a finished or timed out
+/// [`QueryInfo::PutRecordAt`] or [`QueryInfo::GetRecordFrom`] query.
+fn targeted_query_result(info: QueryInfo) -> QueryResult {
+    match info {
+        QueryInfo::PutRecordAt {
+            record,
+            peers,
+            success,
+        } => QueryResult::PutRecordAt(PutRecordAtResult {
+            key: record.key,
+            failed: unanswered_peers(peers, &[&success]),
+            success,
+        }),
+        QueryInfo::GetRecordFrom {
+            key,
+            peers,
+            records,
+            not_found,
+        } => {
+            let found = records.iter().filter_map(|r| r.peer).collect::<Vec<_>>();
+            QueryResult::GetRecordFrom(GetRecordFromResult {
+                key,
+                failed: unanswered_peers(peers, &[&found, &not_found]),
+                records,
+                not_found,
+            })
+        }
+        _ => unreachable!("not a targeted record query"),
+    }
+}
+
 impl<TStore> NetworkBehaviour for Kademlia<TStore>
 where
     TStore: RecordStore + Send + 'static,
@@ -1976,7 +3595,7 @@ where
         Ok(KademliaHandler::new(
             KademliaHandlerConfig {
                 protocol_config: self.protocol_config.clone(),
-                allow_listening: true,
+                allow_listening: self.mode == Mode::Server,
                 idle_timeout: self.connection_idle_timeout,
             },
             ConnectedPoint::Listener {
@@ -1997,7 +3616,7 @@ where
         Ok(KademliaHandler::new(
             KademliaHandlerConfig {
                 protocol_config: self.protocol_config.clone(),
-                allow_listening: true,
+                allow_listening: self.mode == Mode::Server,
                 idle_timeout: self.connection_idle_timeout,
             },
             ConnectedPoint::Dialer {
@@ -2022,7 +3641,7 @@ where
 
         // We should order addresses from decreasing likelyhood of connectivity, so start with
         // the addresses of that peer in the k-buckets.
-        let key = kbucket::Key::from(peer_id);
+        let key = self.peer_key(peer_id);
         let mut peer_addrs =
             if let kbucket::Entry::Present(mut entry, _) = self.kbuckets.entry(&key) {
                 let addrs = entry.value().iter().cloned().collect::<Vec<_>>();
@@ -2063,7 +3682,16 @@ where
             }
 
             KademliaHandlerEvent::FindNodeReq { key, request_id } => {
-                let closer_peers = self.find_closest(&kbucket::Key::new(key), &source);
+                let throttled =
+                    self.inbound_request_throttled(source, connection, Some(request_id));
+                if throttled && self.inbound_throttling == KademliaInboundThrottling::Drop {
+                    return;
+                }
+                let closer_peers = if throttled {
+                    Vec::new()
+                } else {
+                    self.find_closest(&self.record_key(key), &source)
+                };
 
                 self.queued_events.push_back(ToSwarm::GenerateEvent(
                     KademliaEvent::InboundRequest {
@@ -2091,8 +3719,19 @@ where
             }
 
             KademliaHandlerEvent::GetProvidersReq { key, request_id } => {
-                let provider_peers = self.provider_peers(&key, &source);
-                let closer_peers = self.find_closest(&kbucket::Key::new(key), &source);
+                let throttled =
+                    self.inbound_request_throttled(source, connection, Some(request_id));
+                if throttled && self.inbound_throttling == KademliaInboundThrottling::Drop {
+                    return;
+                }
+                let (provider_peers, closer_peers) = if throttled {
+                    (Vec::new(), Vec::new())
+                } else {
+                    (
+                        self.provider_peers(&key, &source),
+                        self.find_closest(&self.record_key(key), &source),
+                    )
+                };
 
                 self.queued_events.push_back(ToSwarm::GenerateEvent(
                     KademliaEvent::InboundRequest {
@@ -2132,6 +3771,16 @@ where
                     {
                         *providers_found += provider_peers.len();
                         let providers = provider_peers.iter().map(|p| p.node_id).collect();
+                        let addresses = provider_peers
+                            .iter()
+                            .filter(|p| !p.multiaddrs.is_empty())
+                            .map(|p| (p.node_id, p.multiaddrs.clone()))
+                            .collect();
+                        let metadata = provider_peers
+                            .iter()
+                            .filter(|p| !p.metadata.is_empty())
+                            .map(|p| (p.node_id, p.metadata.clone()))
+                            .collect();
 
                         self.queued_events.push_back(ToSwarm::GenerateEvent(
                             KademliaEvent::OutboundQueryProgressed {
@@ -2140,6 +3789,8 @@ where
                                     GetProvidersOk::FoundProviders {
                                         key: key.clone(),
                                         providers,
+                                        addresses,
+                                        metadata,
                                     },
                                 )),
                                 step: step.clone(),
@@ -2170,15 +3821,35 @@ where
                 if provider.node_id != source {
                     return;
                 }
+                if self.inbound_request_throttled(source, connection, None) {
+                    return;
+                }
 
                 self.provider_received(key, provider);
             }
 
             KademliaHandlerEvent::GetRecord { key, request_id } => {
+                let throttled =
+                    self.inbound_request_throttled(source, connection, Some(request_id));
+                if throttled {
+                    if self.inbound_throttling == KademliaInboundThrottling::EmptyResponse {
+                        self.queued_events.push_back(ToSwarm::NotifyHandler {
+                            peer_id: source,
+                            handler: NotifyHandler::One(connection),
+                            event: KademliaHandlerIn::GetRecordRes {
+                                record: None,
+                                closer_peers: Vec::new(),
+                                request_id,
+                            },
+                        });
+                    }
+                    return;
+                }
+
                 // Lookup the record locally.
                 let record = match self.store.get(&key) {
                     Some(record) => {
-                        if record.is_expired(Instant::now()) {
+                        if record.is_expired(self.clock.now()) {
                             self.store.remove(&key);
                             None
                         } else {
@@ -2188,7 +3859,7 @@ where
                     None => None,
                 };
 
-                let closer_peers = self.find_closest(&kbucket::Key::new(key), &source);
+                let closer_peers = self.find_closest(&self.record_key(key), &source);
 
                 self.queued_events.push_back(ToSwarm::GenerateEvent(
                     KademliaEvent::InboundRequest {
@@ -2215,6 +3886,7 @@ where
                 closer_peers,
                 user_data,
             } => {
+                let key_hasher = self.queries.config().key_hasher.clone();
                 if let Some(query) = self.queries.get_mut(&user_data) {
                     let stats = query.stats().clone();
                     if let QueryInfo::GetRecord {
@@ -2246,8 +3918,10 @@ where
                         } else {
                             log::trace!("Record with key {:?} not found at {}", key, source);
                             if let KademliaCaching::Enabled { max_peers } = self.caching {
-                                let source_key = kbucket::Key::from(source);
-                                let target_key = kbucket::Key::from(key.clone());
+                                let source_key =
+                                    kbucket::Key::peer_with_hasher(source, &*key_hasher);
+                                let target_key =
+                                    kbucket::Key::with_hasher(key.clone(), &*key_hasher);
                                 let distance = source_key.distance(&target_key);
                                 cache_candidates.insert(distance, source);
                                 if cache_candidates.len() > max_peers as usize {
@@ -2259,6 +3933,21 @@ where
                                 }
                             }
                         }
+                    } else if let QueryInfo::RepairRecord { holders, .. } = &mut query.inner.info {
+                        if record.map_or(false, |r| self.record_validator.validate(&r)) {
+                            holders.push(source);
+                        }
+                    } else if let QueryInfo::GetRecordFrom {
+                        records, not_found, ..
+                    } = &mut query.inner.info
+                    {
+                        match record {
+                            Some(record) => records.push(PeerRecord {
+                                peer: Some(source),
+                                record,
+                            }),
+                            None => not_found.push(source),
+                        }
                     }
                 }
 
@@ -2266,6 +3955,16 @@ where
             }
 
             KademliaHandlerEvent::PutRecord { record, request_id } => {
+                if self.inbound_request_throttled(source, connection, Some(request_id)) {
+                    if self.inbound_throttling == KademliaInboundThrottling::EmptyResponse {
+                        self.queued_events.push_back(ToSwarm::NotifyHandler {
+                            peer_id: source,
+                            handler: NotifyHandler::One(connection),
+                            event: KademliaHandlerIn::Reset(request_id),
+                        });
+                    }
+                    return;
+                }
                 self.record_received(source, connection, request_id, record);
             }
 
@@ -2295,6 +3994,8 @@ where
                                 );
                             }
                         }
+                    } else if let QueryInfo::PutRecordAt { success, .. } = &mut query.inner.info {
+                        success.push(source);
                     }
                 }
             }
@@ -2306,121 +4007,25 @@ where
         cx: &mut Context<'_>,
         _: &mut impl PollParameters,
     ) -> Poll<ToSwarm<Self::OutEvent, THandlerInEvent<Self>>> {
-        let now = Instant::now();
-
-        // Calculate the available capacity for queries triggered by background jobs.
-        let mut jobs_query_capacity = JOBS_MAX_QUERIES.saturating_sub(self.queries.size());
-
-        // Run the periodic provider announcement job.
-        if let Some(mut job) = self.add_provider_job.take() {
-            let num = usize::min(JOBS_MAX_NEW_QUERIES, jobs_query_capacity);
-            for _ in 0..num {
-                if let Poll::Ready(r) = job.poll(cx, &mut self.store, now) {
-                    self.start_add_provider(r.key, AddProviderContext::Republish)
-                } else {
-                    break;
-                }
-            }
-            jobs_query_capacity -= num;
-            self.add_provider_job = Some(job);
-        }
-
-        // Run the periodic record replication / publication job.
-        if let Some(mut job) = self.put_record_job.take() {
-            let num = usize::min(JOBS_MAX_NEW_QUERIES, jobs_query_capacity);
-            for _ in 0..num {
-                if let Poll::Ready(r) = job.poll(cx, &mut self.store, now) {
-                    let context =
-                        if r.publisher.as_ref() == Some(self.kbuckets.local_key().preimage()) {
-                            PutRecordContext::Republish
-                        } else {
-                            PutRecordContext::Replicate
-                        };
-                    self.start_put_record(r, Quorum::All, context)
-                } else {
-                    break;
-                }
-            }
-            self.put_record_job = Some(job);
-        }
-
         loop {
-            // Drain queued events first.
-            if let Some(event) = self.queued_events.pop_front() {
-                return Poll::Ready(event);
-            }
-
-            // Drain applied pending entries from the routing table.
-            if let Some(entry) = self.kbuckets.take_applied_pending() {
-                let kbucket::Node { key, value } = entry.inserted;
-                let event = KademliaEvent::RoutingUpdated {
-                    bucket_range: self
-                        .kbuckets
-                        .bucket(&key)
-                        .map(|b| b.range())
-                        .expect("Self to never be applied from pending."),
-                    peer: key.into_preimage(),
-                    is_new_peer: true,
-                    addresses: value,
-                    old_peer: entry.evicted.map(|n| n.key.into_preimage()),
-                };
-                return Poll::Ready(ToSwarm::GenerateEvent(event));
-            }
-
-            // Look for a finished query.
-            loop {
-                match self.queries.poll(now) {
-                    QueryPoolState::Finished(q) => {
-                        if let Some(event) = self.query_finished(q) {
-                            return Poll::Ready(ToSwarm::GenerateEvent(event));
-                        }
-                    }
-                    QueryPoolState::Timeout(q) => {
-                        if let Some(event) = self.query_timeout(q) {
-                            return Poll::Ready(ToSwarm::GenerateEvent(event));
-                        }
-                    }
-                    QueryPoolState::Waiting(Some((query, peer_id))) => {
-                        let event = query.inner.info.to_request(query.id());
-                        // TODO: AddProvider requests yield no response, so the query completes
-                        // as soon as all requests have been sent. However, the handler should
-                        // better emit an event when the request has been sent (and report
-                        // an error if sending fails), instead of immediately reporting
-                        // "success" somewhat prematurely here.
-                        if let QueryInfo::AddProvider {
-                            phase: AddProviderPhase::AddProvider { .. },
-                            ..
-                        } = &query.inner.info
-                        {
-                            query.on_success(&peer_id, vec![])
-                        }
-
-                        if self.connected_peers.contains(&peer_id) {
-                            self.queued_events.push_back(ToSwarm::NotifyHandler {
-                                peer_id,
-                                event,
-                                handler: NotifyHandler::Any,
-                            });
-                        } else if &peer_id != self.kbuckets.local_key().preimage() {
-                            query.inner.pending_rpcs.push((peer_id, event));
-                            self.queued_events.push_back(ToSwarm::Dial {
-                                opts: DialOpts::peer_id(peer_id).build(),
-                            });
-                        }
+            self.check_proximity();
+            match self.poll_queries(cx) {
+                Poll::Ready(ToSwarm::GenerateEvent(event)) => {
+                    if let Some(event) = self.divert_routing_event(event) {
+                        return Poll::Ready(ToSwarm::GenerateEvent(event));
                     }
-                    QueryPoolState::Waiting(None) | QueryPoolState::Idle => break,
                 }
-            }
-
-            // No immediate event was produced as a result of a finished query.
-            // If no new events have been queued either, signal `NotReady` to
-            // be polled again later.
-            if self.queued_events.is_empty() {
-                return Poll::Pending;
+                // Report proximity changes of routing table updates made while polling.
+                Poll::Pending if self.proximity_dirty => {}
+                poll => return poll,
             }
         }
     }
 
+    fn queued_actions(&self) -> usize {
+        self.queued_events.len()
+    }
+
     fn on_swarm_event(&mut self, event: FromSwarm<Self::ConnectionHandler>) {
         self.listen_addresses.on_swarm_event(&event);
         self.external_addresses.on_swarm_event(&event);
@@ -2434,6 +4039,9 @@ where
             }
             FromSwarm::DialFailure(dial_failure) => self.on_dial_failure(dial_failure),
             FromSwarm::AddressChange(address_change) => self.on_address_change(address_change),
+            FromSwarm::PeerProtocolsChanged(peer_protocols_changed) => {
+                self.on_peer_protocols_changed(peer_protocols_changed)
+            }
             FromSwarm::ExpiredListenAddr(_)
             | FromSwarm::NewExternalAddr(_)
             | FromSwarm::NewListenAddr(_)
@@ -2441,8 +4049,45 @@ where
             | FromSwarm::NewListener(_)
             | FromSwarm::ListenerClosed(_)
             | FromSwarm::ListenerError(_)
-            | FromSwarm::ExpiredExternalAddr(_) => {}
+            | FromSwarm::ExpiredExternalAddr(_)
+            | FromSwarm::PeerListenAddrsChanged(_)
+            | FromSwarm::NatStatusChanged(_) => {}
+        }
+    }
+}
+
+/// Provider lookups via [`Routing`] run as regular [`Kademlia::get_providers`] queries.
+///
+/// Their results are reported through [`Routing::poll_providers`] instead of as
+/// [`KademliaEvent`]s, while the behaviour itself still needs to be polled by the
+/// [`Swarm`](libp2p_swarm::Swarm) for the queries to make progress.
+impl<TStore> Routing for Kademlia<TStore>
+where
+    TStore: RecordStore + Send + 'static,
+{
+    fn find_providers(&mut self, id: LookupId, key: &record::Key) {
+        let query = self.get_providers(key.clone());
+        self.routing_queries.insert(query, id);
+    }
+
+    fn cancel(&mut self, id: LookupId) {
+        let query = self
+            .routing_queries
+            .iter()
+            .find_map(|(query, lookup)| (*lookup == id).then_some(*query));
+        if let Some(mut query) = query.and_then(|query| self.query_mut(&query)) {
+            query.finish();
+        }
+    }
+
+    fn poll_providers(&mut self, cx: &mut Context<'_>) -> Poll<RoutingEvent> {
+        if let Some(event) = self.routing_events.pop_front() {
+            return Poll::Ready(event);
         }
+
+        self.routing_waker = Some(cx.waker().clone());
+
+        Poll::Pending
     }
 }
 
@@ -2525,6 +4170,12 @@ pub enum KademliaEvent {
         old_peer: Option<PeerId>,
     },
 
+    /// A record in the local store has been rejected by the configured
+    /// [`RecordValidator`] and removed.
+    ///
+    /// See [`KademliaConfig::set_record_validator`].
+    InvalidRecordRemoved { record: Record },
+
     /// A peer has connected for whom no listen address is known.
     ///
     /// If the peer is to be added to the routing table, a known
@@ -2556,6 +4207,15 @@ pub enum KademliaEvent {
     /// See [`Kademlia::kbucket`] for insight into the contents of
     /// the k-bucket of `peer`.
     PendingRoutablePeer { peer: PeerId, address: Multiaddr },
+
+    /// The local node became or stopped being one of the `replication_factor` closest nodes to
+    /// a key tracked with [`Kademlia::track_proximity`], as the routing table changed.
+    ProximityChanged {
+        /// The tracked key.
+        key: record::Key,
+        /// Whether the local node is now among the closest nodes to the key.
+        now_responsible: bool,
+    },
 }
 
 /// Information about progress events.
@@ -2648,6 +4308,43 @@ pub enum QueryResult {
 
     /// The result of a (automatic) republishing of a (value-)record.
     RepublishRecord(PutRecordResult),
+
+    /// The result of a (automatic) repair of an under-replicated (value-)record,
+    /// see [`KademliaConfig::set_record_repair_interval`].
+    RepairRecord(PutRecordResult),
+
+    /// The result of [`Kademlia::get_record_from`].
+    GetRecordFrom(GetRecordFromResult),
+
+    /// The result of [`Kademlia::put_record_at`].
+    PutRecordAt(PutRecordAtResult),
+
+    /// The result of [`Kademlia::find_and_dial`].
+    FindAndDial(FindAndDialResult),
+}
+
+/// The per-peer result of [`Kademlia::get_record_from`].
+#[derive(Debug, Clone)]
+pub struct GetRecordFromResult {
+    pub key: record::Key,
+    /// The records returned by the peers that had one.
+    pub records: Vec<PeerRecord>,
+    /// The peers that responded without a record.
+    pub not_found: Vec<PeerId>,
+    /// The peers that could not be reached, failed the request
+    /// or did not respond before the query timed out.
+    pub failed: Vec<PeerId>,
+}
+
+/// The per-peer result of [`Kademlia::put_record_at`].
+#[derive(Debug, Clone)]
+pub struct PutRecordAtResult {
+    pub key: record::Key,
+    /// The peers that acknowledged storing the record.
+    pub success: Vec<PeerId>,
+    /// The peers that could not be reached, failed the request
+    /// or did not respond before the query timed out.
+    pub failed: Vec<PeerId>,
 }
 
 /// The result of [`Kademlia::get_record`].
@@ -2785,6 +4482,11 @@ pub type GetClosestPeersResult = Result<GetClosestPeersOk, GetClosestPeersError>
 pub struct GetClosestPeersOk {
     pub key: Vec<u8>,
     pub peers: Vec<PeerId>,
+    /// Whether any of the `peers` has been reported by fewer distinct peers
+    /// than required by [`KademliaConfig::set_closest_peers_quorum`].
+    ///
+    /// Always `false` if no quorum is configured.
+    pub low_confidence: bool,
 }
 
 /// The error result of [`Kademlia::get_closest_peers`].
@@ -2811,6 +4513,44 @@ impl GetClosestPeersError {
     }
 }
 
+/// The result of [`Kademlia::find_and_dial`].
+pub type FindAndDialResult = Result<FindAndDialOk, FindAndDialError>;
+
+/// The successful result of [`Kademlia::find_and_dial`].
+#[derive(Debug, Clone)]
+pub struct FindAndDialOk {
+    pub peer: PeerId,
+    /// The addresses found for the peer.
+    ///
+    /// Empty if the peer was connected already and no addresses were found.
+    pub addresses: Vec<Multiaddr>,
+}
+
+/// The error result of [`Kademlia::find_and_dial`].
+#[derive(Debug, Clone, Error)]
+pub enum FindAndDialError {
+    #[error("no addresses of the peer were found")]
+    NotFound { peer: PeerId },
+    #[error("the lookup timed out before any addresses of the peer were found")]
+    Timeout { peer: PeerId },
+    #[error("dialing the peer failed")]
+    DialFailed {
+        peer: PeerId,
+        addresses: Vec<Multiaddr>,
+    },
+}
+
+impl FindAndDialError {
+    /// Gets the peer that could not be dialed.
+    pub fn peer(&self) -> &PeerId {
+        match self {
+            FindAndDialError::NotFound { peer }
+            | FindAndDialError::Timeout { peer }
+            | FindAndDialError::DialFailed { peer, .. } => peer,
+        }
+    }
+}
+
 /// The result of [`Kademlia::get_providers`].
 pub type GetProvidersResult = Result<GetProvidersOk, GetProvidersError>;
 
@@ -2821,6 +4561,12 @@ pub enum GetProvidersOk {
         key: record::Key,
         /// The new set of providers discovered.
         providers: HashSet<PeerId>,
+        /// The known addresses of the new providers, as far as they were
+        /// included in the response.
+        addresses: HashMap<PeerId, Vec<Multiaddr>>,
+        /// The metadata announced by the new providers, as far as they announced any,
+        /// see [`ProviderRecord::metadata`].
+        metadata: HashMap<PeerId, Vec<u8>>,
     },
     FinishedWithNoAdditionalRecord {
         closest_peers: Vec<PeerId>,
@@ -2895,6 +4641,7 @@ impl From<kbucket::EntryView<kbucket::Key<PeerId>, Addresses>> for KadPeer {
                 NodeStatus::Connected => KadConnectionType::Connected,
                 NodeStatus::Disconnected => KadConnectionType::NotConnected,
             },
+            metadata: Vec::new(),
         }
     }
 }
@@ -2912,6 +4659,11 @@ struct QueryInner {
     /// A request is pending if the targeted peer is not currently connected
     /// and these requests are sent as soon as a connection to the peer is established.
     pending_rpcs: SmallVec<[(PeerId, KademliaHandlerIn<QueryId>); K_VALUE.get()]>,
+    /// The distinct peers that reported each peer discovered during a
+    /// [`Kademlia::get_closest_peers`] query.
+    ///
+    /// Only tracked if [`KademliaConfig::set_closest_peers_quorum`] is set.
+    reporters: FnvHashMap<PeerId, FnvHashSet<PeerId>>,
 }
 
 impl QueryInner {
@@ -2920,10 +4672,32 @@ impl QueryInner {
             info,
             addresses: Default::default(),
             pending_rpcs: SmallVec::default(),
+            reporters: Default::default(),
         }
     }
 }
 
+/// The dial of a [`Kademlia::find_and_dial`] query whose lookup finished.
+struct FindAndDial {
+    /// The query the dial belongs to.
+    query: QueryId,
+    /// The statistics of the lookup.
+    stats: QueryStats,
+    peer: PeerId,
+    /// The addresses the peer is dialed at.
+    addresses: Vec<Multiaddr>,
+}
+
+/// A provider lookup delegated to the router set via [`Kademlia::set_routing`].
+struct DelegatedLookup {
+    /// The [`Kademlia::get_providers`] query the lookup belongs to.
+    query: QueryId,
+    /// The key for which to search for providers.
+    key: record::Key,
+    /// The outcome of the DHT query, held back until the router finished.
+    outcome: Option<(QueryStats, ProgressStep, GetProvidersResult)>,
+}
+
 /// The context of a [`QueryInfo::AddProvider`] query.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum AddProviderContext {
@@ -2948,6 +4722,9 @@ pub enum PutRecordContext {
     /// The context is a custom store operation targeting specific
     /// peers initiated by [`Kademlia::put_record_to`].
     Custom,
+    /// The context is the periodic repair of under-replicated records,
+    /// see [`KademliaConfig::set_record_repair_interval`].
+    Repair,
 }
 
 /// Information about a running query.
@@ -3018,6 +4795,49 @@ pub enum QueryInfo {
         /// i.e. the peers that are candidates for caching the record.
         cache_candidates: BTreeMap<kbucket::Distance, PeerId>,
     },
+
+    /// A query checking the replication of a stored record, started by
+    /// the record repair job.
+    ///
+    /// See [`KademliaConfig::set_record_repair_interval`].
+    RepairRecord {
+        record: Record,
+        /// The peers that returned a valid record for the key so far.
+        holders: Vec<PeerId>,
+    },
+
+    /// A query initiated by [`Kademlia::put_record_at`].
+    PutRecordAt {
+        record: Record,
+        /// The peers the record is stored at.
+        peers: Vec<PeerId>,
+        /// The peers that acknowledged storing the record so far.
+        success: Vec<PeerId>,
+    },
+
+    /// A query initiated by [`Kademlia::get_record_from`].
+    GetRecordFrom {
+        /// The key to look for.
+        key: record::Key,
+        /// The peers the record is requested from.
+        peers: Vec<PeerId>,
+        /// The records returned so far.
+        records: Vec<PeerRecord>,
+        /// The peers that responded without a record so far.
+        not_found: Vec<PeerId>,
+    },
+
+    /// The lookup of a query initiated by [`Kademlia::find_and_dial`].
+    FindAndDial {
+        /// The peer to look up and dial.
+        peer: PeerId,
+    },
+
+    /// A request to a peer on probation, see [`KademliaConfig::set_peer_probation`].
+    Probation {
+        /// The peer on probation.
+        peer: PeerId,
+    },
 }
 
 impl QueryInfo {
@@ -3033,6 +4853,12 @@ impl QueryInfo {
                 key: key.clone(),
                 user_data: query_id,
             },
+            QueryInfo::FindAndDial { peer } | QueryInfo::Probation { peer } => {
+                KademliaHandlerIn::FindNodeReq {
+                    key: peer.to_bytes(),
+                    user_data: query_id,
+                }
+            }
             QueryInfo::GetProviders { key, .. } => KademliaHandlerIn::GetProvidersReq {
                 key: key.clone(),
                 user_data: query_id,
@@ -3045,6 +4871,7 @@ impl QueryInfo {
                 AddProviderPhase::AddProvider {
                     provider_id,
                     external_addresses,
+                    metadata,
                     ..
                 } => KademliaHandlerIn::AddProvider {
                     key: key.clone(),
@@ -3052,6 +4879,7 @@ impl QueryInfo {
                         node_id: *provider_id,
                         multiaddrs: external_addresses.clone(),
                         connection_ty: crate::protocol::KadConnectionType::Connected,
+                        metadata: metadata.clone(),
                     },
                 },
             },
@@ -3069,6 +4897,18 @@ impl QueryInfo {
                     user_data: query_id,
                 },
             },
+            QueryInfo::PutRecordAt { record, .. } => KademliaHandlerIn::PutRecord {
+                record: record.clone(),
+                user_data: query_id,
+            },
+            QueryInfo::GetRecordFrom { key, .. } => KademliaHandlerIn::GetRecord {
+                key: key.clone(),
+                user_data: query_id,
+            },
+            QueryInfo::RepairRecord { record, .. } => KademliaHandlerIn::GetRecord {
+                key: record.key.clone(),
+                user_data: query_id,
+            },
         }
     }
 }
@@ -3086,6 +4926,8 @@ pub enum AddProviderPhase {
         provider_id: PeerId,
         /// The external addresses of the provider being advertised.
         external_addresses: Vec<Multiaddr>,
+        /// The metadata announced along with the provider record.
+        metadata: Vec<u8>,
         /// Query statistics from the finished `GetClosestPeers` phase.
         get_closest_peers_stats: QueryStats,
     },
@@ -3185,8 +5027,10 @@ pub enum RoutingUpdate {
     Pending,
     /// The routing table update failed, either because the
     /// corresponding bucket for the peer is full and the
-    /// pending slot(s) are occupied, or because the given
+    /// pending slot(s) are occupied, because the given
     /// peer ID is deemed invalid (e.g. refers to the local
-    /// peer ID).
+    /// peer ID), or because the address exceeds a limit set via
+    /// [`KademliaConfig::set_max_peers_per_ip_prefix`] or
+    /// [`KademliaConfig::set_max_bucket_peers_per_ip_prefix`].
     Failed,
 }
@@ -37,13 +37,14 @@ use libp2p_core::{
 use libp2p_identity as identity;
 use libp2p_identity::PeerId;
 use libp2p_noise as noise;
-use libp2p_swarm::{ConnectionId, Swarm, SwarmBuilder, SwarmEvent};
+use libp2p_swarm::{ConnectionId, ManualClock, Swarm, SwarmBuilder, SwarmEvent};
 use libp2p_yamux as yamux;
 use quickcheck::*;
 use rand::{random, rngs::StdRng, thread_rng, Rng, SeedableRng};
 use std::{
     collections::{HashMap, HashSet},
     num::NonZeroUsize,
+    sync::atomic::Ordering,
     time::Duration,
     u64,
 };
@@ -311,6 +312,107 @@ fn query_iter() {
     }
 }
 
+#[test]
+fn query_iter_with_custom_key_hasher() {
+    let mut cfg = KademliaConfig::default();
+    cfg.set_key_hasher(Arc::new(kbucket::IdentityHasher));
+    let mut swarms = build_connected_nodes_with_config(8, 1, cfg)
+        .into_iter()
+        .map(|(_a, s)| s)
+        .collect::<Vec<_>>();
+    let swarm_ids: Vec<_> = swarms.iter().map(Swarm::local_peer_id).cloned().collect();
+
+    let search_target = PeerId::random();
+    let search_target_key = kbucket::Key::peer_with_hasher(search_target, &kbucket::IdentityHasher);
+    let qid = swarms[0].behaviour_mut().get_closest_peers(search_target);
+
+    let mut expected_distances: Vec<_> = swarm_ids
+        .iter()
+        .skip(1)
+        .map(|p| kbucket::Key::peer_with_hasher(*p, &kbucket::IdentityHasher))
+        .map(|k| k.distance(&search_target_key))
+        .collect();
+    expected_distances.sort();
+
+    block_on(poll_fn(move |ctx| {
+        for swarm in swarms.iter_mut() {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(
+                        KademliaEvent::OutboundQueryProgressed {
+                            id,
+                            result: QueryResult::GetClosestPeers(Ok(ok)),
+                            ..
+                        },
+                    ))) => {
+                        assert_eq!(id, qid);
+                        let distances: Vec<_> = ok
+                            .peers
+                            .iter()
+                            .map(|p| swarm.behaviour().distance(&ok.key, p))
+                            .collect();
+                        assert_eq!(expected_distances, distances);
+                        return Poll::Ready(());
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn closest_peers_quorum() {
+    fn run(swarms: Vec<(Multiaddr, TestSwarm)>) -> bool {
+        let mut swarms = swarms.into_iter().map(|(_a, s)| s).collect::<Vec<_>>();
+        let qid = swarms[0]
+            .behaviour_mut()
+            .get_closest_peers(PeerId::random());
+
+        block_on(poll_fn(move |ctx| {
+            for swarm in swarms.iter_mut() {
+                loop {
+                    match swarm.poll_next_unpin(ctx) {
+                        Poll::Ready(Some(SwarmEvent::Behaviour(
+                            KademliaEvent::OutboundQueryProgressed {
+                                id,
+                                result: QueryResult::GetClosestPeers(Ok(ok)),
+                                ..
+                            },
+                        ))) => {
+                            assert_eq!(id, qid);
+                            assert!(!ok.peers.is_empty());
+                            return Poll::Ready(ok.low_confidence);
+                        }
+                        // Ignore any other event.
+                        Poll::Ready(Some(_)) => (),
+                        e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                        Poll::Pending => break,
+                    }
+                }
+            }
+            Poll::Pending
+        }))
+    }
+
+    let mut cfg = KademliaConfig::default();
+    cfg.set_closest_peers_quorum(Some(NonZeroUsize::new(2).unwrap()));
+
+    // Every peer is reported by all other peers.
+    assert!(!run(build_fully_connected_nodes_with_config(
+        5,
+        cfg.clone()
+    )));
+    // Every peer is only reported by its predecessor.
+    assert!(run(build_connected_nodes_with_config(5, 1, cfg)));
+    // Without a quorum, results are never flagged.
+    assert!(!run(build_connected_nodes(5, 1)));
+}
+
 #[test]
 fn unresponsive_not_returned_direct() {
     let _ = env_logger::try_init();
@@ -482,6 +584,116 @@ fn get_record_not_found() {
     }))
 }
 
+#[test]
+fn client_mode_refuses_inbound_requests() {
+    let mut swarms = build_nodes(2);
+
+    let second_id = *swarms[1].1.local_peer_id();
+    let second_addr = swarms[1].0.clone();
+    swarms[1].1.behaviour_mut().set_mode(Mode::Client);
+    assert_eq!(swarms[1].1.behaviour().mode(), Mode::Client);
+    swarms[0]
+        .1
+        .behaviour_mut()
+        .add_address(&second_id, second_addr);
+
+    let mut swarms = swarms
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
+
+    let target_key = record::Key::from(random_multihash());
+    let qid = swarms[0].behaviour_mut().get_record(target_key);
+
+    block_on(poll_fn(move |ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(
+                        KademliaEvent::OutboundQueryProgressed {
+                            id,
+                            result: QueryResult::GetRecord(Err(e)),
+                            ..
+                        },
+                    ))) => {
+                        assert_eq!(id, qid);
+                        match e {
+                            GetRecordError::NotFound { closest_peers, .. } => {
+                                // The peer in client mode did not answer the request.
+                                assert!(closest_peers.is_empty());
+                                return Poll::Ready(());
+                            }
+                            e => panic!("Unexpected error result: {e:?}"),
+                        }
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn inbound_requests_exceeding_rate_limit_are_dropped() {
+    let mut server_cfg = KademliaConfig::default();
+    server_cfg.set_inbound_rate_limit(Some(InboundRateLimit::new(
+        std::num::NonZeroU32::new(1).unwrap(),
+        Duration::from_secs(60),
+    )));
+    let (_, client) = build_node();
+    let (server_addr, server) = build_node_with_config(server_cfg);
+    let server_id = *server.local_peer_id();
+    let mut swarms = vec![client, server];
+    swarms[0]
+        .behaviour_mut()
+        .add_address(&server_id, server_addr);
+
+    // Returns the closest peers of a `get_record` query that did not find a record.
+    let get_record = |swarms: &mut Vec<TestSwarm>| {
+        let qid = swarms[0]
+            .behaviour_mut()
+            .get_record(record::Key::from(random_multihash()));
+        block_on(poll_fn(|ctx| {
+            for swarm in swarms.iter_mut() {
+                loop {
+                    match swarm.poll_next_unpin(ctx) {
+                        Poll::Ready(Some(SwarmEvent::Behaviour(
+                            KademliaEvent::OutboundQueryProgressed {
+                                id,
+                                result:
+                                    QueryResult::GetRecord(Err(GetRecordError::NotFound {
+                                        closest_peers,
+                                        ..
+                                    })),
+                                ..
+                            },
+                        ))) if id == qid => return Poll::Ready(closest_peers),
+                        // Ignore any other event.
+                        Poll::Ready(Some(_)) => (),
+                        e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                        Poll::Pending => break,
+                    }
+                }
+            }
+
+            Poll::Pending
+        }))
+    };
+
+    assert_eq!(get_record(&mut swarms), vec![server_id]);
+    // The second request exceeds the limit and is reset.
+    assert!(get_record(&mut swarms).is_empty());
+
+    let stats = swarms[1].behaviour().inbound_throttle_stats();
+    assert_eq!(stats.throttled_by_peer_limit(), 1);
+    assert_eq!(stats.throttled_by_global_limit(), 0);
+}
+
 /// A node joining a fully connected network via three (ALPHA_VALUE) bootnodes
 /// should be able to put a record to the X closest nodes of the network where X
 /// is equal to the configured replication factor.
@@ -786,18 +998,387 @@ fn get_record() {
                                 GetRecordOk::FinishedWithNoAdditionalRecord { .. }
                             ));
                         }
-                        return Poll::Ready(());
+                        return Poll::Ready(());
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn targeted_record_operations() {
+    let mut swarms = build_nodes(3);
+
+    // Let the first peer know of the other two peers.
+    for i in 1..3 {
+        let (peer_id, address) = (*Swarm::local_peer_id(&swarms[i].1), swarms[i].0.clone());
+        swarms[0].1.behaviour_mut().add_address(&peer_id, address);
+    }
+
+    let mut swarms = swarms
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
+    let peer_ids: Vec<_> = swarms.iter().map(Swarm::local_peer_id).cloned().collect();
+    // A peer without any known address, which can never be reached.
+    let unreachable = PeerId::random();
+
+    let record = Record::new(random_multihash(), vec![4, 5, 6]);
+    let put_qid = swarms[0]
+        .behaviour_mut()
+        .put_record_at(record.clone(), [peer_ids[1], unreachable, peer_ids[1]]);
+    let mut get_qid = None;
+
+    block_on(poll_fn(move |ctx| {
+        for i in 0..swarms.len() {
+            loop {
+                match swarms[i].poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(
+                        KademliaEvent::OutboundQueryProgressed {
+                            id,
+                            result: QueryResult::PutRecordAt(r),
+                            ..
+                        },
+                    ))) => {
+                        assert_eq!(id, put_qid);
+                        assert_eq!(r.key, record.key);
+                        assert_eq!(r.success, vec![peer_ids[1]]);
+                        assert_eq!(r.failed, vec![unreachable]);
+                        assert!(swarms[2].behaviour_mut().store.get(&record.key).is_none());
+                        get_qid = Some(swarms[0].behaviour_mut().get_record_from(
+                            record.key.clone(),
+                            [peer_ids[1], peer_ids[2], unreachable],
+                        ));
+                    }
+                    Poll::Ready(Some(SwarmEvent::Behaviour(
+                        KademliaEvent::OutboundQueryProgressed {
+                            id,
+                            result: QueryResult::GetRecordFrom(r),
+                            ..
+                        },
+                    ))) => {
+                        assert_eq!(Some(id), get_qid);
+                        assert_eq!(r.key, record.key);
+                        assert_eq!(r.records.len(), 1);
+                        assert_eq!(r.records[0].peer, Some(peer_ids[1]));
+                        assert_eq!(r.records[0].record.value, record.value);
+                        assert_eq!(r.not_found, vec![peer_ids[2]]);
+                        assert_eq!(r.failed, vec![unreachable]);
+                        return Poll::Ready(());
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn find_and_dial() {
+    let mut swarms = build_nodes(3);
+
+    // Connect the peers in a chain, such that the first peer only learns about
+    // the address of the last peer through the lookup.
+    for i in 0..2 {
+        let (peer_id, address) = (
+            *Swarm::local_peer_id(&swarms[i + 1].1),
+            swarms[i + 1].0.clone(),
+        );
+        swarms[i].1.behaviour_mut().add_address(&peer_id, address);
+    }
+
+    let target_addr = swarms[2].0.clone();
+    let mut swarms = swarms
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
+    let target = *Swarm::local_peer_id(&swarms[2]);
+    let unknown = PeerId::random();
+
+    let found_qid = swarms[0].behaviour_mut().find_and_dial(target);
+    let mut not_found_qid = None;
+
+    block_on(poll_fn(move |ctx| {
+        for i in 0..swarms.len() {
+            loop {
+                match swarms[i].poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(
+                        KademliaEvent::OutboundQueryProgressed {
+                            id,
+                            result: QueryResult::FindAndDial(r),
+                            step,
+                            ..
+                        },
+                    ))) => {
+                        assert!(step.last);
+                        if id == found_qid {
+                            let ok = r.expect("Peer to be found and dialed.");
+                            assert_eq!(ok.peer, target);
+                            assert!(ok.addresses.contains(&target_addr));
+                            assert!(swarms[0].is_connected(&target));
+                            not_found_qid = Some(swarms[0].behaviour_mut().find_and_dial(unknown));
+                        } else {
+                            assert_eq!(Some(id), not_found_qid);
+                            match r {
+                                Err(FindAndDialError::NotFound { peer }) => {
+                                    assert_eq!(peer, unknown)
+                                }
+                                r => panic!("Unexpected result: {r:?}"),
+                            }
+                            return Poll::Ready(());
+                        }
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        Poll::Pending
+    }))
+}
+
+#[test]
+fn record_repair_job() {
+    /// Rejects records with an empty value.
+    #[derive(Debug)]
+    struct NonEmptyValidator;
+
+    impl RecordValidator for NonEmptyValidator {
+        fn validate(&self, record: &Record) -> bool {
+            !record.value.is_empty()
+        }
+    }
+
+    let mut cfg = KademliaConfig::default();
+    cfg.set_replication_interval(None);
+    cfg.set_publication_interval(None);
+    cfg.set_record_validator(Arc::new(NonEmptyValidator));
+    cfg.set_record_repair_interval(Some(Duration::from_secs(60 * 60)));
+
+    let mut swarms = build_fully_connected_nodes_with_config(3, cfg)
+        .into_iter()
+        .map(|(_addr, swarm)| swarm)
+        .collect::<Vec<_>>();
+
+    let valid = Record::new(random_multihash(), vec![4, 5, 6]);
+    let invalid = Record::new(random_multihash(), vec![]);
+    swarms[0].behaviour_mut().store.put(valid.clone()).unwrap();
+    swarms[0]
+        .behaviour_mut()
+        .store
+        .put(invalid.clone())
+        .unwrap();
+    swarms[0]
+        .behaviour_mut()
+        .record_repair_job
+        .as_mut()
+        .unwrap()
+        .asap();
+
+    let mut removed = false;
+    let mut repaired = false;
+    block_on(poll_fn(|ctx| {
+        for swarm in &mut swarms {
+            loop {
+                match swarm.poll_next_unpin(ctx) {
+                    Poll::Ready(Some(SwarmEvent::Behaviour(
+                        KademliaEvent::InvalidRecordRemoved { record },
+                    ))) => {
+                        assert_eq!(record, invalid);
+                        removed = true;
+                    }
+                    Poll::Ready(Some(SwarmEvent::Behaviour(
+                        KademliaEvent::OutboundQueryProgressed {
+                            result: QueryResult::RepairRecord(res),
+                            ..
+                        },
+                    ))) => {
+                        assert_eq!(res.unwrap().key, valid.key);
+                        repaired = true;
+                    }
+                    // Ignore any other event.
+                    Poll::Ready(Some(_)) => (),
+                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                    Poll::Pending => break,
+                }
+            }
+        }
+
+        if removed && repaired {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }));
+
+    assert!(swarms[0].behaviour_mut().store.get(&invalid.key).is_none());
+    for swarm in &mut swarms[1..] {
+        assert_eq!(
+            swarm
+                .behaviour_mut()
+                .store
+                .get(&valid.key)
+                .map(|r| r.value.clone()),
+            Some(valid.value.clone())
+        );
+    }
+}
+
+#[test]
+fn record_repair_job_follows_clock() {
+    #[derive(Debug)]
+    struct RejectAllValidator;
+
+    impl RecordValidator for RejectAllValidator {
+        fn validate(&self, _: &Record) -> bool {
+            false
+        }
+    }
+
+    let interval = Duration::from_secs(60 * 60);
+    let clock = ManualClock::new();
+    let mut cfg = KademliaConfig::default();
+    cfg.set_replication_interval(None);
+    cfg.set_publication_interval(None);
+    cfg.set_record_validator(Arc::new(RejectAllValidator));
+    cfg.set_record_repair_interval(Some(interval));
+    cfg.set_clock(clock.clone());
+
+    let (_, mut swarm) = build_node_with_config(cfg);
+    let record = Record::new(random_multihash(), vec![1, 2, 3]);
+    swarm.behaviour_mut().store.put(record.clone()).unwrap();
+
+    let mut removed = false;
+    let mut poll_swarm = |swarm: &mut TestSwarm| {
+        block_on(poll_fn(|ctx| loop {
+            match swarm.poll_next_unpin(ctx) {
+                Poll::Ready(Some(SwarmEvent::Behaviour(KademliaEvent::InvalidRecordRemoved {
+                    record: r,
+                }))) => {
+                    assert_eq!(r, record);
+                    removed = true;
+                }
+                Poll::Ready(Some(_)) => {}
+                e @ Poll::Ready(None) => panic!("Unexpected return value: {e:?}"),
+                Poll::Pending => return Poll::Ready(()),
+            }
+        }))
+    };
+
+    // The job does not run before the interval has passed on the clock.
+    poll_swarm(&mut swarm);
+    clock.advance(interval - Duration::from_secs(1));
+    poll_swarm(&mut swarm);
+    assert!(swarm.behaviour_mut().store.get(&record.key).is_some());
+
+    clock.advance(Duration::from_secs(1));
+    poll_swarm(&mut swarm);
+    assert!(removed);
+    assert!(swarm.behaviour_mut().store.get(&record.key).is_none());
+}
+
+#[test]
+fn put_record_in_namespace() {
+    let num_nodes = 8;
+    let mut cfg = KademliaConfig::default();
+    cfg.set_record_namespace(
+        b"/app/".to_vec(),
+        NonZeroUsize::new(2).unwrap(),
+        Quorum::All,
+    );
+    cfg.set_record_namespace(
+        b"/app/pk/".to_vec(),
+        NonZeroUsize::new(4).unwrap(),
+        Quorum::One,
+    );
+
+    for (key, replication_factor) in [("/app/x", 2), ("/app/pk/x", 4), ("/other/x", num_nodes - 1)]
+    {
+        let mut swarms = build_fully_connected_nodes_with_config(num_nodes, cfg.clone())
+            .into_iter()
+            .map(|(_addr, swarm)| swarm)
+            .collect::<Vec<_>>();
+
+        let record = Record::new(record::Key::new(&key), vec![4, 5, 6]);
+        let qid = swarms[0]
+            .behaviour_mut()
+            .put_record(
+                record.clone(),
+                Quorum::N(NonZeroUsize::new(replication_factor).unwrap()),
+            )
+            .unwrap();
+
+        block_on(poll_fn(|ctx| {
+            for swarm in &mut swarms {
+                loop {
+                    match swarm.poll_next_unpin(ctx) {
+                        Poll::Ready(Some(SwarmEvent::Behaviour(
+                            KademliaEvent::OutboundQueryProgressed {
+                                id,
+                                result: QueryResult::PutRecord(res),
+                                ..
+                            },
+                        ))) => {
+                            assert_eq!(id, qid);
+                            res.unwrap();
+                            return Poll::Ready(());
+                        }
+                        // Ignore any other event.
+                        Poll::Ready(Some(_)) => (),
+                        e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
+                        Poll::Pending => break,
                     }
-                    // Ignore any other event.
-                    Poll::Ready(Some(_)) => (),
-                    e @ Poll::Ready(_) => panic!("Unexpected return value: {e:?}"),
-                    Poll::Pending => break,
                 }
             }
-        }
 
-        Poll::Pending
-    }))
+            Poll::Pending
+        }));
+
+        let stored_at = swarms[1..]
+            .iter()
+            .filter(|s| s.behaviour().store.get(&record.key).is_some())
+            .count();
+        assert_eq!(stored_at, replication_factor, "{key}");
+    }
+}
+
+#[test]
+fn put_record_with_quorum_exceeding_namespace_is_rejected() {
+    let local_peer_id = PeerId::random();
+    let mut cfg = KademliaConfig::default();
+    cfg.set_record_namespace(
+        b"/app/".to_vec(),
+        NonZeroUsize::new(2).unwrap(),
+        Quorum::All,
+    );
+    let mut kademlia = Kademlia::with_config(local_peer_id, MemoryStore::new(local_peer_id), cfg);
+
+    let record = Record::new(record::Key::new(&"/app/x"), vec![4, 5, 6]);
+    assert!(matches!(
+        kademlia.put_record(record.clone(), Quorum::N(NonZeroUsize::new(3).unwrap())),
+        Err(store::Error::QuorumTooLarge)
+    ));
+    assert!(kademlia.store.get(&record.key).is_none());
+
+    kademlia
+        .put_record(record.clone(), Quorum::N(NonZeroUsize::new(2).unwrap()))
+        .unwrap();
+    assert!(kademlia.store.get(&record.key).is_some());
 }
 
 #[test]
@@ -1077,6 +1658,77 @@ fn exceed_jobs_max_queries() {
     }))
 }
 
+#[test]
+fn paused_queries_held_until_resumed() {
+    let (_addr, mut swarm) = build_node();
+    swarm.behaviour_mut().pause();
+    assert!(swarm.behaviour().is_paused());
+
+    let qid = swarm.behaviour_mut().get_closest_peers(PeerId::random());
+
+    let woken = Arc::new(WakeFlag::default());
+    let waker = futures::task::waker(woken.clone());
+    let mut ctx = Context::from_waker(&waker);
+
+    // There are no other nodes, so the query would finish instantly if not paused.
+    loop {
+        woken.0.store(false, Ordering::SeqCst);
+        match swarm.poll_next_unpin(&mut ctx) {
+            Poll::Ready(Some(SwarmEvent::Behaviour(e))) => panic!("Unexpected event: {e:?}"),
+            Poll::Ready(_) => {}
+            Poll::Pending if woken.0.load(Ordering::SeqCst) => {}
+            Poll::Pending => break,
+        }
+    }
+    assert!(swarm.behaviour().query(&qid).is_some());
+
+    // Resuming wakes the task to drive the query.
+    swarm.behaviour_mut().resume();
+    assert!(woken.0.load(Ordering::SeqCst));
+
+    block_on(poll_fn(move |ctx| loop {
+        match swarm.poll_next_unpin(ctx) {
+            Poll::Ready(Some(SwarmEvent::Behaviour(KademliaEvent::OutboundQueryProgressed {
+                id,
+                result: QueryResult::GetClosestPeers(Ok(_)),
+                ..
+            }))) => {
+                assert_eq!(id, qid);
+                return Poll::Ready(());
+            }
+            Poll::Ready(Some(SwarmEvent::Behaviour(e))) => panic!("Unexpected event: {e:?}"),
+            Poll::Ready(_) => {}
+            Poll::Pending => panic!("Expected event"),
+        }
+    }))
+}
+
+#[test]
+fn paused_queries_fail_if_configured() {
+    let mut cfg = KademliaConfig::default();
+    cfg.set_paused_queries(KademliaPausedQueries::Fail);
+    let (_addr, mut swarm) = build_node_with_config(cfg);
+    swarm.behaviour_mut().pause();
+
+    let qid = swarm.behaviour_mut().get_closest_peers(PeerId::random());
+
+    block_on(poll_fn(move |ctx| loop {
+        match swarm.poll_next_unpin(ctx) {
+            Poll::Ready(Some(SwarmEvent::Behaviour(KademliaEvent::OutboundQueryProgressed {
+                id,
+                result: QueryResult::GetClosestPeers(Err(GetClosestPeersError::Timeout { .. })),
+                ..
+            }))) => {
+                assert_eq!(id, qid);
+                return Poll::Ready(());
+            }
+            Poll::Ready(Some(SwarmEvent::Behaviour(e))) => panic!("Unexpected event: {e:?}"),
+            Poll::Ready(_) => {}
+            Poll::Pending => panic!("Expected event"),
+        }
+    }))
+}
+
 #[test]
 fn exp_decr_expiration_overflow() {
     fn prop_no_panic(ttl: Duration, factor: u32) {
@@ -1316,6 +1968,7 @@ fn network_behaviour_on_address_change() {
         endpoint: &endpoint,
         failed_addresses: &[],
         other_established: 0,
+        user_data: None,
     }));
 
     // At this point the remote is not yet known to support the
@@ -1377,6 +2030,256 @@ fn network_behaviour_on_address_change() {
     );
 }
 
+#[test]
+fn network_behaviour_on_peer_protocols_changed() {
+    let local_peer_id = PeerId::random();
+    let remote_peer_id = PeerId::random();
+    let address: Multiaddr = Protocol::Memory(1).into();
+
+    let mut kademlia = Kademlia::new(local_peer_id, MemoryStore::new(local_peer_id));
+    kademlia.add_address(&remote_peer_id, address);
+
+    // Changes of unrelated protocols keep the peer in the routing table.
+    kademlia.on_swarm_event(FromSwarm::PeerProtocolsChanged(PeerProtocolsChanged {
+        peer_id: remote_peer_id,
+        added: &[],
+        removed: &["/ipfs/ping/1.0.0".to_string()],
+    }));
+    assert_eq!(
+        kademlia.kbuckets().map(|b| b.num_entries()).sum::<usize>(),
+        1
+    );
+
+    kademlia.on_swarm_event(FromSwarm::PeerProtocolsChanged(PeerProtocolsChanged {
+        peer_id: remote_peer_id,
+        added: &[],
+        removed: &[String::from_utf8(crate::protocol::DEFAULT_PROTO_NAME.to_vec()).unwrap()],
+    }));
+    assert_eq!(kademlia.kbuckets().count(), 0);
+}
+
+#[test]
+fn peer_removed_once_no_configured_protocol_remains() {
+    let local_peer_id = PeerId::random();
+    let remote_peer_id = PeerId::random();
+    let address: Multiaddr = Protocol::Memory(1).into();
+    let protocols = ["/kad/1.0.0".to_string(), "/kad/2.0.0".to_string()];
+
+    let mut cfg = KademliaConfig::default();
+    cfg.set_protocol_names(
+        protocols
+            .iter()
+            .map(|p| Cow::Owned(p.as_bytes().to_vec()))
+            .collect(),
+    );
+    let mut kademlia = Kademlia::with_config(local_peer_id, MemoryStore::new(local_peer_id), cfg);
+    kademlia.add_address(&remote_peer_id, address.clone());
+    kademlia.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+        peer_id: remote_peer_id,
+        connection_id: ConnectionId::new_unchecked(0),
+        endpoint: &ConnectedPoint::Dialer {
+            address,
+            role_override: Endpoint::Dialer,
+        },
+        failed_addresses: &[],
+        other_established: 0,
+        user_data: None,
+    }));
+    kademlia.on_swarm_event(FromSwarm::PeerProtocolsChanged(PeerProtocolsChanged {
+        peer_id: remote_peer_id,
+        added: &protocols,
+        removed: &[],
+    }));
+
+    // The peer still supports the other protocol.
+    kademlia.on_swarm_event(FromSwarm::PeerProtocolsChanged(PeerProtocolsChanged {
+        peer_id: remote_peer_id,
+        added: &[],
+        removed: &protocols[..1],
+    }));
+    assert_eq!(
+        kademlia.kbuckets().map(|b| b.num_entries()).sum::<usize>(),
+        1
+    );
+
+    kademlia.on_swarm_event(FromSwarm::PeerProtocolsChanged(PeerProtocolsChanged {
+        peer_id: remote_peer_id,
+        added: &[],
+        removed: &protocols[1..],
+    }));
+    assert_eq!(kademlia.kbuckets().count(), 0);
+}
+
+#[test]
+fn network_behaviour_on_dial_failure() {
+    let local_peer_id = PeerId::random();
+    let remote_peer_id = PeerId::random();
+    let address: Multiaddr = Protocol::Memory(1).into();
+
+    let other_address: Multiaddr = Protocol::Memory(2).into();
+
+    let mut kademlia = Kademlia::new(local_peer_id, MemoryStore::new(local_peer_id));
+    kademlia.add_address(&remote_peer_id, address.clone());
+    kademlia.add_address(&remote_peer_id, other_address.clone());
+    let addresses = |kademlia: &mut Kademlia<MemoryStore>| {
+        kademlia
+            .kbucket(remote_peer_id)
+            .map(|bucket| {
+                bucket
+                    .iter()
+                    .flat_map(|e| e.node.value.iter().cloned().collect::<Vec<_>>())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    };
+
+    // A transient failure keeps the peer in the routing table.
+    kademlia.on_swarm_event(FromSwarm::DialFailure(DialFailure {
+        peer_id: Some(remote_peer_id),
+        error: &DialError::Aborted,
+        connection_id: ConnectionId::new_unchecked(0),
+        user_data: None,
+    }));
+    assert_eq!(
+        addresses(&mut kademlia),
+        vec![address.clone(), other_address.clone()]
+    );
+
+    // Another peer at one of the addresses only removes that address.
+    kademlia.on_swarm_event(FromSwarm::DialFailure(DialFailure {
+        peer_id: Some(remote_peer_id),
+        error: &DialError::WrongPeerId {
+            obtained: PeerId::random(),
+            endpoint: ConnectedPoint::Dialer {
+                address,
+                role_override: Endpoint::Dialer,
+            },
+        },
+        connection_id: ConnectionId::new_unchecked(1),
+        user_data: None,
+    }));
+    assert_eq!(addresses(&mut kademlia), vec![other_address.clone()]);
+
+    kademlia.on_swarm_event(FromSwarm::DialFailure(DialFailure {
+        peer_id: Some(remote_peer_id),
+        error: &DialError::LocalPeerId {
+            endpoint: ConnectedPoint::Dialer {
+                address: other_address,
+                role_override: Endpoint::Dialer,
+            },
+        },
+        connection_id: ConnectionId::new_unchecked(2),
+        user_data: None,
+    }));
+    assert_eq!(kademlia.kbuckets().count(), 0);
+}
+
+#[test]
+fn insert_only_peers_advertising_kademlia() {
+    let local_peer_id = PeerId::random();
+    let mut cfg = KademliaConfig::default();
+    cfg.set_protocol_confirmation(KademliaProtocolConfirmation::Advertised);
+    let mut kademlia = Kademlia::with_config(local_peer_id, MemoryStore::new(local_peer_id), cfg);
+    let kad_protocol = String::from_utf8(crate::protocol::DEFAULT_PROTO_NAME.to_vec()).unwrap();
+
+    let connect = |kademlia: &mut Kademlia<MemoryStore>, connection: usize| {
+        let peer_id = PeerId::random();
+        let connection_id = ConnectionId::new_unchecked(connection);
+        let endpoint = ConnectedPoint::Dialer {
+            address: Protocol::Memory(connection as u64).into(),
+            role_override: Endpoint::Dialer,
+        };
+        kademlia.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+            peer_id,
+            connection_id,
+            endpoint: &endpoint,
+            failed_addresses: &[],
+            other_established: 0,
+            user_data: None,
+        }));
+        (peer_id, connection_id, endpoint)
+    };
+    let advertise = |kademlia: &mut Kademlia<MemoryStore>, peer_id, protocol: &str| {
+        kademlia.on_swarm_event(FromSwarm::PeerProtocolsChanged(PeerProtocolsChanged {
+            peer_id,
+            added: &[protocol.to_string()],
+            removed: &[],
+        }));
+    };
+    let num_entries = |kademlia: &mut Kademlia<MemoryStore>| {
+        kademlia.kbuckets().map(|b| b.num_entries()).sum::<usize>()
+    };
+
+    // A peer negotiating Kademlia is held back until it advertises Kademlia.
+    let (peer_id, connection_id, endpoint) = connect(&mut kademlia, 1);
+    kademlia.on_connection_handler_event(
+        peer_id,
+        connection_id,
+        KademliaHandlerEvent::ProtocolConfirmed { endpoint },
+    );
+    assert_eq!(num_entries(&mut kademlia), 0);
+
+    advertise(&mut kademlia, peer_id, "/ipfs/ping/1.0.0");
+    assert_eq!(num_entries(&mut kademlia), 0);
+
+    advertise(&mut kademlia, peer_id, &kad_protocol);
+    assert_eq!(num_entries(&mut kademlia), 1);
+
+    // A peer advertising Kademlia is inserted once it negotiates Kademlia.
+    let (peer_id, connection_id, endpoint) = connect(&mut kademlia, 2);
+    advertise(&mut kademlia, peer_id, &kad_protocol);
+    assert_eq!(num_entries(&mut kademlia), 1);
+
+    kademlia.on_connection_handler_event(
+        peer_id,
+        connection_id,
+        KademliaHandlerEvent::ProtocolConfirmed { endpoint },
+    );
+    assert_eq!(num_entries(&mut kademlia), 2);
+}
+
+#[test]
+fn add_address_limits_peers_per_ip_prefix() {
+    let local_peer_id = PeerId::random();
+    let mut cfg = KademliaConfig::default();
+    cfg.set_max_peers_per_ip_prefix(Some(2));
+    let mut kademlia = Kademlia::with_config(local_peer_id, MemoryStore::new(local_peer_id), cfg);
+
+    let mut add = |addr: &str| kademlia.add_address(&PeerId::random(), addr.parse().unwrap());
+    assert!(matches!(
+        add("/ip4/10.0.0.1/tcp/4001"),
+        RoutingUpdate::Success
+    ));
+    assert!(matches!(
+        add("/ip4/10.0.0.2/tcp/4001"),
+        RoutingUpdate::Success
+    ));
+    assert!(matches!(
+        add("/ip4/10.0.0.3/tcp/4001"),
+        RoutingUpdate::Failed
+    ));
+    assert!(matches!(
+        add("/ip4/10.0.1.1/tcp/4001"),
+        RoutingUpdate::Success
+    ));
+    assert!(matches!(
+        add("/ip6/2001:db8::1/tcp/4001"),
+        RoutingUpdate::Success
+    ));
+
+    // A known peer can't gain an address in the exhausted network either.
+    let peer = PeerId::random();
+    let update = kademlia.add_address(&peer, "/ip4/10.0.2.1/tcp/4001".parse().unwrap());
+    assert!(matches!(update, RoutingUpdate::Success));
+    let update = kademlia.add_address(&peer, "/ip4/10.0.0.4/tcp/4001".parse().unwrap());
+    assert!(matches!(update, RoutingUpdate::Failed));
+
+    assert_eq!(
+        kademlia.kbuckets().map(|b| b.num_entries()).sum::<usize>(),
+        5
+    );
+}
+
 #[test]
 fn get_providers_single() {
     fn prop(key: record::Key) {
@@ -1431,9 +2334,81 @@ fn get_providers_single() {
     QuickCheck::new().tests(10).quickcheck(prop as fn(_))
 }
 
+#[test]
+fn local_provider_advertises_external_addresses_only() {
+    fn provider_addresses(cfg: KademliaConfig) -> Vec<Multiaddr> {
+        let local_id = PeerId::random();
+        let mut kademlia = Kademlia::with_config(local_id, MemoryStore::new(local_id), cfg);
+        let listen_addr: Multiaddr = "/ip4/192.168.0.2/tcp/4001".parse().unwrap();
+        let external_addr: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+        kademlia.on_swarm_event(FromSwarm::NewListenAddr(
+            libp2p_swarm::behaviour::NewListenAddr {
+                listener_id: libp2p_core::transport::ListenerId::new(),
+                addr: &listen_addr,
+                transport: libp2p_core::transport::TransportKind::Tcp,
+            },
+        ));
+        kademlia.on_swarm_event(FromSwarm::NewExternalAddr(
+            libp2p_swarm::behaviour::NewExternalAddr {
+                addr: &external_addr,
+            },
+        ));
+
+        let key = Key::from(random_multihash());
+        kademlia.start_providing(key.clone()).unwrap();
+        let providers = kademlia.provider_peers(&key, &PeerId::random());
+        assert_eq!(providers.len(), 1);
+        assert_eq!(providers[0].node_id, local_id);
+
+        providers[0].multiaddrs.clone()
+    }
+
+    assert_eq!(
+        provider_addresses(Default::default()),
+        vec!["/ip4/1.2.3.4/tcp/4001".parse::<Multiaddr>().unwrap()]
+    );
+
+    let mut cfg = KademliaConfig::default();
+    cfg.set_advertise_listen_addresses(true);
+    assert_eq!(
+        provider_addresses(cfg),
+        vec![
+            "/ip4/192.168.0.2/tcp/4001".parse::<Multiaddr>().unwrap(),
+            "/ip4/1.2.3.4/tcp/4001".parse().unwrap()
+        ]
+    );
+}
+
+#[test]
+fn provider_metadata_is_bounded() {
+    let local_id = PeerId::random();
+    let mut kademlia = Kademlia::new(local_id, MemoryStore::new(local_id));
+    let key = Key::from(random_multihash());
+
+    assert!(matches!(
+        kademlia.start_providing_with_metadata(
+            key.clone(),
+            vec![0; crate::MAX_PROVIDER_METADATA_SIZE + 1]
+        ),
+        Err(store::Error::ValueTooLarge)
+    ));
+    assert!(kademlia.store.providers(&key).is_empty());
+
+    let metadata = vec![0; crate::MAX_PROVIDER_METADATA_SIZE];
+    kademlia
+        .start_providing_with_metadata(key.clone(), metadata.clone())
+        .unwrap();
+    let providers = kademlia.provider_peers(&key, &PeerId::random());
+    assert_eq!(providers.len(), 1);
+    assert_eq!(providers[0].metadata, metadata);
+}
+
 fn get_providers_limit<const N: usize>() {
     fn prop<const N: usize>(key: record::Key) {
-        let mut swarms = build_nodes(3);
+        // The nodes have no external addresses, thus advertise the listen addresses.
+        let mut cfg = KademliaConfig::default();
+        cfg.set_advertise_listen_addresses(true);
+        let mut swarms = build_nodes_with_config(3, cfg);
 
         // Let first peer know of second peer and second peer know of third peer.
         for i in 0..2 {
@@ -1454,7 +2429,7 @@ fn get_providers_limit<const N: usize>() {
         for swarm in swarms.iter_mut().take(3).skip(1) {
             swarm
                 .behaviour_mut()
-                .start_providing(key.clone())
+                .start_providing_with_metadata(key.clone(), b"/ipfs/bitswap/1.2.0".to_vec())
                 .expect("could not provide");
         }
 
@@ -1487,6 +2462,8 @@ fn get_providers_limit<const N: usize>() {
                                 if let GetProvidersOk::FoundProviders {
                                     key: found_key,
                                     providers,
+                                    addresses,
+                                    metadata,
                                 } = ok
                                 {
                                     // There are a total of 2 providers.
@@ -1494,6 +2471,15 @@ fn get_providers_limit<const N: usize>() {
                                     for provider in &providers {
                                         // Providers should be either 2 or 3
                                         assert_ne!(swarm.local_peer_id(), provider);
+                                        // Providers are returned along with their addresses.
+                                        assert!(addresses
+                                            .get(provider)
+                                            .map_or(false, |addrs| !addrs.is_empty()));
+                                        // As well as the metadata they announced.
+                                        assert_eq!(
+                                            metadata.get(provider).map(Vec::as_slice),
+                                            Some(&b"/ipfs/bitswap/1.2.0"[..])
+                                        );
                                     }
                                     all_providers.extend(providers);
 
@@ -1531,3 +2517,247 @@ fn get_providers_limit_n_2() {
 fn get_providers_limit_n_5() {
     get_providers_limit::<5>();
 }
+
+/// A router answering every lookup with a fixed provider after a delay.
+struct DelayedRouter {
+    provider: PeerId,
+    lookups: VecDeque<(LookupId, Delay)>,
+    events: VecDeque<RoutingEvent>,
+}
+
+impl Routing for DelayedRouter {
+    fn find_providers(&mut self, id: LookupId, _: &record::Key) {
+        self.lookups
+            .push_back((id, Delay::new(Duration::from_millis(100))));
+    }
+
+    fn poll_providers(&mut self, cx: &mut Context<'_>) -> Poll<RoutingEvent> {
+        if let Some(event) = self.events.pop_front() {
+            return Poll::Ready(event);
+        }
+        if let Some((id, delay)) = self.lookups.front_mut() {
+            if delay.poll_unpin(cx).is_ready() {
+                let id = *id;
+                self.lookups.pop_front();
+                self.events.push_back(RoutingEvent::Finished { id });
+                return Poll::Ready(RoutingEvent::FoundProviders {
+                    id,
+                    providers: HashSet::from([self.provider]),
+                });
+            }
+        }
+        Poll::Pending
+    }
+}
+
+#[test]
+fn get_providers_delegated_routing() {
+    let (_, mut swarm) = build_node();
+    let provider = PeerId::random();
+    swarm.behaviour_mut().set_routing(DelayedRouter {
+        provider,
+        lookups: Default::default(),
+        events: Default::default(),
+    });
+
+    let key = record::Key::from(random_multihash());
+    let query_id = swarm.behaviour_mut().get_providers(key);
+
+    let mut found = HashSet::new();
+    block_on(async {
+        loop {
+            match swarm.next().await.unwrap() {
+                SwarmEvent::Behaviour(KademliaEvent::OutboundQueryProgressed {
+                    id,
+                    result: QueryResult::GetProviders(Ok(ok)),
+                    step,
+                    ..
+                }) if id == query_id => match ok {
+                    GetProvidersOk::FoundProviders { providers, .. } => {
+                        assert!(!step.last);
+                        found.extend(providers);
+                    }
+                    GetProvidersOk::FinishedWithNoAdditionalRecord { .. } => {
+                        assert!(step.last);
+                        break;
+                    }
+                },
+                SwarmEvent::Behaviour(e) => panic!("Unexpected event: {e:?}"),
+                _ => {}
+            }
+        }
+    });
+
+    assert_eq!(found, HashSet::from([provider]));
+}
+
+#[test]
+fn record_peer_latency_is_smoothed() {
+    let local_peer_id = PeerId::random();
+    let mut cfg = KademliaConfig::default();
+    cfg.set_latency_weighting(true);
+    let mut kademlia = Kademlia::with_config(local_peer_id, MemoryStore::new(local_peer_id), cfg);
+
+    let peer = PeerId::random();
+    kademlia.add_address(&peer, "/ip4/10.0.0.1/tcp/4001".parse().unwrap());
+    assert_eq!(kademlia.peer_latency(&peer), None);
+
+    kademlia.record_peer_latency(peer, Duration::from_millis(80));
+    assert_eq!(
+        kademlia.peer_latency(&peer),
+        Some(Duration::from_millis(80))
+    );
+
+    kademlia.record_peer_latency(peer, Duration::from_millis(160));
+    assert_eq!(
+        kademlia.peer_latency(&peer),
+        Some(Duration::from_millis(90))
+    );
+
+    kademlia.remove_peer(&peer);
+    assert_eq!(kademlia.peer_latency(&peer), None);
+}
+
+#[test]
+fn peer_latency_expires_without_samples() {
+    let local_peer_id = PeerId::random();
+    let clock = ManualClock::new();
+    let mut cfg = KademliaConfig::default();
+    cfg.set_latency_weighting(true);
+    cfg.set_clock(clock.clone());
+    let mut kademlia = Kademlia::with_config(local_peer_id, MemoryStore::new(local_peer_id), cfg);
+
+    let peer = PeerId::random();
+    kademlia.record_peer_latency(peer, Duration::from_millis(80));
+    clock.advance(Duration::from_secs(29 * 60));
+    assert_eq!(
+        kademlia.peer_latency(&peer),
+        Some(Duration::from_millis(80))
+    );
+
+    clock.advance(Duration::from_secs(60));
+    assert_eq!(kademlia.peer_latency(&peer), None);
+
+    // A new sample is not smoothed with the expired latency.
+    kademlia.record_peer_latency(peer, Duration::from_millis(160));
+    assert_eq!(
+        kademlia.peer_latency(&peer),
+        Some(Duration::from_millis(160))
+    );
+}
+
+#[test]
+fn peers_on_probation_are_inserted_once_they_answer() {
+    let mut cfg = KademliaConfig::default();
+    cfg.set_peer_probation(true);
+    let (_, mut probing) = build_node_with_config(cfg);
+    let (addr, mut other) = build_node();
+    let probing_id = *probing.local_peer_id();
+    let other_id = *other.local_peer_id();
+    let probing_addr = block_on(async {
+        loop {
+            if let SwarmEvent::NewListenAddr { address, .. } = probing.select_next_some().await {
+                return address;
+            }
+        }
+    });
+    other.behaviour_mut().add_address(&probing_id, probing_addr);
+    probing.dial(addr).unwrap();
+
+    block_on(poll_fn(move |ctx| {
+        loop {
+            match other.poll_next_unpin(ctx) {
+                // Make the probing peer confirm the Kademlia protocol on its connection.
+                Poll::Ready(Some(SwarmEvent::ConnectionEstablished { .. })) => {
+                    other.behaviour_mut().get_closest_peers(PeerId::random());
+                }
+                Poll::Ready(..) => {}
+                Poll::Pending => break,
+            }
+        }
+        loop {
+            match probing.poll_next_unpin(ctx) {
+                Poll::Ready(Some(SwarmEvent::Behaviour(KademliaEvent::RoutingUpdated {
+                    peer,
+                    ..
+                }))) => {
+                    assert_eq!(peer, other_id);
+                    // The peer was only inserted after answering the probation request.
+                    assert!(probing.behaviour().verified_peers.contains(&peer));
+                    assert!(probing.behaviour().probation.is_empty());
+                    return Poll::Ready(());
+                }
+                Poll::Ready(..) => {}
+                Poll::Pending => break,
+            }
+        }
+        Poll::Pending
+    }));
+}
+
+#[test]
+fn proximity_changes_with_routing_table() {
+    let hashers: [Arc<dyn kbucket::KeyHasher>; 2] = [
+        Arc::new(kbucket::Sha256Hasher),
+        Arc::new(kbucket::IdentityHasher),
+    ];
+    for hasher in hashers {
+        let local_peer_id = PeerId::random();
+        let mut cfg = KademliaConfig::default();
+        cfg.set_replication_factor(NonZeroUsize::new(1).unwrap());
+        cfg.set_key_hasher(hasher.clone());
+        let mut kademlia =
+            Kademlia::with_config(local_peer_id, MemoryStore::new(local_peer_id), cfg);
+
+        // Keys and peers are compared in the keyspace of the configured hasher.
+        let key = Key::new(&random::<[u8; 32]>());
+        let target = kbucket::Key::with_hasher(key.clone(), &*hasher);
+        let local_distance =
+            kbucket::Key::peer_with_hasher(local_peer_id, &*hasher).distance(&target);
+        let closer_peer = std::iter::repeat_with(PeerId::random)
+            .find(|peer| {
+                kbucket::Key::peer_with_hasher(*peer, &*hasher).distance(&target) < local_distance
+            })
+            .unwrap();
+
+        // Without any other nodes, the local node is the closest node.
+        assert!(kademlia.track_proximity(key.clone()));
+
+        let proximity_changes = |kademlia: &mut Kademlia<MemoryStore>| {
+            kademlia.check_proximity();
+            kademlia
+                .queued_events
+                .drain(..)
+                .filter_map(|event| match event {
+                    ToSwarm::GenerateEvent(KademliaEvent::ProximityChanged {
+                        key: k,
+                        now_responsible,
+                    }) => {
+                        assert_eq!(k, key);
+                        Some(now_responsible)
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        kademlia.add_address(&closer_peer, "/ip4/1.2.3.4/tcp/4001".parse().unwrap());
+        assert_eq!(proximity_changes(&mut kademlia), vec![false]);
+
+        kademlia.remove_peer(&closer_peer);
+        assert_eq!(proximity_changes(&mut kademlia), vec![true]);
+
+        kademlia.untrack_proximity(&key);
+        kademlia.add_address(&closer_peer, "/ip4/1.2.3.4/tcp/4001".parse().unwrap());
+        assert!(proximity_changes(&mut kademlia).is_empty());
+    }
+}
+
+#[derive(Default)]
+struct WakeFlag(std::sync::atomic::AtomicBool);
+
+impl futures::task::ArcWake for WakeFlag {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.store(true, Ordering::SeqCst);
+    }
+}
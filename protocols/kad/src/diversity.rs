@@ -0,0 +1,224 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Limits on the number of routing table entries within the same IP network, making it harder
+//! for an attacker to fill the routing table of a node with peers under its control.
+
+use crate::addresses::Addresses;
+use crate::kbucket::{KBucketsTable, Key};
+use libp2p_core::multiaddr::Protocol;
+use libp2p_core::Multiaddr;
+use libp2p_identity::PeerId;
+use std::collections::{HashMap, HashSet};
+
+/// The /24 network of an IPv4 or the /48 network of an IPv6 address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum IpPrefix {
+    V4([u8; 3]),
+    V6([u16; 3]),
+}
+
+impl IpPrefix {
+    /// Returns the prefix of the IP address the given [`Multiaddr`] starts with, if any.
+    pub(crate) fn of(addr: &Multiaddr) -> Option<Self> {
+        match addr.iter().next()? {
+            Protocol::Ip4(ip) => {
+                let [a, b, c, _] = ip.octets();
+                Some(IpPrefix::V4([a, b, c]))
+            }
+            Protocol::Ip6(ip) => {
+                let [a, b, c, ..] = ip.segments();
+                Some(IpPrefix::V6([a, b, c]))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The configured limits on routing table entries sharing an [`IpPrefix`].
+///
+/// See [`KademliaConfig::set_max_peers_per_ip_prefix`](crate::KademliaConfig::set_max_peers_per_ip_prefix)
+/// and [`KademliaConfig::set_max_bucket_peers_per_ip_prefix`](crate::KademliaConfig::set_max_bucket_peers_per_ip_prefix).
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct IpDiversity {
+    /// The maximum number of entries in the routing table sharing a prefix.
+    pub(crate) max_peers: Option<usize>,
+    /// The maximum number of entries in a single k-bucket sharing a prefix.
+    pub(crate) max_bucket_peers: Option<usize>,
+}
+
+impl IpDiversity {
+    /// Returns the first prefix among the given addresses of `peer` for which a limit is exceeded
+    /// if `peer` is in the routing table with these addresses.
+    ///
+    /// The entry of `peer` itself, if present, is not counted.
+    pub(crate) fn violation<'a>(
+        &self,
+        table: &mut KBucketsTable<Key<PeerId>, Addresses>,
+        peer: &Key<PeerId>,
+        addresses: impl IntoIterator<Item = &'a Multiaddr>,
+    ) -> Option<IpPrefix> {
+        if self.max_peers.is_none() && self.max_bucket_peers.is_none() {
+            return None;
+        }
+
+        let prefixes = addresses
+            .into_iter()
+            .filter_map(IpPrefix::of)
+            .collect::<HashSet<_>>();
+        if prefixes.is_empty() {
+            return None;
+        }
+
+        let distance = table.local_key().distance(peer);
+        let mut num_peers = HashMap::<IpPrefix, usize>::new();
+        let mut num_bucket_peers = HashMap::<IpPrefix, usize>::new();
+        for bucket in table.iter() {
+            let is_peer_bucket = bucket.contains(&distance);
+            for entry in bucket.iter().filter(|e| e.node.key != peer) {
+                let shared = entry
+                    .node
+                    .value
+                    .iter()
+                    .filter_map(IpPrefix::of)
+                    .filter(|p| prefixes.contains(p))
+                    .collect::<HashSet<_>>();
+                for prefix in shared {
+                    *num_peers.entry(prefix).or_default() += 1;
+                    if is_peer_bucket {
+                        *num_bucket_peers.entry(prefix).or_default() += 1;
+                    }
+                }
+            }
+        }
+
+        let exceeds = |max: Option<usize>, counts: &HashMap<IpPrefix, usize>, p: &IpPrefix| {
+            max.map_or(false, |max| counts.get(p).copied().unwrap_or(0) >= max)
+        };
+
+        prefixes.into_iter().find(|p| {
+            exceeds(self.max_peers, &num_peers, p)
+                || exceeds(self.max_bucket_peers, &num_bucket_peers, p)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kbucket::{Entry, InsertResult, NodeStatus};
+    use std::time::Duration;
+
+    fn table() -> KBucketsTable<Key<PeerId>, Addresses> {
+        KBucketsTable::new(Key::from(PeerId::random()), Duration::from_secs(60))
+    }
+
+    fn insert(table: &mut KBucketsTable<Key<PeerId>, Addresses>, addr: &str) -> Key<PeerId> {
+        let key = Key::from(PeerId::random());
+        match table.entry(&key) {
+            Entry::Absent(entry) => {
+                let addresses = Addresses::new(addr.parse().unwrap());
+                let result = entry.insert(addresses, NodeStatus::Connected);
+                assert!(matches!(result, InsertResult::Inserted));
+            }
+            _ => panic!("Unexpected entry"),
+        }
+        key
+    }
+
+    #[test]
+    fn prefix_of_ip4_and_ip6_addresses() {
+        let a = IpPrefix::of(&"/ip4/192.168.1.1/tcp/1".parse().unwrap());
+        let b = IpPrefix::of(&"/ip4/192.168.1.200/udp/2/quic-v1".parse().unwrap());
+        let c = IpPrefix::of(&"/ip4/192.168.2.1/tcp/1".parse().unwrap());
+        assert_eq!(a, Some(IpPrefix::V4([192, 168, 1])));
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+
+        let d = IpPrefix::of(&"/ip6/2001:db8:1:2::1/tcp/1".parse().unwrap());
+        let e = IpPrefix::of(&"/ip6/2001:db8:1:ffff::1/tcp/1".parse().unwrap());
+        assert_eq!(d, Some(IpPrefix::V6([0x2001, 0xdb8, 1])));
+        assert_eq!(d, e);
+
+        assert_eq!(
+            IpPrefix::of(&"/dns4/example.com/tcp/1".parse().unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn limits_peers_per_prefix_in_table() {
+        let mut table = table();
+        let diversity = IpDiversity {
+            max_peers: Some(2),
+            max_bucket_peers: None,
+        };
+        let addr: Multiaddr = "/ip4/10.0.0.3/tcp/1".parse().unwrap();
+        let peer = Key::from(PeerId::random());
+
+        insert(&mut table, "/ip4/10.0.0.1/tcp/1");
+        insert(&mut table, "/ip4/10.0.1.1/tcp/1");
+        assert_eq!(diversity.violation(&mut table, &peer, [&addr]), None);
+
+        let existing = insert(&mut table, "/ip4/10.0.0.2/tcp/1");
+        assert_eq!(
+            diversity.violation(&mut table, &peer, [&addr]),
+            Some(IpPrefix::V4([10, 0, 0]))
+        );
+        // An entry is not counted against itself.
+        assert_eq!(diversity.violation(&mut table, &existing, [&addr]), None);
+        // Addresses without an IP are never limited.
+        let dns = "/dns4/example.com/tcp/1".parse().unwrap();
+        assert_eq!(diversity.violation(&mut table, &peer, [&dns]), None);
+    }
+
+    #[test]
+    fn limits_peers_per_prefix_in_bucket() {
+        let mut table = table();
+        let diversity = IpDiversity {
+            max_peers: None,
+            max_bucket_peers: Some(1),
+        };
+        let addr: Multiaddr = "/ip4/10.0.0.3/tcp/1".parse().unwrap();
+
+        let existing = insert(&mut table, "/ip4/10.0.0.1/tcp/1");
+        let distance = table.local_key().distance(&existing);
+
+        // Find peers falling into the same and into another bucket than the existing one.
+        let mut same = None;
+        let mut other = None;
+        while same.is_none() || other.is_none() {
+            let key = Key::from(PeerId::random());
+            let index = table.local_key().distance(&key).ilog2();
+            if index == distance.ilog2() {
+                same.get_or_insert(key);
+            } else {
+                other.get_or_insert(key);
+            }
+        }
+
+        assert!(diversity
+            .violation(&mut table, &same.unwrap(), [&addr])
+            .is_some());
+        assert!(diversity
+            .violation(&mut table, &other.unwrap(), [&addr])
+            .is_none());
+    }
+}
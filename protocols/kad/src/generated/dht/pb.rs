@@ -123,6 +123,7 @@ pub struct Peer {
     pub id: Vec<u8>,
     pub addrs: Vec<Vec<u8>>,
     pub connection: dht::pb::mod_Message::ConnectionType,
+    pub metadata: Vec<u8>,
 }
 
 impl<'a> MessageRead<'a> for Peer {
@@ -133,6 +134,7 @@ impl<'a> MessageRead<'a> for Peer {
                 Ok(10) => msg.id = r.read_bytes(bytes)?.to_owned(),
                 Ok(18) => msg.addrs.push(r.read_bytes(bytes)?.to_owned()),
                 Ok(24) => msg.connection = r.read_enum(bytes)?,
+                Ok(5330) => msg.metadata = r.read_bytes(bytes)?.to_owned(),
                 Ok(t) => { r.read_unknown(bytes, t)?; }
                 Err(e) => return Err(e),
             }
@@ -147,12 +149,14 @@ impl MessageWrite for Peer {
         + if self.id.is_empty() { 0 } else { 1 + sizeof_len((&self.id).len()) }
         + self.addrs.iter().map(|s| 1 + sizeof_len((s).len())).sum::<usize>()
         + if self.connection == dht::pb::mod_Message::ConnectionType::NOT_CONNECTED { 0 } else { 1 + sizeof_varint(*(&self.connection) as u64) }
+        + if self.metadata.is_empty() { 0 } else { 2 + sizeof_len((&self.metadata).len()) }
     }
 
     fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
         if !self.id.is_empty() { w.write_with_tag(10, |w| w.write_bytes(&**&self.id))?; }
         for s in &self.addrs { w.write_with_tag(18, |w| w.write_bytes(&**s))?; }
         if self.connection != dht::pb::mod_Message::ConnectionType::NOT_CONNECTED { w.write_with_tag(24, |w| w.write_enum(*&self.connection as i32))?; }
+        if !self.metadata.is_empty() { w.write_with_tag(5330, |w| w.write_bytes(&**&self.metadata))?; }
         Ok(())
     }
 }
@@ -207,8 +207,14 @@ impl<TUserData> InboundSubstreamState<TUserData> {
                 phantom: PhantomData,
             },
         ) {
+            InboundSubstreamState::WaitingBehaviour(_, substream, mut waker) => {
+                *self = InboundSubstreamState::Closing(substream);
+
+                if let Some(waker) = waker.take() {
+                    waker.wake();
+                }
+            }
             InboundSubstreamState::WaitingMessage { substream, .. }
-            | InboundSubstreamState::WaitingBehaviour(_, substream, _)
             | InboundSubstreamState::PendingSend(_, substream, _)
             | InboundSubstreamState::PendingFlush(_, substream)
             | InboundSubstreamState::Closing(substream) => {
@@ -380,6 +386,12 @@ pub enum KademliaHandlerIn<TUserData> {
     /// for the query on the remote.
     Reset(KademliaRequestId),
 
+    /// Changes whether inbound requests are accepted, see
+    /// [`KademliaHandlerConfig::allow_listening`].
+    ///
+    /// Sent by the behaviour when its [`Mode`](crate::Mode) changes.
+    SetAllowListening(bool),
+
     /// Request for the list of nodes whose IDs are the closest to `key`. The number of nodes
     /// returned is not specified, but should be around 20.
     FindNodeReq {
@@ -542,6 +554,15 @@ where
             future::Either::Right(p) => void::unreachable(p),
         };
 
+        if !self.config.allow_listening {
+            // Listening was disabled while the substream was being negotiated.
+            log::debug!(
+                "Dropping inbound substream from {:?} as listening is disabled.",
+                self.remote_peer_id,
+            );
+            return;
+        }
+
         if let ProtocolStatus::Unconfirmed = self.protocol_status {
             // Upon the first successfully negotiated substream, we know that the
             // remote is configured with the same protocol name and we want
@@ -573,7 +594,6 @@ where
             }
         }
 
-        debug_assert!(self.config.allow_listening);
         let connec_unique_id = self.next_connec_unique_id;
         self.next_connec_unique_id.0 += 1;
         self.inbound_substreams
@@ -643,6 +663,9 @@ where
                     state.close();
                 }
             }
+            KademliaHandlerIn::SetAllowListening(allow_listening) => {
+                self.config.allow_listening = allow_listening;
+            }
             KademliaHandlerIn::FindNodeReq { key, user_data } => {
                 let msg = KadRequestMsg::FindNode { key };
                 self.requested_streams.push_back(SubstreamProtocol::new(
@@ -39,7 +39,7 @@
 //! intervals should be shorter than publication intervals and
 //! publication intervals should be shorter than the TTL.
 //!
-//! This module implements two periodic jobs:
+//! This module implements three periodic jobs:
 //!
 //!   * [`PutRecordJob`]: For (re-)publication and (re-)replication of
 //!     regular (value-)records.
@@ -47,6 +47,10 @@
 //!   * [`AddProviderJob`]: For (re-)publication of provider records.
 //!     Provider records currently have no separate replication mechanism.
 //!
+//!   * [`RecordRepairJob`]: For checking a random sample of the stored
+//!     regular (value-)records, which are then re-validated and repaired
+//!     if found to be under-replicated.
+//!
 //! A periodic job is driven like a `Future` or `Stream` by `poll`ing it.
 //! Once a job starts running it emits records to send to the `k` closest
 //! nodes to the key, where `k` is the replication factor.
@@ -63,11 +67,13 @@
 
 use crate::record::{self, store::RecordStore, ProviderRecord, Record};
 use futures::prelude::*;
-use futures_timer::Delay;
 use instant::Instant;
 use libp2p_identity::PeerId;
+use libp2p_swarm::{Clock, Timer};
+use rand::seq::IteratorRandom;
 use std::collections::HashSet;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
 use std::vec;
@@ -85,6 +91,7 @@ pub const JOBS_MAX_NEW_QUERIES: usize = 10;
 #[derive(Debug)]
 struct PeriodicJob<T> {
     interval: Duration,
+    clock: Arc<dyn Clock>,
     state: PeriodicJobState<T>,
 }
 
@@ -100,9 +107,13 @@ impl<T> PeriodicJob<T> {
     /// for the delay to expire.
     fn asap(&mut self) {
         if let PeriodicJobState::Waiting(delay, deadline) = &mut self.state {
-            let new_deadline = Instant::now().checked_sub(Duration::from_secs(1)).unwrap();
+            let new_deadline = self
+                .clock
+                .now()
+                .checked_sub(Duration::from_secs(1))
+                .unwrap();
             *deadline = new_deadline;
-            delay.reset(Duration::from_secs(1));
+            *delay = self.clock.timer(Duration::from_secs(1));
         }
     }
 
@@ -122,7 +133,7 @@ impl<T> PeriodicJob<T> {
 #[derive(Debug)]
 enum PeriodicJobState<T> {
     Running(T),
-    Waiting(Delay, Instant),
+    Waiting(Timer, Instant),
 }
 
 //////////////////////////////////////////////////////////////////////////////
@@ -146,10 +157,11 @@ impl PutRecordJob {
         replicate_interval: Duration,
         publish_interval: Option<Duration>,
         record_ttl: Option<Duration>,
+        clock: Arc<dyn Clock>,
     ) -> Self {
-        let now = Instant::now();
+        let now = clock.now();
         let deadline = now + replicate_interval;
-        let delay = Delay::new(replicate_interval);
+        let delay = clock.timer(replicate_interval);
         let next_publish = publish_interval.map(|i| now + i);
         Self {
             local_id,
@@ -159,6 +171,7 @@ impl PutRecordJob {
             skipped: HashSet::new(),
             inner: PeriodicJob {
                 interval: replicate_interval,
+                clock,
                 state: PeriodicJobState::Waiting(delay, deadline),
             },
         }
@@ -181,7 +194,13 @@ impl PutRecordJob {
     /// The job is guaranteed to run on the next invocation of `poll`.
     pub fn asap(&mut self, publish: bool) {
         if publish {
-            self.next_publish = Some(Instant::now().checked_sub(Duration::from_secs(1)).unwrap())
+            self.next_publish = Some(
+                self.inner
+                    .clock
+                    .now()
+                    .checked_sub(Duration::from_secs(1))
+                    .unwrap(),
+            )
         }
         self.inner.asap()
     }
@@ -237,7 +256,7 @@ impl PutRecordJob {
 
             // Wait for the next run.
             let deadline = now + self.inner.interval;
-            let delay = Delay::new(self.inner.interval);
+            let delay = self.inner.clock.timer(self.inner.interval);
             self.inner.state = PeriodicJobState::Waiting(delay, deadline);
             assert!(!self.inner.check_ready(cx, now));
         }
@@ -256,15 +275,16 @@ pub struct AddProviderJob {
 
 impl AddProviderJob {
     /// Creates a new periodic job for provider announcements.
-    pub fn new(interval: Duration) -> Self {
-        let now = Instant::now();
+    pub fn new(interval: Duration, clock: Arc<dyn Clock>) -> Self {
+        let now = clock.now();
         Self {
             inner: PeriodicJob {
                 interval,
                 state: {
                     let deadline = now + interval;
-                    PeriodicJobState::Waiting(Delay::new(interval), deadline)
+                    PeriodicJobState::Waiting(clock.timer(interval), deadline)
                 },
+                clock,
             },
         }
     }
@@ -315,7 +335,86 @@ impl AddProviderJob {
             }
 
             let deadline = now + self.inner.interval;
-            let delay = Delay::new(self.inner.interval);
+            let delay = self.inner.clock.timer(self.inner.interval);
+            self.inner.state = PeriodicJobState::Waiting(delay, deadline);
+            assert!(!self.inner.check_ready(cx, now));
+        }
+
+        Poll::Pending
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////////
+// RecordRepairJob
+
+/// Periodic job for checking the health of a sample of the stored records.
+pub struct RecordRepairJob {
+    sample_size: usize,
+    inner: PeriodicJob<vec::IntoIter<Record>>,
+}
+
+impl RecordRepairJob {
+    /// Creates a new periodic job checking up to `sample_size` randomly
+    /// chosen records on every run.
+    pub fn new(interval: Duration, sample_size: usize, clock: Arc<dyn Clock>) -> Self {
+        let now = clock.now();
+        Self {
+            sample_size,
+            inner: PeriodicJob {
+                interval,
+                state: {
+                    let deadline = now + interval;
+                    PeriodicJobState::Waiting(clock.timer(interval), deadline)
+                },
+                clock,
+            },
+        }
+    }
+
+    /// Checks whether the job is currently running.
+    pub fn is_running(&self) -> bool {
+        self.inner.is_running()
+    }
+
+    /// Cuts short the remaining delay, if the job is currently waiting
+    /// for the delay to expire.
+    ///
+    /// The job is guaranteed to run on the next invocation of `poll`.
+    pub fn asap(&mut self) {
+        self.inner.asap()
+    }
+
+    /// Polls the job for records to check.
+    ///
+    /// Must be called in the context of a task. When `NotReady` is returned,
+    /// the current task is registered to be notified when the job is ready
+    /// to be run.
+    pub fn poll<T>(&mut self, cx: &mut Context<'_>, store: &mut T, now: Instant) -> Poll<Record>
+    where
+        T: RecordStore,
+    {
+        if self.inner.check_ready(cx, now) {
+            let records = store
+                .records()
+                .choose_multiple(&mut rand::thread_rng(), self.sample_size)
+                .into_iter()
+                .map(|r| r.into_owned())
+                .collect::<Vec<_>>()
+                .into_iter();
+            self.inner.state = PeriodicJobState::Running(records);
+        }
+
+        if let PeriodicJobState::Running(records) = &mut self.inner.state {
+            for r in records {
+                if r.is_expired(now) {
+                    store.remove(&r.key)
+                } else {
+                    return Poll::Ready(r);
+                }
+            }
+
+            let deadline = now + self.inner.interval;
+            let delay = self.inner.clock.timer(self.inner.interval);
             self.inner.state = PeriodicJobState::Waiting(delay, deadline);
             assert!(!self.inner.check_ready(cx, now));
         }
@@ -329,6 +428,7 @@ mod tests {
     use super::*;
     use crate::record::store::MemoryStore;
     use futures::{executor::block_on, future::poll_fn};
+    use libp2p_swarm::SystemClock;
     use quickcheck::*;
     use rand::Rng;
 
@@ -338,13 +438,19 @@ mod tests {
         let replicate_interval = Duration::from_secs(rng.gen_range(1..60));
         let publish_interval = Some(replicate_interval * rng.gen_range(1..10));
         let record_ttl = Some(Duration::from_secs(rng.gen_range(1..600)));
-        PutRecordJob::new(id, replicate_interval, publish_interval, record_ttl)
+        PutRecordJob::new(
+            id,
+            replicate_interval,
+            publish_interval,
+            record_ttl,
+            Arc::new(SystemClock),
+        )
     }
 
     fn rand_add_provider_job() -> AddProviderJob {
         let mut rng = rand::thread_rng();
         let interval = Duration::from_secs(rng.gen_range(1..60));
-        AddProviderJob::new(interval)
+        AddProviderJob::new(interval, Arc::new(SystemClock))
     }
 
     #[test]
@@ -412,4 +518,37 @@ mod tests {
 
         quickcheck(prop as fn(_))
     }
+
+    #[test]
+    fn run_record_repair_job() {
+        fn prop(records: Vec<Record>, sample_size: u8) {
+            let mut job = RecordRepairJob::new(
+                Duration::from_secs(60),
+                sample_size as usize,
+                Arc::new(SystemClock),
+            );
+            // Fill a record store.
+            let mut store = MemoryStore::new(PeerId::random());
+            for r in records {
+                let _ = store.put(r);
+            }
+            let num_records = store.records().count();
+
+            block_on(poll_fn(|ctx| {
+                let now = Instant::now() + job.inner.interval;
+                // A sample of the (non-expired) records in the store must be yielded by the job.
+                let mut sampled = HashSet::new();
+                while let Poll::Ready(r) = job.poll(ctx, &mut store, now) {
+                    assert!(!r.is_expired(now));
+                    assert_eq!(store.get(&r.key).map(|r| r.into_owned()), Some(r.clone()));
+                    assert!(sampled.insert(r.key));
+                }
+                assert!(sampled.len() <= usize::min(sample_size as usize, num_records));
+                assert!(!job.is_running());
+                Poll::Ready(())
+            }));
+        }
+
+        quickcheck(prop as fn(_, _))
+    }
 }
@@ -24,6 +24,7 @@ use libp2p_identity::PeerId;
 use sha2::digest::generic_array::{typenum::U32, GenericArray};
 use sha2::{Digest, Sha256};
 use std::borrow::Borrow;
+use std::fmt;
 use std::hash::{Hash, Hasher};
 use uint::*;
 
@@ -59,6 +60,17 @@ impl<T> Key<T> {
         Key { preimage, bytes }
     }
 
+    /// Constructs a new `Key` by running the given value through the given
+    /// [`KeyHasher`] instead of the default SHA-256.
+    pub fn with_hasher<H>(preimage: T, hasher: &H) -> Key<T>
+    where
+        T: Borrow<[u8]>,
+        H: KeyHasher + ?Sized,
+    {
+        let bytes = KeyBytes::with_hasher(preimage.borrow(), hasher);
+        Key { preimage, bytes }
+    }
+
     /// Borrows the preimage of the key.
     pub fn preimage(&self) -> &T {
         &self.preimage
@@ -107,6 +119,21 @@ impl From<PeerId> for Key<PeerId> {
     }
 }
 
+impl Key<PeerId> {
+    /// Constructs the `Key` of a peer by running its bytes through the given
+    /// [`KeyHasher`] instead of the default SHA-256.
+    pub fn peer_with_hasher<H>(peer: PeerId, hasher: &H) -> Key<PeerId>
+    where
+        H: KeyHasher + ?Sized,
+    {
+        let bytes = KeyBytes::with_hasher(peer.to_bytes(), hasher);
+        Key {
+            preimage: peer,
+            bytes,
+        }
+    }
+}
+
 impl From<Vec<u8>> for Key<Vec<u8>> {
     fn from(b: Vec<u8>) -> Self {
         Key::new(b)
@@ -153,6 +180,21 @@ impl KeyBytes {
         KeyBytes(Sha256::digest(value.borrow()))
     }
 
+    /// Creates a new key in the DHT keyspace by running the given
+    /// value through the given [`KeyHasher`].
+    pub fn with_hasher<T, H>(value: T, hasher: &H) -> Self
+    where
+        T: Borrow<[u8]>,
+        H: KeyHasher + ?Sized,
+    {
+        KeyBytes(GenericArray::from(hasher.hash(value.borrow())))
+    }
+
+    /// Returns the raw bytes of the key.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+
     /// Computes the distance of the keys according to the XOR metric.
     pub fn distance<U>(&self, other: &U) -> Distance
     where
@@ -180,6 +222,44 @@ impl AsRef<KeyBytes> for KeyBytes {
     }
 }
 
+/// Maps values (peer IDs and record keys) into the 256-bit DHT keyspace.
+///
+/// The default is [`Sha256Hasher`], as used by the IPFS DHT. Networks with a
+/// different key derivation can provide their own implementation, see
+/// [`KademliaConfig::set_key_hasher`](crate::KademliaConfig::set_key_hasher).
+/// All nodes of a network must agree on the hasher in use.
+pub trait KeyHasher: fmt::Debug + Send + Sync + 'static {
+    /// Hashes the given value into the DHT keyspace.
+    fn hash(&self, value: &[u8]) -> [u8; 32];
+}
+
+/// The default [`KeyHasher`], computing the SHA-256 digest of a value.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha256Hasher;
+
+impl KeyHasher for Sha256Hasher {
+    fn hash(&self, value: &[u8]) -> [u8; 32] {
+        Sha256::digest(value).into()
+    }
+}
+
+/// A [`KeyHasher`] for keyspaces of pre-hashed keys.
+///
+/// Uses the last 32 bytes of a value as its key, left-padding shorter
+/// values with zeros. For multihash-encoded values, such as peer IDs
+/// with an identity or SHA-256 digest, this is the trailing digest.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IdentityHasher;
+
+impl KeyHasher for IdentityHasher {
+    fn hash(&self, value: &[u8]) -> [u8; 32] {
+        let mut key = [0; 32];
+        let len = value.len().min(32);
+        key[32 - len..].copy_from_slice(&value[value.len() - len..]);
+        key
+    }
+}
+
 /// A distance between two keys in the DHT keyspace.
 #[derive(Copy, Clone, PartialEq, Eq, Default, PartialOrd, Ord, Debug)]
 pub struct Distance(pub(super) U256);
@@ -212,6 +292,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn identity_hasher_uses_trailing_bytes() {
+        let digest: [u8; 32] = core::array::from_fn(|i| i as u8);
+        let mh = Multihash::wrap(SHA_256_MH, &digest).unwrap();
+        let key = Key::with_hasher(mh.to_bytes(), &IdentityHasher);
+        assert_eq!(key.as_ref().as_bytes(), &digest);
+
+        let short = Key::with_hasher(vec![1, 2], &IdentityHasher);
+        let mut expected = [0; 32];
+        expected[30..].copy_from_slice(&[1, 2]);
+        assert_eq!(short.as_ref().as_bytes(), &expected);
+    }
+
+    #[test]
+    fn sha256_hasher_matches_default() {
+        let peer = PeerId::random();
+        assert_eq!(Key::peer_with_hasher(peer, &Sha256Hasher), Key::from(peer));
+    }
+
     #[test]
     fn identity() {
         fn prop(a: Key<PeerId>) -> bool {
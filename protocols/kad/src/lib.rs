@@ -45,8 +45,11 @@ pub mod record;
 
 mod addresses;
 mod behaviour;
+mod diversity;
 mod jobs;
 mod query;
+mod rate_limit;
+mod routing;
 
 mod proto {
     include!("generated/mod.rs");
@@ -59,19 +62,25 @@ mod proto {
 pub use addresses::Addresses;
 pub use behaviour::{
     AddProviderContext, AddProviderError, AddProviderOk, AddProviderPhase, AddProviderResult,
-    BootstrapError, BootstrapOk, BootstrapResult, GetClosestPeersError, GetClosestPeersOk,
-    GetClosestPeersResult, GetProvidersError, GetProvidersOk, GetProvidersResult, GetRecordError,
-    GetRecordOk, GetRecordResult, InboundRequest, NoKnownPeers, PeerRecord, PutRecordContext,
-    PutRecordError, PutRecordOk, PutRecordPhase, PutRecordResult, QueryInfo, QueryMut, QueryRef,
-    QueryResult, QueryStats, RoutingUpdate,
+    BootstrapError, BootstrapOk, BootstrapResult, FindAndDialError, FindAndDialOk,
+    FindAndDialResult, GetClosestPeersError, GetClosestPeersOk, GetClosestPeersResult,
+    GetProvidersError, GetProvidersOk, GetProvidersResult, GetRecordError, GetRecordFromResult,
+    GetRecordOk, GetRecordResult, InboundRequest, NoKnownPeers, PeerRecord, PutRecordAtResult,
+    PutRecordContext, PutRecordError, PutRecordOk, PutRecordPhase, PutRecordResult, QueryInfo,
+    QueryMut, QueryRef, QueryResult, QueryStats, RoutingUpdate,
 };
 pub use behaviour::{
     Kademlia, KademliaBucketInserts, KademliaCaching, KademliaConfig, KademliaEvent,
-    KademliaStoreInserts, ProgressStep, Quorum,
+    KademliaInboundThrottling, KademliaPausedQueries, KademliaProtocolConfirmation,
+    KademliaStoreInserts, Mode, ProgressStep, Quorum,
 };
 pub use protocol::KadConnectionType;
 pub use query::QueryId;
-pub use record::{store, ProviderRecord, Record};
+pub use rate_limit::{InboundRateLimit, InboundThrottleStats};
+pub use record::{
+    store, AcceptAllValidator, ProviderRecord, Record, RecordValidator, MAX_PROVIDER_METADATA_SIZE,
+};
+pub use routing::{LookupId, Routers, Routing, RoutingEvent, RoutingStrategy};
 
 use std::num::NonZeroUsize;
 
@@ -36,8 +36,9 @@ use instant::Instant;
 use libp2p_core::upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo};
 use libp2p_core::Multiaddr;
 use libp2p_identity::PeerId;
+use libp2p_swarm::{Clock, SystemClock};
 use quick_protobuf::{BytesReader, Writer};
-use std::{borrow::Cow, convert::TryFrom, time::Duration};
+use std::{borrow::Cow, convert::TryFrom, sync::Arc, time::Duration};
 use std::{io, iter};
 use unsigned_varint::codec;
 
@@ -93,6 +94,31 @@ pub struct KadPeer {
     pub multiaddrs: Vec<Multiaddr>,
     /// How the sender is connected to that remote.
     pub connection_ty: KadConnectionType,
+    /// The metadata announced along with a provider record of the peer,
+    /// see [`ProviderRecord::metadata`](crate::ProviderRecord::metadata). Empty otherwise.
+    pub metadata: Vec<u8>,
+}
+
+impl KadPeer {
+    /// Creates a new [`KadPeer`] without metadata.
+    pub fn new(
+        node_id: PeerId,
+        multiaddrs: Vec<Multiaddr>,
+        connection_ty: KadConnectionType,
+    ) -> Self {
+        KadPeer {
+            node_id,
+            multiaddrs,
+            connection_ty,
+            metadata: Vec::new(),
+        }
+    }
+
+    /// Sets the metadata of the peer, see [`KadPeer::metadata`].
+    pub fn with_metadata(mut self, metadata: Vec<u8>) -> Self {
+        self.metadata = metadata;
+        self
+    }
 }
 
 // Builds a `KadPeer` from a corresponding protobuf message.
@@ -118,6 +144,7 @@ impl TryFrom<proto::Peer> for KadPeer {
             node_id,
             multiaddrs: addrs,
             connection_ty: peer.connection.into(),
+            metadata: peer.metadata,
         })
     }
 }
@@ -128,6 +155,7 @@ impl From<KadPeer> for proto::Peer {
             id: peer.node_id.to_bytes(),
             addrs: peer.multiaddrs.into_iter().map(|a| a.to_vec()).collect(),
             connection: peer.connection_ty.into(),
+            metadata: peer.metadata,
         }
     }
 }
@@ -142,6 +170,8 @@ pub struct KademliaProtocolConfig {
     protocol_names: Vec<Cow<'static, [u8]>>,
     /// Maximum allowed size of a packet.
     max_packet_size: usize,
+    /// Converts between the expiry of records and their TTL on the wire.
+    clock: Arc<dyn Clock>,
 }
 
 impl KademliaProtocolConfig {
@@ -160,6 +190,11 @@ impl KademliaProtocolConfig {
     pub fn set_max_packet_size(&mut self, size: usize) {
         self.max_packet_size = size;
     }
+
+    /// Sets the [`Clock`] converting between the expiry of records and their TTL on the wire.
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
 }
 
 impl Default for KademliaProtocolConfig {
@@ -167,6 +202,7 @@ impl Default for KademliaProtocolConfig {
         KademliaProtocolConfig {
             protocol_names: iter::once(Cow::Borrowed(DEFAULT_PROTO_NAME)).collect(),
             max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            clock: Arc::new(SystemClock),
         }
     }
 }
@@ -193,27 +229,29 @@ where
 
         let mut codec = UviBytes::default();
         codec.set_max_len(self.max_packet_size);
+        let encode_clock = self.clock.clone();
+        let decode_clock = self.clock;
 
         future::ok(
             Framed::new(incoming, codec)
                 .err_into()
-                .with::<_, _, fn(_) -> _, _>(|response| {
-                    let proto_struct = resp_msg_to_proto(response);
+                .with::<_, _, EncodeFn<KadResponseMsg>, _>(Box::new(move |response| {
+                    let proto_struct = resp_msg_to_proto(response, encode_clock.now());
                     let mut buf = Vec::with_capacity(proto_struct.get_size());
                     let mut writer = Writer::new(&mut buf);
                     proto_struct
                         .write_message(&mut writer)
                         .expect("Encoding to succeed");
                     future::ready(Ok(io::Cursor::new(buf)))
-                })
-                .and_then::<_, fn(_) -> _>(|bytes| {
+                }))
+                .and_then::<_, DecodeFn<KadRequestMsg>>(Box::new(move |bytes| {
                     let mut reader = BytesReader::from_bytes(&bytes);
                     let request = match proto::Message::from_reader(&mut reader, &bytes) {
                         Ok(r) => r,
                         Err(err) => return future::ready(Err(err.into())),
                     };
-                    future::ready(proto_to_req_msg(request))
-                }),
+                    future::ready(proto_to_req_msg(request, decode_clock.now()))
+                })),
         )
     }
 }
@@ -231,27 +269,29 @@ where
 
         let mut codec = UviBytes::default();
         codec.set_max_len(self.max_packet_size);
+        let encode_clock = self.clock.clone();
+        let decode_clock = self.clock;
 
         future::ok(
             Framed::new(incoming, codec)
                 .err_into()
-                .with::<_, _, fn(_) -> _, _>(|request| {
-                    let proto_struct = req_msg_to_proto(request);
+                .with::<_, _, EncodeFn<KadRequestMsg>, _>(Box::new(move |request| {
+                    let proto_struct = req_msg_to_proto(request, encode_clock.now());
                     let mut buf = Vec::with_capacity(proto_struct.get_size());
                     let mut writer = Writer::new(&mut buf);
                     proto_struct
                         .write_message(&mut writer)
                         .expect("Encoding to succeed");
                     future::ready(Ok(io::Cursor::new(buf)))
-                })
-                .and_then::<_, fn(_) -> _>(|bytes| {
+                }))
+                .and_then::<_, DecodeFn<KadResponseMsg>>(Box::new(move |bytes| {
                     let mut reader = BytesReader::from_bytes(&bytes);
                     let response = match proto::Message::from_reader(&mut reader, &bytes) {
                         Ok(r) => r,
                         Err(err) => return future::ready(Err(err.into())),
                     };
-                    future::ready(proto_to_resp_msg(response))
-                }),
+                    future::ready(proto_to_resp_msg(response, decode_clock.now()))
+                })),
         )
     }
 }
@@ -268,12 +308,19 @@ pub type KadStreamSink<S, A, B> = stream::AndThen<
         io::Cursor<Vec<u8>>,
         A,
         future::Ready<Result<io::Cursor<Vec<u8>>, io::Error>>,
-        fn(A) -> future::Ready<Result<io::Cursor<Vec<u8>>, io::Error>>,
+        EncodeFn<A>,
     >,
     future::Ready<Result<B, io::Error>>,
-    fn(BytesMut) -> future::Ready<Result<B, io::Error>>,
+    DecodeFn<B>,
 >;
 
+/// Encodes the messages sent on a [`KadStreamSink`].
+pub type EncodeFn<A> =
+    Box<dyn FnMut(A) -> future::Ready<Result<io::Cursor<Vec<u8>>, io::Error>> + Send>;
+
+/// Decodes the messages received on a [`KadStreamSink`].
+pub type DecodeFn<B> = Box<dyn FnMut(BytesMut) -> future::Ready<Result<B, io::Error>> + Send>;
+
 /// Request that we can send to a peer or that we received from a peer.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum KadRequestMsg {
@@ -350,7 +397,7 @@ pub enum KadResponseMsg {
 }
 
 /// Converts a `KadRequestMsg` into the corresponding protobuf message for sending.
-fn req_msg_to_proto(kad_msg: KadRequestMsg) -> proto::Message {
+fn req_msg_to_proto(kad_msg: KadRequestMsg, now: Instant) -> proto::Message {
     match kad_msg {
         KadRequestMsg::Ping => proto::Message {
             type_pb: proto::MessageType::PING,
@@ -384,14 +431,14 @@ fn req_msg_to_proto(kad_msg: KadRequestMsg) -> proto::Message {
         KadRequestMsg::PutValue { record } => proto::Message {
             type_pb: proto::MessageType::PUT_VALUE,
             key: record.key.to_vec(),
-            record: Some(record_to_proto(record)),
+            record: Some(record_to_proto(record, now)),
             ..proto::Message::default()
         },
     }
 }
 
 /// Converts a `KadResponseMsg` into the corresponding protobuf message for sending.
-fn resp_msg_to_proto(kad_msg: KadResponseMsg) -> proto::Message {
+fn resp_msg_to_proto(kad_msg: KadResponseMsg, now: Instant) -> proto::Message {
     match kad_msg {
         KadResponseMsg::Pong => proto::Message {
             type_pb: proto::MessageType::PING,
@@ -420,7 +467,7 @@ fn resp_msg_to_proto(kad_msg: KadResponseMsg) -> proto::Message {
             type_pb: proto::MessageType::GET_VALUE,
             clusterLevelRaw: 9,
             closerPeers: closer_peers.into_iter().map(KadPeer::into).collect(),
-            record: record.map(record_to_proto),
+            record: record.map(|record| record_to_proto(record, now)),
             ..proto::Message::default()
         },
         KadResponseMsg::PutValue { key, value } => proto::Message {
@@ -439,11 +486,11 @@ fn resp_msg_to_proto(kad_msg: KadResponseMsg) -> proto::Message {
 /// Converts a received protobuf message into a corresponding `KadRequestMsg`.
 ///
 /// Fails if the protobuf message is not a valid and supported Kademlia request message.
-fn proto_to_req_msg(message: proto::Message) -> Result<KadRequestMsg, io::Error> {
+fn proto_to_req_msg(message: proto::Message, now: Instant) -> Result<KadRequestMsg, io::Error> {
     match message.type_pb {
         proto::MessageType::PING => Ok(KadRequestMsg::Ping),
         proto::MessageType::PUT_VALUE => {
-            let record = record_from_proto(message.record.unwrap_or_default())?;
+            let record = record_from_proto(message.record.unwrap_or_default(), now)?;
             Ok(KadRequestMsg::PutValue { record })
         }
         proto::MessageType::GET_VALUE => Ok(KadRequestMsg::GetValue {
@@ -475,12 +522,12 @@ fn proto_to_req_msg(message: proto::Message) -> Result<KadRequestMsg, io::Error>
 /// Converts a received protobuf message into a corresponding `KadResponseMessage`.
 ///
 /// Fails if the protobuf message is not a valid and supported Kademlia response message.
-fn proto_to_resp_msg(message: proto::Message) -> Result<KadResponseMsg, io::Error> {
+fn proto_to_resp_msg(message: proto::Message, now: Instant) -> Result<KadResponseMsg, io::Error> {
     match message.type_pb {
         proto::MessageType::PING => Ok(KadResponseMsg::Pong),
         proto::MessageType::GET_VALUE => {
             let record = if let Some(r) = message.record {
-                Some(record_from_proto(r)?)
+                Some(record_from_proto(r, now)?)
             } else {
                 None
             };
@@ -544,7 +591,8 @@ fn proto_to_resp_msg(message: proto::Message) -> Result<KadResponseMsg, io::Erro
     }
 }
 
-fn record_from_proto(record: proto::Record) -> Result<Record, io::Error> {
+/// Converts a record received at `now`, which determines its expiry.
+fn record_from_proto(record: proto::Record, now: Instant) -> Result<Record, io::Error> {
     let key = record::Key::from(record.key);
     let value = record.value;
 
@@ -557,7 +605,7 @@ fn record_from_proto(record: proto::Record) -> Result<Record, io::Error> {
     };
 
     let expires = if record.ttl > 0 {
-        Some(Instant::now() + Duration::from_secs(record.ttl as u64))
+        Some(now + Duration::from_secs(record.ttl as u64))
     } else {
         None
     };
@@ -570,7 +618,8 @@ fn record_from_proto(record: proto::Record) -> Result<Record, io::Error> {
     })
 }
 
-fn record_to_proto(record: Record) -> proto::Record {
+/// Converts a record sent at `now`, which determines its TTL.
+fn record_to_proto(record: Record, now: Instant) -> proto::Record {
     proto::Record {
         key: record.key.to_vec(),
         value: record.value,
@@ -578,7 +627,6 @@ fn record_to_proto(record: Record) -> proto::Record {
         ttl: record
             .expires
             .map(|t| {
-                let now = Instant::now();
                 if t > now {
                     (t - now).as_secs() as u32
                 } else {
@@ -601,6 +649,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use quick_protobuf::{MessageRead, MessageWrite};
 
     #[test]
     fn skip_invalid_multiaddr() {
@@ -617,6 +666,7 @@ mod tests {
             id: PeerId::random().to_bytes(),
             addrs: vec![valid_multiaddr_bytes, invalid_multiaddr],
             connection: proto::ConnectionType::CAN_CONNECT,
+            metadata: Vec::new(),
         };
 
         let peer = KadPeer::try_from(payload).expect("not to fail");
@@ -624,6 +674,42 @@ mod tests {
         assert_eq!(peer.multiaddrs, vec![valid_multiaddr])
     }
 
+    #[test]
+    fn provider_metadata_roundtrip() {
+        let peer = KadPeer::new(
+            PeerId::random(),
+            vec!["/ip4/1.2.3.4/tcp/4001".parse().unwrap()],
+            KadConnectionType::Connected,
+        )
+        .with_metadata(b"/ipfs/bitswap/1.2.0".to_vec());
+
+        let payload = proto::Peer::from(peer.clone());
+        assert_eq!(payload.metadata, peer.metadata);
+
+        let mut buf = Vec::with_capacity(payload.get_size());
+        payload
+            .write_message(&mut Writer::new(&mut buf))
+            .expect("Encoding to succeed");
+        let decoded = proto::Peer::from_reader(&mut BytesReader::from_bytes(&buf), &buf)
+            .expect("Decoding to succeed");
+
+        assert_eq!(KadPeer::try_from(decoded).expect("not to fail"), peer);
+    }
+
+    #[test]
+    fn record_ttl_is_relative_to_the_given_time() {
+        let sent = Instant::now();
+        let mut record = Record::new(b"key".to_vec(), b"value".to_vec());
+        record.expires = Some(sent + Duration::from_secs(60));
+
+        let payload = record_to_proto(record.clone(), sent);
+        assert_eq!(payload.ttl, 60);
+
+        let received = sent + Duration::from_secs(3600);
+        let decoded = record_from_proto(payload, received).expect("not to fail");
+        assert_eq!(decoded.expires, Some(received + Duration::from_secs(60)));
+    }
+
     /*// TODO: restore
     use self::libp2p_tcp::TcpTransport;
     use self::tokio::runtime::current_thread::Runtime;
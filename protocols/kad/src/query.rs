@@ -26,13 +26,19 @@ use peers::closest::{
 use peers::fixed::FixedPeersIter;
 use peers::PeersIterState;
 
-use crate::kbucket::{Key, KeyBytes};
+use crate::kbucket::{Key, KeyBytes, KeyHasher, Sha256Hasher};
 use crate::{ALPHA_VALUE, K_VALUE};
 use either::Either;
 use fnv::FnvHashMap;
 use instant::Instant;
 use libp2p_identity::PeerId;
-use std::{num::NonZeroUsize, time::Duration};
+use std::{num::NonZeroUsize, sync::Arc, time::Duration};
+
+/// The maximum number of peers whose latency is tracked by a [`QueryPool`].
+const MAX_PEER_LATENCIES: usize = 4096;
+
+/// The duration after which the latency of a peer without new samples is forgotten.
+const PEER_LATENCY_TTL: Duration = Duration::from_secs(30 * 60);
 
 /// A `QueryPool` provides an aggregate state machine for driving `Query`s to completion.
 ///
@@ -43,6 +49,8 @@ pub struct QueryPool<TInner> {
     next_id: usize,
     config: QueryConfig,
     queries: FnvHashMap<QueryId, Query<TInner>>,
+    /// Smoothed round-trip times of peers, see [`QueryPool::record_latency`].
+    latencies: PeerLatencies,
 }
 
 /// The observable states emitted by [`QueryPool::poll`].
@@ -65,9 +73,30 @@ impl<TInner> QueryPool<TInner> {
             next_id: 0,
             config,
             queries: Default::default(),
+            latencies: Default::default(),
         }
     }
 
+    /// Records a round-trip time sample of the given peer.
+    ///
+    /// Samples are smoothed with an exponentially weighted moving average. With
+    /// [`QueryConfig::latency_weighting`], iterative queries prefer peers of low latency.
+    /// The latency of a peer is forgotten if no new sample is recorded for
+    /// [`PEER_LATENCY_TTL`].
+    pub fn record_latency(&mut self, peer: PeerId, rtt: Duration, now: Instant) {
+        self.latencies.record(peer, rtt, now)
+    }
+
+    /// Returns the smoothed round-trip time of the given peer, if known.
+    pub fn latency(&self, peer: &PeerId, now: Instant) -> Option<Duration> {
+        self.latencies.get(peer, now)
+    }
+
+    /// Forgets the round-trip time of the given peer.
+    pub fn remove_latency(&mut self, peer: &PeerId) {
+        self.latencies.remove(peer);
+    }
+
     /// Gets a reference to the `QueryConfig` used by the pool.
     pub fn config(&self) -> &QueryConfig {
         &self.config
@@ -114,12 +143,29 @@ impl<TInner> QueryPool<TInner> {
 
     /// Adds a query to the pool that iterates towards the closest peers to the target.
     pub fn add_iter_closest<T, I>(&mut self, target: T, peers: I, inner: TInner) -> QueryId
+    where
+        T: Into<KeyBytes> + Clone,
+        I: IntoIterator<Item = Key<PeerId>>,
+    {
+        let num_results = self.config.replication_factor;
+        self.add_iter_closest_with_num_results(target, peers, num_results, inner)
+    }
+
+    /// Adds a query to the pool that iterates towards the `num_results` closest peers to the
+    /// target, instead of the configured replication factor.
+    pub fn add_iter_closest_with_num_results<T, I>(
+        &mut self,
+        target: T,
+        peers: I,
+        num_results: NonZeroUsize,
+        inner: TInner,
+    ) -> QueryId
     where
         T: Into<KeyBytes> + Clone,
         I: IntoIterator<Item = Key<PeerId>>,
     {
         let id = self.next_query_id();
-        self.continue_iter_closest(id, target, peers, inner);
+        self.continue_iter_closest_with_num_results(id, target, peers, num_results, inner);
         id
     }
 
@@ -128,10 +174,26 @@ impl<TInner> QueryPool<TInner> {
     where
         T: Into<KeyBytes> + Clone,
         I: IntoIterator<Item = Key<PeerId>>,
+    {
+        let num_results = self.config.replication_factor;
+        self.continue_iter_closest_with_num_results(id, target, peers, num_results, inner)
+    }
+
+    fn continue_iter_closest_with_num_results<T, I>(
+        &mut self,
+        id: QueryId,
+        target: T,
+        peers: I,
+        num_results: NonZeroUsize,
+        inner: TInner,
+    ) where
+        T: Into<KeyBytes> + Clone,
+        I: IntoIterator<Item = Key<PeerId>>,
     {
         let cfg = ClosestPeersIterConfig {
-            num_results: self.config.replication_factor,
+            num_results,
             parallelism: self.config.parallelism,
+            key_hasher: self.config.key_hasher.clone(),
             ..ClosestPeersIterConfig::default()
         };
 
@@ -163,15 +225,43 @@ impl<TInner> QueryPool<TInner> {
         self.queries.get_mut(id)
     }
 
+    /// Removes and returns the queries that have not been polled yet.
+    pub fn remove_unstarted(&mut self) -> Vec<Query<TInner>> {
+        let ids = self
+            .queries
+            .iter()
+            .filter(|(_, query)| query.stats.start.is_none())
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        ids.into_iter()
+            .filter_map(|id| self.queries.remove(&id))
+            .collect()
+    }
+
     /// Polls the pool to advance the queries.
     pub fn poll(&mut self, now: Instant) -> QueryPoolState<'_, TInner> {
         let mut finished = None;
         let mut timeout = None;
         let mut waiting = None;
 
+        for query in self.queries.values_mut() {
+            for (peer, rtt) in query.latency_samples.drain(..) {
+                self.latencies.record(peer, rtt, now);
+            }
+        }
+        self.latencies.poll_expiry(now);
+
+        let no_latencies = FnvHashMap::default();
+        let latencies = if self.config.latency_weighting {
+            &self.latencies.rtts
+        } else {
+            &no_latencies
+        };
+
         for (&query_id, query) in self.queries.iter_mut() {
             query.stats.start = query.stats.start.or(Some(now));
-            match query.next(now) {
+            match query.next(now, latencies) {
                 PeersIterState::Finished => {
                     finished = Some(query_id);
                     break;
@@ -216,6 +306,88 @@ impl<TInner> QueryPool<TInner> {
     }
 }
 
+/// Smoothed round-trip times of peers, forgotten after [`PEER_LATENCY_TTL`] without samples.
+#[derive(Default)]
+struct PeerLatencies {
+    rtts: FnvHashMap<PeerId, Duration>,
+    /// When the last sample of each peer in `rtts` was recorded.
+    sampled_at: FnvHashMap<PeerId, Instant>,
+    /// When expired entries are pruned next, see [`PeerLatencies::poll_expiry`].
+    next_expiry: Option<Instant>,
+}
+
+impl PeerLatencies {
+    /// Adds a round-trip time sample to the smoothed latency of the peer.
+    ///
+    /// If [`MAX_PEER_LATENCIES`] peers are tracked already, expired entries
+    /// and then the least recently sampled peer make room for the new one.
+    fn record(&mut self, peer: PeerId, rtt: Duration, now: Instant) {
+        let expired = self.is_expired(&peer, now);
+        match self.rtts.get_mut(&peer) {
+            Some(latency) if !expired => *latency = (*latency * 7 + rtt) / 8,
+            Some(latency) => *latency = rtt,
+            None => {
+                if self.rtts.len() >= MAX_PEER_LATENCIES {
+                    self.expire(now);
+                }
+                if self.rtts.len() >= MAX_PEER_LATENCIES {
+                    let oldest = self
+                        .sampled_at
+                        .iter()
+                        .min_by_key(|(_, sampled_at)| **sampled_at)
+                        .map(|(peer, _)| *peer);
+                    if let Some(oldest) = oldest {
+                        self.remove(&oldest);
+                    }
+                }
+                self.rtts.insert(peer, rtt);
+            }
+        }
+        self.sampled_at.insert(peer, now);
+    }
+
+    fn get(&self, peer: &PeerId, now: Instant) -> Option<Duration> {
+        if self.is_expired(peer, now) {
+            return None;
+        }
+        self.rtts.get(peer).copied()
+    }
+
+    fn remove(&mut self, peer: &PeerId) {
+        self.rtts.remove(peer);
+        self.sampled_at.remove(peer);
+    }
+
+    fn is_expired(&self, peer: &PeerId, now: Instant) -> bool {
+        self.sampled_at.get(peer).map_or(false, |sampled_at| {
+            now.saturating_duration_since(*sampled_at) >= PEER_LATENCY_TTL
+        })
+    }
+
+    /// Forgets the latencies of all peers without samples for [`PEER_LATENCY_TTL`].
+    fn expire(&mut self, now: Instant) {
+        let rtts = &mut self.rtts;
+        self.sampled_at.retain(|peer, sampled_at| {
+            let expired = now.saturating_duration_since(*sampled_at) >= PEER_LATENCY_TTL;
+            if expired {
+                rtts.remove(peer);
+            }
+            !expired
+        });
+    }
+
+    /// Prunes expired entries, at most once per tenth of [`PEER_LATENCY_TTL`].
+    fn poll_expiry(&mut self, now: Instant) {
+        if self
+            .next_expiry
+            .map_or(true, |next_expiry| now >= next_expiry)
+        {
+            self.expire(now);
+            self.next_expiry = Some(now + PEER_LATENCY_TTL / 10);
+        }
+    }
+}
+
 /// Unique identifier for an active query.
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub struct QueryId(usize);
@@ -242,6 +414,16 @@ pub struct QueryConfig {
     ///
     /// See [`crate::behaviour::KademliaConfig::disjoint_query_paths`] for details.
     pub disjoint_query_paths: bool,
+
+    /// Whether iterative queries prefer peers of low latency.
+    ///
+    /// See [`crate::behaviour::KademliaConfig::set_latency_weighting`] for details.
+    pub latency_weighting: bool,
+
+    /// The hasher mapping peer IDs and record keys into the DHT keyspace.
+    ///
+    /// See [`crate::behaviour::KademliaConfig::set_key_hasher`] for details.
+    pub key_hasher: Arc<dyn KeyHasher>,
 }
 
 impl Default for QueryConfig {
@@ -251,6 +433,8 @@ impl Default for QueryConfig {
             replication_factor: NonZeroUsize::new(K_VALUE.get()).expect("K_VALUE > 0"),
             parallelism: ALPHA_VALUE,
             disjoint_query_paths: false,
+            latency_weighting: false,
+            key_hasher: Arc::new(Sha256Hasher),
         }
     }
 }
@@ -263,6 +447,10 @@ pub struct Query<TInner> {
     peer_iter: QueryPeerIter,
     /// Execution statistics of the query.
     stats: QueryStats,
+    /// When the pending requests to peers were sent.
+    requests_sent: FnvHashMap<PeerId, Instant>,
+    /// Round-trip times of successful requests, not yet recorded by the [`QueryPool`].
+    latency_samples: Vec<(PeerId, Duration)>,
     /// The opaque inner query state.
     pub inner: TInner,
 }
@@ -282,6 +470,8 @@ impl<TInner> Query<TInner> {
             inner,
             peer_iter,
             stats: QueryStats::empty(),
+            requests_sent: Default::default(),
+            latency_samples: Vec::new(),
         }
     }
 
@@ -297,6 +487,7 @@ impl<TInner> Query<TInner> {
 
     /// Informs the query that the attempt to contact `peer` failed.
     pub fn on_failure(&mut self, peer: &PeerId) {
+        self.requests_sent.remove(peer);
         let updated = match &mut self.peer_iter {
             QueryPeerIter::Closest(iter) => iter.on_failure(peer),
             QueryPeerIter::ClosestDisjoint(iter) => iter.on_failure(peer),
@@ -322,6 +513,9 @@ impl<TInner> Query<TInner> {
         if updated {
             self.stats.success += 1;
         }
+        if let Some(sent) = self.requests_sent.remove(peer) {
+            self.latency_samples.push((*peer, sent.elapsed()));
+        }
     }
 
     /// Checks whether the query is currently waiting for a result from `peer`.
@@ -334,15 +528,20 @@ impl<TInner> Query<TInner> {
     }
 
     /// Advances the state of the underlying peer iterator.
-    fn next(&mut self, now: Instant) -> PeersIterState<'_> {
+    fn next(
+        &mut self,
+        now: Instant,
+        latencies: &FnvHashMap<PeerId, Duration>,
+    ) -> PeersIterState<'_> {
         let state = match &mut self.peer_iter {
-            QueryPeerIter::Closest(iter) => iter.next(now),
-            QueryPeerIter::ClosestDisjoint(iter) => iter.next(now),
+            QueryPeerIter::Closest(iter) => iter.next_with_latencies(now, latencies),
+            QueryPeerIter::ClosestDisjoint(iter) => iter.next_with_latencies(now, latencies),
             QueryPeerIter::Fixed(iter) => iter.next(),
         };
 
-        if let PeersIterState::Waiting(Some(_)) = state {
+        if let PeersIterState::Waiting(Some(peer)) = &state {
             self.stats.requests += 1;
+            self.requests_sent.insert(peer.clone().into_owned(), now);
         }
 
         state
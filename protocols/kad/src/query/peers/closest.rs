@@ -20,12 +20,13 @@
 
 use super::*;
 
-use crate::kbucket::{Distance, Key, KeyBytes};
+use crate::kbucket::{Distance, Key, KeyBytes, KeyHasher, Sha256Hasher};
 use crate::{ALPHA_VALUE, K_VALUE};
+use fnv::FnvHashMap;
 use instant::Instant;
 use libp2p_identity::PeerId;
 use std::collections::btree_map::{BTreeMap, Entry};
-use std::{iter::FromIterator, num::NonZeroUsize, time::Duration};
+use std::{iter::FromIterator, num::NonZeroUsize, sync::Arc, time::Duration};
 
 pub mod disjoint;
 
@@ -72,6 +73,10 @@ pub struct ClosestPeersIterConfig {
     /// the peer when evaluating the termination conditions, until and unless a
     /// result is delivered. Defaults to `10` seconds.
     pub peer_timeout: Duration,
+
+    /// The hasher mapping peer IDs into the DHT keyspace of the target.
+    /// Defaults to [`Sha256Hasher`].
+    pub key_hasher: Arc<dyn KeyHasher>,
 }
 
 impl Default for ClosestPeersIterConfig {
@@ -80,6 +85,7 @@ impl Default for ClosestPeersIterConfig {
             parallelism: ALPHA_VALUE,
             num_results: K_VALUE,
             peer_timeout: Duration::from_secs(10),
+            key_hasher: Arc::new(Sha256Hasher),
         }
     }
 }
@@ -157,7 +163,7 @@ impl ClosestPeersIter {
             return false;
         }
 
-        let key = Key::from(*peer);
+        let key = Key::peer_with_hasher(*peer, &*self.config.key_hasher);
         let distance = key.distance(&self.target);
 
         // Mark the peer as succeeded.
@@ -181,7 +187,7 @@ impl ClosestPeersIter {
 
         // Incorporate the reported closer peers into the iterator.
         for peer in closer_peers {
-            let key = peer.into();
+            let key = Key::peer_with_hasher(peer, &*self.config.key_hasher);
             let distance = self.target.distance(&key);
             let peer = Peer {
                 key,
@@ -233,7 +239,7 @@ impl ClosestPeersIter {
             return false;
         }
 
-        let key = Key::from(*peer);
+        let key = Key::peer_with_hasher(*peer, &*self.config.key_hasher);
         let distance = key.distance(&self.target);
 
         match self.closest_peers.entry(distance) {
@@ -276,6 +282,18 @@ impl ClosestPeersIter {
 
     /// Advances the state of the iterator, potentially getting a new peer to contact.
     pub fn next(&mut self, now: Instant) -> PeersIterState<'_> {
+        self.next_with_latencies(now, &FnvHashMap::default())
+    }
+
+    /// Like [`ClosestPeersIter::next`], but contacts the peer with the lowest known latency
+    /// among the next `parallelism` closest peers not contacted yet, instead of the closest.
+    ///
+    /// Peers of unknown latency are ranked after those of known latency, by distance.
+    pub fn next_with_latencies(
+        &mut self,
+        now: Instant,
+        latencies: &FnvHashMap<PeerId, Duration>,
+    ) -> PeersIterState<'_> {
         if let State::Finished = self.state {
             return PeersIterState::Finished;
         }
@@ -290,7 +308,9 @@ impl ClosestPeersIter {
         // Check if the iterator is at capacity w.r.t. the allowed parallelism.
         let at_capacity = self.at_capacity();
 
-        for peer in self.closest_peers.values_mut() {
+        let mut to_contact = None;
+
+        for (distance, peer) in self.closest_peers.iter_mut() {
             match peer.state {
                 PeerState::Waiting(timeout) => {
                     if now >= timeout {
@@ -327,10 +347,8 @@ impl ClosestPeersIter {
 
                 PeerState::NotContacted => {
                     if !at_capacity {
-                        let timeout = now + self.config.peer_timeout;
-                        peer.state = PeerState::Waiting(timeout);
-                        self.num_waiting += 1;
-                        return PeersIterState::Waiting(Some(Cow::Borrowed(peer.key.preimage())));
+                        to_contact = Some(*distance);
+                        break;
                     } else {
                         return PeersIterState::WaitingAtCapacity;
                     }
@@ -342,6 +360,18 @@ impl ClosestPeersIter {
             }
         }
 
+        if let Some(distance) = to_contact {
+            let distance = self.lowest_latency_candidate(distance, latencies);
+            let timeout = now + self.config.peer_timeout;
+            let peer = self
+                .closest_peers
+                .get_mut(&distance)
+                .expect("candidate to be a known peer");
+            peer.state = PeerState::Waiting(timeout);
+            self.num_waiting += 1;
+            return PeersIterState::Waiting(Some(Cow::Borrowed(peer.key.preimage())));
+        }
+
         if self.num_waiting > 0 {
             // The iterator is still waiting for results and not at capacity w.r.t.
             // the allowed parallelism, but there are no new peers to contact
@@ -379,6 +409,31 @@ impl ClosestPeersIter {
             .take(self.config.num_results.get())
     }
 
+    /// Returns the distance of the peer with the lowest latency among the `parallelism` closest
+    /// peers not contacted yet, starting with the closest one at distance `closest`.
+    fn lowest_latency_candidate(
+        &self,
+        closest: Distance,
+        latencies: &FnvHashMap<PeerId, Duration>,
+    ) -> Distance {
+        if latencies.is_empty() {
+            return closest;
+        }
+
+        self.closest_peers
+            .range(closest..)
+            .filter(|(_, peer)| matches!(peer.state, PeerState::NotContacted))
+            .take(self.config.parallelism.get())
+            .min_by_key(|(_, peer)| {
+                latencies
+                    .get(peer.key.preimage())
+                    .copied()
+                    .unwrap_or(Duration::MAX)
+            })
+            .map(|(distance, _)| *distance)
+            .unwrap_or(closest)
+    }
+
     /// Checks if the iterator is at capacity w.r.t. the permitted parallelism.
     ///
     /// While the iterator is stalled, up to `num_results` parallel requests
@@ -479,7 +534,7 @@ mod tests {
     use libp2p_core::multihash::Multihash;
     use libp2p_identity::PeerId;
     use quickcheck::*;
-    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
     use std::{iter, time::Duration};
 
     fn random_peers<R: Rng>(n: usize, g: &mut R) -> Vec<PeerId> {
@@ -519,6 +574,7 @@ mod tests {
                 parallelism: NonZeroUsize::new(g.gen_range(1..10)).unwrap(),
                 num_results: NonZeroUsize::new(g.gen_range(1..25)).unwrap(),
                 peer_timeout: Duration::from_secs(g.gen_range(10..30)),
+                ..ClosestPeersIterConfig::default()
             };
             ClosestPeersIter::with_config(config, target, known_closest_peers)
         }
@@ -789,6 +845,49 @@ mod tests {
         QuickCheck::new().tests(10).quickcheck(prop as fn(_))
     }
 
+    #[test]
+    fn prefer_low_latency_peers() {
+        let target = Key::from(PeerId::random());
+        let mut peers = random_peers(10, &mut thread_rng())
+            .into_iter()
+            .map(Key::from)
+            .collect::<Vec<_>>();
+        peers.sort_by_key(|p| p.distance(&target));
+
+        let config = ClosestPeersIterConfig {
+            parallelism: NonZeroUsize::new(3).unwrap(),
+            ..ClosestPeersIterConfig::default()
+        };
+        let mut iter = ClosestPeersIter::with_config(config, target, peers.clone());
+
+        // Of the 3 closest peers, the third responds fastest. The sixth is even faster,
+        // but never among the 3 closest peers not yet contacted.
+        let latencies = FnvHashMap::from_iter([
+            (*peers[1].preimage(), Duration::from_millis(200)),
+            (*peers[2].preimage(), Duration::from_millis(50)),
+            (*peers[5].preimage(), Duration::from_millis(10)),
+        ]);
+
+        let now = Instant::now();
+        let mut contacted = Vec::new();
+        for _ in 0..3 {
+            match iter.next_with_latencies(now, &latencies) {
+                PeersIterState::Waiting(Some(p)) => contacted.push(p.into_owned()),
+                s => panic!("Unexpected iterator state: {s:?}"),
+            }
+        }
+
+        // Peers of known latency come first, then the closest one of unknown latency.
+        assert_eq!(
+            contacted,
+            vec![
+                *peers[2].preimage(),
+                *peers[1].preimage(),
+                *peers[0].preimage()
+            ]
+        );
+    }
+
     fn stalled_at_capacity() {
         fn prop(mut iter: ClosestPeersIter) {
             iter.state = State::Stalled;
@@ -20,12 +20,14 @@
 
 use super::*;
 use crate::kbucket::{Key, KeyBytes};
+use fnv::FnvHashMap;
 use instant::Instant;
 use libp2p_identity::PeerId;
 use std::{
     collections::HashMap,
     iter::{Cycle, Map, Peekable},
     ops::{Index, IndexMut, Range},
+    time::Duration,
 };
 
 /// Wraps around a set of [`ClosestPeersIter`], enforcing a disjoint discovery
@@ -195,6 +197,16 @@ impl ClosestDisjointPeersIter {
     }
 
     pub fn next(&mut self, now: Instant) -> PeersIterState<'_> {
+        self.next_with_latencies(now, &FnvHashMap::default())
+    }
+
+    /// Like [`ClosestDisjointPeersIter::next`], preferring peers of low latency on each path,
+    /// see [`ClosestPeersIter::next_with_latencies`].
+    pub fn next_with_latencies(
+        &mut self,
+        now: Instant,
+        latencies: &FnvHashMap<PeerId, Duration>,
+    ) -> PeersIterState<'_> {
         let mut state = None;
 
         // Ensure querying each iterator at most once.
@@ -203,7 +215,7 @@ impl ClosestDisjointPeersIter {
             let iter = &mut self.iters[i];
 
             loop {
-                match iter.next(now) {
+                match iter.next_with_latencies(now, latencies) {
                     PeersIterState::Waiting(None) => {
                         match state {
                             Some(PeersIterState::Waiting(Some(_))) => {
@@ -324,10 +336,12 @@ impl ClosestDisjointPeersIter {
     ///       differentiate benign from faulty paths it as well returns faulty
     ///       peers and thus overall returns more than `num_results` peers.
     pub fn into_result(self) -> impl Iterator<Item = PeerId> {
-        let result_per_path = self
-            .iters
-            .into_iter()
-            .map(|iter| iter.into_result().map(Key::from));
+        let key_hasher = self.config.key_hasher;
+        let result_per_path = self.iters.into_iter().map(|iter| {
+            let key_hasher = key_hasher.clone();
+            iter.into_result()
+                .map(move |peer| Key::peer_with_hasher(peer, &*key_hasher))
+        });
 
         ResultIter::new(self.target, result_per_path).map(Key::into_preimage)
     }
@@ -604,6 +618,7 @@ mod tests {
                 parallelism: Parallelism::arbitrary(g).0,
                 num_results: NumResults::arbitrary(g).0,
                 peer_timeout: Duration::from_secs(1),
+                ..ClosestPeersIterConfig::default()
             }
         }
     }
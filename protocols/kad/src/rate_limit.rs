@@ -0,0 +1,255 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Rate limits on inbound requests, protecting nodes in server mode from request floods.
+
+use instant::Instant;
+use libp2p_identity::PeerId;
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+/// A limit of `max_requests` inbound requests per `interval`.
+///
+/// Up to `max_requests` requests are accepted in a burst, after which the capacity is
+/// replenished evenly over `interval`. See
+/// [`KademliaConfig::set_inbound_rate_limit`](crate::KademliaConfig::set_inbound_rate_limit).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InboundRateLimit {
+    max_requests: NonZeroU32,
+    interval: Duration,
+}
+
+impl InboundRateLimit {
+    /// Creates a limit of `max_requests` requests per `interval`.
+    pub fn new(max_requests: NonZeroU32, interval: Duration) -> Self {
+        Self {
+            max_requests,
+            interval,
+        }
+    }
+
+    /// The maximum number of requests per [`InboundRateLimit::interval`].
+    pub fn max_requests(&self) -> NonZeroU32 {
+        self.max_requests
+    }
+
+    /// The interval over which the capacity for [`InboundRateLimit::max_requests`] requests is
+    /// replenished.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+/// Counters of the inbound requests exceeding the rate limits, see
+/// [`Kademlia::inbound_throttle_stats`](crate::Kademlia::inbound_throttle_stats).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InboundThrottleStats {
+    throttled_by_peer_limit: u64,
+    throttled_by_global_limit: u64,
+}
+
+impl InboundThrottleStats {
+    /// The number of requests throttled because their sender exceeded the per-peer limit.
+    pub fn throttled_by_peer_limit(&self) -> u64 {
+        self.throttled_by_peer_limit
+    }
+
+    /// The number of requests throttled because all peers together exceeded the global limit.
+    pub fn throttled_by_global_limit(&self) -> u64 {
+        self.throttled_by_global_limit
+    }
+
+    /// The total number of throttled requests.
+    pub fn throttled(&self) -> u64 {
+        self.throttled_by_peer_limit + self.throttled_by_global_limit
+    }
+}
+
+/// The remaining capacity for requests under an [`InboundRateLimit`].
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: &InboundRateLimit, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(limit.max_requests.get()),
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, limit: &InboundRateLimit, now: Instant) {
+        let max = f64::from(limit.max_requests.get());
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        let refilled = if limit.interval.is_zero() {
+            max
+        } else {
+            elapsed.as_secs_f64() * max / limit.interval.as_secs_f64()
+        };
+        self.tokens = (self.tokens + refilled).min(max);
+        self.last_refill = now;
+    }
+
+    fn is_full(&self, limit: &InboundRateLimit) -> bool {
+        self.tokens >= f64::from(limit.max_requests.get())
+    }
+}
+
+/// Enforces the per-peer and the global [`InboundRateLimit`].
+#[derive(Debug)]
+pub(crate) struct InboundRateLimiter {
+    per_peer: Option<InboundRateLimit>,
+    global: Option<InboundRateLimit>,
+    peers: HashMap<PeerId, TokenBucket>,
+    global_bucket: Option<TokenBucket>,
+    /// When to forget the peers that have not sent requests for a while next.
+    next_prune: Option<Instant>,
+    stats: InboundThrottleStats,
+}
+
+impl InboundRateLimiter {
+    pub(crate) fn new(
+        per_peer: Option<InboundRateLimit>,
+        global: Option<InboundRateLimit>,
+    ) -> Self {
+        Self {
+            per_peer,
+            global,
+            peers: HashMap::new(),
+            global_bucket: None,
+            next_prune: None,
+            stats: InboundThrottleStats::default(),
+        }
+    }
+
+    pub(crate) fn stats(&self) -> InboundThrottleStats {
+        self.stats
+    }
+
+    /// Returns whether an inbound request of `peer` is within the limits, accounting for it if so.
+    pub(crate) fn try_acquire(&mut self, peer: PeerId, now: Instant) -> bool {
+        if self.per_peer.is_none() && self.global.is_none() {
+            return true;
+        }
+        self.prune(now);
+
+        if let Some(limit) = &self.per_peer {
+            let bucket = self
+                .peers
+                .entry(peer)
+                .or_insert_with(|| TokenBucket::new(limit, now));
+            bucket.refill(limit, now);
+            if bucket.tokens < 1.0 {
+                self.stats.throttled_by_peer_limit += 1;
+                return false;
+            }
+        }
+
+        if let Some(limit) = &self.global {
+            let bucket = self
+                .global_bucket
+                .get_or_insert_with(|| TokenBucket::new(limit, now));
+            bucket.refill(limit, now);
+            if bucket.tokens < 1.0 {
+                self.stats.throttled_by_global_limit += 1;
+                return false;
+            }
+            bucket.tokens -= 1.0;
+        }
+
+        if let Some(bucket) = self.peers.get_mut(&peer) {
+            bucket.tokens -= 1.0;
+        }
+        true
+    }
+
+    /// Forgets the peers whose capacity has been replenished completely, at most once per
+    /// interval of the per-peer limit.
+    fn prune(&mut self, now: Instant) {
+        let limit = match &self.per_peer {
+            Some(limit) => limit,
+            None => return,
+        };
+        if self.next_prune.map_or(false, |next| now < next) {
+            return;
+        }
+        self.next_prune = Some(now + limit.interval);
+        self.peers.retain(|_, bucket| {
+            bucket.refill(limit, now);
+            !bucket.is_full(limit)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(max_requests: u32, interval: Duration) -> InboundRateLimit {
+        InboundRateLimit::new(NonZeroU32::new(max_requests).unwrap(), interval)
+    }
+
+    #[test]
+    fn limits_requests_per_peer() {
+        let mut limiter = InboundRateLimiter::new(Some(limit(2, Duration::from_secs(10))), None);
+        let (a, b) = (PeerId::random(), PeerId::random());
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire(a, now));
+        assert!(limiter.try_acquire(a, now));
+        assert!(!limiter.try_acquire(a, now));
+        // Other peers are limited separately.
+        assert!(limiter.try_acquire(b, now));
+
+        // The capacity is replenished over the interval.
+        assert!(limiter.try_acquire(a, now + Duration::from_secs(5)));
+        assert!(!limiter.try_acquire(a, now + Duration::from_secs(5)));
+
+        assert_eq!(limiter.stats().throttled_by_peer_limit(), 2);
+        assert_eq!(limiter.stats().throttled_by_global_limit(), 0);
+
+        // Peers with replenished capacity are forgotten.
+        assert!(limiter.try_acquire(a, now + Duration::from_secs(30)));
+        assert_eq!(limiter.peers.len(), 1);
+    }
+
+    #[test]
+    fn limits_requests_of_all_peers() {
+        let mut limiter = InboundRateLimiter::new(
+            Some(limit(2, Duration::from_secs(10))),
+            Some(limit(3, Duration::from_secs(10))),
+        );
+        let (a, b) = (PeerId::random(), PeerId::random());
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire(a, now));
+        assert!(limiter.try_acquire(a, now));
+        assert!(!limiter.try_acquire(a, now));
+        assert!(limiter.try_acquire(b, now));
+        assert!(!limiter.try_acquire(b, now));
+
+        assert_eq!(limiter.stats().throttled_by_peer_limit(), 1);
+        assert_eq!(limiter.stats().throttled_by_global_limit(), 1);
+        assert_eq!(limiter.stats().throttled(), 2);
+    }
+}
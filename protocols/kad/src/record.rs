@@ -105,6 +105,30 @@ impl Record {
     }
 }
 
+/// Validates the records stored in the DHT, e.g. by verifying a signature
+/// contained in the value against the key.
+///
+/// Used by the record repair job to detect corrupted records in the
+/// local store, see
+/// [`KademliaConfig::set_record_validator`](crate::KademliaConfig::set_record_validator).
+pub trait RecordValidator: std::fmt::Debug + Send + Sync + 'static {
+    /// Returns whether the given record is valid.
+    fn validate(&self, record: &Record) -> bool;
+}
+
+/// The default [`RecordValidator`], accepting all records.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AcceptAllValidator;
+
+impl RecordValidator for AcceptAllValidator {
+    fn validate(&self, _: &Record) -> bool {
+        true
+    }
+}
+
+/// The maximum size in bytes of the [`ProviderRecord::metadata`].
+pub const MAX_PROVIDER_METADATA_SIZE: usize = 1024;
+
 /// A record stored in the DHT whose value is the ID of a peer
 /// who can provide the value on-demand.
 ///
@@ -121,6 +145,9 @@ pub struct ProviderRecord {
     pub expires: Option<Instant>,
     /// The known addresses that the provider may be listening on.
     pub addresses: Vec<Multiaddr>,
+    /// Opaque metadata announced by the provider along with the record, e.g. the
+    /// protocols the value can be obtained with. At most [`MAX_PROVIDER_METADATA_SIZE`] bytes.
+    pub metadata: Vec<u8>,
 }
 
 impl Hash for ProviderRecord {
@@ -149,9 +176,16 @@ impl ProviderRecord {
             provider,
             expires: None,
             addresses,
+            metadata: Vec::new(),
         }
     }
 
+    /// Sets the metadata of the provider record, see [`ProviderRecord::metadata`].
+    pub fn with_metadata(mut self, metadata: Vec<u8>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
     /// Checks whether the provider record is expired w.r.t. the given `Instant`.
     pub fn is_expired(&self, now: Instant) -> bool {
         self.expires.map_or(false, |t| now >= t)
@@ -202,6 +236,7 @@ mod tests {
                     None
                 },
                 addresses: vec![],
+                metadata: Vec::arbitrary(g),
             }
         }
     }
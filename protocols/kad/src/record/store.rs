@@ -44,6 +44,10 @@ pub enum Error {
     /// The store cannot store this value because it is too large.
     #[error("the value is too large to be stored")]
     ValueTooLarge,
+
+    /// The quorum of a record exceeds the replication factor of its namespace.
+    #[error("the quorum exceeds the replication factor of the record's namespace")]
+    QuorumTooLarge,
 }
 
 /// Trait for types implementing a record store.
@@ -26,6 +26,7 @@ use smallvec::SmallVec;
 use std::borrow::Cow;
 use std::collections::{hash_map, hash_set, HashMap, HashSet};
 use std::iter;
+use std::sync::Arc;
 
 /// In-memory implementation of a `RecordStore`.
 pub struct MemoryStore {
@@ -33,6 +34,8 @@ pub struct MemoryStore {
     local_key: kbucket::Key<PeerId>,
     /// The configuration of the store.
     config: MemoryStoreConfig,
+    /// The hasher used to order providers by their distance to a key.
+    key_hasher: Arc<dyn kbucket::KeyHasher>,
     /// The stored (regular) records.
     records: HashMap<Key, Record>,
     /// The stored provider records.
@@ -81,12 +84,23 @@ impl MemoryStore {
         MemoryStore {
             local_key: kbucket::Key::from(local_id),
             config,
+            key_hasher: Arc::new(kbucket::Sha256Hasher),
             records: HashMap::default(),
             provided: HashSet::default(),
             providers: HashMap::default(),
         }
     }
 
+    /// Sets the hasher used to order providers by their distance to a key.
+    ///
+    /// Should match the hasher configured via
+    /// [`KademliaConfig::set_key_hasher`](crate::KademliaConfig::set_key_hasher).
+    pub fn with_key_hasher(mut self, hasher: Arc<dyn kbucket::KeyHasher>) -> Self {
+        self.local_key = kbucket::Key::peer_with_hasher(*self.local_key.preimage(), &*hasher);
+        self.key_hasher = hasher;
+        self
+    }
+
     /// Retains the records satisfying a predicate.
     pub fn retain<F>(&mut self, f: F)
     where
@@ -160,10 +174,11 @@ impl RecordStore for MemoryStore {
         } else {
             // It is a new provider record for that key.
             let local_key = self.local_key.clone();
-            let key = kbucket::Key::new(record.key.clone());
-            let provider = kbucket::Key::from(record.provider);
+            let key_hasher = &*self.key_hasher;
+            let key = kbucket::Key::with_hasher(record.key.clone(), key_hasher);
+            let provider = kbucket::Key::peer_with_hasher(record.provider, key_hasher);
             if let Some(i) = providers.iter().position(|p| {
-                let pk = kbucket::Key::from(p.provider);
+                let pk = kbucket::Key::peer_with_hasher(p.provider, key_hasher);
                 provider.distance(&key) < pk.distance(&key)
             }) {
                 // Insert the new provider.
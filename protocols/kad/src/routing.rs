@@ -0,0 +1,349 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Composable content routing.
+//!
+//! A [`Routing`] implementation is a source of provider records, e.g. the
+//! Kademlia DHT itself, a delegated HTTP router or an application-specific
+//! index. Multiple sources can be layered with [`Routers`].
+
+use crate::record;
+use fnv::FnvHashMap;
+use libp2p_identity::PeerId;
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+
+static NEXT_LOOKUP_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// Identifier of a provider lookup started via [`Routing::find_providers`].
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LookupId(usize);
+
+impl LookupId {
+    /// Returns the next available [`LookupId`].
+    pub fn next() -> Self {
+        Self(NEXT_LOOKUP_ID.fetch_add(1, Ordering::SeqCst))
+    }
+}
+
+/// A source of provider records.
+pub trait Routing {
+    /// Starts looking up the providers of `key`.
+    ///
+    /// Progress of the lookup must be reported via [`Routing::poll_providers`],
+    /// tagged with the given `id`. Every lookup must eventually be concluded
+    /// with a [`RoutingEvent::Finished`].
+    fn find_providers(&mut self, id: LookupId, key: &record::Key);
+
+    /// Aborts an ongoing lookup.
+    ///
+    /// Events for the lookup may still be reported after it was cancelled
+    /// and are to be ignored by the caller.
+    fn cancel(&mut self, _id: LookupId) {}
+
+    /// Polls for progress of ongoing lookups.
+    fn poll_providers(&mut self, cx: &mut Context<'_>) -> Poll<RoutingEvent>;
+}
+
+impl<T: Routing + ?Sized> Routing for Box<T> {
+    fn find_providers(&mut self, id: LookupId, key: &record::Key) {
+        (**self).find_providers(id, key)
+    }
+
+    fn cancel(&mut self, id: LookupId) {
+        (**self).cancel(id)
+    }
+
+    fn poll_providers(&mut self, cx: &mut Context<'_>) -> Poll<RoutingEvent> {
+        (**self).poll_providers(cx)
+    }
+}
+
+/// Progress of a lookup started via [`Routing::find_providers`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoutingEvent {
+    /// New providers were discovered.
+    FoundProviders {
+        id: LookupId,
+        /// The new set of providers discovered.
+        providers: HashSet<PeerId>,
+    },
+    /// The lookup is complete and no further providers will be reported.
+    Finished { id: LookupId },
+}
+
+impl RoutingEvent {
+    /// Returns the lookup this event belongs to.
+    pub fn id(&self) -> LookupId {
+        match self {
+            RoutingEvent::FoundProviders { id, .. } | RoutingEvent::Finished { id } => *id,
+        }
+    }
+}
+
+/// The order in which [`Routers`] consults its routers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RoutingStrategy {
+    /// Consult one router at a time, in the order they were added, and stop
+    /// at the first router that found any providers.
+    Sequential,
+    /// Consult all routers at once. The lookup finishes once every router
+    /// has finished.
+    Parallel,
+}
+
+/// A [`Routing`] implementation combining multiple routers.
+pub struct Routers {
+    strategy: RoutingStrategy,
+    routers: Vec<Box<dyn Routing + Send>>,
+    lookups: FnvHashMap<LookupId, RoutersLookup>,
+    events: VecDeque<RoutingEvent>,
+}
+
+struct RoutersLookup {
+    key: record::Key,
+    /// For [`RoutingStrategy::Sequential`], the index of the router currently consulted.
+    current: usize,
+    /// The number of routers that have not yet finished the lookup.
+    pending: usize,
+    /// Whether any providers were reported for the lookup.
+    found: bool,
+}
+
+impl Routers {
+    /// Creates an empty combinator using the given strategy.
+    pub fn new(strategy: RoutingStrategy) -> Self {
+        Routers {
+            strategy,
+            routers: Vec::new(),
+            lookups: Default::default(),
+            events: Default::default(),
+        }
+    }
+
+    /// Adds a router, consulted after all previously added routers.
+    pub fn with(mut self, router: impl Routing + Send + 'static) -> Self {
+        self.push(router);
+        self
+    }
+
+    /// Adds a router, consulted after all previously added routers.
+    pub fn push(&mut self, router: impl Routing + Send + 'static) {
+        self.routers.push(Box::new(router));
+    }
+
+    /// Returns the number of routers.
+    pub fn len(&self) -> usize {
+        self.routers.len()
+    }
+
+    /// Returns `true` if no routers have been added.
+    pub fn is_empty(&self) -> bool {
+        self.routers.is_empty()
+    }
+
+    fn on_router_event(&mut self, router: usize, event: RoutingEvent) {
+        let id = event.id();
+        let lookup = match self.lookups.get_mut(&id) {
+            Some(lookup) => lookup,
+            None => return,
+        };
+        if self.strategy == RoutingStrategy::Sequential && lookup.current != router {
+            return;
+        }
+
+        match event {
+            RoutingEvent::FoundProviders { providers, .. } => {
+                if !providers.is_empty() {
+                    lookup.found = true;
+                    self.events
+                        .push_back(RoutingEvent::FoundProviders { id, providers });
+                }
+            }
+            RoutingEvent::Finished { .. } => {
+                lookup.pending -= 1;
+                match self.strategy {
+                    RoutingStrategy::Sequential if !lookup.found && lookup.pending > 0 => {
+                        lookup.current += 1;
+                        self.routers[lookup.current].find_providers(id, &lookup.key);
+                    }
+                    RoutingStrategy::Parallel if lookup.pending > 0 => {}
+                    _ => {
+                        self.lookups.remove(&id);
+                        self.events.push_back(RoutingEvent::Finished { id });
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Routing for Routers {
+    fn find_providers(&mut self, id: LookupId, key: &record::Key) {
+        if self.routers.is_empty() {
+            self.events.push_back(RoutingEvent::Finished { id });
+            return;
+        }
+
+        match self.strategy {
+            RoutingStrategy::Sequential => self.routers[0].find_providers(id, key),
+            RoutingStrategy::Parallel => {
+                for router in self.routers.iter_mut() {
+                    router.find_providers(id, key);
+                }
+            }
+        }
+        self.lookups.insert(
+            id,
+            RoutersLookup {
+                key: key.clone(),
+                current: 0,
+                pending: self.routers.len(),
+                found: false,
+            },
+        );
+    }
+
+    fn cancel(&mut self, id: LookupId) {
+        if let Some(lookup) = self.lookups.remove(&id) {
+            match self.strategy {
+                RoutingStrategy::Sequential => self.routers[lookup.current].cancel(id),
+                RoutingStrategy::Parallel => {
+                    for router in self.routers.iter_mut() {
+                        router.cancel(id);
+                    }
+                }
+            }
+        }
+    }
+
+    fn poll_providers(&mut self, cx: &mut Context<'_>) -> Poll<RoutingEvent> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Poll::Ready(event);
+            }
+
+            let mut progressed = false;
+            for i in 0..self.routers.len() {
+                if let Poll::Ready(event) = self.routers[i].poll_providers(cx) {
+                    self.on_router_event(i, event);
+                    progressed = true;
+                }
+            }
+
+            if !progressed {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::future::poll_fn;
+
+    /// A router that immediately answers every lookup with a fixed set of providers.
+    struct StaticRouter {
+        providers: HashSet<PeerId>,
+        events: VecDeque<RoutingEvent>,
+        lookups: usize,
+    }
+
+    impl StaticRouter {
+        fn new(providers: impl IntoIterator<Item = PeerId>) -> Self {
+            StaticRouter {
+                providers: providers.into_iter().collect(),
+                events: Default::default(),
+                lookups: 0,
+            }
+        }
+    }
+
+    impl Routing for StaticRouter {
+        fn find_providers(&mut self, id: LookupId, _: &record::Key) {
+            self.lookups += 1;
+            self.events.push_back(RoutingEvent::FoundProviders {
+                id,
+                providers: self.providers.clone(),
+            });
+            self.events.push_back(RoutingEvent::Finished { id });
+        }
+
+        fn poll_providers(&mut self, _: &mut Context<'_>) -> Poll<RoutingEvent> {
+            match self.events.pop_front() {
+                Some(event) => Poll::Ready(event),
+                None => Poll::Pending,
+            }
+        }
+    }
+
+    fn run_lookup(routers: &mut Routers) -> HashSet<PeerId> {
+        let id = LookupId::next();
+        routers.find_providers(id, &record::Key::new(&b"key".to_vec()));
+
+        let mut found = HashSet::new();
+        loop {
+            match block_on(poll_fn(|cx| routers.poll_providers(cx))) {
+                RoutingEvent::FoundProviders {
+                    id: event_id,
+                    providers,
+                } => {
+                    assert_eq!(event_id, id);
+                    found.extend(providers);
+                }
+                RoutingEvent::Finished { id: event_id } => {
+                    assert_eq!(event_id, id);
+                    return found;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sequential_stops_at_first_router_with_providers() {
+        let provider = PeerId::random();
+        let mut routers = Routers::new(RoutingStrategy::Sequential)
+            .with(StaticRouter::new([]))
+            .with(StaticRouter::new([provider]))
+            .with(StaticRouter::new([PeerId::random()]));
+
+        assert_eq!(run_lookup(&mut routers), HashSet::from([provider]));
+    }
+
+    #[test]
+    fn parallel_merges_all_routers() {
+        let providers = [PeerId::random(), PeerId::random()];
+        let mut routers = Routers::new(RoutingStrategy::Parallel)
+            .with(StaticRouter::new([providers[0]]))
+            .with(StaticRouter::new([providers[1]]));
+
+        assert_eq!(run_lookup(&mut routers), HashSet::from(providers));
+    }
+
+    #[test]
+    fn empty_routers_finish_immediately() {
+        let mut routers = Routers::new(RoutingStrategy::Parallel);
+
+        assert!(run_lookup(&mut routers).is_empty());
+    }
+}
@@ -57,6 +57,17 @@ pub struct InterfaceState<U, T> {
     recv_buffer: [u8; 4096],
     /// Buffers pending to send on the main socket.
     send_buffer: VecDeque<Vec<u8>>,
+    /// Buffers pending to send via unicast to the given address from the mDNS port.
+    unicast_send_buffer: VecDeque<(Vec<u8>, SocketAddr)>,
+    /// Whether to answer queries asking for it via unicast.
+    unicast_responses: bool,
+    /// Minimum interval between two multicast responses.
+    multicast_response_interval: Duration,
+    /// When the last multicast response to a query was sent.
+    last_multicast_response: Option<Instant>,
+    /// Timer for a multicast response held back by `multicast_response_interval`, together with
+    /// the ID of the latest query it answers.
+    pending_multicast_response: Option<(T, u16)>,
     /// Discovery interval.
     query_interval: Duration,
     /// Discovery timer.
@@ -132,6 +143,11 @@ where
             send_socket,
             recv_buffer: [0; 4096],
             send_buffer: Default::default(),
+            unicast_send_buffer: Default::default(),
+            unicast_responses: config.unicast_responses,
+            multicast_response_interval: config.multicast_response_interval,
+            last_multicast_response: None,
+            pending_multicast_response: None,
             discovered: Default::default(),
             query_interval,
             timeout: T::interval_at(Instant::now(), query_interval),
@@ -149,6 +165,48 @@ where
         self.timeout = T::interval_at(Instant::now(), self.query_interval);
     }
 
+    /// Queues a multicast response to the query with the given ID, unless a response has been
+    /// multicast within the last `multicast_response_interval`. In that case the response is
+    /// deferred until the interval elapsed, answering all queries received in between.
+    fn queue_multicast_response(
+        &mut self,
+        query_id: u16,
+        listen_addresses: &ListenAddresses,
+        now: Instant,
+    ) {
+        if let Some((_, pending_query_id)) = self.pending_multicast_response.as_mut() {
+            *pending_query_id = query_id;
+            return;
+        }
+
+        match deferred_until(
+            self.last_multicast_response,
+            self.multicast_response_interval,
+            now,
+        ) {
+            Some(at) => {
+                log::trace!("deferring multicast response on iface {}", self.addr);
+                self.pending_multicast_response = Some((T::at(at), query_id));
+            }
+            None => self.send_multicast_response(query_id, listen_addresses, now),
+        }
+    }
+
+    fn send_multicast_response(
+        &mut self,
+        query_id: u16,
+        listen_addresses: &ListenAddresses,
+        now: Instant,
+    ) {
+        self.last_multicast_response = Some(now);
+        self.send_buffer.extend(build_query_response(
+            query_id,
+            self.local_peer_id,
+            listen_addresses.iter(),
+            self.ttl,
+        ));
+    }
+
     pub fn poll(
         &mut self,
         cx: &mut Context,
@@ -161,6 +219,14 @@ where
                 self.send_buffer.push_back(build_query());
             }
 
+            if let Some((timer, query_id)) = self.pending_multicast_response.as_mut() {
+                if Pin::new(timer).poll_next(cx).is_ready() {
+                    let query_id = *query_id;
+                    self.pending_multicast_response = None;
+                    self.send_multicast_response(query_id, listen_addresses, Instant::now());
+                }
+            }
+
             // 2nd priority: Keep local buffers small: Send packets to remote.
             if let Some(packet) = self.send_buffer.pop_front() {
                 match Pin::new(&mut self.send_socket).poll_write(
@@ -182,6 +248,27 @@ where
                 }
             }
 
+            if let Some((packet, to)) = self.unicast_send_buffer.pop_front() {
+                match Pin::new(&mut self.recv_socket).poll_write(cx, &packet, to) {
+                    Poll::Ready(Ok(_)) => {
+                        log::trace!("sent unicast packet to {} on iface {}", to, self.addr);
+                        continue;
+                    }
+                    Poll::Ready(Err(err)) => {
+                        log::error!(
+                            "error sending unicast packet to {} on iface {} {}",
+                            to,
+                            self.addr,
+                            err
+                        );
+                        continue;
+                    }
+                    Poll::Pending => {
+                        self.unicast_send_buffer.push_front((packet, to));
+                    }
+                }
+            }
+
             // 3rd priority: Keep local buffers small: Return discovered addresses.
             if let Some(discovered) = self.discovered.pop_front() {
                 return Poll::Ready(discovered);
@@ -200,12 +287,25 @@ where
                         self.addr
                     );
 
-                    self.send_buffer.extend(build_query_response(
-                        query.query_id(),
-                        self.local_peer_id,
-                        listen_addresses.iter(),
-                        self.ttl,
-                    ));
+                    if self.unicast_responses && query.unicast_response() {
+                        let to = *query.remote_addr();
+                        self.unicast_send_buffer.extend(
+                            build_query_response(
+                                query.query_id(),
+                                self.local_peer_id,
+                                listen_addresses.iter(),
+                                self.ttl,
+                            )
+                            .into_iter()
+                            .map(|packet| (packet, to)),
+                        );
+                    } else {
+                        self.queue_multicast_response(
+                            query.query_id(),
+                            listen_addresses,
+                            Instant::now(),
+                        );
+                    }
                     continue;
                 }
                 Poll::Ready(Ok(Ok(Some(MdnsPacket::Response(response))))) => {
@@ -246,3 +346,42 @@ where
         }
     }
 }
+
+/// Returns until when a multicast response has to be deferred, given when the `last` one was
+/// sent, or `None` if it may be sent right away.
+fn deferred_until(last: Option<Instant>, interval: Duration, now: Instant) -> Option<Instant> {
+    let next = last? + interval;
+    (now < next).then_some(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multicast_responses_are_deferred_within_interval() {
+        let now = Instant::now();
+        let interval = Duration::from_secs(1);
+
+        assert_eq!(deferred_until(None, interval, now), None);
+        assert_eq!(
+            deferred_until(Some(now), interval, now + Duration::from_millis(500)),
+            Some(now + interval)
+        );
+        assert_eq!(deferred_until(Some(now), interval, now + interval), None);
+    }
+
+    #[test]
+    fn multicast_responses_are_not_rate_limited_by_default() {
+        let now = Instant::now();
+
+        assert_eq!(
+            deferred_until(
+                Some(now),
+                Config::default().multicast_response_interval,
+                now
+            ),
+            None
+        );
+    }
+}
@@ -54,14 +54,15 @@ impl MdnsPacket {
             return Ok(Some(MdnsPacket::Response(MdnsResponse::new(&packet, from))));
         }
 
-        if packet
+        if let Some(query) = packet
             .queries()
             .iter()
-            .any(|q| q.name().to_utf8() == SERVICE_NAME_FQDN)
+            .find(|q| q.name().to_utf8() == SERVICE_NAME_FQDN)
         {
             return Ok(Some(MdnsPacket::Query(MdnsQuery {
                 from,
                 query_id: packet.header().id(),
+                unicast_response: query.mdns_unicast_response(),
             })));
         }
 
@@ -87,6 +88,8 @@ pub struct MdnsQuery {
     from: SocketAddr,
     /// Id of the received DNS query. We need to pass this ID back in the results.
     query_id: u16,
+    /// Whether the question has the "unicast-response" (QU) bit set.
+    unicast_response: bool,
 }
 
 impl MdnsQuery {
@@ -99,6 +102,12 @@ impl MdnsQuery {
     pub fn query_id(&self) -> u16 {
         self.query_id
     }
+
+    /// Whether the sender asked for a unicast response, see
+    /// [RFC 6762 section 5.4](https://tools.ietf.org/html/rfc6762#section-5.4).
+    pub fn unicast_response(&self) -> bool {
+        self.unicast_response
+    }
 }
 
 impl fmt::Debug for MdnsQuery {
@@ -106,6 +115,7 @@ impl fmt::Debug for MdnsQuery {
         f.debug_struct("MdnsQuery")
             .field("from", self.remote_addr())
             .field("query_id", &self.query_id)
+            .field("unicast_response", &self.unicast_response)
             .finish()
     }
 }
@@ -319,9 +329,28 @@ impl fmt::Debug for MdnsPeer {
 
 #[cfg(test)]
 mod tests {
-    use super::super::dns::build_query_response;
+    use super::super::dns::{build_query, build_query_response};
     use super::*;
 
+    #[test]
+    fn parse_unicast_response_bit() {
+        let from = "192.168.1.2:5353".parse().unwrap();
+
+        let mut query = build_query();
+        match MdnsPacket::new_from_bytes(&query, from) {
+            Ok(Some(MdnsPacket::Query(query))) => assert!(!query.unicast_response()),
+            other => panic!("unexpected packet: {other:?}"),
+        }
+
+        // The top bit of the question class is the QU bit.
+        let class = query.len() - 2;
+        query[class] |= 0x80;
+        match MdnsPacket::new_from_bytes(&query, from) {
+            Ok(Some(MdnsPacket::Query(query))) => assert!(query.unicast_response()),
+            other => panic!("unexpected packet: {other:?}"),
+        }
+    }
+
     #[test]
     fn test_create_mdns_peer() {
         let ttl = 300;
@@ -100,9 +100,11 @@ pub mod tokio {
     impl Builder for TokioTimer {
         fn at(instant: Instant) -> Self {
             // Taken from: https://docs.rs/async-io/1.7.0/src/async_io/lib.rs.html#91
+            // Unlike async-io, tokio adds the period to the instant of a tick without checking
+            // for overflows, thus the period is kept at roughly 30 years instead of the maximum.
             let mut inner = time::interval_at(
                 TokioInstant::from_std(instant),
-                Duration::new(std::u64::MAX, 1_000_000_000 - 1),
+                Duration::from_secs(30 * 365 * 24 * 60 * 60),
             );
             inner.set_missed_tick_behavior(MissedTickBehavior::Skip);
             Self { inner }
@@ -91,6 +91,15 @@ pub struct Config {
     pub query_interval: Duration,
     /// Use IPv6 instead of IPv4.
     pub enable_ipv6: bool,
+    /// Answer queries that have the "unicast-response" (QU) bit set via unicast instead of
+    /// multicast.
+    pub unicast_responses: bool,
+    /// Minimum interval between two multicast responses on an interface. Queries received in
+    /// between are answered by a single response once the interval elapsed, as recommended by
+    /// [RFC 6762 section 6](https://tools.ietf.org/html/rfc6762#section-6).
+    ///
+    /// Defaults to [`Duration::ZERO`], i.e. responses are not rate-limited.
+    pub multicast_response_interval: Duration,
 }
 
 impl Default for Config {
@@ -99,6 +108,8 @@ impl Default for Config {
             ttl: Duration::from_secs(6 * 60),
             query_interval: Duration::from_secs(5 * 60),
             enable_ipv6: false,
+            unicast_responses: true,
+            multicast_response_interval: Duration::ZERO,
         }
     }
 }
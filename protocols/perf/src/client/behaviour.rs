@@ -115,6 +115,7 @@ impl NetworkBehaviour for Behaviour {
                 endpoint: _,
                 handler: _,
                 remaining_established,
+                reason: _,
             }) => {
                 if remaining_established == 0 {
                     assert!(self.connected.remove(&peer_id));
@@ -129,7 +130,10 @@ impl NetworkBehaviour for Behaviour {
             | FromSwarm::ListenerError(_)
             | FromSwarm::ListenerClosed(_)
             | FromSwarm::NewExternalAddr(_)
-            | FromSwarm::ExpiredExternalAddr(_) => {}
+            | FromSwarm::ExpiredExternalAddr(_)
+            | FromSwarm::PeerProtocolsChanged(_)
+            | FromSwarm::PeerListenAddrsChanged(_)
+            | FromSwarm::NatStatusChanged(_) => {}
         }
     }
 
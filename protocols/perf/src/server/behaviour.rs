@@ -91,6 +91,9 @@ impl NetworkBehaviour for Behaviour {
             FromSwarm::ListenerClosed(_) => {}
             FromSwarm::NewExternalAddr(_) => {}
             FromSwarm::ExpiredExternalAddr(_) => {}
+            FromSwarm::PeerProtocolsChanged(_) => {}
+            FromSwarm::PeerListenAddrsChanged(_) => {}
+            FromSwarm::NatStatusChanged(_) => {}
         }
     }
 
@@ -18,24 +18,27 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::{protocol, PROTOCOL_NAME};
+use crate::protocol::{self, PayloadMismatch, PING_SIZE};
+use crate::PROTOCOL_NAME;
 use futures::future::BoxFuture;
 use futures::prelude::*;
-use futures_timer::Delay;
 use libp2p_core::upgrade::ReadyUpgrade;
-use libp2p_core::{upgrade::NegotiationError, UpgradeError};
+use libp2p_core::{upgrade::NegotiationError, Multiaddr, MultiaddrPattern, UpgradeError};
 use libp2p_swarm::handler::{
     ConnectionEvent, DialUpgradeError, FullyNegotiatedInbound, FullyNegotiatedOutbound,
+    StreamPriority,
 };
 use libp2p_swarm::{
-    ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, KeepAlive,
-    NegotiatedSubstream, SubstreamProtocol,
+    Clock, ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, KeepAlive,
+    NegotiatedSubstream, SubstreamProtocol, SystemClock, Timer,
 };
+use rand::{thread_rng, RngCore};
 use std::collections::VecDeque;
 use std::{
     error::Error,
     fmt, io,
     num::NonZeroU32,
+    sync::Arc,
     task::{Context, Poll},
     time::Duration,
 };
@@ -56,8 +59,19 @@ pub struct Config {
     /// Whether the connection should generally be kept alive unless
     /// `max_failures` occur.
     keep_alive: bool,
+    /// Addresses of connections whose transport checks liveness itself.
+    native_keep_alive: Vec<MultiaddrPattern>,
+    /// The size of the payload of outbound pings.
+    payload_size: usize,
+    /// Whether the payload of outbound pings is random or a fixed pattern.
+    random_payload: bool,
+    /// The clock of the ping interval and timeout.
+    clock: Arc<dyn Clock>,
 }
 
+/// The maximum size of the payload of outbound pings, see [`Config::with_payload_size`].
+pub const MAX_PAYLOAD_SIZE: usize = 256 * PING_SIZE;
+
 impl Config {
     /// Creates a new [`Config`] with the following default settings:
     ///
@@ -65,6 +79,9 @@ impl Config {
     ///   * [`Config::with_timeout`] 20s
     ///   * [`Config::with_max_failures`] 1
     ///   * [`Config::with_keep_alive`] false
+    ///   * [`Config::with_payload_size`] 32 bytes
+    ///   * [`Config::with_random_payload`] true
+    ///   * [`Config::with_clock`] [`SystemClock`]
     ///
     /// These settings have the following effect:
     ///
@@ -82,6 +99,10 @@ impl Config {
             interval: Duration::from_secs(15),
             max_failures: NonZeroU32::new(1).expect("1 != 0"),
             keep_alive: false,
+            native_keep_alive: Vec::new(),
+            payload_size: PING_SIZE,
+            random_payload: true,
+            clock: Arc::new(SystemClock),
         }
     }
 
@@ -122,6 +143,80 @@ impl Config {
         self.keep_alive = b;
         self
     }
+
+    /// Marks connections whose remote address matches the given pattern as having native
+    /// keep-alive, i.e. as using a transport that checks the liveness of the connection itself.
+    ///
+    /// No outbound pings are sent on these connections. Inbound pings are still answered.
+    ///
+    /// ```
+    /// # use libp2p_core::MultiaddrPattern;
+    /// # use libp2p_ping as ping;
+    /// // QUIC sends keep-alive packets itself.
+    /// let config = ping::Config::new()
+    ///     .with_native_keep_alive("/**/quic/**".parse::<MultiaddrPattern>().unwrap())
+    ///     .with_native_keep_alive("/**/quic-v1/**".parse::<MultiaddrPattern>().unwrap());
+    /// ```
+    pub fn with_native_keep_alive(mut self, pattern: MultiaddrPattern) -> Self {
+        self.native_keep_alive.push(pattern);
+        self
+    }
+
+    /// Sets the size of the payload of outbound pings, e.g. to probe the path MTU.
+    ///
+    /// The ping protocol echoes pings of 32 bytes. Larger payloads are sent as consecutive
+    /// pings on the same substream, so that they are answered by any remote, and the
+    /// round-trip time is measured until the whole payload has been echoed.
+    ///
+    /// # Panics
+    ///
+    /// If `size` is not a non-zero multiple of 32 or exceeds [`MAX_PAYLOAD_SIZE`].
+    pub fn with_payload_size(mut self, size: usize) -> Self {
+        assert!(
+            size > 0 && size % PING_SIZE == 0 && size <= MAX_PAYLOAD_SIZE,
+            "ping payload size must be a non-zero multiple of {PING_SIZE} of at most {MAX_PAYLOAD_SIZE} bytes"
+        );
+        self.payload_size = size;
+        self
+    }
+
+    /// Sets whether the payload of outbound pings is random, the default, or a fixed pattern.
+    ///
+    /// Random payloads defeat middleboxes that answer or cache pings on behalf of the remote.
+    /// Either way, a ping whose payload is not echoed unchanged fails with [`Failure::Mismatch`].
+    pub fn with_random_payload(mut self, random: bool) -> Self {
+        self.random_payload = random;
+        self
+    }
+
+    /// Sets the [`Clock`] driving the ping interval and timeout.
+    ///
+    /// Defaults to the [`SystemClock`]. A [`libp2p_swarm::ManualClock`] allows
+    /// tests to advance time instantly.
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Creates the payload of an outbound ping.
+    fn payload(&self) -> Vec<u8> {
+        let mut payload = vec![0; self.payload_size];
+        if self.random_payload {
+            thread_rng().fill_bytes(&mut payload);
+        } else {
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte = i as u8;
+            }
+        }
+        payload
+    }
+
+    /// Whether a connection to the given remote address has native keep-alive.
+    pub(crate) fn has_native_keep_alive(&self, remote_addr: &Multiaddr) -> bool {
+        self.native_keep_alive
+            .iter()
+            .any(|pattern| pattern.matches(remote_addr))
+    }
 }
 
 impl Default for Config {
@@ -149,6 +244,8 @@ pub enum Failure {
     Timeout,
     /// The peer does not support the ping protocol.
     Unsupported,
+    /// The peer did not echo the payload of the ping unchanged.
+    Mismatch,
     /// The ping failed for reasons other than a timeout.
     Other {
         error: Box<dyn std::error::Error + Send + 'static>,
@@ -161,6 +258,7 @@ impl fmt::Display for Failure {
             Failure::Timeout => f.write_str("Ping timeout"),
             Failure::Other { error } => write!(f, "Ping error: {error}"),
             Failure::Unsupported => write!(f, "Ping protocol not supported"),
+            Failure::Mismatch => f.write_str("Ping payload mismatch"),
         }
     }
 }
@@ -171,6 +269,7 @@ impl Error for Failure {
             Failure::Timeout => None,
             Failure::Other { error } => Some(&**error),
             Failure::Unsupported => None,
+            Failure::Mismatch => None,
         }
     }
 }
@@ -184,7 +283,7 @@ pub struct Handler {
     config: Config,
     /// The timer used for the delay to the next ping as well as
     /// the ping timeout.
-    timer: Delay,
+    timer: Timer,
     /// Outbound ping failures that are pending to be processed by `poll()`.
     pending_errors: VecDeque<Failure>,
     /// The number of consecutive ping failures that occurred.
@@ -199,6 +298,8 @@ pub struct Handler {
     inbound: Option<PongFuture>,
     /// Tracks the state of our handler.
     state: State,
+    /// Whether to send outbound pings, i.e. whether the connection lacks native keep-alive.
+    outbound_enabled: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -218,16 +319,23 @@ impl Handler {
     /// Builds a new [`Handler`] with the given configuration.
     pub fn new(config: Config) -> Self {
         Handler {
+            timer: config.clock.timer(Duration::new(0, 0)),
             config,
-            timer: Delay::new(Duration::new(0, 0)),
             pending_errors: VecDeque::with_capacity(2),
             failures: 0,
             outbound: None,
             inbound: None,
             state: State::Active,
+            outbound_enabled: true,
         }
     }
 
+    /// Disables outbound pings, e.g. because the connection has native keep-alive.
+    pub(crate) fn without_outbound_pings(mut self) -> Self {
+        self.outbound_enabled = false;
+        self
+    }
+
     fn on_dial_upgrade_error(
         &mut self,
         DialUpgradeError { error, .. }: DialUpgradeError<
@@ -264,6 +372,7 @@ impl ConnectionHandler for Handler {
 
     fn listen_protocol(&self) -> SubstreamProtocol<ReadyUpgrade<&'static [u8]>, ()> {
         SubstreamProtocol::new(ReadyUpgrade::new(PROTOCOL_NAME), ())
+            .with_priority(StreamPriority::High)
     }
 
     fn on_behaviour_event(&mut self, _: Void) {}
@@ -332,6 +441,10 @@ impl ConnectionHandler for Handler {
                 }
             }
 
+            if !self.outbound_enabled {
+                break;
+            }
+
             // Continue outbound pings.
             match self.outbound.take() {
                 Some(OutboundState::Ping(mut ping)) => match ping.poll_unpin(cx) {
@@ -345,15 +458,19 @@ impl ConnectionHandler for Handler {
                     }
                     Poll::Ready(Ok((stream, rtt))) => {
                         self.failures = 0;
-                        self.timer.reset(self.config.interval);
+                        self.timer = self.config.clock.timer(self.config.interval);
                         self.outbound = Some(OutboundState::Idle(stream));
                         return Poll::Ready(ConnectionHandlerEvent::Custom(Ok(Success::Ping {
                             rtt,
                         })));
                     }
                     Poll::Ready(Err(e)) => {
-                        self.pending_errors
-                            .push_front(Failure::Other { error: Box::new(e) });
+                        let mismatch = e.get_ref().map_or(false, |e| e.is::<PayloadMismatch>());
+                        self.pending_errors.push_front(if mismatch {
+                            Failure::Mismatch
+                        } else {
+                            Failure::Other { error: Box::new(e) }
+                        });
                     }
                 },
                 Some(OutboundState::Idle(stream)) => match self.timer.poll_unpin(cx) {
@@ -362,9 +479,11 @@ impl ConnectionHandler for Handler {
                         break;
                     }
                     Poll::Ready(()) => {
-                        self.timer.reset(self.config.timeout);
-                        self.outbound =
-                            Some(OutboundState::Ping(protocol::send_ping(stream).boxed()));
+                        self.timer = self.config.clock.timer(self.config.timeout);
+                        let payload = self.config.payload();
+                        self.outbound = Some(OutboundState::Ping(
+                            protocol::send_ping(stream, payload).boxed(),
+                        ));
                     }
                 },
                 Some(OutboundState::OpenStream) => {
@@ -374,7 +493,8 @@ impl ConnectionHandler for Handler {
                 None => {
                     self.outbound = Some(OutboundState::OpenStream);
                     let protocol = SubstreamProtocol::new(ReadyUpgrade::new(PROTOCOL_NAME), ())
-                        .with_timeout(self.config.timeout);
+                        .with_timeout(self.config.timeout)
+                        .with_priority(StreamPriority::High);
                     return Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest {
                         protocol,
                     });
@@ -405,8 +525,11 @@ impl ConnectionHandler for Handler {
                 protocol: stream,
                 ..
             }) => {
-                self.timer.reset(self.config.timeout);
-                self.outbound = Some(OutboundState::Ping(protocol::send_ping(stream).boxed()));
+                self.timer = self.config.clock.timer(self.config.timeout);
+                let payload = self.config.payload();
+                self.outbound = Some(OutboundState::Ping(
+                    protocol::send_ping(stream, payload).boxed(),
+                ));
             }
             ConnectionEvent::DialUpgradeError(dial_upgrade_error) => {
                 self.on_dial_upgrade_error(dial_upgrade_error)
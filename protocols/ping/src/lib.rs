@@ -46,7 +46,7 @@ mod handler;
 mod protocol;
 
 use handler::Handler;
-pub use handler::{Config, Failure, Success};
+pub use handler::{Config, Failure, Success, MAX_PAYLOAD_SIZE};
 use libp2p_core::{Endpoint, Multiaddr};
 use libp2p_identity::PeerId;
 use libp2p_swarm::{
@@ -109,6 +109,17 @@ impl Behaviour {
             events: VecDeque::new(),
         }
     }
+
+    fn handler_for(&self, remote_addr: &Multiaddr) -> Handler {
+        let handler = Handler::new(self.config.clone());
+
+        if self.config.has_native_keep_alive(remote_addr) {
+            log::debug!("Not pinging {remote_addr}, connection has native keep-alive");
+            return handler.without_outbound_pings();
+        }
+
+        handler
+    }
 }
 
 impl Default for Behaviour {
@@ -126,19 +137,19 @@ impl NetworkBehaviour for Behaviour {
         _: ConnectionId,
         _: PeerId,
         _: &Multiaddr,
-        _: &Multiaddr,
+        remote_addr: &Multiaddr,
     ) -> std::result::Result<THandler<Self>, ConnectionDenied> {
-        Ok(Handler::new(self.config.clone()))
+        Ok(self.handler_for(remote_addr))
     }
 
     fn handle_established_outbound_connection(
         &mut self,
         _: ConnectionId,
         _: PeerId,
-        _: &Multiaddr,
+        addr: &Multiaddr,
         _: Endpoint,
     ) -> std::result::Result<THandler<Self>, ConnectionDenied> {
-        Ok(Handler::new(self.config.clone()))
+        Ok(self.handler_for(addr))
     }
 
     fn on_connection_handler_event(
@@ -170,6 +181,10 @@ impl NetworkBehaviour for Behaviour {
         }
     }
 
+    fn queued_actions(&self) -> usize {
+        self.events.len()
+    }
+
     fn on_swarm_event(
         &mut self,
         event: libp2p_swarm::behaviour::FromSwarm<Self::ConnectionHandler>,
@@ -186,7 +201,10 @@ impl NetworkBehaviour for Behaviour {
             | FromSwarm::ListenerError(_)
             | FromSwarm::ListenerClosed(_)
             | FromSwarm::NewExternalAddr(_)
-            | FromSwarm::ExpiredExternalAddr(_) => {}
+            | FromSwarm::ExpiredExternalAddr(_)
+            | FromSwarm::PeerProtocolsChanged(_)
+            | FromSwarm::PeerListenAddrsChanged(_)
+            | FromSwarm::NatStatusChanged(_) => {}
         }
     }
 }
@@ -20,8 +20,7 @@
 
 use futures::prelude::*;
 use instant::Instant;
-use rand::{distributions, prelude::*};
-use std::{io, time::Duration};
+use std::{error::Error, fmt, io, time::Duration};
 
 pub const PROTOCOL_NAME: &[u8] = b"/ipfs/ping/1.0.0";
 
@@ -47,29 +46,41 @@ pub const PROTOCOL_NAME: &[u8] = b"/ipfs/ping/1.0.0";
 #[derive(Default, Debug, Copy, Clone)]
 pub struct Ping;
 
-const PING_SIZE: usize = 32;
+pub(crate) const PING_SIZE: usize = 32;
 
-/// Sends a ping and waits for the pong.
-pub async fn send_ping<S>(mut stream: S) -> io::Result<(S, Duration)>
+/// Sends a ping with the given payload and waits for the pong.
+///
+/// A payload larger than [`PING_SIZE`] is sent as consecutive pings,
+/// which the remote answers one after the other.
+pub async fn send_ping<S>(mut stream: S, payload: Vec<u8>) -> io::Result<(S, Duration)>
 where
     S: AsyncRead + AsyncWrite + Unpin,
 {
-    let payload: [u8; PING_SIZE] = thread_rng().sample(distributions::Standard);
+    debug_assert_eq!(payload.len() % PING_SIZE, 0);
     stream.write_all(&payload).await?;
     stream.flush().await?;
     let started = Instant::now();
-    let mut recv_payload = [0u8; PING_SIZE];
+    let mut recv_payload = vec![0u8; payload.len()];
     stream.read_exact(&mut recv_payload).await?;
     if recv_payload == payload {
         Ok((stream, started.elapsed()))
     } else {
-        Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            "Ping payload mismatch",
-        ))
+        Err(io::Error::new(io::ErrorKind::InvalidData, PayloadMismatch))
+    }
+}
+
+/// The payload echoed by the remote differs from the payload of the ping.
+#[derive(Debug)]
+pub(crate) struct PayloadMismatch;
+
+impl fmt::Display for PayloadMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Ping payload mismatch")
     }
 }
 
+impl Error for PayloadMismatch {}
+
 /// Waits for a ping and sends a pong.
 pub async fn recv_ping<S>(mut stream: S) -> io::Result<S>
 where
@@ -88,13 +99,16 @@ mod tests {
     use futures::StreamExt;
     use libp2p_core::{
         multiaddr::multiaddr,
-        transport::{memory::MemoryTransport, Transport},
+        transport::{
+            memory::{Channel, MemoryTransport},
+            Boxed, Transport,
+        },
+        Multiaddr,
     };
     use rand::{thread_rng, Rng};
     use std::time::Duration;
 
-    #[test]
-    fn ping_pong() {
+    fn listen() -> (Boxed<Channel<Vec<u8>>>, Multiaddr) {
         let mem_addr = multiaddr![Memory(thread_rng().gen::<u64>())];
         let mut transport = MemoryTransport::new().boxed();
         transport.listen_on(mem_addr).unwrap();
@@ -104,6 +118,65 @@ mod tests {
             .now_or_never()
             .and_then(|ev| ev.into_new_address())
             .expect("MemoryTransport not listening on an address!");
+        (transport, listener_addr)
+    }
+
+    #[test]
+    fn ping_pong_with_larger_payload() {
+        let (mut transport, listener_addr) = listen();
+
+        async_std::task::spawn(async move {
+            let transport_event = transport.next().await.unwrap();
+            let (listener_upgrade, _) = transport_event.into_incoming().unwrap();
+            let conn = listener_upgrade.await.unwrap();
+            // Every 32 bytes are answered as a separate ping.
+            let conn = recv_ping(conn).await.unwrap();
+            let conn = recv_ping(conn).await.unwrap();
+            recv_ping(conn).await.unwrap();
+        });
+
+        async_std::task::block_on(async move {
+            let c = MemoryTransport::new()
+                .dial(listener_addr)
+                .unwrap()
+                .await
+                .unwrap();
+            let payload = (0..3 * PING_SIZE).map(|i| i as u8).collect();
+            send_ping(c, payload).await.unwrap();
+        });
+    }
+
+    #[test]
+    fn payload_mismatch() {
+        let (mut transport, listener_addr) = listen();
+
+        async_std::task::spawn(async move {
+            let transport_event = transport.next().await.unwrap();
+            let (listener_upgrade, _) = transport_event.into_incoming().unwrap();
+            let mut conn = listener_upgrade.await.unwrap();
+            let mut payload = [0u8; PING_SIZE];
+            conn.read_exact(&mut payload).await.unwrap();
+            payload[0] ^= 1;
+            conn.write_all(&payload).await.unwrap();
+            conn.flush().await.unwrap();
+        });
+
+        async_std::task::block_on(async move {
+            let c = MemoryTransport::new()
+                .dial(listener_addr)
+                .unwrap()
+                .await
+                .unwrap();
+            match send_ping(c, vec![0; PING_SIZE]).await {
+                Err(e) => assert!(e.get_ref().unwrap().is::<PayloadMismatch>()),
+                Ok(_) => panic!("Expected payload mismatch"),
+            }
+        });
+    }
+
+    #[test]
+    fn ping_pong() {
+        let (mut transport, listener_addr) = listen();
 
         async_std::task::spawn(async move {
             let transport_event = transport.next().await.unwrap();
@@ -118,7 +191,7 @@ mod tests {
                 .unwrap()
                 .await
                 .unwrap();
-            let (_, rtt) = send_ping(c).await.unwrap();
+            let (_, rtt) = send_ping(c, vec![0; PING_SIZE]).await.unwrap();
             assert!(rtt > Duration::from_secs(0));
         });
     }
@@ -23,7 +23,7 @@
 use futures::prelude::*;
 use libp2p_ping as ping;
 use libp2p_swarm::keep_alive;
-use libp2p_swarm::{NetworkBehaviour, Swarm, SwarmEvent};
+use libp2p_swarm::{ManualClock, NetworkBehaviour, Swarm, SwarmEvent};
 use libp2p_swarm_test::SwarmExt;
 use quickcheck::*;
 use std::{num::NonZeroU8, time::Duration};
@@ -118,6 +118,123 @@ async fn count_ping_failures_until_connection_closed(mut swarm: Swarm<Behaviour>
     }
 }
 
+#[test]
+fn native_keep_alive_skips_outbound_pings() {
+    let cfg = ping::Config::new().with_interval(Duration::from_millis(10));
+
+    let mut swarm1 = Swarm::new_ephemeral(|_| {
+        Behaviour::new(cfg.clone().with_native_keep_alive("/**".parse().unwrap()))
+    });
+    let mut swarm2 = Swarm::new_ephemeral(|_| Behaviour::new(cfg.clone()));
+
+    async_std::task::block_on(async {
+        swarm1.listen().await;
+        swarm2.connect(&mut swarm1).await;
+
+        for _ in 0..5 {
+            match libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await {
+                (
+                    [BehaviourEvent::Ping(ping::Event {
+                        result: Ok(ping::Success::Pong),
+                        ..
+                    })],
+                    [BehaviourEvent::Ping(ping::Event {
+                        result: Ok(ping::Success::Ping { .. }),
+                        ..
+                    })],
+                ) => {}
+                events => panic!("Unexpected events: {events:?}"),
+            }
+        }
+    });
+}
+
+#[test]
+fn larger_payload_is_answered_as_consecutive_pings() {
+    let cfg = ping::Config::new().with_interval(Duration::from_millis(10));
+
+    let mut swarm1 = Swarm::new_ephemeral(|_| {
+        Behaviour::new(cfg.clone().with_native_keep_alive("/**".parse().unwrap()))
+    });
+    let mut swarm2 = Swarm::new_ephemeral(|_| {
+        Behaviour::new(
+            cfg.clone()
+                .with_payload_size(3 * 32)
+                .with_random_payload(false),
+        )
+    });
+
+    async_std::task::block_on(async {
+        swarm1.listen().await;
+        swarm2.connect(&mut swarm1).await;
+
+        for _ in 0..3 {
+            match libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await {
+                (
+                    [BehaviourEvent::Ping(ping::Event {
+                        result: Ok(ping::Success::Pong),
+                        ..
+                    }), BehaviourEvent::Ping(ping::Event {
+                        result: Ok(ping::Success::Pong),
+                        ..
+                    }), BehaviourEvent::Ping(ping::Event {
+                        result: Ok(ping::Success::Pong),
+                        ..
+                    })],
+                    [BehaviourEvent::Ping(ping::Event {
+                        result: Ok(ping::Success::Ping { .. }),
+                        ..
+                    })],
+                ) => {}
+                events => panic!("Unexpected events: {events:?}"),
+            }
+        }
+    });
+}
+
+#[test]
+fn interval_follows_clock() {
+    let interval = Duration::from_secs(60 * 60);
+    let clock = ManualClock::new();
+    let cfg = ping::Config::new()
+        .with_interval(interval)
+        .with_clock(clock.clone());
+
+    let mut swarm1 = Swarm::new_ephemeral(|_| {
+        Behaviour::new(cfg.clone().with_native_keep_alive("/**".parse().unwrap()))
+    });
+    let mut swarm2 = Swarm::new_ephemeral(|_| Behaviour::new(cfg.clone()));
+
+    async_std::task::block_on(async {
+        swarm1.listen().await;
+        swarm2.connect(&mut swarm1).await;
+
+        for _ in 0..3 {
+            match libp2p_swarm_test::drive(&mut swarm1, &mut swarm2).await {
+                (
+                    [BehaviourEvent::Ping(ping::Event {
+                        result: Ok(ping::Success::Pong),
+                        ..
+                    })],
+                    [BehaviourEvent::Ping(ping::Event {
+                        result: Ok(ping::Success::Ping { .. }),
+                        ..
+                    })],
+                ) => {}
+                events => panic!("Unexpected events: {events:?}"),
+            }
+            // The next ping is sent once the interval has passed on the clock.
+            clock.advance(interval);
+        }
+    });
+}
+
+#[test]
+#[should_panic(expected = "ping payload size must be a non-zero multiple of 32")]
+fn payload_size_must_be_multiple_of_ping_size() {
+    let _ = ping::Config::new().with_payload_size(40);
+}
+
 #[test]
 fn unsupported_doesnt_fail() {
     let mut swarm1 = Swarm::new_ephemeral(|_| keep_alive::Behaviour);
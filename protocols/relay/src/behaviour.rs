@@ -22,27 +22,32 @@
 
 mod handler;
 pub mod rate_limiter;
+pub mod reservation_store;
 
 use crate::behaviour::handler::Handler;
+use crate::behaviour::reservation_store::{PersistedReservation, ReservationStore};
+use crate::copy_future::ByteQuota;
 use crate::multiaddr_ext::MultiaddrExt;
 use crate::proto;
 use crate::protocol::{inbound_hop, outbound_stop};
+use crate::ReservationVoucher;
 use either::Either;
 use instant::Instant;
 use libp2p_core::multiaddr::Protocol;
 use libp2p_core::{ConnectedPoint, Endpoint, Multiaddr};
-use libp2p_identity::PeerId;
-use libp2p_swarm::behaviour::{ConnectionClosed, FromSwarm};
+use libp2p_identity::{Keypair, PeerId};
+use libp2p_swarm::behaviour::{ConnectionClosed, ConnectionEstablished, FromSwarm};
 use libp2p_swarm::{
-    dummy, ConnectionDenied, ConnectionHandlerUpgrErr, ConnectionId, ExternalAddresses,
-    NetworkBehaviour, NotifyHandler, PollParameters, THandler, THandlerInEvent, THandlerOutEvent,
-    ToSwarm,
+    dummy, Clock, ConnectionDenied, ConnectionHandlerUpgrErr, ConnectionId, ExternalAddresses,
+    ListenAddresses, NetworkBehaviour, NotifyHandler, PollParameters, SystemClock, THandler,
+    THandlerInEvent, THandlerOutEvent, ToSwarm,
 };
 use std::collections::{hash_map, HashMap, HashSet, VecDeque};
-use std::num::NonZeroU32;
+use std::num::{NonZeroU32, NonZeroUsize};
 use std::ops::Add;
+use std::sync::Arc;
 use std::task::{Context, Poll};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use void::Void;
 
 /// Configuration for the relay [`Behaviour`].
@@ -50,17 +55,78 @@ use void::Void;
 /// # Panics
 ///
 /// [`Config::max_circuit_duration`] may not exceed [`u32::MAX`].
+///
+/// [`Behaviour::new`] panics if [`Config::voucher_keypair`] is not the identity keypair of the
+/// relay, or if [`Config::require_circuit_voucher`] is set without a [`Config::voucher_keypair`].
 pub struct Config {
     pub max_reservations: usize,
     pub max_reservations_per_peer: usize,
     pub reservation_duration: Duration,
     pub reservation_rate_limiters: Vec<Box<dyn rate_limiter::RateLimiter>>,
+    /// Persists reservations across restarts, see [`reservation_store`].
+    pub reservation_store: Option<Box<dyn ReservationStore>>,
 
     pub max_circuits: usize,
     pub max_circuits_per_peer: usize,
     pub max_circuit_duration: Duration,
     pub max_circuit_bytes: u64,
+    /// Do not limit the bytes relayed per circuit, ignoring [`Config::max_circuit_bytes`] and
+    /// announcing circuits without data limit.
+    ///
+    /// Disabled by default. Only enable on relays of private infrastructure, preferably in
+    /// combination with [`Config::peer_byte_quota`].
+    pub unlimited_circuit_bytes: bool,
+    /// Maximum number of bytes relayed for each source peer across all of its circuits, reset
+    /// every [`Config::peer_byte_quota_interval`].
+    ///
+    /// Circuits exceeding the quota are closed. Further circuits of the peer are denied with
+    /// `RESOURCE_LIMIT_EXCEEDED` until the quota is reset.
+    pub peer_byte_quota: Option<u64>,
+    /// Interval at which the [`Config::peer_byte_quota`] of all peers is reset. Defaults to a day.
+    pub peer_byte_quota_interval: Duration,
     pub circuit_src_rate_limiters: Vec<Box<dyn rate_limiter::RateLimiter>>,
+    /// Maximum number of bytes a circuit relays per direction in one turn before yielding to the
+    /// other circuits of the same connection.
+    ///
+    /// Circuits sharing a connection are scheduled round robin over chunks of this size, so that
+    /// a bulk transfer does not starve interactive circuits. Smaller chunks improve
+    /// the latency of interactive circuits at the cost of more frequent scheduling. Defaults to 8
+    /// kibibyte.
+    pub circuit_chunk_size: NonZeroUsize,
+    /// Time the destination of a circuit has to answer the STOP `CONNECT` request.
+    ///
+    /// On timeout the circuit request of the source is denied with `CONNECTION_FAILED`,
+    /// releasing the resources held for the circuit.
+    pub stop_timeout: Duration,
+
+    /// Emit an [`Event::Message`] for every HOP and STOP message handled by the relay.
+    pub message_events: bool,
+
+    /// Issues a [`ReservationVoucher`] for every accepted reservation, signed with the given
+    /// keypair. Must be the identity keypair of the relay, as vouchers are only valid when signed
+    /// by the relay they were issued by.
+    pub voucher_keypair: Option<Keypair>,
+    /// Only accept circuits presenting a valid, unexpired [`ReservationVoucher`] issued by this
+    /// relay for the destination, denying all others with `PERMISSION_DENIED`.
+    ///
+    /// Protects the relay from being used to probe arbitrary peers. Requires
+    /// [`Config::voucher_keypair`] to be set for reserving peers to obtain vouchers, as otherwise
+    /// every circuit would be denied.
+    pub require_circuit_voucher: bool,
+
+    /// The [`Clock`] driving the expiry of reservations and the rate limiters.
+    ///
+    /// Defaults to the [`SystemClock`]. A [`libp2p_swarm::ManualClock`] allows tests to
+    /// advance time instantly. Reservation expiry persisted via [`Config::reservation_store`]
+    /// remains based on the wall-clock time of the system.
+    pub clock: Arc<dyn Clock>,
+
+    /// Allow relaying through another relay, i.e. accept circuits to destinations announced
+    /// at `/p2p-circuit` addresses and include relayed addresses of the relay in reservations.
+    ///
+    /// Disabled by default, denying such circuits with `PERMISSION_DENIED`. Only enable in
+    /// networks deliberately relaying over multiple hops.
+    pub allow_relay_chaining: bool,
 }
 
 impl std::fmt::Debug for Config {
@@ -73,14 +139,25 @@ impl std::fmt::Debug for Config {
                 "reservation_rate_limiters",
                 &format!("[{} rate limiters]", self.reservation_rate_limiters.len()),
             )
+            .field("reservation_store", &self.reservation_store.is_some())
             .field("max_circuits", &self.max_circuits)
             .field("max_circuits_per_peer", &self.max_circuits_per_peer)
             .field("max_circuit_duration", &self.max_circuit_duration)
             .field("max_circuit_bytes", &self.max_circuit_bytes)
+            .field("unlimited_circuit_bytes", &self.unlimited_circuit_bytes)
+            .field("peer_byte_quota", &self.peer_byte_quota)
+            .field("peer_byte_quota_interval", &self.peer_byte_quota_interval)
             .field(
                 "circuit_src_rate_limiters",
                 &format!("[{} rate limiters]", self.circuit_src_rate_limiters.len()),
             )
+            .field("circuit_chunk_size", &self.circuit_chunk_size)
+            .field("stop_timeout", &self.stop_timeout)
+            .field("message_events", &self.message_events)
+            .field("voucher_keypair", &self.voucher_keypair.is_some())
+            .field("require_circuit_voucher", &self.require_circuit_voucher)
+            .field("clock", &self.clock)
+            .field("allow_relay_chaining", &self.allow_relay_chaining)
             .finish()
     }
 }
@@ -118,12 +195,27 @@ impl Default for Config {
             max_reservations_per_peer: 4,
             reservation_duration: Duration::from_secs(60 * 60),
             reservation_rate_limiters,
+            reservation_store: None,
 
             max_circuits: 16,
             max_circuits_per_peer: 4,
             max_circuit_duration: Duration::from_secs(2 * 60),
             max_circuit_bytes: 1 << 17, // 128 kibibyte
+            unlimited_circuit_bytes: false,
+            peer_byte_quota: None,
+            peer_byte_quota_interval: Duration::from_secs(24 * 60 * 60),
             circuit_src_rate_limiters,
+            circuit_chunk_size: NonZeroUsize::new(1 << 13).expect("8192 > 0"), // 8 kibibyte
+            stop_timeout: Duration::from_secs(10),
+
+            message_events: false,
+
+            voucher_keypair: None,
+            require_circuit_voucher: false,
+
+            clock: Arc::new(SystemClock),
+
+            allow_relay_chaining: false,
         }
     }
 }
@@ -151,6 +243,9 @@ pub enum Event {
     },
     /// An inbound reservation has timed out.
     ReservationTimedOut { src_peer_id: PeerId },
+    /// A reservation restored from the [`ReservationStore`] has been re-activated on a new
+    /// connection of the peer.
+    ReservationRestored { src_peer_id: PeerId },
     CircuitReqReceiveFailed {
         src_peer_id: PeerId,
         error: ConnectionHandlerUpgrErr<void::Void>,
@@ -189,6 +284,56 @@ pub enum Event {
         dst_peer_id: PeerId,
         error: Option<std::io::Error>,
     },
+    /// A HOP or STOP message has been handled, see [`Config::message_events`].
+    Message {
+        /// The peer that sent the HOP request the message belongs to.
+        src_peer_id: PeerId,
+        message: ProtocolMessage,
+    },
+}
+
+/// A HOP or STOP message handled by the relay, reported via [`Event::Message`].
+///
+/// The `latency` of a response is measured from receiving the corresponding request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolMessage {
+    /// A HOP `RESERVE` request has been received.
+    ReservationReq {
+        /// Whether the request replaces an existing reservation.
+        renewed: bool,
+    },
+    /// A HOP `STATUS` response to a reservation request has been sent.
+    ReservationRes {
+        outcome: MessageOutcome,
+        latency: Duration,
+    },
+    /// A HOP `CONNECT` request has been received.
+    CircuitReq { dst_peer_id: PeerId },
+    /// The STOP `CONNECT` request sent to the destination of a circuit has been answered.
+    ///
+    /// The `latency` is measured from sending the STOP `CONNECT` request.
+    StopRes {
+        dst_peer_id: PeerId,
+        outcome: MessageOutcome,
+        latency: Duration,
+    },
+    /// A HOP `STATUS` response to a circuit request has been sent.
+    CircuitRes {
+        dst_peer_id: PeerId,
+        outcome: MessageOutcome,
+        latency: Duration,
+    },
+}
+
+/// The outcome of a request reported via [`ProtocolMessage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageOutcome {
+    /// The request has been accepted.
+    Accepted,
+    /// The request has been denied.
+    Denied,
+    /// Sending or receiving the response failed.
+    Failed,
 }
 
 /// [`NetworkBehaviour`] implementation of the relay server
@@ -201,22 +346,173 @@ pub struct Behaviour {
     reservations: HashMap<PeerId, HashSet<ConnectionId>>,
     circuits: CircuitsTracker,
 
+    /// Saves the active reservations, see [`Config::reservation_store`].
+    reservation_store: Option<Box<dyn ReservationStore>>,
+    /// Expiry of the active reservation of each peer. Only tracked with a `reservation_store`.
+    reservation_expiry: HashMap<PeerId, SystemTime>,
+    /// Reservations loaded from the `reservation_store`, waiting for the peer to reconnect.
+    restored_reservations: HashMap<PeerId, SystemTime>,
+
+    /// Arrival of pending reservation requests per connection. Only tracked with
+    /// [`Config::message_events`].
+    pending_reservation_reqs: HashMap<ConnectionId, VecDeque<Instant>>,
+    /// Arrival of pending circuit requests per source connection and destination. Only tracked
+    /// with [`Config::message_events`].
+    pending_circuit_reqs: HashMap<(ConnectionId, PeerId), VecDeque<Instant>>,
+    /// Sending of pending STOP `CONNECT` requests. Only tracked with [`Config::message_events`].
+    pending_stop_reqs: HashMap<CircuitId, Instant>,
+
+    /// The bytes relayed for each source peer. Only tracked with [`Config::peer_byte_quota`].
+    byte_quotas: HashMap<PeerId, ByteQuota>,
+    /// Start of the current [`Config::peer_byte_quota_interval`].
+    byte_quotas_reset: Instant,
+
     /// Queue of actions to return when polled.
     queued_actions: VecDeque<Action>,
 
     external_addresses: ExternalAddresses,
+    listen_addresses: ListenAddresses,
 }
 
 impl Behaviour {
-    pub fn new(local_peer_id: PeerId, config: Config) -> Self {
+    pub fn new(local_peer_id: PeerId, mut config: Config) -> Self {
+        if let Some(keypair) = config.voucher_keypair.as_ref() {
+            assert_eq!(
+                keypair.public().to_peer_id(),
+                local_peer_id,
+                "voucher keypair must be the identity keypair of the relay"
+            );
+        }
+        assert!(
+            !config.require_circuit_voucher || config.voucher_keypair.is_some(),
+            "requiring circuit vouchers requires a voucher keypair to issue them"
+        );
+
+        let mut reservation_store = config.reservation_store.take();
+
+        let restored_reservations = match reservation_store.as_mut().map(|store| store.load()) {
+            Some(Ok(reservations)) => {
+                let now = SystemTime::now();
+                reservations
+                    .into_iter()
+                    .filter(|r| r.expires > now)
+                    .map(|r| (r.peer_id, r.expires))
+                    .collect()
+            }
+            Some(Err(e)) => {
+                log::warn!("Failed to load persisted reservations: {e}");
+                HashMap::new()
+            }
+            None => HashMap::new(),
+        };
+
         Self {
+            byte_quotas: Default::default(),
+            byte_quotas_reset: config.clock.now(),
             config,
             local_peer_id,
             reservations: Default::default(),
             circuits: Default::default(),
+            reservation_store,
+            reservation_expiry: Default::default(),
+            restored_reservations,
+            pending_reservation_reqs: Default::default(),
+            pending_circuit_reqs: Default::default(),
+            pending_stop_reqs: Default::default(),
             queued_actions: Default::default(),
             external_addresses: Default::default(),
+            listen_addresses: Default::default(),
+        }
+    }
+
+    /// Returns the number of bytes relayed for the given source peer since the
+    /// [`Config::peer_byte_quota`] was last reset.
+    ///
+    /// Always `0` without a [`Config::peer_byte_quota`].
+    pub fn peer_bytes_relayed(&self, peer_id: &PeerId) -> u64 {
+        self.byte_quotas.get(peer_id).map_or(0, ByteQuota::used)
+    }
+
+    /// Returns the quota of the given source peer, resetting the quotas of all peers if the
+    /// [`Config::peer_byte_quota_interval`] elapsed.
+    fn byte_quota(&mut self, peer_id: PeerId, now: Instant) -> Option<ByteQuota> {
+        let limit = self.config.peer_byte_quota?;
+
+        if now.saturating_duration_since(self.byte_quotas_reset)
+            >= self.config.peer_byte_quota_interval
+        {
+            self.byte_quotas_reset = now;
+            let circuits = &self.circuits;
+            self.byte_quotas
+                .retain(|peer, _| circuits.num_circuits_of_peer(*peer) > 0);
+            for quota in self.byte_quotas.values() {
+                quota.reset();
+            }
+        }
+
+        Some(
+            self.byte_quotas
+                .entry(peer_id)
+                .or_insert_with(|| ByteQuota::new(limit))
+                .clone(),
+        )
+    }
+
+    /// The maximum bytes relayed per circuit, `None` with [`Config::unlimited_circuit_bytes`].
+    fn max_circuit_bytes(&self) -> Option<u64> {
+        (!self.config.unlimited_circuit_bytes).then_some(self.config.max_circuit_bytes)
+    }
+
+    fn on_connection_established(
+        &mut self,
+        ConnectionEstablished {
+            peer_id,
+            connection_id,
+            endpoint,
+            ..
+        }: ConnectionEstablished,
+    ) {
+        let is_relayed = match endpoint {
+            ConnectedPoint::Dialer { address, .. } => address.is_relayed(),
+            ConnectedPoint::Listener { local_addr, .. } => local_addr.is_relayed(),
+        };
+        if is_relayed {
+            return;
         }
+
+        let expires = match self.restored_reservations.remove(&peer_id) {
+            Some(expires) => expires,
+            None => return,
+        };
+        let remaining = match expires.duration_since(SystemTime::now()) {
+            Ok(remaining) => remaining,
+            Err(_) => {
+                // Expired while waiting for the peer to reconnect.
+                self.persist_reservations();
+                return;
+            }
+        };
+
+        self.reservations
+            .entry(peer_id)
+            .or_default()
+            .insert(connection_id);
+        self.reservation_expiry.insert(peer_id, expires);
+
+        self.queued_actions.push_back(
+            ToSwarm::NotifyHandler {
+                handler: NotifyHandler::One(connection_id),
+                peer_id,
+                event: Either::Left(handler::In::RestoreReservation { remaining }),
+            }
+            .into(),
+        );
+        self.queued_actions.push_back(
+            ToSwarm::GenerateEvent(Event::ReservationRestored {
+                src_peer_id: peer_id,
+            })
+            .into(),
+        );
     }
 
     fn on_connection_closed(
@@ -227,16 +523,18 @@ impl Behaviour {
             ..
         }: ConnectionClosed<<Self as NetworkBehaviour>::ConnectionHandler>,
     ) {
-        if let hash_map::Entry::Occupied(mut peer) = self.reservations.entry(peer_id) {
-            peer.get_mut().remove(&connection_id);
-            if peer.get().is_empty() {
-                peer.remove();
-            }
-        }
+        self.remove_reservation(peer_id, connection_id);
 
-        for circuit in self
-            .circuits
-            .remove_by_connection(peer_id, connection_id)
+        self.pending_reservation_reqs.remove(&connection_id);
+        self.pending_circuit_reqs
+            .retain(|(src_connection_id, _), _| *src_connection_id != connection_id);
+
+        let removed_circuits = self.circuits.remove_by_connection(peer_id, connection_id);
+        let circuits = &self.circuits;
+        self.pending_stop_reqs
+            .retain(|circuit_id, _| circuits.contains(*circuit_id));
+
+        for circuit in removed_circuits
             .iter()
             // Only emit [`CircuitClosed`] for accepted requests.
             .filter(|c| matches!(c.status, CircuitStatus::Accepted))
@@ -253,6 +551,145 @@ impl Behaviour {
     }
 }
 
+impl Behaviour {
+    /// Removes the reservation of the peer on the given connection.
+    ///
+    /// Returns `false` if the peer has no reservations.
+    fn remove_reservation(&mut self, peer_id: PeerId, connection_id: ConnectionId) -> bool {
+        let mut peer = match self.reservations.entry(peer_id) {
+            hash_map::Entry::Occupied(peer) => peer,
+            hash_map::Entry::Vacant(_) => return false,
+        };
+
+        peer.get_mut().remove(&connection_id);
+        if peer.get().is_empty() {
+            peer.remove();
+            if self.reservation_expiry.remove(&peer_id).is_some() {
+                self.persist_reservations();
+            }
+        }
+
+        true
+    }
+
+    /// Emits an [`Event::Message`] if enabled via [`Config::message_events`].
+    fn on_message(&mut self, src_peer_id: PeerId, message: ProtocolMessage) {
+        if !self.config.message_events {
+            return;
+        }
+
+        self.queued_actions.push_back(
+            ToSwarm::GenerateEvent(Event::Message {
+                src_peer_id,
+                message,
+            })
+            .into(),
+        );
+    }
+
+    /// Returns the time since the oldest pending reservation request on the connection arrived.
+    fn reservation_req_latency(&mut self, connection_id: ConnectionId) -> Duration {
+        let now = self.config.clock.now();
+        pop_latency(self.pending_reservation_reqs.entry(connection_id), now)
+    }
+
+    /// Returns the time since the oldest pending circuit request to `dst_peer_id` on the
+    /// connection arrived.
+    fn circuit_req_latency(
+        &mut self,
+        connection_id: ConnectionId,
+        dst_peer_id: PeerId,
+    ) -> Duration {
+        let now = self.config.clock.now();
+        pop_latency(
+            self.pending_circuit_reqs
+                .entry((connection_id, dst_peer_id)),
+            now,
+        )
+    }
+
+    /// Emits an [`Event::Message`] for the response to the STOP `CONNECT` request of the circuit.
+    fn on_stop_res(
+        &mut self,
+        circuit_id: CircuitId,
+        src_peer_id: PeerId,
+        dst_peer_id: PeerId,
+        outcome: MessageOutcome,
+    ) {
+        let latency = self
+            .pending_stop_reqs
+            .remove(&circuit_id)
+            .map(|sent| self.config.clock.now().saturating_duration_since(sent))
+            .unwrap_or_default();
+        self.on_message(
+            src_peer_id,
+            ProtocolMessage::StopRes {
+                dst_peer_id,
+                outcome,
+                latency,
+            },
+        );
+    }
+
+    /// Issues a voucher for the reservation of `peer_id`, see [`Config::voucher_keypair`].
+    fn issue_voucher(&self, peer_id: PeerId) -> Option<Box<ReservationVoucher>> {
+        let keypair = self.config.voucher_keypair.as_ref()?;
+        let expiration = SystemTime::now() + self.config.reservation_duration;
+
+        match ReservationVoucher::new(keypair, peer_id, expiration) {
+            Ok(voucher) => Some(Box::new(voucher)),
+            Err(e) => {
+                log::warn!("Failed to sign reservation voucher for {peer_id}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Whether `voucher` is an unexpired voucher of this relay for the reservation of `dst`.
+    /// Whether a circuit to a destination announced at the given addresses is denied, as it
+    /// relays through another relay, see [`Config::allow_relay_chaining`].
+    fn denies_relay_chaining(&self, dst_addrs: &[Multiaddr]) -> bool {
+        !self.config.allow_relay_chaining && dst_addrs.iter().any(|a| a.is_relayed())
+    }
+
+    fn is_valid_voucher(&self, dst: PeerId, voucher: Option<&[u8]>) -> bool {
+        let voucher = match voucher.map(ReservationVoucher::from_bytes) {
+            Some(Ok(voucher)) => voucher,
+            Some(Err(e)) => {
+                log::debug!("Invalid reservation voucher for {dst}: {e}");
+                return false;
+            }
+            None => return false,
+        };
+
+        voucher.relay() == self.local_peer_id && voucher.peer() == dst && !voucher.is_expired()
+    }
+
+    /// Saves all active and restored reservations to the [`ReservationStore`], if any.
+    fn persist_reservations(&mut self) {
+        let store = match self.reservation_store.as_mut() {
+            Some(store) => store,
+            None => return,
+        };
+
+        let now = SystemTime::now();
+        let reservations = self
+            .reservation_expiry
+            .iter()
+            .chain(self.restored_reservations.iter())
+            .filter(|(_, expires)| **expires > now)
+            .map(|(peer_id, expires)| PersistedReservation {
+                peer_id: *peer_id,
+                expires: *expires,
+            })
+            .collect::<Vec<_>>();
+
+        if let Err(e) = store.save(&reservations) {
+            log::warn!("Failed to persist reservations: {e}");
+        }
+    }
+}
+
 impl NetworkBehaviour for Behaviour {
     type ConnectionHandler = Either<Handler, dummy::ConnectionHandler>;
     type OutEvent = Event;
@@ -273,7 +710,10 @@ impl NetworkBehaviour for Behaviour {
             handler::Config {
                 reservation_duration: self.config.reservation_duration,
                 max_circuit_duration: self.config.max_circuit_duration,
-                max_circuit_bytes: self.config.max_circuit_bytes,
+                max_circuit_bytes: self.max_circuit_bytes(),
+                circuit_chunk_size: self.config.circuit_chunk_size,
+                stop_timeout: self.config.stop_timeout,
+                clock: self.config.clock.clone(),
             },
             ConnectedPoint::Listener {
                 local_addr: local_addr.clone(),
@@ -298,7 +738,10 @@ impl NetworkBehaviour for Behaviour {
             handler::Config {
                 reservation_duration: self.config.reservation_duration,
                 max_circuit_duration: self.config.max_circuit_duration,
-                max_circuit_bytes: self.config.max_circuit_bytes,
+                max_circuit_bytes: self.max_circuit_bytes(),
+                circuit_chunk_size: self.config.circuit_chunk_size,
+                stop_timeout: self.config.stop_timeout,
+                clock: self.config.clock.clone(),
             },
             ConnectedPoint::Dialer {
                 address: addr.clone(),
@@ -309,13 +752,16 @@ impl NetworkBehaviour for Behaviour {
 
     fn on_swarm_event(&mut self, event: FromSwarm<Self::ConnectionHandler>) {
         self.external_addresses.on_swarm_event(&event);
+        self.listen_addresses.on_swarm_event(&event);
 
         match event {
             FromSwarm::ConnectionClosed(connection_closed) => {
                 self.on_connection_closed(connection_closed)
             }
-            FromSwarm::ConnectionEstablished(_)
-            | FromSwarm::DialFailure(_)
+            FromSwarm::ConnectionEstablished(connection_established) => {
+                self.on_connection_established(connection_established)
+            }
+            FromSwarm::DialFailure(_)
             | FromSwarm::AddressChange(_)
             | FromSwarm::ListenFailure(_)
             | FromSwarm::NewListener(_)
@@ -324,7 +770,10 @@ impl NetworkBehaviour for Behaviour {
             | FromSwarm::ListenerError(_)
             | FromSwarm::ListenerClosed(_)
             | FromSwarm::NewExternalAddr(_)
-            | FromSwarm::ExpiredExternalAddr(_) => {}
+            | FromSwarm::ExpiredExternalAddr(_)
+            | FromSwarm::PeerProtocolsChanged(_)
+            | FromSwarm::PeerListenAddrsChanged(_)
+            | FromSwarm::NatStatusChanged(_) => {}
         }
     }
 
@@ -345,7 +794,7 @@ impl NetworkBehaviour for Behaviour {
                 endpoint,
                 renewed,
             } => {
-                let now = Instant::now();
+                let now = self.config.clock.now();
 
                 assert!(
                     !endpoint.is_relayed(),
@@ -353,6 +802,14 @@ impl NetworkBehaviour for Behaviour {
                      denies all inbound substreams."
                 );
 
+                if self.config.message_events {
+                    self.pending_reservation_reqs
+                        .entry(connection)
+                        .or_default()
+                        .push_back(now);
+                }
+                self.on_message(event_source, ProtocolMessage::ReservationReq { renewed });
+
                 let action = if
                 // Deny if it is a new reservation and exceeds `max_reservations_per_peer`.
                 (!renewed
@@ -397,6 +854,7 @@ impl NetworkBehaviour for Behaviour {
                         handler: NotifyHandler::One(connection),
                         peer_id: event_source,
                         inbound_reservation_req,
+                        voucher: self.issue_voucher(event_source),
                     }
                 };
 
@@ -410,6 +868,23 @@ impl NetworkBehaviour for Behaviour {
                     .or_default()
                     .insert(connection);
 
+                if self.reservation_store.is_some() {
+                    self.restored_reservations.remove(&event_source);
+                    self.reservation_expiry.insert(
+                        event_source,
+                        SystemTime::now() + self.config.reservation_duration,
+                    );
+                    self.persist_reservations();
+                }
+
+                let latency = self.reservation_req_latency(connection);
+                self.on_message(
+                    event_source,
+                    ProtocolMessage::ReservationRes {
+                        outcome: MessageOutcome::Accepted,
+                        latency,
+                    },
+                );
                 self.queued_actions.push_back(
                     ToSwarm::GenerateEvent(Event::ReservationReqAccepted {
                         src_peer_id: event_source,
@@ -419,6 +894,14 @@ impl NetworkBehaviour for Behaviour {
                 );
             }
             handler::Event::ReservationReqAcceptFailed { error } => {
+                let latency = self.reservation_req_latency(connection);
+                self.on_message(
+                    event_source,
+                    ProtocolMessage::ReservationRes {
+                        outcome: MessageOutcome::Failed,
+                        latency,
+                    },
+                );
                 self.queued_actions.push_back(
                     ToSwarm::GenerateEvent(Event::ReservationReqAcceptFailed {
                         src_peer_id: event_source,
@@ -428,6 +911,14 @@ impl NetworkBehaviour for Behaviour {
                 );
             }
             handler::Event::ReservationReqDenied {} => {
+                let latency = self.reservation_req_latency(connection);
+                self.on_message(
+                    event_source,
+                    ProtocolMessage::ReservationRes {
+                        outcome: MessageOutcome::Denied,
+                        latency,
+                    },
+                );
                 self.queued_actions.push_back(
                     ToSwarm::GenerateEvent(Event::ReservationReqDenied {
                         src_peer_id: event_source,
@@ -436,6 +927,14 @@ impl NetworkBehaviour for Behaviour {
                 );
             }
             handler::Event::ReservationReqDenyFailed { error } => {
+                let latency = self.reservation_req_latency(connection);
+                self.on_message(
+                    event_source,
+                    ProtocolMessage::ReservationRes {
+                        outcome: MessageOutcome::Failed,
+                        latency,
+                    },
+                );
                 self.queued_actions.push_back(
                     ToSwarm::GenerateEvent(Event::ReservationReqDenyFailed {
                         src_peer_id: event_source,
@@ -445,20 +944,11 @@ impl NetworkBehaviour for Behaviour {
                 );
             }
             handler::Event::ReservationTimedOut {} => {
-                match self.reservations.entry(event_source) {
-                    hash_map::Entry::Occupied(mut peer) => {
-                        peer.get_mut().remove(&connection);
-                        if peer.get().is_empty() {
-                            peer.remove();
-                        }
-                    }
-                    hash_map::Entry::Vacant(_) => {
-                        unreachable!(
-                            "Expect to track timed out reservation with peer {:?} on connection {:?}",
-                            event_source,
-                            connection,
-                        );
-                    }
+                if !self.remove_reservation(event_source, connection) {
+                    unreachable!(
+                        "Expect to track timed out reservation with peer {:?} on connection {:?}",
+                        event_source, connection,
+                    );
                 }
 
                 self.queued_actions.push_back(
@@ -472,7 +962,7 @@ impl NetworkBehaviour for Behaviour {
                 inbound_circuit_req,
                 endpoint,
             } => {
-                let now = Instant::now();
+                let now = self.config.clock.now();
 
                 assert!(
                     !endpoint.is_relayed(),
@@ -480,8 +970,44 @@ impl NetworkBehaviour for Behaviour {
                      denies all inbound substreams."
                 );
 
-                let action = if self.circuits.num_circuits_of_peer(event_source)
+                let dst_peer_id = inbound_circuit_req.dst();
+                if self.config.message_events {
+                    self.pending_circuit_reqs
+                        .entry((connection, dst_peer_id))
+                        .or_default()
+                        .push_back(now);
+                }
+                self.on_message(event_source, ProtocolMessage::CircuitReq { dst_peer_id });
+
+                let action = if self.denies_relay_chaining(inbound_circuit_req.dst_addrs()) {
+                    // Deny circuit to a destination reachable via another relay only.
+                    ToSwarm::NotifyHandler {
+                        handler: NotifyHandler::One(connection),
+                        peer_id: event_source,
+                        event: Either::Left(handler::In::DenyCircuitReq {
+                            circuit_id: None,
+                            inbound_circuit_req,
+                            status: proto::Status::PERMISSION_DENIED,
+                        }),
+                    }
+                } else if self.config.require_circuit_voucher
+                    && !self.is_valid_voucher(dst_peer_id, inbound_circuit_req.voucher())
+                {
+                    // Deny circuit without valid voucher of the destination.
+                    ToSwarm::NotifyHandler {
+                        handler: NotifyHandler::One(connection),
+                        peer_id: event_source,
+                        event: Either::Left(handler::In::DenyCircuitReq {
+                            circuit_id: None,
+                            inbound_circuit_req,
+                            status: proto::Status::PERMISSION_DENIED,
+                        }),
+                    }
+                } else if self.circuits.num_circuits_of_peer(event_source)
                     > self.config.max_circuits_per_peer
+                    || self
+                        .byte_quota(event_source, now)
+                        .map_or(false, |quota| quota.is_exhausted())
                     || self.circuits.len() >= self.config.max_circuits
                     || !self
                         .config
@@ -489,7 +1015,8 @@ impl NetworkBehaviour for Behaviour {
                         .iter_mut()
                         .all(|limiter| {
                             limiter.try_next(event_source, endpoint.get_remote_address(), now)
-                        }) {
+                        })
+                {
                     // Deny circuit exceeding limits.
                     ToSwarm::NotifyHandler {
                         handler: NotifyHandler::One(connection),
@@ -513,6 +1040,10 @@ impl NetworkBehaviour for Behaviour {
                         dst_peer_id: inbound_circuit_req.dst(),
                         dst_connection_id: *dst_conn,
                     });
+                    if self.config.message_events {
+                        self.pending_stop_reqs
+                            .insert(circuit_id, self.config.clock.now());
+                    }
 
                     ToSwarm::NotifyHandler {
                         handler: NotifyHandler::One(*dst_conn),
@@ -520,9 +1051,9 @@ impl NetworkBehaviour for Behaviour {
                         event: Either::Left(handler::In::NegotiateOutboundConnect {
                             circuit_id,
                             inbound_circuit_req,
-                            relay_peer_id: self.local_peer_id,
                             src_peer_id: event_source,
                             src_connection_id: connection,
+                            src_addrs: observed_src_addr(&endpoint).into_iter().collect(),
                         }),
                     }
                 } else {
@@ -556,6 +1087,15 @@ impl NetworkBehaviour for Behaviour {
                     self.circuits.remove(circuit_id);
                 }
 
+                let latency = self.circuit_req_latency(connection, dst_peer_id);
+                self.on_message(
+                    event_source,
+                    ProtocolMessage::CircuitRes {
+                        dst_peer_id,
+                        outcome: MessageOutcome::Denied,
+                        latency,
+                    },
+                );
                 self.queued_actions.push_back(
                     ToSwarm::GenerateEvent(Event::CircuitReqDenied {
                         src_peer_id: event_source,
@@ -573,6 +1113,15 @@ impl NetworkBehaviour for Behaviour {
                     self.circuits.remove(circuit_id);
                 }
 
+                let latency = self.circuit_req_latency(connection, dst_peer_id);
+                self.on_message(
+                    event_source,
+                    ProtocolMessage::CircuitRes {
+                        dst_peer_id,
+                        outcome: MessageOutcome::Failed,
+                        latency,
+                    },
+                );
                 self.queued_actions.push_back(
                     ToSwarm::GenerateEvent(Event::CircuitReqDenyFailed {
                         src_peer_id: event_source,
@@ -591,6 +1140,12 @@ impl NetworkBehaviour for Behaviour {
                 dst_stream,
                 dst_pending_data,
             } => {
+                self.on_stop_res(
+                    circuit_id,
+                    src_peer_id,
+                    event_source,
+                    MessageOutcome::Accepted,
+                );
                 self.queued_actions.push_back(
                     ToSwarm::NotifyHandler {
                         handler: NotifyHandler::One(src_connection_id),
@@ -602,6 +1157,7 @@ impl NetworkBehaviour for Behaviour {
                             dst_handler_notifier,
                             dst_stream,
                             dst_pending_data,
+                            quota: self.byte_quotas.get(&src_peer_id).cloned(),
                         }),
                     }
                     .into(),
@@ -615,6 +1171,15 @@ impl NetworkBehaviour for Behaviour {
                 status,
                 error,
             } => {
+                // Only a status reported by the destination is a denial, all other failures map to
+                // `CONNECTION_FAILED`.
+                let outcome = match status {
+                    proto::Status::PERMISSION_DENIED | proto::Status::RESOURCE_LIMIT_EXCEEDED => {
+                        MessageOutcome::Denied
+                    }
+                    _ => MessageOutcome::Failed,
+                };
+                self.on_stop_res(circuit_id, src_peer_id, event_source, outcome);
                 self.queued_actions.push_back(
                     ToSwarm::NotifyHandler {
                         handler: NotifyHandler::One(src_connection_id),
@@ -641,6 +1206,15 @@ impl NetworkBehaviour for Behaviour {
                 circuit_id,
             } => {
                 self.circuits.accepted(circuit_id);
+                let latency = self.circuit_req_latency(connection, dst_peer_id);
+                self.on_message(
+                    event_source,
+                    ProtocolMessage::CircuitRes {
+                        dst_peer_id,
+                        outcome: MessageOutcome::Accepted,
+                        latency,
+                    },
+                );
                 self.queued_actions.push_back(
                     ToSwarm::GenerateEvent(Event::CircuitReqAccepted {
                         src_peer_id: event_source,
@@ -655,6 +1229,15 @@ impl NetworkBehaviour for Behaviour {
                 error,
             } => {
                 self.circuits.remove(circuit_id);
+                let latency = self.circuit_req_latency(connection, dst_peer_id);
+                self.on_message(
+                    event_source,
+                    ProtocolMessage::CircuitRes {
+                        dst_peer_id,
+                        outcome: MessageOutcome::Failed,
+                        latency,
+                    },
+                );
                 self.queued_actions.push_back(
                     ToSwarm::GenerateEvent(Event::CircuitReqAcceptFailed {
                         src_peer_id: event_source,
@@ -689,7 +1272,12 @@ impl NetworkBehaviour for Behaviour {
         _: &mut impl PollParameters,
     ) -> Poll<ToSwarm<Self::OutEvent, THandlerInEvent<Self>>> {
         if let Some(action) = self.queued_actions.pop_front() {
-            return Poll::Ready(action.build(self.local_peer_id, &self.external_addresses));
+            return Poll::Ready(action.build(
+                self.local_peer_id,
+                &self.external_addresses,
+                &self.listen_addresses,
+                self.config.allow_relay_chaining,
+            ));
         }
 
         Poll::Pending
@@ -752,6 +1340,10 @@ impl CircuitsTracker {
         removed
     }
 
+    fn contains(&self, circuit_id: CircuitId) -> bool {
+        self.circuits.contains_key(&circuit_id)
+    }
+
     fn num_circuits_of_peer(&self, peer: PeerId) -> usize {
         self.circuits
             .iter()
@@ -786,6 +1378,25 @@ impl Add<u64> for CircuitId {
     }
 }
 
+/// Removes the oldest pending request of the entry, returning the time since it arrived.
+fn pop_latency<K>(entry: hash_map::Entry<'_, K, VecDeque<Instant>>, now: Instant) -> Duration {
+    let mut pending = match entry {
+        hash_map::Entry::Occupied(pending) => pending,
+        hash_map::Entry::Vacant(_) => return Duration::ZERO,
+    };
+
+    let latency = pending
+        .get_mut()
+        .pop_front()
+        .map(|received| now.saturating_duration_since(received))
+        .unwrap_or_default();
+    if pending.get().is_empty() {
+        pending.remove();
+    }
+
+    latency
+}
+
 /// A [`ToSwarm`], either complete, or still requiring data from [`PollParameters`]
 /// before being returned in [`Behaviour::poll`].
 #[allow(clippy::large_enum_variant)]
@@ -795,6 +1406,7 @@ enum Action {
         inbound_reservation_req: inbound_hop::ReservationReq,
         handler: NotifyHandler,
         peer_id: PeerId,
+        voucher: Option<Box<ReservationVoucher>>,
     },
 }
 
@@ -809,6 +1421,8 @@ impl Action {
         self,
         local_peer_id: PeerId,
         external_addresses: &ExternalAddresses,
+        listen_addresses: &ListenAddresses,
+        allow_relay_chaining: bool,
     ) -> ToSwarm<Event, Either<handler::In, Void>> {
         match self {
             Action::Done(action) => action,
@@ -816,22 +1430,220 @@ impl Action {
                 inbound_reservation_req,
                 handler,
                 peer_id,
+                voucher,
             } => ToSwarm::NotifyHandler {
                 handler,
                 peer_id,
                 event: Either::Left(handler::In::AcceptReservationReq {
                     inbound_reservation_req,
-                    addrs: external_addresses
-                        .iter()
-                        .cloned()
-                        // Add local peer ID in case it isn't present yet.
-                        .filter_map(|a| match a.iter().last()? {
-                            Protocol::P2p(_) => Some(a),
-                            _ => Some(a.with(Protocol::P2p(local_peer_id.into()))),
-                        })
-                        .collect(),
+                    addrs: reservation_addrs(
+                        local_peer_id,
+                        external_addresses.iter(),
+                        listen_addresses.iter(),
+                        allow_relay_chaining,
+                    ),
+                    voucher,
                 }),
             },
         }
     }
 }
+
+/// The addresses of the relay to include in a reservation.
+///
+/// Besides the external addresses, these are the publicly routable addresses the relay listens on,
+/// so that a dual-stack relay advertises both its IPv4 and IPv6 addresses, even if its external
+/// addresses were only observed on one of them. Relayed addresses are only included if
+/// [`Config::allow_relay_chaining`] is set.
+fn reservation_addrs<'a>(
+    local_peer_id: PeerId,
+    external_addresses: impl Iterator<Item = &'a Multiaddr>,
+    listen_addresses: impl Iterator<Item = &'a Multiaddr>,
+    allow_relay_chaining: bool,
+) -> Vec<Multiaddr> {
+    let mut addrs: Vec<Multiaddr> = Vec::new();
+    for addr in external_addresses
+        .chain(listen_addresses.filter(|a| a.is_public_ip()))
+        .filter(|a| allow_relay_chaining || !a.is_relayed())
+    {
+        // Add local peer ID in case it isn't present yet.
+        let addr = match addr.iter().last() {
+            Some(Protocol::P2p(_)) => addr.clone(),
+            _ => addr.clone().with(Protocol::P2p(local_peer_id.into())),
+        };
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    addrs
+}
+
+/// The address the source of a circuit was observed at, reported to the destination in the STOP
+/// message.
+///
+/// Relayed source connections are not reported, as their address is not one of the source itself.
+fn observed_src_addr(endpoint: &ConnectedPoint) -> Option<Multiaddr> {
+    let mut addr = endpoint.get_remote_address().clone();
+    if addr.is_relayed() {
+        return None;
+    }
+    if let Some(Protocol::P2p(_)) = addr.iter().last() {
+        addr.pop();
+    }
+    Some(addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "requires a voucher keypair")]
+    fn requiring_vouchers_without_keypair_is_rejected() {
+        Behaviour::new(
+            PeerId::random(),
+            Config {
+                require_circuit_voucher: true,
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "identity keypair of the relay")]
+    fn voucher_keypair_of_other_peer_is_rejected() {
+        Behaviour::new(
+            PeerId::random(),
+            Config {
+                voucher_keypair: Some(Keypair::generate_ed25519()),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn reservation_addrs_include_public_listen_addrs_of_both_stacks() {
+        let local_peer_id = PeerId::random();
+        let external: Vec<Multiaddr> = vec!["/ip4/1.2.3.4/tcp/4001".parse().unwrap()];
+        let listen: Vec<Multiaddr> = vec![
+            "/ip4/1.2.3.4/tcp/4001".parse().unwrap(),
+            "/ip4/192.168.1.2/tcp/4001".parse().unwrap(),
+            "/ip6/::1/tcp/4001".parse().unwrap(),
+            "/ip6/fe80::1/tcp/4001".parse().unwrap(),
+            "/ip6/2a01:4f8::1/tcp/4001".parse().unwrap(),
+        ];
+
+        let addrs = reservation_addrs(local_peer_id, external.iter(), listen.iter(), false);
+
+        let expected: Vec<Multiaddr> = vec![
+            "/ip4/1.2.3.4/tcp/4001".parse().unwrap(),
+            "/ip6/2a01:4f8::1/tcp/4001".parse().unwrap(),
+        ];
+        let expected: Vec<Multiaddr> = expected
+            .into_iter()
+            .map(|a| a.with(Protocol::P2p(local_peer_id.into())))
+            .collect();
+        assert_eq!(addrs, expected);
+    }
+
+    #[test]
+    fn reservation_addrs_exclude_relayed_addrs() {
+        let local_peer_id = PeerId::random();
+        let direct: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+        let relayed: Multiaddr =
+            format!("/ip4/5.6.7.8/tcp/4001/p2p/{}/p2p-circuit", PeerId::random())
+                .parse()
+                .unwrap();
+        let external = [direct.clone(), relayed.clone()];
+
+        assert_eq!(
+            reservation_addrs(local_peer_id, external.iter(), std::iter::empty(), false),
+            vec![direct.clone().with(Protocol::P2p(local_peer_id.into()))]
+        );
+        assert_eq!(
+            reservation_addrs(local_peer_id, external.iter(), std::iter::empty(), true),
+            vec![
+                direct.with(Protocol::P2p(local_peer_id.into())),
+                relayed.with(Protocol::P2p(local_peer_id.into()))
+            ]
+        );
+    }
+
+    #[test]
+    fn circuits_to_relayed_destinations_are_denied_unless_chaining_is_allowed() {
+        let direct: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+        let relayed: Multiaddr =
+            format!("/ip4/5.6.7.8/tcp/4001/p2p/{}/p2p-circuit", PeerId::random())
+                .parse()
+                .unwrap();
+
+        let behaviour = Behaviour::new(PeerId::random(), Config::default());
+        assert!(!behaviour.denies_relay_chaining(&[]));
+        assert!(!behaviour.denies_relay_chaining(std::slice::from_ref(&direct)));
+        assert!(behaviour.denies_relay_chaining(&[direct.clone(), relayed.clone()]));
+
+        let behaviour = Behaviour::new(
+            PeerId::random(),
+            Config {
+                allow_relay_chaining: true,
+                ..Default::default()
+            },
+        );
+        assert!(!behaviour.denies_relay_chaining(&[direct, relayed]));
+    }
+
+    #[test]
+    fn connect_over_relayed_connection_is_denied() {
+        use libp2p_core::upgrade::UpgradeInfo;
+        use libp2p_swarm::ConnectionHandler;
+
+        let local_peer_id = PeerId::random();
+        let mut behaviour = Behaviour::new(local_peer_id, Config::default());
+        let direct: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+        let relayed: Multiaddr =
+            format!("/ip4/5.6.7.8/tcp/4001/p2p/{}/p2p-circuit", PeerId::random())
+                .parse()
+                .unwrap();
+
+        let mut hop_protocols = |local_addr: &Multiaddr| {
+            behaviour
+                .handle_established_inbound_connection(
+                    ConnectionId::new_unchecked(0),
+                    PeerId::random(),
+                    local_addr,
+                    &direct,
+                )
+                .unwrap()
+                .listen_protocol()
+                .upgrade()
+                .protocol_info()
+                .count()
+        };
+
+        // The relay does not accept HOP requests, e.g. to CONNECT, over relayed connections.
+        assert_eq!(hop_protocols(&direct), 1);
+        assert_eq!(hop_protocols(&relayed), 0);
+    }
+
+    #[test]
+    fn observed_src_addr_keeps_ip6() {
+        let peer_id = PeerId::random();
+        let endpoint = ConnectedPoint::Listener {
+            local_addr: "/ip6/2a01:4f8::1/tcp/4001".parse().unwrap(),
+            send_back_addr: "/ip6/2a01:4f8::2/tcp/51234"
+                .parse::<Multiaddr>()
+                .unwrap()
+                .with(Protocol::P2p(peer_id.into())),
+        };
+        assert_eq!(
+            observed_src_addr(&endpoint),
+            Some("/ip6/2a01:4f8::2/tcp/51234".parse().unwrap())
+        );
+
+        let relayed = ConnectedPoint::Listener {
+            local_addr: "/ip4/1.2.3.4/tcp/4001".parse().unwrap(),
+            send_back_addr: "/ip4/5.6.7.8/tcp/4001/p2p-circuit".parse().unwrap(),
+        };
+        assert_eq!(observed_src_addr(&relayed), None);
+    }
+}
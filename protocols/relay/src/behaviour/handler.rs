@@ -19,16 +19,16 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::behaviour::CircuitId;
-use crate::copy_future::CopyFuture;
+use crate::copy_future::{ByteQuota, CopyFuture};
 use crate::proto;
 use crate::protocol::{inbound_hop, outbound_stop};
+use crate::ReservationVoucher;
 use bytes::Bytes;
 use either::Either;
 use futures::channel::oneshot::{self, Canceled};
 use futures::future::{BoxFuture, FutureExt, TryFutureExt};
 use futures::io::AsyncWriteExt;
 use futures::stream::{FuturesUnordered, StreamExt};
-use futures_timer::Delay;
 use instant::Instant;
 use libp2p_core::{upgrade, ConnectedPoint, Multiaddr};
 use libp2p_identity::PeerId;
@@ -37,11 +37,13 @@ use libp2p_swarm::handler::{
     ListenUpgradeError,
 };
 use libp2p_swarm::{
-    ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, ConnectionId, KeepAlive,
-    NegotiatedSubstream, SubstreamProtocol,
+    Clock, ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, ConnectionId,
+    KeepAlive, NegotiatedSubstream, SubstreamProtocol, Timer,
 };
 use std::collections::VecDeque;
 use std::fmt;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
@@ -49,13 +51,18 @@ use std::time::Duration;
 pub struct Config {
     pub reservation_duration: Duration,
     pub max_circuit_duration: Duration,
-    pub max_circuit_bytes: u64,
+    /// `None` if the bytes relayed per circuit are not limited.
+    pub max_circuit_bytes: Option<u64>,
+    pub circuit_chunk_size: NonZeroUsize,
+    pub stop_timeout: Duration,
+    pub clock: Arc<dyn Clock>,
 }
 
 pub enum In {
     AcceptReservationReq {
         inbound_reservation_req: inbound_hop::ReservationReq,
         addrs: Vec<Multiaddr>,
+        voucher: Option<Box<ReservationVoucher>>,
     },
     DenyReservationReq {
         inbound_reservation_req: inbound_hop::ReservationReq,
@@ -69,10 +76,14 @@ pub enum In {
     NegotiateOutboundConnect {
         circuit_id: CircuitId,
         inbound_circuit_req: inbound_hop::CircuitReq,
-        relay_peer_id: PeerId,
         src_peer_id: PeerId,
         src_connection_id: ConnectionId,
+        /// The addresses the source was observed at, reported to the destination.
+        src_addrs: Vec<Multiaddr>,
     },
+    /// Re-activates a reservation restored from a
+    /// [`ReservationStore`](crate::behaviour::reservation_store::ReservationStore).
+    RestoreReservation { remaining: Duration },
     AcceptAndDriveCircuit {
         circuit_id: CircuitId,
         dst_peer_id: PeerId,
@@ -80,6 +91,8 @@ pub enum In {
         dst_handler_notifier: oneshot::Sender<()>,
         dst_stream: NegotiatedSubstream,
         dst_pending_data: Bytes,
+        /// The quota of the source peer to count the relayed bytes against.
+        quota: Option<ByteQuota>,
     },
 }
 
@@ -89,9 +102,11 @@ impl fmt::Debug for In {
             In::AcceptReservationReq {
                 inbound_reservation_req: _,
                 addrs,
+                voucher,
             } => f
                 .debug_struct("In::AcceptReservationReq")
                 .field("addrs", addrs)
+                .field("voucher", &voucher.is_some())
                 .finish(),
             In::DenyReservationReq {
                 inbound_reservation_req: _,
@@ -112,15 +127,19 @@ impl fmt::Debug for In {
             In::NegotiateOutboundConnect {
                 circuit_id,
                 inbound_circuit_req: _,
-                relay_peer_id,
                 src_peer_id,
                 src_connection_id,
+                src_addrs,
             } => f
                 .debug_struct("In::NegotiateOutboundConnect")
                 .field("circuit_id", circuit_id)
-                .field("relay_peer_id", relay_peer_id)
                 .field("src_peer_id", src_peer_id)
                 .field("src_connection_id", src_connection_id)
+                .field("src_addrs", src_addrs)
+                .finish(),
+            In::RestoreReservation { remaining } => f
+                .debug_struct("In::RestoreReservation")
+                .field("remaining", remaining)
                 .finish(),
             In::AcceptAndDriveCircuit {
                 circuit_id,
@@ -129,10 +148,12 @@ impl fmt::Debug for In {
                 dst_handler_notifier: _,
                 dst_stream: _,
                 dst_pending_data: _,
+                quota,
             } => f
                 .debug_struct("In::AcceptAndDriveCircuit")
                 .field("circuit_id", circuit_id)
                 .field("dst_peer_id", dst_peer_id)
+                .field("quota", quota)
                 .finish(),
         }
     }
@@ -369,7 +390,7 @@ pub struct Handler {
     /// Future handling inbound reservation request.
     reservation_request_future: Option<ReservationRequestFuture>,
     /// Timeout for the currently active reservation.
-    active_reservation: Option<Delay>,
+    active_reservation: Option<Timer>,
 
     /// Futures accepting an inbound circuit request.
     circuit_accept_futures:
@@ -627,11 +648,12 @@ impl ConnectionHandler for Handler {
             In::AcceptReservationReq {
                 inbound_reservation_req,
                 addrs,
+                voucher,
             } => {
                 if self
                     .reservation_request_future
                     .replace(ReservationRequestFuture::Accepting(
-                        inbound_reservation_req.accept(addrs).boxed(),
+                        inbound_reservation_req.accept(addrs, voucher).boxed(),
                     ))
                     .is_some()
                 {
@@ -652,18 +674,22 @@ impl ConnectionHandler for Handler {
                     log::warn!("Dropping existing deny/accept future in favor of new one.")
                 }
             }
+            In::RestoreReservation { remaining } => {
+                self.active_reservation = Some(self.config.clock.timer(remaining));
+            }
             In::NegotiateOutboundConnect {
                 circuit_id,
                 inbound_circuit_req,
-                relay_peer_id,
                 src_peer_id,
                 src_connection_id,
+                src_addrs,
             } => {
                 self.queued_events
                     .push_back(ConnectionHandlerEvent::OutboundSubstreamRequest {
                         protocol: SubstreamProtocol::new(
                             outbound_stop::Upgrade {
-                                relay_peer_id,
+                                src_peer_id,
+                                src_addrs,
                                 max_circuit_duration: self.config.max_circuit_duration,
                                 max_circuit_bytes: self.config.max_circuit_bytes,
                             },
@@ -673,7 +699,8 @@ impl ConnectionHandler for Handler {
                                 src_peer_id,
                                 src_connection_id,
                             },
-                        ),
+                        )
+                        .with_timeout(self.config.stop_timeout),
                     });
             }
             In::DenyCircuitReq {
@@ -696,6 +723,7 @@ impl ConnectionHandler for Handler {
                 dst_handler_notifier,
                 dst_stream,
                 dst_pending_data,
+                quota,
             } => {
                 self.circuit_accept_futures.push(
                     inbound_circuit_req
@@ -708,6 +736,7 @@ impl ConnectionHandler for Handler {
                             dst_handler_notifier,
                             dst_stream,
                             dst_pending_data,
+                            quota,
                         })
                         .map_err(move |e| (circuit_id, dst_peer_id, e))
                         .boxed(),
@@ -799,9 +828,11 @@ impl ConnectionHandler for Handler {
                         dst_handler_notifier,
                         mut dst_stream,
                         dst_pending_data,
+                        quota,
                     } = parts;
                     let max_circuit_duration = self.config.max_circuit_duration;
                     let max_circuit_bytes = self.config.max_circuit_bytes;
+                    let circuit_chunk_size = self.config.circuit_chunk_size;
 
                     let circuit = async move {
                         let (result_1, result_2) = futures::future::join(
@@ -812,13 +843,17 @@ impl ConnectionHandler for Handler {
                         result_1?;
                         result_2?;
 
-                        CopyFuture::new(
+                        let copy_future = CopyFuture::new(
                             src_stream,
                             dst_stream,
                             max_circuit_duration,
                             max_circuit_bytes,
                         )
-                        .await?;
+                        .with_chunk_size(circuit_chunk_size);
+                        match quota {
+                            Some(quota) => copy_future.with_quota(quota).await?,
+                            None => copy_future.await?,
+                        }
 
                         // Inform destination handler that the stream to the destination is dropped.
                         drop(dst_handler_notifier);
@@ -870,7 +905,7 @@ impl ConnectionHandler for Handler {
                         Ok(()) => {
                             let renewed = self
                                 .active_reservation
-                                .replace(Delay::new(self.config.reservation_duration))
+                                .replace(self.config.clock.timer(self.config.reservation_duration))
                                 .is_some();
                             return Poll::Ready(ConnectionHandlerEvent::Custom(
                                 Event::ReservationReqAccepted { renewed },
@@ -974,4 +1009,5 @@ pub struct CircuitParts {
     dst_handler_notifier: oneshot::Sender<()>,
     dst_stream: NegotiatedSubstream,
     dst_pending_data: Bytes,
+    quota: Option<ByteQuota>,
 }
@@ -0,0 +1,214 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Persistence of reservations across restarts of the relay.
+//!
+//! With a [`ReservationStore`] configured via [`Config::reservation_store`](crate::Config),
+//! the relay saves its active reservations whenever they change and restores them on startup.
+//! A restored reservation is re-activated for the remainder of its lifetime once the peer
+//! reconnects, without the peer having to make a new reservation.
+
+use libp2p_identity::PeerId;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A reservation as saved by a [`ReservationStore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PersistedReservation {
+    pub peer_id: PeerId,
+    pub expires: SystemTime,
+}
+
+/// Storage for the active reservations of a relay.
+pub trait ReservationStore: Send {
+    /// Loads the reservations saved last.
+    fn load(&mut self) -> io::Result<Vec<PersistedReservation>>;
+
+    /// Saves the reservations, replacing any previously saved ones.
+    ///
+    /// Called from within [`NetworkBehaviour`](libp2p_swarm::NetworkBehaviour) callbacks,
+    /// thus it should return quickly.
+    fn save(&mut self, reservations: &[PersistedReservation]) -> io::Result<()>;
+}
+
+/// A [`ReservationStore`] saving reservations to a file.
+///
+/// Each line of the file holds the peer ID and the expiry, in seconds since the Unix epoch, of
+/// one reservation.
+///
+/// The file is written by a background thread, so that [`ReservationStore::save`] never blocks
+/// the swarm. Saves issued while a write is in progress are coalesced into a single write of the
+/// latest reservations. Dropping the store waits for the last save to be written.
+#[derive(Debug)]
+pub struct FileReservationStore {
+    path: PathBuf,
+    writer: Option<Writer>,
+}
+
+#[derive(Debug)]
+struct Writer {
+    sender: mpsc::Sender<Vec<PersistedReservation>>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl FileReservationStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            writer: None,
+        }
+    }
+
+    fn spawn_writer(&self) -> io::Result<Writer> {
+        let (sender, receiver) = mpsc::channel::<Vec<PersistedReservation>>();
+        let path = self.path.clone();
+        let thread = thread::Builder::new()
+            .name("libp2p-relay-reservation-store".to_owned())
+            .spawn(move || {
+                while let Ok(mut reservations) = receiver.recv() {
+                    // Only the latest reservations are of interest.
+                    while let Ok(latest) = receiver.try_recv() {
+                        reservations = latest;
+                    }
+                    if let Err(e) = write_file(&path, &reservations) {
+                        log::warn!("Failed to persist reservations: {e}");
+                    }
+                }
+            })?;
+
+        Ok(Writer { sender, thread })
+    }
+}
+
+impl Drop for FileReservationStore {
+    fn drop(&mut self) {
+        if let Some(Writer { sender, thread }) = self.writer.take() {
+            drop(sender);
+            let _ = thread.join();
+        }
+    }
+}
+
+impl ReservationStore for FileReservationStore {
+    fn load(&mut self) -> io::Result<Vec<PersistedReservation>> {
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut reservations = Vec::new();
+        for line in io::BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            reservations.push(parse_line(&line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid reservation entry: {line}"),
+                )
+            })?);
+        }
+
+        Ok(reservations)
+    }
+
+    fn save(&mut self, reservations: &[PersistedReservation]) -> io::Result<()> {
+        let writer = match self.writer.as_mut() {
+            Some(writer) => writer,
+            None => self.writer.insert(self.spawn_writer()?),
+        };
+
+        writer.sender.send(reservations.to_vec()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "Reservation store writer terminated",
+            )
+        })
+    }
+}
+
+fn write_file(path: &Path, reservations: &[PersistedReservation]) -> io::Result<()> {
+    // Write to a temporary file first, so that a crash mid-write does not corrupt the store.
+    let tmp_path = path.with_extension("tmp");
+    let mut file = io::BufWriter::new(fs::File::create(&tmp_path)?);
+    for PersistedReservation { peer_id, expires } in reservations {
+        let expires = expires
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+            .as_secs();
+        writeln!(file, "{peer_id} {expires}")?;
+    }
+    file.into_inner()?.sync_all()?;
+
+    fs::rename(tmp_path, path)
+}
+
+fn parse_line(line: &str) -> Option<PersistedReservation> {
+    let (peer_id, expires) = line.trim().split_once(' ')?;
+
+    Some(PersistedReservation {
+        peer_id: peer_id.parse().ok()?,
+        expires: UNIX_EPOCH + Duration::from_secs(expires.parse().ok()?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_store_roundtrip() {
+        let path = std::env::temp_dir().join(format!(
+            "libp2p-relay-reservations-{}",
+            rand::random::<u64>()
+        ));
+        let mut store = FileReservationStore::new(&path);
+
+        assert!(store.load().unwrap().is_empty());
+
+        let reservations = vec![
+            PersistedReservation {
+                peer_id: PeerId::random(),
+                expires: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+            },
+            PersistedReservation {
+                peer_id: PeerId::random(),
+                expires: UNIX_EPOCH + Duration::from_secs(1_700_000_060),
+            },
+        ];
+        store.save(&reservations).unwrap();
+        drop(store);
+        let mut store = FileReservationStore::new(&path);
+        assert_eq!(store.load().unwrap(), reservations);
+
+        store.save(&reservations[..1]).unwrap();
+        store.save(&[]).unwrap();
+        drop(store);
+        assert!(FileReservationStore::new(&path).load().unwrap().is_empty());
+
+        fs::remove_file(path).unwrap();
+    }
+}
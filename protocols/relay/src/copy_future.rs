@@ -32,29 +32,92 @@ use futures::ready;
 use futures_timer::Delay;
 use std::convert::TryInto;
 use std::io;
+use std::num::NonZeroUsize;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
+/// Number of bytes a peer may have relayed across all of its circuits.
+///
+/// Clones share the number of bytes used.
+#[derive(Debug, Clone)]
+pub struct ByteQuota {
+    limit: u64,
+    used: Arc<AtomicU64>,
+}
+
+impl ByteQuota {
+    pub(crate) fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            used: Default::default(),
+        }
+    }
+
+    pub(crate) fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn is_exhausted(&self) -> bool {
+        self.used() >= self.limit
+    }
+
+    /// Records `bytes` as used, returning whether the quota is exceeded.
+    fn consume(&self, bytes: u64) -> bool {
+        self.used.fetch_add(bytes, Ordering::Relaxed) + bytes > self.limit
+    }
+
+    pub(crate) fn reset(&self) {
+        self.used.store(0, Ordering::Relaxed);
+    }
+}
+
 pub struct CopyFuture<S, D> {
     src: BufReader<S>,
     dst: BufReader<D>,
 
     max_circuit_duration: Delay,
-    max_circuit_bytes: u64,
+    /// `None` if the bytes sent over the circuit are not limited.
+    max_circuit_bytes: Option<u64>,
     bytes_sent: u64,
+    quota: Option<ByteQuota>,
+    /// Number of bytes relayed per turn before yielding to the other circuits polled by the same
+    /// task.
+    chunk_size: usize,
 }
 
 impl<S: AsyncRead, D: AsyncRead> CopyFuture<S, D> {
-    pub fn new(src: S, dst: D, max_circuit_duration: Duration, max_circuit_bytes: u64) -> Self {
+    pub fn new(
+        src: S,
+        dst: D,
+        max_circuit_duration: Duration,
+        max_circuit_bytes: Option<u64>,
+    ) -> Self {
         CopyFuture {
             src: BufReader::new(src),
             dst: BufReader::new(dst),
             max_circuit_duration: Delay::new(max_circuit_duration),
             max_circuit_bytes,
             bytes_sent: Default::default(),
+            quota: None,
+            chunk_size: usize::MAX,
         }
     }
+
+    /// Relays at most `chunk_size` bytes per direction and poll before yielding, see
+    /// [`CopyFuture::poll`].
+    pub(crate) fn with_chunk_size(mut self, chunk_size: NonZeroUsize) -> Self {
+        self.chunk_size = chunk_size.get();
+        self
+    }
+
+    /// Counts the bytes sent against the given quota, failing once it is exceeded.
+    pub(crate) fn with_quota(mut self, quota: ByteQuota) -> Self {
+        self.quota = Some(quota);
+        self
+    }
 }
 
 impl<S, D> Future for CopyFuture<S, D>
@@ -64,11 +127,23 @@ where
 {
     type Output = io::Result<()>;
 
+    /// Relays data in both directions until both are pending or done.
+    ///
+    /// Circuits are scheduled round robin: each poll is a turn granting each direction of the
+    /// circuit a quantum of `chunk_size` bytes. Writes are capped at the remaining quantum of
+    /// their direction, and once a direction used up its quantum the circuit wakes itself and
+    /// yields, letting the other circuits of the task relay their chunk before its next turn.
+    /// Thus a bulk circuit cannot starve interactive ones, nor the reverse direction of itself.
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = &mut *self;
+        let mut src_quantum = this.chunk_size;
+        let mut dst_quantum = this.chunk_size;
 
         loop {
-            if this.bytes_sent > this.max_circuit_bytes {
+            if this
+                .max_circuit_bytes
+                .map_or(false, |max| this.bytes_sent > max)
+            {
                 return Poll::Ready(Err(io::Error::new(
                     io::ErrorKind::Other,
                     "Max circuit bytes reached.",
@@ -79,26 +154,37 @@ where
                 Pending,
                 Done,
                 Progressed,
+                Exhausted,
             }
 
-            let src_status = match forward_data(&mut this.src, &mut this.dst, cx) {
-                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
-                Poll::Ready(Ok(0)) => Status::Done,
-                Poll::Ready(Ok(i)) => {
-                    this.bytes_sent += i;
-                    Status::Progressed
+            let src_status = if src_quantum == 0 {
+                Status::Exhausted
+            } else {
+                match forward_data(&mut this.src, &mut this.dst, src_quantum, cx) {
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Ready(Ok(0)) => Status::Done,
+                    Poll::Ready(Ok(i)) => {
+                        this.on_sent(i)?;
+                        src_quantum -= i as usize;
+                        Status::Progressed
+                    }
+                    Poll::Pending => Status::Pending,
                 }
-                Poll::Pending => Status::Pending,
             };
 
-            let dst_status = match forward_data(&mut this.dst, &mut this.src, cx) {
-                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
-                Poll::Ready(Ok(0)) => Status::Done,
-                Poll::Ready(Ok(i)) => {
-                    this.bytes_sent += i;
-                    Status::Progressed
+            let dst_status = if dst_quantum == 0 {
+                Status::Exhausted
+            } else {
+                match forward_data(&mut this.dst, &mut this.src, dst_quantum, cx) {
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Ready(Ok(0)) => Status::Done,
+                    Poll::Ready(Ok(i)) => {
+                        this.on_sent(i)?;
+                        dst_quantum -= i as usize;
+                        Status::Progressed
+                    }
+                    Poll::Pending => Status::Pending,
                 }
-                Poll::Pending => Status::Pending,
             };
 
             match (src_status, dst_status) {
@@ -106,6 +192,12 @@ where
                 (Status::Done, Status::Done) => return Poll::Ready(Ok(())),
                 // Either source or destination made progress.
                 (Status::Progressed, _) | (_, Status::Progressed) => {}
+                // Either source or destination used up its quantum. Yield to the other circuits,
+                // continuing in the next turn.
+                (Status::Exhausted, _) | (_, Status::Exhausted) => {
+                    cx.waker().wake_by_ref();
+                    break;
+                }
                 // Both are pending. Check if max circuit duration timer fired, otherwise return
                 // Poll::Pending.
                 (Status::Pending, Status::Pending) => break,
@@ -123,13 +215,27 @@ where
     }
 }
 
-/// Forwards data from `source` to `destination`.
+impl<S, D> CopyFuture<S, D> {
+    fn on_sent(&mut self, bytes: u64) -> io::Result<()> {
+        self.bytes_sent += bytes;
+        if self.quota.as_ref().map_or(false, |q| q.consume(bytes)) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Peer byte quota exhausted.",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Forwards at most `max` bytes of data from `source` to `destination`.
 ///
 /// Returns `0` when done, i.e. `source` having reached EOF, returns number of bytes sent otherwise,
 /// thus indicating progress.
 fn forward_data<S: AsyncBufRead + Unpin, D: AsyncWrite + Unpin>(
     mut src: &mut S,
     mut dst: &mut D,
+    max: usize,
     cx: &mut Context<'_>,
 ) -> Poll<io::Result<u64>> {
     let buffer = ready!(Pin::new(&mut src).poll_fill_buf(cx))?;
@@ -139,7 +245,8 @@ fn forward_data<S: AsyncBufRead + Unpin, D: AsyncWrite + Unpin>(
         return Poll::Ready(Ok(0));
     }
 
-    let i = ready!(Pin::new(dst).poll_write(cx, buffer))?;
+    let len = buffer.len().min(max);
+    let i = ready!(Pin::new(dst).poll_write(cx, &buffer[..len]))?;
     if i == 0 {
         return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
     }
@@ -150,11 +257,13 @@ fn forward_data<S: AsyncBufRead + Unpin, D: AsyncWrite + Unpin>(
 
 #[cfg(test)]
 mod tests {
-    use super::CopyFuture;
+    use super::{ByteQuota, CopyFuture};
     use futures::executor::block_on;
+    use futures::future::Future;
     use futures::io::{AsyncRead, AsyncWrite};
     use quickcheck::QuickCheck;
     use std::io::ErrorKind;
+    use std::num::NonZeroUsize;
     use std::pin::Pin;
     use std::task::{Context, Poll};
     use std::time::Duration;
@@ -254,7 +363,7 @@ mod tests {
                 connection_a,
                 connection_b,
                 Duration::from_secs(60),
-                max_circuit_bytes,
+                Some(max_circuit_bytes),
             );
 
             match block_on(&mut copy_future) {
@@ -279,7 +388,7 @@ mod tests {
             PendingConnection {},
             PendingConnection {},
             Duration::from_millis(1),
-            u64::MAX,
+            None,
         );
 
         std::thread::sleep(Duration::from_millis(2));
@@ -288,4 +397,118 @@ mod tests {
             block_on(copy_future).expect_err("Expect maximum circuit duration to be reached.");
         assert_eq!(error.kind(), ErrorKind::TimedOut);
     }
+
+    #[test]
+    fn unlimited_circuit_bytes() {
+        let a = vec![1; 1 << 20];
+        let b = vec![2; 1 << 20];
+        let mut copy_future = CopyFuture::new(
+            Connection {
+                read: a.clone(),
+                write: Vec::new(),
+            },
+            Connection {
+                read: b.clone(),
+                write: Vec::new(),
+            },
+            Duration::from_secs(60),
+            None,
+        );
+
+        block_on(&mut copy_future).unwrap();
+        assert_eq!(copy_future.src.into_inner().write, b);
+        assert_eq!(copy_future.dst.into_inner().write, a);
+    }
+
+    #[test]
+    fn quota_is_shared_across_circuits() {
+        let quota = ByteQuota::new(100);
+        let new_circuit = |len: usize| {
+            CopyFuture::new(
+                Connection {
+                    read: vec![0; len],
+                    write: Vec::new(),
+                },
+                Connection {
+                    read: Vec::new(),
+                    write: Vec::new(),
+                },
+                Duration::from_secs(60),
+                None,
+            )
+            .with_quota(quota.clone())
+        };
+
+        block_on(new_circuit(60)).unwrap();
+        assert_eq!(quota.used(), 60);
+        assert!(!quota.is_exhausted());
+
+        let error = block_on(new_circuit(60)).expect_err("Expect quota to be exhausted.");
+        assert_eq!(error.to_string(), "Peer byte quota exhausted.");
+        assert!(quota.is_exhausted());
+
+        quota.reset();
+        block_on(new_circuit(60)).unwrap();
+    }
+
+    #[test]
+    fn circuits_yield_after_chunk() {
+        let a = vec![1; 1000];
+        let mut copy_future = CopyFuture::new(
+            Connection {
+                read: a.clone(),
+                write: Vec::new(),
+            },
+            Connection {
+                read: Vec::new(),
+                write: Vec::new(),
+            },
+            Duration::from_secs(60),
+            None,
+        )
+        .with_chunk_size(NonZeroUsize::new(100).unwrap());
+
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        // Each turn relays a single chunk and yields.
+        for turn in 1..=10 {
+            assert!(Pin::new(&mut copy_future).poll(&mut cx).is_pending());
+            assert_eq!(copy_future.dst.get_ref().write.len(), turn * 100);
+        }
+        assert!(Pin::new(&mut copy_future).poll(&mut cx).is_ready());
+        assert_eq!(copy_future.dst.into_inner().write, a);
+    }
+
+    #[test]
+    fn both_directions_progress_each_turn() {
+        let a = vec![1; 1000];
+        let b = vec![2; 1000];
+        let mut copy_future = CopyFuture::new(
+            Connection {
+                read: a.clone(),
+                write: Vec::new(),
+            },
+            Connection {
+                read: b.clone(),
+                write: Vec::new(),
+            },
+            Duration::from_secs(60),
+            None,
+        )
+        .with_chunk_size(NonZeroUsize::new(100).unwrap());
+
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        // A bulk transfer in one direction does not starve the other direction.
+        for turn in 1..=10 {
+            assert!(Pin::new(&mut copy_future).poll(&mut cx).is_pending());
+            assert_eq!(copy_future.dst.get_ref().write.len(), turn * 100);
+            assert_eq!(copy_future.src.get_ref().write.len(), turn * 100);
+        }
+        assert!(Pin::new(&mut copy_future).poll(&mut cx).is_ready());
+        assert_eq!(copy_future.src.into_inner().write, b);
+        assert_eq!(copy_future.dst.into_inner().write, a);
+    }
 }
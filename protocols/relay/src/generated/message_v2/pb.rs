@@ -308,6 +308,46 @@ impl MessageWrite for Reservation {
     }
 }
 
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct ReservationVoucher {
+    pub relay: Vec<u8>,
+    pub peer: Vec<u8>,
+    pub expiration: u64,
+}
+
+impl<'a> MessageRead<'a> for ReservationVoucher {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.relay = r.read_bytes(bytes)?.to_owned(),
+                Ok(18) => msg.peer = r.read_bytes(bytes)?.to_owned(),
+                Ok(24) => msg.expiration = r.read_uint64(bytes)?,
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for ReservationVoucher {
+    fn get_size(&self) -> usize {
+        0
+        + 1 + sizeof_len((&self.relay).len())
+        + 1 + sizeof_len((&self.peer).len())
+        + 1 + sizeof_varint(*(&self.expiration) as u64)
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        w.write_with_tag(10, |w| w.write_bytes(&**&self.relay))?;
+        w.write_with_tag(18, |w| w.write_bytes(&**&self.peer))?;
+        w.write_with_tag(24, |w| w.write_uint64(*&self.expiration))?;
+        Ok(())
+    }
+}
+
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct Limit {
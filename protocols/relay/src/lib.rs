@@ -29,16 +29,22 @@ mod multiaddr_ext;
 mod priv_client;
 mod protocol;
 pub mod v2;
+mod voucher;
 
 mod proto {
     include!("generated/mod.rs");
     pub use self::message_v2::pb::mod_HopMessage::Type as HopMessageType;
     pub use self::message_v2::pb::mod_StopMessage::Type as StopMessageType;
-    pub use self::message_v2::pb::{HopMessage, Limit, Peer, Reservation, Status, StopMessage};
+    pub use self::message_v2::pb::{
+        HopMessage, Limit, Peer, Reservation, ReservationVoucher, Status, StopMessage,
+    };
 }
 
-pub use behaviour::{Behaviour, CircuitId, Config, Event};
+pub use behaviour::{
+    reservation_store, Behaviour, CircuitId, Config, Event, MessageOutcome, ProtocolMessage,
+};
 pub use protocol::{HOP_PROTOCOL_NAME, STOP_PROTOCOL_NAME};
+pub use voucher::{ReservationVoucher, VoucherError};
 
 /// Types related to the relay protocol inbound.
 pub mod inbound {
@@ -53,7 +59,9 @@ pub mod inbound {
 /// Types related to the relay protocol outbound.
 pub mod outbound {
     pub mod hop {
-        pub use crate::protocol::outbound_hop::FatalUpgradeError;
+        pub use crate::protocol::outbound_hop::{
+            CircuitFailedReason, FatalUpgradeError, ReservationFailedReason,
+        };
     }
     pub mod stop {
         pub use crate::protocol::outbound_stop::FatalUpgradeError;
@@ -62,7 +70,10 @@ pub mod outbound {
 
 /// Everything related to the relay protocol from a client's perspective.
 pub mod client {
-    pub use crate::priv_client::{new, transport::Transport, Behaviour, Connection, Event};
+    pub use crate::priv_client::{
+        new, new_with_config, transport::Transport, Behaviour, CircuitStats, Config, Connection,
+        Event, InboundCircuitPolicy, RelayScore,
+    };
 
     pub mod transport {
         pub use crate::priv_client::transport::Error;
@@ -3,10 +3,39 @@ use libp2p_core::Multiaddr;
 
 pub(crate) trait MultiaddrExt {
     fn is_relayed(&self) -> bool;
+
+    /// Whether the address starts with a globally routable IPv4 or IPv6 address.
+    fn is_public_ip(&self) -> bool;
 }
 
 impl MultiaddrExt for Multiaddr {
     fn is_relayed(&self) -> bool {
         self.iter().any(|p| p == Protocol::P2pCircuit)
     }
+
+    fn is_public_ip(&self) -> bool {
+        match self.iter().next() {
+            Some(Protocol::Ip4(ip)) => {
+                !(ip.is_private()
+                    || ip.is_loopback()
+                    || ip.is_link_local()
+                    || ip.is_unspecified()
+                    || ip.is_broadcast()
+                    || ip.is_documentation())
+            }
+            Some(Protocol::Ip6(ip)) => {
+                let segment = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local addresses, fc00::/7.
+                    || segment & 0xfe00 == 0xfc00
+                    // Unicast link-local addresses, fe80::/10.
+                    || segment & 0xffc0 == 0xfe80
+                    // Documentation addresses, 2001:db8::/32.
+                    || (segment == 0x2001 && ip.segments()[1] == 0x0db8))
+            }
+            _ => false,
+        }
+    }
 }
@@ -21,11 +21,14 @@
 //! [`NetworkBehaviour`] to act as a circuit relay v2 **client**.
 
 mod handler;
+mod relay_selection;
 pub(crate) mod transport;
 
 use crate::multiaddr_ext::MultiaddrExt;
 use crate::priv_client::handler::Handler;
+use crate::priv_client::relay_selection::RelayCandidates;
 use crate::protocol::{self, inbound_stop, outbound_hop};
+use crate::ReservationVoucher;
 use bytes::Bytes;
 use either::Either;
 use futures::channel::mpsc::Receiver;
@@ -34,23 +37,32 @@ use futures::future::{BoxFuture, FutureExt};
 use futures::io::{AsyncRead, AsyncWrite};
 use futures::ready;
 use futures::stream::StreamExt;
+use futures_timer::Delay;
+use instant::Instant;
 use libp2p_core::{Endpoint, Multiaddr};
 use libp2p_identity::PeerId;
-use libp2p_swarm::behaviour::{ConnectionClosed, ConnectionEstablished, FromSwarm};
+use libp2p_swarm::behaviour::{
+    ConnectionClosed, ConnectionEstablished, FromSwarm, NatStatusChanged, PeerListenAddrsChanged,
+};
 use libp2p_swarm::dial_opts::DialOpts;
 use libp2p_swarm::{
     dummy, ConnectionDenied, ConnectionHandler, ConnectionHandlerUpgrErr, ConnectionId,
-    DialFailure, NegotiatedSubstream, NetworkBehaviour, NotifyHandler, PollParameters, THandler,
-    THandlerInEvent, THandlerOutEvent, ToSwarm,
+    DialFailure, NatStatus, NegotiatedSubstream, NetworkBehaviour, NotifyHandler, PollParameters,
+    THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
 };
-use std::collections::{hash_map, HashMap, VecDeque};
+use std::collections::{hash_map, HashMap, HashSet, VecDeque};
 use std::io::{Error, ErrorKind, IoSlice};
 use std::ops::DerefMut;
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
 use transport::Transport;
 use void::Void;
 
+pub use relay_selection::RelayScore;
+
 /// The events produced by the client `Behaviour`.
 #[derive(Debug)]
 pub enum Event {
@@ -84,19 +96,163 @@ pub enum Event {
         relay_peer_id: PeerId,
         error: ConnectionHandlerUpgrErr<void::Void>,
     },
-    /// An inbound circuit request has been denied.
+    /// An inbound circuit request has been denied, either because we hold no
+    /// reservation with the relay or because of the configured
+    /// [`InboundCircuitPolicy`].
     InboundCircuitReqDenied { src_peer_id: PeerId },
     /// Denying an inbound circuit request failed.
     InboundCircuitReqDenyFailed {
         src_peer_id: PeerId,
         error: inbound_stop::UpgradeError,
     },
+    /// The statistics of an active circuit, reported every [`Config::circuit_stats_interval`].
+    CircuitStatsReported { stats: CircuitStats },
+}
+
+/// Statistics of an active circuit, see [`Behaviour::circuit_stats`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitStats {
+    /// The relay the circuit is established via.
+    pub relay_peer_id: PeerId,
+    /// The peer at the other end of the circuit.
+    pub remote_peer_id: PeerId,
+    /// [`Endpoint::Dialer`] for outbound, [`Endpoint::Listener`] for inbound circuits.
+    pub endpoint: Endpoint,
+    /// The number of bytes read from the circuit.
+    pub bytes_read: u64,
+    /// The number of bytes written to the circuit.
+    pub bytes_written: u64,
+    /// How long the circuit has been established.
+    pub duration: Duration,
+}
+
+/// The byte counters of a circuit, shared between its [`Connection`] and the [`Behaviour`].
+#[derive(Debug)]
+pub struct CircuitCounters {
+    established: Instant,
+    read: AtomicU64,
+    written: AtomicU64,
+}
+
+impl CircuitCounters {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            established: Instant::now(),
+            read: AtomicU64::new(0),
+            written: AtomicU64::new(0),
+        })
+    }
+
+    fn on_read(&self, n: usize) {
+        self.read.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    fn on_written(&self, n: usize) {
+        self.written.fetch_add(n as u64, Ordering::Relaxed);
+    }
+}
+
+/// An active circuit tracked by the [`Behaviour`].
+struct Circuit {
+    relay_peer_id: PeerId,
+    remote_peer_id: PeerId,
+    endpoint: Endpoint,
+    counters: Arc<CircuitCounters>,
+}
+
+impl Circuit {
+    /// Whether the [`Connection`] of the circuit has been dropped.
+    fn is_closed(&self) -> bool {
+        Arc::strong_count(&self.counters) == 1
+    }
+
+    fn stats(&self) -> CircuitStats {
+        CircuitStats {
+            relay_peer_id: self.relay_peer_id,
+            remote_peer_id: self.remote_peer_id,
+            endpoint: self.endpoint,
+            bytes_read: self.counters.read.load(Ordering::Relaxed),
+            bytes_written: self.counters.written.load(Ordering::Relaxed),
+            duration: self.counters.established.elapsed(),
+        }
+    }
+}
+
+/// Configuration for the relay client [`Behaviour`].
+#[derive(Clone)]
+pub struct Config {
+    /// Decides which inbound circuits, i.e. connections from remote peers
+    /// through a relay we hold a reservation with, are accepted.
+    ///
+    /// When `None`, all inbound circuits are accepted.
+    pub inbound_circuit_policy: Option<Arc<dyn InboundCircuitPolicy>>,
+    /// How often candidate relays, see [`Behaviour::add_relay_candidate`], are pinged.
+    ///
+    /// Defaults to 5 minutes.
+    pub relay_probe_interval: Duration,
+    /// Whether the relayed addresses of our reservations are only reported as listen addresses,
+    /// thus advertised to other peers e.g. via identify or Kademlia, while the local node is not
+    /// publicly reachable, see [`Behaviour::set_nat_status`].
+    ///
+    /// Defaults to `false`, i.e. relayed addresses are always reported.
+    pub advertise_only_when_private: bool,
+    /// How often the statistics of the active circuits are reported via
+    /// [`Event::CircuitStatsReported`].
+    ///
+    /// Defaults to `None`, i.e. statistics are only available via [`Behaviour::circuit_stats`].
+    pub circuit_stats_interval: Option<Duration>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            inbound_circuit_policy: None,
+            relay_probe_interval: Duration::from_secs(5 * 60),
+            advertise_only_when_private: false,
+            circuit_stats_interval: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Config")
+            .field(
+                "inbound_circuit_policy",
+                &self.inbound_circuit_policy.as_ref().map(|_| "<policy>"),
+            )
+            .field("relay_probe_interval", &self.relay_probe_interval)
+            .field(
+                "advertise_only_when_private",
+                &self.advertise_only_when_private,
+            )
+            .field("circuit_stats_interval", &self.circuit_stats_interval)
+            .finish()
+    }
+}
+
+/// Decides whether to accept an inbound circuit request.
+///
+/// The policy is consulted before the circuit is accepted. Rejected requests
+/// are answered with a `PERMISSION_DENIED` status and reported via
+/// [`Event::InboundCircuitReqDenied`].
+pub trait InboundCircuitPolicy: Send + Sync {
+    /// Returns whether the circuit from `src_peer_id`, relayed by `relay_peer_id`
+    /// reachable at `relay_addr`, is accepted.
+    fn accept(&self, src_peer_id: PeerId, relay_peer_id: PeerId, relay_addr: &Multiaddr) -> bool;
+}
+
+impl<T: Fn(PeerId, PeerId, &Multiaddr) -> bool + Send + Sync> InboundCircuitPolicy for T {
+    fn accept(&self, src_peer_id: PeerId, relay_peer_id: PeerId, relay_addr: &Multiaddr) -> bool {
+        self(src_peer_id, relay_peer_id, relay_addr)
+    }
 }
 
 /// [`NetworkBehaviour`] implementation of the relay client
 /// functionality of the circuit relay v2 protocol.
 pub struct Behaviour {
     local_peer_id: PeerId,
+    config: Config,
 
     from_transport: Receiver<transport::TransportToBehaviourMsg>,
     /// Set of directly connected peers, i.e. not connected via a relayed
@@ -107,27 +263,324 @@ pub struct Behaviour {
     queued_actions: VecDeque<ToSwarm<Event, Either<handler::In, Void>>>,
 
     pending_handler_commands: HashMap<ConnectionId, handler::In>,
+
+    /// See [`Behaviour::set_reservations_enabled`].
+    reservations_enabled: bool,
+    /// Reservation requests of the transport, held back while reservations are disabled.
+    paused_listen_reqs: VecDeque<transport::TransportToBehaviourMsg>,
+    /// Waker of the last call to [`NetworkBehaviour::poll`], woken once reservations are
+    /// re-enabled to process the reservation requests held back.
+    waker: Option<Waker>,
+
+    /// See [`Behaviour::add_relay_candidate`].
+    relay_candidates: RelayCandidates,
+    /// When to ping the candidate relays next.
+    next_relay_probe: Delay,
+
+    /// The connections our reservations are held on, by relay.
+    reservations: HashMap<PeerId, ConnectionId>,
+    /// Relays a renewal of our reservation is due or in flight with.
+    renewing_reservations: HashSet<PeerId>,
+    /// The listen addresses last reported for relays, see
+    /// [`Behaviour::on_relay_addresses_changed`].
+    relay_listen_addrs: HashMap<PeerId, Vec<Multiaddr>>,
+    /// Vouchers of our reservations, by relay.
+    reservation_vouchers: HashMap<PeerId, ReservationVoucher>,
+    /// Vouchers presented when establishing circuits, by relay and destination.
+    circuit_vouchers: HashMap<(PeerId, PeerId), ReservationVoucher>,
+
+    /// See [`Behaviour::set_nat_status`].
+    nat_status: NatStatus,
+    /// Whether the [`Transport`] reports the relayed addresses of our reservations.
+    advertise_circuit_addrs: Arc<transport::AdvertiseCircuitAddrs>,
+
+    /// The active circuits, see [`Behaviour::circuit_stats`].
+    circuits: Vec<Circuit>,
+    /// When to report the statistics of the active circuits next.
+    next_circuit_stats: Option<Delay>,
 }
 
 /// Create a new client relay [`Behaviour`] with it's corresponding [`Transport`].
 pub fn new(local_peer_id: PeerId) -> (Transport, Behaviour) {
-    let (transport, from_transport) = Transport::new();
+    new_with_config(local_peer_id, Config::default())
+}
+
+/// Create a new client relay [`Behaviour`] with it's corresponding [`Transport`],
+/// using the given [`Config`].
+pub fn new_with_config(local_peer_id: PeerId, config: Config) -> (Transport, Behaviour) {
+    let nat_status = NatStatus::default();
+    let advertise_circuit_addrs = Arc::new(transport::AdvertiseCircuitAddrs::new(
+        advertise_circuit_addrs(&config, &nat_status),
+    ));
+    let (transport, from_transport) = Transport::new(advertise_circuit_addrs.clone());
+    let next_relay_probe = Delay::new(config.relay_probe_interval);
+    let next_circuit_stats = config.circuit_stats_interval.map(Delay::new);
     let behaviour = Behaviour {
         local_peer_id,
+        config,
         from_transport,
         directly_connected_peers: Default::default(),
         queued_actions: Default::default(),
         pending_handler_commands: Default::default(),
+        reservations_enabled: true,
+        paused_listen_reqs: Default::default(),
+        waker: None,
+        relay_candidates: Default::default(),
+        next_relay_probe,
+        reservations: Default::default(),
+        renewing_reservations: Default::default(),
+        relay_listen_addrs: Default::default(),
+        reservation_vouchers: Default::default(),
+        circuit_vouchers: Default::default(),
+        nat_status,
+        advertise_circuit_addrs,
+        circuits: Default::default(),
+        next_circuit_stats,
     };
     (transport, behaviour)
 }
 
+/// Whether the relayed addresses of our reservations are reported given our reachability.
+fn advertise_circuit_addrs(config: &Config, nat_status: &NatStatus) -> bool {
+    !config.advertise_only_when_private || *nat_status == NatStatus::Private
+}
+
 impl Behaviour {
     #[deprecated(since = "0.15.0", note = "Use libp2p_relay::client::new instead.")]
     pub fn new_transport_and_behaviour(local_peer_id: PeerId) -> (transport::Transport, Self) {
         new(local_peer_id)
     }
 
+    /// Enables or disables reservations with relays.
+    ///
+    /// While disabled, new reservations requested via [`Transport`] listeners are held back and
+    /// existing reservations are not renewed, thus expire at the relay. The listeners themselves
+    /// remain open. Once re-enabled, held back reservations are requested and due renewals sent.
+    ///
+    /// Enabled by default.
+    pub fn set_reservations_enabled(&mut self, enabled: bool) {
+        if self.reservations_enabled == enabled {
+            return;
+        }
+
+        self.reservations_enabled = enabled;
+        for (peer_id, connections) in self.directly_connected_peers.iter() {
+            for connection_id in connections {
+                self.queued_actions.push_back(ToSwarm::NotifyHandler {
+                    peer_id: *peer_id,
+                    handler: NotifyHandler::One(*connection_id),
+                    event: Either::Left(handler::In::SetRenewalPaused(!enabled)),
+                });
+            }
+        }
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Whether reservations are enabled, see [`Behaviour::set_reservations_enabled`].
+    pub fn reservations_enabled(&self) -> bool {
+        self.reservations_enabled
+    }
+
+    /// Sets the reachability of the local node behind a NAT, e.g. as determined by AutoNAT.
+    ///
+    /// With [`Config::advertise_only_when_private`], the relayed addresses of our reservations are
+    /// reported as listen addresses once the node is [`NatStatus::Private`] and expired again once
+    /// it is confirmed to be [`NatStatus::Public`].
+    ///
+    /// Changes of the status reported to the [`Swarm`](libp2p_swarm::Swarm), e.g. by the AutoNAT
+    /// behaviour, are applied automatically.
+    pub fn set_nat_status(&mut self, status: NatStatus) {
+        self.advertise_circuit_addrs
+            .set(advertise_circuit_addrs(&self.config, &status));
+        self.nat_status = status;
+    }
+
+    /// The reachability of the local node, see [`Behaviour::set_nat_status`].
+    pub fn nat_status(&self) -> &NatStatus {
+        &self.nat_status
+    }
+
+    /// Adds a candidate relay, reachable at the given address, to select relays from.
+    ///
+    /// The relay is pinged right away and every [`Config::relay_probe_interval`] thereafter,
+    /// dialing it if need be. Together with the outcome of reservations with the relay, the
+    /// round-trip times make up its [`RelayScore`]. See [`Behaviour::select_relays`] to select the
+    /// best relays to listen via.
+    pub fn add_relay_candidate(&mut self, peer_id: PeerId, address: Multiaddr) {
+        self.relay_candidates.insert(peer_id, address);
+        self.probe_relay(peer_id);
+    }
+
+    /// Removes a candidate relay, returning its last score.
+    pub fn remove_relay_candidate(&mut self, peer_id: &PeerId) -> Option<RelayScore> {
+        self.relay_candidates.remove(peer_id)
+    }
+
+    /// Returns the scores of all candidate relays.
+    pub fn relay_scores(&self) -> impl Iterator<Item = (&PeerId, &RelayScore)> {
+        self.relay_candidates.iter()
+    }
+
+    /// Returns the score of the given candidate relay.
+    pub fn relay_score(&self, peer_id: &PeerId) -> Option<&RelayScore> {
+        self.relay_candidates.get(peer_id)
+    }
+
+    /// Selects up to `n` of the candidate relays, best first.
+    ///
+    /// Relays are ranked by their round-trip time divided by their
+    /// [`RelayScore::reliability`]. Relays with unknown round-trip time, e.g. not pinged yet, are
+    /// ranked last. To reserve a slot with a selected relay, listen on
+    /// `<address>/p2p/<peer-id>/p2p-circuit` via the [`Transport`].
+    pub fn select_relays(&self, n: usize) -> Vec<(PeerId, Multiaddr)> {
+        self.relay_candidates.select(n)
+    }
+
+    /// Renews the reservation with the given relay right away.
+    ///
+    /// The relayed addresses no longer included in the renewed reservation are expired, the new
+    /// ones reported as listen addresses. While reservations are disabled, see
+    /// [`Behaviour::set_reservations_enabled`], the renewal is held back.
+    ///
+    /// Returns `false` if we hold no reservation with the relay or a renewal is already due or in
+    /// flight.
+    pub fn refresh_reservation(&mut self, relay_peer_id: &PeerId) -> bool {
+        let connection_id = match self.reservations.get(relay_peer_id) {
+            Some(connection_id) => *connection_id,
+            None => return false,
+        };
+        if !self.renewing_reservations.insert(*relay_peer_id) {
+            return false;
+        }
+        self.queued_actions.push_back(ToSwarm::NotifyHandler {
+            peer_id: *relay_peer_id,
+            handler: NotifyHandler::One(connection_id),
+            event: Either::Left(handler::In::RenewReservation),
+        });
+        true
+    }
+
+    /// Informs the behaviour about the listen addresses of a relay.
+    ///
+    /// Listen addresses reported via [`FromSwarm::PeerListenAddrsChanged`], e.g. by
+    /// `libp2p-identify` running in the same `Swarm`, are handled automatically. Call this for
+    /// addresses learned otherwise. If they differ from the ones previously reported, the
+    /// reservation with the relay is refreshed, see
+    /// [`Behaviour::refresh_reservation`], replacing the stale relayed addresses by the ones
+    /// derived from the relay's new addresses.
+    ///
+    /// Returns whether the reservation is refreshed.
+    pub fn on_relay_addresses_changed(
+        &mut self,
+        relay_peer_id: &PeerId,
+        listen_addrs: &[Multiaddr],
+    ) -> bool {
+        let mut listen_addrs = listen_addrs.to_vec();
+        listen_addrs.sort();
+        listen_addrs.dedup();
+
+        match self.relay_listen_addrs.insert(*relay_peer_id, listen_addrs) {
+            Some(previous) if Some(&previous) != self.relay_listen_addrs.get(relay_peer_id) => {
+                self.refresh_reservation(relay_peer_id)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the voucher the given relay issued for our reservation, if any.
+    ///
+    /// Share it with peers that should be able to reach us via relays requiring vouchers, see
+    /// [`crate::Config::require_circuit_voucher`], who register it via
+    /// [`Behaviour::add_circuit_voucher`].
+    pub fn reservation_voucher(&self, relay_peer_id: &PeerId) -> Option<&ReservationVoucher> {
+        self.reservation_vouchers.get(relay_peer_id)
+    }
+
+    /// Adds the voucher of a remote peer's reservation, presented to the issuing relay when
+    /// establishing a circuit to that peer.
+    pub fn add_circuit_voucher(&mut self, voucher: ReservationVoucher) {
+        self.circuit_vouchers
+            .insert((voucher.relay(), voucher.peer()), voucher);
+    }
+
+    /// Returns the statistics of the active inbound and outbound circuits.
+    ///
+    /// E.g. a circuit transferring a lot of data might justify upgrading to a direct connection.
+    pub fn circuit_stats(&mut self) -> Vec<CircuitStats> {
+        self.circuits.retain(|c| !c.is_closed());
+        self.circuits.iter().map(Circuit::stats).collect()
+    }
+
+    /// Returns the unexpired voucher to present for a circuit to `dst_peer_id` via the relay.
+    fn circuit_voucher(
+        &mut self,
+        relay_peer_id: PeerId,
+        dst_peer_id: PeerId,
+    ) -> Option<ReservationVoucher> {
+        match self.circuit_vouchers.entry((relay_peer_id, dst_peer_id)) {
+            hash_map::Entry::Occupied(entry) if entry.get().is_expired() => {
+                entry.remove();
+                None
+            }
+            hash_map::Entry::Occupied(entry) => Some(entry.get().clone()),
+            hash_map::Entry::Vacant(_) => None,
+        }
+    }
+
+    fn probe_relay(&mut self, peer_id: PeerId) {
+        let address = match self.relay_candidates.get(&peer_id) {
+            Some(score) => score.address().clone(),
+            None => return,
+        };
+
+        match self
+            .directly_connected_peers
+            .get(&peer_id)
+            .and_then(|cs| cs.first())
+        {
+            Some(connection_id) => self.queued_actions.push_back(ToSwarm::NotifyHandler {
+                peer_id,
+                handler: NotifyHandler::One(*connection_id),
+                event: Either::Left(handler::In::Ping),
+            }),
+            None => {
+                let opts = DialOpts::peer_id(peer_id)
+                    .addresses(vec![address])
+                    .extend_addresses_through_behaviour()
+                    .build();
+                self.pending_handler_commands
+                    .insert(opts.connection_id(), handler::In::Ping);
+                self.queued_actions.push_back(ToSwarm::Dial { opts });
+            }
+        }
+    }
+
+    fn new_handler(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+    ) -> Handler {
+        let mut handler = Handler::new(
+            self.local_peer_id,
+            peer,
+            addr.clone(),
+            self.config.inbound_circuit_policy.clone(),
+        );
+
+        if !self.reservations_enabled {
+            handler.on_behaviour_event(handler::In::SetRenewalPaused(true))
+        }
+
+        if let Some(event) = self.pending_handler_commands.remove(&connection_id) {
+            handler.on_behaviour_event(event)
+        }
+
+        handler
+    }
+
     fn on_connection_closed(
         &mut self,
         ConnectionClosed {
@@ -137,6 +590,11 @@ impl Behaviour {
             ..
         }: ConnectionClosed<<Self as NetworkBehaviour>::ConnectionHandler>,
     ) {
+        if self.reservations.get(&peer_id) == Some(&connection_id) {
+            self.reservations.remove(&peer_id);
+            self.renewing_reservations.remove(&peer_id);
+        }
+
         if !endpoint.is_relayed() {
             match self.directly_connected_peers.entry(peer_id) {
                 hash_map::Entry::Occupied(mut connections) => {
@@ -149,6 +607,7 @@ impl Behaviour {
 
                     if connections.get().is_empty() {
                         connections.remove();
+                        self.relay_listen_addrs.remove(&peer_id);
                     }
                 }
                 hash_map::Entry::Vacant(_) => {
@@ -174,13 +633,11 @@ impl NetworkBehaviour for Behaviour {
             return Ok(Either::Right(dummy::ConnectionHandler));
         }
 
-        let mut handler = Handler::new(self.local_peer_id, peer, remote_addr.clone());
-
-        if let Some(event) = self.pending_handler_commands.remove(&connection_id) {
-            handler.on_behaviour_event(event)
-        }
-
-        Ok(Either::Left(handler))
+        Ok(Either::Left(self.new_handler(
+            connection_id,
+            peer,
+            remote_addr,
+        )))
     }
 
     fn handle_established_outbound_connection(
@@ -194,13 +651,7 @@ impl NetworkBehaviour for Behaviour {
             return Ok(Either::Right(dummy::ConnectionHandler));
         }
 
-        let mut handler = Handler::new(self.local_peer_id, peer, addr.clone());
-
-        if let Some(event) = self.pending_handler_commands.remove(&connection_id) {
-            handler.on_behaviour_event(event)
-        }
-
-        Ok(Either::Left(handler))
+        Ok(Either::Left(self.new_handler(connection_id, peer, addr)))
     }
 
     fn on_swarm_event(&mut self, event: FromSwarm<Self::ConnectionHandler>) {
@@ -229,8 +680,29 @@ impl NetworkBehaviour for Behaviour {
             FromSwarm::ConnectionClosed(connection_closed) => {
                 self.on_connection_closed(connection_closed)
             }
-            FromSwarm::DialFailure(DialFailure { connection_id, .. }) => {
-                self.pending_handler_commands.remove(&connection_id);
+            FromSwarm::DialFailure(DialFailure {
+                peer_id,
+                connection_id,
+                ..
+            }) => {
+                if let (Some(handler::In::Ping), Some(peer_id)) = (
+                    self.pending_handler_commands.remove(&connection_id),
+                    peer_id,
+                ) {
+                    self.relay_candidates.on_ping(&peer_id, None);
+                }
+            }
+            FromSwarm::PeerListenAddrsChanged(PeerListenAddrsChanged {
+                peer_id,
+                listen_addrs,
+            }) => {
+                // Only track the relays we are directly connected to, forgotten on disconnect.
+                if self.directly_connected_peers.contains_key(&peer_id) {
+                    self.on_relay_addresses_changed(&peer_id, listen_addrs);
+                }
+            }
+            FromSwarm::NatStatusChanged(NatStatusChanged { new, .. }) => {
+                self.set_nat_status(new.clone())
             }
             FromSwarm::AddressChange(_)
             | FromSwarm::ListenFailure(_)
@@ -240,14 +712,15 @@ impl NetworkBehaviour for Behaviour {
             | FromSwarm::ListenerError(_)
             | FromSwarm::ListenerClosed(_)
             | FromSwarm::NewExternalAddr(_)
-            | FromSwarm::ExpiredExternalAddr(_) => {}
+            | FromSwarm::ExpiredExternalAddr(_)
+            | FromSwarm::PeerProtocolsChanged(_) => {}
         }
     }
 
     fn on_connection_handler_event(
         &mut self,
         event_source: PeerId,
-        _connection: ConnectionId,
+        connection: ConnectionId,
         handler_event: THandlerOutEvent<Self>,
     ) {
         let handler_event = match handler_event {
@@ -256,7 +729,22 @@ impl NetworkBehaviour for Behaviour {
         };
 
         let event = match handler_event {
-            handler::Event::ReservationReqAccepted { renewal, limit } => {
+            handler::Event::ReservationRenewalStarted => {
+                self.renewing_reservations.insert(event_source);
+                return;
+            }
+            handler::Event::ReservationReqAccepted {
+                renewal,
+                limit,
+                voucher,
+            } => {
+                self.relay_candidates.on_reservation(&event_source, true);
+                self.reservations.insert(event_source, connection);
+                self.renewing_reservations.remove(&event_source);
+                match voucher.filter(|v| v.relay() == event_source) {
+                    Some(voucher) => self.reservation_vouchers.insert(event_source, *voucher),
+                    None => self.reservation_vouchers.remove(&event_source),
+                };
                 Event::ReservationReqAccepted {
                     relay_peer_id: event_source,
                     renewal,
@@ -264,13 +752,29 @@ impl NetworkBehaviour for Behaviour {
                 }
             }
             handler::Event::ReservationReqFailed { renewal, error } => {
+                self.relay_candidates.on_reservation(&event_source, false);
+                if self.reservations.get(&event_source) == Some(&connection) {
+                    self.reservations.remove(&event_source);
+                    self.renewing_reservations.remove(&event_source);
+                }
+                self.reservation_vouchers.remove(&event_source);
                 Event::ReservationReqFailed {
                     relay_peer_id: event_source,
                     renewal,
                     error,
                 }
             }
-            handler::Event::OutboundCircuitEstablished { limit } => {
+            handler::Event::OutboundCircuitEstablished {
+                dst_peer_id,
+                limit,
+                counters,
+            } => {
+                self.circuits.push(Circuit {
+                    relay_peer_id: event_source,
+                    remote_peer_id: dst_peer_id,
+                    endpoint: Endpoint::Dialer,
+                    counters,
+                });
                 Event::OutboundCircuitEstablished {
                     relay_peer_id: event_source,
                     limit,
@@ -280,7 +784,17 @@ impl NetworkBehaviour for Behaviour {
                 relay_peer_id: event_source,
                 error,
             },
-            handler::Event::InboundCircuitEstablished { src_peer_id, limit } => {
+            handler::Event::InboundCircuitEstablished {
+                src_peer_id,
+                limit,
+                counters,
+            } => {
+                self.circuits.push(Circuit {
+                    relay_peer_id: event_source,
+                    remote_peer_id: src_peer_id,
+                    endpoint: Endpoint::Listener,
+                    counters,
+                });
                 Event::InboundCircuitEstablished { src_peer_id, limit }
             }
             handler::Event::InboundCircuitReqFailed { error } => Event::InboundCircuitReqFailed {
@@ -293,6 +807,10 @@ impl NetworkBehaviour for Behaviour {
             handler::Event::InboundCircuitReqDenyFailed { src_peer_id, error } => {
                 Event::InboundCircuitReqDenyFailed { src_peer_id, error }
             }
+            handler::Event::Pinged { rtt } => {
+                self.relay_candidates.on_ping(&event_source, rtt);
+                return;
+            }
         };
 
         self.queued_actions.push_back(ToSwarm::GenerateEvent(event))
@@ -303,11 +821,65 @@ impl NetworkBehaviour for Behaviour {
         cx: &mut Context<'_>,
         _poll_parameters: &mut impl PollParameters,
     ) -> Poll<ToSwarm<Self::OutEvent, THandlerInEvent<Self>>> {
+        if self.next_relay_probe.poll_unpin(cx).is_ready() {
+            self.next_relay_probe = Delay::new(self.config.relay_probe_interval);
+            let candidates = self
+                .relay_candidates
+                .iter()
+                .map(|(peer_id, _)| *peer_id)
+                .collect::<Vec<_>>();
+            for peer_id in candidates {
+                self.probe_relay(peer_id);
+            }
+            // Register the new delay with the waker.
+            let _ = self.next_relay_probe.poll_unpin(cx);
+        }
+
+        self.circuits.retain(|c| !c.is_closed());
+
+        if let Some(next_circuit_stats) = self.next_circuit_stats.as_mut() {
+            if next_circuit_stats.poll_unpin(cx).is_ready() {
+                let interval = self
+                    .config
+                    .circuit_stats_interval
+                    .expect("Delay to exist iff interval is configured.");
+                next_circuit_stats.reset(interval);
+                // Register the new delay with the waker.
+                let _ = next_circuit_stats.poll_unpin(cx);
+
+                self.queued_actions.extend(self.circuits.iter().map(|c| {
+                    ToSwarm::GenerateEvent(Event::CircuitStatsReported { stats: c.stats() })
+                }));
+            }
+        }
+
         if let Some(action) = self.queued_actions.pop_front() {
             return Poll::Ready(action);
         }
 
-        let action = match ready!(self.from_transport.poll_next_unpin(cx)) {
+        self.waker = Some(cx.waker().clone());
+
+        let paused_listen_req = if self.reservations_enabled {
+            self.paused_listen_reqs.pop_front()
+        } else {
+            None
+        };
+
+        let msg = match paused_listen_req {
+            Some(msg) => Some(msg),
+            None => loop {
+                match ready!(self.from_transport.poll_next_unpin(cx)) {
+                    Some(msg @ transport::TransportToBehaviourMsg::ListenReq { .. })
+                        if !self.reservations_enabled =>
+                    {
+                        self.paused_listen_reqs.push_back(msg);
+                    }
+                    msg => break msg,
+                }
+            },
+        };
+
+        let action = match msg {
             Some(transport::TransportToBehaviourMsg::ListenReq {
                 relay_peer_id,
                 relay_addr,
@@ -343,6 +915,9 @@ impl NetworkBehaviour for Behaviour {
                 send_back,
                 ..
             }) => {
+                let voucher = self
+                    .circuit_voucher(relay_peer_id, dst_peer_id)
+                    .map(Box::new);
                 match self
                     .directly_connected_peers
                     .get(&relay_peer_id)
@@ -354,6 +929,7 @@ impl NetworkBehaviour for Behaviour {
                         event: Either::Left(handler::In::EstablishCircuit {
                             send_back,
                             dst_peer_id,
+                            voucher,
                         }),
                     },
                     None => {
@@ -368,6 +944,7 @@ impl NetworkBehaviour for Behaviour {
                             handler::In::EstablishCircuit {
                                 send_back,
                                 dst_peer_id,
+                                voucher,
                             },
                         );
 
@@ -396,6 +973,7 @@ pub enum Connection {
         read_buffer: Bytes,
         substream: NegotiatedSubstream,
         drop_notifier: oneshot::Sender<void::Void>,
+        counters: Arc<CircuitCounters>,
     },
 }
 
@@ -405,6 +983,7 @@ impl Connection {
     pub(crate) fn new_inbound(
         circuit: inbound_stop::Circuit,
         drop_notifier: oneshot::Sender<void::Void>,
+        counters: Arc<CircuitCounters>,
     ) -> Self {
         Connection::InboundAccepting {
             accept: async {
@@ -416,6 +995,7 @@ impl Connection {
                     read_buffer,
                     substream,
                     drop_notifier,
+                    counters,
                 })
             }
             .boxed(),
@@ -426,11 +1006,13 @@ impl Connection {
         substream: NegotiatedSubstream,
         read_buffer: Bytes,
         drop_notifier: oneshot::Sender<void::Void>,
+        counters: Arc<CircuitCounters>,
     ) -> Self {
         Connection::Operational {
             substream,
             read_buffer,
             drop_notifier,
+            counters,
         }
     }
 }
@@ -446,8 +1028,14 @@ impl AsyncWrite for Connection {
                 Connection::InboundAccepting { accept } => {
                     *self = ready!(accept.poll_unpin(cx))?;
                 }
-                Connection::Operational { substream, .. } => {
-                    return Pin::new(substream).poll_write(cx, buf);
+                Connection::Operational {
+                    substream,
+                    counters,
+                    ..
+                } => {
+                    let n = ready!(Pin::new(substream).poll_write(cx, buf))?;
+                    counters.on_written(n);
+                    return Poll::Ready(Ok(n));
                 }
             }
         }
@@ -487,8 +1075,14 @@ impl AsyncWrite for Connection {
                 Connection::InboundAccepting { accept } => {
                     *self = ready!(accept.poll_unpin(cx))?;
                 }
-                Connection::Operational { substream, .. } => {
-                    return Pin::new(substream).poll_write_vectored(cx, bufs);
+                Connection::Operational {
+                    substream,
+                    counters,
+                    ..
+                } => {
+                    let n = ready!(Pin::new(substream).poll_write_vectored(cx, bufs))?;
+                    counters.on_written(n);
+                    return Poll::Ready(Ok(n));
                 }
             }
         }
@@ -509,16 +1103,20 @@ impl AsyncRead for Connection {
                 Connection::Operational {
                     read_buffer,
                     substream,
+                    counters,
                     ..
                 } => {
                     if !read_buffer.is_empty() {
                         let n = std::cmp::min(read_buffer.len(), buf.len());
                         let data = read_buffer.split_to(n);
                         buf[0..n].copy_from_slice(&data[..]);
+                        counters.on_read(n);
                         return Poll::Ready(Ok(n));
                     }
 
-                    return Pin::new(substream).poll_read(cx, buf);
+                    let n = ready!(Pin::new(substream).poll_read(cx, buf))?;
+                    counters.on_read(n);
+                    return Poll::Ready(Ok(n));
                 }
             }
         }
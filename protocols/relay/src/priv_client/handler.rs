@@ -18,17 +18,20 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::priv_client::transport;
+use crate::multiaddr_ext::MultiaddrExt;
+use crate::priv_client::{transport, InboundCircuitPolicy};
 use crate::proto;
-use crate::protocol::{self, inbound_stop, outbound_hop};
+use crate::protocol::{self, inbound_stop, outbound_hop, outbound_ping};
+use crate::ReservationVoucher;
 use either::Either;
 use futures::channel::{mpsc, oneshot};
-use futures::future::{BoxFuture, FutureExt};
+use futures::future::{self, BoxFuture, FutureExt};
 use futures::sink::SinkExt;
 use futures::stream::{FuturesUnordered, StreamExt};
 use futures_timer::Delay;
 use instant::Instant;
 use libp2p_core::multiaddr::Protocol;
+use libp2p_core::upgrade::ReadyUpgrade;
 use libp2p_core::{upgrade, Multiaddr};
 use libp2p_identity::PeerId;
 use libp2p_swarm::handler::{
@@ -42,6 +45,8 @@ use libp2p_swarm::{
 use log::debug;
 use std::collections::{HashMap, VecDeque};
 use std::fmt;
+use std::io;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
@@ -50,14 +55,24 @@ use std::time::Duration;
 /// Circuits to be denied exceeding the limit are dropped.
 const MAX_NUMBER_DENYING_CIRCUIT: usize = 8;
 
+/// The time to wait for the response to a ping.
+const PING_TIMEOUT: Duration = Duration::from_secs(20);
+
 pub enum In {
     Reserve {
         to_listener: mpsc::Sender<transport::ToListenerMsg>,
     },
     EstablishCircuit {
         dst_peer_id: PeerId,
+        voucher: Option<Box<ReservationVoucher>>,
         send_back: oneshot::Sender<Result<super::Connection, ()>>,
     },
+    /// Pauses or resumes the renewal of the reservation with the relay.
+    SetRenewalPaused(bool),
+    /// Renews the reservation with the relay right away, unless renewal is paused.
+    RenewReservation,
+    /// Measures the round-trip time to the relay.
+    Ping,
 }
 
 impl fmt::Debug for In {
@@ -66,21 +81,31 @@ impl fmt::Debug for In {
             In::Reserve { to_listener: _ } => f.debug_struct("In::Reserve").finish(),
             In::EstablishCircuit {
                 dst_peer_id,
+                voucher,
                 send_back: _,
             } => f
                 .debug_struct("In::EstablishCircuit")
                 .field("dst_peer_id", dst_peer_id)
+                .field("voucher", &voucher.is_some())
                 .finish(),
+            In::SetRenewalPaused(paused) => {
+                f.debug_tuple("In::SetRenewalPaused").field(paused).finish()
+            }
+            In::RenewReservation => f.debug_struct("In::RenewReservation").finish(),
+            In::Ping => f.debug_struct("In::Ping").finish(),
         }
     }
 }
 
 #[derive(Debug)]
 pub enum Event {
+    /// The renewal of the reservation has been requested from the relay.
+    ReservationRenewalStarted,
     ReservationReqAccepted {
         /// Indicates whether the request replaces an existing reservation.
         renewal: bool,
         limit: Option<protocol::Limit>,
+        voucher: Option<Box<ReservationVoucher>>,
     },
     ReservationReqFailed {
         /// Indicates whether the request replaces an existing reservation.
@@ -88,7 +113,11 @@ pub enum Event {
         error: ConnectionHandlerUpgrErr<outbound_hop::ReservationFailedReason>,
     },
     /// An outbound circuit has been established.
-    OutboundCircuitEstablished { limit: Option<protocol::Limit> },
+    OutboundCircuitEstablished {
+        dst_peer_id: PeerId,
+        limit: Option<protocol::Limit>,
+        counters: Arc<super::CircuitCounters>,
+    },
     OutboundCircuitReqFailed {
         error: ConnectionHandlerUpgrErr<outbound_hop::CircuitFailedReason>,
     },
@@ -96,6 +125,7 @@ pub enum Event {
     InboundCircuitEstablished {
         src_peer_id: PeerId,
         limit: Option<protocol::Limit>,
+        counters: Arc<super::CircuitCounters>,
     },
     /// An inbound circuit request has failed.
     InboundCircuitReqFailed {
@@ -108,6 +138,8 @@ pub enum Event {
         src_peer_id: PeerId,
         error: inbound_stop::UpgradeError,
     },
+    /// A ping to the relay completed, [`None`] if it failed.
+    Pinged { rtt: Option<Duration> },
 }
 
 pub struct Handler {
@@ -135,6 +167,11 @@ pub struct Handler {
 
     reservation: Reservation,
 
+    /// Whether renewing the [`Handler::reservation`] is paused.
+    ///
+    /// A due renewal is sent once resumed.
+    renewal_paused: bool,
+
     /// Tracks substreams lent out to the transport.
     ///
     /// Contains a [`futures::future::Future`] for each lend out substream that
@@ -148,6 +185,12 @@ pub struct Handler {
     circuit_deny_futs:
         HashMap<PeerId, BoxFuture<'static, Result<(), protocol::inbound_stop::UpgradeError>>>,
 
+    /// Decides which inbound circuits are accepted, see [`InboundCircuitPolicy`].
+    inbound_circuit_policy: Option<Arc<dyn InboundCircuitPolicy>>,
+
+    /// Pings to the relay in flight.
+    pings: FuturesUnordered<BoxFuture<'static, io::Result<Duration>>>,
+
     /// Futures that try to send errors to the transport.
     ///
     /// We may drop errors if this handler ends up in a terminal state (by returning
@@ -156,7 +199,12 @@ pub struct Handler {
 }
 
 impl Handler {
-    pub fn new(local_peer_id: PeerId, remote_peer_id: PeerId, remote_addr: Multiaddr) -> Self {
+    pub fn new(
+        local_peer_id: PeerId,
+        remote_peer_id: PeerId,
+        remote_addr: Multiaddr,
+        inbound_circuit_policy: Option<Arc<dyn InboundCircuitPolicy>>,
+    ) -> Self {
         Self {
             local_peer_id,
             remote_peer_id,
@@ -164,8 +212,11 @@ impl Handler {
             queued_events: Default::default(),
             pending_error: Default::default(),
             reservation: Reservation::None,
+            renewal_paused: false,
             alive_lend_out_substreams: Default::default(),
             circuit_deny_futs: Default::default(),
+            inbound_circuit_policy,
+            pings: Default::default(),
             send_error_futs: Default::default(),
             keep_alive: KeepAlive::Yes,
         }
@@ -181,15 +232,31 @@ impl Handler {
             <Self as ConnectionHandler>::InboundOpenInfo,
         >,
     ) {
+        let src_peer_id = inbound_circuit.src_peer_id();
+
+        if let Some(policy) = &self.inbound_circuit_policy {
+            if !matches!(self.reservation, Reservation::None)
+                && !policy.accept(src_peer_id, self.remote_peer_id, &self.remote_addr)
+            {
+                log::debug!(
+                    "Denying inbound circuit request from {:?} due to policy.",
+                    src_peer_id,
+                );
+                self.deny_inbound_circuit(inbound_circuit, proto::Status::PERMISSION_DENIED);
+                return;
+            }
+        }
+
         match &mut self.reservation {
             Reservation::Accepted { pending_msgs, .. }
             | Reservation::Renewing { pending_msgs, .. } => {
-                let src_peer_id = inbound_circuit.src_peer_id();
                 let limit = inbound_circuit.limit();
 
                 let (tx, rx) = oneshot::channel();
                 self.alive_lend_out_substreams.push(rx);
-                let connection = super::Connection::new_inbound(inbound_circuit, tx);
+                let counters = super::CircuitCounters::new();
+                let connection =
+                    super::Connection::new_inbound(inbound_circuit, tx, counters.clone());
 
                 pending_msgs.push_back(transport::ToListenerMsg::IncomingRelayedConnection {
                     stream: connection,
@@ -200,38 +267,44 @@ impl Handler {
 
                 self.queued_events.push_back(ConnectionHandlerEvent::Custom(
                     Event::InboundCircuitEstablished {
-                        src_peer_id: self.remote_peer_id,
+                        src_peer_id,
                         limit,
+                        counters,
                     },
                 ));
             }
             Reservation::None => {
-                let src_peer_id = inbound_circuit.src_peer_id();
-
-                if self.circuit_deny_futs.len() == MAX_NUMBER_DENYING_CIRCUIT
-                    && !self.circuit_deny_futs.contains_key(&src_peer_id)
-                {
-                    log::warn!(
-                        "Dropping inbound circuit request to be denied from {:?} due to exceeding limit.",
-                        src_peer_id,
-                    );
-                } else if self
-                    .circuit_deny_futs
-                    .insert(
-                        src_peer_id,
-                        inbound_circuit.deny(proto::Status::NO_RESERVATION).boxed(),
-                    )
-                    .is_some()
-                {
-                    log::warn!(
-                            "Dropping existing inbound circuit request to be denied from {:?} in favor of new one.",
-                            src_peer_id
-                        )
-                }
+                self.deny_inbound_circuit(inbound_circuit, proto::Status::NO_RESERVATION)
             }
         }
     }
 
+    fn deny_inbound_circuit(
+        &mut self,
+        inbound_circuit: inbound_stop::Circuit,
+        status: proto::Status,
+    ) {
+        let src_peer_id = inbound_circuit.src_peer_id();
+
+        if self.circuit_deny_futs.len() == MAX_NUMBER_DENYING_CIRCUIT
+            && !self.circuit_deny_futs.contains_key(&src_peer_id)
+        {
+            log::warn!(
+                "Dropping inbound circuit request to be denied from {:?} due to exceeding limit.",
+                src_peer_id,
+            );
+        } else if self
+            .circuit_deny_futs
+            .insert(src_peer_id, inbound_circuit.deny(status).boxed())
+            .is_some()
+        {
+            log::warn!(
+                "Dropping existing inbound circuit request to be denied from {:?} in favor of new one.",
+                src_peer_id
+            )
+        }
+    }
+
     fn on_fully_negotiated_outbound(
         &mut self,
         FullyNegotiatedOutbound {
@@ -245,11 +318,12 @@ impl Handler {
         match (output, info) {
             // Outbound reservation
             (
-                outbound_hop::Output::Reservation {
+                future::Either::Left(outbound_hop::Output::Reservation {
                     renewal_timeout,
                     addrs,
                     limit,
-                },
+                    voucher,
+                }),
                 OutboundOpenInfo::Reserve { to_listener },
             ) => {
                 let event = self.reservation.accepted(
@@ -258,6 +332,7 @@ impl Handler {
                     to_listener,
                     self.local_peer_id,
                     limit,
+                    voucher,
                 );
 
                 self.queued_events
@@ -266,23 +341,32 @@ impl Handler {
 
             // Outbound circuit
             (
-                outbound_hop::Output::Circuit {
+                future::Either::Left(outbound_hop::Output::Circuit {
                     substream,
                     read_buffer,
                     limit,
+                }),
+                OutboundOpenInfo::Connect {
+                    send_back,
+                    dst_peer_id,
                 },
-                OutboundOpenInfo::Connect { send_back },
             ) => {
                 let (tx, rx) = oneshot::channel();
+                let counters = super::CircuitCounters::new();
                 match send_back.send(Ok(super::Connection::new_outbound(
                     substream,
                     read_buffer,
                     tx,
+                    counters.clone(),
                 ))) {
                     Ok(()) => {
                         self.alive_lend_out_substreams.push(rx);
                         self.queued_events.push_back(ConnectionHandlerEvent::Custom(
-                            Event::OutboundCircuitEstablished { limit },
+                            Event::OutboundCircuitEstablished {
+                                dst_peer_id,
+                                limit,
+                                counters,
+                            },
                         ));
                     }
                     Err(_) => debug!(
@@ -293,6 +377,22 @@ impl Handler {
                 }
             }
 
+            // Outbound ping
+            (future::Either::Right(stream), OutboundOpenInfo::Ping) => {
+                self.pings.push(
+                    async move {
+                        let ping = outbound_ping::send_ping(stream);
+                        let timeout = Delay::new(PING_TIMEOUT);
+                        futures::pin_mut!(ping);
+                        match future::select(ping, timeout).await {
+                            future::Either::Left((result, _)) => result,
+                            future::Either::Right(((), _)) => Err(io::ErrorKind::TimedOut.into()),
+                        }
+                    }
+                    .boxed(),
+                );
+            }
+
             _ => unreachable!(),
         }
     }
@@ -347,7 +447,23 @@ impl Handler {
             <Self as ConnectionHandler>::OutboundProtocol,
         >,
     ) {
+        let error = match open_info {
+            OutboundOpenInfo::Ping => {
+                debug!("Failed to ping relay {}: {}", self.remote_peer_id, error);
+                self.queued_events
+                    .push_back(ConnectionHandlerEvent::Custom(Event::Pinged { rtt: None }));
+                return;
+            }
+            _ => error.map_upgrade_err(|e| {
+                e.map_err(|e| match e {
+                    Either::Left(e) => e,
+                    Either::Right(v) => void::unreachable(v),
+                })
+            }),
+        };
+
         match open_info {
+            OutboundOpenInfo::Ping => unreachable!("Handled above."),
             OutboundOpenInfo::Reserve { mut to_listener } => {
                 let non_fatal_error = match error {
                     ConnectionHandlerUpgrErr::Timeout => ConnectionHandlerUpgrErr::Timeout,
@@ -411,7 +527,7 @@ impl Handler {
                     },
                 ));
             }
-            OutboundOpenInfo::Connect { send_back } => {
+            OutboundOpenInfo::Connect { send_back, .. } => {
                 let non_fatal_error = match error {
                     ConnectionHandlerUpgrErr::Timeout => ConnectionHandlerUpgrErr::Timeout,
                     ConnectionHandlerUpgrErr::Timer => ConnectionHandlerUpgrErr::Timer,
@@ -471,7 +587,7 @@ impl ConnectionHandler for Handler {
         Either<inbound_stop::FatalUpgradeError, outbound_hop::FatalUpgradeError>,
     >;
     type InboundProtocol = inbound_stop::Upgrade;
-    type OutboundProtocol = outbound_hop::Upgrade;
+    type OutboundProtocol = Either<outbound_hop::Upgrade, ReadyUpgrade<&'static [u8]>>;
     type OutboundOpenInfo = OutboundOpenInfo;
     type InboundOpenInfo = ();
 
@@ -485,7 +601,7 @@ impl ConnectionHandler for Handler {
                 self.queued_events
                     .push_back(ConnectionHandlerEvent::OutboundSubstreamRequest {
                         protocol: SubstreamProtocol::new(
-                            outbound_hop::Upgrade::Reserve,
+                            Either::Left(outbound_hop::Upgrade::Reserve),
                             OutboundOpenInfo::Reserve { to_listener },
                         ),
                     });
@@ -493,12 +609,32 @@ impl ConnectionHandler for Handler {
             In::EstablishCircuit {
                 send_back,
                 dst_peer_id,
+                voucher,
             } => {
                 self.queued_events
                     .push_back(ConnectionHandlerEvent::OutboundSubstreamRequest {
                         protocol: SubstreamProtocol::new(
-                            outbound_hop::Upgrade::Connect { dst_peer_id },
-                            OutboundOpenInfo::Connect { send_back },
+                            Either::Left(outbound_hop::Upgrade::Connect {
+                                dst_peer_id,
+                                voucher,
+                            }),
+                            OutboundOpenInfo::Connect {
+                                send_back,
+                                dst_peer_id,
+                            },
+                        ),
+                    });
+            }
+            In::SetRenewalPaused(paused) => {
+                self.renewal_paused = paused;
+            }
+            In::RenewReservation => self.reservation.renew_now(),
+            In::Ping => {
+                self.queued_events
+                    .push_back(ConnectionHandlerEvent::OutboundSubstreamRequest {
+                        protocol: SubstreamProtocol::new(
+                            Either::Right(ReadyUpgrade::new(outbound_ping::PROTOCOL_NAME)),
+                            OutboundOpenInfo::Ping,
                         ),
                     });
             }
@@ -531,8 +667,20 @@ impl ConnectionHandler for Handler {
             return Poll::Ready(event);
         }
 
-        if let Poll::Ready(Some(protocol)) = self.reservation.poll(cx) {
-            return Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest { protocol });
+        if let Poll::Ready(Some(protocol)) = self.reservation.poll(self.renewal_paused, cx) {
+            self.queued_events.push_back(ConnectionHandlerEvent::Custom(
+                Event::ReservationRenewalStarted,
+            ));
+            return Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest {
+                protocol: protocol.map_upgrade(Either::Left),
+            });
+        }
+
+        if let Poll::Ready(Some(result)) = self.pings.poll_next_unpin(cx) {
+            let rtt = result
+                .map_err(|e| debug!("Failed to ping relay {}: {}", self.remote_peer_id, e))
+                .ok();
+            return Poll::Ready(ConnectionHandlerEvent::Custom(Event::Pinged { rtt }));
         }
 
         // Deny incoming circuit requests.
@@ -576,6 +724,7 @@ impl ConnectionHandler for Handler {
         if matches!(self.reservation, Reservation::None)
             && self.alive_lend_out_substreams.is_empty()
             && self.circuit_deny_futs.is_empty()
+            && self.pings.is_empty()
         {
             match self.keep_alive {
                 KeepAlive::Yes => {
@@ -642,6 +791,7 @@ impl Reservation {
         to_listener: mpsc::Sender<transport::ToListenerMsg>,
         local_peer_id: PeerId,
         limit: Option<protocol::Limit>,
+        voucher: Option<Box<ReservationVoucher>>,
     ) -> Event {
         let (renewal, mut pending_msgs) = match std::mem::replace(self, Self::None) {
             Reservation::Accepted { pending_msgs, .. }
@@ -653,6 +803,8 @@ impl Reservation {
             transport::Reservation {
                 addrs: addrs
                     .into_iter()
+                    // Relaying through multiple hops is not supported.
+                    .filter(|a| !a.is_relayed())
                     .map(|a| {
                         a.with(Protocol::P2pCircuit)
                             .with(Protocol::P2p(local_peer_id.into()))
@@ -667,7 +819,21 @@ impl Reservation {
             to_listener,
         };
 
-        Event::ReservationReqAccepted { renewal, limit }
+        Event::ReservationReqAccepted {
+            renewal,
+            limit,
+            voucher,
+        }
+    }
+
+    /// Makes an accepted reservation due for renewal.
+    fn renew_now(&mut self) {
+        if let Reservation::Accepted {
+            renewal_timeout, ..
+        } = self
+        {
+            renewal_timeout.reset(Duration::ZERO);
+        }
     }
 
     /// Marks the current reservation as failed.
@@ -713,10 +879,15 @@ impl Reservation {
 
     fn poll(
         &mut self,
+        renewal_paused: bool,
         cx: &mut Context<'_>,
     ) -> Poll<Option<SubstreamProtocol<outbound_hop::Upgrade, OutboundOpenInfo>>> {
         self.forward_messages_to_transport_listener(cx);
 
+        if renewal_paused {
+            return Poll::Pending;
+        }
+
         // Check renewal timeout if any.
         let (next_reservation, poll_val) = match std::mem::replace(self, Reservation::None) {
             Reservation::Accepted {
@@ -754,5 +925,7 @@ pub enum OutboundOpenInfo {
     },
     Connect {
         send_back: oneshot::Sender<Result<super::Connection, ()>>,
+        dst_peer_id: PeerId,
     },
+    Ping,
 }
@@ -0,0 +1,222 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Scoring of candidate relays, see [`Behaviour::add_relay_candidate`](super::Behaviour::add_relay_candidate).
+
+use libp2p_core::Multiaddr;
+use libp2p_identity::PeerId;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Latency and reliability of a candidate relay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelayScore {
+    address: Multiaddr,
+    rtt: Option<Duration>,
+    failed_pings: u32,
+    reservations_accepted: u32,
+    reservations_failed: u32,
+}
+
+impl RelayScore {
+    fn new(address: Multiaddr) -> Self {
+        Self {
+            address,
+            rtt: None,
+            failed_pings: 0,
+            reservations_accepted: 0,
+            reservations_failed: 0,
+        }
+    }
+
+    /// The address the relay is reached at.
+    pub fn address(&self) -> &Multiaddr {
+        &self.address
+    }
+
+    /// The round-trip time to the relay, smoothed over the pings sent with each new measurement
+    /// weighing a quarter.
+    ///
+    /// [`None`] if the relay has not been pinged yet or the last ping failed.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
+    /// The number of pings failed in a row.
+    pub fn failed_pings(&self) -> u32 {
+        self.failed_pings
+    }
+
+    /// The number of reservations, including renewals, accepted by the relay.
+    pub fn reservations_accepted(&self) -> u32 {
+        self.reservations_accepted
+    }
+
+    /// The number of reservations, including renewals, failed with the relay.
+    pub fn reservations_failed(&self) -> u32 {
+        self.reservations_failed
+    }
+
+    /// The estimated probability of a reservation with the relay to succeed.
+    ///
+    /// Starts at `0.5` for relays without any reservation attempts.
+    pub fn reliability(&self) -> f64 {
+        f64::from(self.reservations_accepted + 1)
+            / f64::from(self.reservations_accepted + self.reservations_failed + 2)
+    }
+
+    /// The round-trip time weighted by the [`RelayScore::reliability`], lower being better.
+    fn cost(&self) -> Option<f64> {
+        self.rtt.map(|rtt| rtt.as_secs_f64() / self.reliability())
+    }
+
+    fn cmp_cost(&self, other: &Self) -> Ordering {
+        match (self.cost(), other.cost()) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => other.reliability().total_cmp(&self.reliability()),
+        }
+    }
+}
+
+/// The candidate relays and their [`RelayScore`]s.
+#[derive(Debug, Default)]
+pub(crate) struct RelayCandidates {
+    scores: HashMap<PeerId, RelayScore>,
+}
+
+impl RelayCandidates {
+    pub(crate) fn insert(&mut self, peer_id: PeerId, address: Multiaddr) {
+        self.scores
+            .entry(peer_id)
+            .and_modify(|score| score.address = address.clone())
+            .or_insert_with(|| RelayScore::new(address));
+    }
+
+    pub(crate) fn remove(&mut self, peer_id: &PeerId) -> Option<RelayScore> {
+        self.scores.remove(peer_id)
+    }
+
+    pub(crate) fn get(&self, peer_id: &PeerId) -> Option<&RelayScore> {
+        self.scores.get(peer_id)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&PeerId, &RelayScore)> {
+        self.scores.iter()
+    }
+
+    pub(crate) fn on_ping(&mut self, peer_id: &PeerId, rtt: Option<Duration>) {
+        let score = match self.scores.get_mut(peer_id) {
+            Some(score) => score,
+            None => return,
+        };
+
+        match rtt {
+            Some(rtt) => {
+                score.failed_pings = 0;
+                score.rtt = Some(match score.rtt {
+                    Some(old) => (old * 3 + rtt) / 4,
+                    None => rtt,
+                });
+            }
+            None => {
+                score.failed_pings += 1;
+                score.rtt = None;
+            }
+        }
+    }
+
+    pub(crate) fn on_reservation(&mut self, peer_id: &PeerId, accepted: bool) {
+        if let Some(score) = self.scores.get_mut(peer_id) {
+            if accepted {
+                score.reservations_accepted += 1;
+            } else {
+                score.reservations_failed += 1;
+            }
+        }
+    }
+
+    /// Returns up to `n` candidates, best first.
+    pub(crate) fn select(&self, n: usize) -> Vec<(PeerId, Multiaddr)> {
+        let mut candidates = self.scores.iter().collect::<Vec<_>>();
+        candidates.sort_by(|(_, a), (_, b)| a.cmp_cost(b));
+
+        candidates
+            .into_iter()
+            .take(n)
+            .map(|(peer_id, score)| (*peer_id, score.address.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_low_latency_reliable_relays() {
+        let fast = PeerId::random();
+        let fast_unreliable = PeerId::random();
+        let slow = PeerId::random();
+        let unpinged = PeerId::random();
+
+        let mut candidates = RelayCandidates::default();
+        for peer_id in [fast, fast_unreliable, slow, unpinged] {
+            candidates.insert(peer_id, Multiaddr::empty());
+        }
+
+        candidates.on_ping(&fast, Some(Duration::from_millis(10)));
+        candidates.on_ping(&fast_unreliable, Some(Duration::from_millis(10)));
+        candidates.on_ping(&slow, Some(Duration::from_millis(100)));
+        for _ in 0..30 {
+            candidates.on_reservation(&fast_unreliable, false);
+        }
+        candidates.on_reservation(&fast, true);
+
+        let selected = candidates
+            .select(4)
+            .into_iter()
+            .map(|(peer_id, _)| peer_id)
+            .collect::<Vec<_>>();
+        assert_eq!(selected, vec![fast, slow, fast_unreliable, unpinged]);
+        assert_eq!(candidates.select(1).len(), 1);
+    }
+
+    #[test]
+    fn failed_ping_resets_rtt() {
+        let relay = PeerId::random();
+        let mut candidates = RelayCandidates::default();
+        candidates.insert(relay, Multiaddr::empty());
+
+        candidates.on_ping(&relay, Some(Duration::from_millis(100)));
+        candidates.on_ping(&relay, Some(Duration::from_millis(20)));
+        assert_eq!(
+            candidates.get(&relay).unwrap().rtt(),
+            Some(Duration::from_millis(80))
+        );
+
+        candidates.on_ping(&relay, None);
+        let score = candidates.get(&relay).unwrap();
+        assert_eq!(score.rtt(), None);
+        assert_eq!(score.failed_pings(), 1);
+    }
+}
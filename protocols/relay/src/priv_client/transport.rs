@@ -29,11 +29,14 @@ use futures::ready;
 use futures::sink::SinkExt;
 use futures::stream::SelectAll;
 use futures::stream::{Stream, StreamExt};
+use futures::task::AtomicWaker;
 use libp2p_core::multiaddr::{Multiaddr, Protocol};
 use libp2p_core::transport::{ListenerId, TransportError, TransportEvent};
 use libp2p_identity::PeerId;
 use std::collections::VecDeque;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::task::{Context, Poll};
 use thiserror::Error;
 
@@ -43,6 +46,15 @@ use thiserror::Error;
 /// an other transport to do the actual transmission of data. They should be combined through the
 /// [`OrTransport`](libp2p_core::transport::choice::OrTransport).
 ///
+/// Circuits are established over the connections the [`Swarm`](libp2p_swarm::Swarm) has to the
+/// relay, whichever transport these use, e.g. TCP, WebSocket or QUIC. The relayed [`Connection`]s
+/// themselves still need to be secured and multiplexed. Thus, when combining with a transport
+/// that does so on its own, like QUIC, upgrade the relay transport separately before combining:
+///
+/// ```text
+/// relay_transport.upgrade(..).authenticate(..).multiplex(..).or_transport(quic_transport)
+/// ```
+///
 /// Allows the local node to:
 ///
 /// 1. Establish relayed connections by dialing `/p2p-circuit` addresses.
@@ -91,18 +103,138 @@ pub struct Transport {
     to_behaviour: mpsc::Sender<TransportToBehaviourMsg>,
     pending_to_behaviour: VecDeque<TransportToBehaviourMsg>,
     listeners: SelectAll<Listener>,
+    /// Whether the addresses of the reservations are reported, set by the behaviour.
+    advertise: Arc<AdvertiseCircuitAddrs>,
+    /// Whether the addresses of the reservations were reported, as of the last poll.
+    advertised: bool,
+    /// Addresses reported or expired due to a change of `advertise`.
+    queued_events: VecDeque<<Listener as Stream>::Item>,
 }
 
 impl Transport {
-    pub(crate) fn new() -> (Self, mpsc::Receiver<TransportToBehaviourMsg>) {
+    pub(crate) fn new(
+        advertise: Arc<AdvertiseCircuitAddrs>,
+    ) -> (Self, mpsc::Receiver<TransportToBehaviourMsg>) {
         let (to_behaviour, from_transport) = mpsc::channel(0);
         let transport = Transport {
             to_behaviour,
             pending_to_behaviour: VecDeque::new(),
             listeners: SelectAll::new(),
+            advertised: advertise.get(),
+            advertise,
+            queued_events: VecDeque::new(),
         };
         (transport, from_transport)
     }
+
+    /// Reports or expires the addresses of all reservations if `advertise` changed.
+    ///
+    /// Addresses whose report or expiry is still queued are not reported to the swarm at all,
+    /// thus the queued event is dropped instead.
+    fn poll_advertise(&mut self, cx: &mut Context<'_>) {
+        self.advertise.waker.register(cx.waker());
+
+        let advertise = self.advertise.get();
+        if advertise == self.advertised {
+            return;
+        }
+        self.advertised = advertise;
+
+        for listener in self.listeners.iter_mut() {
+            listener.advertise = advertise;
+            if listener.is_closed {
+                continue;
+            }
+            for listen_addr in listener.addrs.iter().cloned() {
+                let listener_id = listener.listener_id;
+                // The pending opposite event of the address, if any, was not reported yet.
+                let pending_removed = if advertise {
+                    remove_queued_address_event(
+                        &mut self.queued_events,
+                        listener_id,
+                        &listen_addr,
+                        true,
+                    )
+                } else {
+                    remove_queued_address_event(
+                        &mut self.queued_events,
+                        listener_id,
+                        &listen_addr,
+                        false,
+                    ) | remove_queued_address_event(
+                        &mut listener.queued_events,
+                        listener_id,
+                        &listen_addr,
+                        false,
+                    )
+                };
+                if pending_removed {
+                    continue;
+                }
+                self.queued_events.push_back(if advertise {
+                    TransportEvent::NewAddress {
+                        listener_id,
+                        listen_addr,
+                    }
+                } else {
+                    TransportEvent::AddressExpired {
+                        listener_id,
+                        listen_addr,
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Removes the queued [`TransportEvent::AddressExpired`], if `expired`, or
+/// [`TransportEvent::NewAddress`] events of the given address, returning whether any was removed.
+fn remove_queued_address_event<TUpgr, TErr>(
+    queue: &mut VecDeque<TransportEvent<TUpgr, TErr>>,
+    listener_id: ListenerId,
+    listen_addr: &Multiaddr,
+    expired: bool,
+) -> bool {
+    let len = queue.len();
+    queue.retain(|event| match event {
+        TransportEvent::NewAddress {
+            listener_id: id,
+            listen_addr: addr,
+        } => expired || *id != listener_id || addr != listen_addr,
+        TransportEvent::AddressExpired {
+            listener_id: id,
+            listen_addr: addr,
+        } => !expired || *id != listener_id || addr != listen_addr,
+        _ => true,
+    });
+    queue.len() != len
+}
+
+/// Whether the [`Transport`] reports the addresses of reservations, i.e. the relayed addresses
+/// other peers can reach the local node at, see [`crate::client::Config::advertise_only_when_private`].
+#[derive(Debug, Default)]
+pub(crate) struct AdvertiseCircuitAddrs {
+    enabled: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl AdvertiseCircuitAddrs {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Self {
+            enabled: AtomicBool::new(enabled),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    pub(crate) fn get(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set(&self, enabled: bool) {
+        if self.enabled.swap(enabled, Ordering::SeqCst) != enabled {
+            self.waker.wake();
+        }
+    }
 }
 
 impl libp2p_core::Transport for Transport {
@@ -144,6 +276,8 @@ impl libp2p_core::Transport for Transport {
             queued_events: Default::default(),
             from_behaviour,
             is_closed: false,
+            addrs: Vec::new(),
+            advertise: self.advertised,
         };
         self.listeners.push(listener);
         Ok(listener_id)
@@ -215,7 +349,13 @@ impl libp2p_core::Transport for Transport {
     where
         Self: Sized,
     {
+        self.poll_advertise(cx);
+
         loop {
+            if let Some(event) = self.queued_events.pop_front() {
+                return Poll::Ready(event);
+            }
+
             if !self.pending_to_behaviour.is_empty() {
                 match self.to_behaviour.poll_ready(cx) {
                     Poll::Ready(Ok(())) => {
@@ -307,6 +447,10 @@ pub struct Listener {
     /// The listener can be closed either manually with [`Transport::remove_listener`](libp2p_core::Transport) or if
     /// the sender side of the `from_behaviour` channel is dropped.
     is_closed: bool,
+    /// The addresses of the current reservation.
+    addrs: Vec<Multiaddr>,
+    /// Whether the addresses of the reservation are reported.
+    advertise: bool,
 }
 
 impl Listener {
@@ -354,13 +498,29 @@ impl Stream for Listener {
                         self.queued_events.is_empty(),
                         "Assert empty due to previous `pop_front` attempt."
                     );
-                    // Returned as [`ListenerEvent::NewAddress`] in next iteration of loop.
-                    self.queued_events = addrs
+                    let stale_addrs = std::mem::replace(&mut self.addrs, addrs)
+                        .into_iter()
+                        .filter(|a| !self.addrs.contains(a))
+                        .collect::<Vec<_>>();
+                    if !self.advertise {
+                        continue;
+                    }
+                    // Addresses the relay no longer reports, e.g. after its addresses changed,
+                    // are expired. Returned as [`ListenerEvent::AddressExpired`] and
+                    // [`ListenerEvent::NewAddress`] in next iterations of loop.
+                    let listener_id = self.listener_id;
+                    self.queued_events = stale_addrs
                         .into_iter()
-                        .map(|listen_addr| TransportEvent::NewAddress {
-                            listener_id: self.listener_id,
+                        .map(|listen_addr| TransportEvent::AddressExpired {
+                            listener_id,
                             listen_addr,
                         })
+                        .chain(self.addrs.iter().cloned().map(|listen_addr| {
+                            TransportEvent::NewAddress {
+                                listener_id,
+                                listen_addr,
+                            }
+                        }))
                         .collect();
                 }
                 ToListenerMsg::IncomingRelayedConnection {
@@ -24,6 +24,7 @@ use std::time::Duration;
 pub mod inbound_hop;
 pub mod inbound_stop;
 pub mod outbound_hop;
+pub mod outbound_ping;
 pub mod outbound_stop;
 
 pub const HOP_PROTOCOL_NAME: &[u8; 31] = b"/libp2p/circuit/relay/0.2.0/hop";
@@ -20,6 +20,7 @@
 
 use crate::proto;
 use crate::protocol::{HOP_PROTOCOL_NAME, MAX_MESSAGE_SIZE};
+use crate::ReservationVoucher;
 use asynchronous_codec::{Framed, FramedParts};
 use bytes::Bytes;
 use futures::{future::BoxFuture, prelude::*};
@@ -34,7 +35,7 @@ use thiserror::Error;
 pub struct Upgrade {
     pub reservation_duration: Duration,
     pub max_circuit_duration: Duration,
-    pub max_circuit_bytes: u64,
+    pub max_circuit_bytes: Option<u64>,
 }
 
 impl upgrade::UpgradeInfo for Upgrade {
@@ -61,7 +62,7 @@ impl upgrade::InboundUpgrade<NegotiatedSubstream> for Upgrade {
             let proto::HopMessage {
                 type_pb,
                 peer,
-                reservation: _,
+                reservation,
                 limit: _,
                 status: _,
             } = substream
@@ -77,9 +78,21 @@ impl upgrade::InboundUpgrade<NegotiatedSubstream> for Upgrade {
                     max_circuit_bytes: self.max_circuit_bytes,
                 }),
                 proto::HopMessageType::CONNECT => {
-                    let dst = PeerId::from_bytes(&peer.ok_or(FatalUpgradeError::MissingPeer)?.id)
-                        .map_err(|_| FatalUpgradeError::ParsePeerId)?;
-                    Req::Connect(CircuitReq { dst, substream })
+                    let peer = peer.ok_or(FatalUpgradeError::MissingPeer)?;
+                    let dst =
+                        PeerId::from_bytes(&peer.id).map_err(|_| FatalUpgradeError::ParsePeerId)?;
+                    let dst_addrs = peer
+                        .addrs
+                        .into_iter()
+                        .filter_map(|a| Multiaddr::try_from(a).ok())
+                        .collect();
+                    let voucher = reservation.and_then(|r| r.voucher);
+                    Req::Connect(CircuitReq {
+                        dst,
+                        dst_addrs,
+                        voucher,
+                        substream,
+                    })
                 }
                 proto::HopMessageType::STATUS => {
                     return Err(FatalUpgradeError::UnexpectedTypeStatus.into())
@@ -129,11 +142,15 @@ pub struct ReservationReq {
     substream: Framed<NegotiatedSubstream, quick_protobuf_codec::Codec<proto::HopMessage>>,
     reservation_duration: Duration,
     max_circuit_duration: Duration,
-    max_circuit_bytes: u64,
+    max_circuit_bytes: Option<u64>,
 }
 
 impl ReservationReq {
-    pub async fn accept(self, addrs: Vec<Multiaddr>) -> Result<(), UpgradeError> {
+    pub async fn accept(
+        self,
+        addrs: Vec<Multiaddr>,
+        voucher: Option<Box<ReservationVoucher>>,
+    ) -> Result<(), UpgradeError> {
         let msg = proto::HopMessage {
             type_pb: proto::HopMessageType::STATUS,
             peer: None,
@@ -143,7 +160,7 @@ impl ReservationReq {
                     .duration_since(SystemTime::UNIX_EPOCH)
                     .unwrap()
                     .as_secs(),
-                voucher: None,
+                voucher: voucher.map(|v| v.to_bytes()),
             }),
             limit: Some(proto::Limit {
                 duration: Some(
@@ -152,7 +169,7 @@ impl ReservationReq {
                         .try_into()
                         .expect("`max_circuit_duration` not to exceed `u32::MAX`."),
                 ),
-                data: Some(self.max_circuit_bytes),
+                data: self.max_circuit_bytes,
             }),
             status: Some(proto::Status::OK),
         };
@@ -183,6 +200,8 @@ impl ReservationReq {
 
 pub struct CircuitReq {
     dst: PeerId,
+    dst_addrs: Vec<Multiaddr>,
+    voucher: Option<Vec<u8>>,
     substream: Framed<NegotiatedSubstream, quick_protobuf_codec::Codec<proto::HopMessage>>,
 }
 
@@ -191,6 +210,16 @@ impl CircuitReq {
         self.dst
     }
 
+    /// The addresses of the destination announced by the source, if any.
+    pub fn dst_addrs(&self) -> &[Multiaddr] {
+        &self.dst_addrs
+    }
+
+    /// The encoded reservation voucher of the destination, if provided by the source.
+    pub fn voucher(&self) -> Option<&[u8]> {
+        self.voucher.as_deref()
+    }
+
     pub async fn accept(mut self) -> Result<(NegotiatedSubstream, Bytes), UpgradeError> {
         let msg = proto::HopMessage {
             type_pb: proto::HopMessageType::STATUS,
@@ -20,6 +20,7 @@
 
 use crate::proto;
 use crate::protocol::{Limit, HOP_PROTOCOL_NAME, MAX_MESSAGE_SIZE};
+use crate::ReservationVoucher;
 use asynchronous_codec::{Framed, FramedParts};
 use bytes::Bytes;
 use futures::{future::BoxFuture, prelude::*};
@@ -34,7 +35,11 @@ use thiserror::Error;
 
 pub enum Upgrade {
     Reserve,
-    Connect { dst_peer_id: PeerId },
+    Connect {
+        dst_peer_id: PeerId,
+        /// The reservation voucher of the destination, presented to the relay.
+        voucher: Option<Box<ReservationVoucher>>,
+    },
 }
 
 impl upgrade::UpgradeInfo for Upgrade {
@@ -60,13 +65,24 @@ impl upgrade::OutboundUpgrade<NegotiatedSubstream> for Upgrade {
                 limit: None,
                 status: None,
             },
-            Upgrade::Connect { dst_peer_id } => proto::HopMessage {
+            Upgrade::Connect {
+                dst_peer_id,
+                ref voucher,
+            } => proto::HopMessage {
                 type_pb: proto::HopMessageType::CONNECT,
                 peer: Some(proto::Peer {
                     id: dst_peer_id.to_bytes(),
                     addrs: vec![],
                 }),
-                reservation: None,
+                reservation: voucher.as_ref().map(|v| proto::Reservation {
+                    expire: v
+                        .expiration()
+                        .duration_since(SystemTime::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs(),
+                    addrs: vec![],
+                    voucher: Some(v.to_bytes()),
+                }),
                 limit: None,
                 status: None,
             },
@@ -145,12 +161,25 @@ impl upgrade::OutboundUpgrade<NegotiatedSubstream> for Upgrade {
                         .map(Delay::new)
                         .ok_or(FatalUpgradeError::InvalidReservationExpiration)?;
 
+                    let voucher = match reservation
+                        .voucher
+                        .map(|v| ReservationVoucher::from_bytes(&v))
+                    {
+                        Some(Ok(voucher)) => Some(Box::new(voucher)),
+                        Some(Err(e)) => {
+                            log::debug!("Ignoring invalid reservation voucher: {e}");
+                            None
+                        }
+                        None => None,
+                    };
+
                     substream.close().await?;
 
                     Output::Reservation {
                         renewal_timeout,
                         addrs,
                         limit,
+                        voucher,
                     }
                 }
                 Upgrade::Connect { .. } => {
@@ -267,6 +296,7 @@ pub enum Output {
         renewal_timeout: Delay,
         addrs: Vec<Multiaddr>,
         limit: Option<Limit>,
+        voucher: Option<Box<ReservationVoucher>>,
     },
     Circuit {
         substream: NegotiatedSubstream,
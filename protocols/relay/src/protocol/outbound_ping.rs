@@ -0,0 +1,113 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Measuring the round-trip time to a relay via the [ping protocol].
+//!
+//! [ping protocol]: https://github.com/libp2p/specs/blob/master/ping/ping.md
+
+use futures::prelude::*;
+use instant::Instant;
+use rand::{distributions, prelude::*};
+use std::{io, time::Duration};
+
+pub const PROTOCOL_NAME: &[u8] = b"/ipfs/ping/1.0.0";
+
+const PING_SIZE: usize = 32;
+
+/// Sends a single ping over the given stream, returning the round-trip time.
+///
+/// The round-trip time includes sending the ping, such that a relay slow to accept our data is
+/// not mistaken for a fast one.
+pub async fn send_ping<S>(mut stream: S) -> io::Result<Duration>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let payload: [u8; PING_SIZE] = thread_rng().sample(distributions::Standard);
+    let started = Instant::now();
+    stream.write_all(&payload).await?;
+    stream.flush().await?;
+    let mut recv_payload = [0u8; PING_SIZE];
+    stream.read_exact(&mut recv_payload).await?;
+    if recv_payload != payload {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Ping payload mismatch",
+        ));
+    }
+
+    Ok(started.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::io::Cursor;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    /// Echoes the written ping, taking [`SlowEcho::delay`] to flush it.
+    struct SlowEcho {
+        echo: Cursor<Vec<u8>>,
+        delay: Duration,
+    }
+
+    impl AsyncWrite for SlowEcho {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.echo.get_mut().extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            std::thread::sleep(self.delay);
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncRead for SlowEcho {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.echo).poll_read(cx, buf)
+        }
+    }
+
+    #[test]
+    fn rtt_includes_sending_the_ping() {
+        let delay = Duration::from_millis(50);
+        let stream = SlowEcho {
+            echo: Cursor::new(Vec::new()),
+            delay,
+        };
+
+        let rtt = futures::executor::block_on(send_ping(stream)).unwrap();
+
+        assert!(rtt >= delay);
+    }
+}
@@ -23,7 +23,7 @@ use crate::protocol::{MAX_MESSAGE_SIZE, STOP_PROTOCOL_NAME};
 use asynchronous_codec::{Framed, FramedParts};
 use bytes::Bytes;
 use futures::{future::BoxFuture, prelude::*};
-use libp2p_core::upgrade;
+use libp2p_core::{upgrade, Multiaddr};
 use libp2p_identity::PeerId;
 use libp2p_swarm::NegotiatedSubstream;
 use std::convert::TryInto;
@@ -32,9 +32,10 @@ use std::time::Duration;
 use thiserror::Error;
 
 pub struct Upgrade {
-    pub relay_peer_id: PeerId,
+    pub src_peer_id: PeerId,
+    pub src_addrs: Vec<Multiaddr>,
     pub max_circuit_duration: Duration,
-    pub max_circuit_bytes: u64,
+    pub max_circuit_bytes: Option<u64>,
 }
 
 impl upgrade::UpgradeInfo for Upgrade {
@@ -55,8 +56,8 @@ impl upgrade::OutboundUpgrade<NegotiatedSubstream> for Upgrade {
         let msg = proto::StopMessage {
             type_pb: proto::StopMessageType::CONNECT,
             peer: Some(proto::Peer {
-                id: self.relay_peer_id.to_bytes(),
-                addrs: vec![],
+                id: self.src_peer_id.to_bytes(),
+                addrs: self.src_addrs.iter().map(|a| a.to_vec()).collect(),
             }),
             limit: Some(proto::Limit {
                 duration: Some(
@@ -65,7 +66,7 @@ impl upgrade::OutboundUpgrade<NegotiatedSubstream> for Upgrade {
                         .try_into()
                         .expect("`max_circuit_duration` not to exceed `u32::MAX`."),
                 ),
-                data: Some(self.max_circuit_bytes),
+                data: self.max_circuit_bytes,
             }),
             status: None,
         };
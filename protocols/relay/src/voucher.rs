@@ -0,0 +1,199 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Reservation vouchers, proving that a peer holds a reservation with a relay.
+//!
+//! A voucher is a [`SignedEnvelope`] issued by the relay when accepting a reservation. See
+//! [`crate::Config::require_circuit_voucher`] for relays only accepting circuits presenting the
+//! voucher of the destination.
+
+use crate::proto;
+use libp2p_core::signed_envelope::{self, SignedEnvelope};
+use libp2p_identity::{Keypair, PeerId, SigningError};
+use quick_protobuf::{BytesReader, MessageRead, MessageWrite, Writer};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DOMAIN_SEP: &str = "libp2p-relay-rsvp";
+const PAYLOAD_TYPE: &[u8] = &[0x03, 0x02];
+
+/// A reservation voucher, signed by the relay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReservationVoucher {
+    relay: PeerId,
+    peer: PeerId,
+    expiration: SystemTime,
+    envelope: SignedEnvelope,
+}
+
+impl ReservationVoucher {
+    /// Issues a voucher for the reservation of `peer`, signed with the relay's identity `key`.
+    pub fn new(key: &Keypair, peer: PeerId, expiration: SystemTime) -> Result<Self, SigningError> {
+        let relay = key.public().to_peer_id();
+        let expiration_secs = expiration
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let payload = {
+            let voucher = proto::ReservationVoucher {
+                relay: relay.to_bytes(),
+                peer: peer.to_bytes(),
+                expiration: expiration_secs,
+            };
+
+            let mut buf = Vec::with_capacity(voucher.get_size());
+            let mut writer = Writer::new(&mut buf);
+            voucher
+                .write_message(&mut writer)
+                .expect("Encoding to succeed");
+
+            buf
+        };
+
+        let envelope = SignedEnvelope::new(
+            key,
+            String::from(DOMAIN_SEP),
+            PAYLOAD_TYPE.to_vec(),
+            payload,
+        )?;
+
+        Ok(Self {
+            relay,
+            peer,
+            expiration: UNIX_EPOCH + Duration::from_secs(expiration_secs),
+            envelope,
+        })
+    }
+
+    /// Decodes a voucher from its signed envelope, verifying it is signed by its relay.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, VoucherError> {
+        let envelope = SignedEnvelope::from_protobuf_encoding(bytes)?;
+        let (payload, signing_key) =
+            envelope.payload_and_signing_key(String::from(DOMAIN_SEP), PAYLOAD_TYPE)?;
+        let mut reader = BytesReader::from_bytes(payload);
+        let voucher = proto::ReservationVoucher::from_reader(&mut reader, payload)?;
+
+        let relay = PeerId::from_bytes(&voucher.relay)?;
+        let peer = PeerId::from_bytes(&voucher.peer)?;
+
+        if relay != signing_key.to_peer_id() {
+            return Err(VoucherError::MismatchedSignature);
+        }
+
+        Ok(Self {
+            relay,
+            peer,
+            expiration: UNIX_EPOCH + Duration::from_secs(voucher.expiration),
+            envelope,
+        })
+    }
+
+    /// Encodes the voucher as signed envelope.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.envelope.clone().into_protobuf_encoding()
+    }
+
+    /// The relay that issued the voucher.
+    pub fn relay(&self) -> PeerId {
+        self.relay
+    }
+
+    /// The peer holding the reservation.
+    pub fn peer(&self) -> PeerId {
+        self.peer
+    }
+
+    /// When the reservation, and thus the voucher, expires.
+    pub fn expiration(&self) -> SystemTime {
+        self.expiration
+    }
+
+    /// Whether the voucher has expired.
+    pub fn is_expired(&self) -> bool {
+        self.expiration <= SystemTime::now()
+    }
+}
+
+/// Errors that occur whilst decoding a [`ReservationVoucher`].
+#[derive(thiserror::Error, Debug)]
+pub enum VoucherError {
+    /// Failed to decode the signed envelope.
+    #[error("Failed to decode envelope")]
+    BadEnvelope(#[from] signed_envelope::DecodingError),
+    /// Failed to extract the payload from the envelope.
+    #[error("Failed to extract payload from envelope")]
+    BadPayload(#[from] signed_envelope::ReadPayloadError),
+    /// Failed to decode the payload as a voucher.
+    #[error("Failed to decode bytes as ReservationVoucher")]
+    InvalidVoucher(#[from] quick_protobuf::Error),
+    /// Failed to decode a peer ID.
+    #[error("Failed to decode bytes as PeerId")]
+    InvalidPeerId(#[from] libp2p_core::multihash::Error),
+    /// The voucher is not signed by its relay.
+    #[error("The signer of the envelope is different than the relay of the voucher")]
+    MismatchedSignature,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let relay = Keypair::generate_ed25519();
+        let peer = PeerId::random();
+        let expiration = SystemTime::now() + Duration::from_secs(60);
+
+        let voucher = ReservationVoucher::new(&relay, peer, expiration).unwrap();
+        let decoded = ReservationVoucher::from_bytes(&voucher.to_bytes()).unwrap();
+
+        assert_eq!(decoded, voucher);
+        assert_eq!(decoded.relay(), relay.public().to_peer_id());
+        assert_eq!(decoded.peer(), peer);
+        assert!(!decoded.is_expired());
+    }
+
+    #[test]
+    fn rejects_voucher_not_signed_by_relay() {
+        let signer = Keypair::generate_ed25519();
+        let payload = {
+            let voucher = proto::ReservationVoucher {
+                relay: PeerId::random().to_bytes(),
+                peer: PeerId::random().to_bytes(),
+                expiration: u64::MAX,
+            };
+            let mut buf = Vec::new();
+            voucher.write_message(&mut Writer::new(&mut buf)).unwrap();
+            buf
+        };
+        let envelope = SignedEnvelope::new(
+            &signer,
+            String::from(DOMAIN_SEP),
+            PAYLOAD_TYPE.to_vec(),
+            payload,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ReservationVoucher::from_bytes(&envelope.into_protobuf_encoding()),
+            Err(VoucherError::MismatchedSignature)
+        ));
+    }
+}
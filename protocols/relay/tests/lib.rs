@@ -19,10 +19,10 @@
 // DEALINGS IN THE SOFTWARE.
 
 use futures::executor::LocalPool;
-use futures::future::FutureExt;
+use futures::future::{self, FutureExt};
 use futures::io::{AsyncRead, AsyncWrite};
 use futures::stream::StreamExt;
-use futures::task::Spawn;
+use futures::task::{ArcWake, Spawn};
 use libp2p_core::multiaddr::{Multiaddr, Protocol};
 use libp2p_core::muxing::StreamMuxerBox;
 use libp2p_core::transport::choice::OrTransport;
@@ -34,8 +34,15 @@ use libp2p_identity::PublicKey;
 use libp2p_ping as ping;
 use libp2p_plaintext::PlainText2Config;
 use libp2p_relay as relay;
-use libp2p_swarm::{AddressScore, NetworkBehaviour, Swarm, SwarmBuilder, SwarmEvent};
-use std::time::Duration;
+use libp2p_relay::reservation_store::{PersistedReservation, ReservationStore};
+use libp2p_swarm::{
+    keep_alive, AddressScore, ConnectionHandlerUpgrErr, FromSwarm, ManualClock, NatStatus,
+    NetworkBehaviour, PeerListenAddrsChanged, Swarm, SwarmBuilder, SwarmEvent,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
 
 #[test]
 fn reservation() {
@@ -80,101 +87,987 @@ fn reservation() {
     ));
 }
 
+#[test]
+fn reservation_held_back_while_disabled() {
+    let _ = env_logger::try_init();
+    let mut pool = LocalPool::new();
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay();
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone(), AddressScore::Infinite);
+    spawn_swarm_on_pool(&pool, relay);
+
+    let client_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit);
+    let mut client = build_client();
+    let client_peer_id = *client.local_peer_id();
+
+    client.behaviour_mut().relay.set_reservations_enabled(false);
+    client.listen_on(client_addr.clone()).unwrap();
+
+    // The relay is not even dialed while reservations are disabled.
+    pool.run_until_stalled();
+    let woken = Arc::new(WakeFlag::default());
+    let waker = futures::task::waker(woken.clone());
+    loop {
+        woken.0.store(false, Ordering::SeqCst);
+        assert!(client
+            .poll_next_unpin(&mut Context::from_waker(&waker))
+            .is_pending());
+        if !woken.0.load(Ordering::SeqCst) {
+            break;
+        }
+    }
+
+    // Enabling reservations wakes the task to process the held back reservation request.
+    client.behaviour_mut().relay.set_reservations_enabled(true);
+    assert!(woken.0.load(Ordering::SeqCst));
+
+    assert!(pool.run_until(wait_for_dial(&mut client, relay_peer_id)));
+    pool.run_until(wait_for_reservation(
+        &mut client,
+        client_addr.with(Protocol::P2p(client_peer_id.into())),
+        relay_peer_id,
+        false, // No renewal.
+    ));
+}
+
 #[test]
 fn new_reservation_to_same_relay_replaces_old() {
     let _ = env_logger::try_init();
     let mut pool = LocalPool::new();
 
     let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
-    let mut relay = build_relay();
+    let mut relay = build_relay();
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone(), AddressScore::Infinite);
+    spawn_swarm_on_pool(&pool, relay);
+
+    let mut client = build_client();
+    let client_peer_id = *client.local_peer_id();
+    let client_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit);
+    let client_addr_with_peer_id = client_addr
+        .clone()
+        .with(Protocol::P2p(client_peer_id.into()));
+
+    let old_listener = client.listen_on(client_addr.clone()).unwrap();
+
+    // Wait for connection to relay.
+    assert!(pool.run_until(wait_for_dial(&mut client, relay_peer_id)));
+
+    // Wait for first (old) reservation.
+    pool.run_until(wait_for_reservation(
+        &mut client,
+        client_addr_with_peer_id.clone(),
+        relay_peer_id,
+        false, // No renewal.
+    ));
+
+    // Trigger new reservation.
+    let new_listener = client.listen_on(client_addr).unwrap();
+
+    // Wait for
+    // - listener of old reservation to close
+    // - new reservation to be accepted
+    // - new listener address to be reported
+    pool.run_until(async {
+        let mut old_listener_closed = false;
+        let mut new_reservation_accepted = false;
+        let mut new_listener_address_reported = false;
+        loop {
+            match client.select_next_some().await {
+                SwarmEvent::ListenerClosed {
+                    addresses,
+                    listener_id,
+                    ..
+                } => {
+                    assert_eq!(addresses, vec![client_addr_with_peer_id.clone()]);
+                    assert_eq!(listener_id, old_listener);
+
+                    old_listener_closed = true;
+                    if new_reservation_accepted && new_listener_address_reported {
+                        break;
+                    }
+                }
+                SwarmEvent::Behaviour(ClientEvent::Relay(
+                    relay::client::Event::ReservationReqAccepted {
+                        relay_peer_id: peer_id,
+                        ..
+                    },
+                )) => {
+                    assert_eq!(relay_peer_id, peer_id);
+
+                    new_reservation_accepted = true;
+                    if old_listener_closed && new_listener_address_reported {
+                        break;
+                    }
+                }
+                SwarmEvent::NewListenAddr {
+                    address,
+                    listener_id,
+                } => {
+                    assert_eq!(address, client_addr_with_peer_id);
+                    assert_eq!(listener_id, new_listener);
+
+                    new_listener_address_reported = true;
+                    if old_listener_closed && new_reservation_accepted {
+                        break;
+                    }
+                }
+                SwarmEvent::Behaviour(ClientEvent::Ping(_)) => {}
+                e => panic!("{e:?}"),
+            }
+        }
+    });
+}
+
+#[test]
+fn connect() {
+    connect_via_relay(
+        Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>())),
+        memory_transport,
+    );
+}
+
+#[test]
+fn connect_via_relay_over_quic() {
+    connect_via_relay(
+        "/ip4/127.0.0.1/udp/0/quic-v1".parse().unwrap(),
+        quic_transport,
+    );
+}
+
+#[test]
+fn connect_via_relay_over_websocket() {
+    connect_via_relay(
+        "/ip4/127.0.0.1/tcp/0/ws".parse().unwrap(),
+        websocket_transport,
+    );
+}
+
+/// Connects two clients via a relay listening on `relay_listen_addr`, all of them using the given
+/// transport to connect to the relay.
+fn connect_via_relay(
+    relay_listen_addr: Multiaddr,
+    base_transport: fn(&identity::Keypair) -> Boxed<(PeerId, StreamMuxerBox)>,
+) {
+    let _ = env_logger::try_init();
+    let mut pool = LocalPool::new();
+
+    let mut relay = build_relay_with_transport(Default::default(), base_transport);
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_listen_addr).unwrap();
+    let relay_addr = pool.run_until(async {
+        loop {
+            if let SwarmEvent::NewListenAddr { address, .. } = relay.select_next_some().await {
+                break address;
+            }
+        }
+    });
+    relay.add_external_address(relay_addr.clone(), AddressScore::Infinite);
+    spawn_swarm_on_pool(&pool, relay);
+
+    let mut dst = build_client_with_transport(base_transport);
+    let dst_peer_id = *dst.local_peer_id();
+    let dst_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit)
+        .with(Protocol::P2p(dst_peer_id.into()));
+
+    dst.listen_on(dst_addr.clone()).unwrap();
+
+    assert!(pool.run_until(wait_for_dial(&mut dst, relay_peer_id)));
+
+    pool.run_until(wait_for_reservation(
+        &mut dst,
+        dst_addr.clone(),
+        relay_peer_id,
+        false, // No renewal.
+    ));
+    spawn_swarm_on_pool(&pool, dst);
+
+    let mut src = build_client_with_transport(base_transport);
+
+    src.dial(dst_addr).unwrap();
+
+    pool.run_until(async {
+        loop {
+            match src.select_next_some().await {
+                SwarmEvent::Dialing(peer_id) if peer_id == relay_peer_id => {}
+                SwarmEvent::ConnectionEstablished { peer_id, .. } if peer_id == relay_peer_id => {}
+                SwarmEvent::Behaviour(ClientEvent::Ping(ping::Event { peer, .. }))
+                    if peer == dst_peer_id =>
+                {
+                    break
+                }
+                SwarmEvent::Behaviour(ClientEvent::Relay(
+                    relay::client::Event::OutboundCircuitEstablished { .. },
+                )) => {}
+                SwarmEvent::Behaviour(ClientEvent::Ping(ping::Event { peer, .. }))
+                    if peer == relay_peer_id => {}
+                SwarmEvent::ConnectionEstablished { peer_id, .. } if peer_id == dst_peer_id => {
+                    break
+                }
+                e => panic!("{e:?}"),
+            }
+        }
+    })
+}
+
+#[test]
+fn report_circuit_stats() {
+    let _ = env_logger::try_init();
+    let mut pool = LocalPool::new();
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay();
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone(), AddressScore::Infinite);
+    spawn_swarm_on_pool(&pool, relay);
+
+    let mut dst = build_client();
+    let dst_peer_id = *dst.local_peer_id();
+    let dst_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit)
+        .with(Protocol::P2p(dst_peer_id.into()));
+
+    dst.listen_on(dst_addr.clone()).unwrap();
+
+    assert!(pool.run_until(wait_for_dial(&mut dst, relay_peer_id)));
+    pool.run_until(wait_for_reservation(
+        &mut dst,
+        dst_addr.clone(),
+        relay_peer_id,
+        false, // No renewal.
+    ));
+    spawn_swarm_on_pool(&pool, dst);
+
+    let mut src = build_keep_alive_client_with_config(relay::client::Config {
+        circuit_stats_interval: Some(Duration::from_millis(100)),
+        ..Default::default()
+    });
+    src.dial(dst_addr).unwrap();
+
+    // Wait for data to have been exchanged over the circuit in both directions.
+    let stats = pool.run_until(async {
+        loop {
+            if let SwarmEvent::Behaviour(KeepAliveClientEvent::Relay(
+                relay::client::Event::CircuitStatsReported { stats },
+            )) = src.select_next_some().await
+            {
+                if stats.bytes_read > 0 && stats.bytes_written > 0 {
+                    break stats;
+                }
+            }
+        }
+    });
+
+    assert_eq!(stats.relay_peer_id, relay_peer_id);
+    assert_eq!(stats.remote_peer_id, dst_peer_id);
+    assert_eq!(stats.endpoint, libp2p_core::Endpoint::Dialer);
+
+    let current = src.behaviour_mut().relay.circuit_stats();
+    assert_eq!(current.len(), 1);
+    assert!(current[0].bytes_written >= stats.bytes_written);
+    assert!(current[0].duration >= stats.duration);
+}
+
+#[test]
+fn deny_inbound_circuit_by_policy() {
+    let _ = env_logger::try_init();
+    let mut pool = LocalPool::new();
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay();
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone(), AddressScore::Infinite);
+    spawn_swarm_on_pool(&pool, relay);
+
+    let mut dst = build_client_with_config(relay::client::Config {
+        inbound_circuit_policy: Some(Arc::new(|_, _, _: &Multiaddr| false)),
+        ..Default::default()
+    });
+    let dst_peer_id = *dst.local_peer_id();
+    let dst_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit)
+        .with(Protocol::P2p(dst_peer_id.into()));
+
+    dst.listen_on(dst_addr.clone()).unwrap();
+
+    assert!(pool.run_until(wait_for_dial(&mut dst, relay_peer_id)));
+
+    pool.run_until(wait_for_reservation(
+        &mut dst,
+        dst_addr.clone(),
+        relay_peer_id,
+        false, // No renewal.
+    ));
+
+    let mut src = build_client();
+    let src_peer_id = *src.local_peer_id();
+
+    src.dial(dst_addr).unwrap();
+    spawn_swarm_on_pool(&pool, src);
+
+    pool.run_until(async {
+        loop {
+            match dst.select_next_some().await {
+                SwarmEvent::Behaviour(ClientEvent::Relay(
+                    relay::client::Event::InboundCircuitReqDenied { src_peer_id: peer },
+                )) if peer == src_peer_id => break,
+                SwarmEvent::Behaviour(ClientEvent::Ping(_)) => {}
+                e => panic!("{e:?}"),
+            }
+        }
+    });
+}
+
+#[test]
+fn stop_request_to_unresponsive_destination_times_out() {
+    let _ = env_logger::try_init();
+    let mut pool = LocalPool::new();
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay_with_config(relay::Config {
+        stop_timeout: Duration::from_secs(1),
+        ..Default::default()
+    });
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone(), AddressScore::Infinite);
+
+    let mut dst = build_client();
+    let dst_peer_id = *dst.local_peer_id();
+    let dst_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit)
+        .with(Protocol::P2p(dst_peer_id.into()));
+
+    dst.listen_on(dst_addr.clone()).unwrap();
+
+    pool.run_until(async {
+        let reservation = async {
+            assert!(wait_for_dial(&mut dst, relay_peer_id).await);
+            wait_for_reservation(&mut dst, dst_addr.clone(), relay_peer_id, false).await;
+        };
+        let relay_events = relay.by_ref().for_each(|_| future::ready(()));
+        futures::pin_mut!(reservation);
+        future::select(reservation, relay_events).await;
+    });
+
+    // From now on the destination is no longer polled, thus never answers the STOP request.
+    let mut src = build_client();
+    let src_peer_id = *src.local_peer_id();
+
+    src.dial(dst_addr).unwrap();
+
+    pool.run_until(async {
+        let mut stop_timed_out = false;
+        let mut dial_failed = false;
+
+        while !(stop_timed_out && dial_failed) {
+            match future::select(relay.select_next_some(), src.select_next_some()).await {
+                future::Either::Left((
+                    SwarmEvent::Behaviour(RelayEvent::Relay(
+                        relay::Event::CircuitReqOutboundConnectFailed {
+                            src_peer_id: peer,
+                            dst_peer_id: dst,
+                            error: ConnectionHandlerUpgrErr::Timeout,
+                        },
+                    )),
+                    _,
+                )) => {
+                    assert_eq!(peer, src_peer_id);
+                    assert_eq!(dst, dst_peer_id);
+                    stop_timed_out = true;
+                }
+                future::Either::Right((
+                    SwarmEvent::OutgoingConnectionError {
+                        peer_id: Some(peer),
+                        ..
+                    },
+                    _,
+                )) if peer == dst_peer_id => {
+                    dial_failed = true;
+                }
+                _ => {}
+            }
+        }
+    });
+}
+
+#[test]
+fn require_reservation_voucher_for_circuit() {
+    let _ = env_logger::try_init();
+    let mut pool = LocalPool::new();
+
+    let relay_key = identity::Keypair::generate_ed25519();
+    let relay_peer_id = relay_key.public().to_peer_id();
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = SwarmBuilder::with_async_std_executor(
+        memory_transport(&relay_key),
+        Relay {
+            ping: ping::Behaviour::new(ping::Config::new()),
+            relay: relay::Behaviour::new(
+                relay_peer_id,
+                relay::Config {
+                    voucher_keypair: Some(relay_key.clone()),
+                    require_circuit_voucher: true,
+                    ..Default::default()
+                },
+            ),
+        },
+        relay_peer_id,
+    )
+    .build();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone(), AddressScore::Infinite);
+    spawn_swarm_on_pool(&pool, relay);
+
+    let mut dst = build_client();
+    let dst_peer_id = *dst.local_peer_id();
+    let dst_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit)
+        .with(Protocol::P2p(dst_peer_id.into()));
+
+    dst.listen_on(dst_addr.clone()).unwrap();
+
+    assert!(pool.run_until(wait_for_dial(&mut dst, relay_peer_id)));
+    pool.run_until(wait_for_reservation(
+        &mut dst,
+        dst_addr.clone(),
+        relay_peer_id,
+        false, // No renewal.
+    ));
+
+    let voucher = dst
+        .behaviour()
+        .relay
+        .reservation_voucher(&relay_peer_id)
+        .cloned()
+        .expect("relay to issue a voucher");
+    assert_eq!(voucher.relay(), relay_peer_id);
+    assert_eq!(voucher.peer(), dst_peer_id);
+    spawn_swarm_on_pool(&pool, dst);
+
+    let mut src = build_client();
+
+    // Without a voucher the relay denies the circuit.
+    src.dial(dst_addr.clone()).unwrap();
+    pool.run_until(async {
+        loop {
+            match src.select_next_some().await {
+                SwarmEvent::Behaviour(ClientEvent::Relay(
+                    relay::client::Event::OutboundCircuitReqFailed {
+                        relay_peer_id: peer,
+                        ..
+                    },
+                )) if peer == relay_peer_id => break,
+                SwarmEvent::ConnectionEstablished { peer_id, .. } if peer_id == dst_peer_id => {
+                    panic!("Expected circuit to be denied")
+                }
+                _ => {}
+            }
+        }
+    });
+
+    // With the voucher of the destination the circuit is established.
+    src.behaviour_mut().relay.add_circuit_voucher(voucher);
+    src.dial(dst_addr).unwrap();
+    pool.run_until(async {
+        loop {
+            match src.select_next_some().await {
+                SwarmEvent::ConnectionEstablished { peer_id, .. } if peer_id == dst_peer_id => {
+                    break
+                }
+                SwarmEvent::Behaviour(ClientEvent::Relay(
+                    relay::client::Event::OutboundCircuitReqFailed { error, .. },
+                )) => panic!("{error:?}"),
+                _ => {}
+            }
+        }
+    });
+}
+
+#[test]
+fn deny_circuits_of_peer_exceeding_byte_quota_until_reset() {
+    let _ = env_logger::try_init();
+    let mut pool = LocalPool::new();
+
+    let peer_byte_quota_interval = Duration::from_secs(60);
+    let clock = ManualClock::new();
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay_with_config(relay::Config {
+        unlimited_circuit_bytes: true,
+        peer_byte_quota: Some(1),
+        peer_byte_quota_interval,
+        clock: Arc::new(clock.clone()),
+        ..Default::default()
+    });
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone(), AddressScore::Infinite);
+
+    let mut dst = build_client();
+    let dst_peer_id = *dst.local_peer_id();
+    let dst_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit)
+        .with(Protocol::P2p(dst_peer_id.into()));
+
+    dst.listen_on(dst_addr.clone()).unwrap();
+
+    pool.run_until(async {
+        loop {
+            if let future::Either::Left((
+                SwarmEvent::Behaviour(RelayEvent::Relay(relay::Event::ReservationReqAccepted {
+                    ..
+                })),
+                _,
+            )) = future::select(relay.select_next_some(), dst.select_next_some()).await
+            {
+                break;
+            }
+        }
+    });
+    spawn_swarm_on_pool(&pool, dst);
+
+    let mut src = build_client();
+    let src_peer_id = *src.local_peer_id();
+
+    // Waits for the next circuit event of the source at the relay, while driving the source.
+    let mut next_circuit_event = |relay: &mut Swarm<Relay>, src: &mut Swarm<Client>| {
+        pool.run_until(async {
+            loop {
+                if let future::Either::Left((SwarmEvent::Behaviour(RelayEvent::Relay(event)), _)) =
+                    future::select(relay.select_next_some(), src.select_next_some()).await
+                {
+                    match event {
+                        relay::Event::CircuitReqAccepted {
+                            src_peer_id: peer, ..
+                        }
+                        | relay::Event::CircuitReqDenied {
+                            src_peer_id: peer, ..
+                        }
+                        | relay::Event::CircuitClosed {
+                            src_peer_id: peer, ..
+                        } if peer == src_peer_id => break event,
+                        _ => {}
+                    }
+                }
+            }
+        })
+    };
+
+    // The first circuit is accepted and closed once it exhausted the quota of the source.
+    src.dial(dst_addr.clone()).unwrap();
+    let event = next_circuit_event(&mut relay, &mut src);
+    assert!(
+        matches!(event, relay::Event::CircuitReqAccepted { .. }),
+        "{event:?}"
+    );
+    let event = next_circuit_event(&mut relay, &mut src);
+    assert!(
+        matches!(event, relay::Event::CircuitClosed { .. }),
+        "{event:?}"
+    );
+    assert!(relay.behaviour().relay.peer_bytes_relayed(&src_peer_id) >= 1);
+
+    // Further circuits are denied until the quota is reset.
+    src.dial(dst_addr.clone()).unwrap();
+    let event = next_circuit_event(&mut relay, &mut src);
+    assert!(
+        matches!(event, relay::Event::CircuitReqDenied { .. }),
+        "{event:?}"
+    );
+
+    clock.advance(peer_byte_quota_interval);
+    src.dial(dst_addr).unwrap();
+    let event = next_circuit_event(&mut relay, &mut src);
+    assert!(
+        matches!(event, relay::Event::CircuitReqAccepted { .. }),
+        "{event:?}"
+    );
+}
+
+#[test]
+fn report_hop_and_stop_messages() {
+    let _ = env_logger::try_init();
+    let mut pool = LocalPool::new();
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay_with_config(relay::Config {
+        message_events: true,
+        ..Default::default()
+    });
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone(), AddressScore::Infinite);
+
+    let mut dst = build_client();
+    let dst_peer_id = *dst.local_peer_id();
+    let dst_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit)
+        .with(Protocol::P2p(dst_peer_id.into()));
+
+    dst.listen_on(dst_addr.clone()).unwrap();
+    spawn_swarm_on_pool(&pool, dst);
+
+    let reservation_messages = pool.run_until(next_messages(&mut relay, 2));
+    assert_eq!(
+        reservation_messages[0],
+        (
+            dst_peer_id,
+            relay::ProtocolMessage::ReservationReq { renewed: false }
+        )
+    );
+    assert!(matches!(
+        reservation_messages[1],
+        (
+            peer,
+            relay::ProtocolMessage::ReservationRes {
+                outcome: relay::MessageOutcome::Accepted,
+                ..
+            }
+        ) if peer == dst_peer_id
+    ));
+
+    let mut src = build_client();
+    let src_peer_id = *src.local_peer_id();
+
+    src.dial(dst_addr).unwrap();
+    spawn_swarm_on_pool(&pool, src);
+
+    let circuit_messages = pool.run_until(next_messages(&mut relay, 3));
+    assert_eq!(
+        circuit_messages[0],
+        (
+            src_peer_id,
+            relay::ProtocolMessage::CircuitReq { dst_peer_id }
+        )
+    );
+    assert!(matches!(
+        circuit_messages[1],
+        (
+            peer,
+            relay::ProtocolMessage::StopRes {
+                dst_peer_id: stop_dst,
+                outcome: relay::MessageOutcome::Accepted,
+                ..
+            }
+        ) if peer == src_peer_id && stop_dst == dst_peer_id
+    ));
+    assert!(matches!(
+        circuit_messages[2],
+        (
+            peer,
+            relay::ProtocolMessage::CircuitRes {
+                dst_peer_id: circuit_dst,
+                outcome: relay::MessageOutcome::Accepted,
+                ..
+            }
+        ) if peer == src_peer_id && circuit_dst == dst_peer_id
+    ));
+}
+
+#[test]
+fn restore_persisted_reservation() {
+    let _ = env_logger::try_init();
+    let mut pool = LocalPool::new();
+
+    let mut client = build_client();
+    let client_peer_id = *client.local_peer_id();
+
+    let store = MemoryReservationStore::default();
+    store.0.lock().unwrap().extend([
+        PersistedReservation {
+            peer_id: client_peer_id,
+            expires: SystemTime::now() + Duration::from_secs(2),
+        },
+        // Expired reservations are not restored.
+        PersistedReservation {
+            peer_id: PeerId::random(),
+            expires: SystemTime::now() - Duration::from_secs(1),
+        },
+    ]);
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay_with_config(relay::Config {
+        reservation_store: Some(Box::new(store.clone())),
+        ..Default::default()
+    });
+    let relay_peer_id = *relay.local_peer_id();
+    relay.listen_on(relay_addr.clone()).unwrap();
+
+    client
+        .dial(relay_addr.with(Protocol::P2p(relay_peer_id.into())))
+        .unwrap();
+    spawn_swarm_on_pool(&pool, client);
+
+    pool.run_until(async {
+        let mut restored = false;
+        loop {
+            match relay.select_next_some().await {
+                SwarmEvent::Behaviour(RelayEvent::Relay(relay::Event::ReservationRestored {
+                    src_peer_id,
+                })) if !restored => {
+                    assert_eq!(src_peer_id, client_peer_id);
+                    restored = true;
+                }
+                // The restored reservation times out with its remaining lifetime.
+                SwarmEvent::Behaviour(RelayEvent::Relay(relay::Event::ReservationTimedOut {
+                    src_peer_id,
+                })) if restored => {
+                    assert_eq!(src_peer_id, client_peer_id);
+                    break;
+                }
+                SwarmEvent::Behaviour(RelayEvent::Ping(_)) => {}
+                SwarmEvent::Behaviour(event) => panic!("Unexpected event: {event:?}"),
+                SwarmEvent::ConnectionClosed { .. } => {
+                    panic!("Expected the restored reservation to time out first")
+                }
+                _ => {}
+            }
+        }
+    });
+
+    assert!(store.0.lock().unwrap().is_empty());
+}
+
+#[test]
+fn reservation_times_out_on_clock() {
+    let _ = env_logger::try_init();
+    let mut pool = LocalPool::new();
+
+    let reservation_duration = Duration::from_secs(60 * 60);
+    let clock = ManualClock::new();
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay_with_config(relay::Config {
+        reservation_duration,
+        clock: Arc::new(clock.clone()),
+        ..Default::default()
+    });
     let relay_peer_id = *relay.local_peer_id();
 
     relay.listen_on(relay_addr.clone()).unwrap();
     relay.add_external_address(relay_addr.clone(), AddressScore::Infinite);
-    spawn_swarm_on_pool(&pool, relay);
 
+    let client_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit);
     let mut client = build_client();
     let client_peer_id = *client.local_peer_id();
+    client.listen_on(client_addr).unwrap();
+    spawn_swarm_on_pool(&pool, client);
+
+    pool.run_until(async {
+        loop {
+            match relay.select_next_some().await {
+                SwarmEvent::Behaviour(RelayEvent::Relay(
+                    relay::Event::ReservationReqAccepted { src_peer_id, .. },
+                )) => {
+                    assert_eq!(src_peer_id, client_peer_id);
+                    // Let the reservation expire without waiting for it.
+                    clock.advance(reservation_duration);
+                }
+                SwarmEvent::Behaviour(RelayEvent::Relay(relay::Event::ReservationTimedOut {
+                    src_peer_id,
+                })) => {
+                    assert_eq!(src_peer_id, client_peer_id);
+                    break;
+                }
+                _ => {}
+            }
+        }
+    });
+}
+
+#[test]
+fn circuit_addrs_advertised_only_when_private() {
+    let _ = env_logger::try_init();
+    let mut pool = LocalPool::new();
+
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay();
+    let relay_peer_id = *relay.local_peer_id();
+
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone(), AddressScore::Infinite);
+    spawn_swarm_on_pool(&pool, relay);
+
     let client_addr = relay_addr
+        .clone()
         .with(Protocol::P2p(relay_peer_id.into()))
         .with(Protocol::P2pCircuit);
+    let mut client = build_client_with_config(relay::client::Config {
+        advertise_only_when_private: true,
+        ..Default::default()
+    });
+    let client_peer_id = *client.local_peer_id();
     let client_addr_with_peer_id = client_addr
         .clone()
         .with(Protocol::P2p(client_peer_id.into()));
 
-    let old_listener = client.listen_on(client_addr.clone()).unwrap();
-
-    // Wait for connection to relay.
+    client.listen_on(client_addr).unwrap();
     assert!(pool.run_until(wait_for_dial(&mut client, relay_peer_id)));
 
-    // Wait for first (old) reservation.
-    pool.run_until(wait_for_reservation(
-        &mut client,
-        client_addr_with_peer_id.clone(),
-        relay_peer_id,
-        false, // No renewal.
-    ));
+    // While the reachability is unknown, the relayed address is not reported.
+    pool.run_until(async {
+        loop {
+            match client.select_next_some().await {
+                SwarmEvent::Behaviour(ClientEvent::Relay(
+                    relay::client::Event::ReservationReqAccepted { .. },
+                )) => break,
+                SwarmEvent::NewListenAddr { address, .. } => panic!("Unexpected {address}"),
+                _ => {}
+            }
+        }
+    });
 
-    // Trigger new reservation.
-    let new_listener = client.listen_on(client_addr).unwrap();
+    // The status reported to the swarm, e.g. by AutoNAT, is applied by the behaviour.
+    client.set_nat_status(NatStatus::Private);
+    assert_eq!(client.behaviour().relay.nat_status(), &NatStatus::Private);
+    pool.run_until(async {
+        loop {
+            if let SwarmEvent::NewListenAddr { address, .. } = client.select_next_some().await {
+                assert_eq!(address, client_addr_with_peer_id);
+                break;
+            }
+        }
+    });
 
-    // Wait for
-    // - listener of old reservation to close
-    // - new reservation to be accepted
-    // - new listener address to be reported
+    client
+        .behaviour_mut()
+        .relay
+        .set_nat_status(NatStatus::Public(relay_addr));
     pool.run_until(async {
-        let mut old_listener_closed = false;
-        let mut new_reservation_accepted = false;
-        let mut new_listener_address_reported = false;
         loop {
-            match client.select_next_some().await {
-                SwarmEvent::ListenerClosed {
-                    addresses,
-                    listener_id,
-                    ..
-                } => {
-                    assert_eq!(addresses, vec![client_addr_with_peer_id.clone()]);
-                    assert_eq!(listener_id, old_listener);
+            if let SwarmEvent::ExpiredListenAddr { address, .. } = client.select_next_some().await {
+                assert_eq!(address, client_addr_with_peer_id);
+                break;
+            }
+        }
+    });
+}
 
-                    old_listener_closed = true;
-                    if new_reservation_accepted && new_listener_address_reported {
-                        break;
-                    }
-                }
-                SwarmEvent::Behaviour(ClientEvent::Relay(
-                    relay::client::Event::ReservationReqAccepted {
-                        relay_peer_id: peer_id,
-                        ..
-                    },
-                )) => {
-                    assert_eq!(relay_peer_id, peer_id);
+#[test]
+fn refresh_reservation_on_relay_address_change() {
+    let _ = env_logger::try_init();
+    let mut pool = LocalPool::new();
 
-                    new_reservation_accepted = true;
-                    if old_listener_closed && new_listener_address_reported {
-                        break;
-                    }
-                }
-                SwarmEvent::NewListenAddr {
-                    address,
-                    listener_id,
-                } => {
-                    assert_eq!(address, client_addr_with_peer_id);
-                    assert_eq!(listener_id, new_listener);
+    let relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let new_relay_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
+    let mut relay = build_relay_with_config(relay::Config {
+        reservation_duration: Duration::from_secs(60),
+        ..Default::default()
+    });
+    let relay_peer_id = *relay.local_peer_id();
 
-                    new_listener_address_reported = true;
-                    if old_listener_closed && new_reservation_accepted {
-                        break;
+    relay.listen_on(relay_addr.clone()).unwrap();
+    relay.add_external_address(relay_addr.clone(), AddressScore::Infinite);
+
+    let mut client = build_client();
+    let client_peer_id = *client.local_peer_id();
+    let circuit_addr = |addr: &Multiaddr| {
+        addr.clone()
+            .with(Protocol::P2p(relay_peer_id.into()))
+            .with(Protocol::P2pCircuit)
+            .with(Protocol::P2p(client_peer_id.into()))
+    };
+
+    client
+        .listen_on(
+            relay_addr
+                .clone()
+                .with(Protocol::P2p(relay_peer_id.into()))
+                .with(Protocol::P2pCircuit),
+        )
+        .unwrap();
+
+    // Drives the relay while waiting for the next listen address event of the client.
+    let mut next_listen_addr_event = |relay: &mut Swarm<Relay>, client: &mut Swarm<Client>| {
+        pool.run_until(async {
+            loop {
+                match future::select(relay.select_next_some(), client.select_next_some()).await {
+                    future::Either::Right((SwarmEvent::NewListenAddr { address, .. }, _)) => {
+                        return (true, address)
                     }
+                    future::Either::Right((SwarmEvent::ExpiredListenAddr { address, .. }, _)) => {
+                        return (false, address)
+                    }
+                    _ => {}
                 }
-                SwarmEvent::Behaviour(ClientEvent::Ping(_)) => {}
-                e => panic!("{e:?}"),
             }
-        }
-    });
+        })
+    };
+
+    assert_eq!(
+        next_listen_addr_event(&mut relay, &mut client),
+        (true, circuit_addr(&relay_addr))
+    );
+    assert!(!client
+        .behaviour_mut()
+        .relay
+        .refresh_reservation(&PeerId::random()));
+    assert!(!client
+        .behaviour_mut()
+        .relay
+        .on_relay_addresses_changed(&relay_peer_id, std::slice::from_ref(&relay_addr)));
+    assert!(!client
+        .behaviour_mut()
+        .relay
+        .on_relay_addresses_changed(&relay_peer_id, std::slice::from_ref(&relay_addr)));
+
+    relay.remove_external_address(&relay_addr);
+    relay.add_external_address(new_relay_addr.clone(), AddressScore::Infinite);
+    // The change is picked up from the listen addresses reported to all behaviours, e.g. by
+    // identify.
+    client
+        .behaviour_mut()
+        .relay
+        .on_swarm_event(FromSwarm::PeerListenAddrsChanged(PeerListenAddrsChanged {
+            peer_id: relay_peer_id,
+            listen_addrs: std::slice::from_ref(&new_relay_addr),
+        }));
+    // The renewal is in flight already.
+    assert!(!client
+        .behaviour_mut()
+        .relay
+        .refresh_reservation(&relay_peer_id));
+
+    assert_eq!(
+        next_listen_addr_event(&mut relay, &mut client),
+        (false, circuit_addr(&relay_addr))
+    );
+    assert_eq!(
+        next_listen_addr_event(&mut relay, &mut client),
+        (true, circuit_addr(&new_relay_addr))
+    );
 }
 
 #[test]
-fn connect() {
+fn relay_candidates_are_scored() {
     let _ = env_logger::try_init();
     let mut pool = LocalPool::new();
 
@@ -186,51 +1079,59 @@ fn connect() {
     relay.add_external_address(relay_addr.clone(), AddressScore::Infinite);
     spawn_swarm_on_pool(&pool, relay);
 
-    let mut dst = build_client();
-    let dst_peer_id = *dst.local_peer_id();
-    let dst_addr = relay_addr
-        .with(Protocol::P2p(relay_peer_id.into()))
-        .with(Protocol::P2pCircuit)
-        .with(Protocol::P2p(dst_peer_id.into()));
+    let unreachable_peer_id = PeerId::random();
+    let unreachable_addr = Multiaddr::empty().with(Protocol::Memory(rand::random::<u64>()));
 
-    dst.listen_on(dst_addr.clone()).unwrap();
+    let mut client = build_client();
+    let client_peer_id = *client.local_peer_id();
+    client
+        .behaviour_mut()
+        .relay
+        .add_relay_candidate(unreachable_peer_id, unreachable_addr);
+    client
+        .behaviour_mut()
+        .relay
+        .add_relay_candidate(relay_peer_id, relay_addr.clone());
 
-    assert!(pool.run_until(wait_for_dial(&mut dst, relay_peer_id)));
+    // Both candidates are probed right away.
+    pool.run_until(future::poll_fn(|cx| {
+        while client.poll_next_unpin(cx).is_ready() {}
 
+        let relay = &client.behaviour().relay;
+        let pinged = relay.relay_score(&relay_peer_id).unwrap().rtt().is_some();
+        let failed = relay
+            .relay_score(&unreachable_peer_id)
+            .unwrap()
+            .failed_pings()
+            == 1;
+        if pinged && failed {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }));
+
+    let selected = client.behaviour().relay.select_relays(1);
+    assert_eq!(selected, vec![(relay_peer_id, relay_addr.clone())]);
+
+    let client_addr = relay_addr
+        .with(Protocol::P2p(relay_peer_id.into()))
+        .with(Protocol::P2pCircuit);
+    client.listen_on(client_addr.clone()).unwrap();
     pool.run_until(wait_for_reservation(
-        &mut dst,
-        dst_addr.clone(),
+        &mut client,
+        client_addr.with(Protocol::P2p(client_peer_id.into())),
         relay_peer_id,
         false, // No renewal.
     ));
-    spawn_swarm_on_pool(&pool, dst);
-
-    let mut src = build_client();
-
-    src.dial(dst_addr).unwrap();
 
-    pool.run_until(async {
-        loop {
-            match src.select_next_some().await {
-                SwarmEvent::Dialing(peer_id) if peer_id == relay_peer_id => {}
-                SwarmEvent::ConnectionEstablished { peer_id, .. } if peer_id == relay_peer_id => {}
-                SwarmEvent::Behaviour(ClientEvent::Ping(ping::Event { peer, .. }))
-                    if peer == dst_peer_id =>
-                {
-                    break
-                }
-                SwarmEvent::Behaviour(ClientEvent::Relay(
-                    relay::client::Event::OutboundCircuitEstablished { .. },
-                )) => {}
-                SwarmEvent::Behaviour(ClientEvent::Ping(ping::Event { peer, .. }))
-                    if peer == relay_peer_id => {}
-                SwarmEvent::ConnectionEstablished { peer_id, .. } if peer_id == dst_peer_id => {
-                    break
-                }
-                e => panic!("{e:?}"),
-            }
-        }
-    })
+    let score = client
+        .behaviour()
+        .relay
+        .relay_score(&relay_peer_id)
+        .unwrap();
+    assert_eq!(score.reservations_accepted(), 1);
+    assert_eq!(score.reservations_failed(), 0);
 }
 
 #[test]
@@ -286,40 +1187,97 @@ fn reuse_connection() {
 }
 
 fn build_relay() -> Swarm<Relay> {
+    build_relay_with_config(relay::Config {
+        reservation_duration: Duration::from_secs(2),
+        ..Default::default()
+    })
+}
+
+fn build_relay_with_config(config: relay::Config) -> Swarm<Relay> {
+    build_relay_with_transport(config, memory_transport)
+}
+
+fn build_relay_with_transport(
+    config: relay::Config,
+    transport: fn(&identity::Keypair) -> Boxed<(PeerId, StreamMuxerBox)>,
+) -> Swarm<Relay> {
+    let local_key = identity::Keypair::generate_ed25519();
+    let local_peer_id = local_key.public().to_peer_id();
+
+    SwarmBuilder::with_async_std_executor(
+        transport(&local_key),
+        Relay {
+            ping: ping::Behaviour::new(ping::Config::new()),
+            relay: relay::Behaviour::new(local_peer_id, config),
+        },
+        local_peer_id,
+    )
+    .build()
+}
+
+fn build_client() -> Swarm<Client> {
+    build_client_with_config(Default::default())
+}
+
+fn build_client_with_config(config: relay::client::Config) -> Swarm<Client> {
     let local_key = identity::Keypair::generate_ed25519();
     let local_public_key = local_key.public();
     let local_peer_id = local_public_key.to_peer_id();
 
-    let transport = upgrade_transport(MemoryTransport::default().boxed(), local_public_key);
+    let (relay_transport, behaviour) = relay::client::new_with_config(local_peer_id, config);
+    let transport = upgrade_transport(
+        OrTransport::new(relay_transport, MemoryTransport::default()).boxed(),
+        local_public_key,
+    );
 
     SwarmBuilder::with_async_std_executor(
         transport,
-        Relay {
+        Client {
             ping: ping::Behaviour::new(ping::Config::new()),
-            relay: relay::Behaviour::new(
-                local_peer_id,
-                relay::Config {
-                    reservation_duration: Duration::from_secs(2),
-                    ..Default::default()
-                },
-            ),
+            relay: behaviour,
         },
         local_peer_id,
     )
     .build()
 }
 
-fn build_client() -> Swarm<Client> {
+/// Builds a client keeping its connections alive, e.g. to observe a circuit over time.
+fn build_keep_alive_client_with_config(config: relay::client::Config) -> Swarm<KeepAliveClient> {
     let local_key = identity::Keypair::generate_ed25519();
     let local_public_key = local_key.public();
     let local_peer_id = local_public_key.to_peer_id();
 
-    let (relay_transport, behaviour) = relay::client::new(local_peer_id);
+    let (relay_transport, behaviour) = relay::client::new_with_config(local_peer_id, config);
     let transport = upgrade_transport(
         OrTransport::new(relay_transport, MemoryTransport::default()).boxed(),
         local_public_key,
     );
 
+    SwarmBuilder::with_async_std_executor(
+        transport,
+        KeepAliveClient {
+            relay: behaviour,
+            keep_alive: keep_alive::Behaviour,
+        },
+        local_peer_id,
+    )
+    .build()
+}
+
+/// Builds a client whose relay transport is upgraded on its own and then combined with the given
+/// transport, which has to be secured and multiplexed already.
+fn build_client_with_transport(
+    transport: fn(&identity::Keypair) -> Boxed<(PeerId, StreamMuxerBox)>,
+) -> Swarm<Client> {
+    let local_key = identity::Keypair::generate_ed25519();
+    let local_peer_id = local_key.public().to_peer_id();
+
+    let (relay_transport, behaviour) = relay::client::new(local_peer_id);
+    let transport = upgrade_transport(relay_transport.boxed(), local_key.public())
+        .or_transport(transport(&local_key))
+        .map(|either, _| either.into_inner())
+        .boxed();
+
     SwarmBuilder::with_async_std_executor(
         transport,
         Client {
@@ -331,6 +1289,26 @@ fn build_client() -> Swarm<Client> {
     .build()
 }
 
+fn memory_transport(local_key: &identity::Keypair) -> Boxed<(PeerId, StreamMuxerBox)> {
+    upgrade_transport(MemoryTransport::default().boxed(), local_key.public())
+}
+
+fn quic_transport(local_key: &identity::Keypair) -> Boxed<(PeerId, StreamMuxerBox)> {
+    libp2p_quic::async_std::Transport::new(libp2p_quic::Config::new(local_key))
+        .map(|(peer_id, connection), _| (peer_id, StreamMuxerBox::new(connection)))
+        .boxed()
+}
+
+fn websocket_transport(local_key: &identity::Keypair) -> Boxed<(PeerId, StreamMuxerBox)> {
+    upgrade_transport(
+        libp2p_websocket::WsConfig::new(libp2p_tcp::async_io::Transport::new(
+            libp2p_tcp::Config::default(),
+        ))
+        .boxed(),
+        local_key.public(),
+    )
+}
+
 fn upgrade_transport<StreamSink>(
     transport: Boxed<StreamSink>,
     local_public_key: PublicKey,
@@ -359,6 +1337,27 @@ struct Client {
     ping: ping::Behaviour,
 }
 
+#[derive(NetworkBehaviour)]
+#[behaviour(prelude = "libp2p_swarm::derive_prelude")]
+struct KeepAliveClient {
+    relay: relay::client::Behaviour,
+    keep_alive: keep_alive::Behaviour,
+}
+
+#[derive(Clone, Default)]
+struct MemoryReservationStore(Arc<Mutex<Vec<PersistedReservation>>>);
+
+impl ReservationStore for MemoryReservationStore {
+    fn load(&mut self) -> std::io::Result<Vec<PersistedReservation>> {
+        Ok(self.0.lock().unwrap().clone())
+    }
+
+    fn save(&mut self, reservations: &[PersistedReservation]) -> std::io::Result<()> {
+        *self.0.lock().unwrap() = reservations.to_vec();
+        Ok(())
+    }
+}
+
 fn spawn_swarm_on_pool<B: NetworkBehaviour + Send>(pool: &LocalPool, swarm: Swarm<B>) {
     pool.spawner()
         .spawn_obj(swarm.collect::<Vec<_>>().map(|_| ()).boxed().into())
@@ -400,6 +1399,23 @@ async fn wait_for_reservation(
     }
 }
 
+async fn next_messages(
+    relay: &mut Swarm<Relay>,
+    n: usize,
+) -> Vec<(PeerId, relay::ProtocolMessage)> {
+    let mut messages = Vec::new();
+    while messages.len() < n {
+        if let SwarmEvent::Behaviour(RelayEvent::Relay(relay::Event::Message {
+            src_peer_id,
+            message,
+        })) = relay.select_next_some().await
+        {
+            messages.push((src_peer_id, message));
+        }
+    }
+    messages
+}
+
 async fn wait_for_dial(client: &mut Swarm<Client>, remote: PeerId) -> bool {
     loop {
         match client.select_next_some().await {
@@ -413,3 +1429,12 @@ async fn wait_for_dial(client: &mut Swarm<Client>, remote: PeerId) -> bool {
         }
     }
 }
+
+#[derive(Default)]
+struct WakeFlag(AtomicBool);
+
+impl ArcWake for WakeFlag {
+    fn wake_by_ref(arc_self: &Arc<Self>) {
+        arc_self.0.store(true, Ordering::SeqCst);
+    }
+}
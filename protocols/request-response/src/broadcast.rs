@@ -0,0 +1,201 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Requests sent to several peers at once, see
+//! [`Behaviour::send_request_to_many`](crate::Behaviour::send_request_to_many).
+
+use crate::{Event, OutboundFailure, RequestId};
+use futures::FutureExt;
+use futures_timer::Delay;
+use libp2p_identity::PeerId;
+use std::{collections::HashMap, fmt, num::NonZeroUsize, task::Context, time::Duration};
+
+/// The ID of a request sent to several peers with
+/// [`Behaviour::send_request_to_many`](crate::Behaviour::send_request_to_many).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BroadcastId(pub(crate) u64);
+
+impl fmt::Display for BroadcastId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// When the responses to a request sent to several peers are reported.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AggregationPolicy {
+    quorum: Option<NonZeroUsize>,
+    timeout: Duration,
+}
+
+impl AggregationPolicy {
+    /// Waits for the responses of all peers, but at most `timeout`.
+    pub fn all(timeout: Duration) -> Self {
+        Self {
+            quorum: None,
+            timeout,
+        }
+    }
+
+    /// Waits for `quorum` responses, but at most `timeout`.
+    pub fn quorum(quorum: NonZeroUsize, timeout: Duration) -> Self {
+        Self {
+            quorum: Some(quorum),
+            timeout,
+        }
+    }
+}
+
+/// The outcome of a request sent to several peers.
+pub(crate) struct Aggregate<TResponse> {
+    pub(crate) broadcast_id: BroadcastId,
+    pub(crate) responses: Vec<(PeerId, TResponse)>,
+    pub(crate) failures: Vec<(PeerId, OutboundFailure)>,
+    pub(crate) quorum_reached: bool,
+}
+
+impl<TResponse> Aggregate<TResponse> {
+    pub(crate) fn into_event<TRequest, TChannelResponse>(
+        self,
+    ) -> Event<TRequest, TResponse, TChannelResponse> {
+        Event::BroadcastCompleted {
+            broadcast_id: self.broadcast_id,
+            responses: self.responses,
+            failures: self.failures,
+            quorum_reached: self.quorum_reached,
+        }
+    }
+}
+
+struct Broadcast<TResponse> {
+    quorum: usize,
+    /// The number of requests without response or failure.
+    pending: usize,
+    responses: Vec<(PeerId, TResponse)>,
+    failures: Vec<(PeerId, OutboundFailure)>,
+    timeout: Delay,
+}
+
+impl<TResponse> Broadcast<TResponse> {
+    fn into_aggregate(self, broadcast_id: BroadcastId) -> Aggregate<TResponse> {
+        Aggregate {
+            broadcast_id,
+            quorum_reached: self.responses.len() >= self.quorum,
+            responses: self.responses,
+            failures: self.failures,
+        }
+    }
+}
+
+pub(crate) struct Broadcasts<TResponse> {
+    /// The broadcast of each request whose response or failure is pending, also after the
+    /// broadcast completed.
+    requests: HashMap<RequestId, BroadcastId>,
+    /// The broadcasts that did not complete yet.
+    broadcasts: HashMap<BroadcastId, Broadcast<TResponse>>,
+}
+
+impl<TResponse> Default for Broadcasts<TResponse> {
+    fn default() -> Self {
+        Self {
+            requests: HashMap::new(),
+            broadcasts: HashMap::new(),
+        }
+    }
+}
+
+impl<TResponse> Broadcasts<TResponse> {
+    /// Starts aggregating the outcomes of the given requests.
+    ///
+    /// Completes right away if `requests` is empty.
+    pub(crate) fn insert(
+        &mut self,
+        broadcast_id: BroadcastId,
+        requests: Vec<RequestId>,
+        policy: AggregationPolicy,
+    ) -> Option<Aggregate<TResponse>> {
+        let broadcast = Broadcast {
+            quorum: policy
+                .quorum
+                .map_or(requests.len(), |quorum| quorum.get().min(requests.len())),
+            pending: requests.len(),
+            responses: Vec::new(),
+            failures: Vec::new(),
+            timeout: Delay::new(policy.timeout),
+        };
+        if requests.is_empty() {
+            return Some(broadcast.into_aggregate(broadcast_id));
+        }
+        self.requests
+            .extend(requests.into_iter().map(|request| (request, broadcast_id)));
+        self.broadcasts.insert(broadcast_id, broadcast);
+        None
+    }
+
+    /// Returns whether the request was sent by a broadcast.
+    pub(crate) fn contains(&self, request_id: &RequestId) -> bool {
+        self.requests.contains_key(request_id)
+    }
+
+    /// Records the response or failure of a request sent by a broadcast, returning the outcome
+    /// of the broadcast if it completed.
+    ///
+    /// Outcomes of requests of completed broadcasts are dropped.
+    pub(crate) fn on_outcome(
+        &mut self,
+        request_id: &RequestId,
+        peer: PeerId,
+        outcome: Result<TResponse, OutboundFailure>,
+    ) -> Option<Aggregate<TResponse>> {
+        let broadcast_id = self.requests.remove(request_id)?;
+        let broadcast = self.broadcasts.get_mut(&broadcast_id)?;
+        broadcast.pending -= 1;
+        match outcome {
+            Ok(response) => broadcast.responses.push((peer, response)),
+            Err(error) => broadcast.failures.push((peer, error)),
+        }
+        // Complete once the quorum is reached or can no longer be reached.
+        if broadcast.responses.len() >= broadcast.quorum
+            || broadcast.responses.len() + broadcast.pending < broadcast.quorum
+        {
+            let broadcast = self.broadcasts.remove(&broadcast_id)?;
+            return Some(broadcast.into_aggregate(broadcast_id));
+        }
+        None
+    }
+
+    /// Polls for broadcasts whose timeout passed.
+    pub(crate) fn poll_timeouts(&mut self, cx: &mut Context<'_>) -> Vec<Aggregate<TResponse>> {
+        let timed_out = self
+            .broadcasts
+            .iter_mut()
+            .filter_map(|(id, broadcast)| {
+                broadcast.timeout.poll_unpin(cx).is_ready().then_some(*id)
+            })
+            .collect::<Vec<_>>();
+        timed_out
+            .into_iter()
+            .filter_map(|id| {
+                let broadcast = self.broadcasts.remove(&id)?;
+                Some(broadcast.into_aggregate(id))
+            })
+            .collect()
+    }
+}
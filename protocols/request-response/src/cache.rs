@@ -0,0 +1,113 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Responder-side cache of responses, see [`Behaviour::enable_response_cache`](crate::Behaviour::enable_response_cache).
+
+use instant::Instant;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    hash::{Hash, Hasher},
+    num::NonZeroUsize,
+    time::Duration,
+};
+
+/// Computes the cache key of a request, see
+/// [`Behaviour::enable_response_cache`](crate::Behaviour::enable_response_cache).
+pub(crate) type RequestHasher<TRequest> = Box<dyn Fn(&TRequest) -> Option<u64> + Send>;
+
+/// Responses to previous inbound requests, keyed by the hash of the request and its protocol.
+pub(crate) struct ResponseCache<TRequest, TResponse> {
+    hasher: RequestHasher<TRequest>,
+    clone: fn(&TResponse) -> TResponse,
+    ttl: Duration,
+    capacity: NonZeroUsize,
+    entries: HashMap<u64, (TResponse, Instant)>,
+    /// Keys in insertion order along with their expiry, to evict the oldest entries first.
+    expiries: VecDeque<(u64, Instant)>,
+}
+
+impl<TRequest, TResponse> ResponseCache<TRequest, TResponse> {
+    pub(crate) fn new(
+        ttl: Duration,
+        capacity: NonZeroUsize,
+        hasher: RequestHasher<TRequest>,
+        clone: fn(&TResponse) -> TResponse,
+    ) -> Self {
+        Self {
+            hasher,
+            clone,
+            ttl,
+            capacity,
+            entries: HashMap::new(),
+            expiries: VecDeque::new(),
+        }
+    }
+
+    /// Returns the cache key of a request received via the given protocol, or `None` if its
+    /// response must not be cached.
+    pub(crate) fn key(&self, protocol: &[u8], request: &TRequest) -> Option<u64> {
+        let request_key = (self.hasher)(request)?;
+        let mut hasher = DefaultHasher::new();
+        protocol.hash(&mut hasher);
+        request_key.hash(&mut hasher);
+        Some(hasher.finish())
+    }
+
+    /// Returns a copy of the cached response for the given key, unless it expired.
+    pub(crate) fn get(&self, key: u64) -> Option<TResponse> {
+        match self.entries.get(&key) {
+            Some((response, expires)) if *expires > Instant::now() => Some((self.clone)(response)),
+            _ => None,
+        }
+    }
+
+    /// Caches a copy of the response for the given key for the configured TTL.
+    pub(crate) fn insert(&mut self, key: u64, response: &TResponse) {
+        let now = Instant::now();
+        while matches!(self.expiries.front(), Some((_, expires)) if *expires <= now) {
+            self.pop_oldest();
+        }
+        if self.entries.contains_key(&key) {
+            self.expiries.retain(|(k, _)| *k != key);
+        } else {
+            while self.entries.len() >= self.capacity.get() && self.pop_oldest() {}
+        }
+
+        let expires = now + self.ttl;
+        self.entries.insert(key, ((self.clone)(response), expires));
+        self.expiries.push_back((key, expires));
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.expiries.clear();
+    }
+
+    /// Removes the oldest entry, returning `false` if there is none.
+    fn pop_oldest(&mut self) -> bool {
+        match self.expiries.pop_front() {
+            Some((key, _)) => {
+                self.entries.remove(&key);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -83,7 +83,7 @@ where
             'static,
             Result<
                 (
-                    (RequestId, TCodec::Request),
+                    (RequestId, TCodec::Request, TCodec::Protocol),
                     oneshot::Sender<TCodec::Response>,
                 ),
                 oneshot::Canceled,
@@ -208,6 +208,8 @@ where
     Request {
         request_id: RequestId,
         request: TCodec::Request,
+        /// The protocol negotiated for the request.
+        protocol: TCodec::Protocol,
         sender: oneshot::Sender<TCodec::Response>,
     },
     /// A response has been received.
@@ -238,6 +240,7 @@ impl<TCodec: Codec> fmt::Debug for Event<TCodec> {
             Event::Request {
                 request_id,
                 request: _,
+                protocol: _,
                 sender: _,
             } => f
                 .debug_struct("Event::Request")
@@ -354,12 +357,13 @@ where
         // Check for inbound requests.
         while let Poll::Ready(Some(result)) = self.inbound.poll_next_unpin(cx) {
             match result {
-                Ok(((id, rq), rs_sender)) => {
+                Ok(((id, rq, protocol), rs_sender)) => {
                     // We received an inbound request.
                     self.keep_alive = KeepAlive::Yes;
                     return Poll::Ready(ConnectionHandlerEvent::Custom(Event::Request {
                         request_id: id,
                         request: rq,
+                        protocol,
                         sender: rs_sender,
                     }));
                 }
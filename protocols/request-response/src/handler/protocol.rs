@@ -30,7 +30,13 @@ use futures::{channel::oneshot, future::BoxFuture, prelude::*};
 use libp2p_core::upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo};
 use libp2p_swarm::NegotiatedSubstream;
 use smallvec::SmallVec;
-use std::{fmt, io};
+use std::{
+    fmt, io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 /// The level of support for a particular protocol.
 #[derive(Debug, Clone)]
@@ -71,7 +77,7 @@ where
 {
     pub(crate) codec: TCodec,
     pub(crate) protocols: SmallVec<[TCodec::Protocol; 2]>,
-    pub(crate) request_sender: oneshot::Sender<(RequestId, TCodec::Request)>,
+    pub(crate) request_sender: oneshot::Sender<(RequestId, TCodec::Request, TCodec::Protocol)>,
     pub(crate) response_receiver: oneshot::Receiver<TCodec::Response>,
     pub(crate) request_id: RequestId,
 }
@@ -104,7 +110,7 @@ where
         async move {
             let read = self.codec.read_request(&protocol, &mut io);
             let request = read.await?;
-            match self.request_sender.send((self.request_id, request)) {
+            match self.request_sender.send((self.request_id, request, protocol.clone())) {
                 Ok(()) => {},
                 Err(_) => panic!(
                     "Expect request receiver to be alive i.e. protocol handler to be alive.",
@@ -138,6 +144,9 @@ where
     pub(crate) protocols: SmallVec<[TCodec::Protocol; 2]>,
     pub(crate) request_id: RequestId,
     pub(crate) request: TCodec::Request,
+    /// Set once the substream of the request was negotiated, if the request
+    /// is tracked to be queued again when its connection closes.
+    pub(crate) negotiated: Option<Arc<AtomicBool>>,
 }
 
 impl<TCodec> fmt::Debug for RequestProtocol<TCodec>
@@ -176,6 +185,9 @@ where
         mut io: NegotiatedSubstream,
         protocol: Self::Info,
     ) -> Self::Future {
+        if let Some(negotiated) = self.negotiated.take() {
+            negotiated.store(true, Ordering::Relaxed);
+        }
         async move {
             let write = self.codec.write_request(&protocol, &mut io, self.request);
             write.await?;
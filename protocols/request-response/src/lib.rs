@@ -46,6 +46,12 @@
 //! For that purpose, [`Codec::Protocol`] is typically
 //! instantiated with a sum type.
 //!
+//! This also allows evolving a protocol through several versions, e.g.
+//! `/my-app/1.0.0` and `/my-app/1.1.0`. The version negotiated for an
+//! inbound request is available via [`ResponseChannel::protocol`] and a
+//! specific version can be requested for an outbound request via
+//! [`Behaviour::send_request_with_protocol`].
+//!
 //! ## Limited Protocol Support
 //!
 //! It is possible to only support inbound or outbound requests for
@@ -58,9 +64,13 @@
 
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
+mod broadcast;
+mod cache;
 pub mod codec;
 pub mod handler;
+mod requeue;
 
+pub use broadcast::{AggregationPolicy, BroadcastId};
 pub use codec::{Codec, ProtocolName};
 
 #[allow(deprecated)]
@@ -68,6 +78,8 @@ pub use codec::RequestResponseCodec;
 
 pub use handler::ProtocolSupport;
 
+use broadcast::Broadcasts;
+use cache::ResponseCache;
 use futures::channel::oneshot;
 use handler::{Handler, RequestProtocol};
 use libp2p_core::{ConnectedPoint, Endpoint, Multiaddr};
@@ -78,10 +90,12 @@ use libp2p_swarm::{
     ConnectionDenied, ConnectionId, NetworkBehaviour, NotifyHandler, PollParameters, THandler,
     THandlerInEvent, THandlerOutEvent, ToSwarm,
 };
+use requeue::RequestRequeue;
 use smallvec::SmallVec;
 use std::{
     collections::{HashMap, HashSet, VecDeque},
     fmt,
+    num::NonZeroUsize,
     sync::{atomic::AtomicU64, Arc},
     task::{Context, Poll},
     time::Duration,
@@ -183,6 +197,22 @@ pub enum Event<TRequest, TResponse, TChannelResponse = TResponse> {
         /// The ID of the inbound request whose response was sent.
         request_id: RequestId,
     },
+    /// A request sent with [`Behaviour::send_request_to_many`] completed, as determined by its
+    /// [`AggregationPolicy`].
+    ///
+    /// Responses and failures of the individual requests are only reported here, responses
+    /// arriving after completion are dropped.
+    BroadcastCompleted {
+        /// The ID of the request sent to several peers.
+        broadcast_id: BroadcastId,
+        /// The responses received, in the order of their arrival.
+        responses: Vec<(PeerId, TResponse)>,
+        /// The requests that failed.
+        failures: Vec<(PeerId, OutboundFailure)>,
+        /// Whether the quorum of responses was reached, i.e. `false` if the broadcast timed out
+        /// or too many requests failed.
+        quorum_reached: bool,
+    },
 }
 
 /// Possible failures occurring in the context of sending
@@ -271,9 +301,35 @@ impl std::error::Error for InboundFailure {}
 #[derive(Debug)]
 pub struct ResponseChannel<TResponse> {
     sender: oneshot::Sender<TResponse>,
+    protocol: Vec<u8>,
+    connection_id: ConnectionId,
+    remote_address: Option<Multiaddr>,
+    /// The key to cache the response under, if the response cache is enabled.
+    cache_key: Option<u64>,
 }
 
 impl<TResponse> ResponseChannel<TResponse> {
+    /// The name of the protocol negotiated for the inbound request, i.e. one of
+    /// the inbound protocols the [`Behaviour`] was created with.
+    ///
+    /// The response is written with the same protocol.
+    pub fn protocol(&self) -> &[u8] {
+        &self.protocol
+    }
+
+    /// The ID of the connection the inbound request was received on.
+    pub fn connection_id(&self) -> ConnectionId {
+        self.connection_id
+    }
+
+    /// The address of the remote of the connection the inbound request was received on,
+    /// i.e. the dialed address or the address to send back data to if the remote dialed us.
+    ///
+    /// Returns `None` if the connection closed before the request was reported.
+    pub fn remote_address(&self) -> Option<&Multiaddr> {
+        self.remote_address.as_ref()
+    }
+
     /// Checks whether the response channel is still open, i.e.
     /// the `Behaviour` is still waiting for a
     /// a response to be sent via [`Behaviour::send_response`]
@@ -360,6 +416,16 @@ where
     /// Requests that have not yet been sent and are waiting for a connection
     /// to be established.
     pending_outbound_requests: HashMap<PeerId, SmallVec<[RequestProtocol<TCodec>; 10]>>,
+    /// Responses to previous inbound requests, if enabled via
+    /// [`Behaviour::enable_response_cache`].
+    response_cache: Option<ResponseCache<TCodec::Request, TCodec::Response>>,
+    /// Copies of the requests to queue again when their connection closes, if enabled via
+    /// [`Behaviour::enable_request_requeue`].
+    request_requeue: Option<RequestRequeue<TCodec>>,
+    /// The next ID of a request sent to several peers.
+    next_broadcast_id: BroadcastId,
+    /// The requests sent with [`Behaviour::send_request_to_many`] awaiting their responses.
+    broadcasts: Broadcasts<TCodec::Response>,
 }
 
 impl<TCodec> Behaviour<TCodec>
@@ -393,9 +459,73 @@ where
             connected: HashMap::new(),
             pending_outbound_requests: HashMap::new(),
             addresses: HashMap::new(),
+            response_cache: None,
+            request_requeue: None,
+            next_broadcast_id: BroadcastId(1),
+            broadcasts: Broadcasts::default(),
         }
     }
 
+    /// Enables answering repeated inbound requests from a cache of previous responses.
+    ///
+    /// `hasher` maps a request to the key its response is cached under, or to `None` if the
+    /// request is not idempotent and must always be handled by the application. The response
+    /// passed to [`Behaviour::send_response`] for a request with a key is cached for `ttl`. While
+    /// cached, inbound requests with the same key are answered with it without emitting
+    /// [`Message::Request`]. [`Event::ResponseSent`] and [`Event::InboundFailure`] are still
+    /// emitted for them.
+    ///
+    /// At most `capacity` responses are cached, evicting the oldest ones first.
+    ///
+    /// Enabling the cache again replaces the previous one, dropping all cached responses.
+    pub fn enable_response_cache<F>(&mut self, ttl: Duration, capacity: NonZeroUsize, hasher: F)
+    where
+        F: Fn(&TCodec::Request) -> Option<u64> + Send + 'static,
+        TCodec::Response: Clone,
+    {
+        self.response_cache = Some(ResponseCache::new(
+            ttl,
+            capacity,
+            Box::new(hasher),
+            TCodec::Response::clone,
+        ));
+    }
+
+    /// Disables the response cache, dropping all cached responses.
+    pub fn disable_response_cache(&mut self) {
+        self.response_cache = None;
+    }
+
+    /// Drops all cached responses, e.g. after the data they were computed from changed.
+    pub fn clear_response_cache(&mut self) {
+        if let Some(cache) = self.response_cache.as_mut() {
+            cache.clear();
+        }
+    }
+
+    /// Enables queueing requests again whose connection closed before they were transmitted.
+    ///
+    /// Instead of failing with [`OutboundFailure::ConnectionClosed`], such a request is sent on
+    /// another connection to the peer or, if there is none, kept queued until a connection to the
+    /// peer is established again, for which a dial is initiated. A request that is still queued
+    /// after `expiry` fails with [`OutboundFailure::ConnectionClosed`], or right away with
+    /// [`OutboundFailure::DialFailure`] if the dial failed permanently, see
+    /// [`DialError::is_permanent`](libp2p_swarm::DialError::is_permanent). At most `max_requests`
+    /// requests are queued per peer, further requests fail as before.
+    ///
+    /// A request counts as transmitted once its substream was negotiated, so a request is never
+    /// sent twice. To this end, a copy of every request is kept until it completed.
+    pub fn enable_request_requeue(&mut self, max_requests: usize, expiry: Duration)
+    where
+        TCodec::Request: Clone,
+    {
+        self.request_requeue = Some(RequestRequeue::new(
+            max_requests,
+            expiry,
+            TCodec::Request::clone,
+        ));
+    }
+
     /// Initiates sending a request.
     ///
     /// If the targeted peer is currently not connected, a dialing
@@ -409,12 +539,80 @@ where
     /// > managed via [`Behaviour::add_address`] and
     /// > [`Behaviour::remove_address`].
     pub fn send_request(&mut self, peer: &PeerId, request: TCodec::Request) -> RequestId {
+        let protocols = self.outbound_protocols.clone();
+        self.do_send_request(peer, request, protocols)
+    }
+
+    /// Initiates sending a request with the given protocol.
+    ///
+    /// Unlike [`Behaviour::send_request`], which lets the remote choose among all
+    /// outbound protocols, only `protocol` is offered, e.g. to send a request of a
+    /// specific version of the protocol. The protocol need not be one of the
+    /// outbound protocols the [`Behaviour`] was created with.
+    ///
+    /// If the remote does not support `protocol`, the request fails with
+    /// [`OutboundFailure::UnsupportedProtocols`].
+    pub fn send_request_with_protocol(
+        &mut self,
+        peer: &PeerId,
+        request: TCodec::Request,
+        protocol: TCodec::Protocol,
+    ) -> RequestId {
+        let mut protocols = SmallVec::new();
+        protocols.push(protocol);
+        self.do_send_request(peer, request, protocols)
+    }
+
+    /// Initiates sending the same request to several peers, aggregating their responses.
+    ///
+    /// Requests are sent as with [`Behaviour::send_request`]. Instead of an event per response
+    /// or failure, a single [`Event::BroadcastCompleted`] with all responses and failures is
+    /// emitted once the quorum of `policy` is reached or can no longer be reached, or once its
+    /// timeout passed.
+    pub fn send_request_to_many<I>(
+        &mut self,
+        peers: I,
+        request: TCodec::Request,
+        policy: AggregationPolicy,
+    ) -> BroadcastId
+    where
+        I: IntoIterator<Item = PeerId>,
+        TCodec::Request: Clone,
+    {
+        let broadcast_id = self.next_broadcast_id;
+        self.next_broadcast_id.0 += 1;
+
+        let mut peers = peers.into_iter().collect::<Vec<_>>();
+        peers.sort_unstable_by_key(|peer| peer.to_bytes());
+        peers.dedup();
+        let requests = peers
+            .iter()
+            .map(|peer| {
+                let protocols = self.outbound_protocols.clone();
+                self.do_send_request(peer, request.clone(), protocols)
+            })
+            .collect();
+
+        if let Some(aggregate) = self.broadcasts.insert(broadcast_id, requests, policy) {
+            self.pending_events
+                .push_back(ToSwarm::GenerateEvent(aggregate.into_event()));
+        }
+        broadcast_id
+    }
+
+    fn do_send_request(
+        &mut self,
+        peer: &PeerId,
+        request: TCodec::Request,
+        protocols: SmallVec<[TCodec::Protocol; 2]>,
+    ) -> RequestId {
         let request_id = self.next_request_id();
         let request = RequestProtocol {
             request_id,
             codec: self.codec.clone(),
-            protocols: self.outbound_protocols.clone(),
+            protocols,
             request,
+            negotiated: None,
         };
 
         if let Some(request) = self.try_send_request(peer, request) {
@@ -446,6 +644,9 @@ where
         ch: ResponseChannel<TCodec::Response>,
         rs: TCodec::Response,
     ) -> Result<(), TCodec::Response> {
+        if let (Some(key), Some(cache)) = (ch.cache_key, self.response_cache.as_mut()) {
+            cache.insert(key, &rs);
+        }
         ch.sender.send(rs)
     }
 
@@ -515,6 +716,34 @@ where
             .unwrap_or(false)
     }
 
+    /// Records the response or failure of a request sent with
+    /// [`Behaviour::send_request_to_many`] instead of emitting it, returning the completion of the
+    /// broadcast, if any. Other events are returned unchanged.
+    fn aggregate_broadcast_outcome(
+        &mut self,
+        event: Event<TCodec::Request, TCodec::Response>,
+    ) -> Option<Event<TCodec::Request, TCodec::Response>> {
+        let (peer, request_id, outcome) = match event {
+            Event::Message {
+                peer,
+                message:
+                    Message::Response {
+                        request_id,
+                        response,
+                    },
+            } if self.broadcasts.contains(&request_id) => (peer, request_id, Ok(response)),
+            Event::OutboundFailure {
+                peer,
+                request_id,
+                error,
+            } if self.broadcasts.contains(&request_id) => (peer, request_id, Err(error)),
+            event => return Some(event),
+        };
+        self.broadcasts
+            .on_outcome(&request_id, peer, outcome)
+            .map(|aggregate| aggregate.into_event())
+    }
+
     /// Returns the next request ID.
     fn next_request_id(&mut self) -> RequestId {
         let request_id = self.next_request_id;
@@ -528,12 +757,15 @@ where
     fn try_send_request(
         &mut self,
         peer: &PeerId,
-        request: RequestProtocol<TCodec>,
+        mut request: RequestProtocol<TCodec>,
     ) -> Option<RequestProtocol<TCodec>> {
         if let Some(connections) = self.connected.get_mut(peer) {
             if connections.is_empty() {
                 return Some(request);
             }
+            if let Some(requeue) = self.request_requeue.as_mut() {
+                requeue.track(&mut request);
+            }
             let ix = (request.request_id.0 as usize) % connections.len();
             let conn = &mut connections[ix];
             conn.pending_inbound_responses.insert(request.request_id);
@@ -575,6 +807,9 @@ where
         connection: ConnectionId,
         request: &RequestId,
     ) -> bool {
+        if let Some(requeue) = self.request_requeue.as_mut() {
+            requeue.complete(request);
+        }
         self.get_connection_mut(peer, connection)
             .map(|c| c.pending_inbound_responses.remove(request))
             .unwrap_or(false)
@@ -615,6 +850,7 @@ where
             .find(|c| c.id == connection_id)
             .expect("Address change can only happen on an established connection.");
         connection.address = new_address;
+        connection.remote_address = new.get_remote_address().clone();
     }
 
     fn on_connection_established(
@@ -634,11 +870,18 @@ where
         self.connected
             .entry(peer_id)
             .or_default()
-            .push(Connection::new(connection_id, address));
+            .push(Connection::new(
+                connection_id,
+                address,
+                endpoint.get_remote_address().clone(),
+            ));
 
         if other_established == 0 {
             if let Some(pending) = self.pending_outbound_requests.remove(&peer_id) {
                 for request in pending {
+                    if let Some(requeue) = self.request_requeue.as_mut() {
+                        requeue.dequeue(&request.request_id);
+                    }
                     let request = self.try_send_request(&peer_id, request);
                     assert!(request.is_none());
                 }
@@ -680,7 +923,18 @@ where
                 }));
         }
 
-        for request_id in connection.pending_inbound_responses {
+        let mut pending_inbound_responses = connection
+            .pending_inbound_responses
+            .into_iter()
+            .collect::<Vec<_>>();
+        if self.request_requeue.is_some() {
+            // Keep the order in which the requests were sent.
+            pending_inbound_responses.sort_by_key(|id| id.0);
+            pending_inbound_responses
+                .retain(|request_id| !self.requeue_request(peer_id, request_id));
+        }
+
+        for request_id in pending_inbound_responses {
             self.pending_events
                 .push_back(ToSwarm::GenerateEvent(Event::OutboundFailure {
                     peer: peer_id,
@@ -690,7 +944,74 @@ where
         }
     }
 
-    fn on_dial_failure(&mut self, DialFailure { peer_id, .. }: DialFailure) {
+    /// Sends a request whose connection closed on another connection or queues it until a
+    /// connection is established again, returning `false` if the request may have been
+    /// transmitted already or too many requests are queued.
+    fn requeue_request(&mut self, peer_id: PeerId, request_id: &RequestId) -> bool {
+        let requeue = match self.request_requeue.as_mut() {
+            Some(requeue) => requeue,
+            None => return false,
+        };
+        let request = match requeue.take_untransmitted(request_id) {
+            Some(request) => request,
+            None => return false,
+        };
+        let max_requests = requeue.max_requests();
+
+        let request = match self.try_send_request(&peer_id, request) {
+            Some(request) => request,
+            None => return true,
+        };
+        let pending = self.pending_outbound_requests.entry(peer_id).or_default();
+        if pending.len() >= max_requests {
+            if pending.is_empty() {
+                self.pending_outbound_requests.remove(&peer_id);
+            }
+            return false;
+        }
+        if pending.is_empty() {
+            self.pending_events.push_back(ToSwarm::Dial {
+                opts: DialOpts::peer_id(peer_id).build(),
+            });
+        }
+        pending.push(request);
+        self.request_requeue
+            .as_mut()
+            .expect("request requeue to be enabled")
+            .queue(*request_id);
+        true
+    }
+
+    /// Fails a request queued again after its connection closed that is still not sent.
+    fn expire_requeued_request(&mut self, request_id: RequestId) {
+        let peer = self
+            .pending_outbound_requests
+            .iter()
+            .find_map(|(peer, pending)| {
+                pending
+                    .iter()
+                    .any(|r| r.request_id == request_id)
+                    .then_some(*peer)
+            });
+        let peer = match peer {
+            Some(peer) => peer,
+            None => return,
+        };
+        if let Some(pending) = self.pending_outbound_requests.get_mut(&peer) {
+            pending.retain(|r| r.request_id != request_id);
+            if pending.is_empty() {
+                self.pending_outbound_requests.remove(&peer);
+            }
+        }
+        self.pending_events
+            .push_back(ToSwarm::GenerateEvent(Event::OutboundFailure {
+                peer,
+                request_id,
+                error: OutboundFailure::ConnectionClosed,
+            }));
+    }
+
+    fn on_dial_failure(&mut self, DialFailure { peer_id, error, .. }: DialFailure) {
         if let Some(peer) = peer_id {
             // If there are pending outgoing requests when a dial failure occurs,
             // it is implied that we are not connected to the peer, since pending
@@ -698,7 +1019,25 @@ where
             // only created when a peer is not connected when a request is made.
             // Thus these requests must be considered failed, even if there is
             // another, concurrent dialing attempt ongoing.
-            if let Some(pending) = self.pending_outbound_requests.remove(&peer) {
+            if let Some(mut pending) = self.pending_outbound_requests.remove(&peer) {
+                // Requests queued again after their connection closed are kept until they expire,
+                // unless dialing the peer again is pointless.
+                if let Some(requeue) = self.request_requeue.as_mut() {
+                    if error.is_transient() {
+                        let (requeued, failed) =
+                            pending.into_iter().partition::<SmallVec<[_; 10]>, _>(|r| {
+                                requeue.is_queued(&r.request_id)
+                            });
+                        if !requeued.is_empty() {
+                            self.pending_outbound_requests.insert(peer, requeued);
+                        }
+                        pending = failed;
+                    } else {
+                        for request in &pending {
+                            requeue.dequeue(&request.request_id);
+                        }
+                    }
+                }
                 for request in pending {
                     self.pending_events
                         .push_back(ToSwarm::GenerateEvent(Event::OutboundFailure {
@@ -774,6 +1113,10 @@ where
         ))
     }
 
+    fn queued_actions(&self) -> usize {
+        self.pending_events.len()
+    }
+
     fn on_swarm_event(&mut self, event: FromSwarm<Self::ConnectionHandler>) {
         match event {
             FromSwarm::ConnectionEstablished(connection_established) => {
@@ -792,6 +1135,9 @@ where
             FromSwarm::ListenerClosed(_) => {}
             FromSwarm::NewExternalAddr(_) => {}
             FromSwarm::ExpiredExternalAddr(_) => {}
+            FromSwarm::PeerProtocolsChanged(_) => {}
+            FromSwarm::PeerListenAddrsChanged(_) => {}
+            FromSwarm::NatStatusChanged(_) => {}
         }
     }
 
@@ -822,16 +1168,45 @@ where
             handler::Event::Request {
                 request_id,
                 request,
+                protocol,
                 sender,
             } => {
-                let channel = ResponseChannel { sender };
-                let message = Message::Request {
-                    request_id,
-                    request,
-                    channel,
-                };
-                self.pending_events
-                    .push_back(ToSwarm::GenerateEvent(Event::Message { peer, message }));
+                let cache_key = self
+                    .response_cache
+                    .as_ref()
+                    .and_then(|cache| cache.key(protocol.protocol_name(), &request));
+                let cached_response = cache_key
+                    .zip(self.response_cache.as_ref())
+                    .and_then(|(key, cache)| cache.get(key));
+
+                match cached_response {
+                    Some(response) => {
+                        // Sending only fails if the inbound substream is gone already, which the
+                        // handler reports on its own.
+                        let _ = sender.send(response);
+                    }
+                    None => {
+                        // The connection is only unknown if it closed after the request was
+                        // received, in which case the request fails below.
+                        let remote_address = self
+                            .get_connection_mut(&peer, connection)
+                            .map(|c| c.remote_address.clone());
+                        let channel = ResponseChannel {
+                            sender,
+                            protocol: protocol.protocol_name().to_vec(),
+                            connection_id: connection,
+                            remote_address,
+                            cache_key,
+                        };
+                        let message = Message::Request {
+                            request_id,
+                            request,
+                            channel,
+                        };
+                        self.pending_events
+                            .push_back(ToSwarm::GenerateEvent(Event::Message { peer, message }));
+                    }
+                }
 
                 match self.get_connection_mut(&peer, connection) {
                     Some(connection) => {
@@ -935,12 +1310,35 @@ where
 
     fn poll(
         &mut self,
-        _: &mut Context<'_>,
+        cx: &mut Context<'_>,
         _: &mut impl PollParameters,
     ) -> Poll<ToSwarm<Self::OutEvent, THandlerInEvent<Self>>> {
-        if let Some(ev) = self.pending_events.pop_front() {
-            return Poll::Ready(ev);
-        } else if self.pending_events.capacity() > EMPTY_QUEUE_SHRINK_THRESHOLD {
+        while let Some(Poll::Ready(expired)) = self
+            .request_requeue
+            .as_mut()
+            .map(|requeue| requeue.poll_expired(cx))
+        {
+            for request_id in expired {
+                self.expire_requeued_request(request_id);
+            }
+        }
+
+        for aggregate in self.broadcasts.poll_timeouts(cx) {
+            self.pending_events
+                .push_back(ToSwarm::GenerateEvent(aggregate.into_event()));
+        }
+
+        while let Some(ev) = self.pending_events.pop_front() {
+            match ev {
+                ToSwarm::GenerateEvent(event) => {
+                    if let Some(event) = self.aggregate_broadcast_outcome(event) {
+                        return Poll::Ready(ToSwarm::GenerateEvent(event));
+                    }
+                }
+                ev => return Poll::Ready(ev),
+            }
+        }
+        if self.pending_events.capacity() > EMPTY_QUEUE_SHRINK_THRESHOLD {
             self.pending_events.shrink_to_fit();
         }
 
@@ -957,7 +1355,10 @@ const EMPTY_QUEUE_SHRINK_THRESHOLD: usize = 100;
 /// Internal information tracked for an established connection.
 struct Connection {
     id: ConnectionId,
+    /// The dialed address, if the connection is outbound.
     address: Option<Multiaddr>,
+    /// The address of the remote, see [`ConnectedPoint::get_remote_address`].
+    remote_address: Multiaddr,
     /// Pending outbound responses where corresponding inbound requests have
     /// been received on this connection and emitted via `poll` but have not yet
     /// been answered.
@@ -968,10 +1369,11 @@ struct Connection {
 }
 
 impl Connection {
-    fn new(id: ConnectionId, address: Option<Multiaddr>) -> Self {
+    fn new(id: ConnectionId, address: Option<Multiaddr>, remote_address: Multiaddr) -> Self {
         Self {
             id,
             address,
+            remote_address,
             pending_outbound_responses: Default::default(),
             pending_inbound_responses: Default::default(),
         }
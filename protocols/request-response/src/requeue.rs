@@ -0,0 +1,150 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Requests queued again after their connection closed, see
+//! [`Behaviour::enable_request_requeue`](crate::Behaviour::enable_request_requeue).
+
+use crate::codec::Codec;
+use crate::handler::RequestProtocol;
+use crate::RequestId;
+use futures::FutureExt;
+use futures_timer::Delay;
+use instant::Instant;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+pub(crate) struct RequestRequeue<TCodec: Codec> {
+    clone: fn(&TCodec::Request) -> TCodec::Request,
+    max_requests: usize,
+    expiry: Duration,
+    /// Copies of the requests handed to a connection, along with a flag that is set once the
+    /// substream of the request was negotiated, i.e. once the request may have been transmitted.
+    in_flight: HashMap<RequestId, (RequestProtocol<TCodec>, Arc<AtomicBool>)>,
+    /// The deadlines of the requests queued again.
+    deadlines: HashMap<RequestId, Instant>,
+    /// Fires at the earliest of the `deadlines`.
+    timer: Option<Delay>,
+}
+
+impl<TCodec> RequestRequeue<TCodec>
+where
+    TCodec: Codec + Clone,
+{
+    pub(crate) fn new(
+        max_requests: usize,
+        expiry: Duration,
+        clone: fn(&TCodec::Request) -> TCodec::Request,
+    ) -> Self {
+        Self {
+            clone,
+            max_requests,
+            expiry,
+            in_flight: HashMap::new(),
+            deadlines: HashMap::new(),
+            timer: None,
+        }
+    }
+
+    /// The maximum number of requests queued per peer.
+    pub(crate) fn max_requests(&self) -> usize {
+        self.max_requests
+    }
+
+    /// Keeps a copy of a request about to be handed to a connection.
+    pub(crate) fn track(&mut self, request: &mut RequestProtocol<TCodec>) {
+        let negotiated = Arc::new(AtomicBool::new(false));
+        request.negotiated = Some(negotiated.clone());
+        let copy = RequestProtocol {
+            codec: request.codec.clone(),
+            protocols: request.protocols.clone(),
+            request_id: request.request_id,
+            request: (self.clone)(&request.request),
+            negotiated: None,
+        };
+        self.in_flight
+            .insert(request.request_id, (copy, negotiated));
+    }
+
+    /// Forgets a request that completed on its connection.
+    pub(crate) fn complete(&mut self, request_id: &RequestId) {
+        self.in_flight.remove(request_id);
+    }
+
+    /// Returns the copy of a request whose connection closed,
+    /// unless the request may have been transmitted.
+    pub(crate) fn take_untransmitted(
+        &mut self,
+        request_id: &RequestId,
+    ) -> Option<RequestProtocol<TCodec>> {
+        let (request, negotiated) = self.in_flight.remove(request_id)?;
+        (!negotiated.load(Ordering::Relaxed)).then_some(request)
+    }
+
+    /// Starts the expiry of a request queued again.
+    pub(crate) fn queue(&mut self, request_id: RequestId) {
+        self.deadlines
+            .insert(request_id, Instant::now() + self.expiry);
+        if self.timer.is_none() {
+            self.timer = Some(Delay::new(self.expiry));
+        }
+    }
+
+    /// Returns whether the request is queued again.
+    pub(crate) fn is_queued(&self, request_id: &RequestId) -> bool {
+        self.deadlines.contains_key(request_id)
+    }
+
+    /// Stops the expiry of a request, returning whether it was queued again.
+    pub(crate) fn dequeue(&mut self, request_id: &RequestId) -> bool {
+        self.deadlines.remove(request_id).is_some()
+    }
+
+    /// Polls for requests queued again whose deadline passed.
+    pub(crate) fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<Vec<RequestId>> {
+        match self.timer.as_mut().map(|timer| timer.poll_unpin(cx)) {
+            Some(Poll::Ready(())) => {}
+            _ => return Poll::Pending,
+        }
+
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        self.deadlines.retain(|id, deadline| {
+            if *deadline <= now {
+                expired.push(*id);
+                return false;
+            }
+            true
+        });
+        self.timer = self
+            .deadlines
+            .values()
+            .min()
+            .map(|next| Delay::new(next.saturating_duration_since(now)));
+
+        Poll::Ready(expired)
+    }
+}
@@ -29,10 +29,15 @@ use libp2p_core::{
 use libp2p_identity::PeerId;
 use libp2p_request_response as request_response;
 use libp2p_request_response::ProtocolSupport;
-use libp2p_swarm::{Swarm, SwarmEvent};
+use libp2p_swarm::{dial_opts::DialOpts, Swarm, SwarmEvent};
 use libp2p_swarm_test::SwarmExt;
 use rand::{self, Rng};
-use std::{io, iter};
+use std::{
+    io, iter,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 #[async_std::test]
 async fn is_response_outbound() {
@@ -40,7 +45,10 @@ async fn is_response_outbound() {
     let ping = Ping("ping".to_string().into_bytes());
     let offline_peer = PeerId::random();
 
-    let protocols = iter::once((PingProtocol(), request_response::ProtocolSupport::Full));
+    let protocols = iter::once((
+        PingProtocol("/ping/1"),
+        request_response::ProtocolSupport::Full,
+    ));
     let cfg = request_response::Config::default();
 
     let mut swarm1 =
@@ -83,7 +91,7 @@ async fn ping_protocol() {
     let ping = Ping("ping".to_string().into_bytes());
     let pong = Pong("pong".to_string().into_bytes());
 
-    let protocols = iter::once((PingProtocol(), ProtocolSupport::Full));
+    let protocols = iter::once((PingProtocol("/ping/1"), ProtocolSupport::Full));
     let cfg = request_response::Config::default();
 
     let mut swarm1 = Swarm::new_ephemeral(|_| {
@@ -174,7 +182,7 @@ async fn ping_protocol() {
 async fn emits_inbound_connection_closed_failure() {
     let ping = Ping("ping".to_string().into_bytes());
 
-    let protocols = iter::once((PingProtocol(), ProtocolSupport::Full));
+    let protocols = iter::once((PingProtocol("/ping/1"), ProtocolSupport::Full));
     let cfg = request_response::Config::default();
 
     let mut swarm1 = Swarm::new_ephemeral(|_| {
@@ -228,6 +236,66 @@ async fn emits_inbound_connection_closed_failure() {
     }
 }
 
+#[async_std::test]
+async fn untransmitted_requests_are_requeued_after_disconnect() {
+    let ping = Ping("ping".to_string().into_bytes());
+    let pong = Pong("pong".to_string().into_bytes());
+
+    let protocols = iter::once((PingProtocol("/ping/1"), ProtocolSupport::Full));
+    let cfg = request_response::Config::default();
+
+    let mut swarm1 = Swarm::new_ephemeral(|_| {
+        request_response::Behaviour::new(PingCodec(), protocols.clone(), cfg.clone())
+    });
+    let peer1_id = *swarm1.local_peer_id();
+    let mut swarm2 =
+        Swarm::new_ephemeral(|_| request_response::Behaviour::new(PingCodec(), protocols, cfg));
+    swarm2
+        .behaviour_mut()
+        .enable_request_requeue(10, Duration::from_secs(10));
+
+    let (memory_addr, _) = swarm1.listen().await;
+    swarm2.behaviour_mut().add_address(&peer1_id, memory_addr);
+    swarm2.connect(&mut swarm1).await;
+
+    // The connection closes before the request reaches it.
+    let request_id = swarm2.behaviour_mut().send_request(&peer1_id, ping.clone());
+    swarm2.disconnect_peer_id(peer1_id).unwrap();
+
+    loop {
+        futures::select!(
+            event = swarm1.select_next_some() => match event {
+                SwarmEvent::Behaviour(request_response::Event::Message {
+                    message: request_response::Message::Request { request, channel, .. },
+                    ..
+                }) => {
+                    assert_eq!(&request, &ping);
+                    swarm1
+                        .behaviour_mut()
+                        .send_response(channel, pong.clone())
+                        .unwrap();
+                },
+                SwarmEvent::Behaviour(request_response::Event::ResponseSent { .. }) => {}
+                SwarmEvent::Behaviour(ev) => panic!("Peer1: Unexpected event: {ev:?}"),
+                _ => {}
+            },
+            event = swarm2.select_next_some() => match event {
+                SwarmEvent::Behaviour(request_response::Event::Message {
+                    peer,
+                    message: request_response::Message::Response { request_id: id, response },
+                }) => {
+                    assert_eq!(peer, peer1_id);
+                    assert_eq!(id, request_id);
+                    assert_eq!(response, pong);
+                    break;
+                },
+                SwarmEvent::Behaviour(ev) => panic!("Peer2: Unexpected event: {ev:?}"),
+                _ => {}
+            }
+        )
+    }
+}
+
 /// We expect the substream to be properly closed when response channel is dropped.
 /// Since the ping protocol used here expects a response, the sender considers this
 /// early close as a protocol violation which results in the connection being closed.
@@ -237,7 +305,7 @@ async fn emits_inbound_connection_closed_failure() {
 async fn emits_inbound_connection_closed_if_channel_is_dropped() {
     let ping = Ping("ping".to_string().into_bytes());
 
-    let protocols = iter::once((PingProtocol(), ProtocolSupport::Full));
+    let protocols = iter::once((PingProtocol("/ping/1"), ProtocolSupport::Full));
     let cfg = request_response::Config::default();
 
     let mut swarm1 = Swarm::new_ephemeral(|_| {
@@ -284,10 +352,396 @@ async fn emits_inbound_connection_closed_if_channel_is_dropped() {
     assert_eq!(error, request_response::OutboundFailure::ConnectionClosed);
 }
 
+#[async_std::test]
+async fn negotiated_protocol_version() {
+    let ping = Ping("ping".to_string().into_bytes());
+
+    let protocols = [
+        (PingProtocol("/ping/1"), ProtocolSupport::Full),
+        (PingProtocol("/ping/2"), ProtocolSupport::Full),
+    ];
+    let cfg = request_response::Config::default();
+
+    let mut swarm1 = Swarm::new_ephemeral(|_| {
+        request_response::Behaviour::new(PingCodec(), protocols.clone(), cfg.clone())
+    });
+    let peer1_id = *swarm1.local_peer_id();
+    let mut swarm2 =
+        Swarm::new_ephemeral(|_| request_response::Behaviour::new(PingCodec(), protocols, cfg));
+
+    swarm1.listen().await;
+    swarm2.connect(&mut swarm1).await;
+
+    let expected_ping = ping.clone();
+    let peer1 = async move {
+        loop {
+            if let Ok(request_response::Event::Message {
+                message:
+                    request_response::Message::Request {
+                        request, channel, ..
+                    },
+                ..
+            }) = swarm1.next_swarm_event().await.try_into_behaviour_event()
+            {
+                // Respond with the negotiated protocol.
+                assert_eq!(request, expected_ping);
+                let response = Pong(channel.protocol().to_vec());
+                swarm1
+                    .behaviour_mut()
+                    .send_response(channel, response)
+                    .unwrap();
+            }
+        }
+    };
+    async_std::task::spawn(Box::pin(peer1));
+
+    for (protocol, expected) in [
+        (None, Ok("/ping/1")),
+        (Some("/ping/2"), Ok("/ping/2")),
+        (
+            Some("/ping/3"),
+            Err(request_response::OutboundFailure::UnsupportedProtocols),
+        ),
+    ] {
+        let req_id = match protocol {
+            Some(protocol) => swarm2.behaviour_mut().send_request_with_protocol(
+                &peer1_id,
+                ping.clone(),
+                PingProtocol(protocol),
+            ),
+            None => swarm2.behaviour_mut().send_request(&peer1_id, ping.clone()),
+        };
+
+        let result = loop {
+            match swarm2.next_swarm_event().await.try_into_behaviour_event() {
+                Ok(request_response::Event::Message {
+                    message:
+                        request_response::Message::Response {
+                            request_id,
+                            response,
+                        },
+                    ..
+                }) if request_id == req_id => break Ok(response),
+                Ok(request_response::Event::OutboundFailure {
+                    request_id, error, ..
+                }) if request_id == req_id => break Err(error),
+                Ok(e) => panic!("Peer2: Unexpected event: {e:?}"),
+                Err(..) => {}
+            }
+        };
+        assert_eq!(
+            result,
+            expected.map(|protocol| Pong(protocol.as_bytes().to_vec()))
+        );
+    }
+}
+
+#[async_std::test]
+async fn inbound_request_metadata() {
+    let protocols = iter::once((PingProtocol("/ping/1"), ProtocolSupport::Full));
+    let cfg = request_response::Config::default();
+
+    let mut swarm1 = Swarm::new_ephemeral(|_| {
+        request_response::Behaviour::new(PingCodec(), protocols.clone(), cfg.clone())
+    });
+    let mut swarm2 =
+        Swarm::new_ephemeral(|_| request_response::Behaviour::new(PingCodec(), protocols, cfg));
+    let peer2_id = *swarm2.local_peer_id();
+
+    let (listen_addr, _) = swarm1.listen().await;
+
+    // Send a request from the listener to the dialer once connected.
+    let peer1 = async move {
+        loop {
+            if let SwarmEvent::ConnectionEstablished { peer_id, .. } =
+                swarm1.next_swarm_event().await
+            {
+                assert_eq!(peer_id, peer2_id);
+                swarm1
+                    .behaviour_mut()
+                    .send_request(&peer2_id, Ping(b"ping".to_vec()));
+            }
+        }
+    };
+    async_std::task::spawn(Box::pin(peer1));
+
+    let opts = DialOpts::unknown_peer_id()
+        .address(listen_addr.clone())
+        .build();
+    let connection_id = opts.connection_id();
+    swarm2.dial(opts).unwrap();
+    let channel = loop {
+        if let SwarmEvent::Behaviour(request_response::Event::Message {
+            message: request_response::Message::Request { channel, .. },
+            ..
+        }) = swarm2.next_swarm_event().await
+        {
+            break channel;
+        }
+    };
+
+    assert_eq!(channel.connection_id(), connection_id);
+    assert_eq!(channel.remote_address(), Some(&listen_addr));
+    assert_eq!(channel.protocol(), b"/ping/1");
+}
+
+#[async_std::test]
+async fn repeated_requests_are_answered_from_cache() {
+    let _ = env_logger::try_init();
+
+    let cached = Ping(b"cached".to_vec());
+    let uncached = Ping(b"uncached".to_vec());
+
+    let protocols = iter::once((PingProtocol("/ping/1"), ProtocolSupport::Full));
+    let cfg = request_response::Config::default();
+
+    let mut swarm1 = Swarm::new_ephemeral(|_| {
+        request_response::Behaviour::new(PingCodec(), protocols.clone(), cfg.clone())
+    });
+    let peer1_id = *swarm1.local_peer_id();
+    let mut swarm2 =
+        Swarm::new_ephemeral(|_| request_response::Behaviour::new(PingCodec(), protocols, cfg));
+
+    let expected_cached = cached.clone();
+    swarm1.behaviour_mut().enable_response_cache(
+        Duration::from_secs(60),
+        NonZeroUsize::new(16).unwrap(),
+        move |request: &Ping| (request == &expected_cached).then_some(1),
+    );
+
+    swarm1.listen().await;
+    swarm2.connect(&mut swarm1).await;
+
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let received = requests.clone();
+    let peer1 = async move {
+        let mut num_responses = 0;
+        loop {
+            match swarm1.next_swarm_event().await.try_into_behaviour_event() {
+                Ok(request_response::Event::Message {
+                    message:
+                        request_response::Message::Request {
+                            request, channel, ..
+                        },
+                    ..
+                }) => {
+                    num_responses += 1;
+                    received.lock().unwrap().push(request);
+                    swarm1
+                        .behaviour_mut()
+                        .send_response(channel, Pong(vec![num_responses]))
+                        .unwrap();
+                }
+                Ok(request_response::Event::ResponseSent { .. }) => {}
+                Ok(e) => panic!("Peer1: Unexpected event: {e:?}"),
+                Err(..) => {}
+            }
+        }
+    };
+    async_std::task::spawn(Box::pin(peer1));
+
+    assert_eq!(
+        send_ping(&mut swarm2, peer1_id, cached.clone()).await,
+        Pong(vec![1])
+    );
+    assert_eq!(
+        send_ping(&mut swarm2, peer1_id, uncached.clone()).await,
+        Pong(vec![2])
+    );
+    assert_eq!(
+        send_ping(&mut swarm2, peer1_id, cached.clone()).await,
+        Pong(vec![1])
+    );
+    assert_eq!(
+        send_ping(&mut swarm2, peer1_id, uncached.clone()).await,
+        Pong(vec![3])
+    );
+
+    // Only the first of the cached requests reached the application.
+    assert_eq!(
+        *requests.lock().unwrap(),
+        vec![cached, uncached.clone(), uncached]
+    );
+}
+
+#[async_std::test]
+async fn cached_responses_are_not_shared_across_protocols() {
+    let _ = env_logger::try_init();
+
+    let ping = Ping(b"ping".to_vec());
+
+    let protocols = [
+        (PingProtocol("/ping/1"), ProtocolSupport::Full),
+        (PingProtocol("/ping/2"), ProtocolSupport::Full),
+    ];
+    let cfg = request_response::Config::default();
+
+    let mut swarm1 = Swarm::new_ephemeral(|_| {
+        request_response::Behaviour::new(PingCodec(), protocols.clone(), cfg.clone())
+    });
+    let peer1_id = *swarm1.local_peer_id();
+    let mut swarm2 =
+        Swarm::new_ephemeral(|_| request_response::Behaviour::new(PingCodec(), protocols, cfg));
+
+    swarm1.behaviour_mut().enable_response_cache(
+        Duration::from_secs(60),
+        NonZeroUsize::new(16).unwrap(),
+        |_: &Ping| Some(1),
+    );
+
+    swarm1.listen().await;
+    swarm2.connect(&mut swarm1).await;
+
+    let peer1 = async move {
+        loop {
+            if let Ok(request_response::Event::Message {
+                message: request_response::Message::Request { channel, .. },
+                ..
+            }) = swarm1.next_swarm_event().await.try_into_behaviour_event()
+            {
+                // Respond with the negotiated protocol.
+                let response = Pong(channel.protocol().to_vec());
+                swarm1
+                    .behaviour_mut()
+                    .send_response(channel, response)
+                    .unwrap();
+            }
+        }
+    };
+    async_std::task::spawn(Box::pin(peer1));
+
+    for protocol in ["/ping/1", "/ping/1", "/ping/2"] {
+        let req_id = swarm2.behaviour_mut().send_request_with_protocol(
+            &peer1_id,
+            ping.clone(),
+            PingProtocol(protocol),
+        );
+
+        let response = loop {
+            match swarm2.next_swarm_event().await.try_into_behaviour_event() {
+                Ok(request_response::Event::Message {
+                    message:
+                        request_response::Message::Response {
+                            request_id,
+                            response,
+                        },
+                    ..
+                }) if request_id == req_id => break response,
+                Ok(e) => panic!("Peer2: Unexpected event: {e:?}"),
+                Err(..) => {}
+            }
+        };
+        assert_eq!(response, Pong(protocol.as_bytes().to_vec()));
+    }
+}
+
+#[async_std::test]
+async fn request_to_many_peers_aggregates_responses() {
+    let _ = env_logger::try_init();
+
+    let protocols = iter::once((PingProtocol("/ping/1"), ProtocolSupport::Full));
+    let cfg = request_response::Config::default();
+
+    let mut swarm = Swarm::new_ephemeral(|_| {
+        request_response::Behaviour::new(PingCodec(), protocols.clone(), cfg.clone())
+    });
+    let mut responders = Vec::new();
+    for i in 0..2u8 {
+        let mut responder = Swarm::new_ephemeral(|_| {
+            request_response::Behaviour::new(PingCodec(), protocols.clone(), cfg.clone())
+        });
+        responder.listen().await;
+        swarm.connect(&mut responder).await;
+        responders.push(*responder.local_peer_id());
+
+        async_std::task::spawn(async move {
+            loop {
+                if let Ok(request_response::Event::Message {
+                    message: request_response::Message::Request { channel, .. },
+                    ..
+                }) = responder
+                    .next_swarm_event()
+                    .await
+                    .try_into_behaviour_event()
+                {
+                    responder
+                        .behaviour_mut()
+                        .send_response(channel, Pong(vec![i]))
+                        .unwrap();
+                }
+            }
+        });
+    }
+
+    // The request to the offline peer fails, while the quorum is still reached.
+    let offline_peer = PeerId::random();
+    let policy = request_response::AggregationPolicy::quorum(
+        NonZeroUsize::new(2).unwrap(),
+        Duration::from_secs(10),
+    );
+    let broadcast = swarm.behaviour_mut().send_request_to_many(
+        responders.iter().copied().chain(iter::once(offline_peer)),
+        Ping(b"ping".to_vec()),
+        policy,
+    );
+
+    loop {
+        match swarm.next_swarm_event().await.try_into_behaviour_event() {
+            Ok(request_response::Event::BroadcastCompleted {
+                broadcast_id,
+                mut responses,
+                failures,
+                quorum_reached,
+            }) => {
+                assert_eq!(broadcast_id, broadcast);
+                assert!(quorum_reached);
+                responses.sort_by_key(|(_, pong)| pong.0.clone());
+                assert_eq!(
+                    responses,
+                    vec![
+                        (responders[0], Pong(vec![0])),
+                        (responders[1], Pong(vec![1]))
+                    ]
+                );
+                assert!(failures.iter().all(|(peer, _)| *peer == offline_peer));
+                break;
+            }
+            Ok(e) => panic!("Unexpected event: {e:?}"),
+            Err(..) => {}
+        }
+    }
+}
+
+/// Sends a request and waits for its response.
+async fn send_ping(
+    swarm: &mut Swarm<request_response::Behaviour<PingCodec>>,
+    peer: PeerId,
+    request: Ping,
+) -> Pong {
+    let request_id = swarm.behaviour_mut().send_request(&peer, request);
+    loop {
+        match swarm.next_swarm_event().await.try_into_behaviour_event() {
+            Ok(request_response::Event::Message {
+                message:
+                    request_response::Message::Response {
+                        request_id: id,
+                        response,
+                    },
+                ..
+            }) => {
+                assert_eq!(id, request_id);
+                return response;
+            }
+            Ok(e) => panic!("Unexpected event: {e:?}"),
+            Err(..) => {}
+        }
+    }
+}
+
 // Simple Ping-Pong Protocol
 
 #[derive(Debug, Clone)]
-struct PingProtocol();
+struct PingProtocol(&'static str);
 #[derive(Clone)]
 struct PingCodec();
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -297,7 +751,7 @@ struct Pong(Vec<u8>);
 
 impl ProtocolName for PingProtocol {
     fn protocol_name(&self) -> &[u8] {
-        "/ping/1".as_bytes()
+        self.0.as_bytes()
     }
 }
 
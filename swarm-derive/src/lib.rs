@@ -50,7 +50,7 @@ fn build(ast: &DeriveInput) -> TokenStream {
 /// The version for structs
 fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
     let name = &ast.ident;
-    let (_, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
     let BehaviourAttributes {
         prelude_path,
         user_specified_out_event,
@@ -68,6 +68,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
     let peer_id = quote! { #prelude_path::PeerId };
     let connection_id = quote! { #prelude_path::ConnectionId };
     let poll_parameters = quote! { #prelude_path::PollParameters };
+    let nested_poll_parameters = quote! { #prelude_path::NestedPollParameters };
     let from_swarm = quote! { #prelude_path::FromSwarm };
     let connection_established = quote! { #prelude_path::ConnectionEstablished };
     let address_change = quote! { #prelude_path::AddressChange };
@@ -79,6 +80,9 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
     let expired_listen_addr = quote! { #prelude_path::ExpiredListenAddr };
     let new_external_addr = quote! { #prelude_path::NewExternalAddr };
     let expired_external_addr = quote! { #prelude_path::ExpiredExternalAddr };
+    let peer_protocols_changed = quote! { #prelude_path::PeerProtocolsChanged };
+    let peer_listen_addrs_changed = quote! { #prelude_path::PeerListenAddrsChanged };
+    let nat_status_changed = quote! { #prelude_path::NatStatusChanged };
     let listener_error = quote! { #prelude_path::ListenerError };
     let listener_closed = quote! { #prelude_path::ListenerClosed };
     let t_handler = quote! { #prelude_path::THandler };
@@ -87,14 +91,6 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
     let endpoint = quote! { #prelude_path::Endpoint };
     let connection_denied = quote! { #prelude_path::ConnectionDenied };
 
-    // Build the generics.
-    let impl_generics = {
-        let tp = ast.generics.type_params();
-        let lf = ast.generics.lifetimes();
-        let cst = ast.generics.const_params();
-        quote! {<#(#lf,)* #(#tp,)* #(#cst,)*>}
-    };
-
     let (out_event_name, out_event_definition, out_event_from_clauses) = {
         // If we find a `#[behaviour(out_event = "Foo")]` attribute on the
         // struct, we set `Foo` as the out event. If not, the `OutEvent` is
@@ -151,7 +147,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
 
                     let where_clause = {
                         if let Some(where_clause) = where_clause {
-                            if where_clause.predicates.trailing_punct() {
+                            if where_clause.predicates.empty_or_trailing() {
                                 Some(quote! {#where_clause #(#additional),* })
                             } else {
                                 Some(quote! {#where_clause, #(#additional),*})
@@ -172,7 +168,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
 
                     Some(quote! {
                         #[doc = #msg]
-                        #visibility enum #enum_name #ty_generics
+                        #visibility enum #enum_name #impl_generics
                             #where_clause
                         {
                             #(#enum_variants),*
@@ -208,7 +204,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
             .collect::<Vec<_>>();
 
         if let Some(where_clause) = where_clause {
-            if where_clause.predicates.trailing_punct() {
+            if where_clause.predicates.empty_or_trailing() {
                 Some(quote! {#where_clause #(#additional),* })
             } else {
                 Some(quote! {#where_clause, #(#additional),*})
@@ -233,6 +229,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                         endpoint,
                         failed_addresses,
                         other_established,
+                        user_data,
                     }));
                 },
                 None => quote! {
@@ -242,6 +239,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                         endpoint,
                         failed_addresses,
                         other_established,
+                        user_data,
                     }));
                 },
             })
@@ -301,6 +299,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                             endpoint,
                             handler,
                             remaining_established,
+                            reason,
                         }));
                     },
                     None => quote! {
@@ -310,6 +309,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                             endpoint,
                             handler,
                             remaining_established,
+                            reason,
                         }));
                     },
                 };
@@ -333,6 +333,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                     peer_id,
                     connection_id,
                     error,
+                    user_data,
                 }));
             },
             None => quote! {
@@ -340,6 +341,7 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                     peer_id,
                     connection_id,
                     error,
+                    user_data,
                 }));
             },
         });
@@ -402,12 +404,14 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 self.#i.on_swarm_event(#from_swarm::NewListenAddr(#new_listen_addr {
                         listener_id,
                         addr,
+                        transport,
                     }));
                 },
                 None => quote! {
                 self.#field_n.on_swarm_event(#from_swarm::NewListenAddr(#new_listen_addr {
                         listener_id,
                         addr,
+                        transport,
                     }));
                 },
             })
@@ -425,12 +429,14 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 self.#i.on_swarm_event(#from_swarm::ExpiredListenAddr(#expired_listen_addr {
                         listener_id,
                         addr,
+                        transport,
                     }));
                 },
                 None => quote! {
                 self.#field_n.on_swarm_event(#from_swarm::ExpiredListenAddr(#expired_listen_addr {
                         listener_id,
                         addr,
+                        transport,
                     }));
                 },
             })
@@ -478,6 +484,77 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
             })
     };
 
+    // Build the list of statements to put in the body of `on_swarm_event()`
+    // for the `FromSwarm::PeerProtocolsChanged` variant.
+    let on_peer_protocols_changed_stmts = {
+        data_struct
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(field_n, field)| match field.ident {
+                Some(ref i) => quote! {
+                self.#i.on_swarm_event(#from_swarm::PeerProtocolsChanged(#peer_protocols_changed {
+                        peer_id,
+                        added,
+                        removed,
+                    }));
+                },
+                None => quote! {
+                self.#field_n.on_swarm_event(#from_swarm::PeerProtocolsChanged(#peer_protocols_changed {
+                        peer_id,
+                        added,
+                        removed,
+                    }));
+                },
+            })
+    };
+
+    // Build the list of statements to put in the body of `on_swarm_event()`
+    // for the `FromSwarm::PeerListenAddrsChanged` variant.
+    let on_peer_listen_addrs_changed_stmts = {
+        data_struct
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(field_n, field)| match field.ident {
+                Some(ref i) => quote! {
+                self.#i.on_swarm_event(#from_swarm::PeerListenAddrsChanged(#peer_listen_addrs_changed {
+                        peer_id,
+                        listen_addrs,
+                    }));
+                },
+                None => quote! {
+                self.#field_n.on_swarm_event(#from_swarm::PeerListenAddrsChanged(#peer_listen_addrs_changed {
+                        peer_id,
+                        listen_addrs,
+                    }));
+                },
+            })
+    };
+
+    // Build the list of statements to put in the body of `on_swarm_event()`
+    // for the `FromSwarm::NatStatusChanged` variant.
+    let on_nat_status_changed_stmts = {
+        data_struct
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(field_n, field)| match field.ident {
+                Some(ref i) => quote! {
+                self.#i.on_swarm_event(#from_swarm::NatStatusChanged(#nat_status_changed {
+                        old,
+                        new,
+                    }));
+                },
+                None => quote! {
+                self.#field_n.on_swarm_event(#from_swarm::NatStatusChanged(#nat_status_changed {
+                        old,
+                        new,
+                    }));
+                },
+            })
+    };
+
     // Build the list of statements to put in the body of `on_swarm_event()`
     // for the `FromSwarm::ListenerError` variant.
     let on_listener_error_stmts = {
@@ -715,6 +792,18 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 std::task::Poll::Ready(#network_behaviour_action::ReportObservedAddr { address, score }) => {
                     return std::task::Poll::Ready(#network_behaviour_action::ReportObservedAddr { address, score });
                 }
+                std::task::Poll::Ready(#network_behaviour_action::ReportPeerProtocols { peer_id, added, removed }) => {
+                    return std::task::Poll::Ready(#network_behaviour_action::ReportPeerProtocols { peer_id, added, removed });
+                }
+                std::task::Poll::Ready(#network_behaviour_action::ReportPeerListenAddrs { peer_id, listen_addrs }) => {
+                    return std::task::Poll::Ready(#network_behaviour_action::ReportPeerListenAddrs { peer_id, listen_addrs });
+                }
+                std::task::Poll::Ready(#network_behaviour_action::ReportNatStatus(status)) => {
+                    return std::task::Poll::Ready(#network_behaviour_action::ReportNatStatus(status));
+                }
+                std::task::Poll::Ready(#network_behaviour_action::AbortDial { connection_id }) => {
+                    return std::task::Poll::Ready(#network_behaviour_action::AbortDial { connection_id });
+                }
                 std::task::Poll::Ready(#network_behaviour_action::CloseConnection { peer_id, connection }) => {
                     return std::task::Poll::Ready(#network_behaviour_action::CloseConnection { peer_id, connection });
                 }
@@ -723,6 +812,44 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
         }
     });
 
+    // The content of `poll()`.
+    //
+    // The child polled first rotates with every call, such that a child that always has actions
+    // queued cannot starve the ones after it. The children get a poll count of their own, such
+    // that nested structs rotate independently of the outer one.
+    let poll_body = {
+        let fields_n = data_struct.fields.len();
+        let field_indices = 0..fields_n;
+
+        if fields_n == 0 {
+            quote! {}
+        } else if fields_n == 1 {
+            // A single child has nothing to rotate with.
+            quote! { #(#poll_stmts)* }
+        } else {
+            quote! {
+                let first = (#poll_parameters::poll_count(poll_params) % #fields_n as u64) as usize;
+                let poll_params = &mut #nested_poll_parameters::new(poll_params, #fields_n);
+                for i in 0..#fields_n {
+                    match (first + i) % #fields_n {
+                        #(#field_indices => { #poll_stmts })*
+                        _ => unreachable!("index is less than the number of fields"),
+                    }
+                }
+            }
+        }
+    };
+
+    // The content of `queued_actions()`.
+    let queued_actions_stmts = data_struct
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(field_n, field)| match field.ident {
+            Some(ref i) => quote! { #trait_to_impl::queued_actions(&self.#i) },
+            None => quote! { #trait_to_impl::queued_actions(&self.#field_n) },
+        });
+
     let out_event_reference = if out_event_definition.is_some() {
         quote! { #out_event_name #ty_generics }
     } else {
@@ -797,23 +924,27 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
 
             fn poll(&mut self, cx: &mut std::task::Context, poll_params: &mut impl #poll_parameters) -> std::task::Poll<#network_behaviour_action<Self::OutEvent, #t_handler_in_event<Self>>> {
                 use #prelude_path::futures::*;
-                #(#poll_stmts)*
+                #poll_body
                 std::task::Poll::Pending
             }
 
+            fn queued_actions(&self) -> usize {
+                0 #(+ #queued_actions_stmts)*
+            }
+
             fn on_swarm_event(&mut self, event: #from_swarm<Self::ConnectionHandler>) {
                 match event {
                     #from_swarm::ConnectionEstablished(
-                        #connection_established { peer_id, connection_id, endpoint, failed_addresses, other_established })
+                        #connection_established { peer_id, connection_id, endpoint, failed_addresses, other_established, user_data })
                     => { #(#on_connection_established_stmts)* }
                     #from_swarm::AddressChange(
                         #address_change { peer_id, connection_id, old, new })
                     => { #(#on_address_change_stmts)* }
                     #from_swarm::ConnectionClosed(
-                        #connection_closed { peer_id, connection_id, endpoint, handler: handlers, remaining_established })
+                        #connection_closed { peer_id, connection_id, endpoint, handler: handlers, remaining_established, reason })
                     => { #(#on_connection_closed_stmts)* }
                     #from_swarm::DialFailure(
-                        #dial_failure { peer_id, connection_id, error })
+                        #dial_failure { peer_id, connection_id, error, user_data })
                     => { #(#on_dial_failure_stmts)* }
                     #from_swarm::ListenFailure(
                         #listen_failure { local_addr, send_back_addr, connection_id, error })
@@ -822,10 +953,10 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                         #new_listener { listener_id })
                     => { #(#on_new_listener_stmts)* }
                     #from_swarm::NewListenAddr(
-                        #new_listen_addr { listener_id, addr })
+                        #new_listen_addr { listener_id, addr, transport })
                     => { #(#on_new_listen_addr_stmts)* }
                     #from_swarm::ExpiredListenAddr(
-                        #expired_listen_addr { listener_id, addr })
+                        #expired_listen_addr { listener_id, addr, transport })
                     => { #(#on_expired_listen_addr_stmts)* }
                     #from_swarm::NewExternalAddr(
                         #new_external_addr { addr })
@@ -833,6 +964,15 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                     #from_swarm::ExpiredExternalAddr(
                         #expired_external_addr { addr })
                     => { #(#on_expired_external_addr_stmts)* }
+                    #from_swarm::PeerProtocolsChanged(
+                        #peer_protocols_changed { peer_id, added, removed })
+                    => { #(#on_peer_protocols_changed_stmts)* }
+                    #from_swarm::PeerListenAddrsChanged(
+                        #peer_listen_addrs_changed { peer_id, listen_addrs })
+                    => { #(#on_peer_listen_addrs_changed_stmts)* }
+                    #from_swarm::NatStatusChanged(
+                        #nat_status_changed { old, new })
+                    => { #(#on_nat_status_changed_stmts)* }
                     #from_swarm::ListenerError(
                         #listener_error { listener_id, err })
                     => { #(#on_listener_error_stmts)* }
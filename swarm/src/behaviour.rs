@@ -18,6 +18,7 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+pub mod dynamic;
 mod either;
 mod external_addresses;
 mod listen_addresses;
@@ -31,12 +32,15 @@ use crate::dial_opts::DialOpts;
 #[allow(deprecated)]
 use crate::handler::IntoConnectionHandler;
 use crate::{
-    AddressRecord, AddressScore, ConnectionDenied, DialError, ListenError, THandler,
+    AddressRecord, AddressScore, ConnectionDenied, DialError, ListenError, NatStatus, THandler,
     THandlerInEvent, THandlerOutEvent,
 };
-use libp2p_core::{transport::ListenerId, ConnectedPoint, Endpoint, Multiaddr};
+use libp2p_core::{
+    transport::{ListenerId, TransportKind},
+    ConnectedPoint, Endpoint, Multiaddr,
+};
 use libp2p_identity::PeerId;
-use std::{task::Context, task::Poll};
+use std::{any::Any, task::Context, task::Poll};
 
 /// A [`NetworkBehaviour`] defines the behaviour of the local node on the network.
 ///
@@ -277,6 +281,15 @@ pub trait NetworkBehaviour: 'static {
         cx: &mut Context<'_>,
         params: &mut impl PollParameters,
     ) -> Poll<ToSwarm<Self::OutEvent, THandlerInEvent<Self>>>;
+
+    /// Returns the number of actions that this behaviour has queued to be returned from
+    /// [`NetworkBehaviour::poll`].
+    ///
+    /// This is meant for introspection only, e.g. to find a behaviour that is falling behind
+    /// under load. Defaults to `0` for behaviours that don't report it.
+    fn queued_actions(&self) -> usize {
+        0
+    }
 }
 
 /// Parameters passed to `poll()`, that the `NetworkBehaviour` has access to.
@@ -316,6 +329,72 @@ pub trait PollParameters {
         note = "Pass the node's `PeerId` into the behaviour instead."
     )]
     fn local_peer_id(&self) -> &PeerId;
+
+    /// Returns the number of times the behaviour has been polled before.
+    ///
+    /// Behaviours composed of several others use it to rotate the order in which they poll them,
+    /// so that none of them is starved by the ones polled before it.
+    fn poll_count(&self) -> u64 {
+        0
+    }
+}
+
+/// [`PollParameters`] a behaviour composed of several others passes to each of them.
+///
+/// Reports a [`PollParameters::poll_count`] of its own to the composed behaviours, such that
+/// those composed of several others in turn rotate their poll order independently of the outer
+/// behaviour. Used by the `NetworkBehaviour` derive macro.
+#[doc(hidden)]
+pub struct NestedPollParameters<'a, P> {
+    inner: &'a mut P,
+    poll_count: u64,
+}
+
+impl<'a, P> NestedPollParameters<'a, P>
+where
+    P: PollParameters,
+{
+    /// Wraps the [`PollParameters`] of a behaviour polling `n` composed behaviours, starting with
+    /// a different one on every call.
+    ///
+    /// A composed behaviour is polled first once every `n` calls, thus its poll count advances
+    /// once every `n` calls as well.
+    pub fn new(inner: &'a mut P, n: usize) -> Self {
+        let poll_count = inner.poll_count() / n as u64;
+        Self { inner, poll_count }
+    }
+}
+
+impl<'a, P> PollParameters for NestedPollParameters<'a, P>
+where
+    P: PollParameters,
+{
+    type SupportedProtocolsIter = P::SupportedProtocolsIter;
+    type ListenedAddressesIter = P::ListenedAddressesIter;
+    type ExternalAddressesIter = P::ExternalAddressesIter;
+
+    fn supported_protocols(&self) -> Self::SupportedProtocolsIter {
+        self.inner.supported_protocols()
+    }
+
+    #[allow(deprecated)]
+    fn listened_addresses(&self) -> Self::ListenedAddressesIter {
+        self.inner.listened_addresses()
+    }
+
+    #[allow(deprecated)]
+    fn external_addresses(&self) -> Self::ExternalAddressesIter {
+        self.inner.external_addresses()
+    }
+
+    #[allow(deprecated)]
+    fn local_peer_id(&self) -> &PeerId {
+        self.inner.local_peer_id()
+    }
+
+    fn poll_count(&self) -> u64 {
+        self.poll_count
+    }
 }
 
 #[deprecated(note = "Use `ToSwarm` instead.")]
@@ -379,6 +458,39 @@ pub enum ToSwarm<TOutEvent, TInEvent> {
         score: AddressScore,
     },
 
+    /// Informs the `Swarm` that the set of protocols supported by a remote peer changed, e.g. as
+    /// learned via the identify protocol.
+    ///
+    /// The `Swarm` passes the change on to all [`NetworkBehaviour`]s via
+    /// [`FromSwarm::PeerProtocolsChanged`].
+    ReportPeerProtocols {
+        /// The peer whose protocols changed.
+        peer_id: PeerId,
+        /// The protocols the peer started supporting.
+        added: Vec<String>,
+        /// The protocols the peer stopped supporting.
+        removed: Vec<String>,
+    },
+
+    /// Informs the `Swarm` that the listen addresses of a remote peer changed, e.g. as learned
+    /// via the identify protocol.
+    ///
+    /// The `Swarm` passes the change on to all [`NetworkBehaviour`]s via
+    /// [`FromSwarm::PeerListenAddrsChanged`].
+    ReportPeerListenAddrs {
+        /// The peer whose listen addresses changed.
+        peer_id: PeerId,
+        /// All current listen addresses of the peer.
+        listen_addrs: Vec<Multiaddr>,
+    },
+
+    /// Informs the `Swarm` about the reachability of the local node behind a NAT, e.g. as
+    /// determined by AutoNAT.
+    ///
+    /// The status is reported via [`NodeStatus::nat_status`](crate::NodeStatus::nat_status) and
+    /// passed on to all [`NetworkBehaviour`]s via [`FromSwarm::NatStatusChanged`] if it changed.
+    ReportNatStatus(NatStatus),
+
     /// Instructs the `Swarm` to initiate a graceful close of one or all connections
     /// with the given peer.
     ///
@@ -395,6 +507,16 @@ pub enum ToSwarm<TOutEvent, TInEvent> {
         /// Whether to close a specific or all connections to the given peer.
         connection: CloseConnection,
     },
+
+    /// Instructs the `Swarm` to abort a pending dial, e.g. one initiated via [`ToSwarm::Dial`]
+    /// with [`DialOpts::connection_id`].
+    ///
+    /// The dial fails with [`DialError::Aborted`](crate::DialError::Aborted), reported via
+    /// [`FromSwarm::DialFailure`]. Ignored if there is no such pending dial.
+    AbortDial {
+        /// The id of the connection being dialed.
+        connection_id: ConnectionId,
+    },
 }
 
 impl<TOutEvent, TInEventOld> ToSwarm<TOutEvent, TInEventOld> {
@@ -418,6 +540,23 @@ impl<TOutEvent, TInEventOld> ToSwarm<TOutEvent, TInEventOld> {
             ToSwarm::ReportObservedAddr { address, score } => {
                 ToSwarm::ReportObservedAddr { address, score }
             }
+            ToSwarm::ReportPeerProtocols {
+                peer_id,
+                added,
+                removed,
+            } => ToSwarm::ReportPeerProtocols {
+                peer_id,
+                added,
+                removed,
+            },
+            ToSwarm::ReportPeerListenAddrs {
+                peer_id,
+                listen_addrs,
+            } => ToSwarm::ReportPeerListenAddrs {
+                peer_id,
+                listen_addrs,
+            },
+            ToSwarm::ReportNatStatus(status) => ToSwarm::ReportNatStatus(status),
             ToSwarm::CloseConnection {
                 peer_id,
                 connection,
@@ -425,6 +564,7 @@ impl<TOutEvent, TInEventOld> ToSwarm<TOutEvent, TInEventOld> {
                 peer_id,
                 connection,
             },
+            ToSwarm::AbortDial { connection_id } => ToSwarm::AbortDial { connection_id },
         }
     }
 }
@@ -447,6 +587,23 @@ impl<TOutEvent, THandlerIn> ToSwarm<TOutEvent, THandlerIn> {
             ToSwarm::ReportObservedAddr { address, score } => {
                 ToSwarm::ReportObservedAddr { address, score }
             }
+            ToSwarm::ReportPeerProtocols {
+                peer_id,
+                added,
+                removed,
+            } => ToSwarm::ReportPeerProtocols {
+                peer_id,
+                added,
+                removed,
+            },
+            ToSwarm::ReportPeerListenAddrs {
+                peer_id,
+                listen_addrs,
+            } => ToSwarm::ReportPeerListenAddrs {
+                peer_id,
+                listen_addrs,
+            },
+            ToSwarm::ReportNatStatus(status) => ToSwarm::ReportNatStatus(status),
             ToSwarm::CloseConnection {
                 peer_id,
                 connection,
@@ -454,6 +611,7 @@ impl<TOutEvent, THandlerIn> ToSwarm<TOutEvent, THandlerIn> {
                 peer_id,
                 connection,
             },
+            ToSwarm::AbortDial { connection_id } => ToSwarm::AbortDial { connection_id },
         }
     }
 }
@@ -517,6 +675,16 @@ pub enum FromSwarm<'a, Handler: IntoConnectionHandler> {
     NewExternalAddr(NewExternalAddr<'a>),
     /// Informs the behaviour that an external address was removed.
     ExpiredExternalAddr(ExpiredExternalAddr<'a>),
+    /// Informs the behaviour that the protocols supported by a remote peer changed, as reported
+    /// via [`ToSwarm::ReportPeerProtocols`].
+    PeerProtocolsChanged(PeerProtocolsChanged<'a>),
+    /// Informs the behaviour that the listen addresses of a remote peer changed, as reported via
+    /// [`ToSwarm::ReportPeerListenAddrs`].
+    PeerListenAddrsChanged(PeerListenAddrsChanged<'a>),
+    /// Informs the behaviour that the reachability of the local node behind a NAT changed, as
+    /// reported via [`ToSwarm::ReportNatStatus`] or set via
+    /// [`Swarm::set_nat_status`](crate::Swarm::set_nat_status).
+    NatStatusChanged(NatStatusChanged<'a>),
 }
 
 /// [`FromSwarm`] variant that informs the behaviour about a newly established connection to a peer.
@@ -527,6 +695,8 @@ pub struct ConnectionEstablished<'a> {
     pub endpoint: &'a ConnectedPoint,
     pub failed_addresses: &'a [Multiaddr],
     pub other_established: usize,
+    /// The data attached to the dial via [`DialOpts::with_user_data`], if any.
+    pub user_data: Option<&'a (dyn Any + Send)>,
 }
 
 /// [`FromSwarm`] variant that informs the behaviour about a closed connection to a peer.
@@ -541,6 +711,9 @@ pub struct ConnectionClosed<'a, Handler: IntoConnectionHandler> {
     pub endpoint: &'a ConnectedPoint,
     pub handler: <Handler as IntoConnectionHandler>::Handler,
     pub remaining_established: usize,
+    /// The reason given to [`Swarm::close_connection`](crate::Swarm::close_connection), if the
+    /// connection was closed that way.
+    pub reason: Option<&'a str>,
 }
 
 /// [`FromSwarm`] variant that informs the behaviour that the [`ConnectedPoint`] of an existing
@@ -560,6 +733,8 @@ pub struct DialFailure<'a> {
     pub peer_id: Option<PeerId>,
     pub error: &'a DialError,
     pub connection_id: ConnectionId,
+    /// The data attached to the dial via [`DialOpts::with_user_data`], if any.
+    pub user_data: Option<&'a (dyn Any + Send)>,
 }
 
 /// [`FromSwarm`] variant that informs the behaviour that an error
@@ -587,6 +762,11 @@ pub struct NewListener {
 pub struct NewListenAddr<'a> {
     pub listener_id: ListenerId,
     pub addr: &'a Multiaddr,
+    /// The kind of transport listening on the address.
+    ///
+    /// Inferred from the address, see [`TransportKind::from_address`], rather than reported by
+    /// the listener.
+    pub transport: TransportKind,
 }
 
 /// [`FromSwarm`] variant that informs the behaviour that a multiaddr
@@ -596,6 +776,11 @@ pub struct NewListenAddr<'a> {
 pub struct ExpiredListenAddr<'a> {
     pub listener_id: ListenerId,
     pub addr: &'a Multiaddr,
+    /// The kind of transport that was listening on the address.
+    ///
+    /// Inferred from the address, see [`TransportKind::from_address`], rather than reported by
+    /// the listener.
+    pub transport: TransportKind,
 }
 
 /// [`FromSwarm`] variant that informs the behaviour that a listener experienced an error.
@@ -625,6 +810,34 @@ pub struct ExpiredExternalAddr<'a> {
     pub addr: &'a Multiaddr,
 }
 
+/// [`FromSwarm`] variant that informs the behaviour that the protocols supported by a remote peer
+/// changed.
+#[derive(Clone, Copy)]
+pub struct PeerProtocolsChanged<'a> {
+    pub peer_id: PeerId,
+    /// The protocols the peer started supporting.
+    pub added: &'a [String],
+    /// The protocols the peer stopped supporting.
+    pub removed: &'a [String],
+}
+
+/// [`FromSwarm`] variant that informs the behaviour that the listen addresses of a remote peer
+/// changed.
+#[derive(Clone, Copy)]
+pub struct PeerListenAddrsChanged<'a> {
+    pub peer_id: PeerId,
+    /// All current listen addresses of the peer.
+    pub listen_addrs: &'a [Multiaddr],
+}
+
+/// [`FromSwarm`] variant that informs the behaviour that the reachability of the local node behind
+/// a NAT changed.
+#[derive(Clone, Copy)]
+pub struct NatStatusChanged<'a> {
+    pub old: &'a NatStatus,
+    pub new: &'a NatStatus,
+}
+
 #[allow(deprecated)]
 impl<'a, Handler: IntoConnectionHandler> FromSwarm<'a, Handler> {
     fn map_handler<NewHandler>(
@@ -656,12 +869,14 @@ impl<'a, Handler: IntoConnectionHandler> FromSwarm<'a, Handler> {
                 endpoint,
                 handler,
                 remaining_established,
+                reason,
             }) => Some(FromSwarm::ConnectionClosed(ConnectionClosed {
                 peer_id,
                 connection_id,
                 endpoint,
                 handler: map_handler(handler)?,
                 remaining_established,
+                reason,
             })),
             FromSwarm::ConnectionEstablished(ConnectionEstablished {
                 peer_id,
@@ -669,12 +884,14 @@ impl<'a, Handler: IntoConnectionHandler> FromSwarm<'a, Handler> {
                 endpoint,
                 failed_addresses,
                 other_established,
+                user_data,
             }) => Some(FromSwarm::ConnectionEstablished(ConnectionEstablished {
                 peer_id,
                 connection_id,
                 endpoint,
                 failed_addresses,
                 other_established,
+                user_data,
             })),
             FromSwarm::AddressChange(AddressChange {
                 peer_id,
@@ -691,10 +908,12 @@ impl<'a, Handler: IntoConnectionHandler> FromSwarm<'a, Handler> {
                 peer_id,
                 error,
                 connection_id,
+                user_data,
             }) => Some(FromSwarm::DialFailure(DialFailure {
                 peer_id,
                 error,
                 connection_id,
+                user_data,
             })),
             FromSwarm::ListenFailure(ListenFailure {
                 local_addr,
@@ -710,18 +929,24 @@ impl<'a, Handler: IntoConnectionHandler> FromSwarm<'a, Handler> {
             FromSwarm::NewListener(NewListener { listener_id }) => {
                 Some(FromSwarm::NewListener(NewListener { listener_id }))
             }
-            FromSwarm::NewListenAddr(NewListenAddr { listener_id, addr }) => {
-                Some(FromSwarm::NewListenAddr(NewListenAddr {
-                    listener_id,
-                    addr,
-                }))
-            }
-            FromSwarm::ExpiredListenAddr(ExpiredListenAddr { listener_id, addr }) => {
-                Some(FromSwarm::ExpiredListenAddr(ExpiredListenAddr {
-                    listener_id,
-                    addr,
-                }))
-            }
+            FromSwarm::NewListenAddr(NewListenAddr {
+                listener_id,
+                addr,
+                transport,
+            }) => Some(FromSwarm::NewListenAddr(NewListenAddr {
+                listener_id,
+                addr,
+                transport,
+            })),
+            FromSwarm::ExpiredListenAddr(ExpiredListenAddr {
+                listener_id,
+                addr,
+                transport,
+            }) => Some(FromSwarm::ExpiredListenAddr(ExpiredListenAddr {
+                listener_id,
+                addr,
+                transport,
+            })),
             FromSwarm::ListenerError(ListenerError { listener_id, err }) => {
                 Some(FromSwarm::ListenerError(ListenerError { listener_id, err }))
             }
@@ -738,6 +963,25 @@ impl<'a, Handler: IntoConnectionHandler> FromSwarm<'a, Handler> {
             FromSwarm::ExpiredExternalAddr(ExpiredExternalAddr { addr }) => {
                 Some(FromSwarm::ExpiredExternalAddr(ExpiredExternalAddr { addr }))
             }
+            FromSwarm::PeerProtocolsChanged(PeerProtocolsChanged {
+                peer_id,
+                added,
+                removed,
+            }) => Some(FromSwarm::PeerProtocolsChanged(PeerProtocolsChanged {
+                peer_id,
+                added,
+                removed,
+            })),
+            FromSwarm::PeerListenAddrsChanged(PeerListenAddrsChanged {
+                peer_id,
+                listen_addrs,
+            }) => Some(FromSwarm::PeerListenAddrsChanged(PeerListenAddrsChanged {
+                peer_id,
+                listen_addrs,
+            })),
+            FromSwarm::NatStatusChanged(NatStatusChanged { old, new }) => {
+                Some(FromSwarm::NatStatusChanged(NatStatusChanged { old, new }))
+            }
         }
     }
 }
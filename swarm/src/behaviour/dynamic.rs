@@ -0,0 +1,719 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::behaviour::{
+    AddressChange, ConnectionClosed, ConnectionEstablished, DialFailure, FromSwarm,
+};
+use crate::connection::ConnectionId;
+use crate::handler::{
+    self, ConnectionEvent, ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr,
+    DialUpgradeError, FullyNegotiatedInbound, FullyNegotiatedOutbound, KeepAlive,
+    ListenUpgradeError, SubstreamProtocol,
+};
+use crate::upgrade::SendWrapper;
+use crate::{
+    ConnectionDenied, NetworkBehaviour, NotifyHandler, PollParameters, THandler, THandlerInEvent,
+    THandlerOutEvent, ToSwarm,
+};
+use either::Either;
+use futures::future;
+use libp2p_core::{upgrade::DeniedUpgrade, ConnectedPoint, Endpoint, Multiaddr};
+use libp2p_identity::PeerId;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::{fmt, task::Context, task::Poll};
+
+/// Implementation of [`NetworkBehaviour`] whose inner behaviour can be added and removed while
+/// the swarm is running.
+///
+/// A behaviour added via [`DynamicBehaviour::insert`] is informed about all connections that were
+/// established before, i.e. for each of them the handler is created via
+/// [`NetworkBehaviour::handle_established_inbound_connection`] or
+/// [`NetworkBehaviour::handle_established_outbound_connection`] and installed on the connection,
+/// followed by a [`FromSwarm::ConnectionEstablished`] once the handler is in place. This allows
+/// enabling features at runtime without reconnecting to every peer.
+///
+/// Connections for which the inner behaviour denies the handler are left as they are.
+pub struct DynamicBehaviour<TBehaviour>
+where
+    TBehaviour: NetworkBehaviour,
+{
+    inner: Option<TBehaviour>,
+    /// Incremented with every inserted behaviour to tell its handlers from those of a previous one.
+    generation: u64,
+    /// All established connections.
+    connections: HashMap<ConnectionId, (PeerId, ConnectedPoint)>,
+    /// Connections the inner behaviour has been informed about via
+    /// [`FromSwarm::ConnectionEstablished`].
+    established: HashSet<ConnectionId>,
+    /// Events to the handlers of existing connections.
+    pending_events: VecDeque<(PeerId, ConnectionId, DynamicInEvent<THandler<TBehaviour>>)>,
+}
+
+impl<TBehaviour> DynamicBehaviour<TBehaviour>
+where
+    TBehaviour: NetworkBehaviour,
+{
+    /// Creates a [`DynamicBehaviour`] without an inner behaviour.
+    pub fn new() -> Self {
+        DynamicBehaviour {
+            inner: None,
+            generation: 0,
+            connections: HashMap::new(),
+            established: HashSet::new(),
+            pending_events: VecDeque::new(),
+        }
+    }
+
+    /// Returns `true` if an inner behaviour is present.
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Returns a reference to the inner `NetworkBehaviour`.
+    pub fn as_ref(&self) -> Option<&TBehaviour> {
+        self.inner.as_ref()
+    }
+
+    /// Returns a mutable reference to the inner `NetworkBehaviour`.
+    pub fn as_mut(&mut self) -> Option<&mut TBehaviour> {
+        self.inner.as_mut()
+    }
+
+    /// Adds the behaviour, replaying the established connections to it.
+    ///
+    /// Returns the previous inner behaviour, if any.
+    pub fn insert(&mut self, mut behaviour: TBehaviour) -> Option<TBehaviour> {
+        let previous = self.remove();
+
+        for (connection_id, (peer_id, endpoint)) in self.connections.iter() {
+            let handler = match endpoint {
+                ConnectedPoint::Dialer {
+                    address,
+                    role_override,
+                } => behaviour.handle_established_outbound_connection(
+                    *connection_id,
+                    *peer_id,
+                    address,
+                    *role_override,
+                ),
+                ConnectedPoint::Listener {
+                    local_addr,
+                    send_back_addr,
+                } => behaviour.handle_established_inbound_connection(
+                    *connection_id,
+                    *peer_id,
+                    local_addr,
+                    send_back_addr,
+                ),
+            };
+
+            match handler {
+                Ok(handler) => self.pending_events.push_back((
+                    *peer_id,
+                    *connection_id,
+                    DynamicInEvent(Command::Install {
+                        handler,
+                        generation: self.generation,
+                    }),
+                )),
+                Err(cause) => tracing::debug!(
+                    "Behaviour denied replayed connection {connection_id:?} to {peer_id}: {cause}"
+                ),
+            }
+        }
+
+        self.inner = Some(behaviour);
+
+        previous
+    }
+
+    /// Removes the inner behaviour, uninstalling its handlers from all connections.
+    pub fn remove(&mut self) -> Option<TBehaviour> {
+        let inner = self.inner.take()?;
+
+        self.generation += 1;
+        self.established.clear();
+        self.pending_events.clear();
+        for (connection_id, (peer_id, _)) in self.connections.iter() {
+            self.pending_events.push_back((
+                *peer_id,
+                *connection_id,
+                DynamicInEvent(Command::Uninstall),
+            ));
+        }
+
+        Some(inner)
+    }
+
+    /// Returns the number of connections to the peer the inner behaviour knows about, other than
+    /// the given one.
+    fn num_established(&self, peer_id: PeerId, connection_id: ConnectionId) -> usize {
+        self.established
+            .iter()
+            .filter(|id| **id != connection_id)
+            .filter(|id| matches!(self.connections.get(id), Some((p, _)) if *p == peer_id))
+            .count()
+    }
+
+    /// Informs the inner behaviour about the connection, once its handler is in place.
+    fn on_installed(&mut self, connection_id: ConnectionId) {
+        let (peer_id, endpoint) = match self.connections.get(&connection_id) {
+            Some(connection) => connection.clone(),
+            None => return,
+        };
+        let other_established = self.num_established(peer_id, connection_id);
+
+        let inner = match self.inner.as_mut() {
+            Some(inner) => inner,
+            None => return,
+        };
+        if !self.established.insert(connection_id) {
+            return;
+        }
+
+        inner.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+            peer_id,
+            connection_id,
+            endpoint: &endpoint,
+            failed_addresses: &[],
+            other_established,
+            user_data: None,
+        }));
+    }
+
+    fn on_connection_closed(
+        &mut self,
+        ConnectionClosed {
+            peer_id,
+            connection_id,
+            endpoint,
+            handler,
+            reason,
+            ..
+        }: ConnectionClosed<<Self as NetworkBehaviour>::ConnectionHandler>,
+    ) {
+        // The handler may have been installed without the acknowledgement having arrived yet.
+        if handler.inner.is_some() && handler.generation == self.generation {
+            self.on_installed(connection_id);
+        }
+
+        self.connections.remove(&connection_id);
+        self.pending_events
+            .retain(|(_, id, _)| *id != connection_id);
+
+        let was_established = self.established.remove(&connection_id);
+        let remaining_established = self.num_established(peer_id, connection_id);
+        let (inner, handler) = match (self.inner.as_mut(), handler.inner) {
+            (Some(inner), Some(handler)) if was_established => (inner, handler),
+            _ => return,
+        };
+
+        inner.on_swarm_event(FromSwarm::ConnectionClosed(ConnectionClosed {
+            peer_id,
+            connection_id,
+            endpoint,
+            handler,
+            remaining_established,
+            reason,
+        }));
+    }
+}
+
+impl<TBehaviour> Default for DynamicBehaviour<TBehaviour>
+where
+    TBehaviour: NetworkBehaviour,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<TBehaviour> NetworkBehaviour for DynamicBehaviour<TBehaviour>
+where
+    TBehaviour: NetworkBehaviour,
+{
+    type ConnectionHandler = DynamicConnectionHandler<THandler<TBehaviour>>;
+    type OutEvent = TBehaviour::OutEvent;
+
+    fn handle_pending_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<(), ConnectionDenied> {
+        match self.inner.as_mut() {
+            None => Ok(()),
+            Some(inner) => {
+                inner.handle_pending_inbound_connection(connection_id, local_addr, remote_addr)
+            }
+        }
+    }
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        let handler = self
+            .inner
+            .as_mut()
+            .map(|inner| {
+                inner.handle_established_inbound_connection(
+                    connection_id,
+                    peer,
+                    local_addr,
+                    remote_addr,
+                )
+            })
+            .transpose()?;
+
+        Ok(DynamicConnectionHandler::new(handler, self.generation))
+    }
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        maybe_peer: Option<PeerId>,
+        addresses: &[Multiaddr],
+        effective_role: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        match self.inner.as_mut() {
+            None => Ok(vec![]),
+            Some(inner) => inner.handle_pending_outbound_connection(
+                connection_id,
+                maybe_peer,
+                addresses,
+                effective_role,
+            ),
+        }
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        let handler = self
+            .inner
+            .as_mut()
+            .map(|inner| {
+                inner.handle_established_outbound_connection(
+                    connection_id,
+                    peer,
+                    addr,
+                    role_override,
+                )
+            })
+            .transpose()?;
+
+        Ok(DynamicConnectionHandler::new(handler, self.generation))
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm<Self::ConnectionHandler>) {
+        match event {
+            FromSwarm::ConnectionEstablished(ConnectionEstablished {
+                peer_id,
+                connection_id,
+                endpoint,
+                failed_addresses,
+                user_data,
+                ..
+            }) => {
+                self.connections
+                    .insert(connection_id, (peer_id, endpoint.clone()));
+
+                let other_established = self.num_established(peer_id, connection_id);
+                if let Some(inner) = self.inner.as_mut() {
+                    self.established.insert(connection_id);
+                    inner.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+                        peer_id,
+                        connection_id,
+                        endpoint,
+                        failed_addresses,
+                        other_established,
+                        user_data,
+                    }));
+                }
+            }
+            FromSwarm::ConnectionClosed(connection_closed) => {
+                self.on_connection_closed(connection_closed)
+            }
+            FromSwarm::AddressChange(AddressChange {
+                peer_id,
+                connection_id,
+                old,
+                new,
+            }) => {
+                if let Some((_, endpoint)) = self.connections.get_mut(&connection_id) {
+                    *endpoint = new.clone();
+                }
+
+                if let Some(inner) = self.inner.as_mut() {
+                    inner.on_swarm_event(FromSwarm::AddressChange(AddressChange {
+                        peer_id,
+                        connection_id,
+                        old,
+                        new,
+                    }));
+                }
+            }
+            FromSwarm::DialFailure(DialFailure {
+                peer_id,
+                error,
+                connection_id,
+                user_data,
+            }) => {
+                if let Some(inner) = self.inner.as_mut() {
+                    inner.on_swarm_event(FromSwarm::DialFailure(DialFailure {
+                        peer_id,
+                        error,
+                        connection_id,
+                        user_data,
+                    }));
+                }
+            }
+            event => {
+                if let Some(inner) = self.inner.as_mut() {
+                    if let Some(event) = event.maybe_map_handler(|h| h.inner) {
+                        inner.on_swarm_event(event);
+                    }
+                }
+            }
+        }
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        DynamicOutEvent(event): THandlerOutEvent<Self>,
+    ) {
+        match event {
+            Either::Left(generation) => {
+                if generation == self.generation {
+                    self.on_installed(connection_id);
+                }
+            }
+            Either::Right(event) => {
+                // Drop events of handlers of a previous behaviour or not installed yet.
+                if !self.established.contains(&connection_id) {
+                    return;
+                }
+                if let Some(inner) = self.inner.as_mut() {
+                    inner.on_connection_handler_event(peer_id, connection_id, event)
+                }
+            }
+        }
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+        params: &mut impl PollParameters,
+    ) -> Poll<ToSwarm<Self::OutEvent, THandlerInEvent<Self>>> {
+        if let Some((peer_id, connection_id, event)) = self.pending_events.pop_front() {
+            return Poll::Ready(ToSwarm::NotifyHandler {
+                peer_id,
+                handler: NotifyHandler::One(connection_id),
+                event,
+            });
+        }
+
+        match self.inner.as_mut() {
+            Some(inner) => inner
+                .poll(cx, params)
+                .map(|action| action.map_in(|event| DynamicInEvent(Command::Event(event)))),
+            None => Poll::Pending,
+        }
+    }
+
+    fn queued_actions(&self) -> usize {
+        self.pending_events.len()
+            + self
+                .inner
+                .as_ref()
+                .map_or(0, |inner| inner.queued_actions())
+    }
+}
+
+/// Event sent from a [`DynamicBehaviour`] to its [`DynamicConnectionHandler`]s.
+pub struct DynamicInEvent<TInner: ConnectionHandler>(Command<TInner>);
+
+enum Command<TInner: ConnectionHandler> {
+    /// Installs the handler of a newly inserted behaviour.
+    Install { handler: TInner, generation: u64 },
+    /// Removes the handler of a removed behaviour.
+    Uninstall,
+    /// An event for the installed handler.
+    Event(TInner::InEvent),
+}
+
+impl<TInner: ConnectionHandler> fmt::Debug for DynamicInEvent<TInner> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Command::Install { generation, .. } => f
+                .debug_struct("Install")
+                .field("generation", generation)
+                .finish(),
+            Command::Uninstall => f.write_str("Uninstall"),
+            Command::Event(event) => f.debug_tuple("Event").field(event).finish(),
+        }
+    }
+}
+
+/// Event sent from a [`DynamicConnectionHandler`] to its [`DynamicBehaviour`], either the
+/// acknowledgement of an installed handler or an event of the installed handler.
+#[derive(Debug)]
+pub struct DynamicOutEvent<TEvent>(Either<u64, TEvent>);
+
+/// Implementation of [`ConnectionHandler`] for a [`DynamicBehaviour`], to which the handler of
+/// the inner behaviour can be installed on an existing connection.
+pub struct DynamicConnectionHandler<TInner> {
+    inner: Option<TInner>,
+    /// The generation of the [`DynamicBehaviour`] the handler belongs to.
+    generation: u64,
+    /// Whether to acknowledge the installation of the handler.
+    pending_ack: bool,
+}
+
+impl<TInner> DynamicConnectionHandler<TInner>
+where
+    TInner: ConnectionHandler,
+{
+    fn new(inner: Option<TInner>, generation: u64) -> Self {
+        DynamicConnectionHandler {
+            inner,
+            generation,
+            pending_ack: false,
+        }
+    }
+
+    /// Returns the installed handler, if it belongs to the given generation.
+    fn inner_of(&mut self, generation: u64) -> Option<&mut TInner> {
+        if generation != self.generation {
+            return None;
+        }
+        self.inner.as_mut()
+    }
+
+    fn on_fully_negotiated_inbound(
+        &mut self,
+        FullyNegotiatedInbound {
+            protocol: out,
+            info,
+        }: FullyNegotiatedInbound<
+            <Self as ConnectionHandler>::InboundProtocol,
+            <Self as ConnectionHandler>::InboundOpenInfo,
+        >,
+    ) {
+        let out = match out {
+            future::Either::Left(out) => out,
+            future::Either::Right(v) => void::unreachable(v),
+        };
+        let (generation, info) = match info {
+            Either::Left(info) => info,
+            Either::Right(()) => {
+                panic!("Unexpected Either::Right in enabled `on_fully_negotiated_inbound`.")
+            }
+        };
+
+        // Streams negotiated for a since replaced or removed handler are dropped.
+        if let Some(inner) = self.inner_of(generation) {
+            inner.on_connection_event(ConnectionEvent::FullyNegotiatedInbound(
+                FullyNegotiatedInbound {
+                    protocol: out,
+                    info,
+                },
+            ));
+        }
+    }
+
+    fn on_listen_upgrade_error(
+        &mut self,
+        ListenUpgradeError { info, error: err }: ListenUpgradeError<
+            <Self as ConnectionHandler>::InboundOpenInfo,
+            <Self as ConnectionHandler>::InboundProtocol,
+        >,
+    ) {
+        let (inner, info) = match info {
+            Either::Left((generation, info)) => match self.inner_of(generation) {
+                Some(inner) => (inner, info),
+                None => return,
+            },
+            // Ignore listen upgrade errors without a handler.
+            Either::Right(()) => return,
+        };
+
+        let err = match err {
+            ConnectionHandlerUpgrErr::Timeout => ConnectionHandlerUpgrErr::Timeout,
+            ConnectionHandlerUpgrErr::Timer => ConnectionHandlerUpgrErr::Timer,
+            ConnectionHandlerUpgrErr::Upgrade(err) => {
+                ConnectionHandlerUpgrErr::Upgrade(err.map_err(|err| match err {
+                    Either::Left(e) => e,
+                    Either::Right(v) => void::unreachable(v),
+                }))
+            }
+        };
+
+        inner.on_connection_event(ConnectionEvent::ListenUpgradeError(ListenUpgradeError {
+            info,
+            error: err,
+        }));
+    }
+}
+
+impl<TInner> ConnectionHandler for DynamicConnectionHandler<TInner>
+where
+    TInner: ConnectionHandler,
+{
+    type InEvent = DynamicInEvent<TInner>;
+    type OutEvent = DynamicOutEvent<TInner::OutEvent>;
+    type Error = TInner::Error;
+    type InboundProtocol = Either<SendWrapper<TInner::InboundProtocol>, SendWrapper<DeniedUpgrade>>;
+    type OutboundProtocol = TInner::OutboundProtocol;
+    type OutboundOpenInfo = (u64, TInner::OutboundOpenInfo);
+    type InboundOpenInfo = Either<(u64, TInner::InboundOpenInfo), ()>;
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, Self::InboundOpenInfo> {
+        if let Some(inner) = self.inner.as_ref() {
+            let generation = self.generation;
+            inner
+                .listen_protocol()
+                .map_upgrade(|u| Either::Left(SendWrapper(u)))
+                .map_info(|info| Either::Left((generation, info)))
+        } else {
+            SubstreamProtocol::new(Either::Right(SendWrapper(DeniedUpgrade)), Either::Right(()))
+        }
+    }
+
+    fn on_behaviour_event(&mut self, DynamicInEvent(command): Self::InEvent) {
+        match command {
+            Command::Install {
+                handler,
+                generation,
+            } => {
+                self.inner = Some(handler);
+                self.generation = generation;
+                self.pending_ack = true;
+            }
+            Command::Uninstall => {
+                self.inner = None;
+                self.pending_ack = false;
+            }
+            Command::Event(event) => {
+                if let Some(inner) = self.inner.as_mut() {
+                    inner.on_behaviour_event(event)
+                }
+            }
+        }
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        self.inner
+            .as_ref()
+            .map(|h| h.connection_keep_alive())
+            .unwrap_or(KeepAlive::No)
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<
+        ConnectionHandlerEvent<
+            Self::OutboundProtocol,
+            Self::OutboundOpenInfo,
+            Self::OutEvent,
+            Self::Error,
+        >,
+    > {
+        if self.pending_ack {
+            self.pending_ack = false;
+            return Poll::Ready(ConnectionHandlerEvent::Custom(DynamicOutEvent(
+                Either::Left(self.generation),
+            )));
+        }
+
+        let generation = self.generation;
+        match self.inner.as_mut() {
+            Some(inner) => inner.poll(cx).map(|event| {
+                event
+                    .map_custom(|event| DynamicOutEvent(Either::Right(event)))
+                    .map_outbound_open_info(|info| (generation, info))
+            }),
+            None => Poll::Pending,
+        }
+    }
+
+    fn on_connection_event(
+        &mut self,
+        event: ConnectionEvent<
+            Self::InboundProtocol,
+            Self::OutboundProtocol,
+            Self::InboundOpenInfo,
+            Self::OutboundOpenInfo,
+        >,
+    ) {
+        match event {
+            ConnectionEvent::FullyNegotiatedInbound(fully_negotiated_inbound) => {
+                self.on_fully_negotiated_inbound(fully_negotiated_inbound)
+            }
+            ConnectionEvent::FullyNegotiatedOutbound(FullyNegotiatedOutbound {
+                protocol: out,
+                info: (generation, info),
+            }) => {
+                if let Some(inner) = self.inner_of(generation) {
+                    inner.on_connection_event(ConnectionEvent::FullyNegotiatedOutbound(
+                        FullyNegotiatedOutbound {
+                            protocol: out,
+                            info,
+                        },
+                    ))
+                }
+            }
+            ConnectionEvent::AddressChange(address_change) => {
+                if let Some(inner) = self.inner.as_mut() {
+                    inner.on_connection_event(ConnectionEvent::AddressChange(
+                        handler::AddressChange {
+                            new_address: address_change.new_address,
+                        },
+                    ));
+                }
+            }
+            ConnectionEvent::DialUpgradeError(DialUpgradeError {
+                info: (generation, info),
+                error: err,
+            }) => {
+                if let Some(inner) = self.inner_of(generation) {
+                    inner.on_connection_event(ConnectionEvent::DialUpgradeError(DialUpgradeError {
+                        info,
+                        error: err,
+                    }))
+                }
+            }
+            ConnectionEvent::ListenUpgradeError(listen_upgrade_error) => {
+                self.on_listen_upgrade_error(listen_upgrade_error)
+            }
+        }
+    }
+}
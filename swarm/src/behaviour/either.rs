@@ -168,4 +168,11 @@ where
 
         Poll::Ready(event)
     }
+
+    fn queued_actions(&self) -> usize {
+        match self {
+            Either::Left(behaviour) => behaviour.queued_actions(),
+            Either::Right(behaviour) => behaviour.queued_actions(),
+        }
+    }
 }
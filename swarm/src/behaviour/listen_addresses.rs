@@ -1,19 +1,38 @@
 use crate::behaviour::{ExpiredListenAddr, FromSwarm, NewListenAddr};
 #[allow(deprecated)]
 use crate::IntoConnectionHandler;
+use libp2p_core::transport::{ListenerId, TransportKind};
 use libp2p_core::Multiaddr;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 /// Utility struct for tracking the addresses a [`Swarm`](crate::Swarm) is listening on.
 #[derive(Debug, Default, Clone)]
 pub struct ListenAddresses {
-    addresses: HashSet<Multiaddr>,
+    /// The listen addresses with the kind of transport listening on them and the listeners
+    /// reporting them. An address is removed once it expired on all its listeners.
+    addresses: HashMap<Multiaddr, (TransportKind, HashSet<ListenerId>)>,
 }
 
 impl ListenAddresses {
     /// Returns an [`Iterator`] over all listen addresses.
     pub fn iter(&self) -> impl ExactSizeIterator<Item = &Multiaddr> {
-        self.addresses.iter()
+        self.addresses.keys()
+    }
+
+    /// Returns an [`Iterator`] over the listen addresses of the given kind of transport.
+    pub fn iter_transport(&self, transport: TransportKind) -> impl Iterator<Item = &Multiaddr> {
+        self.addresses
+            .iter()
+            .filter(move |(_, (t, _))| *t == transport)
+            .map(|(addr, _)| addr)
+    }
+
+    /// Returns an [`Iterator`] over the addresses of the given listener.
+    pub fn iter_listener(&self, listener_id: ListenerId) -> impl Iterator<Item = &Multiaddr> {
+        self.addresses
+            .iter()
+            .filter(move |(_, (_, listeners))| listeners.contains(&listener_id))
+            .map(|(addr, _)| addr)
     }
 
     /// Feed a [`FromSwarm`] event to this struct.
@@ -23,13 +42,68 @@ impl ListenAddresses {
         THandler: IntoConnectionHandler,
     {
         match event {
-            FromSwarm::NewListenAddr(NewListenAddr { addr, .. }) => {
-                self.addresses.insert((*addr).clone());
+            FromSwarm::NewListenAddr(NewListenAddr {
+                listener_id,
+                addr,
+                transport,
+            }) => {
+                self.addresses
+                    .entry((*addr).clone())
+                    .or_insert_with(|| (*transport, HashSet::new()))
+                    .1
+                    .insert(*listener_id);
             }
-            FromSwarm::ExpiredListenAddr(ExpiredListenAddr { addr, .. }) => {
-                self.addresses.remove(addr);
+            FromSwarm::ExpiredListenAddr(ExpiredListenAddr {
+                listener_id, addr, ..
+            }) => {
+                if let Some((_, listeners)) = self.addresses.get_mut(*addr) {
+                    listeners.remove(listener_id);
+                    if listeners.is_empty() {
+                        self.addresses.remove(*addr);
+                    }
+                }
             }
             _ => {}
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy;
+
+    #[test]
+    fn address_expires_on_all_listeners() {
+        let mut addresses = ListenAddresses::default();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/4001".parse().unwrap();
+        let listeners = [ListenerId::new(), ListenerId::new()];
+
+        for listener_id in listeners {
+            addresses.on_swarm_event::<dummy::ConnectionHandler>(&FromSwarm::NewListenAddr(
+                NewListenAddr {
+                    listener_id,
+                    addr: &addr,
+                    transport: TransportKind::Tcp,
+                },
+            ));
+        }
+        assert_eq!(addresses.iter().count(), 1);
+        assert_eq!(addresses.iter_transport(TransportKind::Tcp).count(), 1);
+        assert_eq!(addresses.iter_transport(TransportKind::Quic).count(), 0);
+
+        for (i, listener_id) in listeners.into_iter().enumerate() {
+            assert_eq!(addresses.iter_listener(listener_id).count(), 1);
+            addresses.on_swarm_event::<dummy::ConnectionHandler>(&FromSwarm::ExpiredListenAddr(
+                ExpiredListenAddr {
+                    listener_id,
+                    addr: &addr,
+                    transport: TransportKind::Tcp,
+                },
+            ));
+            assert_eq!(addresses.iter_listener(listener_id).count(), 0);
+            // The address is still listened on by the other listener.
+            assert_eq!(addresses.iter().count(), 1 - i);
+        }
+    }
+}
@@ -189,6 +189,12 @@ where
             Poll::Pending
         }
     }
+
+    fn queued_actions(&self) -> usize {
+        self.inner
+            .as_ref()
+            .map_or(0, |inner| inner.queued_actions())
+    }
 }
 
 /// Implementation of [`ConnectionHandler`] that can be in the disabled state.
@@ -0,0 +1,179 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Provides clocks for measuring and awaiting the passing of time.
+//!
+//! Time-based logic of the [`Swarm`](crate::Swarm) and of [`NetworkBehaviour`](crate::NetworkBehaviour)s
+//! accepting a [`Clock`] can be tested deterministically by providing a [`ManualClock`], whose time
+//! only passes when it is explicitly [advanced](ManualClock::advance).
+use futures::future::BoxFuture;
+use futures::prelude::*;
+use futures_timer::Delay;
+use instant::Instant;
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+/// Implemented on objects that provide the current time and timers.
+pub trait Clock: fmt::Debug + Send + Sync + 'static {
+    /// Returns the current point in time.
+    fn now(&self) -> Instant;
+
+    /// Returns a [`Timer`] that completes once `duration` has passed.
+    fn timer(&self, duration: Duration) -> Timer;
+}
+
+/// A future completing at a point in time of the [`Clock`] it was created with.
+#[must_use = "futures do nothing unless polled"]
+pub struct Timer {
+    inner: BoxFuture<'static, ()>,
+}
+
+impl Timer {
+    /// Creates a [`Timer`] completing once the given future completes.
+    pub fn new(inner: impl Future<Output = ()> + Send + 'static) -> Self {
+        Self {
+            inner: inner.boxed(),
+        }
+    }
+}
+
+impl Future for Timer {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.inner.poll_unpin(cx)
+    }
+}
+
+impl fmt::Debug for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timer").finish_non_exhaustive()
+    }
+}
+
+/// The [`Clock`] of the system, used by default.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn timer(&self, duration: Duration) -> Timer {
+        Timer::new(Delay::new(duration))
+    }
+}
+
+/// A [`Clock`] whose time only passes when [advanced](ManualClock::advance).
+///
+/// Clones share the same time. Advancing the clock completes all of its timers that are due
+/// without actually waiting.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    inner: Arc<Mutex<ManualClockInner>>,
+}
+
+#[derive(Debug)]
+struct ManualClockInner {
+    now: Instant,
+    /// The tasks waiting for timers of the clock.
+    wakers: Vec<Waker>,
+}
+
+impl ManualClock {
+    /// Creates a new [`ManualClock`], starting at the current time of the system.
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(ManualClockInner {
+                now: Instant::now(),
+                wakers: Vec::new(),
+            })),
+        }
+    }
+
+    /// Advances the clock by the given duration, waking all tasks waiting for its timers.
+    pub fn advance(&self, duration: Duration) {
+        let wakers = {
+            let mut inner = self.inner.lock().expect("not poisoned");
+            inner.now += duration;
+            std::mem::take(&mut inner.wakers)
+        };
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.inner.lock().expect("not poisoned").now
+    }
+
+    fn timer(&self, duration: Duration) -> Timer {
+        let deadline = self.now() + duration;
+        let inner = self.inner.clone();
+        Timer::new(future::poll_fn(move |cx| {
+            let mut inner = inner.lock().expect("not poisoned");
+            if inner.now >= deadline {
+                return Poll::Ready(());
+            }
+            if !inner.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                inner.wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::noop_waker;
+
+    #[test]
+    fn manual_clock_completes_timers_when_advanced() {
+        let clock = ManualClock::new();
+        let start = clock.now();
+        let mut timer = clock.timer(Duration::from_secs(60));
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(timer.poll_unpin(&mut cx).is_pending());
+
+        clock.advance(Duration::from_secs(59));
+        assert!(timer.poll_unpin(&mut cx).is_pending());
+
+        clock.advance(Duration::from_secs(1));
+        assert!(timer.poll_unpin(&mut cx).is_ready());
+        assert_eq!(clock.now() - start, Duration::from_secs(60));
+    }
+}
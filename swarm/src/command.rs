@@ -0,0 +1,151 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Sending commands to a [`Swarm`](crate::Swarm) from other tasks.
+
+use crate::dial_opts::DialOpts;
+use crate::DialError;
+use futures::channel::{mpsc, oneshot};
+use futures::SinkExt;
+use libp2p_identity::PeerId;
+use std::{error, fmt};
+use void::Void;
+
+/// The number of commands that can be buffered per [`CommandSender`] before sending waits
+/// for the [`Swarm`](crate::Swarm) to process them.
+pub(crate) const COMMAND_BUFFER_SIZE: usize = 32;
+
+/// A command that operates on the [`NetworkBehaviour`](crate::NetworkBehaviour) of a
+/// [`Swarm`](crate::Swarm).
+///
+/// Implemented for all closures taking a mutable reference to the behaviour, but can be
+/// implemented on application-defined types to send typed messages to a behaviour.
+pub trait BehaviourCommand<TBehaviour>: Send + 'static {
+    /// Applies the command to the behaviour.
+    fn apply(self: Box<Self>, behaviour: &mut TBehaviour);
+}
+
+impl<TBehaviour, F> BehaviourCommand<TBehaviour> for F
+where
+    F: FnOnce(&mut TBehaviour) + Send + 'static,
+{
+    fn apply(self: Box<Self>, behaviour: &mut TBehaviour) {
+        (*self)(behaviour)
+    }
+}
+
+/// A command queued by a [`CommandSender`] and processed while the [`Swarm`](crate::Swarm)
+/// is polled.
+pub(crate) enum Command<TBehaviour> {
+    Dial(DialOpts, oneshot::Sender<Result<(), DialError>>),
+    DisconnectPeerId(PeerId, oneshot::Sender<Result<(), ()>>),
+    Behaviour(Box<dyn BehaviourCommand<TBehaviour>>),
+}
+
+/// A cloneable handle to send commands to a [`Swarm`](crate::Swarm), e.g. from another task
+/// or thread than the one driving the [`Swarm`](crate::Swarm).
+///
+/// Obtained via [`Swarm::command_sender`](crate::Swarm::command_sender). Commands are
+/// processed in the order they were sent while the [`Swarm`](crate::Swarm) is polled.
+pub struct CommandSender<TBehaviour> {
+    sender: mpsc::Sender<Command<TBehaviour>>,
+}
+
+impl<TBehaviour> CommandSender<TBehaviour> {
+    pub(crate) fn new(sender: mpsc::Sender<Command<TBehaviour>>) -> Self {
+        Self { sender }
+    }
+
+    /// Dials a known or unknown peer, see [`Swarm::dial`](crate::Swarm::dial).
+    ///
+    /// Resolves once the dial has been initiated by the [`Swarm`](crate::Swarm).
+    pub async fn dial(&mut self, opts: impl Into<DialOpts>) -> Result<(), CommandError<DialError>> {
+        let (tx, rx) = oneshot::channel();
+        self.send(Command::Dial(opts.into(), tx)).await?;
+
+        rx.await
+            .map_err(|_| CommandError::Closed)?
+            .map_err(CommandError::Failed)
+    }
+
+    /// Disconnects a peer, see [`Swarm::disconnect_peer_id`](crate::Swarm::disconnect_peer_id).
+    ///
+    /// Fails with [`CommandError::Failed`] if the peer was not connected.
+    pub async fn disconnect_peer_id(&mut self, peer_id: PeerId) -> Result<(), CommandError<()>> {
+        let (tx, rx) = oneshot::channel();
+        self.send(Command::DisconnectPeerId(peer_id, tx)).await?;
+
+        rx.await
+            .map_err(|_| CommandError::Closed)?
+            .map_err(CommandError::Failed)
+    }
+
+    /// Applies a command to the [`NetworkBehaviour`](crate::NetworkBehaviour) of the
+    /// [`Swarm`](crate::Swarm).
+    ///
+    /// Resolves once the command has been queued, not once it has been applied.
+    pub async fn behaviour(
+        &mut self,
+        command: impl BehaviourCommand<TBehaviour>,
+    ) -> Result<(), CommandError<Void>> {
+        self.send(Command::Behaviour(Box::new(command))).await
+    }
+
+    async fn send<E>(&mut self, command: Command<TBehaviour>) -> Result<(), CommandError<E>> {
+        self.sender
+            .send(command)
+            .await
+            .map_err(|_| CommandError::Closed)
+    }
+}
+
+impl<TBehaviour> Clone for CommandSender<TBehaviour> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<TBehaviour> fmt::Debug for CommandSender<TBehaviour> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandSender").finish_non_exhaustive()
+    }
+}
+
+/// Error of a command sent via a [`CommandSender`].
+#[derive(Debug)]
+pub enum CommandError<E> {
+    /// The [`Swarm`](crate::Swarm) has been dropped.
+    Closed,
+    /// The [`Swarm`](crate::Swarm) failed to execute the command.
+    Failed(E),
+}
+
+impl<E: fmt::Debug> fmt::Display for CommandError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Closed => write!(f, "Swarm has been dropped"),
+            CommandError::Failed(e) => write!(f, "Command failed: {e:?}"),
+        }
+    }
+}
+
+impl<E: fmt::Debug> error::Error for CommandError<E> {}
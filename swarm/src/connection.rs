@@ -28,25 +28,32 @@ pub use error::{
 };
 
 use crate::handler::{
-    AddressChange, ConnectionEvent, ConnectionHandler, DialUpgradeError, FullyNegotiatedInbound,
-    FullyNegotiatedOutbound, ListenUpgradeError,
+    self, AddressChange, ConnectionEvent, ConnectionHandler, DialUpgradeError,
+    FullyNegotiatedInbound, FullyNegotiatedOutbound, ListenUpgradeError,
 };
 use crate::upgrade::{InboundUpgradeSend, OutboundUpgradeSend, SendWrapper};
+use crate::{Clock, Timer};
 use crate::{ConnectionHandlerEvent, ConnectionHandlerUpgrErr, KeepAlive, SubstreamProtocol};
 use futures::stream::FuturesUnordered;
 use futures::FutureExt;
 use futures::StreamExt;
-use futures_timer::Delay;
 use instant::Instant;
 use libp2p_core::connection::ConnectedPoint;
 use libp2p_core::multiaddr::Multiaddr;
-use libp2p_core::muxing::{StreamMuxerBox, StreamMuxerEvent, StreamMuxerExt, SubstreamBox};
-use libp2p_core::upgrade::{InboundUpgradeApply, OutboundUpgradeApply};
+use libp2p_core::muxing::{
+    PriorityHandle, StreamMuxer, StreamMuxerBox, StreamMuxerEvent, StreamMuxerExt, StreamPriority,
+    SubstreamBox,
+};
+use libp2p_core::upgrade::{
+    InboundUpgrade, InboundUpgradeApply, OutboundUpgrade, OutboundUpgradeApply, ProtocolName,
+    UpgradeInfo,
+};
 use libp2p_core::Endpoint;
 use libp2p_core::{upgrade, UpgradeError};
 use libp2p_identity::PeerId;
 use std::future::Future;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::task::Waker;
 use std::time::Duration;
 use std::{fmt, io, mem, pin::Pin, task::Context, task::Poll};
@@ -114,14 +121,20 @@ where
     negotiating_in: FuturesUnordered<
         SubstreamUpgrade<
             THandler::InboundOpenInfo,
-            InboundUpgradeApply<SubstreamBox, SendWrapper<THandler::InboundProtocol>>,
+            InboundUpgradeApply<
+                SubstreamBox,
+                RecordProtocol<NegotiatedPriority<SendWrapper<THandler::InboundProtocol>>>,
+            >,
         >,
     >,
     /// Futures that upgrade outgoing substreams.
     negotiating_out: FuturesUnordered<
         SubstreamUpgrade<
             THandler::OutboundOpenInfo,
-            OutboundUpgradeApply<SubstreamBox, SendWrapper<THandler::OutboundProtocol>>,
+            OutboundUpgradeApply<
+                SubstreamBox,
+                RecordProtocol<SendWrapper<THandler::OutboundProtocol>>,
+            >,
         >,
     >,
     /// The currently planned connection & handler shutdown.
@@ -138,6 +151,14 @@ where
     /// the total number of streams can be enforced at the
     /// [`StreamMuxerBox`](libp2p_core::muxing::StreamMuxerBox) level.
     max_negotiating_inbound_streams: usize,
+    /// Whether the connection is closed when the remote opens an inbound stream
+    /// while [`Connection::max_negotiating_inbound_streams`] are negotiating.
+    close_on_excess_inbound_streams: bool,
+    /// The maximum time for negotiating an inbound stream, overriding longer
+    /// timeouts of the [`SubstreamProtocol`]s of the handler.
+    inbound_negotiation_timeout: Option<Duration>,
+    /// The clock of the keep-alive and substream negotiation timeouts.
+    clock: Arc<dyn Clock>,
     /// Contains all upgrades that are waiting for a new outbound substream.
     ///
     /// The upgrade timeout is already ticking here so this may fail in case the remote is not quick
@@ -172,6 +193,9 @@ where
         handler: THandler,
         substream_upgrade_protocol_override: Option<upgrade::Version>,
         max_negotiating_inbound_streams: usize,
+        close_on_excess_inbound_streams: bool,
+        inbound_negotiation_timeout: Option<Duration>,
+        clock: Arc<dyn Clock>,
     ) -> Self {
         Connection {
             muxing: muxer,
@@ -181,6 +205,9 @@ where
             shutdown: Shutdown::None,
             substream_upgrade_protocol_override,
             max_negotiating_inbound_streams,
+            close_on_excess_inbound_streams,
+            inbound_negotiation_timeout,
+            clock,
             requested_substreams: Default::default(),
         }
     }
@@ -210,7 +237,10 @@ where
             negotiating_in,
             shutdown,
             max_negotiating_inbound_streams,
+            close_on_excess_inbound_streams,
+            inbound_negotiation_timeout,
             substream_upgrade_protocol_override,
+            clock,
         } = self.get_mut();
 
         loop {
@@ -233,9 +263,15 @@ where
                 Poll::Pending => {}
                 Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest { protocol }) => {
                     let timeout = *protocol.timeout();
+                    let priority = protocol.priority();
                     let (upgrade, user_data) = protocol.into_upgrade();
 
-                    requested_substreams.push(SubstreamRequested::new(user_data, timeout, upgrade));
+                    requested_substreams.push(SubstreamRequested::new(
+                        user_data,
+                        clock.timer(timeout),
+                        priority,
+                        upgrade,
+                    ));
                     continue; // Poll handler until exhausted.
                 }
                 Poll::Ready(ConnectionHandlerEvent::Custom(event)) => {
@@ -288,14 +324,14 @@ where
                 (Shutdown::Later(timer, deadline), KeepAlive::Until(t)) => {
                     if *deadline != t {
                         *deadline = t;
-                        if let Some(dur) = deadline.checked_duration_since(Instant::now()) {
-                            timer.reset(dur)
+                        if let Some(dur) = deadline.checked_duration_since(clock.now()) {
+                            *timer = clock.timer(dur)
                         }
                     }
                 }
                 (_, KeepAlive::Until(t)) => {
-                    if let Some(dur) = t.checked_duration_since(Instant::now()) {
-                        *shutdown = Shutdown::Later(Delay::new(dur), t)
+                    if let Some(dur) = t.checked_duration_since(clock.now()) {
+                        *shutdown = Shutdown::Later(clock.timer(dur), t)
                     }
                 }
                 (_, KeepAlive::No) => *shutdown = Shutdown::Asap,
@@ -334,7 +370,10 @@ where
                 match muxing.poll_outbound_unpin(cx)? {
                     Poll::Pending => {}
                     Poll::Ready(substream) => {
-                        let (user_data, timeout, upgrade) = requested_substream.extract();
+                        let (user_data, timeout, priority, upgrade) = requested_substream.extract();
+                        if let Some(handle) = muxing.priority_handle(&substream) {
+                            handle.set(priority);
+                        }
 
                         negotiating_out.push(SubstreamUpgrade::new_outbound(
                             substream,
@@ -353,13 +392,34 @@ where
                 match muxing.poll_inbound_unpin(cx)? {
                     Poll::Pending => {}
                     Poll::Ready(substream) => {
-                        let protocol = handler.listen_protocol();
+                        let mut protocol = handler.listen_protocol();
+                        if let Some(timeout) = *inbound_negotiation_timeout {
+                            if *protocol.timeout() > timeout {
+                                protocol = protocol.with_timeout(timeout);
+                            }
+                        }
+                        let priority_handle = muxing.priority_handle(&substream);
+                        if let Some(handle) = &priority_handle {
+                            handle.set(protocol.priority());
+                        }
 
-                        negotiating_in.push(SubstreamUpgrade::new_inbound(substream, protocol));
+                        negotiating_in.push(SubstreamUpgrade::new_inbound(
+                            substream,
+                            protocol,
+                            priority_handle,
+                            clock.as_ref(),
+                        ));
 
                         continue; // Go back to the top, handler can potentially make progress again.
                     }
                 }
+            } else if *close_on_excess_inbound_streams {
+                if let Poll::Ready(_substream) = muxing.poll_inbound_unpin(cx)? {
+                    return Poll::Ready(Err(ConnectionError::IO(io::Error::new(
+                        io::ErrorKind::Other,
+                        "Remote exceeded the maximum number of negotiating inbound streams",
+                    ))));
+                }
             }
 
             return Poll::Pending; // Nothing can make progress, return `Pending`.
@@ -413,25 +473,30 @@ impl std::error::Error for ConnectionLimit {}
 
 struct SubstreamUpgrade<UserData, Upgrade> {
     user_data: Option<UserData>,
-    timeout: Delay,
+    timeout: Timer,
     upgrade: Upgrade,
+    /// The span of the negotiation, a child of the span of the connection.
+    span: tracing::Span,
 }
 
 impl<UserData, Upgrade>
-    SubstreamUpgrade<UserData, OutboundUpgradeApply<SubstreamBox, SendWrapper<Upgrade>>>
+    SubstreamUpgrade<
+        UserData,
+        OutboundUpgradeApply<SubstreamBox, RecordProtocol<SendWrapper<Upgrade>>>,
+    >
 where
     Upgrade: Send + OutboundUpgradeSend,
 {
     fn new_outbound(
         substream: SubstreamBox,
         user_data: UserData,
-        timeout: Delay,
+        timeout: Timer,
         upgrade: Upgrade,
         version_override: Option<upgrade::Version>,
     ) -> Self {
         let effective_version = match version_override {
             Some(version_override) if version_override != upgrade::Version::default() => {
-                log::debug!(
+                tracing::debug!(
                     "Substream upgrade protocol override: {:?} -> {:?}",
                     upgrade::Version::default(),
                     version_override
@@ -442,34 +507,157 @@ where
             _ => upgrade::Version::default(),
         };
 
+        let span = tracing::debug_span!("outbound_substream", protocol = tracing::field::Empty);
+
         Self {
             user_data: Some(user_data),
             timeout,
-            upgrade: upgrade::apply_outbound(substream, SendWrapper(upgrade), effective_version),
+            upgrade: upgrade::apply_outbound(
+                substream,
+                RecordProtocol {
+                    upgrade: SendWrapper(upgrade),
+                    span: span.clone(),
+                },
+                effective_version,
+            ),
+            span,
         }
     }
 }
 
 impl<UserData, Upgrade>
-    SubstreamUpgrade<UserData, InboundUpgradeApply<SubstreamBox, SendWrapper<Upgrade>>>
+    SubstreamUpgrade<
+        UserData,
+        InboundUpgradeApply<SubstreamBox, RecordProtocol<NegotiatedPriority<SendWrapper<Upgrade>>>>,
+    >
 where
     Upgrade: Send + InboundUpgradeSend,
 {
     fn new_inbound(
         substream: SubstreamBox,
         protocol: SubstreamProtocol<Upgrade, UserData>,
+        priority_handle: Option<PriorityHandle>,
+        clock: &dyn Clock,
     ) -> Self {
         let timeout = *protocol.timeout();
+        let protocol_priorities = protocol.protocol_priorities().to_vec();
         let (upgrade, open_info) = protocol.into_upgrade();
+        let span = tracing::debug_span!("inbound_substream", protocol = tracing::field::Empty);
 
         Self {
             user_data: Some(open_info),
-            timeout: Delay::new(timeout),
-            upgrade: upgrade::apply_inbound(substream, SendWrapper(upgrade)),
+            timeout: clock.timer(timeout),
+            upgrade: upgrade::apply_inbound(
+                substream,
+                RecordProtocol {
+                    upgrade: NegotiatedPriority {
+                        upgrade: SendWrapper(upgrade),
+                        protocol_priorities,
+                        priority_handle,
+                    },
+                    span: span.clone(),
+                },
+            ),
+            span,
         }
     }
 }
 
+/// Applies the priority of the negotiated protocol to an inbound substream, if it differs
+/// between the protocols of the upgrade, see [`SubstreamProtocol::protocol_priority`].
+struct NegotiatedPriority<Upgrade> {
+    upgrade: Upgrade,
+    protocol_priorities: Vec<(Vec<u8>, StreamPriority)>,
+    priority_handle: Option<PriorityHandle>,
+}
+
+impl<Upgrade> UpgradeInfo for NegotiatedPriority<Upgrade>
+where
+    Upgrade: UpgradeInfo,
+{
+    type Info = Upgrade::Info;
+    type InfoIter = Upgrade::InfoIter;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.upgrade.protocol_info()
+    }
+}
+
+impl<C, Upgrade> InboundUpgrade<C> for NegotiatedPriority<Upgrade>
+where
+    Upgrade: InboundUpgrade<C>,
+{
+    type Output = Upgrade::Output;
+    type Error = Upgrade::Error;
+    type Future = Upgrade::Future;
+
+    fn upgrade_inbound(self, socket: C, info: Self::Info) -> Self::Future {
+        if let (Some(handle), Some(priority)) = (
+            self.priority_handle,
+            handler::protocol_priority(&self.protocol_priorities, info.protocol_name()),
+        ) {
+            handle.set(priority);
+        }
+
+        self.upgrade.upgrade_inbound(socket, info)
+    }
+}
+
+/// Records the negotiated protocol of a substream in the `protocol` field of its span.
+struct RecordProtocol<Upgrade> {
+    upgrade: Upgrade,
+    span: tracing::Span,
+}
+
+impl<Upgrade> RecordProtocol<Upgrade> {
+    fn record(&self, info: &impl ProtocolName) {
+        self.span.record(
+            "protocol",
+            String::from_utf8_lossy(info.protocol_name()).as_ref(),
+        );
+    }
+}
+
+impl<Upgrade> UpgradeInfo for RecordProtocol<Upgrade>
+where
+    Upgrade: UpgradeInfo,
+{
+    type Info = Upgrade::Info;
+    type InfoIter = Upgrade::InfoIter;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.upgrade.protocol_info()
+    }
+}
+
+impl<C, Upgrade> InboundUpgrade<C> for RecordProtocol<Upgrade>
+where
+    Upgrade: InboundUpgrade<C>,
+{
+    type Output = Upgrade::Output;
+    type Error = Upgrade::Error;
+    type Future = Upgrade::Future;
+
+    fn upgrade_inbound(self, socket: C, info: Self::Info) -> Self::Future {
+        self.record(&info);
+        self.upgrade.upgrade_inbound(socket, info)
+    }
+}
+
+impl<C, Upgrade> OutboundUpgrade<C> for RecordProtocol<Upgrade>
+where
+    Upgrade: OutboundUpgrade<C>,
+{
+    type Output = Upgrade::Output;
+    type Error = Upgrade::Error;
+    type Future = Upgrade::Future;
+
+    fn upgrade_outbound(self, socket: C, info: Self::Info) -> Self::Future {
+        self.record(&info);
+        self.upgrade.upgrade_outbound(socket, info)
+    }
+}
+
 impl<UserData, Upgrade> Unpin for SubstreamUpgrade<UserData, Upgrade> {}
 
 impl<UserData, Upgrade, UpgradeOutput, TUpgradeError> Future for SubstreamUpgrade<UserData, Upgrade>
@@ -482,32 +670,42 @@ where
     );
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let span = self.span.clone();
+        let _entered = span.enter();
+
         match self.timeout.poll_unpin(cx) {
             Poll::Ready(()) => {
+                tracing::debug!("Substream negotiation timed out");
                 return Poll::Ready((
                     self.user_data
                         .take()
                         .expect("Future not to be polled again once ready."),
                     Err(ConnectionHandlerUpgrErr::Timeout),
-                ))
+                ));
             }
 
             Poll::Pending => {}
         }
 
         match self.upgrade.poll_unpin(cx) {
-            Poll::Ready(Ok(upgrade)) => Poll::Ready((
-                self.user_data
-                    .take()
-                    .expect("Future not to be polled again once ready."),
-                Ok(upgrade),
-            )),
-            Poll::Ready(Err(err)) => Poll::Ready((
-                self.user_data
-                    .take()
-                    .expect("Future not to be polled again once ready."),
-                Err(ConnectionHandlerUpgrErr::Upgrade(err)),
-            )),
+            Poll::Ready(Ok(upgrade)) => {
+                tracing::debug!("Substream negotiated");
+                Poll::Ready((
+                    self.user_data
+                        .take()
+                        .expect("Future not to be polled again once ready."),
+                    Ok(upgrade),
+                ))
+            }
+            Poll::Ready(Err(err)) => {
+                tracing::debug!("Substream negotiation failed");
+                Poll::Ready((
+                    self.user_data
+                        .take()
+                        .expect("Future not to be polled again once ready."),
+                    Err(ConnectionHandlerUpgrErr::Upgrade(err)),
+                ))
+            }
             Poll::Pending => Poll::Pending,
         }
     }
@@ -516,7 +714,8 @@ where
 enum SubstreamRequested<UserData, Upgrade> {
     Waiting {
         user_data: UserData,
-        timeout: Delay,
+        timeout: Timer,
+        priority: StreamPriority,
         upgrade: Upgrade,
         /// A waker to notify our [`FuturesUnordered`] that we have extracted the data.
         ///
@@ -528,20 +727,27 @@ enum SubstreamRequested<UserData, Upgrade> {
 }
 
 impl<UserData, Upgrade> SubstreamRequested<UserData, Upgrade> {
-    fn new(user_data: UserData, timeout: Duration, upgrade: Upgrade) -> Self {
+    fn new(
+        user_data: UserData,
+        timeout: Timer,
+        priority: StreamPriority,
+        upgrade: Upgrade,
+    ) -> Self {
         Self::Waiting {
             user_data,
-            timeout: Delay::new(timeout),
+            timeout,
+            priority,
             upgrade,
             extracted_waker: None,
         }
     }
 
-    fn extract(&mut self) -> (UserData, Delay, Upgrade) {
+    fn extract(&mut self) -> (UserData, Timer, StreamPriority, Upgrade) {
         match mem::replace(self, Self::Done) {
             SubstreamRequested::Waiting {
                 user_data,
                 timeout,
+                priority,
                 upgrade,
                 extracted_waker: waker,
             } => {
@@ -549,7 +755,7 @@ impl<UserData, Upgrade> SubstreamRequested<UserData, Upgrade> {
                     waker.wake();
                 }
 
-                (user_data, timeout, upgrade)
+                (user_data, timeout, priority, upgrade)
             }
             SubstreamRequested::Done => panic!("cannot extract twice"),
         }
@@ -569,6 +775,7 @@ impl<UserData, Upgrade> Future for SubstreamRequested<UserData, Upgrade> {
                 user_data,
                 upgrade,
                 mut timeout,
+                priority,
                 ..
             } => match timeout.poll_unpin(cx) {
                 Poll::Ready(()) => Poll::Ready(Err(user_data)),
@@ -577,6 +784,7 @@ impl<UserData, Upgrade> Future for SubstreamRequested<UserData, Upgrade> {
                         user_data,
                         upgrade,
                         timeout,
+                        priority,
                         extracted_waker: Some(cx.waker().clone()),
                     };
                     Poll::Pending
@@ -602,20 +810,20 @@ enum Shutdown {
     None,
     /// A shut down is planned as soon as possible.
     Asap,
-    /// A shut down is planned for when a `Delay` has elapsed.
-    Later(Delay, Instant),
+    /// A shut down is planned for when a [`Timer`] has elapsed.
+    Later(Timer, Instant),
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::keep_alive;
+    use crate::{keep_alive, ManualClock, SystemClock};
     use futures::AsyncRead;
     use futures::AsyncWrite;
     use libp2p_core::upgrade::DeniedUpgrade;
     use libp2p_core::StreamMuxer;
     use quickcheck::*;
-    use std::sync::{Arc, Weak};
+    use std::sync::Weak;
     use void::Void;
 
     #[test]
@@ -632,6 +840,9 @@ mod tests {
                 keep_alive::ConnectionHandler,
                 None,
                 max_negotiating_inbound_streams,
+                false,
+                None,
+                Arc::new(SystemClock),
             );
 
             let result = Pin::new(&mut connection)
@@ -651,18 +862,22 @@ mod tests {
     #[test]
     fn outbound_stream_timeout_starts_on_request() {
         let upgrade_timeout = Duration::from_secs(1);
+        let clock = ManualClock::new();
         let mut connection = Connection::new(
             StreamMuxerBox::new(PendingStreamMuxer),
             MockConnectionHandler::new(upgrade_timeout),
             None,
             2,
+            false,
+            None,
+            Arc::new(clock.clone()),
         );
 
         connection.handler.open_new_outbound();
         let _ = Pin::new(&mut connection)
             .poll(&mut Context::from_waker(futures::task::noop_waker_ref()));
 
-        std::thread::sleep(upgrade_timeout + Duration::from_secs(1));
+        clock.advance(upgrade_timeout);
 
         let _ = Pin::new(&mut connection)
             .poll(&mut Context::from_waker(futures::task::noop_waker_ref()));
@@ -673,6 +888,85 @@ mod tests {
         ))
     }
 
+    #[test]
+    fn close_on_excess_inbound_streams() {
+        let mut connection = Connection::new(
+            StreamMuxerBox::new(DummyStreamMuxer {
+                counter: Arc::new(()),
+            }),
+            keep_alive::ConnectionHandler,
+            None,
+            2,
+            true,
+            None,
+            Arc::new(SystemClock),
+        );
+
+        let result = Pin::new(&mut connection)
+            .poll(&mut Context::from_waker(futures::task::noop_waker_ref()));
+
+        assert!(matches!(result, Poll::Ready(Err(ConnectionError::IO(_)))));
+    }
+
+    #[test]
+    fn inbound_negotiation_timeout_caps_handler_timeout() {
+        let negotiation_timeout = Duration::from_millis(100);
+        let clock = ManualClock::new();
+        let mut connection = Connection::new(
+            StreamMuxerBox::new(DummyStreamMuxer {
+                counter: Arc::new(()),
+            }),
+            MockConnectionHandler::new(Duration::from_secs(60)),
+            None,
+            1,
+            false,
+            Some(negotiation_timeout),
+            Arc::new(clock.clone()),
+        );
+
+        let _ = Pin::new(&mut connection)
+            .poll(&mut Context::from_waker(futures::task::noop_waker_ref()));
+
+        clock.advance(negotiation_timeout);
+
+        let _ = Pin::new(&mut connection)
+            .poll(&mut Context::from_waker(futures::task::noop_waker_ref()));
+
+        assert!(matches!(
+            connection.handler.error.unwrap(),
+            ConnectionHandlerUpgrErr::Timeout
+        ))
+    }
+
+    #[test]
+    fn keep_alive_timeout_follows_clock() {
+        let clock = ManualClock::new();
+        let mut handler = MockConnectionHandler::new(Duration::from_secs(60));
+        handler.keep_alive = KeepAlive::Until(clock.now() + Duration::from_secs(10));
+        let mut connection = Connection::new(
+            StreamMuxerBox::new(PendingStreamMuxer),
+            handler,
+            None,
+            2,
+            false,
+            None,
+            Arc::new(clock.clone()),
+        );
+
+        let result = Pin::new(&mut connection)
+            .poll(&mut Context::from_waker(futures::task::noop_waker_ref()));
+        assert!(result.is_pending());
+
+        clock.advance(Duration::from_secs(10));
+
+        let result = Pin::new(&mut connection)
+            .poll(&mut Context::from_waker(futures::task::noop_waker_ref()));
+        assert!(matches!(
+            result,
+            Poll::Ready(Err(ConnectionError::KeepAliveTimeout))
+        ));
+    }
+
     struct DummyStreamMuxer {
         counter: Arc<()>,
     }
@@ -774,6 +1068,7 @@ mod tests {
         outbound_requested: bool,
         error: Option<ConnectionHandlerUpgrErr<Void>>,
         upgrade_timeout: Duration,
+        keep_alive: KeepAlive,
     }
 
     impl MockConnectionHandler {
@@ -782,6 +1077,7 @@ mod tests {
                 outbound_requested: false,
                 error: None,
                 upgrade_timeout,
+                keep_alive: KeepAlive::Yes,
             }
         }
 
@@ -823,10 +1119,11 @@ mod tests {
                     protocol,
                     ..
                 }) => void::unreachable(protocol),
-                ConnectionEvent::DialUpgradeError(DialUpgradeError { error, .. }) => {
+                ConnectionEvent::DialUpgradeError(DialUpgradeError { error, .. })
+                | ConnectionEvent::ListenUpgradeError(ListenUpgradeError { error, .. }) => {
                     self.error = Some(error)
                 }
-                ConnectionEvent::AddressChange(_) | ConnectionEvent::ListenUpgradeError(_) => {}
+                ConnectionEvent::AddressChange(_) => {}
             }
         }
 
@@ -835,7 +1132,7 @@ mod tests {
         }
 
         fn connection_keep_alive(&self) -> KeepAlive {
-            KeepAlive::Yes
+            self.keep_alive
         }
 
         fn poll(
@@ -30,6 +30,7 @@ use crate::{
     transport::TransportError,
     ConnectedPoint, ConnectionHandler, Executor, Multiaddr, PeerId,
 };
+use crate::{Clock, SystemClock};
 use concurrent_dial::ConcurrentDial;
 use fnv::FnvHashMap;
 use futures::prelude::*;
@@ -43,6 +44,8 @@ use futures::{
 use instant::Instant;
 use libp2p_core::connection::Endpoint;
 use libp2p_core::muxing::{StreamMuxerBox, StreamMuxerExt};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::task::Waker;
 use std::{
     collections::{hash_map, HashMap},
@@ -52,7 +55,9 @@ use std::{
     pin::Pin,
     task::Context,
     task::Poll,
+    time::Duration,
 };
+use tracing::Instrument;
 use void::Void;
 
 mod concurrent_dial;
@@ -114,6 +119,15 @@ where
     /// See [`Connection::max_negotiating_inbound_streams`].
     max_negotiating_inbound_streams: usize,
 
+    /// See [`Connection::close_on_excess_inbound_streams`].
+    close_on_excess_inbound_streams: bool,
+
+    /// See [`Connection::inbound_negotiation_timeout`].
+    inbound_negotiation_timeout: Option<Duration>,
+
+    /// See [`Connection::clock`].
+    clock: Arc<dyn Clock>,
+
     /// How many [`task::EstablishedConnectionEvent`]s can be buffered before the connection is back-pressured.
     per_connection_event_buffer_size: usize,
 
@@ -144,6 +158,9 @@ pub struct EstablishedConnection<TInEvent> {
     endpoint: ConnectedPoint,
     /// Channel endpoint to send commands to the task.
     sender: mpsc::Sender<task::Command<TInEvent>>,
+    /// The reason given when the connection was closed via
+    /// [`EstablishedConnection::start_close_with_reason`].
+    close_reason: Option<String>,
 }
 
 impl<TInEvent> EstablishedConnection<TInEvent> {
@@ -186,6 +203,15 @@ impl<TInEvent> EstablishedConnection<TInEvent> {
             Err(e) => assert!(e.is_disconnected(), "No capacity for close command."),
         };
     }
+
+    /// Initiates a graceful close of the connection, recording the reason
+    /// to be reported once the connection is closed.
+    ///
+    /// The reason of an earlier call is retained.
+    pub fn start_close_with_reason(&mut self, reason: String) {
+        self.close_reason.get_or_insert(reason);
+        self.start_close();
+    }
 }
 
 struct PendingConnection {
@@ -196,6 +222,8 @@ struct PendingConnection {
     abort_notifier: Option<oneshot::Sender<Void>>,
     /// The moment we became aware of this possible connection, useful for timing metrics.
     accepted_at: Instant,
+    /// The number of addresses of an outgoing connection that have not failed yet.
+    remaining_addresses: Option<Arc<AtomicUsize>>,
 }
 
 impl PendingConnection {
@@ -211,6 +239,37 @@ impl PendingConnection {
     }
 }
 
+/// An outgoing connection attempt in progress, see [`crate::Swarm::pending_dials`].
+#[derive(Debug, Clone)]
+pub struct PendingDial {
+    connection_id: ConnectionId,
+    peer_id: Option<PeerId>,
+    remaining_addresses: usize,
+    elapsed: Duration,
+}
+
+impl PendingDial {
+    /// The [`ConnectionId`] of the dial, as given by [`crate::dial_opts::DialOpts::connection_id`].
+    pub fn connection_id(&self) -> ConnectionId {
+        self.connection_id
+    }
+
+    /// The peer being dialed, if known.
+    pub fn peer_id(&self) -> Option<PeerId> {
+        self.peer_id
+    }
+
+    /// The number of addresses that are being or still to be dialed, i.e. have not failed yet.
+    pub fn remaining_addresses(&self) -> usize {
+        self.remaining_addresses
+    }
+
+    /// The time since the dial was started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
 impl<THandler: ConnectionHandler> fmt::Debug for Pool<THandler> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         f.debug_struct("Pool")
@@ -257,6 +316,8 @@ pub enum PoolEvent<THandler: ConnectionHandler> {
         /// The remaining established connections to the same peer.
         remaining_established_connection_ids: Vec<ConnectionId>,
         handler: THandler,
+        /// The reason given when the connection was actively closed, if any.
+        reason: Option<String>,
     },
 
     /// An outbound connection attempt failed.
@@ -321,6 +382,9 @@ where
             dial_concurrency_factor: config.dial_concurrency_factor,
             substream_upgrade_protocol_override: config.substream_upgrade_protocol_override,
             max_negotiating_inbound_streams: config.max_negotiating_inbound_streams,
+            close_on_excess_inbound_streams: config.close_on_excess_inbound_streams,
+            inbound_negotiation_timeout: config.inbound_negotiation_timeout,
+            clock: config.clock,
             per_connection_event_buffer_size: config.per_connection_event_buffer_size,
             executor,
             pending_connection_events_tx,
@@ -346,6 +410,15 @@ where
             .find_map(|connections| connections.get_mut(&id))
     }
 
+    /// Returns the established connection with the given ID to the given peer.
+    pub fn get_established_of_peer(
+        &mut self,
+        peer: &PeerId,
+        id: ConnectionId,
+    ) -> Option<&mut EstablishedConnection<THandler::InEvent>> {
+        self.established.get_mut(peer)?.get_mut(&id)
+    }
+
     /// Returns true if we are connected to the given peer.
     ///
     /// This will return true only after a `NodeReached` event has been produced by `poll()`.
@@ -398,6 +471,37 @@ where
         })
     }
 
+    /// Returns the outgoing connection attempts in progress.
+    pub fn iter_pending_dials(&self) -> impl Iterator<Item = PendingDial> + '_ {
+        self.pending.iter().filter_map(|(id, info)| {
+            // Aborted attempts are only pending until their task terminated.
+            info.abort_notifier.as_ref()?;
+            let remaining = info.remaining_addresses.as_ref()?;
+            Some(PendingDial {
+                connection_id: *id,
+                peer_id: info.peer_id,
+                remaining_addresses: remaining.load(Ordering::Relaxed),
+                elapsed: info.accepted_at.elapsed(),
+            })
+        })
+    }
+
+    /// Aborts the outgoing connection attempt with the given id.
+    ///
+    /// Returns `false` if there is no such connection attempt or it has already been aborted.
+    pub fn abort_dial(&mut self, id: ConnectionId) -> bool {
+        match self.pending.get_mut(&id) {
+            Some(info)
+                if matches!(info.endpoint, PendingPoint::Dialer { .. })
+                    && info.abort_notifier.is_some() =>
+            {
+                info.abort();
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Returns an iterator over all connected peers, i.e. those that have
     /// at least one established connection in the pool.
     pub fn iter_connected(&self) -> impl Iterator<Item = &PeerId> {
@@ -433,15 +537,23 @@ where
             dial_concurrency_factor_override.unwrap_or(self.dial_concurrency_factor),
         );
 
+        let remaining_addresses = dial.remaining();
         let (abort_notifier, abort_receiver) = oneshot::channel();
 
-        self.executor
-            .spawn(task::new_for_pending_outgoing_connection(
+        let span = tracing::debug_span!(
+            "new_outgoing_connection",
+            id = ?connection_id,
+            peer = ?peer,
+        );
+        self.executor.spawn(
+            task::new_for_pending_outgoing_connection(
                 connection_id,
                 dial,
                 abort_receiver,
                 self.pending_connection_events_tx.clone(),
-            ));
+            )
+            .instrument(span),
+        );
 
         let endpoint = PendingPoint::Dialer { role_override };
 
@@ -453,6 +565,7 @@ where
                 endpoint,
                 abort_notifier: Some(abort_notifier),
                 accepted_at: Instant::now(),
+                remaining_addresses: Some(remaining_addresses),
             },
         );
 
@@ -480,13 +593,21 @@ where
 
         let (abort_notifier, abort_receiver) = oneshot::channel();
 
-        self.executor
-            .spawn(task::new_for_pending_incoming_connection(
+        let span = tracing::debug_span!(
+            "new_incoming_connection",
+            id = ?connection_id,
+            local_addr = %info.local_addr,
+            remote_addr = %info.send_back_addr,
+        );
+        self.executor.spawn(
+            task::new_for_pending_incoming_connection(
                 connection_id,
                 future,
                 abort_receiver,
                 self.pending_connection_events_tx.clone(),
-            ));
+            )
+            .instrument(span),
+        );
 
         self.counters.inc_pending_incoming();
         self.pending.insert(
@@ -496,6 +617,7 @@ where
                 endpoint: endpoint.into(),
                 abort_notifier: Some(abort_notifier),
                 accepted_at: Instant::now(),
+                remaining_addresses: None,
             },
         );
 
@@ -524,6 +646,7 @@ where
             EstablishedConnection {
                 endpoint: endpoint.clone(),
                 sender: command_sender,
+                close_reason: None,
             },
         );
         self.established_connection_events.push(event_receiver);
@@ -536,15 +659,30 @@ where
             handler,
             self.substream_upgrade_protocol_override,
             self.max_negotiating_inbound_streams,
+            self.close_on_excess_inbound_streams,
+            self.inbound_negotiation_timeout,
+            self.clock.clone(),
         );
 
-        self.executor.spawn(task::new_for_established_connection(
-            id,
-            obtained_peer_id,
-            connection,
-            command_receiver,
-            event_sender,
-        ))
+        let span = tracing::debug_span!(
+            "connection",
+            id = ?id,
+            peer = %obtained_peer_id,
+            remote_addr = %endpoint.get_remote_address(),
+            direction = if endpoint.is_dialer() { "outbound" } else { "inbound" },
+            relayed = endpoint.is_relayed(),
+            transport = %endpoint.transport(),
+        );
+        self.executor.spawn(
+            task::new_for_established_connection(
+                id,
+                obtained_peer_id,
+                connection,
+                command_receiver,
+                event_sender,
+            )
+            .instrument(span),
+        )
     }
 
     /// Polls the connection pool for events.
@@ -599,8 +737,11 @@ where
                     .established
                     .get_mut(&peer_id)
                     .expect("`Closed` event for established connection");
-                let EstablishedConnection { endpoint, .. } =
-                    connections.remove(&id).expect("Connection to be present");
+                let EstablishedConnection {
+                    endpoint,
+                    close_reason,
+                    ..
+                } = connections.remove(&id).expect("Connection to be present");
                 self.counters.dec_established(&endpoint);
                 let remaining_established_connection_ids: Vec<ConnectionId> =
                     connections.keys().cloned().collect();
@@ -613,6 +754,7 @@ where
                     error,
                     remaining_established_connection_ids,
                     handler,
+                    reason: close_reason,
                 });
             }
         }
@@ -647,6 +789,7 @@ where
                         endpoint,
                         abort_notifier: _,
                         accepted_at,
+                        remaining_addresses: _,
                     } = self
                         .pending
                         .remove(&id)
@@ -728,7 +871,7 @@ where
                     if let Err(error) = error {
                         self.executor.spawn(poll_fn(move |cx| {
                             if let Err(e) = ready!(muxer.poll_close_unpin(cx)) {
-                                log::debug!(
+                                tracing::debug!(
                                     "Failed to close connection {:?} to peer {}: {:?}",
                                     id,
                                     obtained_peer_id,
@@ -781,6 +924,7 @@ where
                         endpoint,
                         abort_notifier: _,
                         accepted_at: _, // Ignoring the time it took for the connection to fail.
+                        remaining_addresses: _,
                     }) = self.pending.remove(&id)
                     {
                         self.counters.dec_pending(&endpoint);
@@ -1122,6 +1266,22 @@ pub struct PoolConfig {
     ///
     /// See [`Connection::max_negotiating_inbound_streams`].
     max_negotiating_inbound_streams: usize,
+
+    /// Whether a connection is closed when the remote exceeds the maximum number of
+    /// inbound streams concurrently negotiating.
+    ///
+    /// See [`Connection::close_on_excess_inbound_streams`].
+    close_on_excess_inbound_streams: bool,
+
+    /// The maximum time for negotiating an inbound stream.
+    ///
+    /// See [`Connection::inbound_negotiation_timeout`].
+    inbound_negotiation_timeout: Option<Duration>,
+
+    /// The clock of the timers of the connections.
+    ///
+    /// See [`Connection::clock`].
+    clock: Arc<dyn Clock>,
 }
 
 impl PoolConfig {
@@ -1133,6 +1293,9 @@ impl PoolConfig {
             dial_concurrency_factor: NonZeroU8::new(8).expect("8 > 0"),
             substream_upgrade_protocol_override: None,
             max_negotiating_inbound_streams: 128,
+            close_on_excess_inbound_streams: false,
+            inbound_negotiation_timeout: None,
+            clock: Arc::new(SystemClock),
         }
     }
 
@@ -1181,6 +1344,31 @@ impl PoolConfig {
         self.max_negotiating_inbound_streams = v;
         self
     }
+
+    /// Whether to close a connection when the remote exceeds the maximum number of
+    /// inbound streams concurrently negotiating.
+    ///
+    /// See [`Connection::close_on_excess_inbound_streams`].
+    pub fn with_close_on_excess_inbound_streams(mut self, v: bool) -> Self {
+        self.close_on_excess_inbound_streams = v;
+        self
+    }
+
+    /// The maximum time for negotiating an inbound stream.
+    ///
+    /// See [`Connection::inbound_negotiation_timeout`].
+    pub fn with_inbound_negotiation_timeout(mut self, v: Duration) -> Self {
+        self.inbound_negotiation_timeout = Some(v);
+        self
+    }
+
+    /// The clock of the timers of the connections.
+    ///
+    /// See [`Connection::clock`].
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
 }
 
 trait EntryExt<'a, K, V> {
@@ -29,6 +29,10 @@ use libp2p_identity::PeerId;
 use std::{
     num::NonZeroU8,
     pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     task::{Context, Poll},
 };
 
@@ -44,12 +48,15 @@ pub struct ConcurrentDial {
     dials: FuturesUnordered<Dial>,
     pending_dials: Box<dyn Iterator<Item = Dial> + Send>,
     errors: Vec<(Multiaddr, TransportError<std::io::Error>)>,
+    /// The number of addresses that have not failed yet.
+    remaining: Arc<AtomicUsize>,
 }
 
 impl Unpin for ConcurrentDial {}
 
 impl ConcurrentDial {
     pub(crate) fn new(pending_dials: Vec<Dial>, concurrency_factor: NonZeroU8) -> Self {
+        let remaining = Arc::new(AtomicUsize::new(pending_dials.len()));
         let mut pending_dials = pending_dials.into_iter();
 
         let dials = FuturesUnordered::new();
@@ -64,8 +71,14 @@ impl ConcurrentDial {
             dials,
             errors: Default::default(),
             pending_dials: Box::new(pending_dials),
+            remaining,
         }
     }
+
+    /// Returns a handle to the number of addresses that have not failed yet.
+    pub(crate) fn remaining(&self) -> Arc<AtomicUsize> {
+        self.remaining.clone()
+    }
 }
 
 impl Future for ConcurrentDial {
@@ -89,6 +102,7 @@ impl Future for ConcurrentDial {
                     return Poll::Ready(Ok((addr, output, errors)));
                 }
                 Some((addr, Err(e))) => {
+                    self.remaining.fetch_sub(1, Ordering::Relaxed);
                     self.errors.push((addr, e));
                     if let Some(dial) = self.pending_dials.next() {
                         self.dials.push(dial)
@@ -23,8 +23,10 @@ use crate::ConnectionId;
 use libp2p_core::connection::Endpoint;
 use libp2p_core::multiaddr::Protocol;
 use libp2p_core::multihash::Multihash;
+use libp2p_core::transport::PortUse;
 use libp2p_core::Multiaddr;
 use libp2p_identity::PeerId;
+use std::any::Any;
 use std::num::NonZeroU8;
 
 /// Options to configure a dial to a known or unknown peer.
@@ -44,8 +46,11 @@ pub struct DialOpts {
     addresses: Vec<Multiaddr>,
     extend_addresses_through_behaviour: bool,
     role_override: Endpoint,
+    port_use: PortUse,
     dial_concurrency_factor_override: Option<NonZeroU8>,
+    simultaneous_open: bool,
     connection_id: ConnectionId,
+    user_data: Option<Box<dyn Any + Send>>,
 }
 
 impl DialOpts {
@@ -65,7 +70,9 @@ impl DialOpts {
             peer_id,
             condition: Default::default(),
             role_override: Endpoint::Dialer,
+            port_use: PortUse::New,
             dial_concurrency_factor_override: Default::default(),
+            simultaneous_open: false,
         }
     }
 
@@ -94,6 +101,26 @@ impl DialOpts {
         self.connection_id
     }
 
+    /// Attach opaque data to this dial attempt.
+    ///
+    /// The data is handed back with the outcome of the dial, i.e. in
+    /// [`SwarmEvent::ConnectionEstablished`](crate::SwarmEvent::ConnectionEstablished) or
+    /// [`SwarmEvent::OutgoingConnectionError`](crate::SwarmEvent::OutgoingConnectionError) and
+    /// by reference in [`ConnectionEstablished`](crate::behaviour::ConnectionEstablished) or
+    /// [`DialFailure`](crate::DialFailure).
+    ///
+    ///   ```
+    ///   # use libp2p_swarm::dial_opts::DialOpts;
+    ///   # use libp2p_identity::PeerId;
+    ///   DialOpts::peer_id(PeerId::random())
+    ///      .build()
+    ///      .with_user_data("bootstrap");
+    ///   ```
+    pub fn with_user_data(mut self, user_data: impl Any + Send) -> Self {
+        self.user_data = Some(Box::new(user_data));
+        self
+    }
+
     /// Retrieves the [`PeerId`] from the [`DialOpts`] if specified or otherwise tries to parse it
     /// from the multihash in the `/p2p` part of the address, if present.
     ///
@@ -147,6 +174,18 @@ impl DialOpts {
     pub(crate) fn role_override(&self) -> Endpoint {
         self.role_override
     }
+
+    pub(crate) fn simultaneous_open(&self) -> bool {
+        self.simultaneous_open
+    }
+
+    pub(crate) fn port_use(&self) -> PortUse {
+        self.port_use
+    }
+
+    pub(crate) fn take_user_data(&mut self) -> Option<Box<dyn Any + Send>> {
+        self.user_data.take()
+    }
 }
 
 impl From<Multiaddr> for DialOpts {
@@ -166,7 +205,9 @@ pub struct WithPeerId {
     peer_id: PeerId,
     condition: PeerCondition,
     role_override: Endpoint,
+    port_use: PortUse,
     dial_concurrency_factor_override: Option<NonZeroU8>,
+    simultaneous_open: bool,
 }
 
 impl WithPeerId {
@@ -191,7 +232,9 @@ impl WithPeerId {
             addresses,
             extend_addresses_through_behaviour: false,
             role_override: self.role_override,
+            port_use: self.port_use,
             dial_concurrency_factor_override: self.dial_concurrency_factor_override,
+            simultaneous_open: self.simultaneous_open,
         }
     }
 
@@ -206,6 +249,27 @@ impl WithPeerId {
         self
     }
 
+    /// Bind the local socket of the connection to the port of a listening socket, if supported
+    /// by the transport. By default, an ephemeral port is used.
+    ///
+    /// Intended for dials that are part of a hole punch, see
+    /// [`PortUse::Reuse`](libp2p_core::transport::PortUse::Reuse).
+    pub fn reuse_port(mut self) -> Self {
+        self.port_use = PortUse::Reuse;
+        self
+    }
+
+    /// Dial the peer while it dials us at the same time, e.g. as part of a hole punch.
+    ///
+    /// The role of the local node in upgrading the resulting connection is resolved by comparing
+    /// the peer IDs of both nodes, see
+    /// [`simultaneous_open_role`](libp2p_core::upgrade::simultaneous_open_role), and overrides
+    /// the role set via `override_role`. Both nodes need to dial with this option.
+    pub fn simultaneous_open(mut self) -> Self {
+        self.simultaneous_open = true;
+        self
+    }
+
     /// Build the final [`DialOpts`].
     ///
     /// Addresses to dial the peer are retrieved via
@@ -217,8 +281,11 @@ impl WithPeerId {
             addresses: vec![],
             extend_addresses_through_behaviour: true,
             role_override: self.role_override,
+            port_use: self.port_use,
             dial_concurrency_factor_override: self.dial_concurrency_factor_override,
+            simultaneous_open: self.simultaneous_open,
             connection_id: ConnectionId::next(),
+            user_data: None,
         }
     }
 }
@@ -230,7 +297,9 @@ pub struct WithPeerIdWithAddresses {
     addresses: Vec<Multiaddr>,
     extend_addresses_through_behaviour: bool,
     role_override: Endpoint,
+    port_use: PortUse,
     dial_concurrency_factor_override: Option<NonZeroU8>,
+    simultaneous_open: bool,
 }
 
 impl WithPeerIdWithAddresses {
@@ -258,6 +327,24 @@ impl WithPeerIdWithAddresses {
         self
     }
 
+    /// Bind the local socket of the connection to the port of a listening socket, see
+    /// [`WithPeerId::reuse_port`].
+    pub fn reuse_port(mut self) -> Self {
+        self.port_use = PortUse::Reuse;
+        self
+    }
+
+    /// Dial the peer while it dials us at the same time, e.g. as part of a hole punch.
+    ///
+    /// The role of the local node in upgrading the resulting connection is resolved by comparing
+    /// the peer IDs of both nodes, see
+    /// [`simultaneous_open_role`](libp2p_core::upgrade::simultaneous_open_role), and overrides
+    /// the role set via `override_role`. Both nodes need to dial with this option.
+    pub fn simultaneous_open(mut self) -> Self {
+        self.simultaneous_open = true;
+        self
+    }
+
     /// Override
     /// Number of addresses concurrently dialed for a single outbound connection attempt.
     pub fn override_dial_concurrency_factor(mut self, factor: NonZeroU8) -> Self {
@@ -273,8 +360,11 @@ impl WithPeerIdWithAddresses {
             addresses: self.addresses,
             extend_addresses_through_behaviour: self.extend_addresses_through_behaviour,
             role_override: self.role_override,
+            port_use: self.port_use,
             dial_concurrency_factor_override: self.dial_concurrency_factor_override,
+            simultaneous_open: self.simultaneous_open,
             connection_id: ConnectionId::next(),
+            user_data: None,
         }
     }
 }
@@ -288,6 +378,7 @@ impl WithoutPeerId {
         WithoutPeerIdWithAddress {
             address,
             role_override: Endpoint::Dialer,
+            port_use: PortUse::New,
         }
     }
 }
@@ -296,6 +387,7 @@ impl WithoutPeerId {
 pub struct WithoutPeerIdWithAddress {
     address: Multiaddr,
     role_override: Endpoint,
+    port_use: PortUse,
 }
 
 impl WithoutPeerIdWithAddress {
@@ -309,6 +401,13 @@ impl WithoutPeerIdWithAddress {
         self.role_override = Endpoint::Listener;
         self
     }
+
+    /// Bind the local socket of the connection to the port of a listening socket, see
+    /// [`WithPeerId::reuse_port`].
+    pub fn reuse_port(mut self) -> Self {
+        self.port_use = PortUse::Reuse;
+        self
+    }
     /// Build the final [`DialOpts`].
     pub fn build(self) -> DialOpts {
         DialOpts {
@@ -317,8 +416,11 @@ impl WithoutPeerIdWithAddress {
             addresses: vec![self.address],
             extend_addresses_through_behaviour: false,
             role_override: self.role_override,
+            port_use: self.port_use,
             dial_concurrency_factor_override: None,
+            simultaneous_open: false,
             connection_id: ConnectionId::next(),
+            user_data: None,
         }
     }
 }
@@ -0,0 +1,113 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::SwarmEvent;
+use std::ops::{BitOr, BitOrAssign};
+
+/// A set of kinds of [`SwarmEvent`]s an application is interested in,
+/// see [`SwarmBuilder::event_filter`](crate::SwarmBuilder::event_filter).
+///
+/// Events of other kinds are dropped by the [`Swarm`](crate::Swarm) instead of being returned,
+/// thus do not wake up the task polling it. Kinds are combined with `|`, e.g.
+/// `EventFilter::BEHAVIOUR | EventFilter::CONNECTION`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventFilter {
+    bits: u8,
+}
+
+impl EventFilter {
+    /// No events.
+    pub const NONE: Self = Self { bits: 0 };
+    /// [`SwarmEvent::Behaviour`].
+    pub const BEHAVIOUR: Self = Self { bits: 1 };
+    /// [`SwarmEvent::ConnectionEstablished`] and [`SwarmEvent::ConnectionClosed`].
+    pub const CONNECTION: Self = Self { bits: 1 << 1 };
+    /// [`SwarmEvent::IncomingConnection`] and [`SwarmEvent::IncomingConnectionError`].
+    pub const INCOMING: Self = Self { bits: 1 << 2 };
+    /// [`SwarmEvent::Dialing`], [`SwarmEvent::DialAddresses`],
+    /// [`SwarmEvent::OutgoingConnectionError`] and `SwarmEvent::BannedPeer`.
+    pub const DIAL: Self = Self { bits: 1 << 3 };
+    /// [`SwarmEvent::NewListenAddr`], [`SwarmEvent::ExpiredListenAddr`],
+    /// [`SwarmEvent::ListenerClosed`] and [`SwarmEvent::ListenerError`].
+    pub const LISTENER: Self = Self { bits: 1 << 4 };
+    /// [`SwarmEvent::NodeStatus`].
+    pub const NODE_STATUS: Self = Self { bits: 1 << 5 };
+    /// All events, the default.
+    pub const ALL: Self = Self { bits: (1 << 6) - 1 };
+
+    /// Returns the kinds of events in either `self` or `other`.
+    pub const fn union(self, other: Self) -> Self {
+        Self {
+            bits: self.bits | other.bits,
+        }
+    }
+
+    /// Whether all kinds of events in `other` are in `self`.
+    pub const fn contains(self, other: Self) -> bool {
+        self.bits & other.bits == other.bits
+    }
+
+    /// Whether the given event is of one of the kinds in `self`.
+    pub fn matches<TBehaviourOutEvent, THandlerErr>(
+        self,
+        event: &SwarmEvent<TBehaviourOutEvent, THandlerErr>,
+    ) -> bool {
+        #[allow(deprecated)]
+        let kind = match event {
+            SwarmEvent::Behaviour(_) => Self::BEHAVIOUR,
+            SwarmEvent::ConnectionEstablished { .. } | SwarmEvent::ConnectionClosed { .. } => {
+                Self::CONNECTION
+            }
+            SwarmEvent::IncomingConnection { .. } | SwarmEvent::IncomingConnectionError { .. } => {
+                Self::INCOMING
+            }
+            SwarmEvent::Dialing(_)
+            | SwarmEvent::DialAddresses { .. }
+            | SwarmEvent::OutgoingConnectionError { .. }
+            | SwarmEvent::BannedPeer { .. } => Self::DIAL,
+            SwarmEvent::NewListenAddr { .. }
+            | SwarmEvent::ExpiredListenAddr { .. }
+            | SwarmEvent::ListenerClosed { .. }
+            | SwarmEvent::ListenerError { .. } => Self::LISTENER,
+            SwarmEvent::NodeStatus(_) => Self::NODE_STATUS,
+        };
+        self.contains(kind)
+    }
+}
+
+impl Default for EventFilter {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl BitOr for EventFilter {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+impl BitOrAssign for EventFilter {
+    fn bitor_assign(&mut self, rhs: Self) {
+        *self = self.union(rhs);
+    }
+}
@@ -47,6 +47,7 @@ mod pending;
 mod select;
 
 pub use crate::upgrade::{InboundUpgradeSend, OutboundUpgradeSend, SendWrapper, UpgradeInfoSend};
+pub use libp2p_core::muxing::StreamPriority;
 
 use instant::Instant;
 use libp2p_core::{upgrade::UpgradeError, ConnectedPoint, Multiaddr};
@@ -258,11 +259,15 @@ pub struct ListenUpgradeError<IOI, IP: InboundUpgradeSend> {
 ///
 /// The inbound substream protocol(s) are defined by [`ConnectionHandler::listen_protocol`]
 /// and the outbound substream protocol(s) by [`ConnectionHandlerEvent::OutboundSubstreamRequest`].
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SubstreamProtocol<TUpgrade, TInfo> {
     upgrade: TUpgrade,
     info: TInfo,
     timeout: Duration,
+    priority: StreamPriority,
+    /// The priorities of individual protocols of the upgrade, applied once one of them is
+    /// negotiated, see [`ConnectionHandlerSelect`].
+    protocol_priorities: Vec<(Vec<u8>, StreamPriority)>,
 }
 
 impl<TUpgrade, TInfo> SubstreamProtocol<TUpgrade, TInfo> {
@@ -275,6 +280,8 @@ impl<TUpgrade, TInfo> SubstreamProtocol<TUpgrade, TInfo> {
             upgrade,
             info,
             timeout: Duration::from_secs(10),
+            priority: StreamPriority::Normal,
+            protocol_priorities: Vec::new(),
         }
     }
 
@@ -287,6 +294,8 @@ impl<TUpgrade, TInfo> SubstreamProtocol<TUpgrade, TInfo> {
             upgrade: f(self.upgrade),
             info: self.info,
             timeout: self.timeout,
+            priority: self.priority,
+            protocol_priorities: self.protocol_priorities,
         }
     }
 
@@ -299,6 +308,8 @@ impl<TUpgrade, TInfo> SubstreamProtocol<TUpgrade, TInfo> {
             upgrade: self.upgrade,
             info: f(self.info),
             timeout: self.timeout,
+            priority: self.priority,
+            protocol_priorities: self.protocol_priorities,
         }
     }
 
@@ -308,6 +319,31 @@ impl<TUpgrade, TInfo> SubstreamProtocol<TUpgrade, TInfo> {
         self
     }
 
+    /// Sets the priority of the substream, used by the muxer to schedule writes when the
+    /// connection is congested (see [`StreamMuxer::priority_handle`]).
+    ///
+    /// The listen protocol of a [`ConnectionHandlerSelect`] applies the priority of the handler
+    /// whose protocol is negotiated on an inbound substream.
+    ///
+    /// Only muxers implementing [`StreamMuxer::priority_handle`] honour the priority, e.g.
+    /// mplex. It is ignored by yamux.
+    ///
+    /// [`StreamMuxer::priority_handle`]: libp2p_core::muxing::StreamMuxer::priority_handle
+    pub fn with_priority(mut self, priority: StreamPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Sets the priorities of individual protocols of the upgrade, taking precedence over
+    /// [`SubstreamProtocol::priority`] once one of them is negotiated.
+    pub(crate) fn with_protocol_priorities(
+        mut self,
+        protocol_priorities: Vec<(Vec<u8>, StreamPriority)>,
+    ) -> Self {
+        self.protocol_priorities = protocol_priorities;
+        self
+    }
+
     /// Borrows the contained protocol upgrade.
     pub fn upgrade(&self) -> &TUpgrade {
         &self.upgrade
@@ -323,14 +359,40 @@ impl<TUpgrade, TInfo> SubstreamProtocol<TUpgrade, TInfo> {
         &self.timeout
     }
 
+    /// Returns the priority of the substream.
+    pub fn priority(&self) -> StreamPriority {
+        self.priority
+    }
+
+    /// Returns the priority of the substream once the given protocol is negotiated.
+    pub(crate) fn protocol_priority(&self, protocol: &[u8]) -> StreamPriority {
+        protocol_priority(&self.protocol_priorities, protocol).unwrap_or(self.priority)
+    }
+
+    /// Returns the priorities of individual protocols of the upgrade.
+    pub(crate) fn protocol_priorities(&self) -> &[(Vec<u8>, StreamPriority)] {
+        &self.protocol_priorities
+    }
+
     /// Converts the substream protocol configuration into the contained upgrade.
     pub fn into_upgrade(self) -> (TUpgrade, TInfo) {
         (self.upgrade, self.info)
     }
 }
 
+/// Looks up the priority of a protocol in the priorities of individual protocols of an upgrade.
+pub(crate) fn protocol_priority(
+    protocol_priorities: &[(Vec<u8>, StreamPriority)],
+    protocol: &[u8],
+) -> Option<StreamPriority> {
+    protocol_priorities
+        .iter()
+        .find(|(name, _)| name == protocol)
+        .map(|(_, priority)| *priority)
+}
+
 /// Event produced by a handler.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ConnectionHandlerEvent<TConnectionUpgrade, TOutboundOpenInfo, TCustom, TErr> {
     /// Request a new outbound substream to be opened with the remote.
     OutboundSubstreamRequest {
@@ -41,6 +41,7 @@ use std::{
     error,
     fmt::{self, Debug},
     hash::Hash,
+    io,
     iter::{self, FromIterator},
     task::{Context, Poll},
     time::Duration,
@@ -199,6 +200,54 @@ where
                         }
                     }
                 }
+                ProtocolError::ProtocolNameTooLong => {
+                    for (k, h) in &mut self.handlers {
+                        if let Some(i) = info.take(k) {
+                            let e =
+                                NegotiationError::ProtocolError(ProtocolError::ProtocolNameTooLong);
+                            h.on_connection_event(ConnectionEvent::ListenUpgradeError(
+                                ListenUpgradeError {
+                                    info: i,
+                                    error: ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Select(
+                                        e,
+                                    )),
+                                },
+                            ));
+                        }
+                    }
+                }
+                ProtocolError::TooManyBytes => {
+                    for (k, h) in &mut self.handlers {
+                        if let Some(i) = info.take(k) {
+                            let e = NegotiationError::ProtocolError(ProtocolError::TooManyBytes);
+                            h.on_connection_event(ConnectionEvent::ListenUpgradeError(
+                                ListenUpgradeError {
+                                    info: i,
+                                    error: ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Select(
+                                        e,
+                                    )),
+                                },
+                            ));
+                        }
+                    }
+                }
+                e => {
+                    for (k, h) in &mut self.handlers {
+                        if let Some(i) = info.take(k) {
+                            let e = NegotiationError::ProtocolError(ProtocolError::IoError(
+                                io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+                            ));
+                            h.on_connection_event(ConnectionEvent::ListenUpgradeError(
+                                ListenUpgradeError {
+                                    info: i,
+                                    error: ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Select(
+                                        e,
+                                    )),
+                                },
+                            ));
+                        }
+                    }
+                }
             },
             ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Apply((k, e))) => {
                 if let Some(h) = self.handlers.get_mut(&k) {
@@ -275,7 +324,7 @@ where
                         },
                     ));
                 } else {
-                    log::error!("FullyNegotiatedOutbound: no handler for key")
+                    tracing::error!("FullyNegotiatedOutbound: no handler for key")
                 }
             }
             ConnectionEvent::FullyNegotiatedInbound(FullyNegotiatedInbound {
@@ -292,7 +341,7 @@ where
                         ));
                     }
                 } else {
-                    log::error!("FullyNegotiatedInbound: no handler for key")
+                    tracing::error!("FullyNegotiatedInbound: no handler for key")
                 }
             }
             ConnectionEvent::AddressChange(AddressChange { new_address }) => {
@@ -312,7 +361,7 @@ where
                         error,
                     }));
                 } else {
-                    log::error!("DialUpgradeError: no handler for protocol")
+                    tracing::error!("DialUpgradeError: no handler for protocol")
                 }
             }
             ConnectionEvent::ListenUpgradeError(listen_upgrade_error) => {
@@ -325,7 +374,7 @@ where
         if let Some(h) = self.handlers.get_mut(&key) {
             h.on_behaviour_event(event)
         } else {
-            log::error!("on_behaviour_event: no handler for key")
+            tracing::error!("on_behaviour_event: no handler for key")
         }
     }
 
@@ -23,17 +23,18 @@ use crate::handler::IntoConnectionHandler;
 use crate::handler::{
     AddressChange, ConnectionEvent, ConnectionHandler, ConnectionHandlerEvent,
     ConnectionHandlerUpgrErr, DialUpgradeError, FullyNegotiatedInbound, FullyNegotiatedOutbound,
-    InboundUpgradeSend, KeepAlive, ListenUpgradeError, OutboundUpgradeSend, SubstreamProtocol,
+    InboundUpgradeSend, KeepAlive, ListenUpgradeError, OutboundUpgradeSend, StreamPriority,
+    SubstreamProtocol, UpgradeInfoSend,
 };
 use crate::upgrade::SendWrapper;
 use either::Either;
 use futures::future;
 use libp2p_core::{
-    upgrade::{NegotiationError, ProtocolError, SelectUpgrade, UpgradeError},
+    upgrade::{NegotiationError, ProtocolError, ProtocolName, SelectUpgrade, UpgradeError},
     ConnectedPoint,
 };
 use libp2p_identity::PeerId;
-use std::{cmp, task::Context, task::Poll};
+use std::{cmp, io, task::Context, task::Poll};
 
 /// Implementation of `IntoConnectionHandler` that combines two protocols into one.
 #[derive(Debug, Clone)]
@@ -304,6 +305,20 @@ where
                         e1 = NegotiationError::ProtocolError(ProtocolError::TooManyProtocols);
                         e2 = NegotiationError::ProtocolError(ProtocolError::TooManyProtocols)
                     }
+                    ProtocolError::ProtocolNameTooLong => {
+                        e1 = NegotiationError::ProtocolError(ProtocolError::ProtocolNameTooLong);
+                        e2 = NegotiationError::ProtocolError(ProtocolError::ProtocolNameTooLong)
+                    }
+                    ProtocolError::TooManyBytes => {
+                        e1 = NegotiationError::ProtocolError(ProtocolError::TooManyBytes);
+                        e2 = NegotiationError::ProtocolError(ProtocolError::TooManyBytes)
+                    }
+                    e => {
+                        e1 = NegotiationError::ProtocolError(ProtocolError::IoError(
+                            io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+                        ));
+                        e2 = NegotiationError::ProtocolError(e)
+                    }
                 }
                 self.proto1
                     .on_connection_event(ConnectionEvent::ListenUpgradeError(ListenUpgradeError {
@@ -355,10 +370,17 @@ where
         let proto1 = self.proto1.listen_protocol();
         let proto2 = self.proto2.listen_protocol();
         let timeout = *std::cmp::max(proto1.timeout(), proto2.timeout());
+        // Which of the protocols is negotiated is not known yet, hence the priority of the
+        // handler is applied once one of its protocols is negotiated.
+        let protocol_priorities = protocol_priorities(&proto1)
+            .chain(protocol_priorities(&proto2))
+            .collect();
         let (u1, i1) = proto1.into_upgrade();
         let (u2, i2) = proto2.into_upgrade();
         let choice = SelectUpgrade::new(SendWrapper(u1), SendWrapper(u2));
-        SubstreamProtocol::new(choice, (i1, i2)).with_timeout(timeout)
+        SubstreamProtocol::new(choice, (i1, i2))
+            .with_timeout(timeout)
+            .with_protocol_priorities(protocol_priorities)
     }
 
     fn on_behaviour_event(&mut self, event: Self::InEvent) {
@@ -480,3 +502,57 @@ where
         }
     }
 }
+
+/// The priority of each protocol of the upgrade of a [`SubstreamProtocol`].
+fn protocol_priorities<TUpgrade, TInfo>(
+    protocol: &SubstreamProtocol<TUpgrade, TInfo>,
+) -> impl Iterator<Item = (Vec<u8>, StreamPriority)> + '_
+where
+    TUpgrade: UpgradeInfoSend,
+{
+    protocol.upgrade().protocol_info().map(|info| {
+        let name = info.protocol_name().to_vec();
+        let priority = protocol.protocol_priority(&name);
+        (name, priority)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::OneShotHandler;
+    use crate::NegotiatedSubstream;
+    use libp2p_core::upgrade::ReadyUpgrade;
+
+    fn handler(
+        protocol: &'static str,
+        priority: StreamPriority,
+    ) -> OneShotHandler<ReadyUpgrade<&'static str>, ReadyUpgrade<&'static str>, NegotiatedSubstream>
+    {
+        OneShotHandler::new(
+            SubstreamProtocol::new(ReadyUpgrade::new(protocol), ()).with_priority(priority),
+            Default::default(),
+        )
+    }
+
+    #[test]
+    fn listen_protocol_applies_priority_of_negotiated_protocol() {
+        let handler = ConnectionHandler::select(
+            ConnectionHandler::select(
+                handler("/high", StreamPriority::High),
+                handler("/low", StreamPriority::Low),
+            ),
+            handler("/normal", StreamPriority::Normal),
+        );
+
+        let protocol = handler.listen_protocol();
+
+        assert_eq!(protocol.priority(), StreamPriority::Normal);
+        assert_eq!(protocol.protocol_priority(b"/high"), StreamPriority::High);
+        assert_eq!(protocol.protocol_priority(b"/low"), StreamPriority::Low);
+        assert_eq!(
+            protocol.protocol_priority(b"/normal"),
+            StreamPriority::Normal
+        );
+    }
+}
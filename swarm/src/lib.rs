@@ -55,7 +55,10 @@
 
 #![cfg_attr(docsrs, feature(doc_cfg, doc_auto_cfg))]
 
+mod clock;
+mod command;
 mod connection;
+mod event_filter;
 mod registry;
 #[cfg(test)]
 mod test;
@@ -64,9 +67,10 @@ mod upgrade;
 pub mod behaviour;
 pub mod dial_opts;
 pub mod dummy;
-mod executor;
 pub mod handler;
 pub mod keep_alive;
+mod listen_opts;
+mod status;
 
 /// Bundles all symbols required for the [`libp2p_swarm_derive::NetworkBehaviour`] macro.
 #[doc(hidden)]
@@ -81,9 +85,13 @@ pub mod derive_prelude {
     pub use crate::behaviour::ListenFailure;
     pub use crate::behaviour::ListenerClosed;
     pub use crate::behaviour::ListenerError;
+    pub use crate::behaviour::NatStatusChanged;
+    pub use crate::behaviour::NestedPollParameters;
     pub use crate::behaviour::NewExternalAddr;
     pub use crate::behaviour::NewListenAddr;
     pub use crate::behaviour::NewListener;
+    pub use crate::behaviour::PeerListenAddrsChanged;
+    pub use crate::behaviour::PeerProtocolsChanged;
     pub use crate::connection::ConnectionId;
     pub use crate::ConnectionDenied;
     pub use crate::ConnectionHandler;
@@ -113,13 +121,16 @@ pub use behaviour::NetworkBehaviourAction;
 pub use behaviour::{
     AddressChange, CloseConnection, ConnectionClosed, DialFailure, ExpiredExternalAddr,
     ExpiredListenAddr, ExternalAddresses, FromSwarm, ListenAddresses, ListenFailure,
-    ListenerClosed, ListenerError, NetworkBehaviour, NewExternalAddr, NewListenAddr, NotifyHandler,
-    PollParameters, ToSwarm,
+    ListenerClosed, ListenerError, NatStatusChanged, NetworkBehaviour, NewExternalAddr,
+    NewListenAddr, NotifyHandler, PeerListenAddrsChanged, PeerProtocolsChanged, PollParameters,
+    ToSwarm,
 };
+pub use clock::{Clock, ManualClock, SystemClock, Timer};
+pub use command::{BehaviourCommand, CommandError, CommandSender};
 #[allow(deprecated)]
-pub use connection::pool::{ConnectionCounters, ConnectionLimits};
+pub use connection::pool::{ConnectionCounters, ConnectionLimits, PendingDial};
 pub use connection::{ConnectionError, ConnectionId};
-pub use executor::Executor;
+pub use event_filter::EventFilter;
 #[allow(deprecated)]
 pub use handler::IntoConnectionHandler;
 pub use handler::{
@@ -127,37 +138,45 @@ pub use handler::{
     IntoConnectionHandlerSelect, KeepAlive, OneShotHandler, OneShotHandlerConfig,
     SubstreamProtocol,
 };
+pub use libp2p_core::executor::Executor;
 #[cfg(feature = "macros")]
 pub use libp2p_swarm_derive::NetworkBehaviour;
+pub use listen_opts::ListenOpts;
 pub use registry::{AddAddressResult, AddressRecord, AddressScore};
+pub use status::{NatStatus, NodeStatus};
 
 use crate::handler::UpgradeInfoSend;
+use command::{Command, COMMAND_BUFFER_SIZE};
 use connection::pool::{EstablishedConnection, Pool, PoolConfig, PoolEvent};
 use connection::IncomingInfo;
 use connection::{
     PendingConnectionError, PendingInboundConnectionError, PendingOutboundConnectionError,
 };
 use dial_opts::{DialOpts, PeerCondition};
-use futures::{executor::ThreadPoolBuilder, prelude::*, stream::FusedStream};
+use futures::{channel::mpsc, executor::ThreadPoolBuilder, prelude::*, stream::FusedStream};
 use libp2p_core::muxing::SubstreamBox;
 use libp2p_core::{
     connection::ConnectedPoint,
     multiaddr::Protocol,
     multihash::Multihash,
     muxing::StreamMuxerBox,
-    transport::{self, ListenerId, TransportError, TransportEvent},
-    Endpoint, Multiaddr, Negotiated, ProtocolName, Transport,
+    transport::{self, ListenerId, TransportError, TransportEvent, TransportKind},
+    upgrade::simultaneous_open_role,
+    Multiaddr, Negotiated, ProtocolName, Transport,
 };
 use libp2p_identity::PeerId;
 use registry::{AddressIntoIter, Addresses};
 use smallvec::SmallVec;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::num::{NonZeroU32, NonZeroU8, NonZeroUsize};
 use std::{
+    any::Any,
     convert::TryFrom,
     error, fmt, io,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
+    time::Duration,
 };
 
 /// Substream for which a protocol has been chosen.
@@ -205,6 +224,9 @@ pub enum SwarmEvent<TBehaviourOutEvent, THandlerErr> {
         concurrent_dial_errors: Option<Vec<(Multiaddr, TransportError<io::Error>)>>,
         /// How long it took to establish this connection
         established_in: std::time::Duration,
+        /// [`Some`] when the new connection is an outgoing connection dialed with data attached
+        /// via [`DialOpts::with_user_data`].
+        user_data: Option<Box<dyn Any + Send>>,
     },
     /// A connection with the given peer has been closed,
     /// possibly as a result of an error.
@@ -218,6 +240,9 @@ pub enum SwarmEvent<TBehaviourOutEvent, THandlerErr> {
         /// Reason for the disconnection, if it was not a successful
         /// active close.
         cause: Option<ConnectionError<THandlerErr>>,
+        /// The reason given to [`Swarm::close_connection`], if the connection
+        /// was closed that way.
+        reason: Option<String>,
     },
     /// A new connection arrived on a listener and is in the process of protocol negotiation.
     ///
@@ -253,6 +278,8 @@ pub enum SwarmEvent<TBehaviourOutEvent, THandlerErr> {
         peer_id: Option<PeerId>,
         /// Error that has been encountered.
         error: DialError,
+        /// The data attached to the dial via [`DialOpts::with_user_data`], if any.
+        user_data: Option<Box<dyn Any + Send>>,
     },
     /// We connected to a peer, but we immediately closed the connection because that peer is banned.
     #[deprecated(note = "Use `libp2p::allow_block_list` instead.", since = "0.42.1")]
@@ -303,6 +330,21 @@ pub enum SwarmEvent<TBehaviourOutEvent, THandlerErr> {
     /// [`OutgoingConnectionError`](SwarmEvent::OutgoingConnectionError) event
     /// is reported.
     Dialing(PeerId),
+    /// The addresses of a new dialing attempt, after normalization and deduplication.
+    ///
+    /// Only reported if enabled via [`SwarmBuilder::report_dial_addresses`].
+    DialAddresses {
+        /// If known, [`PeerId`] of the peer being dialed.
+        peer_id: Option<PeerId>,
+        /// The [`ConnectionId`] of the dialing attempt.
+        connection_id: ConnectionId,
+        /// The addresses being dialed, without the `/p2p/` suffix of the dialed peer.
+        addresses: Vec<Multiaddr>,
+    },
+    /// A periodic snapshot of the state of the [`Swarm`].
+    ///
+    /// Only reported if enabled via [`SwarmBuilder::node_status_interval`].
+    NodeStatus(NodeStatus),
 }
 
 impl<TBehaviourOutEvent, THandlerErr> SwarmEvent<TBehaviourOutEvent, THandlerErr> {
@@ -314,6 +356,23 @@ impl<TBehaviourOutEvent, THandlerErr> SwarmEvent<TBehaviourOutEvent, THandlerErr
             other => Err(other),
         }
     }
+
+    /// Returns the kind of transport of the connection the event is about, if any.
+    ///
+    /// For [`SwarmEvent::OutgoingConnectionError`], see [`DialError::transports`] instead.
+    pub fn transport(&self) -> Option<TransportKind> {
+        match self {
+            #[allow(deprecated)]
+            SwarmEvent::ConnectionEstablished { endpoint, .. }
+            | SwarmEvent::ConnectionClosed { endpoint, .. }
+            | SwarmEvent::BannedPeer { endpoint, .. } => Some(endpoint.transport()),
+            SwarmEvent::IncomingConnection { local_addr, .. }
+            | SwarmEvent::IncomingConnectionError { local_addr, .. } => {
+                Some(TransportKind::from_address(local_addr))
+            }
+            _ => None,
+        }
+    }
 }
 
 /// Contains the state of the network, plus the way it should behave.
@@ -343,6 +402,9 @@ where
     /// Multiaddresses that our listeners are listening on,
     listened_addrs: HashMap<ListenerId, SmallVec<[Multiaddr; 1]>>,
 
+    /// Options of the active listeners.
+    listener_opts: HashMap<ListenerId, ListenOpts>,
+
     /// List of multiaddresses we're listening on, after account for external IP addresses and
     /// similar mechanisms.
     external_addrs: Addresses,
@@ -354,6 +416,43 @@ where
     /// (or dropped if the peer disconnected) before the `behaviour`
     /// can be polled again.
     pending_event: Option<(PeerId, PendingNotifyHandler, THandlerInEvent<TBehaviour>)>,
+
+    /// The number of times the `behaviour` has been polled, see [`PollParameters::poll_count`].
+    behaviour_polls: u64,
+
+    /// Data attached to pending outbound connections via [`DialOpts::with_user_data`].
+    dial_user_data: HashMap<ConnectionId, Box<dyn Any + Send>>,
+
+    /// See [`SwarmBuilder::report_dial_addresses`].
+    report_dial_addresses: bool,
+
+    /// The addresses of dialing attempts to report via [`SwarmEvent::DialAddresses`].
+    pending_dial_addresses: VecDeque<(Option<PeerId>, ConnectionId, Vec<Multiaddr>)>,
+
+    /// Sending half of the command channel, cloned into every [`CommandSender`].
+    command_sender: mpsc::Sender<Command<TBehaviour>>,
+
+    /// Commands sent via a [`CommandSender`], processed before polling the `behaviour`.
+    commands: mpsc::Receiver<Command<TBehaviour>>,
+
+    /// The protocols supported by connected peers, as reported via
+    /// [`ToSwarm::ReportPeerProtocols`].
+    peer_protocols: HashMap<PeerId, HashSet<String>>,
+
+    /// See [`Swarm::set_nat_status`] and [`ToSwarm::ReportNatStatus`].
+    nat_status: NatStatus,
+
+    /// The clock of the timers of the `Swarm`, see [`SwarmBuilder::clock`].
+    clock: Arc<dyn Clock>,
+
+    /// The timer and interval for reporting [`SwarmEvent::NodeStatus`], if enabled.
+    node_status_timer: Option<(Timer, Duration)>,
+
+    /// See [`SwarmBuilder::event_filter`].
+    event_filter: EventFilter,
+
+    /// See [`SwarmBuilder::max_pending_incoming_upgrades`].
+    max_pending_incoming_upgrades: Option<NonZeroU32>,
 }
 
 impl<TBehaviour> Unpin for Swarm<TBehaviour> where TBehaviour: NetworkBehaviour {}
@@ -385,12 +484,7 @@ where
         behaviour: TBehaviour,
         local_peer_id: PeerId,
     ) -> Self {
-        Self::with_executor(
-            transport,
-            behaviour,
-            local_peer_id,
-            crate::executor::TokioExecutor,
-        )
+        SwarmBuilder::with_tokio_executor(transport, behaviour, local_peer_id).build()
     }
 
     /// Builds a new `Swarm` with an async-std executor.
@@ -404,12 +498,7 @@ where
         behaviour: TBehaviour,
         local_peer_id: PeerId,
     ) -> Self {
-        Self::with_executor(
-            transport,
-            behaviour,
-            local_peer_id,
-            crate::executor::AsyncStdExecutor,
-        )
+        SwarmBuilder::with_async_std_executor(transport, behaviour, local_peer_id).build()
     }
 
     /// Builds a new `Swarm` with a threadpool executor.
@@ -427,7 +516,7 @@ where
         {
             Ok(tp) => SwarmBuilder::with_executor(transport, behaviour, local_peer_id, tp),
             Err(err) => {
-                log::warn!("Failed to create executor thread pool: {:?}", err);
+                tracing::warn!("Failed to create executor thread pool: {:?}", err);
                 SwarmBuilder::without_executor(transport, behaviour, local_peer_id)
             }
         };
@@ -450,12 +539,7 @@ where
         behaviour: TBehaviour,
         local_peer_id: PeerId,
     ) -> Self {
-        Self::with_executor(
-            transport,
-            behaviour,
-            local_peer_id,
-            crate::executor::WasmBindgenExecutor,
-        )
+        SwarmBuilder::with_wasm_executor(transport, behaviour, local_peer_id).build()
     }
 
     /// Builds a new `Swarm` without an executor, instead using the current task.
@@ -483,13 +567,74 @@ where
         }
     }
 
+    /// Returns a snapshot of the state of the [`Swarm`].
+    pub fn node_status(&self) -> NodeStatus {
+        let mut protocol_peers = HashMap::new();
+        for protocol in self.peer_protocols.values().flatten() {
+            *protocol_peers.entry(protocol.clone()).or_default() += 1;
+        }
+
+        NodeStatus {
+            num_listeners: self.listened_addrs.len(),
+            listen_addrs: self.listened_addrs.values().flatten().cloned().collect(),
+            external_addrs: self
+                .external_addrs
+                .iter()
+                .map(|record| record.addr.clone())
+                .collect(),
+            num_peers: self.pool.num_peers(),
+            connection_counters: self.pool.counters().clone(),
+            protocol_peers,
+            nat_status: self.nat_status.clone(),
+        }
+    }
+
+    /// Sets the kinds of events returned by the `Swarm`, see [`SwarmBuilder::event_filter`].
+    pub fn set_event_filter(&mut self, filter: EventFilter) {
+        self.event_filter = filter;
+    }
+
+    /// The kinds of events returned by the `Swarm`, see [`SwarmBuilder::event_filter`].
+    pub fn event_filter(&self) -> EventFilter {
+        self.event_filter
+    }
+
+    /// Sets the reachability of the local node behind a NAT, reported via [`Swarm::node_status`].
+    ///
+    /// Overwritten by the next status reported by a [`NetworkBehaviour`] via
+    /// [`ToSwarm::ReportNatStatus`], e.g. by AutoNAT. A change of the status is passed on to the
+    /// behaviour via [`FromSwarm::NatStatusChanged`].
+    pub fn set_nat_status(&mut self, status: NatStatus) {
+        if status == self.nat_status {
+            return;
+        }
+        let old = std::mem::replace(&mut self.nat_status, status);
+        self.behaviour
+            .on_swarm_event(FromSwarm::NatStatusChanged(NatStatusChanged {
+                old: &old,
+                new: &self.nat_status,
+            }));
+    }
+
     /// Starts listening on the given address.
     /// Returns an error if the address is not supported.
     ///
     /// Listeners report their new listening addresses as [`SwarmEvent::NewListenAddr`].
     /// Depending on the underlying transport, one listener may have multiple listening addresses.
     pub fn listen_on(&mut self, addr: Multiaddr) -> Result<ListenerId, TransportError<io::Error>> {
-        let id = self.transport.listen_on(addr)?;
+        self.listen_with_opts(ListenOpts::new(addr))
+    }
+
+    /// Starts listening with the given [`ListenOpts`].
+    /// Returns an error if the address is not supported.
+    ///
+    /// See [`Swarm::listen_on`].
+    pub fn listen_with_opts(
+        &mut self,
+        opts: ListenOpts,
+    ) -> Result<ListenerId, TransportError<io::Error>> {
+        let id = self.transport.listen_on(opts.address().clone())?;
+        self.listener_opts.insert(id, opts);
         self.behaviour
             .on_swarm_event(FromSwarm::NewListener(behaviour::NewListener {
                 listener_id: id,
@@ -499,6 +644,9 @@ where
 
     /// Remove some listener.
     ///
+    /// The listener is closed asynchronously. Once closed, all its addresses are reported as
+    /// expired and a [`SwarmEvent::ListenerClosed`] is emitted.
+    ///
     /// Returns `true` if there was a listener with this ID, `false`
     /// otherwise.
     pub fn remove_listener(&mut self, listener_id: ListenerId) -> bool {
@@ -529,13 +677,21 @@ where
     /// swarm.dial("/ip6/::1/tcp/12345".parse::<Multiaddr>().unwrap());
     /// ```
     pub fn dial(&mut self, opts: impl Into<DialOpts>) -> Result<(), DialError> {
-        let dial_opts = opts.into();
+        let mut dial_opts = opts.into();
+        let user_data = dial_opts.take_user_data();
 
         let peer_id = dial_opts
             .get_or_parse_peer_id()
             .map_err(DialError::InvalidPeerId)?;
         let condition = dial_opts.peer_condition();
         let connection_id = dial_opts.connection_id();
+        let role_override = match peer_id {
+            Some(peer_id) if dial_opts.simultaneous_open() => {
+                simultaneous_open_role(&self.local_peer_id, &peer_id)
+                    .unwrap_or_else(|| dial_opts.role_override())
+            }
+            _ => dial_opts.role_override(),
+        };
 
         let should_dial = match (condition, peer_id) {
             (PeerCondition::Always, _) => true,
@@ -553,6 +709,7 @@ where
                     peer_id,
                     error: &e,
                     connection_id,
+                    user_data: user_data.as_deref(),
                 }));
 
             return Err(e);
@@ -568,6 +725,7 @@ where
                         peer_id: Some(peer_id),
                         error: &error,
                         connection_id,
+                        user_data: user_data.as_deref(),
                     }));
 
                 return Err(error);
@@ -581,7 +739,7 @@ where
                 connection_id,
                 peer_id,
                 addresses_from_opts.as_slice(),
-                dial_opts.role_override(),
+                role_override,
             ) {
                 Ok(addresses) => {
                     if dial_opts.extend_addresses_through_behaviour() {
@@ -590,7 +748,7 @@ where
                         let num_addresses = addresses.len();
 
                         if num_addresses > 0 {
-                            log::debug!("discarding {num_addresses} addresses from `NetworkBehaviour` because `DialOpts::extend_addresses_through_behaviour is `false` for connection {connection_id:?}")
+                            tracing::debug!("discarding {num_addresses} addresses from `NetworkBehaviour` because `DialOpts::extend_addresses_through_behaviour is `false` for connection {connection_id:?}")
                         }
                     }
                 }
@@ -602,6 +760,7 @@ where
                             peer_id,
                             error: &error,
                             connection_id,
+                            user_data: user_data.as_deref(),
                         }));
 
                     return Err(error);
@@ -609,10 +768,11 @@ where
             }
 
             let mut unique_addresses = HashSet::new();
-            addresses_from_opts.retain(|addr| {
-                !self.listened_addrs.values().flatten().any(|a| a == addr)
-                    && unique_addresses.insert(addr.clone())
-            });
+            let addresses_from_opts = addresses_from_opts
+                .into_iter()
+                .filter_map(|addr| self.normalize_dial_address(peer_id, addr))
+                .filter(|addr| unique_addresses.insert(addr.clone()))
+                .collect::<Vec<_>>();
 
             if addresses_from_opts.is_empty() {
                 let error = DialError::NoAddresses;
@@ -621,6 +781,7 @@ where
                         peer_id,
                         error: &error,
                         connection_id,
+                        user_data: user_data.as_deref(),
                     }));
                 return Err(error);
             };
@@ -628,14 +789,22 @@ where
             addresses_from_opts
         };
 
+        // Only reported once the dial is accepted by the pool.
+        let dial_addresses = (self.report_dial_addresses
+            && self.event_filter.contains(EventFilter::DIAL))
+        .then(|| addresses.clone());
+
         let dials = addresses
             .into_iter()
             .map(|a| match p2p_addr(peer_id, a) {
                 Ok(address) => {
-                    let dial = match dial_opts.role_override() {
-                        Endpoint::Dialer => self.transport.dial(address.clone()),
-                        Endpoint::Listener => self.transport.dial_as_listener(address.clone()),
-                    };
+                    let dial = self.transport.dial_with_opts(
+                        address.clone(),
+                        transport::DialOpts {
+                            role: role_override,
+                            port_use: dial_opts.port_use(),
+                        },
+                    );
                     match dial {
                         Ok(fut) => fut
                             .map(|r| (address, r.map_err(TransportError::Other)))
@@ -654,11 +823,20 @@ where
         match self.pool.add_outgoing(
             dials,
             peer_id,
-            dial_opts.role_override(),
+            role_override,
             dial_opts.dial_concurrency_override(),
             connection_id,
         ) {
-            Ok(()) => Ok(()),
+            Ok(()) => {
+                if let Some(user_data) = user_data {
+                    self.dial_user_data.insert(connection_id, user_data);
+                }
+                if let Some(addresses) = dial_addresses {
+                    self.pending_dial_addresses
+                        .push_back((peer_id, connection_id, addresses));
+                }
+                Ok(())
+            }
             Err(connection_limit) => {
                 #[allow(deprecated)]
                 let error = DialError::ConnectionLimit(connection_limit);
@@ -667,6 +845,7 @@ where
                         peer_id,
                         error: &error,
                         connection_id,
+                        user_data: user_data.as_deref(),
                     }));
 
                 Err(error)
@@ -674,11 +853,57 @@ where
         }
     }
 
+    /// Normalizes an address to dial the given peer at.
+    ///
+    /// Strips the `/p2p/` suffix of the dialed peer, which is appended again when dialing, so
+    /// that addresses with and without it are deduplicated. Returns `None` for addresses of the
+    /// local node, i.e. with a `/p2p/` suffix of the local peer or equal to a listen or external
+    /// address.
+    fn normalize_dial_address(
+        &self,
+        peer_id: Option<PeerId>,
+        mut addr: Multiaddr,
+    ) -> Option<Multiaddr> {
+        if let Some(Protocol::P2p(hash)) = addr.iter().last() {
+            if &hash == self.local_peer_id.as_ref() {
+                return None;
+            }
+            if peer_id.map_or(false, |peer_id| &hash == peer_id.as_ref()) {
+                addr.pop();
+            }
+        }
+
+        if self.listened_addrs.values().flatten().any(|a| *a == addr)
+            || self.external_addrs.iter().any(|record| record.addr == addr)
+        {
+            return None;
+        }
+
+        Some(addr)
+    }
+
     /// Returns an iterator that produces the list of addresses we're listening on.
     pub fn listeners(&self) -> impl Iterator<Item = &Multiaddr> {
         self.listened_addrs.values().flatten()
     }
 
+    /// Returns an iterator over the addresses the given listener is listening on.
+    pub fn listener_addresses(&self, listener_id: ListenerId) -> impl Iterator<Item = &Multiaddr> {
+        self.listened_addrs.get(&listener_id).into_iter().flatten()
+    }
+
+    /// Returns the [`ListenOpts`] the given listener was started with.
+    pub fn listener_opts(&self, listener_id: ListenerId) -> Option<&ListenOpts> {
+        self.listener_opts.get(&listener_id)
+    }
+
+    /// Whether the addresses of the given listener are announced, see [`ListenOpts::announce`].
+    fn is_announced(&self, listener_id: ListenerId) -> bool {
+        self.listener_opts
+            .get(&listener_id)
+            .map_or(true, ListenOpts::is_announced)
+    }
+
     /// Returns the peer ID of the swarm passed as parameter.
     pub fn local_peer_id(&self) -> &PeerId {
         &self.local_peer_id
@@ -783,6 +1008,28 @@ where
         }
     }
 
+    /// Gracefully closes the established connection with the given ID to the given peer,
+    /// leaving other connections to the peer open.
+    ///
+    /// The `reason` is reported in the resulting [`SwarmEvent::ConnectionClosed`].
+    /// It is not sent to the remote.
+    ///
+    /// Returns `false` if there is no such connection.
+    pub fn close_connection(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        reason: impl Into<String>,
+    ) -> bool {
+        match self.pool.get_established_of_peer(&peer_id, connection_id) {
+            Some(conn) => {
+                conn.start_close_with_reason(reason.into());
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Checks whether there is an established connection to a peer.
     pub fn is_connected(&self, peer_id: &PeerId) -> bool {
         self.pool.is_connected(*peer_id)
@@ -793,6 +1040,38 @@ where
         self.pool.iter_connected()
     }
 
+    /// Returns the dials in progress.
+    pub fn pending_dials(&self) -> impl Iterator<Item = PendingDial> + '_ {
+        self.pool.iter_pending_dials()
+    }
+
+    /// Aborts the dial with the given [`ConnectionId`], see [`DialOpts::connection_id`].
+    ///
+    /// The dial fails with [`DialError::Aborted`], reported to the behaviour and via
+    /// [`SwarmEvent::OutgoingConnectionError`]. Returns `false` if there is no such dial in progress.
+    pub fn abort_dial(&mut self, connection_id: ConnectionId) -> bool {
+        self.pool.abort_dial(connection_id)
+    }
+
+    /// Returns a [`CommandSender`] to operate on the `Swarm` from another task or thread.
+    ///
+    /// Commands are processed while the `Swarm` is polled, in the order in which they were sent.
+    pub fn command_sender(&self) -> CommandSender<TBehaviour> {
+        CommandSender::new(self.command_sender.clone())
+    }
+
+    fn handle_command(&mut self, command: Command<TBehaviour>) {
+        match command {
+            Command::Dial(opts, reply) => {
+                let _ = reply.send(self.dial(opts));
+            }
+            Command::DisconnectPeerId(peer_id, reply) => {
+                let _ = reply.send(self.disconnect_peer_id(peer_id));
+            }
+            Command::Behaviour(command) => command.apply(&mut self.behaviour),
+        }
+    }
+
     /// Returns a reference to the provided [`NetworkBehaviour`].
     pub fn behaviour(&self) -> &TBehaviour {
         &self.behaviour
@@ -816,6 +1095,8 @@ where
                 concurrent_dial_errors,
                 established_in,
             } => {
+                let user_data = self.dial_user_data.remove(&id);
+
                 if self.banned_peers.contains(&peer_id) {
                     #[allow(deprecated)]
                     return Some(SwarmEvent::BannedPeer { peer_id, endpoint });
@@ -840,12 +1121,14 @@ where
                                         connection_id: id,
                                         error: &dial_error,
                                         peer_id: Some(peer_id),
+                                        user_data: user_data.as_deref(),
                                     },
                                 ));
 
                                 return Some(SwarmEvent::OutgoingConnectionError {
                                     peer_id: Some(peer_id),
                                     error: dial_error,
+                                    user_data,
                                 });
                             }
                         }
@@ -900,7 +1183,7 @@ where
                 self.pool
                     .spawn_connection(id, peer_id, &endpoint, connection, handler);
 
-                log::debug!(
+                tracing::debug!(
                     "Connection established: {:?} {:?}; Total (peer): {}.",
                     peer_id,
                     endpoint,
@@ -923,6 +1206,7 @@ where
                             endpoint: &endpoint,
                             failed_addresses: &failed_addresses,
                             other_established: other_established_connection_ids.len(),
+                            user_data: user_data.as_deref(),
                         },
                     ));
                 self.supported_protocols = supported_protocols;
@@ -932,6 +1216,7 @@ where
                     endpoint,
                     concurrent_dial_errors,
                     established_in,
+                    user_data,
                 });
             }
             PoolEvent::PendingOutboundConnectionError {
@@ -940,23 +1225,26 @@ where
                 peer,
             } => {
                 let error = error.into();
+                let user_data = self.dial_user_data.remove(&connection_id);
 
                 self.behaviour
                     .on_swarm_event(FromSwarm::DialFailure(DialFailure {
                         peer_id: peer,
                         error: &error,
                         connection_id,
+                        user_data: user_data.as_deref(),
                     }));
 
                 if let Some(peer) = peer {
-                    log::debug!("Connection attempt to {:?} failed with {:?}.", peer, error,);
+                    tracing::debug!("Connection attempt to {:?} failed with {:?}.", peer, error,);
                 } else {
-                    log::debug!("Connection attempt to unknown peer failed with {:?}", error);
+                    tracing::debug!("Connection attempt to unknown peer failed with {:?}", error);
                 }
 
                 return Some(SwarmEvent::OutgoingConnectionError {
                     peer_id: peer,
                     error,
+                    user_data,
                 });
             }
             PoolEvent::PendingInboundConnectionError {
@@ -967,7 +1255,7 @@ where
             } => {
                 let error = error.into();
 
-                log::debug!("Incoming connection failed: {:?}", error);
+                tracing::debug!("Incoming connection failed: {:?}", error);
                 self.behaviour
                     .on_swarm_event(FromSwarm::ListenFailure(ListenFailure {
                         local_addr: &local_addr,
@@ -987,17 +1275,17 @@ where
                 error,
                 remaining_established_connection_ids,
                 handler,
-                ..
+                reason,
             } => {
                 if let Some(error) = error.as_ref() {
-                    log::debug!(
+                    tracing::debug!(
                         "Connection closed with error {:?}: {:?}; Total (peer): {}.",
                         error,
                         connected,
                         remaining_established_connection_ids.len()
                     );
                 } else {
-                    log::debug!(
+                    tracing::debug!(
                         "Connection closed: {:?}; Total (peer): {}.",
                         connected,
                         remaining_established_connection_ids.len()
@@ -1007,6 +1295,9 @@ where
                 let endpoint = connected.endpoint;
                 let num_established =
                     u32::try_from(remaining_established_connection_ids.len()).unwrap();
+                if num_established == 0 {
+                    self.peer_protocols.remove(&peer_id);
+                }
 
                 self.behaviour
                     .on_swarm_event(FromSwarm::ConnectionClosed(ConnectionClosed {
@@ -1015,12 +1306,14 @@ where
                         endpoint: &endpoint,
                         handler,
                         remaining_established: num_established as usize,
+                        reason: reason.as_deref(),
                     }));
                 return Some(SwarmEvent::ConnectionClosed {
                     peer_id,
                     endpoint,
                     cause: error,
                     num_established,
+                    reason,
                 });
             }
             PoolEvent::ConnectionEvent { peer_id, id, event } => {
@@ -1111,7 +1404,7 @@ where
                                 error: &error,
                                 connection_id,
                             }));
-                        log::debug!("Incoming connection rejected: {:?}", connection_limit);
+                        tracing::debug!("Incoming connection rejected: {:?}", connection_limit);
                     }
                 };
             }
@@ -1119,16 +1412,19 @@ where
                 listener_id,
                 listen_addr,
             } => {
-                log::debug!("Listener {:?}; New address: {:?}", listener_id, listen_addr);
+                tracing::debug!("Listener {:?}; New address: {:?}", listener_id, listen_addr);
                 let addrs = self.listened_addrs.entry(listener_id).or_default();
                 if !addrs.contains(&listen_addr) {
                     addrs.push(listen_addr.clone())
                 }
-                self.behaviour
-                    .on_swarm_event(FromSwarm::NewListenAddr(NewListenAddr {
-                        listener_id,
-                        addr: &listen_addr,
-                    }));
+                if self.is_announced(listener_id) {
+                    self.behaviour
+                        .on_swarm_event(FromSwarm::NewListenAddr(NewListenAddr {
+                            listener_id,
+                            addr: &listen_addr,
+                            transport: TransportKind::from_address(&listen_addr),
+                        }));
+                }
                 return Some(SwarmEvent::NewListenAddr {
                     listener_id,
                     address: listen_addr,
@@ -1138,7 +1434,7 @@ where
                 listener_id,
                 listen_addr,
             } => {
-                log::debug!(
+                tracing::debug!(
                     "Listener {:?}; Expired address {:?}.",
                     listener_id,
                     listen_addr
@@ -1146,11 +1442,15 @@ where
                 if let Some(addrs) = self.listened_addrs.get_mut(&listener_id) {
                     addrs.retain(|a| a != &listen_addr);
                 }
-                self.behaviour
-                    .on_swarm_event(FromSwarm::ExpiredListenAddr(ExpiredListenAddr {
-                        listener_id,
-                        addr: &listen_addr,
-                    }));
+                if self.is_announced(listener_id) {
+                    self.behaviour.on_swarm_event(FromSwarm::ExpiredListenAddr(
+                        ExpiredListenAddr {
+                            listener_id,
+                            addr: &listen_addr,
+                            transport: TransportKind::from_address(&listen_addr),
+                        },
+                    ));
+                }
                 return Some(SwarmEvent::ExpiredListenAddr {
                     listener_id,
                     address: listen_addr,
@@ -1160,12 +1460,22 @@ where
                 listener_id,
                 reason,
             } => {
-                log::debug!("Listener {:?}; Closed by {:?}.", listener_id, reason);
+                tracing::debug!("Listener {:?}; Closed by {:?}.", listener_id, reason);
                 let addrs = self.listened_addrs.remove(&listener_id).unwrap_or_default();
-                for addr in addrs.iter() {
-                    self.behaviour.on_swarm_event(FromSwarm::ExpiredListenAddr(
-                        ExpiredListenAddr { listener_id, addr },
-                    ));
+                let announced = self
+                    .listener_opts
+                    .remove(&listener_id)
+                    .map_or(true, |opts| opts.is_announced());
+                if announced {
+                    for addr in addrs.iter() {
+                        self.behaviour.on_swarm_event(FromSwarm::ExpiredListenAddr(
+                            ExpiredListenAddr {
+                                listener_id,
+                                addr,
+                                transport: TransportKind::from_address(addr),
+                            },
+                        ));
+                    }
                 }
                 self.behaviour
                     .on_swarm_event(FromSwarm::ListenerClosed(ListenerClosed {
@@ -1239,8 +1549,9 @@ where
                 let translated_addresses = {
                     let mut addrs: Vec<_> = self
                         .listened_addrs
-                        .values()
-                        .flatten()
+                        .iter()
+                        .filter(|(id, _)| self.is_announced(**id))
+                        .flat_map(|(_, addrs)| addrs)
                         .filter_map(|server| self.transport.address_translation(server, &address))
                         .collect();
 
@@ -1253,6 +1564,36 @@ where
                     self.add_external_address(addr, score);
                 }
             }
+            ToSwarm::ReportPeerProtocols {
+                peer_id,
+                added,
+                removed,
+            } => {
+                if self.pool.is_connected(peer_id) {
+                    let protocols = self.peer_protocols.entry(peer_id).or_default();
+                    protocols.extend(added.iter().cloned());
+                    for protocol in &removed {
+                        protocols.remove(protocol);
+                    }
+                }
+                self.behaviour
+                    .on_swarm_event(FromSwarm::PeerProtocolsChanged(PeerProtocolsChanged {
+                        peer_id,
+                        added: &added,
+                        removed: &removed,
+                    }));
+            }
+            ToSwarm::ReportPeerListenAddrs {
+                peer_id,
+                listen_addrs,
+            } => {
+                self.behaviour
+                    .on_swarm_event(FromSwarm::PeerListenAddrsChanged(PeerListenAddrsChanged {
+                        peer_id,
+                        listen_addrs: &listen_addrs,
+                    }));
+            }
+            ToSwarm::ReportNatStatus(status) => self.set_nat_status(status),
             ToSwarm::CloseConnection {
                 peer_id,
                 connection,
@@ -1266,6 +1607,9 @@ where
                     self.pool.disconnect(peer_id);
                 }
             },
+            ToSwarm::AbortDial { connection_id } => {
+                self.pool.abort_dial(connection_id);
+            }
         }
 
         None
@@ -1273,10 +1617,23 @@ where
 
     /// Internal function used by everything event-related.
     ///
-    /// Polls the `Swarm` for the next event.
+    /// Polls the `Swarm` for the next event passing the [`EventFilter`].
     fn poll_next_event(
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
+    ) -> Poll<SwarmEvent<TBehaviour::OutEvent, THandlerErr<TBehaviour>>> {
+        loop {
+            let event = futures::ready!(self.as_mut().poll_next_unfiltered_event(cx));
+            if self.event_filter.matches(&event) {
+                return Poll::Ready(event);
+            }
+        }
+    }
+
+    /// Polls the `Swarm` for the next event, regardless of the [`EventFilter`].
+    fn poll_next_unfiltered_event(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
     ) -> Poll<SwarmEvent<TBehaviour::OutEvent, THandlerErr<TBehaviour>>> {
         // We use a `this` variable because the compiler can't mutably borrow multiple times
         // across a `Deref`.
@@ -1292,6 +1649,26 @@ where
         //
         // (2) is polled before (3) to prioritize existing connections over upgrading new incoming connections.
         loop {
+            if let Some((peer_id, connection_id, addresses)) =
+                this.pending_dial_addresses.pop_front()
+            {
+                return Poll::Ready(SwarmEvent::DialAddresses {
+                    peer_id,
+                    connection_id,
+                    addresses,
+                });
+            }
+
+            if let Some((timer, interval)) = this.node_status_timer.as_mut() {
+                if timer.poll_unpin(cx).is_ready() {
+                    *timer = this.clock.timer(*interval);
+                    // Skip taking the snapshot if nobody is interested in it.
+                    if this.event_filter.contains(EventFilter::NODE_STATUS) {
+                        return Poll::Ready(SwarmEvent::NodeStatus(this.node_status()));
+                    }
+                }
+            }
+
             match this.pending_event.take() {
                 // Try to deliver the pending event emitted by the [`NetworkBehaviour`] in the previous
                 // iteration to the connection handler(s).
@@ -1317,15 +1694,32 @@ where
                         }
                     }
                 },
-                // No pending event. Allow the [`NetworkBehaviour`] to make progress.
+                // No pending event. Apply commands sent from other tasks, then allow the
+                // [`NetworkBehaviour`] to make progress.
                 None => {
+                    if let Poll::Ready(Some(command)) = this.commands.poll_next_unpin(cx) {
+                        this.handle_command(command);
+                        continue;
+                    }
+
                     let behaviour_poll = {
                         let mut parameters = SwarmPollParameters {
                             local_peer_id: &this.local_peer_id,
                             supported_protocols: &this.supported_protocols,
-                            listened_addrs: this.listened_addrs.values().flatten().collect(),
+                            listened_addrs: this
+                                .listened_addrs
+                                .iter()
+                                .filter(|(id, _)| {
+                                    this.listener_opts
+                                        .get(id)
+                                        .map_or(true, ListenOpts::is_announced)
+                                })
+                                .flat_map(|(_, addrs)| addrs)
+                                .collect(),
                             external_addrs: &this.external_addrs,
+                            poll_count: this.behaviour_polls,
                         };
+                        this.behaviour_polls = this.behaviour_polls.wrapping_add(1);
                         this.behaviour.poll(cx, &mut parameters)
                     };
 
@@ -1355,6 +1749,14 @@ where
                 }
             };
 
+            // Pause the listener(s) while too many incoming connections are upgrading. The pool
+            // wakes us up once one of them completes.
+            if this.max_pending_incoming_upgrades.map_or(false, |max| {
+                this.pool.counters().num_pending_incoming() >= max.get()
+            }) {
+                return Poll::Pending;
+            }
+
             // Poll the listener(s) for new connections.
             match Pin::new(&mut this.transport).poll(cx) {
                 Poll::Pending => {}
@@ -1493,6 +1895,7 @@ pub struct SwarmPollParameters<'a> {
     supported_protocols: &'a [Vec<u8>],
     listened_addrs: Vec<&'a Multiaddr>,
     external_addrs: &'a Addresses,
+    poll_count: u64,
 }
 
 impl<'a> PollParameters for SwarmPollParameters<'a> {
@@ -1515,6 +1918,10 @@ impl<'a> PollParameters for SwarmPollParameters<'a> {
     fn local_peer_id(&self) -> &PeerId {
         self.local_peer_id
     }
+
+    fn poll_count(&self) -> u64 {
+        self.poll_count
+    }
 }
 
 /// A [`SwarmBuilder`] provides an API for configuring and constructing a [`Swarm`].
@@ -1525,6 +1932,11 @@ pub struct SwarmBuilder<TBehaviour> {
     pool_config: PoolConfig,
     #[allow(deprecated)]
     connection_limits: ConnectionLimits,
+    report_dial_addresses: bool,
+    node_status_interval: Option<Duration>,
+    clock: Arc<dyn Clock>,
+    event_filter: EventFilter,
+    max_pending_incoming_upgrades: Option<NonZeroU32>,
 }
 
 impl<TBehaviour> SwarmBuilder<TBehaviour>
@@ -1540,13 +1952,12 @@ where
         local_peer_id: PeerId,
         executor: impl Executor + Send + 'static,
     ) -> Self {
-        Self {
-            local_peer_id,
+        Self::new(
             transport,
             behaviour,
-            pool_config: PoolConfig::new(Some(Box::new(executor))),
-            connection_limits: Default::default(),
-        }
+            local_peer_id,
+            Some(Box::new(executor)),
+        )
     }
 
     /// Sets executor to the `wasm` executor.
@@ -1568,7 +1979,7 @@ where
             transport,
             behaviour,
             local_peer_id,
-            crate::executor::WasmBindgenExecutor,
+            libp2p_core::executor::WasmBindgenExecutor,
         )
     }
 
@@ -1587,7 +1998,7 @@ where
             transport,
             behaviour,
             local_peer_id,
-            crate::executor::TokioExecutor,
+            libp2p_core::executor::TokioExecutor,
         )
     }
 
@@ -1606,7 +2017,7 @@ where
             transport,
             behaviour,
             local_peer_id,
-            crate::executor::AsyncStdExecutor,
+            libp2p_core::executor::AsyncStdExecutor,
         )
     }
 
@@ -1621,13 +2032,30 @@ where
         transport: transport::Boxed<(PeerId, StreamMuxerBox)>,
         behaviour: TBehaviour,
         local_peer_id: PeerId,
+    ) -> Self {
+        Self::new(transport, behaviour, local_peer_id, None)
+    }
+
+    /// Creates a new [`SwarmBuilder`] spawning the connection tasks on `executor`, or polling them
+    /// on the current task if `None`. All constructors go through here, the built-in executors
+    /// being provided by [`libp2p_core::executor`].
+    fn new(
+        transport: transport::Boxed<(PeerId, StreamMuxerBox)>,
+        behaviour: TBehaviour,
+        local_peer_id: PeerId,
+        executor: Option<Box<dyn Executor + Send>>,
     ) -> Self {
         Self {
             local_peer_id,
             transport,
             behaviour,
-            pool_config: PoolConfig::new(None),
+            pool_config: PoolConfig::new(executor),
             connection_limits: Default::default(),
+            report_dial_addresses: false,
+            node_status_interval: None,
+            clock: Arc::new(SystemClock),
+            event_filter: EventFilter::ALL,
+            max_pending_incoming_upgrades: None,
         }
     }
 
@@ -1703,18 +2131,116 @@ where
         self
     }
 
+    /// Close a connection when the remote opens a new inbound stream while the
+    /// [`SwarmBuilder::max_negotiating_inbound_streams`] are negotiating,
+    /// instead of holding back the new stream until a negotiation finishes.
+    ///
+    /// Disabled by default. Enabling this cuts off peers flooding a connection
+    /// with streams, at the cost of closing connections of peers legitimately
+    /// opening many streams at once.
+    pub fn close_on_excess_inbound_streams(mut self, v: bool) -> Self {
+        self.pool_config = self.pool_config.with_close_on_excess_inbound_streams(v);
+        self
+    }
+
+    /// Report the addresses of every dialing attempt via [`SwarmEvent::DialAddresses`].
+    ///
+    /// Disabled by default. The addresses are reported after normalization and
+    /// deduplication, i.e. as they are dialed.
+    pub fn report_dial_addresses(mut self, v: bool) -> Self {
+        self.report_dial_addresses = v;
+        self
+    }
+
+    /// Report a snapshot of the state of the [`Swarm`] via [`SwarmEvent::NodeStatus`] every
+    /// `interval`, e.g. to serve a liveness endpoint.
+    ///
+    /// Disabled by default.
+    pub fn node_status_interval(mut self, interval: Duration) -> Self {
+        self.node_status_interval = Some(interval);
+        self
+    }
+
+    /// The [`Clock`] driving the timers of the `Swarm`, e.g. for reporting
+    /// [`SwarmEvent::NodeStatus`], and of its connections, i.e. the keep-alive
+    /// and substream negotiation timeouts.
+    ///
+    /// Defaults to the [`SystemClock`]. A [`ManualClock`] allows tests to
+    /// advance time instantly.
+    pub fn clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// The kinds of [`SwarmEvent`]s returned by the `Swarm`.
+    ///
+    /// Defaults to [`EventFilter::ALL`]. Events of other kinds are dropped, reducing the
+    /// wakeups of applications only interested in e.g. [`SwarmEvent::Behaviour`]. Can be
+    /// changed later via [`Swarm::set_event_filter`].
+    pub fn event_filter(mut self, filter: EventFilter) -> Self {
+        self.event_filter = filter;
+        self
+    }
+
+    /// The maximum time for negotiating an inbound stream, i.e. for the
+    /// inbound upgrade of the [`ConnectionHandler`] to complete.
+    ///
+    /// Applies to all inbound streams, in addition to the timeout of the
+    /// [`SubstreamProtocol`] of the handler, whichever is shorter. By default,
+    /// only the latter applies.
+    pub fn inbound_negotiation_timeout(mut self, v: Duration) -> Self {
+        self.pool_config = self.pool_config.with_inbound_negotiation_timeout(v);
+        self
+    }
+
+    /// The maximum number of incoming connections upgrading concurrently, i.e. accepted but
+    /// not yet established.
+    ///
+    /// Once reached, the `Swarm` stops polling the [`Transport`] for new incoming connections
+    /// until an upgrade completes, leaving them in the accept queue of the listener sockets
+    /// instead of accepting and buffering them. This protects the CPU during connection floods.
+    /// Other events of the listeners, e.g. new listen addresses, are delayed as well.
+    ///
+    /// Unlike the pending incoming limit of the [`ConnectionLimits`], connections exceeding the
+    /// bound are not denied. Unlimited by default.
+    pub fn max_pending_incoming_upgrades(mut self, max: NonZeroU32) -> Self {
+        self.max_pending_incoming_upgrades = Some(max);
+        self
+    }
+
     /// Builds a `Swarm` with the current configuration.
     pub fn build(self) -> Swarm<TBehaviour> {
+        let (command_sender, commands) = mpsc::channel(COMMAND_BUFFER_SIZE);
+
         Swarm {
             local_peer_id: self.local_peer_id,
             transport: self.transport,
-            pool: Pool::new(self.local_peer_id, self.pool_config, self.connection_limits),
+            pool: Pool::new(
+                self.local_peer_id,
+                self.pool_config.with_clock(self.clock.clone()),
+                self.connection_limits,
+            ),
             behaviour: self.behaviour,
             supported_protocols: Default::default(),
             listened_addrs: HashMap::new(),
+            listener_opts: HashMap::new(),
             external_addrs: Addresses::default(),
             banned_peers: HashSet::new(),
             pending_event: None,
+            behaviour_polls: 0,
+            dial_user_data: HashMap::new(),
+            report_dial_addresses: self.report_dial_addresses,
+            pending_dial_addresses: VecDeque::new(),
+            command_sender,
+            commands,
+            peer_protocols: HashMap::new(),
+            nat_status: NatStatus::Unknown,
+            node_status_timer: self
+                .node_status_interval
+                .map(|interval| (self.clock.timer(interval), interval)),
+            clock: self.clock,
+            event_filter: self.event_filter,
+            max_pending_incoming_upgrades: self.max_pending_incoming_upgrades,
         }
     }
 }
@@ -1774,6 +2300,57 @@ impl From<PendingOutboundConnectionError> for DialError {
     }
 }
 
+impl DialError {
+    /// Returns the kinds of transports involved in the failed dial, one for each address whose
+    /// connection attempt failed.
+    pub fn transports(&self) -> Vec<TransportKind> {
+        match self {
+            DialError::Transport(errors) => errors
+                .iter()
+                .map(|(address, _)| TransportKind::from_address(address))
+                .collect(),
+            DialError::LocalPeerId { endpoint } | DialError::WrongPeerId { endpoint, .. } => {
+                vec![endpoint.transport()]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether retrying the dial later may succeed, e.g. because the addresses of the peer were
+    /// unreachable, no addresses are known yet, the attempt was aborted or denied by a
+    /// [`NetworkBehaviour`], like the deprecated [`DialError::ConnectionLimit`] that connection
+    /// limits implemented as [`NetworkBehaviour`]s replace.
+    ///
+    /// Transport errors are transient unless none of the addresses is supported by the transport.
+    /// Use [`DialError::is_permanent`] for the opposite.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            #[allow(deprecated)]
+            DialError::ConnectionLimit(_) => true,
+            DialError::NoAddresses
+            | DialError::DialPeerConditionFalse(_)
+            | DialError::Aborted
+            | DialError::Denied { .. } => true,
+            DialError::Transport(errors) => errors
+                .iter()
+                .any(|(_, error)| !matches!(error, TransportError::MultiaddrNotSupported(_))),
+            #[allow(deprecated)]
+            DialError::Banned => false,
+            DialError::LocalPeerId { .. }
+            | DialError::InvalidPeerId(_)
+            | DialError::WrongPeerId { .. } => false,
+        }
+    }
+
+    /// Whether retrying the dial is pointless, e.g. because the peer is banned, presents a
+    /// different peer ID than expected or none of its addresses is supported.
+    ///
+    /// This is the opposite of [`DialError::is_transient`].
+    pub fn is_permanent(&self) -> bool {
+        !self.is_transient()
+    }
+}
+
 impl fmt::Display for DialError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -2581,6 +3158,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn max_pending_incoming_upgrades() {
+        let mut swarm = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler)
+            .max_pending_incoming_upgrades(NonZeroU32::new(1).unwrap())
+            .build();
+        swarm.listen_on("/memory/0".parse().unwrap()).unwrap();
+
+        block_on(async {
+            let listen_addr = match swarm.select_next_some().await {
+                SwarmEvent::NewListenAddr { address, .. } => address,
+                e => panic!("Unexpected swarm event {e:?}"),
+            };
+
+            // Open connections that never complete the upgrade.
+            let dial = || {
+                transport::MemoryTransport::default()
+                    .dial(listen_addr.clone())
+                    .unwrap()
+            };
+            let first = dial().await.unwrap();
+            let _second = dial().await.unwrap();
+
+            match swarm.select_next_some().await {
+                SwarmEvent::IncomingConnection { .. } => {}
+                e => panic!("Unexpected swarm event {e:?}"),
+            }
+
+            // The second connection is not accepted while the first one is upgrading.
+            poll_fn(|cx| {
+                assert!(swarm.poll_next_unpin(cx).is_pending());
+                Poll::Ready(())
+            })
+            .await;
+
+            drop(first);
+            match swarm.select_next_some().await {
+                SwarmEvent::IncomingConnectionError { .. } => {}
+                e => panic!("Unexpected swarm event {e:?}"),
+            }
+            match swarm.select_next_some().await {
+                SwarmEvent::IncomingConnection { .. } => {}
+                e => panic!("Unexpected swarm event {e:?}"),
+            }
+        });
+    }
+
     #[test]
     fn max_established_incoming() {
         #[derive(Debug, Clone)]
@@ -2761,6 +3384,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn simultaneous_open_resolves_role_by_peer_id() {
+        let mut swarm = new_test_swarm::<_, ()>(dummy::ConnectionHandler).build();
+        let local_peer_id = *swarm.local_peer_id();
+        let address: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+
+        for _ in 0..10 {
+            let remote_peer_id = PeerId::random();
+            swarm
+                .dial(
+                    DialOpts::peer_id(remote_peer_id)
+                        .addresses(vec![address.clone()])
+                        .override_role()
+                        .simultaneous_open()
+                        .build(),
+                )
+                .unwrap();
+
+            let (_, _, role, _) = swarm
+                .behaviour()
+                .handle_pending_outbound_connection
+                .last()
+                .unwrap();
+            assert_eq!(
+                Some(*role),
+                simultaneous_open_role(&local_peer_id, &remote_peer_id)
+            );
+        }
+    }
+
     #[test]
     fn dial_self() {
         // Check whether dialing ourselves correctly fails.
@@ -2838,6 +3491,184 @@ mod tests {
         assert!(!swarm.is_connected(&peer_id));
     }
 
+    #[async_std::test]
+    async fn unannounced_listener_is_hidden_from_behaviour() {
+        let mut swarm = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler).build();
+
+        let announced_addr: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        let hidden_addr: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        let announced = swarm.listen_on(announced_addr.clone()).unwrap();
+        let hidden = swarm
+            .listen_with_opts(ListenOpts::new(hidden_addr.clone()).announce(false))
+            .unwrap();
+        assert!(!swarm.listener_opts(hidden).unwrap().is_announced());
+
+        let mut new_addrs = HashMap::new();
+        while new_addrs.len() < 2 {
+            if let SwarmEvent::NewListenAddr {
+                listener_id,
+                address,
+            } = swarm.select_next_some().await
+            {
+                new_addrs.insert(listener_id, address);
+            }
+        }
+        assert_eq!(new_addrs[&announced], announced_addr);
+        assert_eq!(new_addrs[&hidden], hidden_addr);
+        assert_eq!(
+            swarm.listener_addresses(hidden).collect::<Vec<_>>(),
+            vec![&hidden_addr]
+        );
+        assert_eq!(
+            swarm.behaviour().on_new_listen_addr,
+            vec![(announced, announced_addr, TransportKind::Memory)]
+        );
+
+        assert!(swarm.remove_listener(hidden));
+        loop {
+            if let SwarmEvent::ListenerClosed {
+                listener_id,
+                addresses,
+                ..
+            } = swarm.select_next_some().await
+            {
+                assert_eq!(listener_id, hidden);
+                assert_eq!(addresses, vec![hidden_addr]);
+                break;
+            }
+        }
+        assert!(swarm.listener_opts(hidden).is_none());
+        assert_eq!(swarm.listener_addresses(hidden).count(), 0);
+        assert!(swarm.behaviour().on_expired_listen_addr.is_empty());
+        assert_eq!(swarm.behaviour().on_listener_closed, vec![(hidden, true)]);
+    }
+
+    #[async_std::test]
+    async fn close_connection_with_reason() {
+        let mut swarm1 = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler).build();
+        let mut swarm2 = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler).build();
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+        let peer2 = *swarm2.local_peer_id();
+        async_std::task::spawn(async move {
+            loop {
+                swarm2.select_next_some().await;
+            }
+        });
+
+        for _ in 0..2 {
+            swarm1.dial(addr2.clone()).unwrap();
+        }
+        let mut established = 0;
+        while established < 2 {
+            if let SwarmEvent::ConnectionEstablished { .. } = swarm1.select_next_some().await {
+                established += 1;
+            }
+        }
+
+        let (_, connection_id, _, _) = swarm1.behaviour().on_connection_established[0];
+        assert!(!swarm1.close_connection(PeerId::random(), connection_id, "going away"));
+        assert!(swarm1.close_connection(peer2, connection_id, "going away"));
+
+        loop {
+            if let SwarmEvent::ConnectionClosed {
+                peer_id,
+                num_established,
+                cause,
+                reason,
+                ..
+            } = swarm1.select_next_some().await
+            {
+                assert_eq!(peer_id, peer2);
+                assert_eq!(num_established, 1);
+                assert!(cause.is_none());
+                assert_eq!(reason.as_deref(), Some("going away"));
+                break;
+            }
+        }
+        let (_, closed_id, _, _) = swarm1.behaviour().on_connection_closed[0];
+        assert_eq!(closed_id, connection_id);
+        assert_eq!(
+            swarm1.behaviour().on_connection_closed_reason,
+            vec![Some("going away".to_owned())]
+        );
+        assert!(swarm1.is_connected(&peer2));
+    }
+
+    #[async_std::test]
+    async fn command_sender_drives_swarm() {
+        type Behaviour = CallTraceBehaviour<MockBehaviour<keep_alive::ConnectionHandler, ()>>;
+
+        /// Reports the number of closed connections seen by the behaviour.
+        struct CountClosedConnections(futures::channel::oneshot::Sender<usize>);
+
+        impl BehaviourCommand<Behaviour> for CountClosedConnections {
+            fn apply(self: Box<Self>, behaviour: &mut Behaviour) {
+                let _ = self.0.send(behaviour.on_connection_closed.len());
+            }
+        }
+
+        let mut swarm1 = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler).build();
+        let mut swarm2 = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler).build();
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+        let peer2 = *swarm2.local_peer_id();
+        async_std::task::spawn(async move {
+            loop {
+                swarm2.select_next_some().await;
+            }
+        });
+
+        let mut commands = swarm1.command_sender();
+
+        let dial = async_std::task::spawn({
+            let mut commands = commands.clone();
+            async move { commands.dial(addr2).await }
+        });
+        loop {
+            if let SwarmEvent::ConnectionEstablished { peer_id, .. } =
+                swarm1.select_next_some().await
+            {
+                assert_eq!(peer_id, peer2);
+                break;
+            }
+        }
+        dial.await.unwrap();
+
+        let disconnect = async_std::task::spawn({
+            let mut commands = commands.clone();
+            async move { commands.disconnect_peer_id(peer2).await }
+        });
+        loop {
+            if let SwarmEvent::ConnectionClosed { peer_id, .. } = swarm1.select_next_some().await {
+                assert_eq!(peer_id, peer2);
+                break;
+            }
+        }
+        disconnect.await.unwrap();
+
+        let (tx, mut rx) = futures::channel::oneshot::channel();
+        commands
+            .behaviour(CountClosedConnections(tx))
+            .await
+            .unwrap();
+        let closed = loop {
+            match future::select(swarm1.select_next_some(), &mut rx).await {
+                future::Either::Left(_) => continue,
+                future::Either::Right((closed, _)) => break closed.unwrap(),
+            }
+        };
+        assert_eq!(closed, 1);
+
+        drop(swarm1);
+        assert!(matches!(
+            commands.disconnect_peer_id(peer2).await,
+            Err(CommandError::Closed)
+        ));
+    }
+
     #[async_std::test]
     async fn multiple_addresses_err() {
         // Tries dialing multiple addresses, and makes sure there's one dialing error per address.
@@ -2870,6 +3701,7 @@ mod tests {
                 peer_id,
                 // multiaddr,
                 error: DialError::Transport(errors),
+                ..
             } => {
                 assert_eq!(target, peer_id.unwrap());
 
@@ -2885,6 +3717,92 @@ mod tests {
         }
     }
 
+    #[async_std::test]
+    async fn events_report_transport() {
+        let mut swarm1 = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler).build();
+        let mut swarm2 = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler).build();
+
+        let addr2: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2.listen_on(addr2.clone()).unwrap();
+        async_std::task::spawn(async move {
+            loop {
+                swarm2.select_next_some().await;
+            }
+        });
+
+        swarm1.dial(addr2).unwrap();
+        loop {
+            let event = swarm1.select_next_some().await;
+            if let SwarmEvent::ConnectionEstablished { .. } = event {
+                assert_eq!(event.transport(), Some(TransportKind::Memory));
+                break;
+            }
+        }
+
+        swarm1
+            .dial(multiaddr![Ip4([0, 0, 0, 0]), Tcp(rand::random::<u16>())])
+            .unwrap();
+        loop {
+            if let SwarmEvent::OutgoingConnectionError { error, .. } =
+                swarm1.select_next_some().await
+            {
+                assert_eq!(error.transports(), vec![TransportKind::Tcp]);
+                break;
+            }
+        }
+    }
+
+    #[async_std::test]
+    async fn dial_user_data_is_returned_with_outcome() {
+        let mut swarm1 = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler).build();
+        let mut swarm2 = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler).build();
+
+        let addr: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm1.listen_on(addr.clone()).unwrap();
+        loop {
+            if let SwarmEvent::NewListenAddr { .. } = swarm1.select_next_some().await {
+                break;
+            }
+        }
+
+        let unreachable: Multiaddr = multiaddr::Protocol::Memory(rand::random::<u64>()).into();
+        swarm2
+            .dial(
+                DialOpts::unknown_peer_id()
+                    .address(unreachable)
+                    .build()
+                    .with_user_data("unreachable"),
+            )
+            .unwrap();
+        swarm2
+            .dial(
+                DialOpts::peer_id(*swarm1.local_peer_id())
+                    .addresses(vec![addr])
+                    .build()
+                    .with_user_data(42u32),
+            )
+            .unwrap();
+
+        let mut failed = None;
+        let mut established = None;
+        while failed.is_none() || established.is_none() {
+            match future::select(swarm1.select_next_some(), swarm2.select_next_some()).await {
+                future::Either::Right((
+                    SwarmEvent::OutgoingConnectionError { user_data, .. },
+                    _,
+                )) => failed = user_data,
+                future::Either::Right((SwarmEvent::ConnectionEstablished { user_data, .. }, _)) => {
+                    established = user_data
+                }
+                _ => {}
+            }
+        }
+
+        assert_eq!(failed.unwrap().downcast_ref::<&str>(), Some(&"unreachable"));
+        assert_eq!(established.unwrap().downcast_ref::<u32>(), Some(&42));
+        assert!(swarm2.dial_user_data.is_empty());
+    }
+
     #[test]
     fn aborting_pending_connection_surfaces_error() {
         let _ = env_logger::try_init();
@@ -2920,6 +3838,282 @@ mod tests {
         }
     }
 
+    #[test]
+    fn pending_dials_can_be_aborted() {
+        let mut dialer = new_test_swarm::<_, ()>(dummy::ConnectionHandler).build();
+        let mut listener = new_test_swarm::<_, ()>(dummy::ConnectionHandler).build();
+
+        let listener_peer_id = *listener.local_peer_id();
+        listener.listen_on(multiaddr![Memory(0u64)]).unwrap();
+        let listener_address = match block_on(listener.next()).unwrap() {
+            SwarmEvent::NewListenAddr { address, .. } => address,
+            e => panic!("Unexpected network event: {e:?}"),
+        };
+
+        let dial_opts = || {
+            DialOpts::peer_id(listener_peer_id)
+                .addresses(vec![listener_address.clone(), multiaddr![Memory(1u64)]])
+                // Only dial the listener, which is not polled and thus never completes the dial.
+                .override_dial_concurrency_factor(NonZeroU8::new(1).unwrap())
+                .build()
+        };
+        let opts = dial_opts();
+        let dial_id = opts.connection_id();
+        dialer.dial(opts).unwrap();
+
+        let pending = dialer.pending_dials().collect::<Vec<_>>();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].connection_id(), dial_id);
+        assert_eq!(pending[0].peer_id(), Some(listener_peer_id));
+        assert_eq!(pending[0].remaining_addresses(), 2);
+
+        assert!(dialer.abort_dial(dial_id));
+        assert!(!dialer.abort_dial(dial_id));
+        assert_eq!(dialer.pending_dials().count(), 0);
+
+        match block_on(dialer.next()).unwrap() {
+            SwarmEvent::OutgoingConnectionError {
+                error: DialError::Aborted,
+                ..
+            } => {}
+            e => panic!("Unexpected swarm event {e:?}."),
+        }
+
+        // Dials can be aborted by the behaviour as well.
+        let opts = dial_opts();
+        let dial_id = opts.connection_id();
+        dialer.dial(opts).unwrap();
+        dialer.behaviour_mut().inner().next_action = Some(ToSwarm::AbortDial {
+            connection_id: dial_id,
+        });
+
+        match block_on(dialer.next()).unwrap() {
+            SwarmEvent::OutgoingConnectionError {
+                error: DialError::Aborted,
+                ..
+            } => {}
+            e => panic!("Unexpected swarm event {e:?}."),
+        }
+        assert_eq!(dialer.pending_dials().count(), 0);
+        assert_eq!(dialer.behaviour().on_dial_failure.len(), 2);
+    }
+
+    #[async_std::test]
+    async fn dial_addresses_are_normalized_and_deduplicated() {
+        let target = PeerId::random();
+        let mut swarm = new_test_swarm::<_, ()>(dummy::ConnectionHandler)
+            .report_dial_addresses(true)
+            .build();
+        let local_peer_id = *swarm.local_peer_id();
+
+        let listen_addr: Multiaddr = multiaddr![Memory(rand::random::<u64>())];
+        swarm.listen_on(listen_addr.clone()).unwrap();
+        loop {
+            if let SwarmEvent::NewListenAddr { .. } = swarm.select_next_some().await {
+                break;
+            }
+        }
+
+        let addr1 = multiaddr![Ip4([0, 0, 0, 0]), Tcp(rand::random::<u16>())];
+        let addr2 = multiaddr![Udp(rand::random::<u16>())];
+        let opts = DialOpts::peer_id(target)
+            .addresses(vec![
+                addr1.clone(),
+                addr1.clone().with(Protocol::P2p(target.into())),
+                addr2.clone(),
+                addr2.clone(),
+                addr1.clone().with(Protocol::P2p(local_peer_id.into())),
+                listen_addr,
+            ])
+            .build();
+        let connection_id = opts.connection_id();
+        swarm.dial(opts).unwrap();
+
+        match swarm.next().await.unwrap() {
+            SwarmEvent::DialAddresses {
+                peer_id,
+                connection_id: id,
+                addresses,
+            } => {
+                assert_eq!(peer_id, Some(target));
+                assert_eq!(id, connection_id);
+                assert_eq!(addresses, vec![addr1, addr2]);
+            }
+            e => panic!("Unexpected event: {e:?}"),
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn dial_addresses_not_reported_for_rejected_dials() {
+        let limits = ConnectionLimits::default().with_max_pending_outgoing(Some(0));
+        let mut swarm = new_test_swarm::<_, ()>(dummy::ConnectionHandler)
+            .connection_limits(limits)
+            .report_dial_addresses(true)
+            .build();
+
+        let addr = multiaddr![Memory(rand::random::<u64>())];
+        let error = swarm
+            .dial(
+                DialOpts::peer_id(PeerId::random())
+                    .addresses(vec![addr])
+                    .build(),
+            )
+            .expect_err("Unexpected dialing success.");
+
+        assert!(matches!(error, DialError::ConnectionLimit(_)));
+        assert!(swarm.pending_dial_addresses.is_empty());
+    }
+
+    #[async_std::test]
+    async fn node_status_is_reported_periodically() {
+        let mut swarm1 = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler)
+            .node_status_interval(Duration::from_millis(10))
+            .build();
+        let mut swarm2 = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler).build();
+        let peer2 = *swarm2.local_peer_id();
+
+        let addr: Multiaddr = multiaddr![Memory(rand::random::<u64>())];
+        swarm1.listen_on(addr.clone()).unwrap();
+        swarm2.dial(addr.clone()).unwrap();
+        swarm1.set_nat_status(NatStatus::Private);
+
+        let status = loop {
+            match future::select(swarm1.select_next_some(), swarm2.select_next_some()).await {
+                future::Either::Left((SwarmEvent::ConnectionEstablished { .. }, _)) => {
+                    swarm1.behaviour_mut().inner().next_action =
+                        Some(ToSwarm::ReportPeerProtocols {
+                            peer_id: peer2,
+                            added: vec!["/foo/1.0.0".to_owned()],
+                            removed: vec![],
+                        });
+                }
+                future::Either::Left((SwarmEvent::NodeStatus(status), _))
+                    if !status.protocol_peers().is_empty() =>
+                {
+                    break status
+                }
+                _ => {}
+            }
+        };
+
+        assert_eq!(status.num_listeners(), 1);
+        assert_eq!(status.listen_addrs(), &[addr]);
+        assert_eq!(status.num_peers(), 1);
+        assert_eq!(status.protocol_peers().get("/foo/1.0.0"), Some(&1));
+        assert_eq!(status.nat_status(), &NatStatus::Private);
+    }
+
+    #[test]
+    fn peer_listen_addrs_reported_by_behaviour_are_passed_on() {
+        let mut swarm = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler).build();
+        let peer_id = PeerId::random();
+        let addr: Multiaddr = multiaddr![Memory(rand::random::<u64>())];
+
+        swarm.behaviour_mut().inner().next_action = Some(ToSwarm::ReportPeerListenAddrs {
+            peer_id,
+            listen_addrs: vec![addr.clone()],
+        });
+        let poll = futures::executor::block_on(async { futures::poll!(swarm.next()) });
+
+        assert!(poll.is_pending());
+        assert_eq!(
+            swarm.behaviour().on_peer_listen_addrs_changed,
+            [(peer_id, vec![addr])]
+        );
+    }
+
+    #[test]
+    fn nat_status_reported_by_behaviour_replaces_status_set_manually() {
+        let mut swarm = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler).build();
+        swarm.set_nat_status(NatStatus::Private);
+        assert_eq!(swarm.node_status().nat_status(), &NatStatus::Private);
+
+        let addr: Multiaddr = multiaddr![Memory(rand::random::<u64>())];
+        swarm.behaviour_mut().inner().next_action =
+            Some(ToSwarm::ReportNatStatus(NatStatus::Public(addr.clone())));
+        let poll = futures::executor::block_on(async { futures::poll!(swarm.next()) });
+
+        assert!(poll.is_pending());
+        assert_eq!(
+            swarm.node_status().nat_status(),
+            &NatStatus::Public(addr.clone())
+        );
+
+        // Only changes of the status are passed on to the behaviour.
+        swarm.set_nat_status(NatStatus::Public(addr.clone()));
+        assert_eq!(
+            swarm.behaviour().on_nat_status_changed,
+            [
+                (NatStatus::Unknown, NatStatus::Private),
+                (NatStatus::Private, NatStatus::Public(addr))
+            ]
+        );
+    }
+
+    #[async_std::test]
+    async fn events_are_filtered() {
+        let mut swarm1 = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler)
+            .node_status_interval(Duration::from_millis(1))
+            .event_filter(EventFilter::CONNECTION)
+            .build();
+        let mut swarm2 = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler).build();
+        let peer2 = *swarm2.local_peer_id();
+        assert!(swarm1.event_filter().contains(EventFilter::CONNECTION));
+        assert!(!swarm1.event_filter().contains(EventFilter::LISTENER));
+
+        let addr: Multiaddr = multiaddr![Memory(rand::random::<u64>())];
+        swarm1.listen_on(addr.clone()).unwrap();
+        swarm2.dial(addr).unwrap();
+
+        // Neither the new listen address, the incoming connection nor the node status is
+        // reported.
+        loop {
+            match future::select(swarm1.select_next_some(), swarm2.select_next_some()).await {
+                future::Either::Left((SwarmEvent::ConnectionEstablished { peer_id, .. }, _)) => {
+                    assert_eq!(peer_id, peer2);
+                    break;
+                }
+                future::Either::Left((e, _)) => panic!("Unexpected event: {e:?}"),
+                future::Either::Right(_) => {}
+            }
+        }
+
+        swarm1.set_event_filter(EventFilter::CONNECTION | EventFilter::NODE_STATUS);
+        loop {
+            match future::select(swarm1.select_next_some(), swarm2.select_next_some()).await {
+                future::Either::Left((SwarmEvent::NodeStatus(status), _)) => {
+                    assert_eq!(status.num_peers(), 1);
+                    break;
+                }
+                future::Either::Left((e, _)) => panic!("Unexpected event: {e:?}"),
+                future::Either::Right(_) => {}
+            }
+        }
+    }
+
+    #[test]
+    fn node_status_timer_follows_clock() {
+        let clock = ManualClock::new();
+        let mut swarm = new_test_swarm::<_, ()>(keep_alive::ConnectionHandler)
+            .node_status_interval(Duration::from_secs(60))
+            .clock(clock.clone())
+            .build();
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(swarm.poll_next_unpin(&mut cx).is_pending());
+
+        clock.advance(Duration::from_secs(60));
+        match swarm.poll_next_unpin(&mut cx) {
+            Poll::Ready(Some(SwarmEvent::NodeStatus(status))) => {
+                assert_eq!(status.num_peers(), 0)
+            }
+            e => panic!("Unexpected poll result: {e:?}"),
+        }
+        assert!(swarm.poll_next_unpin(&mut cx).is_pending());
+    }
+
     #[test]
     fn dial_error_prints_sources() {
         // This constitutes a fairly typical error for chained transports.
@@ -2938,4 +4132,42 @@ mod tests {
         // Unfortunately, we have some "empty" errors that lead to multiple colons without text but that is the best we can do.
         assert_eq!("Failed to negotiate transport protocol(s): [(/ip4/127.0.0.1/tcp/80: : Handshake failed: No listener on the given port.)]", string)
     }
+
+    #[test]
+    fn dial_error_classification() {
+        let address: Multiaddr = "/ip4/127.0.0.1/tcp/80".parse().unwrap();
+        let unreachable = || {
+            TransportError::Other(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                "connection refused",
+            ))
+        };
+        let unsupported = || TransportError::MultiaddrNotSupported(address.clone());
+
+        assert!(DialError::NoAddresses.is_transient());
+        assert!(DialError::Aborted.is_transient());
+        assert!(DialError::Denied {
+            cause: ConnectionDenied::new(io::Error::new(io::ErrorKind::Other, "limit"))
+        }
+        .is_transient());
+        assert!(DialError::Transport(vec![(address.clone(), unreachable())]).is_transient());
+        assert!(DialError::Transport(vec![
+            (address.clone(), unsupported()),
+            (address.clone(), unreachable())
+        ])
+        .is_transient());
+
+        assert!(DialError::Transport(vec![(address.clone(), unsupported())]).is_permanent());
+        assert!(DialError::WrongPeerId {
+            obtained: PeerId::random(),
+            endpoint: ConnectedPoint::Dialer {
+                address,
+                role_override: Endpoint::Dialer,
+            },
+        }
+        .is_permanent());
+        #[allow(deprecated)]
+        let banned = DialError::Banned;
+        assert!(banned.is_permanent());
+    }
 }
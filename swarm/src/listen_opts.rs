@@ -0,0 +1,77 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use libp2p_core::Multiaddr;
+
+/// Options to configure a listener.
+///
+/// Used in [`Swarm::listen_with_opts`](crate::Swarm::listen_with_opts).
+///
+///   ```
+///   # use libp2p_swarm::ListenOpts;
+///   ListenOpts::new("/ip4/127.0.0.1/tcp/0".parse().unwrap()).announce(false);
+///   ```
+#[derive(Debug, Clone)]
+pub struct ListenOpts {
+    address: Multiaddr,
+    announce: bool,
+}
+
+impl ListenOpts {
+    /// Listen on the given address.
+    ///
+    /// Transport-specific settings are expressed through the address, e.g. the port of a TCP
+    /// listener.
+    pub fn new(address: Multiaddr) -> Self {
+        Self {
+            address,
+            announce: true,
+        }
+    }
+
+    /// Whether the addresses of the listener are announced.
+    ///
+    /// The addresses of a listener that is not announced are still reported as
+    /// [`SwarmEvent::NewListenAddr`](crate::SwarmEvent::NewListenAddr), but are hidden from the
+    /// [`NetworkBehaviour`](crate::NetworkBehaviour), thus are e.g. not advertised to other
+    /// peers, and are not used to derive external addresses.
+    ///
+    /// Defaults to `true`.
+    pub fn announce(mut self, announce: bool) -> Self {
+        self.announce = announce;
+        self
+    }
+
+    /// Get the address to listen on.
+    pub fn address(&self) -> &Multiaddr {
+        &self.address
+    }
+
+    /// Whether the addresses of the listener are announced, see [`ListenOpts::announce`].
+    pub fn is_announced(&self) -> bool {
+        self.announce
+    }
+}
+
+impl From<Multiaddr> for ListenOpts {
+    fn from(address: Multiaddr) -> Self {
+        ListenOpts::new(address)
+    }
+}
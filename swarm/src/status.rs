@@ -0,0 +1,94 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Snapshots of the state of a [`Swarm`](crate::Swarm) for liveness endpoints and status
+//! dashboards, see [`NodeStatus`].
+
+use crate::ConnectionCounters;
+use libp2p_core::Multiaddr;
+use std::collections::HashMap;
+
+/// The reachability of the local node behind a NAT, as last reported by a
+/// [`NetworkBehaviour`](crate::NetworkBehaviour) via
+/// [`ToSwarm::ReportNatStatus`](crate::ToSwarm::ReportNatStatus), e.g. by AutoNAT, or set via
+/// [`Swarm::set_nat_status`](crate::Swarm::set_nat_status).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum NatStatus {
+    /// The reachability is not known.
+    #[default]
+    Unknown,
+    /// The node is publicly reachable at the given address.
+    Public(Multiaddr),
+    /// The node is not publicly reachable.
+    Private,
+}
+
+/// A snapshot of the state of a [`Swarm`](crate::Swarm) obtained via
+/// [`Swarm::node_status`](crate::Swarm::node_status) or periodically reported via
+/// [`SwarmEvent::NodeStatus`](crate::SwarmEvent::NodeStatus).
+#[derive(Clone, Debug)]
+pub struct NodeStatus {
+    pub(crate) num_listeners: usize,
+    pub(crate) listen_addrs: Vec<Multiaddr>,
+    pub(crate) external_addrs: Vec<Multiaddr>,
+    pub(crate) num_peers: usize,
+    pub(crate) connection_counters: ConnectionCounters,
+    pub(crate) protocol_peers: HashMap<String, usize>,
+    pub(crate) nat_status: NatStatus,
+}
+
+impl NodeStatus {
+    /// The number of active listeners.
+    pub fn num_listeners(&self) -> usize {
+        self.num_listeners
+    }
+
+    /// The addresses the listeners are listening on.
+    pub fn listen_addrs(&self) -> &[Multiaddr] {
+        &self.listen_addrs
+    }
+
+    /// The external addresses of the node, in order of descending score.
+    pub fn external_addrs(&self) -> &[Multiaddr] {
+        &self.external_addrs
+    }
+
+    /// The number of connected peers, i.e. peers with whom at least one established connection
+    /// exists.
+    pub fn num_peers(&self) -> usize {
+        self.num_peers
+    }
+
+    /// Gets counters for ongoing network connections.
+    pub fn connection_counters(&self) -> &ConnectionCounters {
+        &self.connection_counters
+    }
+
+    /// The number of connected peers supporting each protocol, as reported by the behaviours via
+    /// [`ToSwarm::ReportPeerProtocols`](crate::ToSwarm::ReportPeerProtocols).
+    pub fn protocol_peers(&self) -> &HashMap<String, usize> {
+        &self.protocol_peers
+    }
+
+    /// The reachability of the node behind a NAT.
+    pub fn nat_status(&self) -> &NatStatus {
+        &self.nat_status
+    }
+}
@@ -20,13 +20,18 @@
 
 use crate::behaviour::{
     ConnectionClosed, ConnectionEstablished, DialFailure, ExpiredExternalAddr, ExpiredListenAddr,
-    FromSwarm, ListenerClosed, ListenerError, NewExternalAddr, NewListenAddr, NewListener,
+    FromSwarm, ListenerClosed, ListenerError, NatStatusChanged, NewExternalAddr, NewListenAddr,
+    NewListener, PeerListenAddrsChanged,
 };
 use crate::{
-    ConnectionDenied, ConnectionHandler, ConnectionId, NetworkBehaviour, PollParameters, THandler,
-    THandlerInEvent, THandlerOutEvent, ToSwarm,
+    ConnectionDenied, ConnectionHandler, ConnectionId, NatStatus, NetworkBehaviour, PollParameters,
+    THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
+};
+use libp2p_core::{
+    multiaddr::Multiaddr,
+    transport::{ListenerId, TransportKind},
+    ConnectedPoint, Endpoint,
 };
-use libp2p_core::{multiaddr::Multiaddr, transport::ListenerId, ConnectedPoint, Endpoint};
 use libp2p_identity::PeerId;
 use std::collections::HashMap;
 use std::task::{Context, Poll};
@@ -131,7 +136,10 @@ where
             | FromSwarm::ListenerError(_)
             | FromSwarm::ListenerClosed(_)
             | FromSwarm::NewExternalAddr(_)
-            | FromSwarm::ExpiredExternalAddr(_) => {}
+            | FromSwarm::ExpiredExternalAddr(_)
+            | FromSwarm::PeerProtocolsChanged(_)
+            | FromSwarm::PeerListenAddrsChanged(_)
+            | FromSwarm::NatStatusChanged(_) => {}
         }
     }
 
@@ -160,15 +168,19 @@ where
     pub handle_established_outbound_connection: Vec<(PeerId, Multiaddr, Endpoint, ConnectionId)>,
     pub on_connection_established: Vec<(PeerId, ConnectionId, ConnectedPoint, usize)>,
     pub on_connection_closed: Vec<(PeerId, ConnectionId, ConnectedPoint, usize)>,
+    /// The reasons of the connections in `on_connection_closed`.
+    pub on_connection_closed_reason: Vec<Option<String>>,
     pub on_connection_handler_event: Vec<(PeerId, ConnectionId, THandlerOutEvent<TInner>)>,
     pub on_dial_failure: Vec<Option<PeerId>>,
     pub on_new_listener: Vec<ListenerId>,
-    pub on_new_listen_addr: Vec<(ListenerId, Multiaddr)>,
+    pub on_new_listen_addr: Vec<(ListenerId, Multiaddr, TransportKind)>,
     pub on_new_external_addr: Vec<Multiaddr>,
-    pub on_expired_listen_addr: Vec<(ListenerId, Multiaddr)>,
+    pub on_expired_listen_addr: Vec<(ListenerId, Multiaddr, TransportKind)>,
     pub on_expired_external_addr: Vec<Multiaddr>,
     pub on_listener_error: Vec<ListenerId>,
     pub on_listener_closed: Vec<(ListenerId, bool)>,
+    pub on_nat_status_changed: Vec<(NatStatus, NatStatus)>,
+    pub on_peer_listen_addrs_changed: Vec<(PeerId, Vec<Multiaddr>)>,
     pub poll: usize,
 }
 
@@ -186,6 +198,7 @@ where
             handle_established_outbound_connection: Vec::new(),
             on_connection_established: Vec::new(),
             on_connection_closed: Vec::new(),
+            on_connection_closed_reason: Vec::new(),
             on_connection_handler_event: Vec::new(),
             on_dial_failure: Vec::new(),
             on_new_listener: Vec::new(),
@@ -195,6 +208,8 @@ where
             on_expired_external_addr: Vec::new(),
             on_listener_error: Vec::new(),
             on_listener_closed: Vec::new(),
+            on_nat_status_changed: Vec::new(),
+            on_peer_listen_addrs_changed: Vec::new(),
             poll: 0,
         }
     }
@@ -207,6 +222,7 @@ where
         self.handle_established_outbound_connection = Vec::new();
         self.on_connection_established = Vec::new();
         self.on_connection_closed = Vec::new();
+        self.on_connection_closed_reason = Vec::new();
         self.on_connection_handler_event = Vec::new();
         self.on_dial_failure = Vec::new();
         self.on_new_listen_addr = Vec::new();
@@ -214,6 +230,8 @@ where
         self.on_expired_listen_addr = Vec::new();
         self.on_listener_error = Vec::new();
         self.on_listener_closed = Vec::new();
+        self.on_nat_status_changed = Vec::new();
+        self.on_peer_listen_addrs_changed = Vec::new();
         self.poll = 0;
     }
 
@@ -289,6 +307,7 @@ where
             endpoint,
             failed_addresses,
             other_established,
+            user_data,
         }: ConnectionEstablished,
     ) {
         let mut other_peer_connections = self
@@ -331,6 +350,7 @@ where
                 endpoint,
                 failed_addresses,
                 other_established,
+                user_data,
             }));
     }
 
@@ -342,6 +362,7 @@ where
             endpoint,
             handler,
             remaining_established,
+            reason,
         }: ConnectionClosed<<Self as NetworkBehaviour>::ConnectionHandler>,
     ) {
         let mut other_closed_connections = self
@@ -385,6 +406,8 @@ where
             endpoint.clone(),
             remaining_established,
         ));
+        self.on_connection_closed_reason
+            .push(reason.map(ToOwned::to_owned));
         self.inner
             .on_swarm_event(FromSwarm::ConnectionClosed(ConnectionClosed {
                 peer_id,
@@ -392,6 +415,7 @@ where
                 endpoint,
                 handler,
                 remaining_established,
+                reason,
             }));
     }
 }
@@ -490,6 +514,7 @@ where
                 peer_id,
                 connection_id,
                 error,
+                user_data,
             }) => {
                 self.on_dial_failure.push(peer_id);
                 self.inner
@@ -497,6 +522,7 @@ where
                         peer_id,
                         connection_id,
                         error,
+                        user_data,
                     }));
             }
             FromSwarm::NewListener(NewListener { listener_id }) => {
@@ -504,21 +530,32 @@ where
                 self.inner
                     .on_swarm_event(FromSwarm::NewListener(NewListener { listener_id }));
             }
-            FromSwarm::NewListenAddr(NewListenAddr { listener_id, addr }) => {
-                self.on_new_listen_addr.push((listener_id, addr.clone()));
+            FromSwarm::NewListenAddr(NewListenAddr {
+                listener_id,
+                addr,
+                transport,
+            }) => {
+                self.on_new_listen_addr
+                    .push((listener_id, addr.clone(), transport));
                 self.inner
                     .on_swarm_event(FromSwarm::NewListenAddr(NewListenAddr {
                         listener_id,
                         addr,
+                        transport,
                     }));
             }
-            FromSwarm::ExpiredListenAddr(ExpiredListenAddr { listener_id, addr }) => {
+            FromSwarm::ExpiredListenAddr(ExpiredListenAddr {
+                listener_id,
+                addr,
+                transport,
+            }) => {
                 self.on_expired_listen_addr
-                    .push((listener_id, addr.clone()));
+                    .push((listener_id, addr.clone(), transport));
                 self.inner
                     .on_swarm_event(FromSwarm::ExpiredListenAddr(ExpiredListenAddr {
                         listener_id,
                         addr,
+                        transport,
                     }));
             }
             FromSwarm::NewExternalAddr(NewExternalAddr { addr }) => {
@@ -547,6 +584,24 @@ where
                         reason,
                     }));
             }
+            FromSwarm::NatStatusChanged(NatStatusChanged { old, new }) => {
+                self.on_nat_status_changed.push((old.clone(), new.clone()));
+                self.inner
+                    .on_swarm_event(FromSwarm::NatStatusChanged(NatStatusChanged { old, new }));
+            }
+            FromSwarm::PeerListenAddrsChanged(PeerListenAddrsChanged {
+                peer_id,
+                listen_addrs,
+            }) => {
+                self.on_peer_listen_addrs_changed
+                    .push((peer_id, listen_addrs.to_vec()));
+                self.inner.on_swarm_event(FromSwarm::PeerListenAddrsChanged(
+                    PeerListenAddrsChanged {
+                        peer_id,
+                        listen_addrs,
+                    },
+                ));
+            }
             _ => {}
         }
     }
@@ -583,4 +638,8 @@ where
         self.poll += 1;
         self.inner.poll(cx, args)
     }
+
+    fn queued_actions(&self) -> usize {
+        self.inner.queued_actions()
+    }
 }
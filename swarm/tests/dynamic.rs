@@ -0,0 +1,99 @@
+// Copyright 2023 Protocol Labs.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use libp2p_ping as ping;
+use libp2p_swarm::{behaviour::dynamic::DynamicBehaviour, keep_alive, NetworkBehaviour, Swarm};
+use libp2p_swarm_test::SwarmExt;
+
+#[derive(NetworkBehaviour)]
+#[behaviour(prelude = "libp2p_swarm::derive_prelude")]
+struct Behaviour {
+    keep_alive: keep_alive::Behaviour,
+    ping: DynamicBehaviour<ping::Behaviour>,
+}
+
+impl Behaviour {
+    fn new(ping: Option<ping::Behaviour>) -> Self {
+        let mut dynamic = DynamicBehaviour::new();
+        if let Some(ping) = ping {
+            dynamic.insert(ping);
+        }
+
+        Behaviour {
+            keep_alive: keep_alive::Behaviour,
+            ping: dynamic,
+        }
+    }
+}
+
+#[async_std::test]
+async fn behaviour_inserted_after_connection_uses_existing_connection() {
+    let mut swarm1 = Swarm::new_ephemeral(|_| Behaviour::new(None));
+    let mut swarm2 = Swarm::new_ephemeral(|_| Behaviour::new(Some(ping::Behaviour::default())));
+
+    swarm2.listen().await;
+    swarm1.connect(&mut swarm2).await;
+    let peer2 = *swarm2.local_peer_id();
+
+    assert!(!swarm1.behaviour().ping.is_enabled());
+    swarm1
+        .behaviour_mut()
+        .ping
+        .insert(ping::Behaviour::default());
+    async_std::task::spawn(swarm2.loop_on_next());
+
+    let event = swarm1
+        .wait(|e| match e {
+            libp2p_swarm::SwarmEvent::Behaviour(BehaviourEvent::Ping(event)) => Some(event),
+            _ => None,
+        })
+        .await;
+
+    assert_eq!(event.peer, peer2);
+    assert!(event.result.is_ok());
+    assert_eq!(swarm1.network_info().num_peers(), 1);
+}
+
+#[async_std::test]
+async fn reinserted_behaviour_replaces_handler_of_removed_one() {
+    let mut swarm1 = Swarm::new_ephemeral(|_| Behaviour::new(Some(ping::Behaviour::default())));
+    let mut swarm2 = Swarm::new_ephemeral(|_| Behaviour::new(Some(ping::Behaviour::default())));
+
+    swarm2.listen().await;
+    swarm1.connect(&mut swarm2).await;
+    async_std::task::spawn(swarm2.loop_on_next());
+
+    assert!(swarm1.behaviour_mut().ping.remove().is_some());
+    assert!(swarm1.behaviour_mut().ping.remove().is_none());
+    assert!(swarm1
+        .behaviour_mut()
+        .ping
+        .insert(ping::Behaviour::default())
+        .is_none());
+
+    let event = swarm1
+        .wait(|e| match e {
+            libp2p_swarm::SwarmEvent::Behaviour(BehaviourEvent::Ping(event)) => Some(event),
+            _ => None,
+        })
+        .await;
+
+    assert!(event.result.is_ok());
+}
@@ -519,7 +519,10 @@ fn custom_out_event_no_type_parameters() {
                 | FromSwarm::ListenerError(_)
                 | FromSwarm::ListenerClosed(_)
                 | FromSwarm::NewExternalAddr(_)
-                | FromSwarm::ExpiredExternalAddr(_) => {}
+                | FromSwarm::ExpiredExternalAddr(_)
+                | FromSwarm::PeerProtocolsChanged(_)
+                | FromSwarm::PeerListenAddrsChanged(_)
+                | FromSwarm::NatStatusChanged(_) => {}
             }
         }
     }
@@ -544,3 +547,186 @@ fn custom_out_event_no_type_parameters() {
     require_net_behaviour::<Behaviour<String>>();
     require_net_behaviour::<Behaviour<()>>();
 }
+
+#[test]
+fn generic_with_inline_bounds() {
+    use libp2p_kad::record::store::{MemoryStore, RecordStore};
+
+    #[allow(dead_code)]
+    #[derive(NetworkBehaviour)]
+    #[behaviour(prelude = "libp2p_swarm::derive_prelude")]
+    pub struct Foo<S: RecordStore + Send + 'static> {
+        kad: libp2p_kad::Kademlia<S>,
+        ping: ping::Behaviour,
+    }
+
+    #[allow(dead_code)]
+    fn foo() {
+        require_net_behaviour::<Foo<MemoryStore>>();
+    }
+}
+
+#[test]
+fn generic_with_default_type_parameter() {
+    use libp2p_kad::record::store::{MemoryStore, RecordStore};
+
+    #[allow(dead_code)]
+    #[derive(NetworkBehaviour)]
+    #[behaviour(prelude = "libp2p_swarm::derive_prelude")]
+    pub struct Foo<S = MemoryStore>
+    where
+        S: RecordStore + Send + 'static,
+    {
+        kad: libp2p_kad::Kademlia<S>,
+        ping: ping::Behaviour,
+    }
+
+    #[allow(dead_code)]
+    fn foo() {
+        require_net_behaviour::<Foo>();
+    }
+}
+
+#[test]
+fn generic_with_const_parameter() {
+    use libp2p_kad::record::store::{MemoryStore, RecordStore};
+
+    #[allow(dead_code)]
+    #[derive(NetworkBehaviour)]
+    #[behaviour(prelude = "libp2p_swarm::derive_prelude")]
+    pub struct Foo<S, const N: usize>
+    where
+        S: RecordStore + Send + 'static,
+    {
+        kad: libp2p_kad::Kademlia<S>,
+        ping: ping::Behaviour,
+    }
+
+    #[allow(dead_code)]
+    fn foo() {
+        require_net_behaviour::<Foo<MemoryStore, 3>>();
+    }
+}
+
+#[async_std::test]
+async fn poll_order_rotates_between_fields() {
+    use libp2p_identity::PeerId;
+    use libp2p_swarm::{ConnectionId, PollParameters, Swarm, ToSwarm};
+    use libp2p_swarm_test::SwarmExt;
+    use std::task::{Context, Poll};
+
+    /// Behaviour that always has an event ready.
+    struct Flood;
+
+    impl NetworkBehaviour for Flood {
+        type ConnectionHandler = dummy::ConnectionHandler;
+        type OutEvent = ();
+
+        fn handle_established_inbound_connection(
+            &mut self,
+            _: ConnectionId,
+            _: PeerId,
+            _: &Multiaddr,
+            _: &Multiaddr,
+        ) -> Result<THandler<Self>, ConnectionDenied> {
+            Ok(dummy::ConnectionHandler)
+        }
+
+        fn handle_established_outbound_connection(
+            &mut self,
+            _: ConnectionId,
+            _: PeerId,
+            _: &Multiaddr,
+            _: Endpoint,
+        ) -> Result<THandler<Self>, ConnectionDenied> {
+            Ok(dummy::ConnectionHandler)
+        }
+
+        fn on_connection_handler_event(
+            &mut self,
+            _peer: PeerId,
+            _connection: ConnectionId,
+            message: THandlerOutEvent<Self>,
+        ) {
+            void::unreachable(message);
+        }
+
+        fn poll(
+            &mut self,
+            _ctx: &mut Context,
+            _: &mut impl PollParameters,
+        ) -> Poll<ToSwarm<Self::OutEvent, THandlerInEvent<Self>>> {
+            Poll::Ready(ToSwarm::GenerateEvent(()))
+        }
+
+        fn on_swarm_event(&mut self, _: FromSwarm<Self::ConnectionHandler>) {}
+
+        fn queued_actions(&self) -> usize {
+            1
+        }
+    }
+
+    #[derive(NetworkBehaviour)]
+    #[behaviour(prelude = "libp2p_swarm::derive_prelude")]
+    struct Foo {
+        first: Flood,
+        second: Flood,
+        third: Flood,
+    }
+
+    let mut swarm = Swarm::new_ephemeral(|_| Foo {
+        first: Flood,
+        second: Flood,
+        third: Flood,
+    });
+    assert_eq!(swarm.behaviour().queued_actions(), 3);
+
+    let mut polled = Vec::new();
+    while polled.len() < 6 {
+        if let SwarmEvent::Behaviour(event) = swarm.select_next_some().await {
+            polled.push(match event {
+                FooEvent::First(()) => 1,
+                FooEvent::Second(()) => 2,
+                FooEvent::Third(()) => 3,
+            });
+        }
+    }
+
+    assert_eq!(polled, [1, 2, 3, 1, 2, 3]);
+
+    // Nested behaviours rotate independently of the outer one.
+    #[derive(NetworkBehaviour)]
+    #[behaviour(prelude = "libp2p_swarm::derive_prelude")]
+    struct Inner {
+        first: Flood,
+        second: Flood,
+    }
+
+    #[derive(NetworkBehaviour)]
+    #[behaviour(prelude = "libp2p_swarm::derive_prelude")]
+    struct Outer {
+        inner: Inner,
+        flood: Flood,
+    }
+
+    let mut swarm = Swarm::new_ephemeral(|_| Outer {
+        inner: Inner {
+            first: Flood,
+            second: Flood,
+        },
+        flood: Flood,
+    });
+
+    let mut polled = Vec::new();
+    while polled.len() < 6 {
+        if let SwarmEvent::Behaviour(event) = swarm.select_next_some().await {
+            polled.push(match event {
+                OuterEvent::Inner(InnerEvent::First(())) => 1,
+                OuterEvent::Inner(InnerEvent::Second(())) => 2,
+                OuterEvent::Flood(()) => 3,
+            });
+        }
+    }
+
+    assert_eq!(polled, [1, 3, 2, 3, 1, 3]);
+}
@@ -31,6 +31,13 @@
 //! `/dns6/...` and `/dnsaddr/...` components of the given `Multiaddr` through
 //! a DNS, replacing them with the resolved protocols (typically TCP/IP).
 //!
+//! Likewise, [`Transport::listen_on`] resolves the `/dns/...`, `/dns4/...` and
+//! `/dns6/...` components of a listen address, listening on the inner transport
+//! on the first IP address found for each. The address is resolved again once
+//! the DNS records expire, moving the inner listener to the new IP address if
+//! it changed. This allows configuring listeners by hostname in environments
+//! where IP addresses change, e.g. in container orchestration.
+//!
 //! The `async-std` feature and hence the `DnsConfig` are
 //! enabled by default. Tokio users can furthermore opt-in
 //! to the `tokio-dns-over-rustls` and `tokio-dns-over-https-rustls`
@@ -60,16 +67,18 @@
 #[cfg(feature = "async-std")]
 use async_std_resolver::{AsyncStdConnection, AsyncStdConnectionProvider};
 use futures::{future::BoxFuture, prelude::*};
+use futures_timer::Delay;
 use libp2p_core::{
     connection::Endpoint,
     multiaddr::{Multiaddr, Protocol},
-    transport::{ListenerId, TransportError, TransportEvent},
+    transport::{DialOpts, ListenerId, PortUse, TransportError, TransportEvent},
     Transport,
 };
 use parking_lot::Mutex;
 use smallvec::SmallVec;
 use std::io;
 use std::{
+    collections::{HashMap, HashSet, VecDeque},
     convert::TryFrom,
     error, fmt, iter,
     net::IpAddr,
@@ -77,7 +86,8 @@ use std::{
     pin::Pin,
     str,
     sync::Arc,
-    task::{Context, Poll},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
 };
 #[cfg(any(feature = "async-std", feature = "tokio"))]
 use trust_dns_resolver::system_conf;
@@ -106,6 +116,13 @@ const MAX_DNS_LOOKUPS: usize = 32;
 /// result of a single `/dnsaddr` lookup.
 const MAX_TXT_RECORDS: usize = 16;
 
+/// The minimum time between two resolutions of a listen address,
+/// regardless of the TTL of its DNS records.
+///
+/// This is also the time after which resolving a listen address is
+/// retried, if it failed.
+const MIN_LISTEN_RESOLVE_INTERVAL: Duration = Duration::from_secs(5);
+
 /// A `Transport` wrapper for performing DNS lookups when dialing `Multiaddr`esses
 /// using `async-std` for all async I/O.
 #[cfg(feature = "async-std")]
@@ -126,6 +143,44 @@ where
     inner: Arc<Mutex<T>>,
     /// The DNS resolver used when dialing addresses with DNS components.
     resolver: AsyncResolver<C, P>,
+    /// The listeners on addresses with DNS components.
+    listeners: Vec<DnsListener>,
+    /// Maps the listeners of the inner transport on resolved addresses to the
+    /// IDs of the corresponding [`DnsListener`]s.
+    listener_ids: HashMap<ListenerId, ListenerId>,
+    /// Listeners of the inner transport that were replaced by a listener on a
+    /// newly resolved address, whose closing is not reported.
+    replaced_listeners: HashSet<ListenerId>,
+    /// The addresses reported by the listeners of the inner transport on resolved addresses.
+    listen_addrs: HashMap<ListenerId, Vec<Multiaddr>>,
+    /// The addresses of replaced listeners of the inner transport, whose expiry is yet to be
+    /// reported with the ID of the corresponding [`DnsListener`].
+    expired_addrs: VecDeque<(ListenerId, Multiaddr)>,
+    /// [`DnsListener`]s that were removed before listening on the inner transport,
+    /// whose closing is yet to be reported.
+    removed_listeners: VecDeque<ListenerId>,
+    /// The waker of the task polling the transport, woken on new listeners.
+    waker: Option<Waker>,
+}
+
+/// A listener on an address with DNS components, see [`GenDnsConfig::listen_on`].
+struct DnsListener {
+    /// The ID reported for the listener, independent of the inner listener.
+    id: ListenerId,
+    /// The address to listen on, as given to [`Transport::listen_on`].
+    addr: Multiaddr,
+    /// The listener of the inner transport and the resolved address it listens on.
+    inner: Option<(ListenerId, Multiaddr)>,
+    /// The state of the (re-)resolution of `addr`.
+    resolution: ListenerResolution,
+}
+
+/// The state of the resolution of the address of a [`DnsListener`].
+enum ListenerResolution {
+    /// The address is being resolved.
+    Resolving(BoxFuture<'static, Result<(Multiaddr, Instant), ResolveError>>),
+    /// The address is resolved again once the delay expires.
+    Waiting(Delay),
 }
 
 #[cfg(feature = "async-std")]
@@ -145,6 +200,13 @@ impl<T> DnsConfig<T> {
         Ok(DnsConfig {
             inner: Arc::new(Mutex::new(inner)),
             resolver: async_std_resolver::resolver(cfg, opts).await?,
+            listeners: Vec::new(),
+            listener_ids: HashMap::new(),
+            replaced_listeners: HashSet::new(),
+            listen_addrs: HashMap::new(),
+            expired_addrs: VecDeque::new(),
+            removed_listeners: VecDeque::new(),
+            waker: None,
         })
     }
 }
@@ -167,6 +229,13 @@ impl<T> TokioDnsConfig<T> {
         Ok(TokioDnsConfig {
             inner: Arc::new(Mutex::new(inner)),
             resolver: TokioAsyncResolver::tokio(cfg, opts)?,
+            listeners: Vec::new(),
+            listener_ids: HashMap::new(),
+            replaced_listeners: HashSet::new(),
+            listen_addrs: HashMap::new(),
+            expired_addrs: VecDeque::new(),
+            removed_listeners: VecDeque::new(),
+            waker: None,
         })
     }
 }
@@ -199,25 +268,85 @@ where
     >;
 
     fn listen_on(&mut self, addr: Multiaddr) -> Result<ListenerId, TransportError<Self::Error>> {
-        self.inner
-            .lock()
-            .listen_on(addr)
-            .map_err(|e| e.map(DnsErr::Transport))
+        if addr.iter().any(|p| matches!(p, Protocol::Dnsaddr(_))) {
+            return Err(TransportError::MultiaddrNotSupported(addr));
+        }
+        if !addr
+            .iter()
+            .any(|p| matches!(p, Protocol::Dns(_) | Protocol::Dns4(_) | Protocol::Dns6(_)))
+        {
+            return self
+                .inner
+                .lock()
+                .listen_on(addr)
+                .map_err(|e| e.map(DnsErr::Transport));
+        }
+
+        let id = ListenerId::new();
+        self.listeners.push(DnsListener {
+            id,
+            addr: addr.clone(),
+            inner: None,
+            resolution: ListenerResolution::Resolving(resolve_listen_addr(
+                addr,
+                self.resolver.clone(),
+            )),
+        });
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+        Ok(id)
     }
 
     fn remove_listener(&mut self, id: ListenerId) -> bool {
-        self.inner.lock().remove_listener(id)
+        let index = match self.listeners.iter().position(|l| l.id == id) {
+            Some(index) => index,
+            None => return self.inner.lock().remove_listener(id),
+        };
+        match self.listeners.remove(index).inner {
+            // The closing of the inner listener is reported with the ID of the removed one.
+            Some((inner_id, _)) => {
+                self.inner.lock().remove_listener(inner_id);
+            }
+            None => {
+                self.removed_listeners.push_back(id);
+                if let Some(waker) = self.waker.take() {
+                    waker.wake();
+                }
+            }
+        }
+        true
     }
 
     fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
-        self.do_dial(addr, Endpoint::Dialer)
+        self.do_dial(
+            addr,
+            DialOpts {
+                role: Endpoint::Dialer,
+                port_use: PortUse::New,
+            },
+        )
     }
 
     fn dial_as_listener(
         &mut self,
         addr: Multiaddr,
     ) -> Result<Self::Dial, TransportError<Self::Error>> {
-        self.do_dial(addr, Endpoint::Listener)
+        self.do_dial(
+            addr,
+            DialOpts {
+                role: Endpoint::Listener,
+                port_use: PortUse::New,
+            },
+        )
+    }
+
+    fn dial_with_opts(
+        &mut self,
+        addr: Multiaddr,
+        opts: DialOpts,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.do_dial(addr, opts)
     }
 
     fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
@@ -228,12 +357,42 @@ where
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<TransportEvent<Self::ListenerUpgrade, Self::Error>> {
-        let mut inner = self.inner.lock();
-        Transport::poll(Pin::new(inner.deref_mut()), cx).map(|event| {
-            event
+        let this = self.get_mut();
+        this.waker = Some(cx.waker().clone());
+
+        if let Some(listener_id) = this.removed_listeners.pop_front() {
+            return Poll::Ready(TransportEvent::ListenerClosed {
+                listener_id,
+                reason: Ok(()),
+            });
+        }
+
+        if let Poll::Ready(event) = this.poll_listeners(cx) {
+            return Poll::Ready(event);
+        }
+
+        if let Some((listener_id, listen_addr)) = this.expired_addrs.pop_front() {
+            return Poll::Ready(TransportEvent::AddressExpired {
+                listener_id,
+                listen_addr,
+            });
+        }
+
+        loop {
+            let event = {
+                let mut inner = this.inner.lock();
+                match Transport::poll(Pin::new(inner.deref_mut()), cx) {
+                    Poll::Ready(event) => event,
+                    Poll::Pending => return Poll::Pending,
+                }
+            };
+            let event = event
                 .map_upgrade(|upgr| upgr.map_err::<_, fn(_) -> _>(DnsErr::Transport))
-                .map_err(DnsErr::Transport)
-        })
+                .map_err(DnsErr::Transport);
+            if let Some(event) = this.map_listener_event(event) {
+                return Poll::Ready(event);
+            }
+        }
     }
 }
 
@@ -245,10 +404,202 @@ where
     C: DnsHandle<Error = ResolveError>,
     P: ConnectionProvider<Conn = C>,
 {
+    /// Drives the (re-)resolution of the addresses of the [`DnsListener`]s, listening
+    /// on the inner transport on newly resolved addresses.
+    fn poll_listeners(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<TransportEvent<<Self as Transport>::ListenerUpgrade, <Self as Transport>::Error>>
+    {
+        let mut index = 0;
+        while index < self.listeners.len() {
+            let listener = &mut self.listeners[index];
+            let result = match &mut listener.resolution {
+                ListenerResolution::Waiting(delay) => {
+                    if delay.poll_unpin(cx).is_ready() {
+                        listener.resolution = ListenerResolution::Resolving(resolve_listen_addr(
+                            listener.addr.clone(),
+                            self.resolver.clone(),
+                        ));
+                        // Poll the new resolution right away.
+                        continue;
+                    }
+                    index += 1;
+                    continue;
+                }
+                ListenerResolution::Resolving(resolving) => match resolving.poll_unpin(cx) {
+                    Poll::Ready(result) => result,
+                    Poll::Pending => {
+                        index += 1;
+                        continue;
+                    }
+                },
+            };
+
+            let (addr, valid_until) = match result {
+                Ok(resolved) => resolved,
+                Err(e) => {
+                    log::debug!("Failed to resolve listen address {}: {}", listener.addr, e);
+                    let listener_id = listener.id;
+                    if listener.inner.is_none() {
+                        self.listeners.remove(index);
+                        return Poll::Ready(TransportEvent::ListenerClosed {
+                            listener_id,
+                            reason: Err(DnsErr::ResolveError(e)),
+                        });
+                    }
+                    // Keep listening on the previously resolved address and retry later.
+                    listener.resolution =
+                        ListenerResolution::Waiting(Delay::new(MIN_LISTEN_RESOLVE_INTERVAL));
+                    return Poll::Ready(TransportEvent::ListenerError {
+                        listener_id,
+                        error: DnsErr::ResolveError(e),
+                    });
+                }
+            };
+
+            let ttl = valid_until.saturating_duration_since(Instant::now());
+            listener.resolution =
+                ListenerResolution::Waiting(Delay::new(ttl.max(MIN_LISTEN_RESOLVE_INTERVAL)));
+
+            if listener.inner.as_ref().map(|(_, a)| a) == Some(&addr) {
+                index += 1;
+                continue;
+            }
+
+            log::debug!("Resolved listen address {} -> {}", listener.addr, addr);
+            let mut inner = self.inner.lock();
+            match inner.listen_on(addr.clone()) {
+                Ok(inner_id) => {
+                    self.listener_ids.insert(inner_id, listener.id);
+                    if let Some((old_id, _)) = listener.inner.replace((inner_id, addr)) {
+                        // Not all transports report the expiry of the addresses of a removed
+                        // listener, e.g. TCP doesn't, thus report it on their behalf.
+                        self.expired_addrs.extend(
+                            self.listen_addrs
+                                .remove(&old_id)
+                                .into_iter()
+                                .flatten()
+                                .map(|a| (listener.id, a)),
+                        );
+                        self.replaced_listeners.insert(old_id);
+                        inner.remove_listener(old_id);
+                    }
+                    index += 1;
+                }
+                Err(e) => {
+                    let error = match e {
+                        TransportError::MultiaddrNotSupported(a) => {
+                            DnsErr::MultiaddrNotSupported(a)
+                        }
+                        TransportError::Other(e) => DnsErr::Transport(e),
+                    };
+                    let listener_id = listener.id;
+                    if listener.inner.is_none() {
+                        self.listeners.remove(index);
+                        return Poll::Ready(TransportEvent::ListenerClosed {
+                            listener_id,
+                            reason: Err(error),
+                        });
+                    }
+                    return Poll::Ready(TransportEvent::ListenerError { listener_id, error });
+                }
+            }
+        }
+
+        Poll::Pending
+    }
+
+    /// Reports events of the listeners of the inner transport on resolved addresses with
+    /// the IDs of the corresponding [`DnsListener`]s, filtering out the address expiries and
+    /// closing of replaced listeners.
+    fn map_listener_event(
+        &mut self,
+        event: TransportEvent<<Self as Transport>::ListenerUpgrade, <Self as Transport>::Error>,
+    ) -> Option<TransportEvent<<Self as Transport>::ListenerUpgrade, <Self as Transport>::Error>>
+    {
+        let event = match event {
+            TransportEvent::NewAddress {
+                listener_id,
+                listen_addr,
+            } => {
+                if self.listener_ids.contains_key(&listener_id) {
+                    self.listen_addrs
+                        .entry(listener_id)
+                        .or_default()
+                        .push(listen_addr.clone());
+                }
+                TransportEvent::NewAddress {
+                    listener_id: self.listener_id(listener_id),
+                    listen_addr,
+                }
+            }
+            TransportEvent::AddressExpired {
+                listener_id,
+                listen_addr,
+            } => {
+                if self.replaced_listeners.contains(&listener_id) {
+                    // Already reported when the listener was replaced.
+                    return None;
+                }
+                if let Some(addrs) = self.listen_addrs.get_mut(&listener_id) {
+                    addrs.retain(|a| a != &listen_addr);
+                }
+                TransportEvent::AddressExpired {
+                    listener_id: self.listener_id(listener_id),
+                    listen_addr,
+                }
+            }
+            TransportEvent::Incoming {
+                listener_id,
+                upgrade,
+                local_addr,
+                send_back_addr,
+            } => TransportEvent::Incoming {
+                listener_id: self.listener_id(listener_id),
+                upgrade,
+                local_addr,
+                send_back_addr,
+            },
+            TransportEvent::ListenerError { listener_id, error } => TransportEvent::ListenerError {
+                listener_id: self.listener_id(listener_id),
+                error,
+            },
+            TransportEvent::ListenerClosed {
+                listener_id,
+                reason,
+            } => {
+                let id = self.listener_ids.remove(&listener_id);
+                self.listen_addrs.remove(&listener_id);
+                if self.replaced_listeners.remove(&listener_id) {
+                    return None;
+                }
+                if let Some(id) = id {
+                    // The inner listener closed on its own, so does the `DnsListener`.
+                    self.listeners.retain(|l| l.id != id);
+                }
+                TransportEvent::ListenerClosed {
+                    listener_id: id.unwrap_or(listener_id),
+                    reason,
+                }
+            }
+        };
+        Some(event)
+    }
+
+    /// Returns the ID of the [`DnsListener`] of the given inner listener, if any,
+    /// or else the given ID.
+    fn listener_id(&self, inner_id: ListenerId) -> ListenerId {
+        self.listener_ids
+            .get(&inner_id)
+            .copied()
+            .unwrap_or(inner_id)
+    }
+
     fn do_dial(
         &mut self,
         addr: Multiaddr,
-        role_override: Endpoint,
+        opts: DialOpts,
     ) -> Result<<Self as Transport>::Dial, TransportError<<Self as Transport>::Error>> {
         let resolver = self.resolver.clone();
         let inner = self.inner.clone();
@@ -334,10 +685,7 @@ where
                     log::debug!("Dialing {}", addr);
 
                     let transport = inner.clone();
-                    let dial = match role_override {
-                        Endpoint::Dialer => transport.lock().dial(addr),
-                        Endpoint::Listener => transport.lock().dial_as_listener(addr),
-                    };
+                    let dial = transport.lock().dial_with_opts(addr, opts);
                     let result = match dial {
                         Ok(out) => {
                             // We only count attempts that the inner transport
@@ -560,6 +908,59 @@ where
     }
 }
 
+/// Resolves the `Dns`, `Dns4` and `Dns6` protocol components of a listen address to the
+/// first IP address found for each, returning the resolved address together with the
+/// time until which the DNS records it was resolved from are valid.
+fn resolve_listen_addr<C, P>(
+    addr: Multiaddr,
+    resolver: AsyncResolver<C, P>,
+) -> BoxFuture<'static, Result<(Multiaddr, Instant), ResolveError>>
+where
+    C: DnsHandle<Error = ResolveError>,
+    P: ConnectionProvider<Conn = C>,
+{
+    async move {
+        let mut resolved = Multiaddr::empty();
+        let mut valid_until: Option<Instant> = None;
+        for proto in addr.iter() {
+            let (ip, until) = match proto {
+                Protocol::Dns(name) => {
+                    let ips = resolver.lookup_ip(name.into_owned()).await?;
+                    (ips.iter().next(), ips.valid_until())
+                }
+                Protocol::Dns4(name) => {
+                    let ips = resolver.ipv4_lookup(name.into_owned()).await?;
+                    (
+                        ips.iter().next().map(|ip| IpAddr::from(*ip)),
+                        ips.valid_until(),
+                    )
+                }
+                Protocol::Dns6(name) => {
+                    let ips = resolver.ipv6_lookup(name.into_owned()).await?;
+                    (
+                        ips.iter().next().map(|ip| IpAddr::from(*ip)),
+                        ips.valid_until(),
+                    )
+                }
+                proto => {
+                    resolved.push(proto);
+                    continue;
+                }
+            };
+            let ip = ip.ok_or_else(|| {
+                ResolveError::from(ResolveErrorKind::Message("No matching records found."))
+            })?;
+            resolved.push(Protocol::from(ip));
+            valid_until = Some(valid_until.map_or(until, |v| v.min(until)));
+        }
+        Ok((
+            resolved,
+            valid_until.expect("Listen address to have at least one DNS component."),
+        ))
+    }
+    .boxed()
+}
+
 /// Parses a `<character-string>` of a `dnsaddr` TXT record.
 fn parse_dnsaddr_txt(txt: &[u8]) -> io::Result<Multiaddr> {
     let s = str::from_utf8(txt).map_err(invalid_data)?;
@@ -588,7 +989,6 @@ mod tests {
     fn basic_resolve() {
         let _ = env_logger::try_init();
 
-        #[derive(Clone)]
         struct CustomTransport;
 
         impl Transport for CustomTransport {
@@ -640,7 +1040,7 @@ mod tests {
         where
             C: DnsHandle<Error = ResolveError>,
             P: ConnectionProvider<Conn = C>,
-            T: Transport + Clone + Send + Unpin + 'static,
+            T: Transport + Send + Unpin + 'static,
             T::Error: Send,
             T::Dial: Send,
         {
@@ -739,4 +1139,205 @@ mod tests {
             ));
         }
     }
+
+    #[test]
+    fn listen_resolve() {
+        let _ = env_logger::try_init();
+
+        #[derive(Default)]
+        struct ListenTransport {
+            events: VecDeque<TransportEvent<<Self as Transport>::ListenerUpgrade, io::Error>>,
+        }
+
+        impl Transport for ListenTransport {
+            type Output = ();
+            type Error = std::io::Error;
+            type ListenerUpgrade = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+            type Dial = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+            fn listen_on(
+                &mut self,
+                addr: Multiaddr,
+            ) -> Result<ListenerId, TransportError<Self::Error>> {
+                // Check that all DNS components have been resolved, i.e. replaced.
+                assert!(!addr.iter().any(|p| matches!(
+                    p,
+                    Protocol::Dns(_) | Protocol::Dns4(_) | Protocol::Dns6(_) | Protocol::Dnsaddr(_)
+                )));
+                let listener_id = ListenerId::new();
+                self.events.push_back(TransportEvent::NewAddress {
+                    listener_id,
+                    listen_addr: addr,
+                });
+                Ok(listener_id)
+            }
+
+            fn remove_listener(&mut self, listener_id: ListenerId) -> bool {
+                self.events.push_back(TransportEvent::ListenerClosed {
+                    listener_id,
+                    reason: Ok(()),
+                });
+                true
+            }
+
+            fn dial(&mut self, _: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+                unreachable!()
+            }
+
+            fn dial_as_listener(
+                &mut self,
+                _: Multiaddr,
+            ) -> Result<Self::Dial, TransportError<Self::Error>> {
+                unreachable!()
+            }
+
+            fn address_translation(&self, _: &Multiaddr, _: &Multiaddr) -> Option<Multiaddr> {
+                None
+            }
+
+            fn poll(
+                mut self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+            ) -> Poll<TransportEvent<Self::ListenerUpgrade, Self::Error>> {
+                match self.events.pop_front() {
+                    Some(event) => Poll::Ready(event),
+                    None => Poll::Pending,
+                }
+            }
+        }
+
+        async fn next_event<C, P>(
+            transport: &mut GenDnsConfig<ListenTransport, C, P>,
+        ) -> TransportEvent<
+            <GenDnsConfig<ListenTransport, C, P> as Transport>::ListenerUpgrade,
+            DnsErr<io::Error>,
+        >
+        where
+            C: DnsHandle<Error = ResolveError>,
+            P: ConnectionProvider<Conn = C>,
+        {
+            future::poll_fn(|cx| Pin::new(&mut *transport).poll(cx)).await
+        }
+
+        async fn run<C, P>(mut transport: GenDnsConfig<ListenTransport, C, P>)
+        where
+            C: DnsHandle<Error = ResolveError>,
+            P: ConnectionProvider<Conn = C>,
+        {
+            // Addresses without DNS components are passed through.
+            let id = transport
+                .listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+                .unwrap();
+            match next_event(&mut transport).await {
+                TransportEvent::NewAddress {
+                    listener_id,
+                    listen_addr,
+                } => {
+                    assert_eq!(listener_id, id);
+                    assert_eq!(listen_addr, "/ip4/127.0.0.1/tcp/0".parse().unwrap());
+                }
+                e => panic!("Unexpected event: {e:?}"),
+            }
+
+            // `localhost` is resolved through the hosts file.
+            let id = transport
+                .listen_on("/dns4/localhost/tcp/0".parse().unwrap())
+                .unwrap();
+            match next_event(&mut transport).await {
+                TransportEvent::NewAddress {
+                    listener_id,
+                    listen_addr,
+                } => {
+                    assert_eq!(listener_id, id);
+                    assert_eq!(listen_addr, "/ip4/127.0.0.1/tcp/0".parse().unwrap());
+                }
+                e => panic!("Unexpected event: {e:?}"),
+            }
+
+            // Once the name resolves to a different IP, the listener is replaced, expiring the
+            // address of the previous one.
+            transport.listeners[0].resolution = ListenerResolution::Resolving(
+                future::ready(Ok((
+                    "/ip4/127.0.0.2/tcp/0".parse().unwrap(),
+                    Instant::now() + Duration::from_secs(60),
+                )))
+                .boxed(),
+            );
+            match next_event(&mut transport).await {
+                TransportEvent::AddressExpired {
+                    listener_id,
+                    listen_addr,
+                } => {
+                    assert_eq!(listener_id, id);
+                    assert_eq!(listen_addr, "/ip4/127.0.0.1/tcp/0".parse().unwrap());
+                }
+                e => panic!("Unexpected event: {e:?}"),
+            }
+            match next_event(&mut transport).await {
+                TransportEvent::NewAddress {
+                    listener_id,
+                    listen_addr,
+                } => {
+                    assert_eq!(listener_id, id);
+                    assert_eq!(listen_addr, "/ip4/127.0.0.2/tcp/0".parse().unwrap());
+                }
+                e => panic!("Unexpected event: {e:?}"),
+            }
+
+            // The closing of the inner listener is reported with the ID of the DNS listener.
+            assert!(transport.remove_listener(id));
+            match next_event(&mut transport).await {
+                TransportEvent::ListenerClosed {
+                    listener_id,
+                    reason: Ok(()),
+                } => assert_eq!(listener_id, id),
+                e => panic!("Unexpected event: {e:?}"),
+            }
+
+            // `/dnsaddr` is not supported for listening.
+            assert!(matches!(
+                transport.listen_on("/dnsaddr/localhost".parse().unwrap()),
+                Err(TransportError::MultiaddrNotSupported(_))
+            ));
+
+            // Failure to resolve closes the listener.
+            let id = transport
+                .listen_on("/dns4/example.invalid/tcp/0".parse().unwrap())
+                .unwrap();
+            match next_event(&mut transport).await {
+                TransportEvent::ListenerClosed {
+                    listener_id,
+                    reason: Err(DnsErr::ResolveError(_)),
+                } => assert_eq!(listener_id, id),
+                e => panic!("Unexpected event: {e:?}"),
+            }
+        }
+
+        #[cfg(feature = "async-std")]
+        {
+            let config = ResolverConfig::quad9();
+            let opts = ResolverOpts::default();
+            async_std_crate::task::block_on(
+                DnsConfig::custom(ListenTransport::default(), config, opts)
+                    .then(|dns| run(dns.unwrap())),
+            );
+        }
+
+        #[cfg(feature = "tokio")]
+        {
+            let config = ResolverConfig::quad9();
+            let opts = ResolverOpts::default();
+            let rt = tokio_crate::runtime::Builder::new_current_thread()
+                .enable_io()
+                .enable_time()
+                .build()
+                .unwrap();
+            rt.block_on(run(TokioDnsConfig::custom(
+                ListenTransport::default(),
+                config,
+                opts,
+            )
+            .unwrap()));
+        }
+    }
 }
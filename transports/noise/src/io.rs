@@ -23,11 +23,14 @@
 mod framed;
 pub mod handshake;
 
+use crate::NoiseError;
 use bytes::Bytes;
 use framed::{NoiseFramed, MAX_FRAME_LEN};
 use futures::prelude::*;
 use futures::ready;
+use hkdf::Hkdf;
 use log::trace;
+use sha2::Sha256;
 use std::{
     cmp::min,
     fmt, io,
@@ -40,6 +43,7 @@ use std::{
 /// `T` is the type of the underlying I/O resource.
 pub struct NoiseOutput<T> {
     io: NoiseFramed<T, snow::TransportState>,
+    handshake_hash: Vec<u8>,
     recv_buffer: Bytes,
     recv_offset: usize,
     send_buffer: Vec<u8>,
@@ -53,15 +57,42 @@ impl<T> fmt::Debug for NoiseOutput<T> {
 }
 
 impl<T> NoiseOutput<T> {
-    fn new(io: NoiseFramed<T, snow::TransportState>) -> Self {
+    fn new(io: NoiseFramed<T, snow::TransportState>, handshake_hash: Vec<u8>) -> Self {
         NoiseOutput {
             io,
+            handshake_hash,
             recv_buffer: Bytes::new(),
             recv_offset: 0,
             send_buffer: Vec::new(),
             send_offset: 0,
         }
     }
+
+    /// Derives keying material bound to this session, e.g. as a channel binding
+    /// token for application-layer authentication.
+    ///
+    /// Both peers of a session derive the same `out.len()` bytes for the same
+    /// `label` and `context`, using HKDF-SHA256 over the hash of the finished
+    /// handshake. Different labels and contexts yield independent outputs, and
+    /// the output reveals nothing about the keys encrypting the session.
+    ///
+    /// Fails if more than `255 * 32` bytes are requested.
+    pub fn export_keying_material(
+        &self,
+        label: &[u8],
+        context: &[u8],
+        out: &mut [u8],
+    ) -> Result<(), NoiseError> {
+        let mut info = Vec::with_capacity(label.len() + context.len() + 8);
+        info.extend_from_slice(&(label.len() as u32).to_be_bytes());
+        info.extend_from_slice(label);
+        info.extend_from_slice(&(context.len() as u32).to_be_bytes());
+        info.extend_from_slice(context);
+
+        Hkdf::<Sha256>::new(Some(b"libp2p-noise-exporter"), &self.handshake_hash)
+            .expand(&info, out)
+            .map_err(|_| NoiseError::InvalidExportLength)
+    }
 }
 
 impl<T: AsyncRead + Unpin> AsyncRead for NoiseOutput<T> {
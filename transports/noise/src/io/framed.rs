@@ -100,6 +100,8 @@ impl<T> NoiseFramed<T, snow::HandshakeState> {
             .map(C::public_from_bytes)
             .transpose()?;
 
+        let handshake_hash = self.session.get_handshake_hash().to_vec();
+
         let io = NoiseFramed {
             session: self.session.into_transport_mode()?,
             io: self.io,
@@ -110,7 +112,7 @@ impl<T> NoiseFramed<T, snow::HandshakeState> {
             decrypt_buffer: self.decrypt_buffer,
         };
 
-        Ok((dh_remote_pubkey, NoiseOutput::new(io)))
+        Ok((dh_remote_pubkey, NoiseOutput::new(io, handshake_hash)))
     }
 }
 
@@ -33,6 +33,7 @@ use crate::NoiseError;
 use bytes::Bytes;
 use futures::prelude::*;
 use libp2p_identity as identity;
+use libp2p_identity::PeerId;
 use quick_protobuf::{BytesReader, MessageRead, MessageWrite, Writer};
 use std::io;
 
@@ -77,6 +78,8 @@ pub struct State<T> {
     dh_remote_pubkey_sig: Option<Vec<u8>>,
     /// The known or received public identity key of the remote, if any.
     id_remote_pubkey: Option<identity::PublicKey>,
+    /// The peer the remote has to authenticate as, if any.
+    expected_peer_id: Option<PeerId>,
     /// Legacy configuration parameters.
     #[allow(deprecated)]
     legacy: LegacyConfig,
@@ -94,6 +97,7 @@ impl<T> State<T> {
         session: snow::HandshakeState,
         identity: KeypairIdentity,
         expected_remote_key: Option<identity::PublicKey>,
+        expected_peer_id: Option<PeerId>,
         legacy: LegacyConfig,
     ) -> Self {
         Self {
@@ -101,6 +105,7 @@ impl<T> State<T> {
             io: NoiseFramed::new(io, session),
             dh_remote_pubkey_sig: None,
             id_remote_pubkey: expected_remote_key,
+            expected_peer_id,
             legacy,
         }
     }
@@ -172,6 +177,7 @@ where
 ///
 /// In case `expected_key` is passed, this function will fail if the received key does not match the expected key.
 /// In case the remote does not send us a key, the expected key is assumed to be the remote's key.
+/// In case an expected peer ID is passed, this function will fail if the received key does not belong to it.
 pub async fn recv_identity<T>(state: &mut State<T>) -> Result<(), NoiseError>
 where
     T: AsyncRead + Unpin,
@@ -220,6 +226,14 @@ where
                 return Err(NoiseError::UnexpectedKey);
             }
         }
+        if let Some(expected) = state.expected_peer_id {
+            if pk.to_peer_id() != expected {
+                return Err(NoiseError::PeerIdMismatch {
+                    expected,
+                    actual: Box::new(pk),
+                });
+            }
+        }
         state.id_remote_pubkey = Some(pk);
     }
 
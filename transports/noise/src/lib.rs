@@ -72,6 +72,7 @@ use crate::handshake::State;
 use crate::io::handshake;
 use futures::future::BoxFuture;
 use futures::prelude::*;
+use libp2p_core::upgrade::ExpectPeerId;
 use libp2p_core::{InboundUpgrade, OutboundUpgrade, UpgradeInfo};
 use libp2p_identity as identity;
 use libp2p_identity::PeerId;
@@ -86,6 +87,8 @@ pub struct NoiseConfig<P, C: Zeroize, R = ()> {
     #[allow(deprecated)]
     legacy: LegacyConfig,
     remote: R,
+    /// The peer the remote has to authenticate as, if any.
+    expected_peer_id: Option<PeerId>,
     _marker: std::marker::PhantomData<P>,
 
     /// Prologue to use in the noise handshake.
@@ -134,7 +137,14 @@ where
             .into_builder(&self.prologue, self.dh_keys.keypair.secret(), None)
             .build_responder()?;
 
-        let state = State::new(socket, session, self.dh_keys.identity, None, self.legacy);
+        let state = State::new(
+            socket,
+            session,
+            self.dh_keys.identity,
+            None,
+            self.expected_peer_id,
+            self.legacy,
+        );
 
         Ok(state)
     }
@@ -145,7 +155,14 @@ where
             .into_builder(&self.prologue, self.dh_keys.keypair.secret(), None)
             .build_initiator()?;
 
-        let state = State::new(socket, session, self.dh_keys.identity, None, self.legacy);
+        let state = State::new(
+            socket,
+            session,
+            self.dh_keys.identity,
+            None,
+            self.expected_peer_id,
+            self.legacy,
+        );
 
         Ok(state)
     }
@@ -165,6 +182,7 @@ where
                 LegacyConfig::default()
             },
             remote: (),
+            expected_peer_id: None,
             _marker: std::marker::PhantomData,
             prologue: Vec::default(),
         }
@@ -185,6 +203,7 @@ where
                 LegacyConfig::default()
             },
             remote: (),
+            expected_peer_id: None,
             _marker: std::marker::PhantomData,
             prologue: Vec::default(),
         }
@@ -208,6 +227,7 @@ where
                 LegacyConfig::default()
             },
             remote: (),
+            expected_peer_id: None,
             _marker: std::marker::PhantomData,
             prologue: Vec::default(),
         }
@@ -235,6 +255,7 @@ where
                 LegacyConfig::default()
             },
             remote: (remote_dh, remote_id),
+            expected_peer_id: None,
             _marker: std::marker::PhantomData,
             prologue: Vec::default(),
         }
@@ -256,6 +277,7 @@ where
             session,
             self.dh_keys.identity,
             Some(self.remote.1),
+            self.expected_peer_id,
             self.legacy,
         );
 
@@ -277,6 +299,12 @@ pub enum NoiseError {
     InvalidLength,
     #[error("Remote authenticated with an unexpected public key")]
     UnexpectedKey,
+    #[error("Remote authenticated as {}, expected {expected}", .actual.to_peer_id())]
+    PeerIdMismatch {
+        expected: PeerId,
+        /// The public identity key the remote authenticated with.
+        actual: Box<identity::PublicKey>,
+    },
     #[error("The signature of the remote identity's public key does not verify")]
     BadSignature,
     #[error("Authentication failed")]
@@ -285,6 +313,8 @@ pub enum NoiseError {
     InvalidPayload(DecodeError),
     #[error(transparent)]
     SigningError(#[from] libp2p_identity::SigningError),
+    #[error("Requested more keying material than can be exported")]
+    InvalidExportLength,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -542,6 +572,24 @@ impl NoiseAuthenticated<XX, X25519Spec, ()> {
     }
 }
 
+impl<P, C: Zeroize, R> NoiseAuthenticated<P, C, R> {
+    /// Aborts the handshake with [`NoiseError::PeerIdMismatch`] as soon as the remote sends an
+    /// identity key not matching `peer_id`.
+    ///
+    /// Applied to each dial with a known [`PeerId`] by
+    /// [`Builder::authenticate_pinned`](libp2p_core::transport::upgrade::Builder::authenticate_pinned).
+    pub fn with_expected_peer_id(mut self, peer_id: PeerId) -> Self {
+        self.config.expected_peer_id = Some(peer_id);
+        self
+    }
+}
+
+impl<P, C: Zeroize, R> ExpectPeerId for NoiseAuthenticated<P, C, R> {
+    fn expect_peer_id(self, peer_id: PeerId) -> Self {
+        self.with_expected_peer_id(peer_id)
+    }
+}
+
 impl<P, C: Zeroize, R> UpgradeInfo for NoiseAuthenticated<P, C, R>
 where
     NoiseConfig<P, C, R>: UpgradeInfo,
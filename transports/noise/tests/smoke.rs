@@ -237,6 +237,12 @@ fn run<I, C>(
                 client_session.write_all(&m.0).await.expect("no error")
             }
             client_session.flush().await.expect("no error");
+
+            let mut token = [0; 32];
+            client_session
+                .export_keying_material(b"smoke", b"", &mut token)
+                .expect("valid length");
+            token
         };
 
         let server_fut = async {
@@ -268,9 +274,16 @@ fn run<I, C>(
                     .expect("no error");
                 assert_eq!(server_buffer, m.0)
             }
+
+            let mut token = [0; 32];
+            server_session
+                .export_keying_material(b"smoke", b"", &mut token)
+                .expect("valid length");
+            token
         };
 
-        futures::future::join(server_fut, client_fut).await;
+        let (server_token, client_token) = futures::future::join(server_fut, client_fut).await;
+        assert_eq!(server_token, client_token);
     })
 }
 
@@ -46,7 +46,7 @@ use if_watch::IfEvent;
 use libp2p_core::{
     address_translation,
     multiaddr::{Multiaddr, Protocol},
-    transport::{ListenerId, TransportError, TransportEvent},
+    transport::{DialOpts, ListenerId, PortUse, TransportError, TransportEvent},
 };
 use provider::{Incoming, Provider};
 use socket2::{Domain, Socket, Type};
@@ -71,6 +71,8 @@ pub struct Config {
     backlog: u32,
     /// Whether port reuse should be enabled.
     enable_port_reuse: bool,
+    /// The initial and maximum pause of a listener after failing to accept a connection.
+    accept_error_backoff: (Duration, Duration),
 }
 
 type Port = u16;
@@ -162,12 +164,15 @@ impl Config {
     ///     See [`Config::ttl`].
     ///   * The size of the listen backlog for new listening sockets is `1024`.
     ///     See [`Config::listen_backlog`].
+    ///   * Listeners pause for 100ms after failing to accept a connection, doubling up to 5s
+    ///     on consecutive failures. See [`Config::accept_error_backoff`].
     pub fn new() -> Self {
         Self {
             ttl: None,
             nodelay: None,
             backlog: 1024,
             enable_port_reuse: false,
+            accept_error_backoff: (Duration::from_millis(100), Duration::from_secs(5)),
         }
     }
 
@@ -189,6 +194,18 @@ impl Config {
         self
     }
 
+    /// Configures the pause of a listener after failing to accept a connection.
+    ///
+    /// Failing to accept a connection, e.g. because the process ran out of file descriptors
+    /// (`EMFILE`/`ENFILE`), does not close the listener. Instead, the listener reports a
+    /// [`TransportEvent::ListenerError`] and pauses accepting connections for `initial`,
+    /// doubling the pause on consecutive failures up to `max`. The pause is reset once a
+    /// connection is accepted again.
+    pub fn accept_error_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.accept_error_backoff = (initial, max.max(initial));
+        self
+    }
+
     /// Configures port reuse for local sockets, which implies
     /// reuse of listening ports for outgoing connections to
     /// enhance NAT traversal capabilities.
@@ -217,8 +234,10 @@ impl Config {
     ///      both address/port-dependent mapping and filtering, unless
     ///      there is some means of port prediction.
     ///
-    /// Both use-cases are enabled when port reuse is enabled, with port reuse
-    /// for outgoing connections (`2.` above) always being implied.
+    /// Both use-cases are enabled when port reuse is enabled. Port reuse for
+    /// outgoing connections (`2.` above) only applies to dials requesting it
+    /// via [`PortUse::Reuse`] in their [`DialOpts`], e.g. the dials of a hole
+    /// punch. All other dials use ephemeral local ports.
     ///
     /// > **Note**: Due to the identification of a TCP socket by a 4-tuple
     /// > of source IP address, source port, destination IP address and
@@ -234,7 +253,7 @@ impl Config {
     ///
     /// ```no_run
     /// # use futures::StreamExt;
-    /// # use libp2p_core::transport::{ListenerId, TransportEvent};
+    /// # use libp2p_core::transport::{DialOpts, ListenerId, PortUse, TransportEvent};
     /// # use libp2p_core::{Multiaddr, Transport};
     /// # use std::pin::Pin;
     /// # #[cfg(not(feature = "async-io"))]
@@ -246,13 +265,14 @@ impl Config {
     ///
     /// let listen_addr1: Multiaddr = "/ip4/127.0.0.1/tcp/9001".parse().unwrap();
     /// let listen_addr2: Multiaddr = "/ip4/127.0.0.1/tcp/9002".parse().unwrap();
+    /// let reuse = DialOpts { port_use: PortUse::Reuse, ..Default::default() };
     ///
     /// let mut tcp1 = libp2p_tcp::async_io::Transport::new(libp2p_tcp::Config::new().port_reuse(true)).boxed();
     /// tcp1.listen_on( listen_addr1.clone()).expect("listener");
     /// match tcp1.select_next_some().await {
     ///     TransportEvent::NewAddress { listen_addr, .. } => {
     ///         println!("Listening on {:?}", listen_addr);
-    ///         let mut stream = tcp1.dial(listen_addr2.clone()).unwrap().await?;
+    ///         let mut stream = tcp1.dial_with_opts(listen_addr2.clone(), reuse).unwrap().await?;
     ///         // `stream` has `listen_addr1` as its local socket address.
     ///     }
     ///     _ => {}
@@ -263,7 +283,7 @@ impl Config {
     /// match tcp2.select_next_some().await {
     ///     TransportEvent::NewAddress { listen_addr, .. } => {
     ///         println!("Listening on {:?}", listen_addr);
-    ///         let mut socket = tcp2.dial(listen_addr1).unwrap().await?;
+    ///         let mut socket = tcp2.dial_with_opts(listen_addr1, reuse).unwrap().await?;
     ///         // `stream` has `listen_addr2` as its local socket address.
     ///     }
     ///     _ => {}
@@ -346,6 +366,30 @@ where
         }
     }
 
+    /// Listens for incoming connections on an already bound [`TcpListener`].
+    ///
+    /// This allows binding privileged ports before dropping privileges, or using the sockets
+    /// passed by systemd socket activation, see [`systemd_listeners`]. The listen addresses are
+    /// reported like for [`libp2p_core::Transport::listen_on`].
+    ///
+    /// The socket options of the [`Config`] are not applied to the listener. Its address is only
+    /// registered for port reuse if `SO_REUSEPORT` is set on the listener.
+    pub fn listen_on_tcp_listener(&mut self, listener: TcpListener) -> io::Result<ListenerId> {
+        let id = ListenerId::new();
+        log::debug!("listening on {}", listener.local_addr()?);
+        #[cfg(unix)]
+        let port_reuse = if socket2::SockRef::from(&listener).reuse_port()? {
+            self.port_reuse.clone()
+        } else {
+            PortReuse::Disabled
+        };
+        #[cfg(not(unix))]
+        let port_reuse = self.port_reuse.clone();
+        let listener = self.do_listen_on_bound(id, listener, port_reuse)?;
+        self.listeners.push(listener);
+        Ok(id)
+    }
+
     fn create_socket(&self, socket_addr: &SocketAddr) -> io::Result<Socket> {
         let domain = if socket_addr.is_ipv4() {
             Domain::IPV4
@@ -378,8 +422,19 @@ where
         let socket = self.create_socket(&socket_addr)?;
         socket.bind(&socket_addr.into())?;
         socket.listen(self.config.backlog as _)?;
-        socket.set_nonblocking(true)?;
-        let listener: TcpListener = socket.into();
+        let port_reuse = self.port_reuse.clone();
+        self.do_listen_on_bound(id, socket.into(), port_reuse)
+    }
+
+    /// Listens on an already bound and listening [`TcpListener`], registering its addresses with
+    /// `port_reuse`.
+    fn do_listen_on_bound(
+        &mut self,
+        id: ListenerId,
+        listener: TcpListener,
+        mut port_reuse: PortReuse,
+    ) -> io::Result<ListenStream<T>> {
+        listener.set_nonblocking(true)?;
         let local_addr = listener.local_addr()?;
 
         if local_addr.ip().is_unspecified() {
@@ -387,17 +442,24 @@ where
                 id,
                 listener,
                 Some(T::new_if_watcher()?),
-                self.port_reuse.clone(),
+                port_reuse,
+                self.config.accept_error_backoff,
             );
         }
 
-        self.port_reuse.register(local_addr.ip(), local_addr.port());
+        port_reuse.register(local_addr.ip(), local_addr.port());
         let listen_addr = ip_to_multiaddr(local_addr.ip(), local_addr.port());
         self.pending_events.push_back(TransportEvent::NewAddress {
             listener_id: id,
             listen_addr,
         });
-        ListenStream::<T>::new(id, listener, None, self.port_reuse.clone())
+        ListenStream::<T>::new(
+            id,
+            listener,
+            None,
+            port_reuse,
+            self.config.accept_error_backoff,
+        )
     }
 }
 
@@ -462,6 +524,21 @@ where
     }
 
     fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.dial_with_opts(addr, DialOpts::default())
+    }
+
+    fn dial_as_listener(
+        &mut self,
+        addr: Multiaddr,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.dial(addr)
+    }
+
+    fn dial_with_opts(
+        &mut self,
+        addr: Multiaddr,
+        opts: DialOpts,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
         let socket_addr = if let Ok(socket_addr) = multiaddr_to_socketaddr(addr.clone()) {
             if socket_addr.port() == 0 || socket_addr.ip().is_unspecified() {
                 return Err(TransportError::MultiaddrNotSupported(addr));
@@ -476,9 +553,11 @@ where
             .create_socket(&socket_addr)
             .map_err(TransportError::Other)?;
 
-        if let Some(addr) = self.port_reuse.local_dial_addr(&socket_addr.ip()) {
-            log::trace!("Binding dial socket to listen socket {}", addr);
-            socket.bind(&addr.into()).map_err(TransportError::Other)?;
+        if opts.port_use == PortUse::Reuse {
+            if let Some(addr) = self.port_reuse.local_dial_addr(&socket_addr.ip()) {
+                log::trace!("Binding dial socket to listen socket {}", addr);
+                socket.bind(&addr.into()).map_err(TransportError::Other)?;
+            }
         }
 
         socket
@@ -501,27 +580,9 @@ where
         .boxed())
     }
 
-    fn dial_as_listener(
-        &mut self,
-        addr: Multiaddr,
-    ) -> Result<Self::Dial, TransportError<Self::Error>> {
-        self.dial(addr)
-    }
-
-    /// When port reuse is disabled and hence ephemeral local ports are
-    /// used for outgoing connections, the returned address is the
-    /// `observed` address with the port replaced by the port of the
-    /// `listen` address.
-    ///
-    /// If port reuse is enabled, `Some(observed)` is returned, as there
-    /// is a chance that the `observed` address _and_ port are reachable
-    /// for other peers if there is a NAT in the way that does endpoint-
-    /// independent filtering. Furthermore, even if that is not the case
-    /// and TCP hole punching techniques must be used for NAT traversal,
-    /// the `observed` address is still the one that a remote should connect
-    /// to for the purpose of the hole punching procedure, as it represents
-    /// the mapped IP and port of the NAT device in front of the local
-    /// node.
+    /// As outgoing connections use ephemeral local ports unless dialed with
+    /// [`PortUse::Reuse`], the returned address is the `observed` address
+    /// with the port replaced by the port of the `listen` address.
     ///
     /// `None` is returned if one of the given addresses is not a TCP/IP
     /// address.
@@ -529,10 +590,7 @@ where
         if !is_tcp_addr(listen) || !is_tcp_addr(observed) {
             return None;
         }
-        match &self.port_reuse {
-            PortReuse::Disabled => address_translation(listen, observed),
-            PortReuse::Enabled { .. } => Some(observed.clone()),
-        }
+        address_translation(listen, observed)
     }
 
     /// Poll all listeners.
@@ -581,6 +639,9 @@ where
     /// How long to sleep after a (non-fatal) error while trying
     /// to accept a new connection.
     sleep_on_error: Duration,
+    /// The initial and maximum value of `sleep_on_error`, which doubles
+    /// on consecutive errors while accepting connections.
+    accept_error_backoff: (Duration, Duration),
     /// The current pause, if any.
     pause: Option<Delay>,
     /// Pending event to reported.
@@ -602,6 +663,7 @@ where
         listener: TcpListener,
         if_watcher: Option<T::IfWatcher>,
         port_reuse: PortReuse,
+        accept_error_backoff: (Duration, Duration),
     ) -> io::Result<Self> {
         let listen_addr = listener.local_addr()?;
         let listener = T::new_listener(listener)?;
@@ -613,7 +675,8 @@ where
             listen_addr,
             if_watcher,
             pause: None,
-            sleep_on_error: Duration::from_millis(100),
+            sleep_on_error: accept_error_backoff.0,
+            accept_error_backoff,
             pending_event: None,
             is_closed: false,
             close_listener_waker: None,
@@ -660,6 +723,36 @@ where
         }
     }
 
+    /// Pauses the stream after failing to accept a connection, doubling
+    /// the pause for the next consecutive failure.
+    fn on_accept_error(&mut self, error: &io::Error) {
+        // Running out of file descriptors is usually transient, e.g. until
+        // other connections are closed.
+        if matches!(
+            error.raw_os_error(),
+            Some(libc::EMFILE) | Some(libc::ENFILE)
+        ) {
+            log::warn!(
+                "Listener {:?} ran out of file descriptors, pausing for {:?}: {}",
+                self.listener_id,
+                self.sleep_on_error,
+                error
+            );
+        } else {
+            log::debug!(
+                "Listener {:?} failed to accept a connection, pausing for {:?}: {}",
+                self.listener_id,
+                self.sleep_on_error,
+                error
+            );
+        }
+        self.pause = Some(Delay::new(self.sleep_on_error));
+        self.sleep_on_error = self
+            .sleep_on_error
+            .saturating_mul(2)
+            .min(self.accept_error_backoff.1);
+    }
+
     /// Poll for a next If Event.
     fn poll_if_addr(&mut self, cx: &mut Context<'_>) -> Poll<<Self as Stream>::Item> {
         let if_watcher = match self.if_watcher.as_mut() {
@@ -761,6 +854,7 @@ where
                 let remote_addr = ip_to_multiaddr(remote_addr.ip(), remote_addr.port());
 
                 log::debug!("Incoming connection from {} at {}", remote_addr, local_addr);
+                self.sleep_on_error = self.accept_error_backoff.0;
 
                 return Poll::Ready(Some(TransportEvent::Incoming {
                     listener_id: self.listener_id,
@@ -771,7 +865,7 @@ where
             }
             Poll::Ready(Err(error)) => {
                 // These errors are non-fatal for the listener stream.
-                self.pause = Some(Delay::new(self.sleep_on_error));
+                self.on_accept_error(&error);
                 return Poll::Ready(Some(TransportEvent::ListenerError {
                     listener_id: self.listener_id,
                     error,
@@ -815,6 +909,47 @@ fn multiaddr_to_socketaddr(mut addr: Multiaddr) -> Result<SocketAddr, ()> {
     Err(())
 }
 
+/// Returns the TCP sockets passed to the process via systemd socket activation, see
+/// `sd_listen_fds(3)`.
+///
+/// Only listening TCP sockets are returned, connected sockets passed with `Accept=yes` are not.
+/// Returns an empty list if the process has not been socket-activated. Each socket is returned at
+/// most once, other sockets passed by systemd are left to e.g. [`libp2p_core::transport::systemd`].
+///
+/// The listeners can be used with [`Transport::listen_on_tcp_listener`].
+#[cfg(unix)]
+pub fn systemd_listeners() -> Vec<TcpListener> {
+    use std::os::unix::io::FromRawFd;
+
+    libp2p_core::transport::systemd::take_listen_fds(|fd| {
+        // SAFETY: The socket is only inspected, not taken ownership of.
+        let socket = std::mem::ManuallyDrop::new(unsafe { Socket::from_raw_fd(fd) });
+        socket.r#type().map_or(false, |ty| ty == Type::STREAM)
+            && is_listening(fd)
+            && socket
+                .local_addr()
+                .map_or(false, |addr| addr.as_socket().is_some())
+    })
+}
+
+/// Whether the socket accepts connections, i.e. whether `SO_ACCEPTCONN` is set.
+#[cfg(unix)]
+fn is_listening(fd: std::os::unix::io::RawFd) -> bool {
+    let mut value: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: `value` and `len` are valid for writes and `len` is the size of `value`.
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_ACCEPTCONN,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    ret == 0 && value != 0
+}
+
 // Create a [`Multiaddr`] from the given IP address and port number.
 fn ip_to_multiaddr(ip: IpAddr, port: u16) -> Multiaddr {
     Multiaddr::empty().with(ip.into()).with(Protocol::Tcp(port))
@@ -1111,7 +1246,11 @@ mod tests {
                         .ok();
 
                     // Obtain a future socket through dialing
-                    let mut socket = tcp.dial(dest_addr).unwrap().await.unwrap();
+                    let opts = DialOpts {
+                        port_use: PortUse::Reuse,
+                        ..Default::default()
+                    };
+                    let mut socket = tcp.dial_with_opts(dest_addr, opts).unwrap().await.unwrap();
                     socket.write_all(&[0x1, 0x2, 0x3]).await.unwrap();
                     // socket.flush().await;
                     let mut buf = [0u8; 3];
@@ -1244,6 +1383,115 @@ mod tests {
         test("/ip4/127.0.0.1/tcp/0".parse().unwrap());
     }
 
+    #[test]
+    fn listen_on_tcp_listener() {
+        env_logger::try_init().ok();
+
+        async fn listen<T: Provider>(listener: TcpListener) -> Multiaddr {
+            let mut tcp = Transport::<T>::default();
+            tcp.listen_on_tcp_listener(listener).unwrap();
+            let mut tcp = tcp.boxed();
+            tcp.select_next_some()
+                .await
+                .into_new_address()
+                .expect("listen address")
+        }
+
+        fn test(addr: SocketAddr) {
+            #[cfg(feature = "async-io")]
+            {
+                let listener = TcpListener::bind(addr).unwrap();
+                let expected = ip_to_multiaddr(addr.ip(), listener.local_addr().unwrap().port());
+                let new_addr = async_std::task::block_on(listen::<async_io::Tcp>(listener));
+                assert_eq!(new_addr, expected);
+            }
+
+            #[cfg(feature = "tokio")]
+            {
+                let rt = ::tokio::runtime::Builder::new_current_thread()
+                    .enable_io()
+                    .build()
+                    .unwrap();
+                let listener = TcpListener::bind(addr).unwrap();
+                let expected = ip_to_multiaddr(addr.ip(), listener.local_addr().unwrap().port());
+                let new_addr = rt.block_on(listen::<tokio::Tcp>(listener));
+                assert_eq!(new_addr, expected);
+            }
+        }
+
+        test("127.0.0.1:0".parse().unwrap());
+        test("[::1]:0".parse().unwrap());
+    }
+
+    #[cfg(all(unix, feature = "async-io"))]
+    #[test]
+    fn listen_on_tcp_listener_registers_for_port_reuse_with_reuse_port() {
+        fn registered(reuse_port: bool) -> bool {
+            let socket = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+            socket.set_reuse_port(reuse_port).unwrap();
+            socket
+                .bind(&"127.0.0.1:0".parse::<SocketAddr>().unwrap().into())
+                .unwrap();
+            socket.listen(1).unwrap();
+            let mut tcp = Transport::<async_io::Tcp>::new(Config::new().port_reuse(true));
+            tcp.listen_on_tcp_listener(socket.into()).unwrap();
+            tcp.port_reuse
+                .local_dial_addr(&"127.0.0.1".parse().unwrap())
+                .is_some()
+        }
+
+        assert!(registered(true));
+        assert!(!registered(false));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn systemd_listeners_without_activation() {
+        assert!(systemd_listeners().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_listening_sockets_accept_connections() {
+        use std::os::unix::io::AsRawFd;
+
+        let socket = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+        socket
+            .bind(&"127.0.0.1:0".parse::<SocketAddr>().unwrap().into())
+            .unwrap();
+        assert!(!is_listening(socket.as_raw_fd()));
+        socket.listen(1).unwrap();
+        assert!(is_listening(socket.as_raw_fd()));
+    }
+
+    #[cfg(feature = "async-io")]
+    #[test]
+    fn accept_errors_back_off() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let backoff = (Duration::from_millis(100), Duration::from_millis(250));
+        let mut stream = ListenStream::<async_io::Tcp>::new(
+            ListenerId::new(),
+            listener,
+            None,
+            PortReuse::Disabled,
+            backoff,
+        )
+        .unwrap();
+
+        let error = io::Error::from_raw_os_error(libc::EMFILE);
+        let mut pauses = Vec::new();
+        for _ in 0..4 {
+            pauses.push(stream.sleep_on_error);
+            stream.on_accept_error(&error);
+            assert!(stream.pause.is_some());
+        }
+        assert_eq!(
+            pauses,
+            [100, 200, 250, 250].map(Duration::from_millis).to_vec()
+        );
+        assert!(!stream.is_closed);
+    }
+
     #[test]
     fn listen_invalid_addr() {
         env_logger::try_init().ok();
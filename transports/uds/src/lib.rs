@@ -53,7 +53,10 @@ use log::debug;
 use std::collections::VecDeque;
 use std::pin::Pin;
 use std::task::{Context, Poll};
-use std::{io, path::PathBuf};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
 
 pub type Listener<T> = BoxStream<
     'static,
@@ -64,7 +67,7 @@ pub type Listener<T> = BoxStream<
 >;
 
 macro_rules! codegen {
-    ($feature_name:expr, $uds_config:ident, $build_listener:expr, $unix_stream:ty, $($mut_or_not:tt)*) => {
+    ($feature_name:expr, $uds_config:ident, $build_listener:expr, $from_std_listener:expr, $unix_listener:ty, $unix_stream:ty, $($mut_or_not:tt)*) => {
         /// Represents the configuration for a Unix domain sockets transport capability for libp2p.
         pub struct $uds_config {
             listeners: VecDeque<(ListenerId, Listener<Self>)>,
@@ -77,6 +80,77 @@ macro_rules! codegen {
                     listeners: VecDeque::new(),
                 }
             }
+
+            /// Listens for incoming connections on an already bound
+            /// [`UnixListener`](std::os::unix::net::UnixListener).
+            ///
+            /// This allows using the sockets passed by systemd socket activation, see
+            /// [`systemd_listeners`]. Fails if the socket is not bound to a path.
+            pub fn listen_on_unix_listener(
+                &mut self,
+                listener: std::os::unix::net::UnixListener,
+            ) -> io::Result<ListenerId> {
+                let path = listener
+                    .local_addr()?
+                    .as_pathname()
+                    .map(Path::to_path_buf)
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "Unix socket is not bound to a path",
+                        )
+                    })?;
+                let addr =
+                    Multiaddr::from(Protocol::Unix(path.to_string_lossy().into_owned().into()));
+                listener.set_nonblocking(true)?;
+                let id = ListenerId::new();
+                self.push_listener(id, addr, $from_std_listener(listener));
+                Ok(id)
+            }
+
+            fn push_listener<F>(&mut self, id: ListenerId, addr: Multiaddr, listener: F)
+            where
+                F: Future<Output = io::Result<$unix_listener>> + Send + 'static,
+            {
+                let listener = listener
+                    .map_err(Err)
+                    .map_ok(move |listener| {
+                        stream::once({
+                            let addr = addr.clone();
+                            async move {
+                                debug!("Now listening on {}", addr);
+                                Ok(TransportEvent::NewAddress {
+                                    listener_id: id,
+                                    listen_addr: addr,
+                                })
+                            }
+                        })
+                        .chain(stream::unfold(listener, move |listener| {
+                            let addr = addr.clone();
+                            async move {
+                                let event = match listener.accept().await {
+                                    Ok((stream, _)) => {
+                                        debug!("incoming connection on {}", addr);
+                                        TransportEvent::Incoming {
+                                            upgrade: future::ok(stream),
+                                            local_addr: addr.clone(),
+                                            send_back_addr: addr.clone(),
+                                            listener_id: id,
+                                        }
+                                    }
+                                    Err(error) => TransportEvent::ListenerError {
+                                        listener_id: id,
+                                        error,
+                                    },
+                                };
+                                Some((Ok(event), listener))
+                            }
+                        }))
+                    })
+                    .try_flatten_stream()
+                    .boxed();
+                self.listeners.push_back((id, listener));
+            }
         }
 
         impl Default for $uds_config {
@@ -97,47 +171,7 @@ macro_rules! codegen {
             ) -> Result<ListenerId, TransportError<Self::Error>> {
                 if let Ok(path) = multiaddr_to_path(&addr) {
                     let id = ListenerId::new();
-                    let listener = $build_listener(path)
-                        .map_err(Err)
-                        .map_ok(move |listener| {
-                            stream::once({
-                                let addr = addr.clone();
-                                async move {
-                                    debug!("Now listening on {}", addr);
-                                    Ok(TransportEvent::NewAddress {
-                                        listener_id: id,
-                                        listen_addr: addr,
-                                    })
-                                }
-                            })
-                            .chain(stream::unfold(
-                                listener,
-                                move |listener| {
-                                    let addr = addr.clone();
-                                    async move {
-                                        let event = match listener.accept().await {
-                                            Ok((stream, _)) => {
-                                                debug!("incoming connection on {}", addr);
-                                                TransportEvent::Incoming {
-                                                    upgrade: future::ok(stream),
-                                                    local_addr: addr.clone(),
-                                                    send_back_addr: addr.clone(),
-                                                    listener_id: id,
-                                                }
-                                            }
-                                            Err(error) => TransportEvent::ListenerError {
-                                                listener_id: id,
-                                                error,
-                                            },
-                                        };
-                                        Some((Ok(event), listener))
-                                    }
-                                },
-                            ))
-                        })
-                        .try_flatten_stream()
-                        .boxed();
-                    self.listeners.push_back((id, listener));
+                    self.push_listener(id, addr, $build_listener(path));
                     Ok(id)
                 } else {
                     Err(TransportError::MultiaddrNotSupported(addr))
@@ -222,6 +256,8 @@ codegen!(
     "async-std",
     UdsConfig,
     |addr| async move { async_std::os::unix::net::UnixListener::bind(&addr).await },
+    |listener| async move { Ok(async_std::os::unix::net::UnixListener::from(listener)) },
+    async_std::os::unix::net::UnixListener,
     async_std::os::unix::net::UnixStream,
 );
 #[cfg(feature = "tokio")]
@@ -229,9 +265,31 @@ codegen!(
     "tokio",
     TokioUdsConfig,
     |addr| async move { tokio::net::UnixListener::bind(&addr) },
+    |listener| async move { tokio::net::UnixListener::from_std(listener) },
+    tokio::net::UnixListener,
     tokio::net::UnixStream,
 );
 
+/// Returns the Unix domain sockets passed to the process via systemd socket activation, see
+/// `sd_listen_fds(3)`.
+///
+/// Returns an empty list if the process has not been socket-activated. Each socket is returned at
+/// most once, other sockets passed by systemd are left to e.g. [`libp2p_core::transport::systemd`].
+///
+/// The listeners can be used with `listen_on_unix_listener` of the transports of this crate.
+pub fn systemd_listeners() -> Vec<std::os::unix::net::UnixListener> {
+    use std::os::unix::io::FromRawFd;
+    use std::os::unix::net::UnixListener;
+
+    libp2p_core::transport::systemd::take_listen_fds(|fd| {
+        // SAFETY: The socket is only inspected, not taken ownership of.
+        let listener = std::mem::ManuallyDrop::new(unsafe { UnixListener::from_raw_fd(fd) });
+        listener
+            .local_addr()
+            .map_or(false, |addr| addr.as_pathname().is_some())
+    })
+}
+
 /// Turns a `Multiaddr` containing a single `Unix` component into a path.
 ///
 /// Also returns an error if the path is not absolute, as we don't want to dial/listen on relative
@@ -322,6 +380,32 @@ mod tests {
         });
     }
 
+    #[test]
+    fn listen_on_unix_listener() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let socket = temp_dir.path().join("socket");
+        let listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+
+        async_std::task::block_on(async move {
+            let mut transport = UdsConfig::new();
+            transport.listen_on_unix_listener(listener).unwrap();
+            let mut transport = transport.boxed();
+
+            let listen_addr = transport
+                .select_next_some()
+                .await
+                .into_new_address()
+                .expect("listen address");
+            assert_eq!(multiaddr_to_path(&listen_addr), Ok(socket));
+
+            let dial = UdsConfig::new().dial(listen_addr).unwrap();
+            let (dialed, incoming) = future::join(dial, transport.select_next_some()).await;
+            dialed.unwrap();
+            let (sock, _addr) = incoming.into_incoming().expect("incoming stream");
+            sock.await.unwrap();
+        });
+    }
+
     #[test]
     #[ignore] // TODO: for the moment unix addresses fail to parse
     fn larger_addr_denied() {
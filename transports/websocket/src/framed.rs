@@ -25,7 +25,7 @@ use futures_rustls::{client, rustls, server};
 use libp2p_core::{
     connection::Endpoint,
     multiaddr::{Multiaddr, Protocol},
-    transport::{ListenerId, TransportError, TransportEvent},
+    transport::{DialOpts, ListenerId, PortUse, TransportError, TransportEvent},
     Transport,
 };
 use log::{debug, trace};
@@ -35,7 +35,7 @@ use soketto::{
     extension::deflate::Deflate,
     handshake,
 };
-use std::{collections::HashMap, ops::DerefMut, sync::Arc};
+use std::{borrow::Cow, collections::HashMap, ops::DerefMut, sync::Arc};
 use std::{convert::TryInto, fmt, io, mem, pin::Pin, task::Context, task::Poll};
 use url::Url;
 
@@ -52,11 +52,14 @@ pub struct WsConfig<T> {
     tls_config: tls::Config,
     max_redirects: u8,
     use_deflate: bool,
+    /// HTTP path requested for addresses with the default path `/`.
+    path: Option<String>,
+    /// Additional HTTP headers sent with the websocket handshake.
+    headers: Vec<(String, Vec<u8>)>,
     /// Websocket protocol of the inner listener.
     ///
     /// This is the suffix of the address provided in `listen_on`.
-    /// Can only be [`Protocol::Ws`] or [`Protocol::Wss`].
-    listener_protos: HashMap<ListenerId, Protocol<'static>>,
+    listener_protos: HashMap<ListenerId, WsListenProto<'static>>,
 }
 
 impl<T> WsConfig<T> {
@@ -68,6 +71,8 @@ impl<T> WsConfig<T> {
             tls_config: tls::Config::client(),
             max_redirects: 0,
             use_deflate: false,
+            path: None,
+            headers: Vec::new(),
             listener_protos: HashMap::new(),
         }
     }
@@ -105,6 +110,22 @@ impl<T> WsConfig<T> {
         self.use_deflate = flag;
         self
     }
+
+    /// Set the HTTP path to request when dialing addresses with the
+    /// default path `/`, e.g. `/dns/example.com/tcp/443/tls/ws`.
+    ///
+    /// Addresses specifying another path are dialed with their path.
+    pub fn set_path(&mut self, path: impl Into<String>) -> &mut Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Add an HTTP header to send with the websocket handshake when dialing,
+    /// e.g. for authentication with a reverse proxy.
+    pub fn add_header(&mut self, name: impl Into<String>, value: impl Into<Vec<u8>>) -> &mut Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
 }
 
 type TlsOrPlain<T> = future::Either<future::Either<client::TlsStream<T>, server::TlsStream<T>>, T>;
@@ -125,20 +146,26 @@ where
     fn listen_on(&mut self, addr: Multiaddr) -> Result<ListenerId, TransportError<Self::Error>> {
         let mut inner_addr = addr.clone();
         let proto = match inner_addr.pop() {
-            Some(p @ Protocol::Wss(_)) => {
-                if self.tls_config.server.is_some() {
-                    p
-                } else {
-                    debug!("/wss address but TLS server support is not configured");
-                    return Err(TransportError::MultiaddrNotSupported(addr));
+            Some(Protocol::Wss(path)) => WsListenProto::Wss(path.into_owned().into()),
+            Some(Protocol::Ws(path)) => match inner_addr.iter().last() {
+                Some(Protocol::Tls) => {
+                    inner_addr.pop();
+                    WsListenProto::TlsWs(path.into_owned().into())
                 }
-            }
-            Some(p @ Protocol::Ws(_)) => p,
+                _ => WsListenProto::Ws(path.into_owned().into()),
+            },
             _ => {
                 debug!("{} is not a websocket multiaddr", addr);
                 return Err(TransportError::MultiaddrNotSupported(addr));
             }
         };
+        if proto.use_tls() && self.tls_config.server.is_none() {
+            debug!(
+                "{} is a TLS address but TLS server support is not configured",
+                addr
+            );
+            return Err(TransportError::MultiaddrNotSupported(addr));
+        }
         match self.transport.lock().listen_on(inner_addr) {
             Ok(id) => {
                 self.listener_protos.insert(id, proto);
@@ -153,14 +180,34 @@ where
     }
 
     fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
-        self.do_dial(addr, Endpoint::Dialer)
+        self.do_dial(
+            addr,
+            DialOpts {
+                role: Endpoint::Dialer,
+                port_use: PortUse::New,
+            },
+        )
     }
 
     fn dial_as_listener(
         &mut self,
         addr: Multiaddr,
     ) -> Result<Self::Dial, TransportError<Self::Error>> {
-        self.do_dial(addr, Endpoint::Listener)
+        self.do_dial(
+            addr,
+            DialOpts {
+                role: Endpoint::Listener,
+                port_use: PortUse::New,
+            },
+        )
+    }
+
+    fn dial_with_opts(
+        &mut self,
+        addr: Multiaddr,
+        opts: DialOpts,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.do_dial(addr, opts)
     }
 
     fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
@@ -188,7 +235,7 @@ where
                     .listener_protos
                     .get(&listener_id)
                     .expect("Protocol was inserted in Transport::listen_on.");
-                listen_addr.push(proto.clone());
+                proto.append_on_addr(&mut listen_addr);
                 debug!("Listening on {}", listen_addr);
                 TransportEvent::NewAddress {
                     listener_id,
@@ -203,7 +250,7 @@ where
                     .listener_protos
                     .get(&listener_id)
                     .expect("Protocol was inserted in Transport::listen_on.");
-                listen_addr.push(proto.clone());
+                proto.append_on_addr(&mut listen_addr);
                 TransportEvent::AddressExpired {
                     listener_id,
                     listen_addr,
@@ -235,13 +282,9 @@ where
                     .listener_protos
                     .get(&listener_id)
                     .expect("Protocol was inserted in Transport::listen_on.");
-                let use_tls = match proto {
-                    Protocol::Wss(_) => true,
-                    Protocol::Ws(_) => false,
-                    _ => unreachable!("Map contains only ws and wss protocols."),
-                };
-                local_addr.push(proto.clone());
-                send_back_addr.push(proto.clone());
+                let use_tls = proto.use_tls();
+                proto.append_on_addr(&mut local_addr);
+                proto.append_on_addr(&mut send_back_addr);
                 let upgrade = self.map_upgrade(upgrade, send_back_addr.clone(), use_tls);
                 TransportEvent::Incoming {
                     listener_id,
@@ -266,7 +309,7 @@ where
     fn do_dial(
         &mut self,
         addr: Multiaddr,
-        role_override: Endpoint,
+        opts: DialOpts,
     ) -> Result<<Self as Transport>::Dial, TransportError<<Self as Transport>::Error>> {
         let mut addr = match parse_ws_dial_addr(addr) {
             Ok(addr) => addr,
@@ -283,6 +326,8 @@ where
         let tls_config = self.tls_config.clone();
         let use_deflate = self.use_deflate;
         let max_redirects = self.max_redirects;
+        let path = self.path.clone();
+        let headers = self.headers.clone();
 
         let future = async move {
             loop {
@@ -291,7 +336,9 @@ where
                     addr,
                     tls_config.clone(),
                     use_deflate,
-                    role_override,
+                    path.as_deref(),
+                    &headers,
+                    opts,
                 )
                 .await
                 {
@@ -318,18 +365,19 @@ where
         addr: WsAddress,
         tls_config: tls::Config,
         use_deflate: bool,
-        role_override: Endpoint,
+        path: Option<&str>,
+        headers: &[(String, Vec<u8>)],
+        opts: DialOpts,
     ) -> Result<Either<String, Connection<T::Output>>, Error<T::Error>> {
         trace!("Dialing websocket address: {:?}", addr);
 
-        let dial = match role_override {
-            Endpoint::Dialer => transport.lock().dial(addr.tcp_addr),
-            Endpoint::Listener => transport.lock().dial_as_listener(addr.tcp_addr),
-        }
-        .map_err(|e| match e {
-            TransportError::MultiaddrNotSupported(a) => Error::InvalidMultiaddr(a),
-            TransportError::Other(e) => Error::Transport(e),
-        })?;
+        let dial = transport
+            .lock()
+            .dial_with_opts(addr.tcp_addr, opts)
+            .map_err(|e| match e {
+                TransportError::MultiaddrNotSupported(a) => Error::InvalidMultiaddr(a),
+                TransportError::Other(e) => Error::Transport(e),
+            })?;
 
         let stream = dial.map_err(Error::Transport).await?;
         trace!("TCP connection to {} established.", addr.host_port);
@@ -358,7 +406,20 @@ where
 
         trace!("Sending websocket handshake to {}", addr.host_port);
 
-        let mut client = handshake::Client::new(stream, &addr.host_port, addr.path.as_ref());
+        let path = match path {
+            Some(path) if addr.path == "/" => path,
+            _ => addr.path.as_ref(),
+        };
+        let headers = headers
+            .iter()
+            .map(|(name, value)| handshake::client::Header {
+                name: name.as_str(),
+                value: value.as_slice(),
+            })
+            .collect::<Vec<_>>();
+
+        let mut client = handshake::Client::new(stream, &addr.host_port, path);
+        client.set_headers(&headers);
 
         if use_deflate {
             client.add_extension(Box::new(Deflate::new(connection::Mode::Client)));
@@ -476,6 +537,39 @@ where
     }
 }
 
+/// The websocket protocol(s) a listener was created for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum WsListenProto<'a> {
+    Ws(Cow<'a, str>),
+    Wss(Cow<'a, str>),
+    TlsWs(Cow<'a, str>),
+}
+
+impl<'a> WsListenProto<'a> {
+    fn append_on_addr(&self, addr: &mut Multiaddr) {
+        match self {
+            WsListenProto::Ws(path) => {
+                addr.push(Protocol::Ws(path.clone()));
+            }
+            WsListenProto::Wss(path) => {
+                addr.push(Protocol::Wss(path.clone()));
+            }
+            WsListenProto::TlsWs(path) => {
+                addr.push(Protocol::Tls);
+                addr.push(Protocol::Ws(path.clone()));
+            }
+        }
+    }
+
+    fn use_tls(&self) -> bool {
+        match self {
+            WsListenProto::Ws(_) => false,
+            WsListenProto::Wss(_) => true,
+            WsListenProto::TlsWs(_) => true,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct WsAddress {
     host_port: String,
@@ -519,15 +613,25 @@ fn parse_ws_dial_addr<T>(addr: Multiaddr) -> Result<WsAddress, Error<T>> {
         }
     };
 
-    // Now consume the `Ws` / `Wss` protocol from the end of the address,
-    // preserving the trailing `P2p` protocol that identifies the remote,
-    // if any.
+    // Now consume the `Ws` / `Wss` / `Tls/Ws` protocols from the end of the
+    // address, preserving the trailing `P2p` protocol that identifies the
+    // remote, if any.
     let mut protocols = addr.clone();
     let mut p2p = None;
     let (use_tls, path) = loop {
         match protocols.pop() {
             p @ Some(Protocol::P2p(_)) => p2p = p,
-            Some(Protocol::Ws(path)) => break (false, path.into_owned()),
+            Some(Protocol::Ws(path)) => match protocols.iter().last() {
+                Some(Protocol::Tls) => {
+                    if dns_name.is_none() {
+                        debug!("Missing DNS name in TLS address: {}", addr);
+                        return Err(Error::InvalidMultiaddr(addr));
+                    }
+                    protocols.pop();
+                    break (true, path.into_owned());
+                }
+                _ => break (false, path.into_owned()),
+            },
             Some(Protocol::Wss(path)) => {
                 if dns_name.is_none() {
                     debug!("Missing DNS name in WSS address: {}", addr);
@@ -786,3 +890,44 @@ where
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_identity::PeerId;
+
+    #[test]
+    fn parse_tls_ws_dial_addr() {
+        let peer = PeerId::random();
+        let addr: Multiaddr = format!("/dns4/example.com/tcp/443/tls/ws/p2p/{peer}")
+            .parse()
+            .unwrap();
+        let ws_addr = parse_ws_dial_addr::<io::Error>(addr).unwrap();
+
+        assert!(ws_addr.use_tls);
+        assert_eq!(ws_addr.host_port, "example.com:443");
+        assert_eq!(ws_addr.path, "/");
+        assert_eq!(
+            ws_addr.tcp_addr,
+            format!("/dns4/example.com/tcp/443/p2p/{peer}")
+                .parse()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn tls_ws_dial_addr_requires_dns_name() {
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/443/tls/ws".parse().unwrap();
+        assert!(matches!(
+            parse_ws_dial_addr::<io::Error>(addr),
+            Err(Error::InvalidMultiaddr(_))
+        ));
+    }
+
+    #[test]
+    fn listen_proto_is_appended_on_addr() {
+        let mut addr: Multiaddr = "/ip4/127.0.0.1/tcp/443".parse().unwrap();
+        WsListenProto::TlsWs("/".into()).append_on_addr(&mut addr);
+        assert_eq!(addr, "/ip4/127.0.0.1/tcp/443/tls/ws".parse().unwrap());
+    }
+}
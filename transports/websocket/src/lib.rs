@@ -32,7 +32,7 @@ use futures::{future::BoxFuture, prelude::*, ready};
 use libp2p_core::{
     connection::ConnectedPoint,
     multiaddr::Multiaddr,
-    transport::{map::MapFuture, ListenerId, TransportError, TransportEvent},
+    transport::{map::MapFuture, DialOpts, ListenerId, TransportError, TransportEvent},
     Transport,
 };
 use rw_stream_sink::RwStreamSink;
@@ -174,6 +174,22 @@ where
         self.transport.inner_mut().use_deflate(flag);
         self
     }
+
+    /// Set the HTTP path to request when dialing addresses with the
+    /// default path `/`, e.g. `/dns/example.com/tcp/443/tls/ws`.
+    ///
+    /// Addresses specifying another path are dialed with their path.
+    pub fn set_path(&mut self, path: impl Into<String>) -> &mut Self {
+        self.transport.inner_mut().set_path(path);
+        self
+    }
+
+    /// Add an HTTP header to send with the websocket handshake when dialing,
+    /// e.g. for authentication with a reverse proxy.
+    pub fn add_header(&mut self, name: impl Into<String>, value: impl Into<Vec<u8>>) -> &mut Self {
+        self.transport.inner_mut().add_header(name, value);
+        self
+    }
 }
 
 impl<T> Transport for WsConfig<T>
@@ -208,6 +224,14 @@ where
         self.transport.dial_as_listener(addr)
     }
 
+    fn dial_with_opts(
+        &mut self,
+        addr: Multiaddr,
+        opts: DialOpts,
+    ) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.transport.dial_with_opts(addr, opts)
+    }
+
     fn address_translation(&self, server: &Multiaddr, observed: &Multiaddr) -> Option<Multiaddr> {
         self.transport.address_translation(server, observed)
     }